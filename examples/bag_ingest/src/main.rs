@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use frost::errors::Error;
+use frost::etl::{Ingest, Record};
+
+const BATCH_SIZE: usize = 50;
+
+/// Stands in for a database table with a primary key of (bag, sequence).
+type Table = BTreeMap<(String, u64), (String, usize)>;
+
+fn upsert(table: &mut Table, records: &[Record]) {
+    for record in records {
+        let key = (record.bag.display().to_string(), record.sequence);
+        table.insert(key, (record.topic.clone(), record.data.len()));
+    }
+}
+
+fn ingest_all(
+    dir: &Path,
+    checkpoints: &Path,
+    table: &mut Table,
+    fail_after: Option<usize>,
+) -> Result<(), Error> {
+    let mut ingest = Ingest::open(checkpoints)?.with_batch_size(BATCH_SIZE);
+    let mut batches = 0;
+    for bag in ingest.pending(dir)? {
+        let report = ingest.ingest_bag(&bag, |records| {
+            if Some(batches) == fail_after {
+                return Err(
+                    io::Error::new(io::ErrorKind::Other, "lost the database connection").into(),
+                );
+            }
+            batches += 1;
+            upsert(table, records);
+            Ok(())
+        })?;
+        println!(
+            "{}: skipped {}, ingested {}",
+            bag.display(),
+            report.resumed_from,
+            report.records
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("bag_ingest_{}", std::process::id()));
+    let drop = dir.join("drop");
+    fs::create_dir_all(&drop).unwrap();
+    let checkpoints = dir.join("checkpoints.json");
+
+    fs::write(
+        drop.join("test.bag"),
+        include_bytes!("../../read_bag/fixtures/test.bag"),
+    )
+    .unwrap();
+    // still being recorded, so it is left alone
+    fs::write(drop.join("next.bag.active"), b"").unwrap();
+
+    let mut table = Table::new();
+    let error = ingest_all(&drop, &checkpoints, &mut table, Some(2)).unwrap_err();
+    println!("first run stopped: {}, {} rows stored", error, table.len());
+
+    ingest_all(&drop, &checkpoints, &mut table, None).unwrap();
+    println!("second run finished, {} rows stored", table.len());
+
+    let sequences: Vec<u64> = table.keys().map(|(_, sequence)| *sequence).collect();
+    assert_eq!(sequences, (0..sequences.len() as u64).collect::<Vec<_>>());
+    assert!(ingest_all(&drop, &checkpoints, &mut table, Some(0)).is_ok());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,34 @@
+use std::io::{self, BufWriter, Write};
+
+use frost::msgs::std_msgs;
+use frost::query::Query;
+use frost::DecompressedBag;
+
+fn main() -> io::Result<()> {
+    let bytes = include_bytes!("../../read_bag/fixtures/test.bag");
+    let bag = DecompressedBag::from_bytes(bytes).unwrap();
+
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+
+    writeln!(writer, "topic,data")?;
+
+    let query = Query::new().with_topics(["/chatter", "/array"]);
+    for msg_view in bag.read_messages(&query).unwrap() {
+        let data = match msg_view.topic {
+            "/chatter" => msg_view.instantiate::<std_msgs::String>().unwrap().data,
+            "/array" => msg_view
+                .instantiate::<std_msgs::Float64MultiArray>()
+                .unwrap()
+                .data
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(";"),
+            other => panic!("unexpected topic {other}"),
+        };
+        writeln!(writer, "{},{}", msg_view.topic, data)?;
+    }
+
+    writer.flush()
+}
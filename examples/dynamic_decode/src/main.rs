@@ -0,0 +1,47 @@
+use frost::dynamic::Value;
+use frost::query::Query;
+use frost::DecompressedBag;
+
+fn main() {
+    let bytes = include_bytes!("../../read_bag/fixtures/test.bag");
+    let bag = DecompressedBag::from_bytes(bytes).unwrap();
+
+    // Print the fields of the first message on each topic.
+    for topic in bag.metadata.topics() {
+        let query = Query::new().with_topics([topic]);
+        let msg_view = bag.read_messages(&query).unwrap().next().unwrap();
+        let Value::Message(fields) = msg_view.decode().unwrap() else {
+            unreachable!("messages always decode to Value::Message");
+        };
+        println!("{topic} ({}):", msg_view.data_type());
+        for (name, value) in fields {
+            println!("  {name}: {value:?}");
+        }
+    }
+
+    // Messages are decoded with the definitions recorded in the bag, so no generated types are needed.
+    let (mut strings, mut elements) = (0, 0);
+    for msg_view in bag.read_messages(&Query::all()).unwrap() {
+        let value = msg_view.decode().unwrap();
+        match msg_view.data_type() {
+            "std_msgs/String" => {
+                assert!(value.get("data").and_then(Value::as_str).is_some());
+                strings += 1;
+            }
+            "std_msgs/Float64MultiArray" => {
+                assert_eq!(
+                    value.get_path("layout.dim.0.label").and_then(Value::as_str),
+                    Some("data")
+                );
+                if let Some(Value::Array(data)) = value.get("data") {
+                    assert!(data.iter().all(|element| element.as_f64().is_some()));
+                    elements += data.len();
+                }
+            }
+            data_type => panic!("unexpected type {data_type}"),
+        }
+    }
+
+    println!("{strings} strings, and arrays of {elements} numbers in total");
+    assert_eq!((strings, elements), (100, 300));
+}
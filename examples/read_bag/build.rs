@@ -9,10 +9,8 @@ fn main() {
     let dest_path = Path::new(&out_dir).join("msgs.rs");
 
     frost_codegen::run(Opts {
-        input_paths: vec![
-            PathBuf::from("fixtures/dummy_msgs"),
-            PathBuf::from("../../std_msgs"),
-        ],
+        // std_msgs come from frost's `std-msgs` feature
+        input_paths: vec![PathBuf::from("fixtures/dummy_msgs")],
         output_path: dest_path,
     })
     .unwrap();
@@ -1,6 +1,7 @@
 use std::f32::consts::PI;
 use std::{fs::File, io::Write, path::PathBuf};
 
+use frost::msgs::std_msgs;
 use frost::query::Query;
 use frost::DecompressedBag;
 use tempfile::{tempdir, TempDir};
@@ -8,7 +9,6 @@ use tempfile::{tempdir, TempDir};
 include!(concat!(env!("OUT_DIR"), "/msgs.rs"));
 
 use self::msgs::dummy_msgs;
-use self::msgs::std_msgs;
 
 fn setup_fixture(tmp_dir: &TempDir) -> PathBuf {
     let bytes = include_bytes!("../fixtures/test.bag");
@@ -0,0 +1,38 @@
+use frost::dynamic::Value;
+use frost::query::Query;
+use frost::storage::StreamStorage;
+use frost::{BagMetadata, DecompressedBag};
+
+/// Small enough that the fixture is spooled to a temporary file, as a large bag would be.
+const MEMORY_LIMIT: usize = 4 * 1024;
+
+fn main() {
+    // Any `Read` works, like a network socket, or stdin with `StreamStorage::stdin()`.
+    let stream: &[u8] = include_bytes!("../../read_bag/fixtures/test.bag");
+    let storage = StreamStorage::with_memory_limit(stream, MEMORY_LIMIT).unwrap();
+    println!("spooled to a temporary file: {}", storage.is_spooled());
+
+    // Only record headers and the index are read, skipping over chunk data.
+    let metadata = BagMetadata::from_storage(&storage).unwrap();
+    println!(
+        "{} messages in {} chunks",
+        metadata.message_count(),
+        metadata.chunk_count()
+    );
+
+    // Only the chunks holding /chatter messages are read and decompressed.
+    let query = Query::new().with_topics(["/chatter"]);
+    let bag = DecompressedBag::from_storage_filtered(&storage, &query).unwrap();
+    let mut count = 0;
+    for msg_view in bag.read_messages(&query).unwrap() {
+        let value = msg_view.decode().unwrap();
+        let data = value.get("data").and_then(Value::as_str).unwrap();
+        if count < 3 {
+            println!("{} {}: {data}", msg_view.time, msg_view.topic);
+        }
+        count += 1;
+    }
+
+    println!("read {count} /chatter messages");
+    assert_eq!(count, 100);
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use frost::msgs::std_msgs;
+use frost::query::Query;
+use frost::DecompressedBag;
+
+const WINDOW: Duration = Duration::from_secs(10);
+
+fn main() {
+    let bytes = include_bytes!("../../read_bag/fixtures/test.bag");
+    let bag = DecompressedBag::from_bytes(bytes).unwrap();
+
+    let duration = bag.metadata.duration();
+    println!("bag is {:.2}s long", duration.as_secs_f64());
+
+    // Half-open windows, so a message landing exactly on a boundary is only counted once.
+    let mut total = 0;
+    let mut window_start = Duration::ZERO;
+    while window_start <= duration {
+        let window_end = window_start + WINDOW;
+        let query = Query::new()
+            .with_topics(["/chatter"])
+            .with_time_range(window_start..window_end);
+
+        let messages: Vec<_> = bag
+            .read_messages(&query)
+            .unwrap()
+            .map(|msg_view| msg_view.instantiate::<std_msgs::String>().unwrap().data)
+            .collect();
+
+        println!(
+            "[{:>3}s, {:>3}s): {} messages, first: {:?}",
+            window_start.as_secs(),
+            window_end.as_secs(),
+            messages.len(),
+            messages.first()
+        );
+
+        total += messages.len();
+        window_start = window_end;
+    }
+
+    assert_eq!(total, 100);
+}
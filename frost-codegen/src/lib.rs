@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::BTreeMap;
 use std::env;
 use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::process::Command;
 use std::{
     collections::HashMap,
@@ -51,7 +52,8 @@ struct RosMsg {
 
 impl RosMsg {
     fn new(path: &PathBuf) -> Result<Self, Error> {
-        let text = fs::read_to_string(path)?;
+        // files checked out on Windows may have CRLF line endings, which mustn't leak into constants
+        let text = fs::read_to_string(path)?.replace("\r\n", "\n");
 
         Ok(RosMsg {
             name: path.file_stem().unwrap().to_string_lossy().into_owned(),
@@ -74,7 +76,7 @@ impl RosMsg {
                 if let Statement::Field { msg_type, name } = stmt {
                     let full_type_name = match &msg_type.package_name {
                         Some(package_name) => {
-                            "crate::msgs::".to_owned() + &package_name + "::" + &msg_type.name
+                            "crate::msgs::".to_owned() + package_name + "::" + &msg_type.name
                         }
                         None => builtin_mappings(&msg_type.name)
                             .unwrap_or(&msg_type.name)
@@ -182,18 +184,28 @@ fn get_package_name(path: &PathBuf) -> Result<String, Error> {
     Ok(res.name)
 }
 
+/// Formats the generated file, with the same settings everywhere so the file is identical on every platform.
 fn fmt_file(path: &PathBuf) -> Result<(), Error> {
     let rustfmt_path = env::var("RUSTFMT_PATH").unwrap_or("rustfmt".into());
     let mut fmt_cmd = Command::new(&rustfmt_path);
-    fmt_cmd.arg(path).output()?;
+    fmt_cmd
+        .args(["--edition", "2021", "--config", "newline_style=Unix"])
+        .arg(path)
+        .output()?;
     Ok(())
 }
 
-fn write_all(
-    out_path: &PathBuf,
-    mods: BTreeMap<String, String>,
-    msgs: Vec<(PathBuf, RosMsg)>,
-) -> Result<(), Error> {
+/// A path relative to the input folder it was found in, with `/` separators on every platform.
+fn display_path(path: &Path, input_path: &Path) -> String {
+    path.strip_prefix(input_path)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn write_all(out_path: &PathBuf, packages: Packages) -> Result<(), Error> {
     let file = File::create(out_path)?;
     let mut writer = BufWriter::new(file);
 
@@ -209,40 +221,10 @@ fn write_all(
 
     writer.write_all(b"pub mod msgs {")?;
 
-    let mut seen = HashSet::new();
-
-    for package in mods.values() {
-        if seen.contains(package) {
-            continue;
-        }
-        seen.insert(package.clone());
-
-        let mut msgs_in_package = Vec::new();
-
-        for (msg_path, msg) in msgs.iter() {
-            let parent_path = msg_path.parent().unwrap();
-            if parent_path.file_stem().unwrap() != "msg" {
-                continue;
-            }
-            let parent_path = parent_path.parent().unwrap();
-            match mods.get(parent_path.to_str().unwrap_or("")) {
-                Some(cur_package) => {
-                    if package != cur_package {
-                        continue;
-                    }
-                    msgs_in_package.push(msg);
-                }
-                None => println!("WARN: missing package for {}", &msg_path.to_string_lossy()),
-            };
-        }
-
-        if msgs_in_package.is_empty() {
-            continue;
-        }
-
+    for (package, msgs) in packages.iter() {
         writer.write_all(format!("pub mod r#{package} {{").as_bytes())?;
 
-        for msg in msgs_in_package {
+        for msg in msgs.values() {
             writer
                 .write_all("#[derive(Clone, Debug, serde::Deserialize, PartialEq)]".as_bytes())?;
             writer.write_all(msg.as_struct_definition().as_bytes())?;
@@ -257,17 +239,20 @@ fn write_all(
     Ok(())
 }
 
-fn get_mods_and_msgs(
-    input_paths: &[PathBuf],
-) -> Result<(BTreeMap<String, String>, Vec<(PathBuf, RosMsg)>), Error> {
-    let mut packages = BTreeMap::<String, String>::new();
-    let mut msgs = Vec::<(PathBuf, RosMsg)>::new();
+/// Messages by package name and then by message name, so the generated file doesn't depend on where the inputs are
+/// or the order the filesystem lists them in.
+type Packages = BTreeMap<String, BTreeMap<String, RosMsg>>;
+
+fn get_packages(input_paths: &[PathBuf]) -> Result<Packages, Error> {
+    let mut package_names = HashMap::<PathBuf, String>::new();
+    // each message with its path, and its path as shown in warnings
+    let mut msgs = Vec::<(PathBuf, String, RosMsg)>::new();
 
     input_paths.iter().for_each(|input_path| {
         if !input_path.exists() {
             panic!("{} directory not found", input_path.to_string_lossy());
         }
-        for entry in WalkDir::new(input_path).into_iter() {
+        for entry in WalkDir::new(input_path).sort_by_file_name().into_iter() {
             let Ok(entry) = entry else {
                 continue;
             };
@@ -280,6 +265,7 @@ fn get_mods_and_msgs(
                 continue;
             }
 
+            let shown_path = display_path(entry.path(), input_path);
             let abs_path = entry.into_path().canonicalize().unwrap();
 
             let Some(extension) = abs_path.extension() else {
@@ -290,25 +276,42 @@ fn get_mods_and_msgs(
                 let Ok(package_name) = get_package_name(&abs_path) else {
                     continue;
                 };
-                packages.insert(
-                    abs_path.parent().unwrap().to_string_lossy().into_owned(),
-                    package_name,
-                );
+                package_names.insert(abs_path.parent().unwrap().to_path_buf(), package_name);
             } else if extension == "msg" {
                 let msg = RosMsg::new(&abs_path).unwrap();
-                msgs.push((abs_path, msg));
+                msgs.push((abs_path, shown_path, msg));
             }
         }
     });
-    msgs.sort_by(|(_, a_msg), (_, b_msg)| a_msg.name.cmp(&b_msg.name));
-    Ok((packages, msgs))
+
+    let mut packages = Packages::new();
+    for (msg_path, shown_path, msg) in msgs {
+        let msg_dir = msg_path.parent().unwrap();
+        if msg_dir.file_stem().unwrap() != "msg" {
+            continue;
+        }
+        let Some(package) = package_names.get(msg_dir.parent().unwrap()) else {
+            println!("WARN: missing package for {shown_path}");
+            continue;
+        };
+        let msgs = packages.entry(package.clone()).or_default();
+        if msgs.contains_key(&msg.name) {
+            println!(
+                "WARN: {package}/{} is defined again by {shown_path}",
+                msg.name
+            );
+        }
+        msgs.insert(msg.name.clone(), msg);
+    }
+    Ok(packages)
 }
 
 pub fn run(opts: Opts) -> Result<(), Error> {
-    let (mods, msgs) = get_mods_and_msgs(&opts.input_paths)?;
+    let packages = get_packages(&opts.input_paths)?;
 
-    println!("Found {} message definitions", msgs.len());
+    let count: usize = packages.values().map(BTreeMap::len).sum();
+    println!("Found {count} message definitions");
 
-    write_all(&opts.output_path, mods, msgs)?;
+    write_all(&opts.output_path, packages)?;
     fmt_file(&opts.output_path)
 }
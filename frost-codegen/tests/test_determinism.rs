@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::Path;
+
+use frost_codegen::{run, Opts};
+
+const PACKAGES: [(&str, &[(&str, &str)]); 2] = [
+    (
+        "geometry",
+        &[
+            ("Point", "float64 x\nfloat64 y\nfloat64 z\n"),
+            ("Pose", "geometry/Point position\nstd/Header header\n"),
+        ],
+    ),
+    (
+        "std",
+        &[
+            ("Header", "uint32 seq\ntime stamp\nstring frame_id\n"),
+            (
+                "Status",
+                "uint8 OK=0\nuint8 ERROR=1\nstring NAME=status\nuint8 level\n",
+            ),
+        ],
+    ),
+];
+
+fn write_package(dir: &Path, package: &str, msgs: &[(&str, &str)], line_ending: &str) {
+    let msg_dir = dir.join(package).join("msg");
+    fs::create_dir_all(&msg_dir).unwrap();
+    fs::write(
+        dir.join(package).join("package.xml"),
+        format!("<package><name>{package}</name></package>"),
+    )
+    .unwrap();
+    for (name, definition) in msgs {
+        fs::write(
+            msg_dir.join(format!("{name}.msg")),
+            definition.replace('\n', line_ending),
+        )
+        .unwrap();
+    }
+}
+
+fn generate(input_path: &Path) -> Vec<u8> {
+    let output_path = input_path.join("msgs.rs");
+    run(Opts {
+        input_paths: vec![input_path.to_path_buf()],
+        output_path: output_path.clone(),
+    })
+    .unwrap();
+    fs::read(output_path).unwrap()
+}
+
+#[test]
+fn output_is_deterministic() {
+    let unix = tempfile::tempdir().unwrap();
+    for (package, msgs) in PACKAGES {
+        write_package(unix.path(), package, msgs, "\n");
+    }
+
+    // a checkout elsewhere, created in another order and with Windows line endings
+    let windows = tempfile::tempdir().unwrap();
+    let nested = windows.path().join("src").join("msgs");
+    for (package, msgs) in PACKAGES.iter().rev() {
+        let msgs: Vec<_> = msgs.iter().rev().copied().collect();
+        write_package(&nested, package, &msgs, "\r\n");
+    }
+
+    let expected = generate(unix.path());
+    let actual = generate(&nested);
+    assert!(!expected.contains(&b'\r'));
+    assert_eq!(
+        String::from_utf8(expected).unwrap(),
+        String::from_utf8(actual).unwrap()
+    );
+}
@@ -0,0 +1,83 @@
+//! `#[derive(Msg)]` for hand-written message structs, used through frost's `derive` feature as `frost::Msg`.
+//!
+//! Example
+//! ```rust,ignore
+//! #[derive(serde::Deserialize, frost::Msg)]
+//! #[ros(type = "std_msgs/String", md5sum = "992ce8a1687cec8c8bd883ec73ca41d1")]
+//! struct Chatter {
+//!     data: String,
+//! }
+//! ```
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, LitStr};
+
+/// Implements `frost::msgs::Msg`, with the ROS type and md5sum given by a `#[ros(type = "..", md5sum = "..")]`
+/// attribute. Both are optional, and are checked against a message's connection by `MessageView::instantiate`.
+#[proc_macro_derive(Msg, attributes(ros))]
+pub fn derive_msg(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut ros_type = None;
+    let mut md5sum = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("ros"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type") {
+                let value: LitStr = meta.value()?.parse()?;
+                if value.value().split('/').count() != 2 {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "expected a type like \"package/Name\"",
+                    ));
+                }
+                ros_type = Some(value);
+            } else if meta.path.is_ident("md5sum") {
+                let value: LitStr = meta.value()?.parse()?;
+                let md5 = value.value();
+                if md5.len() != 32 || !md5.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "expected 32 hexadecimal digits",
+                    ));
+                }
+                md5sum = Some(value);
+            } else {
+                return Err(meta.error("expected `type` or `md5sum`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let ros_type = ros_type.map(|ros_type| {
+        quote! {
+            fn ros_type() -> ::core::option::Option<&'static str> {
+                ::core::option::Option::Some(#ros_type)
+            }
+        }
+    });
+    let md5sum = md5sum.map(|md5sum| {
+        quote! {
+            fn md5sum() -> ::core::option::Option<&'static str> {
+                ::core::option::Option::Some(#md5sum)
+            }
+        }
+    });
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::frost::msgs::Msg for #name #type_generics #where_clause {
+            #ros_type
+            #md5sum
+        }
+    })
+}
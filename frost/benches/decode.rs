@@ -0,0 +1,252 @@
+#![allow(dead_code)]
+#![cfg(nightly)]
+#![feature(test)]
+
+#[cfg(all(nightly, test))]
+extern crate test;
+
+use std::hint;
+use std::io::Cursor;
+
+use frost::dynamic::{Schema, Value};
+use frost::msgs::Msg;
+use frost::time::Time;
+use frost::writer::BagWriter;
+use frost::{ConnectionData, DecompressedBag};
+use serde::Deserialize;
+
+const COMPRESSED_LZ4: &[u8] = include_bytes!("../tests/fixtures/compressed_lz4.bag");
+
+const IMU_DEFINITION: &str = "\
+Header header
+geometry_msgs/Quaternion orientation
+float64[9] orientation_covariance
+geometry_msgs/Vector3 angular_velocity
+float64[9] angular_velocity_covariance
+geometry_msgs/Vector3 linear_acceleration
+float64[9] linear_acceleration_covariance
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: geometry_msgs/Quaternion
+float64 x
+float64 y
+float64 z
+float64 w
+================================================================================
+MSG: geometry_msgs/Vector3
+float64 x
+float64 y
+float64 z
+";
+
+const POINT_CLOUD_DEFINITION: &str = "\
+Header header
+uint32 height
+uint32 width
+sensor_msgs/PointField[] fields
+bool is_bigendian
+uint32 point_step
+uint32 row_step
+uint8[] data
+bool is_dense
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: sensor_msgs/PointField
+string name
+uint32 offset
+uint8 datatype
+uint32 count
+";
+
+#[derive(Deserialize)]
+struct Header {
+    seq: u32,
+    stamp: (u32, u32),
+    frame_id: String,
+}
+
+#[derive(Deserialize)]
+struct Chatter {
+    data: String,
+}
+impl Msg for Chatter {}
+
+#[derive(Deserialize)]
+struct Quaternion {
+    x: f64,
+    y: f64,
+    z: f64,
+    w: f64,
+}
+
+#[derive(Deserialize)]
+struct Vector3 {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Deserialize)]
+struct Imu {
+    header: Header,
+    orientation: Quaternion,
+    orientation_covariance: [f64; 9],
+    angular_velocity: Vector3,
+    angular_velocity_covariance: [f64; 9],
+    linear_acceleration: Vector3,
+    linear_acceleration_covariance: [f64; 9],
+}
+impl Msg for Imu {}
+
+#[derive(Deserialize)]
+struct PointField {
+    name: String,
+    offset: u32,
+    datatype: u8,
+    count: u32,
+}
+
+#[derive(Deserialize)]
+struct PointCloud2 {
+    header: Header,
+    height: u32,
+    width: u32,
+    fields: Vec<PointField>,
+    is_bigendian: bool,
+    point_step: u32,
+    row_step: u32,
+    data: Vec<u8>,
+    is_dense: bool,
+}
+impl Msg for PointCloud2 {}
+
+/// Returns a bag with one message of the given type, built from its default value.
+fn single_message_bag(data_type: &str, definition: &str, edit: fn(&mut Value)) -> Vec<u8> {
+    let schema = Schema::parse(data_type, definition).unwrap();
+    let mut value = schema.default_value();
+    edit(&mut value);
+
+    let mut writer = BagWriter::new(Cursor::new(Vec::new())).unwrap();
+    let conn_id = writer.add_connection(&ConnectionData {
+        connection_id: 0,
+        topic: "/bench".to_string(),
+        data_type: data_type.to_string(),
+        md5sum: "*".to_string(),
+        message_definition: definition.to_string(),
+        caller_id: None,
+        latching: false,
+        header_fields: Vec::new(),
+    });
+    writer
+        .write_raw(
+            conn_id,
+            Time { secs: 1, nsecs: 0 },
+            &schema.encode(&value).unwrap(),
+        )
+        .unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+fn imu_bag() -> Vec<u8> {
+    single_message_bag("sensor_msgs/Imu", IMU_DEFINITION, |value| {
+        *value.get_path_mut("header.frame_id").unwrap() = Value::String("imu_link".into());
+    })
+}
+
+fn point_cloud_bag() -> Vec<u8> {
+    single_message_bag("sensor_msgs/PointCloud2", POINT_CLOUD_DEFINITION, |value| {
+        let field = |name: &str, offset| {
+            Value::Message(vec![
+                ("name".into(), Value::String(name.into())),
+                ("offset".into(), Value::U32(offset)),
+                ("datatype".into(), Value::U8(7)),
+                ("count".into(), Value::U32(1)),
+            ])
+        };
+        *value.get_mut("fields").unwrap() =
+            Value::Array(vec![field("x", 0), field("y", 4), field("z", 8)]);
+        *value.get_mut("point_step").unwrap() = Value::U32(12);
+        *value.get_mut("data").unwrap() = Value::Bytes(vec![0; 12 * 4096]);
+    })
+}
+
+#[cfg(all(nightly, test))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frost::query::Query;
+    use test::Bencher;
+
+    /// Decodes every message of `bag` with its schema, as `MessageView::decode` does once it has been cached.
+    fn bench_decode(b: &mut Bencher, bytes: &[u8], topic: &str) {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let connection = bag
+            .metadata
+            .connection_data
+            .values()
+            .find(|connection| connection.topic == topic)
+            .unwrap();
+        let schema = Schema::parse(&connection.data_type, &connection.message_definition).unwrap();
+        let query = Query::new().with_topics([topic]);
+
+        b.iter(|| {
+            for msg_view in bag.read_messages(&query).unwrap() {
+                let data = &msg_view.raw_bytes().unwrap()[4..];
+                hint::black_box(schema.decode(data).unwrap());
+            }
+        });
+    }
+
+    fn bench_instantiate<T: Msg + for<'de> Deserialize<'de>>(
+        b: &mut Bencher,
+        bytes: &[u8],
+        topic: &str,
+    ) {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let query = Query::new().with_topics([topic]);
+
+        b.iter(|| {
+            for msg_view in bag.read_messages(&query).unwrap() {
+                hint::black_box(msg_view.instantiate::<T>().unwrap());
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_decode_std_msgs_string(b: &mut Bencher) {
+        bench_decode(b, COMPRESSED_LZ4, "/chatter");
+    }
+
+    #[bench]
+    fn bench_instantiate_std_msgs_string(b: &mut Bencher) {
+        bench_instantiate::<Chatter>(b, COMPRESSED_LZ4, "/chatter");
+    }
+
+    #[bench]
+    fn bench_decode_imu(b: &mut Bencher) {
+        bench_decode(b, &imu_bag(), "/bench");
+    }
+
+    #[bench]
+    fn bench_instantiate_imu(b: &mut Bencher) {
+        bench_instantiate::<Imu>(b, &imu_bag(), "/bench");
+    }
+
+    #[bench]
+    fn bench_decode_point_cloud(b: &mut Bencher) {
+        bench_decode(b, &point_cloud_bag(), "/bench");
+    }
+
+    #[bench]
+    fn bench_instantiate_point_cloud(b: &mut Bencher) {
+        bench_instantiate::<PointCloud2>(b, &point_cloud_bag(), "/bench");
+    }
+}
@@ -3,6 +3,7 @@ extern crate rustc_version;
 use rustc_version::{version_meta, Channel};
 
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(nightly)");
     // when the nightly toolchain is used, set a "nightly" cfg
     // so that the benchmarks can be compiled and otherwise ignored
     if version_meta().unwrap().channel == Channel::Nightly {
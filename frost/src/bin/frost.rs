@@ -1,46 +1,1556 @@
-use std::collections::HashSet;
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use bpaf::*;
 use itertools::Itertools;
+#[cfg(feature = "regex")]
+use regex::Regex;
 
-use frost::errors::Error;
-use frost::BagMetadata;
+use frost::accounting::TopicBytes;
+use frost::batch::Batch;
+#[cfg(feature = "cache")]
+use frost::cache::MetadataCache;
+use frost::chunk_analysis::{ChunkAnalysis, DEFAULT_FACTOR, MIN_CHUNKS};
+use frost::clock::SystemClock;
+use frost::dedup::DedupKey;
+use frost::diagnostics::{DiagnosticLevel, DiagnosticsReport};
+use frost::echo::{EchoFormat, EchoWriter};
+use frost::edit::{self, IndexEditor};
+use frost::errors::{Error, ErrorKind};
+#[cfg(feature = "images")]
+use frost::export::naming::MESSAGE_PLACEHOLDERS;
+use frost::export::naming::{Collision, FileNamer, NameFields, NameTemplate};
+#[cfg(feature = "yaml")]
+use frost::health::{HealthConfig, HealthReport};
+use frost::import::Importer;
+use frost::layout::ChunkLayout;
+#[cfg(feature = "manifest")]
+use frost::manifest::{Manifest, SignatureStatus, Verification};
+use frost::maps::{MapImageFormat, OccupancyGrid};
+use frost::mcap::{McapCompression, McapReader, McapWriter};
+use frost::merge::{Merge, TimeSource};
+use frost::ordering::TimeOrderReport;
+use frost::output::{Output, OutputPolicy};
+use frost::pcap::PcapReader;
+use frost::plugins::{self, ExternalPlugin};
+use frost::query::Query;
+#[cfg(feature = "ros2")]
+use frost::ros2::MessageSources;
+use frost::rosout::{LogEntry, LogLevel};
+use frost::skew::{estimate_skew, SkewEstimate};
+use frost::storage::{FileStorage, StreamStorage};
+use frost::summary::{human_bytes, INVALID_TIME};
+use frost::tail::Tail;
+use frost::time::{self, Time};
+use frost::topic_search::MatchKind;
+use frost::topic_tree::TopicTree;
+use frost::trajectory::{Stop, Trajectory, TrajectoryFormat};
+use frost::triage::{TriageReport, Verdict};
+use frost::writer::{BagWriter, Compression, Retention};
+use frost::{BagMetadata, Capabilities, DecompressedBag};
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug)]
 enum Opts {
-    TopicOptions { file_path: PathBuf },
-    TypeOptions { file_path: PathBuf },
-    InfoOptions { minimal: bool, file_path: PathBuf },
+    TopicOptions {
+        tree: bool,
+        cache: bool,
+        file_path: PathBuf,
+    },
+    TypeOptions {
+        cache: bool,
+        file_path: PathBuf,
+    },
+    InfoOptions {
+        minimal: bool,
+        compat: Option<InfoCompat>,
+        yaml: bool,
+        key: Option<String>,
+        cache: bool,
+        file_path: PathBuf,
+    },
+    #[cfg(feature = "cache")]
+    CacheListOptions,
+    #[cfg(feature = "cache")]
+    CacheClearOptions,
+    CapabilitiesOptions {
+        format: OutputFormat,
+    },
+    CheckOptions {
+        analyze: bool,
+        jobs: usize,
+        file_paths: Vec<PathBuf>,
+    },
+    PluginsOptions,
+    LayoutOptions {
+        svg: Option<PathBuf>,
+        format: OutputFormat,
+        relative_time: bool,
+        file_path: PathBuf,
+    },
+    SizesOptions {
+        exact: bool,
+        cache: bool,
+        format: OutputFormat,
+        file_path: PathBuf,
+    },
+    MapOptions {
+        topic: String,
+        format: MapImageFormat,
+        output: PathBuf,
+        relative_time: bool,
+        file_path: PathBuf,
+    },
+    DiagnosticsOptions {
+        topic: String,
+        relative_time: bool,
+        file_path: PathBuf,
+    },
+    #[cfg(feature = "yaml")]
+    HealthOptions {
+        config: PathBuf,
+        relative_time: bool,
+        file_path: PathBuf,
+    },
+    TriageOptions {
+        top: usize,
+        file_path: PathBuf,
+    },
+    LogsOptions {
+        min_level: LogLevel,
+        format: OutputFormat,
+        relative_time: bool,
+        file_path: PathBuf,
+    },
+    EchoOptions {
+        topics: TopicFilter,
+        range: TimeRange,
+        format: EchoFormat,
+        file_path: PathBuf,
+    },
+    TailOptions {
+        topics: TopicFilter,
+        range: TimeRange,
+        format: EchoFormat,
+        from_start: bool,
+        interval: u64,
+        file_path: PathBuf,
+    },
+    ExportOptions {
+        topics: TopicFilter,
+        range: TimeRange,
+        compression: McapCompression,
+        output: PathBuf,
+        jobs: usize,
+        policy: OutputPolicy,
+        file_paths: Vec<PathBuf>,
+    },
+    ImportOptions {
+        compression: Compression,
+        msg_dirs: Vec<PathBuf>,
+        output: PathBuf,
+        policy: OutputPolicy,
+        inputs: Vec<PathBuf>,
+    },
+    TrajectoryOptions {
+        topic: String,
+        child_frame: Option<String>,
+        stop_speed: f64,
+        stop_time: f64,
+        format: TrajectoryFormat,
+        output: Option<PathBuf>,
+        relative_time: bool,
+        file_path: PathBuf,
+    },
+    CropOptions {
+        range: TimeRange,
+        output: PathBuf,
+        policy: OutputPolicy,
+        file_path: PathBuf,
+    },
+    MergeOptions {
+        time_source: TimeSource,
+        offsets: Vec<BagOffset>,
+        auto_offsets: bool,
+        rewrite_times: bool,
+        dedup: Option<f64>,
+        compression: OutputCompression,
+        output: PathBuf,
+        policy: OutputPolicy,
+        file_paths: Vec<PathBuf>,
+    },
+    SkewOptions {
+        reference: PathBuf,
+        file_paths: Vec<PathBuf>,
+    },
+    RetainOptions {
+        topics: TopicFilter,
+        keep_every: Vec<KeepEvery>,
+        dedup: Vec<DedupRule>,
+        compression: OutputCompression,
+        output: PathBuf,
+        policy: OutputPolicy,
+        file_path: PathBuf,
+    },
+    SplitTopicsOptions {
+        groups: Vec<TopicGroup>,
+        output_dir: Option<PathBuf>,
+        name: NameTemplate,
+        compression: OutputCompression,
+        policy: OutputPolicy,
+        file_path: PathBuf,
+    },
+    #[cfg(feature = "manifest")]
+    ManifestOptions {
+        key_file: Option<PathBuf>,
+        output: Option<PathBuf>,
+        file_path: PathBuf,
+    },
+    #[cfg(feature = "manifest")]
+    VerifyOptions {
+        key_file: Option<PathBuf>,
+        file_path: PathBuf,
+        manifest_path: PathBuf,
+    },
+    EditOptions {
+        topic: Option<String>,
+        new_type: NewType,
+        latching: Option<bool>,
+        recover: bool,
+        file_path: PathBuf,
+    },
+    #[cfg(feature = "images")]
+    ImagesOptions {
+        topics: TopicFilter,
+        output_dir: PathBuf,
+        name: NameTemplate,
+        collision: Collision,
+        file_path: PathBuf,
+    },
+    #[cfg(feature = "mount")]
+    MountOptions {
+        format: frost::mount::FileFormat,
+        file_path: PathBuf,
+        mountpoint: PathBuf,
+    },
+    #[cfg(feature = "zstd")]
+    DictTrainOptions {
+        max_size: usize,
+        output: PathBuf,
+        policy: OutputPolicy,
+        file_paths: Vec<PathBuf>,
+    },
+    #[cfg(feature = "zstd")]
+    DictRewriteOptions {
+        dictionary: PathBuf,
+        output_dir: PathBuf,
+        jobs: usize,
+        policy: OutputPolicy,
+        file_paths: Vec<PathBuf>,
+    },
+}
+
+/// A regex which must match a whole topic name
+#[cfg(feature = "regex")]
+#[derive(Clone, Debug)]
+struct TopicPattern(Regex);
+
+/// A topic name, as patterns other than topic names need the `regex` feature
+#[cfg(not(feature = "regex"))]
+#[derive(Clone, Debug)]
+struct TopicPattern(String);
+
+impl TopicPattern {
+    #[cfg(feature = "regex")]
+    fn matches(&self, topic: &str) -> bool {
+        self.0.is_match(topic)
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn matches(&self, topic: &str) -> bool {
+        self.0 == topic
+    }
+}
+
+impl FromStr for TopicPattern {
+    type Err = String;
+
+    #[cfg(feature = "regex")]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Regex::new(&format!("^(?:{s})$"))
+            .map(TopicPattern)
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_/~".contains(c))
+        {
+            true => Ok(TopicPattern(s.to_string())),
+            false => Err(format!(
+                "'{s}' is not a topic name, and regexes need frost built with the regex feature"
+            )),
+        }
+    }
+}
+
+/// Chunk compression of rewritten bags, either a codec or `keep` to reuse each source chunk's
+#[derive(Clone, Copy, Debug)]
+enum OutputCompression {
+    Keep,
+    Codec(Compression),
+}
+
+impl OutputCompression {
+    fn apply<W: Seek + Write>(self, bag_writer: BagWriter<W>) -> BagWriter<W> {
+        match self {
+            OutputCompression::Keep => bag_writer.with_source_compression(),
+            OutputCompression::Codec(compression) => bag_writer.with_compression(compression),
+        }
+    }
+}
+
+impl FromStr for OutputCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(OutputCompression::Keep),
+            other => other.parse().map(OutputCompression::Codec),
+        }
+    }
+}
+
+/// A clock correction in seconds for one input bag, parsed from `path=secs`
+#[derive(Clone, Debug)]
+struct BagOffset {
+    path: PathBuf,
+    secs: f64,
+}
+
+impl FromStr for BagOffset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, secs) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected FILE=SECS, got '{s}'"))?;
+        let secs: f64 = secs
+            .parse()
+            .map_err(|e| format!("invalid SECS in '{s}': {e}"))?;
+        if !secs.is_finite() {
+            return Err(format!("invalid SECS in '{s}'"));
+        }
+        Ok(BagOffset {
+            path: path.into(),
+            secs,
+        })
+    }
+}
+
+/// The placeholders of `split-topics --name`
+const SPLIT_PLACEHOLDERS: &[&str] = &["stem", "group", "ext"];
+
+/// A named set of topic patterns, parsed from `name:pattern1,pattern2`
+#[derive(Clone, Debug)]
+struct TopicGroup {
+    name: String,
+    patterns: Vec<TopicPattern>,
+}
+
+impl TopicGroup {
+    fn matches(&self, topic: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(topic))
+    }
+}
+
+/// Keep one of every `n` messages on topics matching a pattern, parsed from `pattern:n`
+#[derive(Clone, Debug)]
+struct KeepEvery {
+    pattern: TopicPattern,
+    n: usize,
+}
+
+impl FromStr for KeepEvery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, n) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected PATTERN:N, got '{s}'"))?;
+        Ok(KeepEvery {
+            pattern: pattern.parse()?,
+            n: n.parse().map_err(|e| format!("invalid N in '{s}': {e}"))?,
+        })
+    }
+}
+
+/// Drop consecutive duplicate messages on topics matching a pattern, parsed from `pattern:field,field` when comparing
+/// fields
+#[derive(Clone, Debug)]
+struct DedupRule {
+    pattern: TopicPattern,
+    key: DedupKey,
+}
+
+impl FromStr for DedupRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, fields) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected PATTERN:FIELDS, got '{s}'"))?;
+        let fields: Vec<String> = fields
+            .split(',')
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect();
+        if fields.is_empty() {
+            return Err(format!("missing fields in '{s}'"));
+        }
+        Ok(DedupRule {
+            pattern: pattern.parse()?,
+            key: DedupKey::Fields(fields),
+        })
+    }
+}
+
+impl FromStr for TopicGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, patterns) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected NAME:PATTERNS, got '{s}'"))?;
+        if name.is_empty() {
+            return Err(format!("missing group name in '{s}'"));
+        }
+        let patterns = patterns
+            .split(',')
+            .filter(|pattern| !pattern.is_empty())
+            .map(TopicPattern::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        if patterns.is_empty() {
+            return Err(format!("missing topic patterns in '{s}'"));
+        }
+        Ok(TopicGroup {
+            name: name.to_string(),
+            patterns,
+        })
+    }
+}
+
+/// `frost edit`'s `--type`, `--md5sum` and `--definition`, which are only valid together.
+#[derive(Clone, Debug)]
+struct NewType {
+    data_type: Option<String>,
+    md5sum: Option<String>,
+    definition: Option<PathBuf>,
+}
+
+/// The topics a command reads: those given with `--topic` or matching a pattern from a `--topics-from` file, or all
+/// topics if there are none, except those matching a pattern from an `--exclude-from` file
+#[derive(Clone, Debug, Default)]
+struct TopicFilter {
+    topics: Vec<String>,
+    include: Vec<TopicPattern>,
+    exclude: Vec<TopicPattern>,
+}
+
+impl TopicFilter {
+    fn is_all(&self) -> bool {
+        self.topics.is_empty() && self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    fn matches(&self, topic: &str) -> bool {
+        let included = (self.topics.is_empty() && self.include.is_empty())
+            || self.topics.iter().any(|t| t == topic)
+            || self.include.iter().any(|pattern| pattern.matches(topic));
+        included && !self.exclude.iter().any(|pattern| pattern.matches(topic))
+    }
+
+    /// Fails if a topic given with `--topic` isn't in the bag.
+    fn check(&self, metadata: &BagMetadata, file_path: &Path) -> Result<(), Error> {
+        self.topics
+            .iter()
+            .try_for_each(|topic| check_topic(metadata, topic, file_path))
+    }
+
+    /// Narrows `query` to the topics of `metadata` the filter matches.
+    fn select(&self, query: Query, metadata: &BagMetadata) -> Query {
+        if self.is_all() {
+            return query;
+        }
+        query.with_topics(
+            metadata
+                .topics()
+                .into_iter()
+                .filter(|topic| self.matches(topic)),
+        )
+    }
+
+    /// A query for the matching topics of the bag at `file_path`, only reading its index to check topics and resolve
+    /// patterns.
+    fn query(&self, file_path: &Path) -> Result<Query, Error> {
+        if self.is_all() {
+            return Ok(Query::all());
+        }
+        let metadata = BagMetadata::from_file(file_path)?;
+        self.check(&metadata, file_path)?;
+        Ok(self.select(Query::all(), &metadata))
+    }
+
+    /// A query matching messages by their topics as they are read, for bags whose topics aren't known yet.
+    fn predicate_query(&self) -> Query {
+        if self.is_all() {
+            return Query::all();
+        }
+        let filter = self.clone();
+        Query::all().with_predicate(move |msg_view| filter.matches(msg_view.topic))
+    }
+}
+
+/// The time range a command reads, from `--start` and `--end` in seconds since the epoch, both inclusive
+#[derive(Clone, Copy, Debug, Default)]
+struct TimeRange {
+    start: Option<f64>,
+    end: Option<f64>,
+}
+
+impl TimeRange {
+    fn start(&self) -> Time {
+        epoch(self.start, time::ZERO)
+    }
+
+    fn end(&self) -> Time {
+        epoch(self.end, time::MAX)
+    }
+
+    /// Narrows `query` to the messages received within the range.
+    fn select(&self, query: Query) -> Query {
+        if self.start.is_none() && self.end.is_none() {
+            return query;
+        }
+        query.with_time_range(self.start()..=self.end())
+    }
+}
+
+/// The time `secs` seconds after the epoch, saturating at the latest time a bag can hold, or `default` if not given.
+fn epoch(secs: Option<f64>, default: Time) -> Time {
+    match secs {
+        None => default,
+        // Checked first, as `Duration::from_secs_f64` panics on values this large.
+        Some(secs) if secs > f64::from(u32::MAX) => time::MAX,
+        Some(secs) => time::ZERO
+            .checked_add(Duration::from_secs_f64(secs))
+            .unwrap_or(time::MAX),
+    }
+}
+
+/// Reads a file of topic patterns, one per line, skipping blank lines and `#` comments.
+fn read_topic_patterns(path: PathBuf) -> Result<Vec<TopicPattern>, String> {
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            line.parse()
+                .map_err(|e| format!("{}:{}: {e}", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// How text outputs print times: as seconds since the epoch, or since the bag's start with `--relative-time`
+#[derive(Clone, Copy, Debug)]
+struct TimeDisplay(Option<Time>);
+
+impl TimeDisplay {
+    fn new(relative_time: bool, metadata: &BagMetadata) -> Self {
+        TimeDisplay(metadata.start_time().filter(|_| relative_time))
+    }
+
+    fn format(&self, time: Time) -> String {
+        match self.0 {
+            Some(start) => format!("t{:+.3}s", time.relative_to(&start)),
+            None => time.to_string(),
+        }
+    }
+}
+
+/// Other tools whose `info` output `frost info` can reproduce.
+#[derive(Clone, Copy, Debug)]
+enum InfoCompat {
+    Rosbag,
+}
+
+impl FromStr for InfoCompat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rosbag" => Ok(InfoCompat::Rosbag),
+            other => Err(format!("unknown compat '{other}', expected 'rosbag'")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!(
+                "unknown format '{other}', expected 'text' or 'json'"
+            )),
+        }
+    }
+}
+
+fn format_parser() -> impl Parser<OutputFormat> {
+    long("format")
+        .help("Output format: text or json")
+        .argument::<OutputFormat>("FORMAT")
+        .fallback(OutputFormat::Text)
+}
+
+fn compression_parser() -> impl Parser<OutputCompression> {
+    long("compression")
+        .help("Chunk compression of written bags: keep (that of the chunks copied from), none, lz4, or bz2 and zstd with the bz2 and zstd features")
+        .argument::<OutputCompression>("COMPRESSION")
+        .fallback(OutputCompression::Keep)
+}
+
+fn output_parser() -> impl Parser<PathBuf> {
+    short('o')
+        .long("output")
+        .help("Path of the bag to write")
+        .argument::<PathBuf>("OUTPUT")
+        .complete_shell(ShellComp::File { mask: None })
+}
+
+/// The files to import, with rosbag2 directories replaced by their storage files.
+fn import_files(inputs: Vec<PathBuf>) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(input);
+            continue;
+        }
+        let mut storage_files = std::fs::read_dir(&input)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .filter_ok(|path| {
+                matches!(
+                    path.extension().and_then(|extension| extension.to_str()),
+                    Some("mcap" | "db3")
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        storage_files.sort();
+        files.extend(storage_files);
+    }
+    Ok(files)
+}
+
+#[cfg(feature = "manifest")]
+fn key_file_parser() -> impl Parser<Option<PathBuf>> {
+    long("key-file")
+        .help("File whose contents are the secret HMAC-SHA256 key authenticating the manifest. An HMAC is not a signature: anyone with the key can also forge manifests")
+        .argument::<PathBuf>("KEY_FILE")
+        .complete_shell(ShellComp::File { mask: None })
+        .optional()
+}
+
+/// Reads a bag's metadata from `file_path`, or from stdin if it is `-`, going through the metadata cache if `cache`.
+fn read_metadata(file_path: &Path, cache: bool) -> Result<BagMetadata, Error> {
+    if file_path == Path::new("-") {
+        let mut metadata = BagMetadata::from_storage(&StreamStorage::stdin()?)?;
+        metadata.file_path = Some(file_path.to_path_buf());
+        return Ok(metadata);
+    }
+    if cache {
+        #[cfg(feature = "cache")]
+        return metadata_cache()?.load(file_path);
+        #[cfg(not(feature = "cache"))]
+        return Err(no_cache());
+    }
+    BagMetadata::from_file(file_path)
+}
+
+#[cfg(feature = "cache")]
+fn metadata_cache() -> Result<MetadataCache, Error> {
+    MetadataCache::open_default().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "neither XDG_CACHE_HOME nor HOME is set, so there is no cache directory",
+        )
+        .into()
+    })
+}
+
+#[cfg(not(feature = "cache"))]
+fn no_cache() -> Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--cache needs frost built with the cache feature",
+    )
+    .into()
+}
+
+fn cache_parser() -> impl Parser<bool> {
+    long("cache")
+        .help("Read the bag's metadata from the cache in ~/.cache/frost, caching it on the first run, see `frost cache`")
+        .switch()
+}
+
+fn jobs_parser() -> impl Parser<usize> {
+    short('j')
+        .long("jobs")
+        .help("Process up to N bags at a time, or one per CPU with 0")
+        .argument::<usize>("N")
+        .fallback(1)
+}
+
+/// `--topic`, `--topics-from` and `--exclude-from`, with `topic_help` describing `--topic`.
+fn topic_filter_parser(topic_help: &'static str) -> impl Parser<TopicFilter> {
+    let topics = long("topic")
+        .help(topic_help)
+        .argument::<String>("TOPIC")
+        .complete(complete_topic)
+        .many();
+    let include = long("topics-from")
+        .help("File of topic regexes, one per line, to also read the topics matching (repeatable)")
+        .argument::<PathBuf>("FILE")
+        .complete_shell(ShellComp::File { mask: None })
+        .parse(read_topic_patterns)
+        .many()
+        .map(|files| files.into_iter().flatten().collect());
+    let exclude = long("exclude-from")
+        .help("File of topic regexes, one per line, whose matching topics are skipped (repeatable)")
+        .argument::<PathBuf>("FILE")
+        .complete_shell(ShellComp::File { mask: None })
+        .parse(read_topic_patterns)
+        .many()
+        .map(|files| files.into_iter().flatten().collect());
+    construct!(TopicFilter {
+        topics,
+        include,
+        exclude
+    })
+}
+
+/// Completes `--topic` with the topics of the first bag on the command line, described by their types.
+#[allow(clippy::ptr_arg)] // bpaf completes arguments by reference to their type
+fn complete_topic(partial: &String) -> Vec<(String, Option<String>)> {
+    let metadata = std::env::args_os()
+        .skip(1)
+        .map(PathBuf::from)
+        .filter(|path| path.is_file())
+        .find_map(|path| BagMetadata::from_file(path).ok());
+    let Some(metadata) = metadata else {
+        return Vec::new();
+    };
+    let mut topics: Vec<_> = metadata
+        .topics_and_types()
+        .into_iter()
+        .filter(|(topic, _)| topic.starts_with(partial.as_str()))
+        .map(|(topic, data_type)| (topic.to_string(), Some(data_type.to_string())))
+        .collect();
+    topics.sort();
+    topics
+}
+
+/// Fails if `topic` isn't in the bag, suggesting the topics it has which are closest to it.
+fn check_topic(metadata: &BagMetadata, topic: &str, file_path: &Path) -> Result<(), Error> {
+    let matches = metadata.find_topic(topic);
+    if matches
+        .first()
+        .map_or(false, |m| m.kind == MatchKind::Exact)
+    {
+        return Ok(());
+    }
+    let mut message = format!("no topic {topic} in {}", file_path.display());
+    if !matches.is_empty() {
+        let suggestions: Vec<_> = matches.iter().take(3).map(|m| m.topic.as_str()).collect();
+        message.push_str(&format!(", did you mean {}?", suggestions.join(" or ")));
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, message).into())
+}
+
+/// `--start` and `--end`, described by `start_help` and `end_help`.
+fn time_range_parser(start_help: &'static str, end_help: &'static str) -> impl Parser<TimeRange> {
+    let start = long("start")
+        .help(start_help)
+        .argument::<f64>("SECS")
+        .guard(
+            |secs| secs.is_finite() && *secs >= 0.0,
+            "--start must not be negative",
+        )
+        .optional();
+    let end = long("end")
+        .help(end_help)
+        .argument::<f64>("SECS")
+        .guard(
+            |secs| secs.is_finite() && *secs >= 0.0,
+            "--end must not be negative",
+        )
+        .optional();
+    construct!(TimeRange { start, end }).guard(
+        |range| range.start() <= range.end(),
+        "--start must not be after --end",
+    )
+}
+
+/// `--start` and `--end` for commands reading messages.
+fn message_range_parser() -> impl Parser<TimeRange> {
+    time_range_parser(
+        "Only read messages received at or after this time, in seconds since the epoch",
+        "Only read messages received at or before this time, in seconds since the epoch",
+    )
+}
+
+fn output_policy_parser() -> impl Parser<OutputPolicy> {
+    let force = long("force")
+        .help("Overwrite outputs which already exist")
+        .switch();
+    let dry_run = long("dry-run")
+        .help("Report what would be written, and how large, without writing anything")
+        .switch();
+    construct!(force, dry_run).map(|(force, dry_run)| {
+        let mut policy = OutputPolicy::new();
+        if force {
+            policy = policy.with_force();
+        }
+        if dry_run {
+            policy = policy.with_dry_run();
+        }
+        policy
+    })
+}
+
+fn relative_time_parser() -> impl Parser<bool> {
+    long("relative-time")
+        .help("Print times as seconds since the bag's start, like t+37.200s, instead of since the epoch")
+        .switch()
 }
 
 fn file_parser() -> impl Parser<PathBuf> {
     positional::<PathBuf>("FILE").complete_shell(ShellComp::File { mask: None })
 }
 
-fn args() -> Opts {
+fn args() -> OptionParser<Opts> {
     let file_path = file_parser();
     let minimal = short('m')
         .long("minimal")
         .help("Show minimal info (without types/topics)")
         .switch();
-    let info_cmd = construct!(Opts::InfoOptions { minimal, file_path })
-        .to_options()
-        .descr("Print rosbag information")
-        .command("info");
+    let compat = long("compat")
+        .help("Print exactly what another tool's info prints, so scripts parsing it keep working: rosbag")
+        .argument::<InfoCompat>("TOOL")
+        .optional();
+    let yaml = short('y')
+        .long("yaml")
+        .help("Print info as YAML, like rosbag info --yaml")
+        .switch();
+    let key = short('k')
+        .long("key")
+        .help("Print one key of the YAML info, like rosbag info --yaml --key, e.g. duration or topics[0].messages")
+        .argument::<String>("KEY")
+        .optional();
+    let cache = cache_parser();
+    let info_cmd = construct!(Opts::InfoOptions {
+        minimal,
+        compat,
+        yaml,
+        key,
+        cache,
+        file_path
+    })
+    .to_options()
+    .descr("Print rosbag information")
+    .command("info");
+    let tree = short('t')
+        .long("tree")
+        .help("Group topics by namespace, with message counts per topic and namespace")
+        .switch();
+    let cache = cache_parser();
     let file_path = file_parser();
-    let topics_cmd = construct!(Opts::TopicOptions { file_path })
-        .to_options()
-        .descr("Print rosbag topics")
-        .command("topics");
+    let topics_cmd = construct!(Opts::TopicOptions {
+        tree,
+        cache,
+        file_path
+    })
+    .to_options()
+    .descr("Print rosbag topics")
+    .command("topics");
+    let cache = cache_parser();
     let file_path = file_parser();
-    let types_cmd = construct!(Opts::TypeOptions { file_path })
+    let types_cmd = construct!(Opts::TypeOptions { cache, file_path })
         .to_options()
         .descr("Print rosbag types")
         .command("types");
-    let parser = construct!([info_cmd, topics_cmd, types_cmd]);
-    parser.to_options().version(env!("CARGO_PKG_VERSION")).run()
+    let groups = long("group")
+        .help("A named group of comma separated topic regexes, like cameras:/camera.* (repeatable)")
+        .argument::<TopicGroup>("NAME:PATTERNS")
+        .some("at least one --group is required");
+    let output_dir = short('o')
+        .long("output-dir")
+        .help("Directory to write the split bags into (defaults to the input's directory)")
+        .argument::<PathBuf>("DIR")
+        .optional();
+    let name = long("name")
+        .help("Template of the split bags' names, with {stem} of FILE, {group} and {ext}")
+        .argument::<NameTemplate>("TEMPLATE")
+        .guard(
+            |name| name.check(SPLIT_PLACEHOLDERS).is_ok(),
+            "--name may only use {stem}, {group} and {ext}",
+        )
+        .fallback("{stem}_{group}.{ext}".parse().expect("a valid template"));
+    let compression = compression_parser();
+    let file_path = file_parser();
+    let policy = output_policy_parser();
+    let split_topics_cmd = construct!(Opts::SplitTopicsOptions {
+        groups,
+        output_dir,
+        name,
+        compression,
+        policy,
+        file_path
+    })
+    .to_options()
+    .descr("Split a rosbag into one bag per group of topics, named <FILE stem>_<group name>.bag by default")
+    .command("split-topics");
+    let topics = topic_filter_parser("Only keep messages on this topic (repeatable)");
+    let drop = long("drop")
+        .help("Drop all messages on topics matching a regex (repeatable)")
+        .argument::<TopicPattern>("PATTERN")
+        .many();
+    let keep_every = long("keep-every")
+        .help("Keep one of every N messages on topics matching a regex, like /camera.*:10 (repeatable)")
+        .argument::<KeepEvery>("PATTERN:N")
+        .many();
+    let dedup_payload = long("dedup")
+        .help("Drop messages identical to the previous one on topics matching a regex (repeatable)")
+        .argument::<TopicPattern>("PATTERN")
+        .map(|pattern| DedupRule {
+            pattern,
+            key: DedupKey::Payload,
+        })
+        .many();
+    let dedup_fields = long("dedup-fields")
+        .help("Drop messages whose comma separated fields equal the previous one's on topics matching a regex, like /map:info.width,data (repeatable)")
+        .argument::<DedupRule>("PATTERN:FIELDS")
+        .many();
+    let dedup = construct!(dedup_payload, dedup_fields)
+        .map(|(payload, fields)| payload.into_iter().chain(fields).collect());
+    let compression = compression_parser();
+    let output = output_parser();
+    let file_path = file_parser();
+    // --drop patterns are exclusions like those of --exclude-from
+    let topics = construct!(topics, drop).map(|(mut topics, drop)| {
+        topics.exclude.extend(drop);
+        topics
+    });
+    let policy = output_policy_parser();
+    let retain_cmd = construct!(Opts::RetainOptions {
+        topics,
+        keep_every,
+        dedup,
+        compression,
+        output,
+        policy,
+        file_path
+    })
+    .to_options()
+    .descr("Rewrite a rosbag, dropping, downsampling or deduplicating topics to save space")
+    .command("retain");
+    let range = time_range_parser(
+        "Keep chunks ending at or after this time, in seconds since the epoch",
+        "Keep chunks starting at or before this time, in seconds since the epoch",
+    );
+    let output = output_parser();
+    let file_path = file_parser();
+    let policy = output_policy_parser();
+    let crop_cmd = construct!(Opts::CropOptions {
+        range,
+        output,
+        policy,
+        file_path
+    })
+    .to_options()
+    .descr("Quickly crop a rosbag to the whole chunks overlapping a time range, without decompressing them")
+    .command("crop");
+    let time_source = long("time-source")
+        .help("Clock to order messages by: receive, or header to use header stamps where messages have them")
+        .argument::<TimeSource>("SOURCE")
+        .fallback(TimeSource::Receive);
+    let offsets = long("offset")
+        .help("Seconds to add to the times of one of the bags to correct its clock, like robot.bag=-0.25 (repeatable)")
+        .argument::<BagOffset>("FILE=SECS")
+        .many();
+    let auto_offsets = long("auto-offsets")
+        .help("Estimate the offsets of bags without an --offset against the first bag, like `frost skew`")
+        .switch();
+    let rewrite_times = long("rewrite-times")
+        .help("Write the corrected times as receive times, so the merged bag replays in that order")
+        .switch();
+    let dedup = long("dedup")
+        .help("Drop messages identical to one from another bag on the same topic within SECS of it, like those of a backup recorder")
+        .argument::<f64>("SECS")
+        .guard(
+            |secs| secs.is_finite() && *secs >= 0.0,
+            "--dedup must not be negative",
+        )
+        .optional();
+    let compression = compression_parser();
+    let output = output_parser();
+    let file_paths = file_parser().some("at least one bag is required");
+    let policy = output_policy_parser();
+    let merge_cmd = construct!(Opts::MergeOptions {
+        time_source,
+        offsets,
+        auto_offsets,
+        rewrite_times,
+        dedup,
+        compression,
+        output,
+        policy,
+        file_paths
+    })
+    .to_options()
+    .descr("Merge rosbags into one ordered by receive times or header stamps, correcting for clock skew")
+    .command("merge");
+    let reference =
+        positional::<PathBuf>("REFERENCE").complete_shell(ShellComp::File { mask: None });
+    let file_paths = file_parser().some("at least one bag to compare is required");
+    let skew_cmd = construct!(Opts::SkewOptions {
+        reference,
+        file_paths
+    })
+    .to_options()
+    .descr("Estimate the clock offsets of rosbags from other machines against a reference, from messages both recorded")
+    .command("skew");
+    let topic = long("topic")
+        .help("Topic whose connections to edit")
+        .argument::<String>("TOPIC")
+        .complete(complete_topic)
+        .optional();
+    let data_type = long("type")
+        .help("Message type to set, like nav_msgs/Odometry, along with --md5sum and --definition")
+        .argument::<String>("TYPE")
+        .optional();
+    let md5sum = long("md5sum")
+        .help("md5sum of the message type to set")
+        .argument::<String>("MD5")
+        .optional();
+    let definition = long("definition")
+        .help("File with the full message definition of the type to set, as recorded in bags, checked against --md5sum")
+        .argument::<PathBuf>("FILE")
+        .complete_shell(ShellComp::File { mask: None })
+        .optional();
+    let new_type = construct!(NewType {
+        data_type,
+        md5sum,
+        definition
+    });
+    let latching = long("latching")
+        .help("Mark the connections as latching or not: true or false")
+        .argument::<bool>("BOOL")
+        .optional();
+    let recover = long("recover")
+        .help("Restore the index of a rosbag whose edit was interrupted, instead of editing it")
+        .switch();
+    let file_path = file_parser();
+    let edit_cmd = construct!(Opts::EditOptions {
+        topic,
+        new_type,
+        latching,
+        recover,
+        file_path
+    })
+    .to_options()
+    .descr("Fix a topic's connections in place, rewriting only the index at the end of the rosbag")
+    .command("edit");
+    let analyze = long("analyze")
+        .help("Also flag chunks whose compression ratio or size is far from the norm, like stuck or noisy sensors")
+        .switch();
+    let jobs = jobs_parser();
+    let file_paths = file_parser().some("at least one bag is required");
+    let check_cmd = construct!(Opts::CheckOptions {
+        analyze,
+        jobs,
+        file_paths
+    })
+    .to_options()
+    .descr("Check that messages were recorded in receive time order, exiting with 1 if not")
+    .command("check");
+    let svg = long("svg")
+        .help("Also render the layout as an SVG heatmap of topics over the bag's storage and time")
+        .argument::<PathBuf>("OUTPUT")
+        .complete_shell(ShellComp::File { mask: None })
+        .optional();
+    let format = format_parser();
+    let relative_time = relative_time_parser();
+    let file_path = file_parser();
+    let layout_cmd = construct!(Opts::LayoutOptions {
+        svg,
+        format,
+        relative_time,
+        file_path
+    })
+    .to_options()
+    .descr("Print how topics are spread over chunks, with each topic's share of every chunk's messages and bytes")
+    .command("layout");
+    let exact = long("exact")
+        .help("Count the serialized bytes of every message exactly, reading each chunk, instead of estimating from the index")
+        .switch();
+    let cache = long("cache")
+        .help("Read the bag's metadata and exact counts from the cache in ~/.cache/frost, caching them on the first run")
+        .switch();
+    let format = format_parser();
+    let file_path = file_parser();
+    let sizes_cmd = construct!(Opts::SizesOptions {
+        exact,
+        cache,
+        format,
+        file_path
+    })
+    .to_options()
+    .descr("Print how many bytes each topic's messages take")
+    .command("sizes");
+    let topic = long("topic")
+        .help("Topic of the nav_msgs/OccupancyGrid messages")
+        .argument::<String>("TOPIC")
+        .complete(complete_topic)
+        .fallback("/map".to_string());
+    let format = long("format")
+        .help("Image format: pgm, or png with the images feature")
+        .argument::<MapImageFormat>("FORMAT")
+        .fallback(MapImageFormat::Pgm);
+    let output = short('o')
+        .long("output")
+        .help("Path of the map to write, without an extension")
+        .argument::<PathBuf>("OUTPUT")
+        .complete_shell(ShellComp::File { mask: None })
+        .fallback(PathBuf::from("map"));
+    let relative_time = relative_time_parser();
+    let file_path = file_parser();
+    let map_cmd = construct!(Opts::MapOptions {
+        topic,
+        format,
+        output,
+        relative_time,
+        file_path
+    })
+    .to_options()
+    .descr("Save the latest occupancy grid on a topic as a map_server image and YAML file")
+    .command("map");
+    let topic = long("topic")
+        .help("Topic of the diagnostic_msgs/DiagnosticArray messages")
+        .argument::<String>("TOPIC")
+        .complete(complete_topic)
+        .fallback("/diagnostics".to_string());
+    let relative_time = relative_time_parser();
+    let file_path = file_parser();
+    let diagnostics_cmd = construct!(Opts::DiagnosticsOptions {
+        topic,
+        relative_time,
+        file_path
+    })
+    .to_options()
+    .descr("Summarize per-component diagnostics: time at each level and first failures")
+    .command("diagnostics");
+    let top = long("top")
+        .help("Number of issues to print, most severe first")
+        .argument::<usize>("N")
+        .fallback(5);
+    let file_path = file_parser();
+    let triage_cmd = construct!(Opts::TriageOptions { top, file_path })
+        .to_options()
+        .descr("Score how usable a rosbag is and list its top issues, exiting with 1 if it is unusable")
+        .command("triage");
+    let min_level = long("min-level")
+        .help("Only print entries at or above this level: debug, info, warn, error, or fatal")
+        .argument::<LogLevel>("LEVEL")
+        .fallback(LogLevel::Debug);
+    let format = format_parser();
+    let relative_time = relative_time_parser();
+    let file_path = file_parser();
+    let logs_cmd = construct!(Opts::LogsOptions {
+        min_level,
+        format,
+        relative_time,
+        file_path
+    })
+    .to_options()
+    .descr("Print rosgraph_msgs/Log entries ordered by time, as text or JSON lines")
+    .command("logs");
+    let topics = topic_filter_parser(
+        "Topic to print messages of (repeatable), all topics if none are given",
+    );
+    let format = long("format")
+        .help("Encoding of the messages: json (lines), cbor, msgpack, or raw (length-prefixed serialized messages)")
+        .argument::<EchoFormat>("FORMAT")
+        .fallback(EchoFormat::Json);
+    let range = message_range_parser();
+    let file_path = file_parser();
+    let echo_cmd = construct!(Opts::EchoOptions {
+        topics,
+        range,
+        format,
+        file_path
+    })
+    .to_options()
+    .descr("Stream decoded messages ordered by time to stdout, as JSON lines or binary records")
+    .command("echo");
+    let topics = topic_filter_parser(
+        "Topic to print messages of (repeatable), all topics if none are given",
+    );
+    let format = long("format")
+        .help("Encoding of the messages: json (lines), cbor, msgpack, or raw (length-prefixed serialized messages)")
+        .argument::<EchoFormat>("FORMAT")
+        .fallback(EchoFormat::Json);
+    let from_start = long("from-start")
+        .help("Also print the messages already in the bag, instead of only new ones")
+        .switch();
+    let interval = long("interval")
+        .help("Milliseconds to wait between checks for new chunks")
+        .argument::<u64>("MS")
+        .fallback(500);
+    let range = message_range_parser();
+    let file_path = file_parser();
+    let tail_cmd = construct!(Opts::TailOptions {
+        topics,
+        range,
+        format,
+        from_start,
+        interval,
+        file_path
+    })
+    .to_options()
+    .descr("Follow a bag being recorded, like a .active bag, printing messages as their chunks are written until recording stops")
+    .command("tail");
+    let topics = topic_filter_parser("Topic to export (repeatable), all topics if none are given");
+    let range = message_range_parser();
+    let compression = long("compression")
+        .help("Chunk compression of the MCAP file: none, lz4, or zstd with the zstd feature")
+        .argument::<McapCompression>("COMPRESSION")
+        .fallback(McapCompression::Lz4);
+    let output = short('o')
+        .long("output")
+        .help("Path of the MCAP file to write, or of the directory to write <FILE stem>.mcap files into for many bags")
+        .argument::<PathBuf>("OUTPUT")
+        .complete_shell(ShellComp::File { mask: None });
+    let jobs = jobs_parser();
+    let file_paths = file_parser().some("at least one bag is required");
+    let policy = output_policy_parser();
+    let export_cmd = construct!(Opts::ExportOptions {
+        topics,
+        range,
+        compression,
+        output,
+        jobs,
+        policy,
+        file_paths
+    })
+    .to_options()
+    .descr("Export a rosbag as an MCAP file with ROS 1 encoded messages, for ROS 2 tools like Foxglove")
+    .command("export");
+    let compression = long("compression")
+        .help("Chunk compression of the written bag: none, lz4, or bz2 and zstd with the bz2 and zstd features")
+        .argument::<Compression>("COMPRESSION")
+        .fallback(Compression::None);
+    let msg_dirs = long("msg-dir")
+        .help("Directory searched for the .msg files of ROS 2 types whose definitions an input doesn't store (repeatable)")
+        .argument::<PathBuf>("DIR")
+        .complete_shell(ShellComp::Dir { mask: None })
+        .many();
+    let output = output_parser();
+    let inputs = positional::<PathBuf>("INPUT")
+        .help("MCAP files, rosbag2 .db3 files, rosbag2 directories, or pcap captures of TCPROS traffic")
+        .complete_shell(ShellComp::File { mask: None })
+        .some("expected an MCAP file or rosbag2 bag");
+    let policy = output_policy_parser();
+    let import_cmd = construct!(Opts::ImportOptions {
+        compression,
+        msg_dirs,
+        output,
+        policy,
+        inputs
+    })
+    .to_options()
+    .descr("Import MCAP files, rosbag2 bags, and packet captures into a rosbag, converting ROS 2 CDR messages")
+    .command("import");
+    let topic = long("topic")
+        .help("Topic of the nav_msgs/Odometry, PoseStamped, or tf2_msgs/TFMessage messages")
+        .argument::<String>("TOPIC")
+        .complete(complete_topic)
+        .fallback("/odom".to_string());
+    let child_frame = long("child-frame")
+        .help("Frame to follow, required for tf topics and optional for odometry")
+        .argument::<String>("FRAME")
+        .optional();
+    let stop_speed = long("stop-speed")
+        .help("Speed in m/s at or below which the robot counts as stopped")
+        .argument::<f64>("M_PER_S")
+        .fallback(0.05);
+    let stop_time = long("stop-time")
+        .help("Shortest stop to report, in seconds")
+        .argument::<f64>("SECS")
+        .guard(
+            |secs| (0.0..=f64::from(u32::MAX)).contains(secs),
+            "--stop-time must be between 0 and 4294967295",
+        )
+        .fallback(2.0);
+    let format = long("format")
+        .help("Format of the exported trajectory: csv or geojson")
+        .argument::<TrajectoryFormat>("FORMAT")
+        .fallback(TrajectoryFormat::Csv);
+    let output = short('o')
+        .long("output")
+        .help("Path to export the trajectory to")
+        .argument::<PathBuf>("OUTPUT")
+        .complete_shell(ShellComp::File { mask: None })
+        .optional();
+    let relative_time = relative_time_parser();
+    let file_path = file_parser();
+    let trajectory_cmd = construct!(Opts::TrajectoryOptions {
+        topic,
+        child_frame,
+        stop_speed,
+        stop_time,
+        format,
+        output,
+        relative_time,
+        file_path
+    })
+    .to_options()
+    .descr("Print the length, speed, and stops of a trajectory, optionally exporting it")
+    .command("trajectory");
+    let plugins_cmd = pure(Opts::PluginsOptions)
+        .to_options()
+        .descr("List the frost-plugin-<COMMAND> executables on the PATH, run as `frost <COMMAND>`")
+        .command("plugins");
+    let capabilities = long("capabilities")
+        .help("Print the bag formats, compressions and features supported by this build")
+        .req_flag(());
+    let format = format_parser();
+    let capabilities_flag =
+        construct!(capabilities, format).map(|((), format)| Opts::CapabilitiesOptions { format });
+    let parser = construct!([
+        info_cmd,
+        topics_cmd,
+        types_cmd,
+        split_topics_cmd,
+        retain_cmd,
+        crop_cmd,
+        merge_cmd,
+        skew_cmd,
+        edit_cmd,
+        check_cmd,
+        layout_cmd,
+        sizes_cmd,
+        map_cmd,
+        diagnostics_cmd,
+        triage_cmd,
+        logs_cmd,
+        echo_cmd,
+        tail_cmd,
+        export_cmd,
+        import_cmd,
+        trajectory_cmd,
+        plugins_cmd
+    ]);
+    #[cfg(feature = "cache")]
+    let parser = {
+        let cache_list_cmd = pure(Opts::CacheListOptions)
+            .to_options()
+            .descr("List the bags in the metadata cache")
+            .command("list");
+        let cache_clear_cmd = pure(Opts::CacheClearOptions)
+            .to_options()
+            .descr("Remove every bag from the metadata cache")
+            .command("clear");
+        let cache_cmd = construct!([cache_list_cmd, cache_clear_cmd])
+            .to_options()
+            .descr("Manage the metadata cache used by --cache")
+            .command("cache");
+        construct!([parser, cache_cmd])
+    };
+    #[cfg(feature = "manifest")]
+    let parser = {
+        let key_file = key_file_parser();
+        let output = short('o')
+            .long("output")
+            .help(
+                "Path of the manifest to write (defaults to <FILE stem>.manifest.json next to the bag)",
+            )
+            .argument::<PathBuf>("OUTPUT")
+            .optional();
+        let file_path = file_parser();
+        let manifest_cmd = construct!(Opts::ManifestOptions {
+            key_file,
+            output,
+            file_path
+        })
+        .to_options()
+        .descr("Write a manifest of per-chunk and metadata SHA-256 hashes, optionally authenticated with an HMAC")
+        .command("manifest");
+        let key_file = key_file_parser();
+        let file_path = file_parser();
+        let manifest_path =
+            positional::<PathBuf>("MANIFEST").complete_shell(ShellComp::File { mask: None });
+        let verify_cmd = construct!(Opts::VerifyOptions {
+            key_file,
+            file_path,
+            manifest_path
+        })
+        .to_options()
+        .descr("Check a rosbag against a manifest, exiting with 1 if it was modified")
+        .command("verify");
+        construct!([parser, manifest_cmd, verify_cmd])
+    };
+    #[cfg(feature = "yaml")]
+    let parser = {
+        let config = long("config")
+            .help(
+                "YAML file listing the signals to summarize as topic, field, and optional min and max",
+            )
+            .argument::<PathBuf>("CONFIG")
+            .complete_shell(ShellComp::File { mask: None });
+        let relative_time = relative_time_parser();
+        let file_path = file_parser();
+        let health_cmd = construct!(Opts::HealthOptions {
+            config,
+            relative_time,
+            file_path
+        })
+        .to_options()
+        .descr("Summarize numeric signals and their threshold violations, exiting with 1 on violations")
+        .command("health");
+        construct!([parser, health_cmd])
+    };
+    #[cfg(feature = "zstd")]
+    let parser = {
+        let max_size = long("max-size")
+            .help("Maximum size of the dictionary in bytes")
+            .argument::<usize>("BYTES")
+            .fallback(112640);
+        let output = short('o')
+            .long("output")
+            .help("Path of the dictionary to write")
+            .argument::<PathBuf>("OUTPUT")
+            .complete_shell(ShellComp::File { mask: None });
+        let file_paths = file_parser().some("at least one bag is required");
+        let policy = output_policy_parser();
+        let dict_train_cmd = construct!(Opts::DictTrainOptions {
+            max_size,
+            output,
+            policy,
+            file_paths
+        })
+        .to_options()
+        .descr("Train a zstd dictionary on messages sampled from many rosbags")
+        .command("dict-train");
+        let dictionary = long("dictionary")
+            .help("Dictionary written by dict-train")
+            .argument::<PathBuf>("DICT")
+            .complete_shell(ShellComp::File { mask: None });
+        let output_dir = short('o')
+            .long("output-dir")
+            .help("Directory to write the rewritten bags into, keeping their file names")
+            .argument::<PathBuf>("DIR");
+        let jobs = jobs_parser();
+        let file_paths = file_parser().some("at least one bag is required");
+        let policy = output_policy_parser();
+        let dict_rewrite_cmd = construct!(Opts::DictRewriteOptions {
+            dictionary,
+            output_dir,
+            jobs,
+            policy,
+            file_paths
+        })
+        .to_options()
+        .descr("Rewrite rosbags with zstd and a trained dictionary, reporting the space saved")
+        .command("dict-rewrite");
+        construct!([parser, dict_train_cmd, dict_rewrite_cmd])
+    };
+    #[cfg(feature = "images")]
+    let parser = {
+        let topics = topic_filter_parser(
+            "Topic of sensor_msgs/Image messages to export (repeatable), all image topics if none are given",
+        );
+        let output_dir = short('o')
+            .long("output-dir")
+            .help("Directory to write the images into")
+            .argument::<PathBuf>("DIR")
+            .complete_shell(ShellComp::Dir { mask: None })
+            .fallback(PathBuf::from("."));
+        let name = long("name")
+            .help("Template of the images' paths in DIR, with {topic}, {type}, {stamp_ns}, {stamp}, {seq} and {ext}")
+            .argument::<NameTemplate>("TEMPLATE")
+            .guard(
+                |name| name.check(MESSAGE_PLACEHOLDERS).is_ok(),
+                "--name may only use {topic}, {type}, {stamp_ns}, {stamp}, {seq} and {ext}",
+            )
+            .fallback("{topic}/{stamp_ns}.{ext}".parse().expect("a valid template"));
+        let collision = long("collision")
+            .help("When an image's name is taken by an existing file: suffix, overwrite, or fail")
+            .argument::<Collision>("COLLISION")
+            .fallback(Collision::Suffix);
+        let file_path = file_parser();
+        let images_cmd = construct!(Opts::ImagesOptions {
+            topics,
+            output_dir,
+            name,
+            collision,
+            file_path
+        })
+        .to_options()
+        .descr("Export sensor_msgs/Image messages as PNG files")
+        .command("images");
+        construct!([parser, images_cmd])
+    };
+    #[cfg(feature = "mount")]
+    let parser = {
+        let format = long("format")
+            .help("Present messages as decoded json or raw serialized bytes")
+            .argument::<frost::mount::FileFormat>("FORMAT")
+            .fallback(frost::mount::FileFormat::Json);
+        let file_path = file_parser();
+        let mountpoint =
+            positional::<PathBuf>("MOUNTPOINT").complete_shell(ShellComp::Dir { mask: None });
+        let mount_cmd = construct!(Opts::MountOptions {
+            format,
+            file_path,
+            mountpoint
+        })
+        .to_options()
+        .descr("Mount a rosbag read-only, with a directory per topic and a file per message")
+        .command("mount");
+        construct!([parser, mount_cmd])
+    };
+    let parser = construct!([parser, capabilities_flag]);
+    parser
+        .to_options()
+        .version(env!("CARGO_PKG_VERSION"))
+        .footer(
+        "Other commands run frost-plugin-<COMMAND> executables from the PATH, see `frost plugins`.",
+    )
 }
 
 fn max_type_len(metadata: &BagMetadata) -> usize {
@@ -68,32 +1578,53 @@ fn print_topics(metadata: &BagMetadata, writer: &mut impl Write) -> Result<(), E
     Ok(())
 }
 
-fn print_types(metadata: &BagMetadata, writer: &mut impl Write) -> Result<(), Error> {
-    for topic in metadata.types().into_iter().sorted() {
-        writer.write_all(format!("{topic}\n").as_bytes())?
+fn print_topic_tree(metadata: &BagMetadata, writer: &mut impl Write) -> Result<(), Error> {
+    fn describe(node: &TopicTree) -> String {
+        match (node.children().next(), node.message_count()) {
+            (None, Some(count)) => format!("{} ({count})", node.name()),
+            (_, own) => format!(
+                "{}/ ({} topics, {} messages{})",
+                node.name(),
+                node.topic_count(),
+                node.total_message_count(),
+                node.topic()
+                    .zip(own)
+                    .map_or_else(String::new, |(topic, count)| format!(
+                        "; {count} on {topic}"
+                    ))
+            ),
+        }
     }
-    Ok(())
-}
 
-fn human_bytes(bytes: u64) -> String {
-    let units = ["bytes", "KB", "MB", "GB"];
-
-    let mut unit = units[0];
-    let mut remainder = bytes as f64;
-
-    for u in units {
-        unit = u;
-        if remainder < 1024.0 {
-            break;
+    fn print_children(
+        node: &TopicTree,
+        prefix: &str,
+        writer: &mut impl Write,
+    ) -> Result<(), Error> {
+        let mut children = node.children().peekable();
+        while let Some(child) = children.next() {
+            let last = children.peek().is_none();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            writer.write_all(format!("{prefix}{branch}{}\n", describe(child)).as_bytes())?;
+            print_children(child, &format!("{prefix}{indent}"), writer)?;
         }
-        remainder /= 1024.0;
+        Ok(())
     }
 
-    if unit == "bytes" {
-        format!("{bytes} bytes")
-    } else {
-        format!("{remainder:.2} {unit} ({bytes} bytes)")
+    let tree = metadata.topic_tree();
+    writer.write_all(format!("{}\n", describe(&tree)).as_bytes())?;
+    print_children(&tree, "", writer)
+}
+
+fn print_types(metadata: &BagMetadata, writer: &mut impl Write) -> Result<(), Error> {
+    for topic in metadata.types().into_iter().sorted() {
+        writer.write_all(format!("{topic}\n").as_bytes())?
     }
+    Ok(())
 }
 
 fn print_all(metadata: &BagMetadata, minimal: bool, writer: &mut impl Write) -> Result<(), Error> {
@@ -126,7 +1657,7 @@ fn print_all(metadata: &BagMetadata, minimal: bool, writer: &mut impl Write) ->
         format!(
             "{0: <13}{1} ({2:.6})\n",
             "start:",
-            start_time.as_datetime().unwrap_or_default(),
+            start_time.as_rfc3339().as_deref().unwrap_or(INVALID_TIME),
             f64::from(start_time)
         )
         .as_bytes(),
@@ -135,7 +1666,7 @@ fn print_all(metadata: &BagMetadata, minimal: bool, writer: &mut impl Write) ->
         format!(
             "{0: <13}{1} ({2:.6})\n",
             "end:",
-            end_time.as_datetime().unwrap_or_default(),
+            end_time.as_rfc3339().as_deref().unwrap_or(INVALID_TIME),
             f64::from(end_time)
         )
         .as_bytes(),
@@ -216,25 +1747,1692 @@ fn print_all(metadata: &BagMetadata, minimal: bool, writer: &mut impl Write) ->
     Ok(())
 }
 
-fn main() -> Result<(), Error> {
-    let args = args();
+fn print_capabilities(
+    capabilities: &Capabilities,
+    format: OutputFormat,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, capabilities)
+                .map_err(std::io::Error::from)?;
+            writer.write_all(b"\n")?;
+        }
+        OutputFormat::Text => {
+            writer
+                .write_all(format!("{0: <14}{1}\n", "version:", capabilities.version).as_bytes())?;
+            for (name, values) in [
+                ("formats:", &capabilities.formats),
+                ("compressions:", &capabilities.compressions),
+                ("features:", &capabilities.features),
+            ] {
+                writer.write_all(format!("{0: <14}{1}\n", name, values.join(", ")).as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
 
-    let stdout = std::io::stdout();
-    let lock = stdout.lock();
-    let mut writer = BufWriter::new(lock);
+fn split_topics(
+    file_path: &Path,
+    groups: &[TopicGroup],
+    output_dir: Option<&Path>,
+    name: NameTemplate,
+    compression: OutputCompression,
+    policy: OutputPolicy,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let bag = DecompressedBag::from_file(file_path)?;
 
-    match args {
-        Opts::TopicOptions { file_path } => {
-            let metadata = BagMetadata::from_file(file_path)?;
-            print_topics(&metadata, &mut writer)
+    let output_dir = output_dir
+        .or_else(|| file_path.parent())
+        .unwrap_or_else(|| Path::new("."));
+    let stem = file_path
+        .file_stem()
+        .map_or_else(|| "bag".into(), |stem| stem.to_string_lossy());
+    // existing bags are refused by the policy, or replaced with --force
+    let mut namer = FileNamer::new(output_dir, name).with_collision(Collision::Overwrite);
+
+    for group in groups {
+        let topics: Vec<&str> = bag
+            .metadata
+            .topics()
+            .into_iter()
+            .filter(|topic| group.matches(topic))
+            .sorted()
+            .collect();
+        if topics.is_empty() {
+            eprintln!("no topics matched group '{}', skipping", group.name);
+            continue;
         }
-        Opts::InfoOptions { minimal, file_path } => {
-            let metadata = BagMetadata::from_file(file_path)?;
-            print_all(&metadata, minimal, &mut writer)
+
+        let out_path = namer.name(
+            &NameFields::new()
+                .with("stem", stem.as_ref())
+                .with("group", group.name.as_str())
+                .with_ext("bag"),
+        )?;
+        let mut bag_writer = compression.apply(BagWriter::new(policy.create(&out_path)?)?);
+        let count = bag_writer.copy_messages(&bag, &Query::new().with_topics(&topics))?;
+        let output = bag_writer.finish()?;
+
+        writer.write_all(
+            format!(
+                "{}: {count} messages on {} topics, {}\n",
+                group.name,
+                topics.len(),
+                commit_output(output)?
+            )
+            .as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+fn edit_index(
+    file_path: &Path,
+    topic: Option<&str>,
+    new_type: NewType,
+    latching: Option<bool>,
+    recover: bool,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    if recover {
+        let message = match edit::recover(file_path)? {
+            true => "restored the index from before the interrupted edit",
+            false => "no interrupted edit to recover",
+        };
+        writer.write_all(format!("{}: {message}\n", file_path.display()).as_bytes())?;
+        return Ok(());
+    }
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+    let topic = topic.ok_or_else(|| invalid("--topic is required"))?;
+    let data_type = match (new_type.data_type, new_type.md5sum, new_type.definition) {
+        (Some(data_type), Some(md5sum), Some(definition)) => {
+            Some((data_type, md5sum, std::fs::read_to_string(definition)?))
         }
-        Opts::TypeOptions { file_path } => {
-            let metadata = BagMetadata::from_file(file_path)?;
+        (None, None, None) => None,
+        _ => {
+            return Err(invalid("--type, --md5sum and --definition must be given together").into())
+        }
+    };
+    if data_type.is_none() && latching.is_none() {
+        return Err(invalid(
+            "nothing to edit, expected --type, --md5sum and --definition, or --latching",
+        )
+        .into());
+    }
+
+    #[cfg(not(feature = "md5"))]
+    if data_type.is_some() {
+        return Err(invalid("--type needs frost built with the md5 feature").into());
+    }
+
+    let mut editor = IndexEditor::open(file_path)?;
+    #[cfg(feature = "md5")]
+    if let Some((data_type, md5sum, definition)) = data_type {
+        editor.set_type(topic, &data_type, &md5sum, &definition)?;
+    }
+    if let Some(latching) = latching {
+        editor.set_latching(topic, latching)?;
+    }
+    let report = editor.commit()?;
+    writer.write_all(
+        format!(
+            "edited {} connections, rewrote the index of {} ({} -> {})\n",
+            report.edited_connections,
+            file_path.display(),
+            human_bytes(report.old_index_len),
+            human_bytes(report.new_index_len)
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+fn tail_bag(
+    file_path: &Path,
+    query: &Query,
+    format: EchoFormat,
+    from_start: bool,
+    interval: Duration,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let mut tail = Tail::open(file_path)?;
+    if !from_start {
+        tail.poll()?;
+    }
+    let mut echo_writer = EchoWriter::new(writer, format);
+    tail.follow(&SystemClock, interval, |bag| {
+        echo_writer.write_messages(bag.filter_messages(query)?)?;
+        echo_writer.flush()
+    })
+}
+
+fn retain(
+    file_path: &Path,
+    topics: &TopicFilter,
+    keep_every: &[KeepEvery],
+    dedup: &[DedupRule],
+    compression: OutputCompression,
+    output: Output,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let bag = DecompressedBag::from_file(file_path)?;
+    topics.check(&bag.metadata, file_path)?;
+
+    let mut retention = Retention::new();
+    for topic in bag.metadata.topics() {
+        if !topics.matches(topic) {
+            retention = retention.drop_topic(topic);
+            continue;
+        }
+        if let Some(rule) = keep_every.iter().find(|rule| rule.pattern.matches(topic)) {
+            retention = retention.keep_every(topic, rule.n);
+        }
+        if let Some(rule) = dedup.iter().find(|rule| rule.pattern.matches(topic)) {
+            retention = retention.drop_duplicates(topic, rule.key.clone());
+        }
+    }
+
+    let mut bag_writer = compression.apply(BagWriter::new(output)?);
+    let count = bag_writer.copy_retained(&bag, &Query::all(), &retention)?;
+    let output = bag_writer.finish()?;
+
+    writer.write_all(
+        format!(
+            "kept {count} of {} messages, {}\n",
+            bag.metadata.message_count(),
+            commit_output(output)?
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+fn crop(
+    file_path: &Path,
+    range: TimeRange,
+    mut output: Output,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let storage = FileStorage::open(file_path)?;
+    let metadata = BagMetadata::from_storage(&storage)?;
+    let report = metadata.crop_chunks(&storage, range.start(), range.end(), &mut output)?;
+
+    writer.write_all(
+        format!(
+            "kept {} of {} chunks ({} messages, {}), {}\n",
+            report.chunk_count,
+            metadata.chunk_count(),
+            report.message_count,
+            human_bytes(report.copied_bytes),
+            commit_output(output)?
+        )
+        .as_bytes(),
+    )?;
+    if let (Some(start), Some(end)) = (report.start, report.end) {
+        writer.write_all(format!("{0: <7}{1} to {2}\n", "range:", start, end).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Commits `output`, describing it for a command's report, like `wrote out.bag, 1.21 MB (1268724 bytes)`.
+fn commit_output(output: Output) -> Result<String, Error> {
+    let verb = match output.is_dry_run() {
+        true => "would write",
+        false => "wrote",
+    };
+    let path = output.path().display().to_string();
+    let size = output.commit()?;
+    Ok(format!("{verb} {path}, {}", human_bytes(size)))
+}
+
+/// Returns the clock offset of each bag, from `--offset` or, with `--auto-offsets`, estimated against the first bag.
+fn bag_offsets(
+    file_paths: &[PathBuf],
+    bags: &[DecompressedBag],
+    offsets: &[BagOffset],
+    auto_offsets: bool,
+    writer: &mut impl Write,
+) -> Result<Vec<f64>, Error> {
+    for offset in offsets {
+        if !file_paths.contains(&offset.path) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "--offset {} does not name one of the bags to merge",
+                    offset.path.display()
+                ),
+            )
+            .into());
+        }
+    }
+
+    let mut bag_offsets = Vec::with_capacity(bags.len());
+    for (i, (file_path, bag)) in file_paths.iter().zip(bags.iter()).enumerate() {
+        let offset = match offsets
+            .iter()
+            .rev()
+            .find(|offset| &offset.path == file_path)
+        {
+            Some(offset) => offset.secs,
+            None if auto_offsets && i > 0 => {
+                let estimate = estimate_skew(&bags[0], bag)?;
+                if estimate.offset.is_none() {
+                    eprintln!(
+                        "no messages of {} were also recorded in {}, leaving its clock uncorrected",
+                        file_path.display(),
+                        file_paths[0].display()
+                    );
+                }
+                estimate.offset.unwrap_or(0.0)
+            }
+            None => 0.0,
+        };
+        if offset != 0.0 {
+            writer.write_all(
+                format!("{}: offset by {offset:.6}s\n", file_path.display()).as_bytes(),
+            )?;
+        }
+        bag_offsets.push(offset);
+    }
+    Ok(bag_offsets)
+}
+
+fn write_merged(
+    merge: &Merge,
+    bags: &[DecompressedBag],
+    time_source: TimeSource,
+    compression: OutputCompression,
+    output: Output,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let mut bag_writer = compression.apply(BagWriter::new(output)?);
+    let count = merge.write_to(&mut bag_writer)?;
+    let output = bag_writer.finish()?;
+
+    let total: usize = bags.iter().map(|bag| bag.metadata.message_count()).sum();
+    let dropped = match total.saturating_sub(count) {
+        0 => String::new(),
+        dropped => format!(" ({dropped} duplicates dropped)"),
+    };
+    writer.write_all(
+        format!(
+            "merged {count} messages from {} bags by {time_source} time{dropped}, {}\n",
+            bags.len(),
+            commit_output(output)?
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+fn dict_train(
+    file_paths: &[PathBuf],
+    max_size: usize,
+    mut output: Output,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let mut trainer = frost::dictionary::DictionaryTrainer::new(max_size);
+    for file_path in file_paths {
+        let bag = DecompressedBag::from_file(file_path)?;
+        trainer.add_bag(&bag)?;
+    }
+    let dictionary = trainer.train()?;
+    output.write_all(dictionary.as_bytes())?;
+
+    writer.write_all(
+        format!(
+            "trained a dictionary with id {} on {} messages from {} bags, {}\n",
+            dictionary.id(),
+            trainer.sample_count(),
+            trainer.bag_count(),
+            commit_output(output)?
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+fn dict_rewrite(
+    file_paths: &[PathBuf],
+    dictionary: &Path,
+    output_dir: &Path,
+    jobs: usize,
+    policy: OutputPolicy,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let dictionary = std::sync::Arc::new(frost::dictionary::Dictionary::from_file(dictionary)?);
+
+    let percent = |after: u64, before: u64| 100f64 * after as f64 / before.max(1) as f64;
+    let total_before = std::sync::atomic::AtomicU64::new(0);
+    let total_after = std::sync::atomic::AtomicU64::new(0);
+    run_batch(
+        file_paths,
+        |file_path| file_path,
+        jobs,
+        |file_path, writer| {
+            let out_path = output_dir.join(file_path.file_name().unwrap_or_default());
+            if out_path == *file_path {
+                eprintln!("refusing to overwrite {}, skipping", file_path.display());
+                return Ok(());
+            }
+            let output = policy.create(&out_path)?;
+            let bag = DecompressedBag::from_file(file_path)?;
+
+            let mut bag_writer = BagWriter::new(output)?.with_dictionary(dictionary.clone());
+            bag_writer.copy_messages(&bag, &Query::all())?;
+            let after = bag_writer.finish()?.commit()?;
+
+            let before = bag.metadata.num_bytes;
+            total_before.fetch_add(before, Ordering::Relaxed);
+            total_after.fetch_add(after, Ordering::Relaxed);
+            writer.write_all(
+                format!(
+                    "{}: {} -> {} ({:.2}%)\n",
+                    file_path.display(),
+                    human_bytes(before),
+                    human_bytes(after),
+                    percent(after, before)
+                )
+                .as_bytes(),
+            )?;
+            Ok(())
+        },
+        writer,
+    )?;
+    let (total_before, total_after) = (total_before.into_inner(), total_after.into_inner());
+    writer.write_all(
+        format!(
+            "fleet: {} -> {} ({:.2}%), saved {}\n",
+            human_bytes(total_before),
+            human_bytes(total_after),
+            percent(total_after, total_before),
+            human_bytes(total_before.saturating_sub(total_after))
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Runs `work` on every item, `jobs` at a time, writing each item's output in order, see [Batch].
+///
+/// With many items, one failing is reported with its path without stopping the others, and makes frost exit with 1
+/// once all are done. A single item's error is returned as is.
+fn run_batch<T, W>(
+    items: &[T],
+    path: impl Fn(&T) -> &Path,
+    jobs: usize,
+    work: W,
+    writer: &mut impl Write,
+) -> Result<(), Error>
+where
+    T: Sync,
+    W: Fn(&T, &mut Vec<u8>) -> Result<(), Error> + Sync,
+{
+    let work = |item: &T| {
+        let mut output = Vec::new();
+        work(item, &mut output).map(|()| output)
+    };
+    if let [item] = items {
+        writer.write_all(&work(item)?)?;
+        return Ok(());
+    }
+
+    let mut write_error = None;
+    let summary = Batch::new(jobs).run(items, work, |item, result| match result {
+        Ok(output) => {
+            if let Err(e) = writer.write_all(&output) {
+                write_error.get_or_insert(e);
+            }
+        }
+        Err(e) => eprintln!("{}: {e}", path(item).display()),
+    });
+    if let Some(e) = write_error {
+        return Err(e.into());
+    }
+    if !summary.is_success() {
+        writer.flush()?;
+        eprintln!("{} of {} bags failed", summary.failed, items.len());
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Pairs the bags to export with their MCAP files: `output` itself for a single bag, unless it is a directory, and
+/// `<FILE stem>.mcap` files in the `output` directory otherwise.
+fn export_paths(
+    file_paths: Vec<PathBuf>,
+    output: PathBuf,
+) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+    if file_paths.len() == 1 && !output.is_dir() {
+        return Ok(vec![(file_paths[0].clone(), output)]);
+    }
+    std::fs::create_dir_all(&output)?;
+    let name = "{stem}.{ext}".parse().expect("a valid template");
+    let mut namer = FileNamer::new(output, name).with_collision(Collision::Overwrite);
+    file_paths
+        .into_iter()
+        .map(|file_path| {
+            let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+            let fields = NameFields::new()
+                .with("stem", stem.as_ref())
+                .with_ext("mcap");
+            let output = namer.name(&fields)?;
+            Ok((file_path, output))
+        })
+        .collect()
+}
+
+#[cfg(feature = "manifest")]
+fn read_key(key_file: Option<&Path>) -> Result<Option<Vec<u8>>, Error> {
+    Ok(key_file.map(std::fs::read).transpose()?)
+}
+
+#[cfg(feature = "manifest")]
+fn write_manifest(
+    file_path: &Path,
+    key_file: Option<&Path>,
+    output: Option<&Path>,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let mut manifest = Manifest::from_file(file_path)?;
+    if let Some(key) = read_key(key_file)? {
+        manifest = manifest.sign(&key);
+    }
+
+    let output = output.map_or_else(
+        || file_path.with_extension("manifest.json"),
+        Path::to_path_buf,
+    );
+    std::fs::write(&output, manifest.to_json())?;
+
+    writer.write_all(
+        format!(
+            "wrote {} manifest of {} chunks to {}\n",
+            if manifest.signature.is_some() {
+                "HMAC authenticated"
+            } else {
+                "unauthenticated"
+            },
+            manifest.chunks.len(),
+            output.display()
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "images")]
+fn export_images(
+    file_path: &Path,
+    topics: &TopicFilter,
+    mut namer: FileNamer,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let bag = DecompressedBag::from_file(file_path)?;
+    topics.check(&bag.metadata, file_path)?;
+    let query = topics.select(
+        Query::new().with_types(["sensor_msgs/Image"]),
+        &bag.metadata,
+    );
+
+    let mut count = 0;
+    for msg_view in bag.read_messages(&query)? {
+        let image = match msg_view.decode_image() {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!(
+                    "skipping the image on {} at {}: {e}",
+                    msg_view.topic, msg_view.time
+                );
+                continue;
+            }
+        };
+        let path = namer.name(&NameFields::for_message(&msg_view).with_ext("png"))?;
+        image
+            .save_with_format(&path, image::ImageFormat::Png)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        count += 1;
+    }
+    writer.write_all(format!("wrote {count} images to {}\n", namer.root().display()).as_bytes())?;
+    Ok(())
+}
+
+fn save_map(
+    file_path: &Path,
+    topic: &str,
+    format: MapImageFormat,
+    output: &Path,
+    relative_time: bool,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let bag = DecompressedBag::from_file(file_path)?;
+    check_topic(&bag.metadata, topic, file_path)?;
+    let query = Query::new().with_topics([topic]);
+    let Some(msg_view) = bag.read_messages(&query)?.last() else {
+        eprintln!("no messages on {topic}");
+        std::process::exit(1);
+    };
+    let grid = OccupancyGrid::from_value(&msg_view.decode()?)?;
+    let (image_path, yaml_path) = grid.save(output, format)?;
+    writer.write_all(
+        format!(
+            "wrote {} and {} ({}x{} cells of {}m at {})\n",
+            image_path.display(),
+            yaml_path.display(),
+            grid.width,
+            grid.height,
+            grid.resolution,
+            TimeDisplay::new(relative_time, &bag.metadata).format(msg_view.time)
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+fn print_diagnostics(
+    report: &DiagnosticsReport,
+    times: TimeDisplay,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let duration = match (report.start, report.end) {
+        (Some(start), Some(end)) => end.duration_since(&start),
+        _ => Duration::ZERO,
+    };
+    writer.write_all(
+        format!(
+            "{0: <12}{1} over {2:.3}s\n",
+            "messages:",
+            report.message_count,
+            duration.as_secs_f64()
+        )
+        .as_bytes(),
+    )?;
+    let failures = report.failures();
+    writer.write_all(
+        format!(
+            "{0: <12}{1} ({2} not OK)\n",
+            "components:",
+            report.components.len(),
+            failures.len()
+        )
+        .as_bytes(),
+    )?;
+    if report.components.is_empty() {
+        return Ok(());
+    }
+
+    let name_len = report
+        .components
+        .keys()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("COMPONENT".len());
+    writer.write_all(format!("\n{0: <name_len$}  ", "COMPONENT").as_bytes())?;
+    for level in DiagnosticLevel::ALL {
+        writer.write_all(format!("{: <10}", level.to_string()).as_bytes())?;
+    }
+    writer.write_all(b"LAST\n")?;
+    for (name, component) in report.components.iter() {
+        writer.write_all(format!("{name: <name_len$}  ").as_bytes())?;
+        for level in DiagnosticLevel::ALL {
+            let secs = format!("{:.1}s", component.time_in(level).as_secs_f64());
+            writer.write_all(format!("{secs: <10}").as_bytes())?;
+        }
+        writer.write_all(format!("{}\n", component.last_level).as_bytes())?;
+    }
+
+    if !failures.is_empty() {
+        writer.write_all(b"\nfirst failures:\n")?;
+    }
+    for (name, component) in failures {
+        for (level, first) in [
+            (DiagnosticLevel::Warn, &component.first_warn),
+            (DiagnosticLevel::Error, &component.first_error),
+            (DiagnosticLevel::Stale, &component.first_stale),
+        ] {
+            if let Some((time, message)) = first {
+                let time = times.format(*time);
+                writer.write_all(format!("{name}: {level} at {time}: {message}\n").as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "yaml")]
+fn print_health(
+    report: &HealthReport,
+    times: TimeDisplay,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let name_len = report
+        .signals
+        .iter()
+        .map(|signal| signal.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("SIGNAL".len());
+    writer.write_all(
+        format!(
+            "{0: <name_len$}  {1: <8}{2: <12}{3: <12}{4: <12}{5}\n",
+            "SIGNAL", "COUNT", "MIN", "MAX", "MEAN", "VIOLATIONS"
+        )
+        .as_bytes(),
+    )?;
+    let stat = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{v:.3}"));
+    for signal in report.signals.iter() {
+        writer.write_all(
+            format!(
+                "{0: <name_len$}  {1: <8}{2: <12}{3: <12}{4: <12}{5}\n",
+                signal.name,
+                signal.count,
+                stat(signal.min),
+                stat(signal.max),
+                stat(signal.mean),
+                signal.violations.len()
+            )
+            .as_bytes(),
+        )?;
+    }
+    for signal in report.signals.iter() {
+        if signal.missing > 0 {
+            writer.write_all(
+                format!(
+                    "{}: {} messages without a numeric {}\n",
+                    signal.name, signal.missing, signal.config.field
+                )
+                .as_bytes(),
+            )?;
+        }
+        for violation in signal.violations.iter() {
+            writer.write_all(
+                format!(
+                    "{}: out of bounds from {} to {} ({:.3}s, {} samples, worst {:.3})\n",
+                    signal.name,
+                    times.format(violation.start),
+                    times.format(violation.end),
+                    violation.duration().as_secs_f64(),
+                    violation.samples,
+                    violation.worst
+                )
+                .as_bytes(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn print_sizes(
+    topic_bytes: &BTreeMap<String, TopicBytes>,
+    exact: bool,
+    format: OutputFormat,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    if let OutputFormat::Json = format {
+        let json = serde_json::json!({"exact": exact, "topics": topic_bytes});
+        serde_json::to_writer(&mut *writer, &json).map_err(std::io::Error::from)?;
+        writer.write_all(b"\n")?;
+        return Ok(());
+    }
+    let topic_len = topic_bytes
+        .keys()
+        .map(String::len)
+        .max()
+        .unwrap_or(0)
+        .max("TOPIC".len());
+    writer.write_all(
+        format!("{0: <topic_len$}  {1: >10}  BYTES\n", "TOPIC", "MESSAGES").as_bytes(),
+    )?;
+    let mut total = TopicBytes::default();
+    for (topic, counts) in topic_bytes.iter() {
+        writer.write_all(
+            format!(
+                "{topic: <topic_len$}  {: >10}  {}\n",
+                counts.messages,
+                human_bytes(counts.bytes)
+            )
+            .as_bytes(),
+        )?;
+        total.messages += counts.messages;
+        total.bytes += counts.bytes;
+    }
+    writer.write_all(
+        format!(
+            "{0: <topic_len$}  {1: >10}  {2}\n",
+            "total",
+            total.messages,
+            human_bytes(total.bytes)
+        )
+        .as_bytes(),
+    )?;
+    if !exact {
+        writer.write_all(b"(estimated from the index, including record headers; --exact counts serialized messages)\n")?;
+    }
+    Ok(())
+}
+
+fn print_layout(
+    layout: &ChunkLayout,
+    format: OutputFormat,
+    times: TimeDisplay,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    if let OutputFormat::Json = format {
+        serde_json::to_writer(&mut *writer, layout).map_err(std::io::Error::from)?;
+        writer.write_all(b"\n")?;
+        return Ok(());
+    }
+    let format_time = |time: Time| match times.0 {
+        Some(_) => times.format(time),
+        None => format!("{:.3}", f64::from(time)),
+    };
+    for chunk in layout.chunks.iter() {
+        let topics = chunk
+            .topics
+            .iter()
+            .sorted_by(|(_, a), (_, b)| b.bytes.cmp(&a.bytes))
+            .map(|(topic, share)| {
+                format!(
+                    "{topic} {:.0}% ({} msgs)",
+                    chunk.byte_share(topic) * 100.0,
+                    share.messages
+                )
+            })
+            .join(", ");
+        writer.write_all(
+            format!(
+                "{:<12}{} - {}  {:>10} {:<5}  {topics}\n",
+                chunk.chunk_pos,
+                format_time(chunk.start_time),
+                format_time(chunk.end_time),
+                chunk.compressed_size,
+                chunk.compression,
+            )
+            .as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_logs(
+    entries: &[LogEntry],
+    format: OutputFormat,
+    times: TimeDisplay,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    for entry in entries {
+        match format {
+            OutputFormat::Text if times.0.is_some() => writer.write_all(
+                format!(
+                    "[{:>5}] [{}] [{}]: {}\n",
+                    entry.level,
+                    times.format(entry.time),
+                    entry.node,
+                    entry.message
+                )
+                .as_bytes(),
+            )?,
+            OutputFormat::Text => writer.write_all(format!("{entry}\n").as_bytes())?,
+            OutputFormat::Json => {
+                serde_json::to_writer(&mut *writer, entry).map_err(std::io::Error::from)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_trajectory(
+    trajectory: &Trajectory,
+    stops: &[Stop],
+    times: TimeDisplay,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    writer.write_all(format!("{0: <16}{1}\n", "poses:", trajectory.poses.len()).as_bytes())?;
+    writer.write_all(format!("{0: <16}{1}\n", "frame:", trajectory.frame_id).as_bytes())?;
+    writer.write_all(
+        format!(
+            "{0: <16}{1:.3}s\n",
+            "duration:",
+            trajectory.duration().as_secs_f64()
+        )
+        .as_bytes(),
+    )?;
+    writer.write_all(format!("{0: <16}{1:.3}m\n", "length:", trajectory.length()).as_bytes())?;
+    writer.write_all(
+        format!(
+            "{0: <16}{1:.3}m/s\n",
+            "average speed:",
+            trajectory.average_speed()
+        )
+        .as_bytes(),
+    )?;
+    writer.write_all(format!("{0: <16}{1}\n", "stops:", stops.len()).as_bytes())?;
+    for stop in stops {
+        writer.write_all(
+            format!(
+                "  {} - {} ({:.3}s) at ({:.3}, {:.3})\n",
+                times.format(stop.start),
+                times.format(stop.end),
+                stop.duration().as_secs_f64(),
+                stop.x,
+                stop.y
+            )
+            .as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_triage(report: &TriageReport, top: usize, writer: &mut impl Write) -> Result<(), Error> {
+    writer.write_all(format!("{0: <10}{1}/100\n", "score:", report.score()).as_bytes())?;
+    writer.write_all(format!("{0: <10}{1}\n", "verdict:", report.verdict()).as_bytes())?;
+    let issues = match report.issues.len() {
+        0 => "none".to_string(),
+        count if count > top => format!("{count}, the top {top}:"),
+        count => format!("{count}:"),
+    };
+    writer.write_all(format!("{0: <10}{1}\n", "issues:", issues).as_bytes())?;
+    for issue in report.issues.iter().take(top) {
+        writer.write_all(format!("  {issue}\n").as_bytes())?;
+    }
+    Ok(())
+}
+
+fn print_time_order(report: &TimeOrderReport, writer: &mut impl Write) -> Result<(), Error> {
+    let status = if report.is_monotonic() {
+        "ok"
+    } else {
+        "OUT OF ORDER, rewrite with `frost retain` to sort by receive time"
+    };
+    writer.write_all(format!("{0: <24}{1}\n", "time order:", status).as_bytes())?;
+    writer.write_all(format!("{0: <24}{1}\n", "messages:", report.message_count).as_bytes())?;
+    writer.write_all(
+        format!(
+            "{0: <24}{1}\n",
+            "out of order in chunks:", report.out_of_order_in_chunks
+        )
+        .as_bytes(),
+    )?;
+    writer.write_all(
+        format!(
+            "{0: <24}{1}\n",
+            "overlapping chunks:", report.overlapping_chunks
+        )
+        .as_bytes(),
+    )?;
+    writer.write_all(
+        format!(
+            "{0: <24}{1:.3}s\n",
+            "max step back:",
+            report.max_step_back.as_secs_f64()
+        )
+        .as_bytes(),
+    )?;
+    for (topic, count) in report.out_of_order_by_topic.iter() {
+        writer
+            .write_all(format!("{0: <24}{1} on {2}\n", "out of order:", count, topic).as_bytes())?;
+    }
+    Ok(())
+}
+
+fn print_chunk_analysis(analysis: &ChunkAnalysis, writer: &mut impl Write) -> Result<(), Error> {
+    let status = match (analysis.anomalies.len(), analysis.median_size) {
+        (_, None) => format!("too few chunks, at least {MIN_CHUNKS} are needed"),
+        (0, Some(_)) => "ok".to_string(),
+        (count, Some(_)) => format!("{count} anomalies"),
+    };
+    writer.write_all(format!("{0: <24}{1}\n", "chunk analysis:", status).as_bytes())?;
+    writer.write_all(format!("{0: <24}{1}\n", "chunks:", analysis.chunk_count).as_bytes())?;
+    if let Some(size) = analysis.median_size {
+        writer.write_all(
+            format!(
+                "{0: <24}{1}\n",
+                "median chunk size:",
+                human_bytes(size as u64)
+            )
+            .as_bytes(),
+        )?;
+    }
+    for (compression, ratio) in analysis.median_ratios.iter() {
+        writer.write_all(
+            format!(
+                "{0: <24}{1:.2} ({2})\n",
+                "median ratio:", ratio, compression
+            )
+            .as_bytes(),
+        )?;
+    }
+    for anomaly in analysis.anomalies.iter() {
+        writer.write_all(format!("{0: <24}{1}\n", "anomaly:", anomaly).as_bytes())?;
+    }
+    Ok(())
+}
+
+fn format_secs(secs: f64) -> String {
+    format!("{secs:.6}s")
+}
+
+fn print_skew(
+    file_path: &Path,
+    estimate: &SkewEstimate,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    writer.write_all(format!("{}:\n", file_path.display()).as_bytes())?;
+    let Some(offset) = estimate.offset else {
+        writer.write_all(b"  no messages were recorded in both bags\n")?;
+        return Ok(());
+    };
+    let topic_len = estimate
+        .topics
+        .iter()
+        .map(|topic| topic.topic.len())
+        .max()
+        .unwrap_or(0)
+        .max("TOPIC".len());
+    writer.write_all(
+        format!(
+            "  {0: <topic_len$}  {1: <9}{2: <14}{3}\n",
+            "TOPIC", "MATCHES", "OFFSET", "SPREAD"
+        )
+        .as_bytes(),
+    )?;
+    for topic in estimate.topics.iter() {
+        writer.write_all(
+            format!(
+                "  {0: <topic_len$}  {1: <9}{2: <14}{3}\n",
+                topic.topic,
+                topic.matches,
+                format_secs(topic.offset),
+                format_secs(topic.spread)
+            )
+            .as_bytes(),
+        )?;
+    }
+    let note = if estimate.latency_cancelled {
+        "topics went both ways, so latency cancels out"
+    } else {
+        "topics went one way, so this is off by about one network latency"
+    };
+    writer.write_all(
+        format!(
+            "  offset: {offset:.6}s ({note}), merge with --offset {}={offset:.6}\n",
+            file_path.display()
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+#[cfg(feature = "manifest")]
+fn print_verification(verification: &Verification, writer: &mut impl Write) -> Result<(), Error> {
+    let ok = |matches: bool| if matches { "ok" } else { "MODIFIED" };
+    writer
+        .write_all(format!("{0: <11}{1}\n", "size:", ok(verification.size_matches)).as_bytes())?;
+    writer.write_all(
+        format!(
+            "{0: <11}{1}\n",
+            "metadata:",
+            ok(verification.metadata_matches)
+        )
+        .as_bytes(),
+    )?;
+    for (status, positions) in [
+        ("MODIFIED", &verification.modified_chunks),
+        ("MISSING", &verification.missing_chunks),
+        ("UNEXPECTED", &verification.unexpected_chunks),
+    ] {
+        for pos in positions {
+            writer.write_all(format!("{0: <11}{1} at {2}\n", "chunk:", status, pos).as_bytes())?;
+        }
+    }
+    let signature = match verification.signature {
+        SignatureStatus::Valid => "valid",
+        SignatureStatus::Invalid => "INVALID",
+        SignatureStatus::Missing => "MISSING",
+        SignatureStatus::NotChecked => "not checked, pass --key-file to check it",
+        SignatureStatus::Unsigned => "none",
+    };
+    writer.write_all(format!("{0: <11}{1}\n", "hmac:", signature).as_bytes())?;
+    writer.write_all(
+        format!(
+            "{0: <11}{1}\n",
+            "result:",
+            if verification.is_valid() {
+                "verified"
+            } else {
+                "FAILED"
+            }
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Runs `frost-plugin-<COMMAND>` with the remaining arguments if the first argument names a plugin on the PATH,
+/// exiting with its exit code, or returns if it doesn't.
+fn run_external_plugin() -> Result<(), Error> {
+    let mut argv = std::env::args_os().skip(1);
+    let Some(command) = argv.next().and_then(|command| command.into_string().ok()) else {
+        return Ok(());
+    };
+    if command.starts_with('-') {
+        return Ok(());
+    }
+    let Some(plugin) = plugins::find(&command) else {
+        return Ok(());
+    };
+    let status = plugin.run(argv)?;
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+fn print_plugins(plugins: &[ExternalPlugin], writer: &mut impl Write) -> Result<(), Error> {
+    let name_len = plugins
+        .iter()
+        .map(|plugin| plugin.name.len())
+        .max()
+        .unwrap_or(0);
+    for plugin in plugins {
+        let description = plugin.description().unwrap_or_default();
+        writer.write_all(
+            format!(
+                "{0: <name_len$}  {1}  ({2})\n",
+                plugin.name,
+                description,
+                plugin.path.display()
+            )
+            .as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let parser = args();
+    let args = match parser.run_inner(Args::current_args()) {
+        Ok(args) => args,
+        Err(_) => {
+            run_external_plugin()?;
+            // not a plugin, so let bpaf report the failure (or print help) and exit
+            parser.run()
+        }
+    };
+
+    let stdout = std::io::stdout();
+    let lock = stdout.lock();
+    let mut writer = BufWriter::new(lock);
+
+    match args {
+        Opts::TopicOptions {
+            tree,
+            cache,
+            file_path,
+        } => {
+            let metadata = read_metadata(&file_path, cache)?;
+            if tree {
+                print_topic_tree(&metadata, &mut writer)
+            } else {
+                print_topics(&metadata, &mut writer)
+            }
+        }
+        Opts::InfoOptions {
+            minimal,
+            compat,
+            yaml,
+            key,
+            cache,
+            file_path,
+        } => {
+            let metadata = read_metadata(&file_path, cache)?;
+            for warning in metadata.warnings() {
+                eprintln!("warning: {warning}");
+            }
+            #[cfg(not(feature = "yaml"))]
+            if key.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--key needs frost built with the yaml feature",
+                )
+                .into());
+            }
+            #[cfg(feature = "yaml")]
+            if let Some(key) = key {
+                let Some(value) = metadata.rosbag_yaml_key(&key) else {
+                    eprintln!("Error getting key \"{key}\"");
+                    std::process::exit(1);
+                };
+                return Ok(writer.write_all(value.as_bytes())?);
+            }
+            match (compat, yaml) {
+                (_, true) => Ok(writer.write_all(metadata.to_rosbag_yaml().as_bytes())?),
+                (Some(InfoCompat::Rosbag), false) => {
+                    Ok(writer.write_all(metadata.to_rosbag_info().as_bytes())?)
+                }
+                (None, false) => print_all(&metadata, minimal, &mut writer),
+            }
+        }
+        Opts::TypeOptions { cache, file_path } => {
+            let metadata = read_metadata(&file_path, cache)?;
             print_types(&metadata, &mut writer)
         }
+        #[cfg(feature = "cache")]
+        Opts::CacheListOptions => {
+            let cache = metadata_cache()?;
+            for entry in cache.entries()? {
+                writer.write_all(
+                    format!(
+                        "{}  {} cached of {}\n",
+                        entry.bag_path.display(),
+                        human_bytes(entry.cached_bytes),
+                        human_bytes(entry.bag_bytes)
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            Ok(())
+        }
+        #[cfg(feature = "cache")]
+        Opts::CacheClearOptions => {
+            let cache = metadata_cache()?;
+            let count = cache.clear()?;
+            writer.write_all(
+                format!("removed {count} entries from {}\n", cache.dir().display()).as_bytes(),
+            )?;
+            Ok(())
+        }
+        Opts::SplitTopicsOptions {
+            groups,
+            output_dir,
+            name,
+            compression,
+            policy,
+            file_path,
+        } => split_topics(
+            &file_path,
+            &groups,
+            output_dir.as_deref(),
+            name,
+            compression,
+            policy,
+            &mut writer,
+        ),
+        #[cfg(feature = "images")]
+        Opts::ImagesOptions {
+            topics,
+            output_dir,
+            name,
+            collision,
+            file_path,
+        } => {
+            let namer = FileNamer::new(output_dir, name).with_collision(collision);
+            export_images(&file_path, &topics, namer, &mut writer)
+        }
+        Opts::CropOptions {
+            range,
+            output,
+            policy,
+            file_path,
+        } => crop(&file_path, range, policy.create(output)?, &mut writer),
+        Opts::MergeOptions {
+            time_source,
+            offsets,
+            auto_offsets,
+            rewrite_times,
+            dedup,
+            compression,
+            output,
+            policy,
+            file_paths,
+        } => {
+            let output = policy.create(output)?;
+            let bags = file_paths
+                .iter()
+                .map(DecompressedBag::from_file)
+                .collect::<Result<Vec<_>, _>>()?;
+            let offsets = bag_offsets(&file_paths, &bags, &offsets, auto_offsets, &mut writer)?;
+            let mut merge = Merge::new().with_time_source(time_source);
+            if rewrite_times {
+                merge = merge.with_rewritten_times();
+            }
+            if let Some(tolerance) = dedup {
+                merge = merge.with_dedup_tolerance(tolerance);
+            }
+            for (bag, offset) in bags.iter().zip(offsets) {
+                merge = merge.add_bag(bag, offset);
+            }
+            write_merged(&merge, &bags, time_source, compression, output, &mut writer)
+        }
+        Opts::SkewOptions {
+            reference,
+            file_paths,
+        } => {
+            let reference_bag = DecompressedBag::from_file(&reference)?;
+            for file_path in file_paths.iter() {
+                let bag = DecompressedBag::from_file(file_path)?;
+                let estimate = estimate_skew(&reference_bag, &bag)?;
+                print_skew(file_path, &estimate, &mut writer)?;
+            }
+            Ok(())
+        }
+        Opts::RetainOptions {
+            topics,
+            keep_every,
+            dedup,
+            compression,
+            output,
+            policy,
+            file_path,
+        } => retain(
+            &file_path,
+            &topics,
+            &keep_every,
+            &dedup,
+            compression,
+            policy.create(output)?,
+            &mut writer,
+        ),
+        #[cfg(feature = "manifest")]
+        Opts::ManifestOptions {
+            key_file,
+            output,
+            file_path,
+        } => write_manifest(
+            &file_path,
+            key_file.as_deref(),
+            output.as_deref(),
+            &mut writer,
+        ),
+        Opts::EditOptions {
+            topic,
+            new_type,
+            latching,
+            recover,
+            file_path,
+        } => edit_index(
+            &file_path,
+            topic.as_deref(),
+            new_type,
+            latching,
+            recover,
+            &mut writer,
+        ),
+        #[cfg(feature = "manifest")]
+        Opts::VerifyOptions {
+            key_file,
+            file_path,
+            manifest_path,
+        } => {
+            let manifest = Manifest::from_json(&std::fs::read_to_string(manifest_path)?)?;
+            let key = read_key(key_file.as_deref())?;
+            let verification = manifest.verify_file(&file_path, key.as_deref())?;
+            print_verification(&verification, &mut writer)?;
+            if !verification.is_valid() {
+                writer.flush()?;
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        #[cfg(feature = "mount")]
+        Opts::MountOptions {
+            format,
+            file_path,
+            mountpoint,
+        } => {
+            let bag = DecompressedBag::from_file(file_path)?;
+            eprintln!(
+                "mounted at {}, unmount with `fusermount -u {}`",
+                mountpoint.display(),
+                mountpoint.display()
+            );
+            frost::mount::mount(bag, &mountpoint, format)
+        }
+        #[cfg(feature = "zstd")]
+        Opts::DictTrainOptions {
+            max_size,
+            output,
+            policy,
+            file_paths,
+        } => dict_train(&file_paths, max_size, policy.create(output)?, &mut writer),
+        #[cfg(feature = "zstd")]
+        Opts::DictRewriteOptions {
+            dictionary,
+            output_dir,
+            jobs,
+            policy,
+            file_paths,
+        } => dict_rewrite(
+            &file_paths,
+            &dictionary,
+            &output_dir,
+            jobs,
+            policy,
+            &mut writer,
+        ),
+        Opts::LayoutOptions {
+            svg,
+            format,
+            relative_time,
+            file_path,
+        } => {
+            let metadata = BagMetadata::from_file(file_path)?;
+            let layout = metadata.chunk_layout();
+            if let Some(svg) = svg {
+                std::fs::write(svg, layout.to_svg())?;
+            }
+            let times = TimeDisplay::new(relative_time, &metadata);
+            print_layout(&layout, format, times, &mut writer)
+        }
+        Opts::SizesOptions {
+            exact,
+            cache,
+            format,
+            file_path,
+        } => {
+            let topic_bytes = match (exact, cache) {
+                #[cfg(feature = "cache")]
+                (true, true) => metadata_cache()?.exact_topic_bytes(&file_path)?,
+                #[cfg(feature = "cache")]
+                (false, true) => metadata_cache()?.load(&file_path)?.estimated_topic_bytes(),
+                #[cfg(not(feature = "cache"))]
+                (_, true) => return Err(no_cache()),
+                (true, false) => {
+                    let storage = FileStorage::open(&file_path)?;
+                    BagMetadata::from_storage(&storage)?.exact_topic_bytes(&storage)?
+                }
+                (false, false) => BagMetadata::from_file(file_path)?.estimated_topic_bytes(),
+            };
+            print_sizes(&topic_bytes, exact, format, &mut writer)
+        }
+        Opts::CheckOptions {
+            analyze,
+            jobs,
+            file_paths,
+        } => {
+            let out_of_order = AtomicBool::new(false);
+            let many = file_paths.len() > 1;
+            run_batch(
+                &file_paths,
+                |file_path| file_path,
+                jobs,
+                |file_path, output| {
+                    let metadata = BagMetadata::from_file(file_path)?;
+                    let report = metadata.time_order_report();
+                    if many {
+                        output.write_all(format!("{}:\n", file_path.display()).as_bytes())?;
+                    }
+                    print_time_order(&report, output)?;
+                    if analyze {
+                        print_chunk_analysis(&metadata.chunk_anomalies(DEFAULT_FACTOR), output)?;
+                    }
+                    if !report.is_monotonic() {
+                        out_of_order.store(true, Ordering::Relaxed);
+                    }
+                    Ok(())
+                },
+                &mut writer,
+            )?;
+            if out_of_order.load(Ordering::Relaxed) {
+                writer.flush()?;
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Opts::MapOptions {
+            topic,
+            format,
+            output,
+            relative_time,
+            file_path,
+        } => save_map(
+            &file_path,
+            &topic,
+            format,
+            &output,
+            relative_time,
+            &mut writer,
+        ),
+        Opts::DiagnosticsOptions {
+            topic,
+            relative_time,
+            file_path,
+        } => {
+            let bag = DecompressedBag::from_file(&file_path)?;
+            check_topic(&bag.metadata, &topic, &file_path)?;
+            let report = bag.diagnostics(&Query::new().with_topics([topic]))?;
+            let times = TimeDisplay::new(relative_time, &bag.metadata);
+            print_diagnostics(&report, times, &mut writer)
+        }
+        #[cfg(feature = "yaml")]
+        Opts::HealthOptions {
+            config,
+            relative_time,
+            file_path,
+        } => {
+            let config = HealthConfig::from_file(config)?;
+            let bag = DecompressedBag::from_file(file_path)?;
+            let report = bag.health_report(&config)?;
+            let times = TimeDisplay::new(relative_time, &bag.metadata);
+            print_health(&report, times, &mut writer)?;
+            if !report.is_healthy() {
+                writer.flush()?;
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Opts::TriageOptions { top, file_path } => {
+            let report = TriageReport::from_file(file_path)?;
+            print_triage(&report, top, &mut writer)?;
+            if report.verdict() == Verdict::Unusable {
+                writer.flush()?;
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Opts::LogsOptions {
+            min_level,
+            format,
+            relative_time,
+            file_path,
+        } => {
+            let bag = DecompressedBag::from_file(file_path)?;
+            let times = TimeDisplay::new(relative_time, &bag.metadata);
+            print_logs(
+                &bag.logs(&Query::all(), min_level)?,
+                format,
+                times,
+                &mut writer,
+            )
+        }
+        Opts::EchoOptions {
+            topics,
+            range,
+            format,
+            file_path,
+        } => {
+            let query = range.select(topics.query(&file_path)?);
+            let bag = DecompressedBag::from_file_filtered(file_path, &query)?;
+            let mut echo_writer = EchoWriter::new(&mut writer, format);
+            let result = echo_writer
+                .write_messages(bag.filter_messages(&query)?)
+                .and_then(|_| echo_writer.flush());
+            match result {
+                // piped into `head` or a consumer which stopped reading
+                Err(err) if matches!(err.kind(), ErrorKind::Io(e) if e.kind() == io::ErrorKind::BrokenPipe) => {
+                    Ok(())
+                }
+                result => result,
+            }
+        }
+        Opts::TailOptions {
+            topics,
+            range,
+            format,
+            from_start,
+            interval,
+            file_path,
+        } => {
+            let query = range.select(topics.predicate_query());
+            let interval = Duration::from_millis(interval);
+            match tail_bag(
+                &file_path,
+                &query,
+                format,
+                from_start,
+                interval,
+                &mut writer,
+            ) {
+                // piped into `head` or a consumer which stopped reading
+                Err(err) if matches!(err.kind(), ErrorKind::Io(e) if e.kind() == io::ErrorKind::BrokenPipe) => {
+                    Ok(())
+                }
+                result => result,
+            }
+        }
+        Opts::ExportOptions {
+            topics,
+            range,
+            compression,
+            output,
+            jobs,
+            policy,
+            file_paths,
+        } => {
+            let exports = export_paths(file_paths, output)?;
+            run_batch(
+                &exports,
+                |(file_path, _)| file_path,
+                jobs,
+                |(file_path, output), writer| {
+                    let query = range.select(topics.query(file_path)?);
+                    let bag = DecompressedBag::from_file_filtered(file_path, &query)?;
+                    let output = policy.create(output)?;
+                    let mut mcap = McapWriter::new(output)?.with_compression(compression);
+                    let count = mcap.copy_messages(&bag, &query)?;
+                    let output = mcap.finish()?;
+                    writer.write_all(
+                        format!("{count} messages, {}\n", commit_output(output)?).as_bytes(),
+                    )?;
+                    Ok(())
+                },
+                &mut writer,
+            )
+        }
+        Opts::ImportOptions {
+            compression,
+            msg_dirs,
+            output,
+            policy,
+            inputs,
+        } => {
+            #[cfg(feature = "ros2")]
+            let sources = {
+                let mut sources = MessageSources::new();
+                for dir in msg_dirs {
+                    sources.add_dir(dir)?;
+                }
+                sources
+            };
+            #[cfg(not(feature = "ros2"))]
+            if !msg_dirs.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--msg-dir needs frost built with the ros2 feature",
+                )
+                .into());
+            }
+            let bag_writer = BagWriter::new(policy.create(output)?)?.with_compression(compression);
+            #[cfg(feature = "ros2")]
+            let mut importer = Importer::new(bag_writer).with_message_sources(sources);
+            #[cfg(not(feature = "ros2"))]
+            let mut importer = Importer::new(bag_writer);
+            for input in import_files(inputs)? {
+                match input.extension().and_then(|extension| extension.to_str()) {
+                    Some("mcap") => importer.import_mcap(McapReader::open(&input)?)?,
+                    Some("pcap" | "pcapng" | "cap") => {
+                        importer.import_pcap(PcapReader::open(&input)?)?
+                    }
+                    #[cfg(feature = "rosbag2")]
+                    Some("db3") => importer.import_db3(&input)?,
+                    #[cfg(not(feature = "rosbag2"))]
+                    Some("db3") => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                            "{}: importing .db3 files needs frost built with the rosbag2 feature",
+                            input.display()
+                        ),
+                        )
+                        .into())
+                    }
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                                "{}: expected an .mcap, .db3, or .pcap file",
+                                input.display()
+                            ),
+                        )
+                        .into())
+                    }
+                }
+            }
+            let (output, report) = importer.finish()?;
+            writer.write_all(
+                format!(
+                    "imported {} messages, {}\n",
+                    report.message_count,
+                    commit_output(output)?
+                )
+                .as_bytes(),
+            )?;
+            for (topic, skipped) in report.skipped {
+                writer.write_all(
+                    format!(
+                        "skipped {} messages on {topic}: {}\n",
+                        skipped.message_count, skipped.reason
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            for warning in report.warnings {
+                eprintln!("warning: {warning}");
+            }
+            Ok(())
+        }
+        Opts::TrajectoryOptions {
+            topic,
+            child_frame,
+            stop_speed,
+            stop_time,
+            format,
+            output,
+            relative_time,
+            file_path,
+        } => {
+            let bag = DecompressedBag::from_file(&file_path)?;
+            check_topic(&bag.metadata, &topic, &file_path)?;
+            let trajectory = bag.trajectory(&topic, child_frame.as_deref())?;
+            let stops = trajectory.stops(stop_speed, Duration::from_secs_f64(stop_time));
+            let times = TimeDisplay::new(relative_time, &bag.metadata);
+            print_trajectory(&trajectory, &stops, times, &mut writer)?;
+            if let Some(output) = output {
+                let contents = match format {
+                    TrajectoryFormat::Csv => trajectory.to_csv(),
+                    TrajectoryFormat::GeoJson => trajectory.to_geojson(&stops),
+                };
+                std::fs::write(output, contents)?;
+            }
+            Ok(())
+        }
+        Opts::PluginsOptions => print_plugins(&plugins::discover(), &mut writer),
+        Opts::CapabilitiesOptions { format } => {
+            print_capabilities(&frost::capabilities(), format, &mut writer)
+        }
     }
 }
@@ -2,26 +2,70 @@ use std::error;
 use std::fmt;
 use std::io;
 
+use crate::time::Time;
+
 #[derive(Debug)]
 pub struct Error {
     kind: ErrorKind,
+    context: Option<Box<MessageContext>>,
 }
 
 impl Error {
     pub(crate) fn new(kind: ErrorKind) -> Error {
-        Error { kind }
+        Error {
+            kind,
+            context: None,
+        }
     }
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+    /// The message being decoded when the error occurred, if any.
+    pub fn context(&self) -> Option<&MessageContext> {
+        self.context.as_deref()
+    }
+    pub(crate) fn with_context(mut self, context: MessageContext) -> Error {
+        self.context = Some(Box::new(context));
+        self
+    }
+}
+
+/// Locates a message that failed to [decode](crate::msgs::MessageView::decode) or
+/// [instantiate](crate::msgs::MessageView::instantiate) in its bag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageContext {
+    pub topic: String,
+    /// The type declared by the message's connection.
+    pub data_type: String,
+    /// The time at which the message was received.
+    pub time: Time,
+    /// The position of the message's chunk in the bag.
+    pub chunk_pos: u64,
+    /// The offset of the message's data, starting with its 4 byte length, in the decompressed chunk.
+    pub offset: usize,
+}
+
+impl fmt::Display for MessageContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} message on {} at {}, at byte {} of the chunk at byte {}",
+            self.data_type, self.topic, self.time, self.offset, self.chunk_pos
+        )
+    }
 }
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
     NotARosbag,
     Deserialization(serde_rosmsg::Error),
     Decompression(lz4_flex::block::DecompressError),
     Io(io::Error),
     Parse(ParseError),
+    Encryption(EncryptionError),
+    Decode(DecodeError),
+    /// The operation was stopped with a [CancellationToken](crate::cancel::CancellationToken).
+    Cancelled,
 }
 
 impl fmt::Display for Error {
@@ -34,6 +78,13 @@ impl fmt::Display for Error {
             ErrorKind::Deserialization(ref e) => e.fmt(f),
             ErrorKind::Decompression(ref e) => e.fmt(f),
             ErrorKind::Parse(ref e) => e.fmt(f),
+            ErrorKind::Encryption(ref e) => e.fmt(f),
+            ErrorKind::Decode(ref e) => e.fmt(f),
+            ErrorKind::Cancelled => write!(f, "cancelled"),
+        }?;
+        match &self.context {
+            Some(context) => write!(f, " (in the {context})"),
+            None => Ok(()),
         }
     }
 }
@@ -42,33 +93,37 @@ impl error::Error for Error {}
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Error {
-        Error {
-            kind: ErrorKind::Io(e),
-        }
+        Error::new(ErrorKind::Io(e))
     }
 }
 
 impl From<serde_rosmsg::Error> for Error {
     fn from(e: serde_rosmsg::Error) -> Error {
-        Error {
-            kind: ErrorKind::Deserialization(e),
-        }
+        Error::new(ErrorKind::Deserialization(e))
     }
 }
 
 impl From<lz4_flex::block::DecompressError> for Error {
     fn from(e: lz4_flex::block::DecompressError) -> Error {
-        Error {
-            kind: ErrorKind::Decompression(e),
-        }
+        Error::new(ErrorKind::Decompression(e))
     }
 }
 
 impl From<ParseError> for Error {
     fn from(e: ParseError) -> Error {
-        Error {
-            kind: ErrorKind::Parse(e),
-        }
+        Error::new(ErrorKind::Parse(e))
+    }
+}
+
+impl From<EncryptionError> for Error {
+    fn from(e: EncryptionError) -> Error {
+        Error::new(ErrorKind::Encryption(e))
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Error {
+        Error::new(ErrorKind::Decode(e))
     }
 }
 
@@ -94,3 +149,80 @@ impl std::fmt::Display for ParseError {
 }
 
 impl std::error::Error for ParseError {}
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// The chunk was encrypted with an algorithm this build of frost can't decrypt.
+    UnsupportedAlgorithm(String),
+    /// No key was supplied for the chunk's key id.
+    MissingKey(String),
+    /// The key is wrong or the chunk has been modified.
+    Authentication,
+}
+
+impl std::fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncryptionError::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "unsupported chunk encryption: {algorithm}")
+            }
+            EncryptionError::MissingKey(key_id) => {
+                write!(
+                    f,
+                    "no key supplied for encrypted chunks with key id '{key_id}'"
+                )
+            }
+            EncryptionError::Authentication => {
+                write!(
+                    f,
+                    "failed to decrypt chunk, the key is wrong or the chunk was modified"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// Errors from decoding or encoding messages with [crate::dynamic], or converting decoded messages into other types.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// A message definition could not be parsed.
+    InvalidDefinition(String),
+    /// A field refers to a message type whose definition is missing.
+    UnknownType(String),
+    /// The message ended before all of its fields were read.
+    UnexpectedEof,
+    /// A field needed for a conversion is missing, has the wrong type, or is inconsistent with other fields.
+    InvalidField(String),
+    /// A message's type or encoding, such as an image's pixel format, is not supported by a conversion.
+    UnsupportedEncoding(String),
+    /// A length in a message is over a [DecodeLimits](crate::dynamic::DecodeLimits) limit.
+    LimitExceeded(String),
+    /// A message is longer than its definition, leaving this many bytes after its last field.
+    TrailingBytes(usize),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidDefinition(reason) => {
+                write!(f, "invalid message definition: {reason}")
+            }
+            DecodeError::UnknownType(data_type) => {
+                write!(f, "missing definition for message type {data_type}")
+            }
+            DecodeError::UnexpectedEof => write!(f, "message is shorter than its definition"),
+            DecodeError::InvalidField(reason) => write!(f, "invalid field: {reason}"),
+            DecodeError::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported encoding: {encoding}")
+            }
+            DecodeError::LimitExceeded(reason) => write!(f, "decode limit exceeded: {reason}"),
+            DecodeError::TrailingBytes(len) => {
+                write!(f, "message is longer than its definition, by {len} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
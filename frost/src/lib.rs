@@ -1,10 +1,13 @@
 #![allow(dead_code)]
 
+// lets generated message code, which refers to `frost::`, be compiled inside this crate
+extern crate self as frost;
+
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::fs::File;
-use std::io::{self, prelude::*, BufReader, Cursor};
+use std::io::{self, prelude::*, Cursor};
+use std::ops::{Range, RangeBounds};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 type ConnectionID = u32;
 type ChunkHeaderLoc = u64;
@@ -12,14 +15,99 @@ type ChunkHeaderLoc = u64;
 use errors::{Error, ErrorKind, ParseError};
 
 use itertools::Itertools;
+pub use util::accounting;
+pub use util::batch;
+#[cfg(feature = "cache")]
+pub use util::cache;
+pub use util::cancel;
+pub use util::chunk_analysis;
+pub use util::clock;
+pub use util::compression;
+pub use util::crop;
+pub use util::crypto;
+pub use util::decoders;
+pub use util::dedup;
+pub use util::diagnostics;
+#[cfg(feature = "zstd")]
+pub use util::dictionary;
+pub use util::dynamic;
+pub use util::echo;
+pub use util::edit;
+pub use util::etl;
+pub use util::explain;
+pub use util::export;
+pub use util::fanout;
+pub use util::health;
+#[cfg(feature = "images")]
+pub use util::images;
+pub use util::import;
+pub use util::layout;
+#[cfg(feature = "manifest")]
+pub use util::manifest;
+pub use util::maps;
+pub use util::mcap;
+pub use util::merge;
+pub use util::metrics;
+#[cfg(feature = "mount")]
+pub use util::mount;
 pub use util::msgs;
+pub use util::ordering;
+pub use util::output;
 use util::parsing::get_lengthed_bytes;
+pub use util::pcap;
+pub use util::plugins;
 pub use util::query;
+#[cfg(feature = "ros2")]
+pub use util::ros2;
+pub use util::rosout;
+pub use util::scan;
+pub use util::schemas;
+pub use util::skew;
+pub use util::storage;
+pub use util::summary;
+pub use util::tail;
 pub use util::time;
+pub use util::topic_search;
+pub use util::topic_tree;
+pub use util::trajectory;
+pub use util::triage;
+
+/// Implements [msgs::Msg] for hand-written message structs, with the `derive` feature.
+///
+/// Example
+/// ```rust
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use frost::query::Query;
+/// use frost::DecompressedBag;
+///
+/// #[derive(serde::Deserialize, frost::Msg)]
+/// #[ros(type = "std_msgs/String", md5sum = "992ce8a1687cec8c8bd883ec73ca41d1")]
+/// struct Chatter {
+///     data: String,
+/// }
+///
+/// let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+/// let query = Query::new().with_topics(["/chatter"]);
+/// for msg_view in bag.read_messages(&query).unwrap() {
+///     assert!(msg_view.instantiate::<Chatter>().unwrap().data.starts_with("foo_"));
+/// }
+/// # }
+/// ```
+#[cfg(feature = "derive")]
+pub use frost_derive::Msg;
 
 pub mod errors;
+pub mod prelude;
+pub mod stable;
 mod util;
-use util::query::{BagIter, Query};
+pub mod writer;
+use util::cancel::CancellationToken;
+use util::compression::ChunkContext;
+use util::crypto::{ChunkEncryption, EncryptionKey};
+use util::msgs::MessageView;
+use util::query::{BagIter, FilteredIter, Query, QueryEstimate};
+use util::storage::{BagStorage, FileStorage, StorageReader};
 use util::time::Time;
 
 /// Metadata about a bag.
@@ -46,9 +134,11 @@ pub struct BagMetadata {
     #[doc(hidden)]
     /// likely to be made crate private soon
     pub connection_data: BTreeMap<ConnectionID, ConnectionData>,
+    /// sorted by receive time within each connection
     pub(crate) index_data: BTreeMap<ConnectionID, Vec<IndexData>>,
     /// The number of bytes seen on-disk when using [BagMetadata::from_file] or the length of the slice passed into [BagMetadata::from_bytes].
     pub num_bytes: u64,
+    pub(crate) warnings: Vec<BagWarning>,
 }
 
 /// Represents an owned and decompresed Bag in memory.
@@ -57,13 +147,144 @@ pub struct DecompressedBag {
     pub(crate) chunk_bytes: BTreeMap<ChunkHeaderLoc, Vec<u8>>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 /// Statistics about a type of compression used in a bag.
 pub struct CompressionInfo {
     pub name: String,
     pub chunk_count: usize,
-    pub total_compressed: usize,
-    pub total_uncompressed: usize,
+    pub total_compressed: u64,
+    pub total_uncompressed: u64,
+}
+
+/// A problem in a bag which frost worked around while parsing it, see [BagMetadata::warnings].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BagWarning {
+    /// Connection records with different topics or types reused `connection_id`.
+    /// The later definition was given the unused `synthetic_id` so both are kept.
+    ///
+    /// [BagMetadata] cannot tell which messages were recorded on which definition without reading chunks,
+    /// so it counts them all against `connection_id`. A [DecompressedBag] attributes each message to the
+    /// definition most recently recorded before it, which is right for ids recycled after a connection closes.
+    DuplicateConnectionId {
+        connection_id: u32,
+        synthetic_id: u32,
+        topic: String,
+    },
+    /// The topic or type of a connection isn't valid UTF-8, like the Latin-1 names some vendor recorders write.
+    /// [ConnectionData::topic] and [ConnectionData::data_type] replace the invalid bytes with U+FFFD, and
+    /// [ConnectionData::raw_topic] and [ConnectionData::raw_data_type] return them as recorded.
+    NonUtf8Name { connection_id: u32, topic: String },
+}
+
+impl std::fmt::Display for BagWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BagWarning::DuplicateConnectionId {
+                connection_id,
+                synthetic_id,
+                topic,
+            } => write!(
+                f,
+                "connection id {connection_id} is reused by {topic}, which was given id {synthetic_id}"
+            ),
+            BagWarning::NonUtf8Name {
+                connection_id,
+                topic,
+            } => write!(
+                f,
+                "connection {connection_id} on {topic} has a topic or type which isn't valid UTF-8"
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+/// Describes what this build of frost is able to read, see [capabilities].
+pub struct Capabilities {
+    /// The version of the frost crate.
+    pub version: &'static str,
+    /// Formats which can be read: ROS 1 bags directly, and the rest by converting them with [import].
+    pub formats: Vec<&'static str>,
+    /// Chunk compressions which [DecompressedBag] can load, including any [compression::register]ed ones.
+    /// [BagMetadata] does not decompress chunks, so it supports bags with any compression.
+    pub compressions: Vec<&'static str>,
+    /// Optional cargo features compiled into this build.
+    pub features: Vec<&'static str>,
+}
+
+/// Returns the formats, compressions, and cargo features supported by this build of frost,
+/// so callers can check if a bag can be decoded before trying to.
+pub fn capabilities() -> Capabilities {
+    let mut features = Vec::new();
+    if cfg!(feature = "color") {
+        features.push("color");
+    }
+    if cfg!(feature = "std-msgs") {
+        features.push("std-msgs");
+    }
+    if cfg!(feature = "encryption") {
+        features.push("encryption");
+    }
+    if cfg!(feature = "zstd") {
+        features.push("zstd");
+    }
+    if cfg!(feature = "mount") {
+        features.push("mount");
+    }
+    if cfg!(feature = "mmap") {
+        features.push("mmap");
+    }
+    if cfg!(feature = "images") {
+        features.push("images");
+    }
+    if cfg!(feature = "rosbag2") {
+        features.push("rosbag2");
+    }
+    if cfg!(feature = "derive") {
+        features.push("derive");
+    }
+    if cfg!(feature = "bz2") {
+        features.push("bz2");
+    }
+    if cfg!(feature = "cache") {
+        features.push("cache");
+    }
+    if cfg!(feature = "manifest") {
+        features.push("manifest");
+    }
+    if cfg!(feature = "md5") {
+        features.push("md5");
+    }
+    if cfg!(feature = "ros2") {
+        features.push("ros2");
+    }
+    if cfg!(feature = "cbor") {
+        features.push("cbor");
+    }
+    if cfg!(feature = "msgpack") {
+        features.push("msgpack");
+    }
+    if cfg!(feature = "yaml") {
+        features.push("yaml");
+    }
+    if cfg!(feature = "regex") {
+        features.push("regex");
+    }
+
+    let mut formats = vec!["ROSBAG V2.0", "MCAP", "pcap", "pcapng"];
+    if cfg!(feature = "ros2") {
+        formats.push("rosbag2 mcap");
+    }
+    if cfg!(feature = "rosbag2") {
+        formats.push("rosbag2 sqlite3");
+    }
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        formats,
+        compressions: compression::names(),
+        features,
+    }
 }
 
 #[derive(Debug)]
@@ -195,11 +416,14 @@ impl BagHeader {
     }
 }
 
-/// Struct to store everything about a Chunk
+/// Everything the bag's records say about a chunk, from [BagMetadata::chunks].
 ///
 /// As ChunkHeader and ChunkInfoHeaders are separate, after parsing all records, combine that info into a Chunk
-struct ChunkMetadata {
+pub struct ChunkMetadata {
     compression: String,
+    /// id of the zstd dictionary the chunk was compressed with
+    dict_id: Option<String>,
+    encryption: Option<ChunkEncryption>,
     uncompressed_size: u32,
     compressed_size: u32,
     chunk_header_pos: u64,
@@ -210,8 +434,45 @@ struct ChunkMetadata {
     message_counts: BTreeMap<ConnectionID, u32>,
 }
 
+impl ChunkMetadata {
+    /// The position of the chunk's record in the bag, which identifies it.
+    pub fn position(&self) -> u64 {
+        self.chunk_header_pos
+    }
+
+    pub fn compression(&self) -> &str {
+        &self.compression
+    }
+
+    /// The size of the chunk's data as stored in the bag.
+    pub fn compressed_size(&self) -> u32 {
+        self.compressed_size
+    }
+
+    pub fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size
+    }
+
+    /// The receive time of the chunk's earliest message.
+    pub fn start_time(&self) -> Time {
+        self.start_time
+    }
+
+    /// The receive time of the chunk's latest message.
+    pub fn end_time(&self) -> Time {
+        self.end_time
+    }
+
+    /// The number of messages in the chunk on each connection id with any, from the bag's chunk info records.
+    pub fn message_counts(&self) -> &BTreeMap<u32, u32> {
+        &self.message_counts
+    }
+}
+
 struct ChunkHeader {
     compression: String,
+    dict_id: Option<String>,
+    encryption: Option<ChunkEncryption>,
     uncompressed_size: u32,
     compressed_size: u32,
     chunk_header_pos: u64,
@@ -229,6 +490,9 @@ impl ChunkHeader {
 
         let mut compression = None;
         let mut size = None;
+        let mut encryption = None;
+        let mut key_id = None;
+        let mut dict_id = None;
 
         loop {
             let (new_index, name, value) = parse_field(buf, i)?;
@@ -237,6 +501,9 @@ impl ChunkHeader {
             match name {
                 b"compression" => compression = Some(String::from_utf8_lossy(value).to_string()),
                 b"size" => size = Some(util::parsing::parse_le_u32(value)?),
+                b"encryption" => encryption = Some(String::from_utf8_lossy(value).to_string()),
+                b"key_id" => key_id = Some(String::from_utf8_lossy(value).to_string()),
+                b"dict_id" => dict_id = Some(String::from_utf8_lossy(value).to_string()),
                 b"op" => {
                     let op = util::parsing::parse_u8(value)?;
                     if op != OpCode::ChunkHeader as u8 {
@@ -258,7 +525,20 @@ impl ChunkHeader {
             }
         }
 
+        let encryption = match encryption {
+            Some(algorithm) => Some(ChunkEncryption {
+                algorithm,
+                key_id: key_id.ok_or_else(|| {
+                    eprintln!("missing key_id when parsing an encrypted ChunkHeader");
+                    ParseError::MissingField
+                })?,
+            }),
+            None => None,
+        };
+
         Ok(ChunkHeader {
+            encryption,
+            dict_id,
             compression: compression.ok_or_else(|| {
                 eprintln!("missing compression when parsing a ChunkHeader");
                 ParseError::MissingField
@@ -302,8 +582,8 @@ impl ChunkInfoHeader {
             match name {
                 b"ver" => version = Some(util::parsing::parse_le_u32(value)?),
                 b"chunk_pos" => chunk_header_pos = Some(util::parsing::parse_le_u64(value)?),
-                b"start_time" => start_time = Some(Time::from(value)?),
-                b"end_time" => end_time = Some(Time::from(value)?),
+                b"start_time" => start_time = Some(Time::from_le_bytes(value)?),
+                b"end_time" => end_time = Some(Time::from_le_bytes(value)?),
                 b"count" => connection_count = Some(util::parsing::parse_le_u32(value)?),
                 b"op" => {
                     let op = util::parsing::parse_u8(value)?;
@@ -422,7 +702,7 @@ impl ConnectionHeader {
 }
 
 #[doc(hidden)] // likey to be made crate private
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 ///Store metadata for connections, including topic, conn id, md5, etc.
 pub struct ConnectionData {
     pub connection_id: u32,
@@ -432,9 +712,41 @@ pub struct ConnectionData {
     pub message_definition: String,
     pub caller_id: Option<String>,
     pub latching: bool,
+    /// Every field of the connection header as recorded, in order, including ones frost has no use for.
+    /// [BagWriter](crate::writer::BagWriter) writes these back as they were, with the fields above substituted in,
+    /// so copied connections replay like the originals. Empty for connections made in code.
+    pub header_fields: Vec<(String, Vec<u8>)>,
 }
 
 impl ConnectionData {
+    /// The topic as recorded, which may not be valid UTF-8 unlike [topic](ConnectionData::topic), see
+    /// [BagWarning::NonUtf8Name].
+    pub fn raw_topic(&self) -> &[u8] {
+        self.recorded_field("topic", &self.topic)
+    }
+
+    /// The type as recorded, which may not be valid UTF-8 unlike [data_type](ConnectionData::data_type), see
+    /// [BagWarning::NonUtf8Name].
+    pub fn raw_data_type(&self) -> &[u8] {
+        self.recorded_field("type", &self.data_type)
+    }
+
+    /// True if the recorded topic or type isn't valid UTF-8.
+    pub fn has_non_utf8_name(&self) -> bool {
+        std::str::from_utf8(self.raw_topic()).is_err()
+            || std::str::from_utf8(self.raw_data_type()).is_err()
+    }
+
+    /// The recorded bytes of the header field `name`, unless `value` was changed since it was read from them.
+    fn recorded_field<'a>(&'a self, name: &str, value: &'a str) -> &'a [u8] {
+        self.header_fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, bytes)| bytes.as_slice())
+            .filter(|bytes| String::from_utf8_lossy(bytes) == value)
+            .unwrap_or(value.as_bytes())
+    }
+
     fn from(buf: &[u8], connection_id: u32, topic: String) -> Result<ConnectionData, ParseError> {
         let mut i = 0;
 
@@ -443,10 +755,12 @@ impl ConnectionData {
         let mut message_definition = None;
         let mut caller_id = None;
         let mut latching = false;
+        let mut header_fields = Vec::new();
 
         loop {
             let (new_index, name, value) = parse_field(buf, i)?;
             i = new_index;
+            header_fields.push((String::from_utf8_lossy(name).to_string(), value.to_vec()));
 
             match name {
                 b"topic" => (),
@@ -457,13 +771,7 @@ impl ConnectionData {
                 }
                 b"callerid" => caller_id = Some(String::from_utf8_lossy(value).to_string()),
                 b"latching" => latching = value == b"1",
-                other => {
-                    eprintln!(
-                        "unexpected field: {} in ConnectionData",
-                        String::from_utf8_lossy(other)
-                    );
-                    return Err(ParseError::UnexpectedField);
-                }
+                _ => (),
             }
 
             if i >= buf.len() {
@@ -488,6 +796,7 @@ impl ConnectionData {
             })?,
             caller_id,
             latching,
+            header_fields,
         })
     }
 }
@@ -560,8 +869,8 @@ pub(crate) struct IndexData {
     chunk_header_pos: ChunkHeaderLoc,
     ///time at which the message was received
     time: Time,
-    ///offset of message data record in uncompressed chunk data   
-    offset: usize,
+    ///offset of message data record in uncompressed chunk data, which rosbag stores as a u32 unlike file positions
+    offset: u32,
 }
 
 impl IndexData {
@@ -572,8 +881,8 @@ impl IndexData {
     ) -> Result<IndexData, ParseError> {
         Ok(IndexData {
             chunk_header_pos,
-            time: Time::from(buf)?,
-            offset: util::parsing::parse_le_u32_at(buf, 8)? as usize,
+            time: Time::from_le_bytes(buf)?,
+            offset: util::parsing::parse_le_u32_at(buf, 8)?,
             conn_id,
         })
     }
@@ -600,7 +909,7 @@ impl MessageDataHeader {
 
             match name {
                 b"conn" => conn = Some(util::parsing::parse_le_u32(value)?),
-                b"time" => time = Some(Time::from(value)?),
+                b"time" => time = Some(Time::from_le_bytes(value)?),
                 b"op" => {
                     let op = util::parsing::parse_u8(value)?;
                     if op != OpCode::MessageData as u8 {
@@ -641,30 +950,32 @@ impl BagMetadata {
     where
         P: AsRef<Path> + Into<PathBuf>,
     {
-        let path: PathBuf = file_path.as_ref().into();
-        let file = File::open(file_path)?;
-        let file_size = file.metadata()?.len();
-
-        let reader = BufReader::new(file);
-
-        let mut bag = Self::from_reader(reader)?;
-        bag.file_path = Some(path);
-        bag.num_bytes = file_size;
-        Ok(bag)
+        Self::from_storage(&FileStorage::open(file_path)?)
     }
 
     /// Read bag metadata from an existing byte slice.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
-        let reader = Cursor::new(bytes);
+        Self::from_storage(&bytes)
+    }
+
+    /// Read bag metadata from any [BagStorage], reading only record headers and skipping over chunk data.
+    pub fn from_storage(storage: &dyn BagStorage) -> Result<Self, Error> {
+        let reader = StorageReader::new(storage)?;
         let mut bag = Self::from_reader(reader)?;
-        bag.num_bytes = bytes.len() as u64;
+        bag.file_path = storage.path().map(Path::to_path_buf);
+        bag.num_bytes = storage.size()?;
         Ok(bag)
     }
 
-    fn from_reader<R: Read + Seek>(mut reader: R) -> Result<BagMetadata, Error> {
+    fn from_reader<R: Read + Seek>(reader: R) -> Result<BagMetadata, Error> {
+        let mut reader = metrics::CountingReader::new(reader);
         let version = version_check(&mut reader)?;
 
-        let (chunk_metadata, connection_data, index_data) = parse_records(&mut reader)?;
+        let (chunk_metadata, connection_data, index_data, warnings) = parse_records(&mut reader)?;
+
+        if let Some(recorder) = metrics::recorder() {
+            recorder.bytes_read(reader.count);
+        }
 
         Ok(BagMetadata {
             version,
@@ -673,6 +984,7 @@ impl BagMetadata {
             connection_data,
             index_data,
             num_bytes: 0,
+            warnings,
         })
     }
 
@@ -712,13 +1024,172 @@ impl BagMetadata {
     pub fn duration(&self) -> Duration {
         let start = self.start_time().unwrap_or(time::ZERO);
         let end = self.end_time().unwrap_or(time::ZERO);
-        end.dur(&start)
+        end.duration_since(&start)
     }
 
     pub fn message_count(&self) -> usize {
         self.index_data.values().map(|v| v.len()).sum()
     }
 
+    /// Returns the number of messages matching a query, computed from the index alone.
+    ///
+    /// This doesn't need the bag's chunks to be loaded, so it can size a query's results before running it.
+    /// Filters on message contents, like [Query::with_predicate], are ignored, so this is at most an upper bound.
+    pub fn count_messages(&self, query: &Query) -> usize {
+        query.matching_index_data(self).count()
+    }
+
+    /// Estimates how many messages a query matches and how much chunk data has to be read to yield them,
+    /// computed from the index alone, ignoring filters on message contents like [Query::with_predicate].
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::query::Query;
+    /// use frost::BagMetadata;
+    ///
+    /// let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    /// let estimate = metadata.estimate(&Query::new().with_topics(["/chatter"]));
+    /// assert_eq!(estimate.message_count, 100);
+    /// assert_eq!(estimate.chunk_count, 1);
+    /// ```
+    pub fn estimate(&self, query: &Query) -> QueryEstimate {
+        let mut message_count = 0;
+        let mut chunks = HashSet::new();
+        for data in query.matching_index_data(self) {
+            message_count += 1;
+            chunks.insert(data.chunk_header_pos);
+        }
+
+        let mut estimate = QueryEstimate {
+            message_count,
+            chunk_count: chunks.len(),
+            ..Default::default()
+        };
+        for chunk in chunks.iter().filter_map(|pos| self.chunk_metadata.get(pos)) {
+            estimate.compressed_bytes += u64::from(chunk.compressed_size);
+            estimate.uncompressed_bytes += u64::from(chunk.uncompressed_size);
+        }
+        estimate
+    }
+
+    /// Returns the byte ranges of the bag needed to run a query, sorted and merged: every record except chunk data,
+    /// which parsing the bag's metadata reads, and the data of the chunks holding matching messages.
+    ///
+    /// With metadata fetched from a remote bag (e.g. through a [ByteSource](storage::ByteSource) making range
+    /// requests), data platforms can download just these ranges, writing them at their offsets in a local file, before
+    /// running the query.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::query::Query;
+    /// use frost::BagMetadata;
+    ///
+    /// let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    /// assert_eq!(metadata.byte_ranges(&Query::all()), [0..metadata.num_bytes]);
+    ///
+    /// // without matching messages, only the metadata is needed
+    /// let ranges = metadata.byte_ranges(&Query::new().with_topics(["/missing"]));
+    /// assert_eq!(ranges.len(), 2);
+    /// ```
+    pub fn byte_ranges(&self, query: &Query) -> Vec<Range<u64>> {
+        let chunks: HashSet<ChunkHeaderLoc> = query
+            .matching_index_data(self)
+            .map(|data| data.chunk_header_pos)
+            .collect();
+
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for (chunk_header_pos, chunk) in self.chunk_metadata.iter() {
+            if chunks.contains(chunk_header_pos) {
+                continue;
+            }
+            if start < chunk.chunk_data_pos {
+                ranges.push(start..chunk.chunk_data_pos);
+            }
+            start = chunk.chunk_data_pos + u64::from(chunk.compressed_size);
+        }
+        if start < self.num_bytes {
+            ranges.push(start..self.num_bytes);
+        }
+        ranges
+    }
+
+    /// Problems in the bag which were worked around while parsing it.
+    pub fn warnings(&self) -> &[BagWarning] {
+        &self.warnings
+    }
+
+    /// Returns the bag's chunks in the order they were written.
+    pub fn chunks(&self) -> impl Iterator<Item = &ChunkMetadata> {
+        self.chunk_metadata.values()
+    }
+
+    /// Returns the chunks holding messages on `topic`, in the order they were written, from their message counts.
+    ///
+    /// Together with the chunks' times, this lets query planning skip the chunks without any requested connections.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::BagMetadata;
+    ///
+    /// let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    /// assert_eq!(metadata.chunks_for_topic("/chatter").len(), 1);
+    /// assert!(metadata.chunks_for_topic("/missing").is_empty());
+    /// ```
+    pub fn chunks_for_topic(&self, topic: &str) -> Vec<&ChunkMetadata> {
+        let mut ids: HashSet<ConnectionID> = self
+            .connection_data
+            .values()
+            .filter(|data| data.topic == topic)
+            .map(|data| data.connection_id)
+            .collect();
+        // chunk infos count messages on reused connection ids against the original id
+        for warning in self.warnings.iter() {
+            let BagWarning::DuplicateConnectionId {
+                connection_id,
+                synthetic_id,
+                ..
+            } = warning
+            else {
+                continue;
+            };
+            if ids.contains(synthetic_id) {
+                ids.insert(*connection_id);
+            }
+        }
+        self.chunk_metadata
+            .values()
+            .filter(|chunk| {
+                chunk
+                    .message_counts
+                    .iter()
+                    .any(|(id, count)| *count > 0 && ids.contains(id))
+            })
+            .collect()
+    }
+
+    /// Returns the number of chunks in the bag.
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_metadata.len()
+    }
+
+    /// Returns the number of connections in the bag. A topic may be recorded over multiple connections.
+    pub fn connection_count(&self) -> usize {
+        self.connection_data.len()
+    }
+
+    /// Returns true if the bag does not contain any messages.
+    pub fn is_empty(&self) -> bool {
+        self.index_data.values().all(|v| v.is_empty())
+    }
+
+    /// Returns true if any connection in the bag is on `topic`.
+    pub fn topic_exists(&self, topic: &str) -> bool {
+        self.connection_data
+            .values()
+            .any(|data| data.topic == topic)
+    }
+
     pub fn topic_message_counts(&self) -> BTreeMap<String, usize> {
         let topic_to_ids = self.topic_to_connection_ids();
         topic_to_ids
@@ -749,8 +1220,8 @@ impl BagMetadata {
                         chunk_count: 0,
                     });
             info.chunk_count += 1;
-            info.total_compressed += metadata.compressed_size as usize;
-            info.total_uncompressed += metadata.uncompressed_size as usize;
+            info.total_compressed += u64::from(metadata.compressed_size);
+            info.total_uncompressed += u64::from(metadata.uncompressed_size);
         }
 
         acc.into_values()
@@ -758,6 +1229,25 @@ impl BagMetadata {
             .collect()
     }
 
+    /// Returns the sum of the bag's uncompressed chunk sizes, which is about how much memory a [DecompressedBag] of it takes.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.chunk_metadata
+            .values()
+            .map(|metadata| u64::from(metadata.uncompressed_size))
+            .sum()
+    }
+
+    /// Returns the ids of the keys needed to decrypt the bag's encrypted chunks, which is empty for unencrypted bags.
+    pub fn encryption_key_ids(&self) -> Vec<&str> {
+        self.chunk_metadata
+            .values()
+            .filter_map(|metadata| metadata.encryption.as_ref())
+            .map(|encryption| encryption.key_id.as_str())
+            .unique()
+            .sorted()
+            .collect()
+    }
+
     pub fn topics(&self) -> Vec<&str> {
         self.connection_data
             .values()
@@ -791,7 +1281,7 @@ fn parse_bag_header<R: Read + Seek>(
         return Err(ParseError::UnindexedBag);
     }
 
-    let data_len = read_le_u32(reader).ok_or_else(|| ParseError::UnexpectedEOF)?;
+    let data_len = read_le_u32(reader).ok_or(ParseError::UnexpectedEOF)?;
     // Skip bag header padding
     reader
         .seek(io::SeekFrom::Current(data_len as i64))
@@ -821,7 +1311,7 @@ fn parse_chunk<R: Read + Seek>(
     reader: &mut R,
     chunk_header_pos: u64,
 ) -> Result<ChunkHeader, ParseError> {
-    let data_len = read_le_u32(reader).ok_or_else(|| ParseError::UnexpectedEOF)?;
+    let data_len = read_le_u32(reader).ok_or(ParseError::UnexpectedEOF)?;
     let chunk_data_pos = reader.stream_position().unwrap();
 
     let chunk_header = ChunkHeader::from(header_buf, chunk_header_pos, chunk_data_pos, data_len)?;
@@ -875,16 +1365,14 @@ fn parse_index<R: Read + Seek>(
     Ok((index_data_header.connection_id, index_data))
 }
 
-fn parse_records<R: Read + Seek>(
-    reader: &mut R,
-) -> Result<
-    (
-        BTreeMap<ChunkHeaderLoc, ChunkMetadata>,
-        BTreeMap<ConnectionID, ConnectionData>,
-        BTreeMap<ConnectionID, Vec<IndexData>>,
-    ),
-    ParseError,
-> {
+type Records = (
+    BTreeMap<ChunkHeaderLoc, ChunkMetadata>,
+    BTreeMap<ConnectionID, ConnectionData>,
+    BTreeMap<ConnectionID, Vec<IndexData>>,
+    Vec<BagWarning>,
+);
+
+fn parse_records<R: Read + Seek>(reader: &mut R) -> Result<Records, ParseError> {
     let mut bag_header: Option<BagHeader> = None;
     let mut chunk_headers: Vec<ChunkHeader> = Vec::new();
     let mut chunk_infos: Vec<(ChunkInfoHeader, Vec<ChunkInfoData>)> = Vec::new();
@@ -893,11 +1381,7 @@ fn parse_records<R: Read + Seek>(
 
     let mut last_chunk_header_pos = None;
 
-    loop {
-        let Some(header_len) = read_le_u32(reader) else {
-            break;
-        };
-
+    while let Some(header_len) = read_le_u32(reader) {
         // TODO: benchmark and compare reading into a map or stack-local map crate
         let mut header_buf = vec![0u8; header_len as usize];
         reader.read_exact(&mut header_buf).map_err(|e| {
@@ -926,7 +1410,7 @@ fn parse_records<R: Read + Seek>(
                 let (connection_id, mut data) = parse_index(&header_buf, reader, chunk_header_pos)?;
                 index_data
                     .entry(connection_id)
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .append(&mut data);
             }
             OpCode::ConnectionHeader => {
@@ -982,6 +1466,8 @@ fn parse_records<R: Read + Seek>(
                 })
                 .map(|(chunk_info_header, chunk_data)| ChunkMetadata {
                     compression: chunk_header.compression,
+                    encryption: chunk_header.encryption,
+                    dict_id: chunk_header.dict_id,
                     uncompressed_size: chunk_header.uncompressed_size,
                     compressed_size: chunk_header.compressed_size,
                     chunk_header_pos: chunk_header.chunk_header_pos,
@@ -997,11 +1483,149 @@ fn parse_records<R: Read + Seek>(
         })
         .map(|metadata| (metadata.chunk_header_pos, metadata))
         .collect();
-    let connection_data: BTreeMap<ConnectionID, ConnectionData> = connections
-        .into_iter()
-        .map(|data| (data.connection_id, data))
-        .collect();
-    Ok((chunk_metadata, connection_data, index_data))
+    // chunks may be out of order, and a stable sort keeps messages received at the same time in order
+    for index in index_data.values_mut() {
+        index.sort_by_key(|data| data.time);
+    }
+    let (connection_data, mut warnings) = dedup_connections(connections);
+    warnings.extend(
+        connection_data
+            .values()
+            .filter(|data| data.has_non_utf8_name())
+            .map(|data| BagWarning::NonUtf8Name {
+                connection_id: data.connection_id,
+                topic: data.topic.clone(),
+            }),
+    );
+    Ok((chunk_metadata, connection_data, index_data, warnings))
+}
+
+/// Returns true if two connection records describe the same connection, ignoring fields such as the caller id.
+fn same_connection(a: &ConnectionData, b: &ConnectionData) -> bool {
+    a.raw_topic() == b.raw_topic() && a.raw_data_type() == b.raw_data_type() && a.md5sum == b.md5sum
+}
+
+/// Keys connections by id, giving connections which reuse the id of a different connection a synthetic id past the largest one.
+fn dedup_connections(
+    connections: Vec<ConnectionData>,
+) -> (BTreeMap<ConnectionID, ConnectionData>, Vec<BagWarning>) {
+    let mut next_id = connections
+        .iter()
+        .map(|data| data.connection_id)
+        .max()
+        .map_or(0, |id| id.saturating_add(1));
+    let mut connection_data: BTreeMap<ConnectionID, ConnectionData> = BTreeMap::new();
+    let mut warnings = Vec::new();
+
+    for mut data in connections {
+        let connection_id = data.connection_id;
+        let Some(existing) = connection_data.get(&connection_id) else {
+            connection_data.insert(connection_id, data);
+            continue;
+        };
+        let seen = same_connection(existing, &data)
+            || warnings.iter().any(|warning| match warning {
+                BagWarning::DuplicateConnectionId {
+                    connection_id: id,
+                    synthetic_id,
+                    ..
+                } => *id == connection_id && same_connection(&connection_data[synthetic_id], &data),
+                BagWarning::NonUtf8Name { .. } => false,
+            });
+        if seen {
+            continue;
+        }
+        data.connection_id = next_id;
+        next_id = next_id.saturating_add(1);
+        warnings.push(BagWarning::DuplicateConnectionId {
+            connection_id,
+            synthetic_id: data.connection_id,
+            topic: data.topic.clone(),
+        });
+        connection_data.insert(data.connection_id, data);
+    }
+    (connection_data, warnings)
+}
+
+/// Moves index entries of reused connection ids to the definition in effect where each message was recorded,
+/// found from the connection records which are written into chunks ahead of their messages.
+fn resolve_duplicate_connections(
+    metadata: &mut BagMetadata,
+    chunk_bytes: &BTreeMap<ChunkHeaderLoc, Vec<u8>>,
+) -> Result<(), ParseError> {
+    let mut candidates: HashMap<ConnectionID, Vec<ConnectionID>> = HashMap::new();
+    for warning in metadata.warnings.iter() {
+        let BagWarning::DuplicateConnectionId {
+            connection_id,
+            synthetic_id,
+            ..
+        } = warning
+        else {
+            continue;
+        };
+        candidates
+            .entry(*connection_id)
+            .or_insert_with(|| vec![*connection_id])
+            .push(*synthetic_id);
+    }
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    // where in the bag each reused id switches definition
+    let mut switches: HashMap<ConnectionID, BTreeMap<(ChunkHeaderLoc, u32), ConnectionID>> =
+        HashMap::new();
+    for (chunk_loc, bytes) in chunk_bytes.iter() {
+        let mut reader = Cursor::new(bytes.as_slice());
+        while let Some(header_len) = read_le_u32(&mut reader) {
+            let record_pos = reader.position() as u32 - 4;
+            let mut header_buf = vec![0u8; header_len as usize];
+            reader
+                .read_exact(&mut header_buf)
+                .map_err(|_| ParseError::UnexpectedEOF)?;
+            if !matches!(read_header_op(&header_buf)?, OpCode::ConnectionHeader) {
+                let data_len = read_le_u32(&mut reader).ok_or(ParseError::UnexpectedEOF)?;
+                reader.set_position(reader.position() + u64::from(data_len));
+                continue;
+            }
+            let data = parse_connection(&header_buf, &mut reader)?;
+            let Some(ids) = candidates.get(&data.connection_id) else {
+                continue;
+            };
+            if let Some(id) = ids
+                .iter()
+                .find(|id| same_connection(&metadata.connection_data[id], &data))
+            {
+                switches
+                    .entry(data.connection_id)
+                    .or_default()
+                    .insert((*chunk_loc, record_pos), *id);
+            }
+        }
+    }
+
+    for (connection_id, switches) in switches.iter() {
+        let Some(entries) = metadata.index_data.remove(connection_id) else {
+            continue;
+        };
+        for mut entry in entries {
+            if let Some((_, id)) = switches
+                .range(..(entry.chunk_header_pos, entry.offset))
+                .next_back()
+            {
+                entry.conn_id = *id;
+            }
+            metadata
+                .index_data
+                .entry(entry.conn_id)
+                .or_default()
+                .push(entry);
+        }
+    }
+    for index in metadata.index_data.values_mut() {
+        index.sort_by_key(|data| data.time);
+    }
+    Ok(())
 }
 
 #[inline(always)]
@@ -1027,22 +1651,57 @@ impl DecompressedBag {
     /// Creates a bag from a vector of bytes.
     /// This will copy the bytes even if it is a decompressed bag.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::load_bytes(bytes, &[], None)
+    }
+
+    /// Creates a bag from a vector of bytes like [DecompressedBag::from_bytes], failing with [ErrorKind::Cancelled]
+    /// once `cancellation` is cancelled, see [crate::cancel].
+    pub fn from_bytes_with_cancellation(
+        bytes: &[u8],
+        cancellation: &CancellationToken,
+    ) -> Result<Self, Error> {
+        Self::load_bytes(bytes, &[], Some(cancellation))
+    }
+
+    /// Creates a bag from a vector of bytes, decrypting encrypted chunks with the key matching their key id.
+    #[cfg(feature = "encryption")]
+    pub fn from_bytes_with_keys(bytes: &[u8], keys: &[EncryptionKey]) -> Result<Self, Error> {
+        Self::load_bytes(bytes, keys, None)
+    }
+
+    fn load_bytes(
+        bytes: &[u8],
+        keys: &[EncryptionKey],
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Self, Error> {
+        if let Some(cancellation) = cancellation {
+            cancellation.check()?;
+        }
+        if let Some(recorder) = metrics::recorder() {
+            recorder.bytes_read(bytes.len() as u64);
+        }
+
         let mut reader = Cursor::new(&bytes);
 
         let version: String = version_check(&mut reader)?;
-        let (chunk_metadata, connection_data, index_data) = parse_records(&mut reader)?;
+        let (chunk_metadata, connection_data, index_data, warnings) = parse_records(&mut reader)?;
 
-        let chunk_bytes = populate_chunk_bytes(&chunk_metadata, bytes)?;
+        let chunk_bytes =
+            populate_chunk_bytes(&chunk_metadata, &bytes, keys, cancellation, None, |_| true)?;
+
+        let mut metadata = BagMetadata {
+            version,
+            file_path: None,
+            chunk_metadata,
+            connection_data,
+            index_data,
+            num_bytes: bytes.len() as u64,
+            warnings,
+        };
+        resolve_duplicate_connections(&mut metadata, &chunk_bytes)?;
 
         Ok(DecompressedBag {
-            metadata: BagMetadata {
-                version,
-                file_path: None,
-                chunk_metadata,
-                connection_data,
-                index_data,
-                num_bytes: bytes.len() as u64,
-            },
+            metadata,
             chunk_bytes,
         })
     }
@@ -1051,62 +1710,474 @@ impl DecompressedBag {
     where
         P: AsRef<Path> + Into<PathBuf>,
     {
-        let path: PathBuf = file_path.as_ref().into();
-        let file = File::open(file_path)?;
+        Self::load_file(file_path, &[], None)
+    }
+
+    /// Reads a bag from a file path like [DecompressedBag::from_file], failing with [ErrorKind::Cancelled] once
+    /// `cancellation` is cancelled, see [crate::cancel].
+    pub fn from_file_with_cancellation<P>(
+        file_path: P,
+        cancellation: &CancellationToken,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        Self::load_file(file_path, &[], Some(cancellation))
+    }
+
+    /// Reads a bag from a file path, decrypting encrypted chunks with the key matching their key id.
+    #[cfg(feature = "encryption")]
+    pub fn from_file_with_keys<P>(file_path: P, keys: &[EncryptionKey]) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        Self::load_file(file_path, keys, None)
+    }
+
+    fn load_file<P>(
+        file_path: P,
+        keys: &[EncryptionKey],
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        Self::load_storage(&FileStorage::open(file_path)?, keys, cancellation)
+    }
+
+    /// Reads and decompresses a whole bag from any [BagStorage].
+    pub fn from_storage(storage: &dyn BagStorage) -> Result<Self, Error> {
+        Self::load_storage(storage, &[], None)
+    }
+
+    /// Reads a bag from a file path like [DecompressedBag::from_file], unless its chunks would decompress to more than
+    /// `max_bytes`, see [DecompressedBag::from_storage_with_limit].
+    pub fn from_file_with_limit<P>(file_path: P, max_bytes: u64) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        Self::from_storage_with_limit(&FileStorage::open(file_path)?, max_bytes)
+    }
+
+    /// Reads a bag from any [BagStorage], unless its chunks would decompress to more than `max_bytes`.
+    ///
+    /// Only record headers are read to check the limit, so an oversized bag fails fast with an
+    /// [io::ErrorKind::OutOfMemory] error instead of being read into memory.
+    pub fn from_storage_with_limit(
+        storage: &dyn BagStorage,
+        max_bytes: u64,
+    ) -> Result<Self, Error> {
+        let mut metadata = BagMetadata::from_storage(storage)?;
+        let size = metadata.uncompressed_size();
+        if size > max_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("bag decompresses to {size} bytes, over the limit of {max_bytes} bytes"),
+            )
+            .into());
+        }
+
+        // the records were parsed to check the size, so only the chunks are left to read
+        let chunk_bytes =
+            populate_chunk_bytes(&metadata.chunk_metadata, storage, &[], None, None, |_| true)?;
+        if let Some(recorder) = metrics::recorder() {
+            let read = metadata
+                .chunk_metadata
+                .values()
+                .map(|chunk| u64::from(chunk.compressed_size))
+                .sum();
+            recorder.bytes_read(read);
+        }
+        resolve_duplicate_connections(&mut metadata, &chunk_bytes)?;
+        Ok(DecompressedBag {
+            metadata,
+            chunk_bytes,
+        })
+    }
+
+    /// Reads a bag from a file path like [DecompressedBag::from_file], reading and decompressing only the chunks
+    /// holding messages which match `query`, see [DecompressedBag::from_storage_filtered].
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::query::Query;
+    /// use frost::DecompressedBag;
+    ///
+    /// let query = Query::new().with_topics(["/chatter"]);
+    /// let bag = DecompressedBag::from_file_filtered("./tests/fixtures/compressed_lz4.bag", &query).unwrap();
+    /// assert_eq!(bag.read_messages(&query).unwrap().count(), 100);
+    /// ```
+    pub fn from_file_filtered<P>(file_path: P, query: &Query) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        Self::from_storage_filtered(&FileStorage::open(file_path)?, query)
+    }
+
+    /// Reads a bag from any [BagStorage], reading and decompressing only the chunks holding messages which match
+    /// `query`, so e.g. reading `/imu` never decompresses chunks of camera images.
+    ///
+    /// Other messages in those chunks can still be read, but those in skipped chunks are no longer read or counted,
+    /// as with [DecompressedBag::drop_chunks].
+    ///
+    /// Loading fails with [ErrorKind::Cancelled] once the query is cancelled, see
+    /// [Query::with_cancellation]. Use [Query::all] to load every chunk of a bag which may be cancelled.
+    ///
+    /// The chunks decompressed and pruned are added to the query's [QueryStats](metrics::QueryStats), if it has any,
+    /// see [Query::with_stats].
+    pub fn from_storage_filtered(storage: &dyn BagStorage, query: &Query) -> Result<Self, Error> {
+        let started = Instant::now();
+        query.check_cancelled()?;
+        let mut metadata = BagMetadata::from_storage(storage)?;
+        let mut chunks: HashSet<ChunkHeaderLoc> = query
+            .matching_index_data(&metadata)
+            .map(|data| data.chunk_header_pos)
+            .collect();
+        // messages on reused connection ids are only attributed to the right definition once chunks are read
+        for warning in metadata.warnings.iter() {
+            let BagWarning::DuplicateConnectionId { connection_id, .. } = warning else {
+                continue;
+            };
+            chunks.extend(
+                metadata
+                    .index_data
+                    .get(connection_id)
+                    .into_iter()
+                    .flatten()
+                    .map(|data| data.chunk_header_pos),
+            );
+        }
+
+        let chunk_bytes = populate_chunk_bytes(
+            &metadata.chunk_metadata,
+            storage,
+            &[],
+            query.cancellation(),
+            query.stats(),
+            |chunk_header_pos| chunks.contains(chunk_header_pos),
+        )?;
+        if let Some(recorder) = metrics::recorder() {
+            let read = chunks
+                .iter()
+                .filter_map(|chunk_header_pos| metadata.chunk_metadata.get(chunk_header_pos))
+                .map(|chunk| u64::from(chunk.compressed_size))
+                .sum();
+            recorder.bytes_read(read);
+        }
+        resolve_duplicate_connections(&mut metadata, &chunk_bytes)?;
+
+        let skipped: HashSet<ChunkHeaderLoc> = metadata
+            .chunk_metadata
+            .keys()
+            .filter(|chunk_header_pos| !chunks.contains(chunk_header_pos))
+            .copied()
+            .collect();
+        let mut bag = DecompressedBag {
+            metadata,
+            chunk_bytes,
+        };
+        bag.remove_chunks(&skipped);
+        if let Some(stats) = query.stats() {
+            stats.chunks_pruned(skipped.len());
+            stats.add_elapsed(started.elapsed());
+        }
+        Ok(bag)
+    }
 
-        let mut reader = BufReader::new(file);
+    /// Reads a bag from any [BagStorage], decrypting encrypted chunks with the key matching their key id.
+    #[cfg(feature = "encryption")]
+    pub fn from_storage_with_keys(
+        storage: &dyn BagStorage,
+        keys: &[EncryptionKey],
+    ) -> Result<Self, Error> {
+        Self::load_storage(storage, keys, None)
+    }
 
-        let mut bytes = Vec::<u8>::new();
-        reader.read_to_end(&mut bytes)?;
+    fn load_storage(
+        storage: &dyn BagStorage,
+        keys: &[EncryptionKey],
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Self, Error> {
+        if let Some(cancellation) = cancellation {
+            cancellation.check()?;
+        }
+        let size = usize::try_from(storage.size()?).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                "bag is too large to load into memory",
+            )
+        })?;
+        let bytes = storage.read_at(0, size)?;
 
-        let mut bag = Self::from_bytes(&bytes)?;
-        bag.metadata.file_path = Some(path);
+        let mut bag = Self::load_bytes(&bytes, keys, cancellation)?;
+        bag.metadata.file_path = storage.path().map(Path::to_path_buf);
 
         Ok(bag)
     }
 
-    pub fn read_messages(&self, query: &Query) -> Result<BagIter, Error> {
+    /// Reads the messages matching a query, in receive time order.
+    ///
+    /// Fails if the query filters on message contents, with [Query::with_predicate] or [Query::with_field], as
+    /// [BagIter] knows how many messages it will yield up front; read those with [DecompressedBag::filter_messages].
+    pub fn read_messages(&self, query: &Query) -> Result<BagIter<'_>, Error> {
+        if query.has_predicates() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the query filters on message contents, read it with filter_messages",
+            )
+            .into());
+        }
         BagIter::new(self, query)
     }
+
+    /// Reads the messages matching a query, in receive time order, checking its predicates as they are read.
+    /// Works for any query, see [Query::with_predicate].
+    pub fn filter_messages(&self, query: &Query) -> Result<FilteredIter<'_>, Error> {
+        FilteredIter::new(self, query)
+    }
+
+    /// Returns the number of messages [DecompressedBag::filter_messages] would yield for a query, without reading any
+    /// of them unless the query filters on their contents, in which case they are decoded one at a time.
+    pub fn count_messages(&self, query: &Query) -> usize {
+        if query.has_predicates() {
+            return self.filter_messages(query).map_or(0, Iterator::count);
+        }
+        self.metadata.count_messages(query)
+    }
+
+    /// Estimates the cost of a query, see [BagMetadata::estimate].
+    pub fn estimate(&self, query: &Query) -> QueryEstimate {
+        self.metadata.estimate(query)
+    }
+
+    /// Returns what a subscriber to `topics` would have received last at `time`: the most recent message received
+    /// at or before it on each topic, found by binary searching the index.
+    ///
+    /// Latched topics map to the most recent message of each latched publisher, like `/tf_static`, since a late
+    /// subscriber receives all of them, along with the most recent message of any other publishers. A topic's
+    /// messages are ordered by receive time, so the most recent is last. Topics without messages by `time` are left out.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::time::Time;
+    /// use frost::DecompressedBag;
+    ///
+    /// let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    /// let time = Time { secs: 50, nsecs: 0 };
+    /// let state = bag.state_at(time, &["/chatter", "/not_in_bag"]);
+    /// assert_eq!(state.len(), 1);
+    /// let latest = state["/chatter"].last().unwrap();
+    /// assert!(latest.time <= time);
+    /// ```
+    pub fn state_at<S: AsRef<str>>(
+        &self,
+        time: Time,
+        topics: &[S],
+    ) -> BTreeMap<&str, Vec<MessageView<'_>>> {
+        let topics: HashSet<&str> = topics.iter().map(AsRef::as_ref).collect();
+        let mut latest: BTreeMap<&str, Vec<&IndexData>> = BTreeMap::new();
+        for (id, connection) in self.metadata.connection_data.iter() {
+            if !topics.contains(connection.topic.as_str()) {
+                continue;
+            }
+            let index = self
+                .metadata
+                .index_data
+                .get(id)
+                .map_or(&[][..], Vec::as_slice);
+            let received = index.partition_point(|data| data.time <= time);
+            let Some(data) = received.checked_sub(1).map(|i| &index[i]) else {
+                continue;
+            };
+            let messages = latest.entry(connection.topic.as_str()).or_default();
+            if connection.latching {
+                messages.push(data);
+                continue;
+            }
+            // only the most recent of the publishers which don't latch
+            let previous = messages
+                .iter()
+                .position(|previous| !self.metadata.connection_data[&previous.conn_id].latching);
+            match previous {
+                Some(i) if messages[i].time <= data.time => messages[i] = data,
+                Some(_) => {}
+                None => messages.push(data),
+            }
+        }
+
+        latest
+            .into_iter()
+            .map(|(topic, mut messages)| {
+                messages.sort_by_key(|data| data.time);
+                let views = messages
+                    .into_iter()
+                    .filter_map(|data| query::message_view(self, data, None))
+                    .collect();
+                (topic, views)
+            })
+            .collect()
+    }
+
+    /// Returns the bytes held by the bag's decompressed chunks, which is most of the memory it uses.
+    pub fn memory_usage(&self) -> u64 {
+        self.chunk_bytes
+            .values()
+            .map(|bytes| bytes.capacity() as u64)
+            .sum()
+    }
+
+    /// Frees the chunks whose messages were all received within `range`, returning the bytes freed.
+    ///
+    /// Their messages are no longer read or counted, so a long-lived process can shed the chunks a first pass is
+    /// done with, like those before a time it has reached, without reopening the bag.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::query::Query;
+    /// use frost::DecompressedBag;
+    ///
+    /// let mut bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    /// let before = bag.memory_usage();
+    /// assert_eq!(bag.drop_chunks(..), before);
+    /// assert_eq!(bag.memory_usage(), 0);
+    /// assert_eq!(bag.read_messages(&Query::all()).unwrap().count(), 0);
+    /// ```
+    pub fn drop_chunks<R: RangeBounds<Time>>(&mut self, range: R) -> u64 {
+        let chunks: HashSet<ChunkHeaderLoc> = self
+            .metadata
+            .chunk_metadata
+            .iter()
+            .filter(|(_, chunk)| {
+                range.contains(&chunk.start_time) && range.contains(&chunk.end_time)
+            })
+            .map(|(chunk_header_pos, _)| *chunk_header_pos)
+            .collect();
+        self.remove_chunks(&chunks)
+    }
+
+    /// Keeps only the messages on `topics`, freeing the chunks without any of them, and returns the bytes freed.
+    /// Messages on other topics are no longer read or counted, even in the chunks which are kept.
+    pub fn retain_topics<S: AsRef<str>>(&mut self, topics: &[S]) -> u64 {
+        let topics: HashSet<&str> = topics.iter().map(AsRef::as_ref).collect();
+        for (id, data) in self.metadata.connection_data.iter() {
+            if !topics.contains(data.topic.as_str()) {
+                if let Some(index) = self.metadata.index_data.get_mut(id) {
+                    *index = Vec::new();
+                }
+            }
+        }
+
+        let used: HashSet<ChunkHeaderLoc> = self
+            .metadata
+            .index_data
+            .values()
+            .flatten()
+            .map(|data| data.chunk_header_pos)
+            .collect();
+        let unused = self
+            .chunk_bytes
+            .keys()
+            .filter(|chunk_header_pos| !used.contains(chunk_header_pos))
+            .copied()
+            .collect();
+        self.remove_chunks(&unused)
+    }
+
+    fn remove_chunks(&mut self, chunks: &HashSet<ChunkHeaderLoc>) -> u64 {
+        for index in self.metadata.index_data.values_mut() {
+            index.retain(|data| !chunks.contains(&data.chunk_header_pos));
+        }
+        chunks
+            .iter()
+            .filter_map(|chunk_header_pos| self.chunk_bytes.remove(chunk_header_pos))
+            .map(|bytes| bytes.capacity() as u64)
+            .sum()
+    }
 }
 
+/// Decompresses the chunks at the positions `include` accepts, reading them from `storage`, until `cancellation` is
+/// cancelled, adding them to `stats`.
 fn populate_chunk_bytes(
     chunk_metadata: &BTreeMap<u64, ChunkMetadata>,
-    bag_bytes: &[u8],
+    storage: &dyn BagStorage,
+    keys: &[EncryptionKey],
+    cancellation: Option<&CancellationToken>,
+    stats: Option<&metrics::QueryStats>,
+    include: impl Fn(&ChunkHeaderLoc) -> bool,
 ) -> Result<BTreeMap<ChunkHeaderLoc, Vec<u8>>, Error> {
     let mut chunk_bytes = BTreeMap::new();
+    let recorder = metrics::recorder();
     //TODO: parallelization
-    for (chunk_loc, metadata) in chunk_metadata.iter() {
-        let chunk_start = metadata.chunk_data_pos as usize;
-        let chunk_end = chunk_start + metadata.compressed_size as usize;
-        let buf = &bag_bytes[chunk_start..chunk_end];
-
-        match metadata.compression.as_str() {
-            "none" => {
-                chunk_bytes.insert(*chunk_loc, buf.to_vec());
-            }
-            "lz4" => {
-                // TODO: figure out what are these bytes I'm removing..
-                let decompressed = lz4_flex::decompress(
-                    &buf[11..(buf.len() - 8)],
-                    metadata.uncompressed_size as usize,
-                )?;
-                chunk_bytes.insert(*chunk_loc, decompressed);
-            }
-            other => {
-                eprintln!("unsupported compression: {}", other);
-                return Err(Error::from(ParseError::InvalidBag));
-            }
+    for (chunk_loc, metadata) in chunk_metadata.iter().filter(|(loc, _)| include(loc)) {
+        if let Some(cancellation) = cancellation {
+            cancellation.check()?;
+        }
+        let started = Instant::now();
+        let past_end = || {
+            eprintln!("chunk at {chunk_loc} extends past the end of the bag");
+            ParseError::UnexpectedEOF
+        };
+        metadata
+            .chunk_data_pos
+            .checked_add(u64::from(metadata.compressed_size))
+            .ok_or_else(past_end)?;
+        let buf = storage
+            .read_at(metadata.chunk_data_pos, metadata.compressed_size as usize)
+            .map_err(|_| past_end())?;
+
+        chunk_bytes.insert(*chunk_loc, decompress_chunk(metadata, &buf, keys)?);
+        if let Some(recorder) = &recorder {
+            recorder.chunk_decompressed(
+                &metadata.compression,
+                metadata.uncompressed_size as u64,
+                started.elapsed(),
+            );
+        }
+        if let Some(stats) = stats {
+            stats.chunk_decompressed(
+                *chunk_loc,
+                metadata.uncompressed_size as u64,
+                started.elapsed(),
+            );
         }
     }
     Ok(chunk_bytes)
 }
 
+/// Decrypts and decompresses the data of a chunk as read from the bag.
+pub(crate) fn decompress_chunk(
+    metadata: &ChunkMetadata,
+    buf: &[u8],
+    keys: &[EncryptionKey],
+) -> Result<Vec<u8>, Error> {
+    let decrypted;
+    let buf = match &metadata.encryption {
+        Some(encryption) => {
+            decrypted = crypto::decrypt(encryption, keys, buf)?;
+            decrypted.as_slice()
+        }
+        None => buf,
+    };
+
+    let decompressor = match compression::lookup(&metadata.compression) {
+        Some(decompressor) => decompressor,
+        None => {
+            eprintln!("unsupported compression: {}", metadata.compression);
+            return Err(Error::from(ParseError::InvalidBag));
+        }
+    };
+    let context = ChunkContext {
+        uncompressed_size: metadata.uncompressed_size as usize,
+        dict_id: metadata.dict_id.as_deref(),
+    };
+    Ok(decompressor.decompress(buf, &context)?)
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
-    use crate::{field_sep_index, version_check};
+    use crate::{capabilities, field_sep_index, version_check};
 
     const DECOMPRESSED: &[u8] = include_bytes!("../tests/fixtures/decompressed.bag");
 
@@ -1125,4 +2196,27 @@ mod tests {
         let buf = b"theresnosep";
         assert!(field_sep_index(buf).is_err());
     }
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(&capabilities.compressions[..2], ["none", "lz4"]);
+        assert_eq!(
+            &capabilities.formats[..4],
+            ["ROSBAG V2.0", "MCAP", "pcap", "pcapng"]
+        );
+        assert_eq!(
+            capabilities.formats.contains(&"rosbag2 mcap"),
+            cfg!(feature = "ros2")
+        );
+        assert_eq!(
+            capabilities.compressions.contains(&"zstd"),
+            cfg!(feature = "zstd")
+        );
+        assert_eq!(
+            capabilities.features.contains(&"std-msgs"),
+            cfg!(feature = "std-msgs")
+        );
+    }
 }
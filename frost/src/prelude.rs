@@ -0,0 +1,33 @@
+//! The types and traits most programs reading or writing bags need, importable at once with `use frost::prelude::*`.
+//!
+//! Items are only ever added to the prelude in minor releases, and are removed or renamed only in major ones,
+//! so a glob import of it keeps compiling across upgrades. For a guarantee covering paths and signatures too, see
+//! [crate::stable].
+//!
+//! Example
+//! ```rust
+//! use frost::prelude::*;
+//!
+//! fn count(bag: &DecompressedBag, topic: &str) -> Result<usize, Error> {
+//!     Ok(bag.read_messages(&Query::new().with_topics([topic]))?.count())
+//! }
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! assert_eq!(count(&bag, "/chatter").unwrap(), 100);
+//!
+//! let msg_view = bag.read_messages(&Query::all()).unwrap().next().unwrap();
+//! let time: Time = msg_view.time;
+//! assert!(matches!(msg_view.decode().unwrap(), Value::Message(_)));
+//! # let _ = time;
+//! ```
+pub use crate::dynamic::Value;
+pub use crate::errors::{DecodeError, Error, ErrorKind};
+pub use crate::msgs::{MessageView, Msg};
+pub use crate::query::{BagIter, FilteredIter, Query, QueryTime};
+pub use crate::storage::BagStorage;
+pub use crate::time::Time;
+pub use crate::writer::{BagWriter, Compression};
+/// `#[derive(Msg)]`, with the `derive` feature.
+#[cfg(feature = "derive")]
+pub use crate::Msg;
+pub use crate::{BagMetadata, ConnectionData, DecompressedBag};
@@ -0,0 +1,139 @@
+//! The semver-stable core of frost's API, versioned so large codebases can upgrade frost without surprise breakage.
+//!
+//! Each version is a module, like [v1], re-exporting the core types and functions for reading and writing bags.
+//! Once a version is published, the items it re-exports keep their names, paths and signatures. New subsystems are
+//! added alongside, outside of it, and only join a later version once their APIs have settled. Depending only on
+//! `frost::stable::v1` makes an upgrade a matter of bumping the version.
+//!
+//! So that they can grow, v1 leaves out:
+//! - exhaustive enums, like [Value](crate::dynamic::Value) and [Compression](crate::writer::Compression), which
+//!   can't gain variants without breaking matches on them. Marking them `#[non_exhaustive]` is itself a breaking
+//!   change, so they only join a version after a breaking release, as [ErrorKind](crate::errors::ErrorKind) did
+//!   in 0.5.0
+//! - types with public fields frost uses internally, like [ConnectionData](crate::ConnectionData)
+//!
+//! and the traits it re-exports which frost implements, like [BagStorage](crate::storage::BagStorage), are sealed.
+//!
+//! Items are deprecated before they are changed or removed:
+//! - a deprecated item keeps working as a shim forwarding to its replacement, and is listed in [DEPRECATIONS]
+//! - it is only removed in a breaking release, the next minor release while frost is `0.x` and the next major
+//!   release after that, at the earliest the [Deprecation::removal] version it lists
+//! - each use of a shim is recorded, see [deprecations_used], and reported to the handler set with
+//!   [set_deprecation_handler], or on stderr after [warn_on_stderr], so uses the compiler's `deprecated` lint misses
+//!   (e.g. from code with `#[allow(deprecated)]`, or generated code) still surface before the removal
+//!
+//! Example
+//! ```rust
+//! use frost::stable::v1::{DecompressedBag, Error, Query};
+//!
+//! fn count(bag: &DecompressedBag, topic: &str) -> Result<usize, Error> {
+//!     Ok(bag.read_messages(&Query::new().with_topics([topic]))?.count())
+//! }
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! assert_eq!(count(&bag, "/chatter").unwrap(), 100);
+//!
+//! // fail a test suite on any use of a deprecated shim
+//! frost::stable::set_deprecation_handler(|deprecation| panic!("{deprecation}"));
+//! ```
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+
+/// The latest version of the stable API.
+pub const VERSION: u32 = 1;
+
+/// The first version of the stable API, covering loading, querying, decoding and writing bags.
+pub mod v1 {
+    pub use crate::errors::{Error, ErrorKind};
+    pub use crate::msgs::{MessageView, Msg};
+    pub use crate::query::{BagIter, Query};
+    pub use crate::storage::BagStorage;
+    pub use crate::time::Time;
+    pub use crate::writer::BagWriter;
+    pub use crate::{capabilities, BagMetadata, Capabilities, DecompressedBag};
+}
+
+/// A deprecated item, kept as a shim until its removal, see the [module docs](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deprecation {
+    /// The item's path, e.g. `frost::time::Time::dur`.
+    pub item: &'static str,
+    /// The version the item was deprecated in.
+    pub since: &'static str,
+    /// The earliest version the item may be removed in.
+    pub removal: &'static str,
+    /// What to use instead.
+    pub replacement: &'static str,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is deprecated since frost {} and may be removed in {}, use {} instead",
+            self.item, self.since, self.removal, self.replacement
+        )
+    }
+}
+
+pub(crate) const TIME_FROM: Deprecation = Deprecation {
+    item: "frost::time::Time::from",
+    since: "0.5.0",
+    removal: "0.6.0",
+    replacement: "frost::time::Time::from_le_bytes",
+};
+
+pub(crate) const TIME_DUR: Deprecation = Deprecation {
+    item: "frost::time::Time::dur",
+    since: "0.5.0",
+    removal: "0.6.0",
+    replacement: "frost::time::Time::duration_since",
+};
+
+/// Every deprecated item which is still available, see the [module docs](self).
+pub const DEPRECATIONS: &[Deprecation] = &[TIME_FROM, TIME_DUR];
+
+static HANDLER: RwLock<Option<fn(&Deprecation)>> = RwLock::new(None);
+static WARN_ON_STDERR: AtomicBool = AtomicBool::new(false);
+static USED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Reports each use of a deprecated shim to `handler`, instead of any warnings on stderr.
+pub fn set_deprecation_handler(handler: fn(&Deprecation)) {
+    *HANDLER.write().unwrap() = Some(handler);
+}
+
+/// Warns on stderr the first time each deprecated shim is used, unless a handler is set with
+/// [set_deprecation_handler]. Off by default, so libraries built on frost don't write to their users' stderr.
+pub fn warn_on_stderr() {
+    WARN_ON_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// The deprecated items used so far by this process, in the order they were first used.
+pub fn deprecations_used() -> Vec<Deprecation> {
+    let used = USED.lock().unwrap();
+    used.iter()
+        .filter_map(|item| DEPRECATIONS.iter().find(|d| d.item == *item))
+        .copied()
+        .collect()
+}
+
+/// Called by deprecated shims each time they are used.
+pub(crate) fn deprecated_use(deprecation: &Deprecation) {
+    let first_use = {
+        let mut used = USED.lock().unwrap();
+        let first_use = !used.contains(&deprecation.item);
+        if first_use {
+            used.push(deprecation.item);
+        }
+        first_use
+    };
+    let handler = *HANDLER.read().unwrap();
+    match handler {
+        Some(handler) => handler(deprecation),
+        None if first_use && WARN_ON_STDERR.load(Ordering::Relaxed) => {
+            eprintln!("warning: {deprecation}")
+        }
+        None => {}
+    }
+}
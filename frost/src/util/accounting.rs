@@ -0,0 +1,96 @@
+//! How many bytes each topic's messages take, for storage chargeback.
+//!
+//! [BagMetadata::estimated_topic_bytes] measures messages from the index alone, as the distance between consecutive
+//! message records in a chunk (see [BagMetadata::chunk_layout]), which also counts record headers and any connection
+//! records in between. [BagMetadata::exact_topic_bytes] makes a second pass over the bag instead, reading and
+//! decompressing one chunk at a time, and sums the lengths of the serialized messages in their data records, so its
+//! counts are exact. With the `cache` feature, `MetadataCache::exact_topic_bytes` keeps them in the bag's cache entry,
+//! so each bag is only scanned once.
+//!
+//! Example
+//! ```rust
+//! use frost::storage::FileStorage;
+//! use frost::BagMetadata;
+//!
+//! let path = "./tests/fixtures/compressed_lz4.bag";
+//! let metadata = BagMetadata::from_file(path).unwrap();
+//! let estimated = metadata.estimated_topic_bytes();
+//! let exact = metadata.exact_topic_bytes(&FileStorage::open(path).unwrap()).unwrap();
+//! assert_eq!(exact["/chatter"].messages, 100);
+//! // the estimate includes each record's header
+//! assert!(exact["/chatter"].bytes < estimated["/chatter"].bytes);
+//! ```
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::errors::{Error, ParseError};
+use crate::storage::BagStorage;
+use crate::util::parsing::parse_le_u32_at;
+use crate::{decompress_chunk, BagMetadata};
+
+/// A topic's messages and the bytes they take, see the [module docs](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TopicBytes {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+impl BagMetadata {
+    /// Estimates each topic's bytes from the index alone, see the [module docs](self).
+    pub fn estimated_topic_bytes(&self) -> BTreeMap<String, TopicBytes> {
+        let mut totals: BTreeMap<String, TopicBytes> = BTreeMap::new();
+        for chunk in self.chunk_layout().chunks {
+            for (topic, share) in chunk.topics {
+                let total = totals.entry(topic).or_default();
+                total.messages += share.messages;
+                total.bytes += share.bytes;
+            }
+        }
+        totals
+    }
+
+    /// Counts each topic's serialized message bytes exactly, reading the bag's chunks from `storage` one at a time,
+    /// see the [module docs](self).
+    pub fn exact_topic_bytes(
+        &self,
+        storage: &dyn BagStorage,
+    ) -> Result<BTreeMap<String, TopicBytes>, Error> {
+        // the offsets of every message in each chunk
+        let mut offsets: BTreeMap<u64, Vec<(u32, &str)>> = BTreeMap::new();
+        for (conn_id, index) in self.index_data.iter() {
+            let Some(connection) = self.connection_data.get(conn_id) else {
+                continue;
+            };
+            for data in index {
+                offsets
+                    .entry(data.chunk_header_pos)
+                    .or_default()
+                    .push((data.offset, connection.topic.as_str()));
+            }
+        }
+
+        let mut totals: BTreeMap<String, TopicBytes> = BTreeMap::new();
+        for (chunk_pos, messages) in offsets {
+            let Some(chunk) = self.chunk_metadata.get(&chunk_pos) else {
+                continue;
+            };
+            let compressed = storage
+                .read_at(chunk.chunk_data_pos, chunk.compressed_size as usize)
+                .map_err(|_| ParseError::UnexpectedEOF)?;
+            let chunk_bytes = decompress_chunk(chunk, &compressed, &[])?;
+            for (offset, topic) in messages {
+                // a message data record is its header's length and header, then its data's length and data
+                let header_len = parse_le_u32_at(&chunk_bytes, offset as usize)?;
+                let data_len_pos = (offset as usize)
+                    .checked_add(4 + header_len as usize)
+                    .ok_or(ParseError::UnexpectedEOF)?;
+                let data_len = parse_le_u32_at(&chunk_bytes, data_len_pos)?;
+                let total = totals.entry(topic.to_string()).or_default();
+                total.messages += 1;
+                total.bytes += u64::from(data_len);
+            }
+        }
+        Ok(totals)
+    }
+}
@@ -0,0 +1,147 @@
+//! Running the same work over many bags on a fixed number of threads, as `frost`'s `--jobs` does.
+//!
+//! A [Batch] hands items to at most [Batch::jobs] worker threads and reports each result in the order of the items,
+//! so output stays deterministic however the work is scheduled. Items are isolated from each other: an error, or
+//! even a panic, processing one bag is reported for that bag and the rest of the batch carries on.
+//!
+//! Example
+//! ```rust
+//! use frost::batch::Batch;
+//! use frost::BagMetadata;
+//!
+//! let paths = ["./tests/fixtures/compressed_lz4.bag", "./tests/fixtures/missing.bag"];
+//! let summary = Batch::new(2).run(
+//!     &paths,
+//!     |path| BagMetadata::from_file(path).map(|metadata| metadata.message_count()),
+//!     |path, result| match result {
+//!         Ok(count) => println!("{path}: {count} messages"),
+//!         Err(e) => eprintln!("{path}: {e}"),
+//!     },
+//! );
+//! assert_eq!((summary.succeeded, summary.failed), (1, 1));
+//! ```
+use std::collections::BTreeMap;
+use std::io;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::errors::Error;
+
+/// A pool of worker threads for running work over many items, see [the module docs](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Batch {
+    jobs: usize,
+}
+
+impl Batch {
+    /// Runs at most `jobs` items at a time, or one per CPU if `jobs` is 0.
+    pub fn new(jobs: usize) -> Self {
+        let jobs = match jobs {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            jobs => jobs,
+        };
+        Batch { jobs }
+    }
+
+    /// The number of worker threads.
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Calls `work` on every item on the worker threads, and `done` with each item and its result on the calling
+    /// thread, in the order of `items`. Panics in `work` are caught and passed to `done` as errors.
+    pub fn run<T, R, W, D>(&self, items: &[T], work: W, mut done: D) -> BatchSummary
+    where
+        T: Sync,
+        R: Send,
+        W: Fn(&T) -> Result<R, Error> + Sync,
+        D: FnMut(&T, Result<R, Error>),
+    {
+        let mut summary = BatchSummary::default();
+        let mut report = |item: &T, result: Result<R, Error>| {
+            match result {
+                Ok(_) => summary.succeeded += 1,
+                Err(_) => summary.failed += 1,
+            }
+            done(item, result);
+        };
+
+        // no threads to spare for a single worker
+        if self.jobs == 1 || items.len() <= 1 {
+            for item in items {
+                report(item, isolate(&work, item));
+            }
+            return summary;
+        }
+
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..self.jobs.min(items.len()) {
+                let sender = sender.clone();
+                let (next, work) = (&next, &work);
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    if sender.send((index, isolate(work, item))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            // results finishing ahead of earlier items wait for them
+            let mut pending = BTreeMap::new();
+            let mut reported = 0;
+            for (index, result) in receiver {
+                pending.insert(index, result);
+                while let Some(result) = pending.remove(&reported) {
+                    report(&items[reported], result);
+                    reported += 1;
+                }
+            }
+        });
+        summary
+    }
+}
+
+impl Default for Batch {
+    /// One job at a time.
+    fn default() -> Self {
+        Batch::new(1)
+    }
+}
+
+/// How many items of a [Batch::run] succeeded and failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl BatchSummary {
+    /// True if no item failed.
+    pub fn is_success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// Runs `work` on an item, turning a panic into an error.
+fn isolate<T, R, W>(work: &W, item: &T) -> Result<R, Error>
+where
+    W: Fn(&T) -> Result<R, Error>,
+{
+    panic::catch_unwind(AssertUnwindSafe(|| work(item))).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown cause".to_string());
+        Err(io::Error::new(io::ErrorKind::Other, format!("panicked: {message}")).into())
+    })
+}
@@ -0,0 +1,346 @@
+//! An opt-in, on-disk cache of bag metadata, so repeated `frost info --cache` or `frost topics --cache` on the same
+//! large files don't have to scan them again. Enabled with the `cache` feature.
+//!
+//! Entries hold the bytes of a bag's records other than chunk data (see [BagMetadata::byte_ranges]), which are
+//! parsed again on a hit, and are keyed by a fingerprint of the bag's canonical path, size, and modification time,
+//! so modifying a bag makes its entry stale rather than wrong. The default directory is `$XDG_CACHE_HOME/frost`, or
+//! `~/.cache/frost`. Entries can also hold a bag's [exact per-topic byte counts](MetadataCache::exact_topic_bytes).
+//!
+//! Example
+//! ```rust
+//! use frost::cache::MetadataCache;
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! let cache = MetadataCache::new(dir.path());
+//!
+//! let path = "./tests/fixtures/compressed_lz4.bag";
+//! let metadata = cache.load(path).unwrap();
+//! assert_eq!(cache.entries().unwrap().len(), 1);
+//! // parsed from the cache this time
+//! assert_eq!(cache.load(path).unwrap().message_count(), metadata.message_count());
+//! ```
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+
+use crate::accounting::TopicBytes;
+use crate::errors::Error;
+use crate::query::Query;
+use crate::storage::{sealed, BagStorage, FileStorage};
+use crate::BagMetadata;
+
+const MAGIC: &[u8] = b"FROSTCACHE\x01";
+const EXTENSION: &str = "frostcache";
+
+/// A directory of cached bag metadata, see the [module docs](self).
+#[derive(Clone, Debug)]
+pub struct MetadataCache {
+    dir: PathBuf,
+}
+
+/// A cached bag, from [MetadataCache::entries].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// The bag's path when it was cached.
+    pub bag_path: PathBuf,
+    /// The size of the bag.
+    pub bag_bytes: u64,
+    /// The size of the cache file.
+    pub cached_bytes: u64,
+}
+
+impl MetadataCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        MetadataCache {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The cache in `$XDG_CACHE_HOME/frost`, or `~/.cache/frost`. `None` if neither variable is set.
+    pub fn open_default() -> Option<Self> {
+        let dir = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        Some(Self::new(dir.join("frost")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reads a bag's metadata from the cache, or from the bag, caching it, if the bag isn't cached or was modified
+    /// since.
+    pub fn load<P: AsRef<Path>>(&self, file_path: P) -> Result<BagMetadata, Error> {
+        let file_path = file_path.as_ref();
+        let entry_path = self.entry_path(file_path)?;
+        if let Some((metadata, _)) = read_entry(&entry_path, file_path) {
+            return Ok(metadata);
+        }
+
+        let storage = FileStorage::open(file_path)?;
+        let metadata = BagMetadata::from_storage(&storage)?;
+        self.store(&entry_path, file_path, &storage, &metadata, None)?;
+        Ok(metadata)
+    }
+
+    /// Reads a bag's [exact per-topic byte counts](BagMetadata::exact_topic_bytes) from the cache, or counts them,
+    /// scanning the bag, and caches them along with its metadata.
+    pub fn exact_topic_bytes<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<BTreeMap<String, TopicBytes>, Error> {
+        let file_path = file_path.as_ref();
+        let entry_path = self.entry_path(file_path)?;
+        let cached = read_entry(&entry_path, file_path);
+        if let Some((_, Some(topic_bytes))) = cached {
+            return Ok(topic_bytes);
+        }
+
+        let storage = FileStorage::open(file_path)?;
+        let metadata = match cached {
+            Some((metadata, _)) => metadata,
+            None => BagMetadata::from_storage(&storage)?,
+        };
+        let topic_bytes = metadata.exact_topic_bytes(&storage)?;
+        self.store(
+            &entry_path,
+            file_path,
+            &storage,
+            &metadata,
+            Some(&topic_bytes),
+        )?;
+        Ok(topic_bytes)
+    }
+
+    /// Lists the cached bags, including stale entries of bags modified since they were cached.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>, Error> {
+        let mut entries = Vec::new();
+        for path in self.entry_files()? {
+            let Ok(file) = File::open(&path) else {
+                continue;
+            };
+            let cached_bytes = file.metadata()?.len();
+            let mut reader = BufReader::new(file);
+            if let Ok((bag_path, bag_bytes)) = read_header(&mut reader) {
+                entries.push(CacheEntry {
+                    bag_path,
+                    bag_bytes,
+                    cached_bytes,
+                });
+            }
+        }
+        entries.sort_by(|a, b| a.bag_path.cmp(&b.bag_path));
+        Ok(entries)
+    }
+
+    /// Removes every entry, returning how many were removed.
+    pub fn clear(&self) -> Result<usize, Error> {
+        let files = self.entry_files()?;
+        for path in files.iter() {
+            fs::remove_file(path)?;
+        }
+        Ok(files.len())
+    }
+
+    fn entry_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(false, |extension| extension == EXTENSION)
+            {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// The entry of a bag in its current state.
+    fn entry_path(&self, file_path: &Path) -> Result<PathBuf, Error> {
+        let canonical = fs::canonicalize(file_path)?;
+        let file_metadata = fs::metadata(&canonical)?;
+        let modified = file_metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut hasher = Sha256::new();
+        hasher.update(canonical.to_string_lossy().as_bytes());
+        hasher.update(file_metadata.len().to_le_bytes());
+        hasher.update(modified.as_nanos().to_le_bytes());
+        Ok(self
+            .dir
+            .join(format!("{:x}.{EXTENSION}", hasher.finalize())))
+    }
+
+    fn store(
+        &self,
+        entry_path: &Path,
+        file_path: &Path,
+        storage: &FileStorage,
+        metadata: &BagMetadata,
+        topic_bytes: Option<&BTreeMap<String, TopicBytes>>,
+    ) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+        // written next to the entry and renamed, so concurrent loads never see a partial entry
+        let partial = entry_path.with_extension(format!("{}.partial", std::process::id()));
+        let mut writer = BufWriter::new(File::create(&partial)?);
+
+        let path = fs::canonicalize(file_path)?;
+        let path = path.to_string_lossy();
+        // a query matching nothing needs only the records other than chunk data
+        let ranges = metadata.byte_ranges(&Query::new().with_topics(Vec::<&str>::new()));
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(path.len() as u32).to_le_bytes())?;
+        writer.write_all(path.as_bytes())?;
+        writer.write_all(&metadata.num_bytes.to_le_bytes())?;
+        writer.write_all(&(ranges.len() as u32).to_le_bytes())?;
+        for range in ranges {
+            let len = range.end - range.start;
+            writer.write_all(&range.start.to_le_bytes())?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(&storage.read_at(range.start, len as usize)?)?;
+        }
+        // optional, so entries written without it still read
+        if let Some(topic_bytes) = topic_bytes {
+            writer.write_all(&(topic_bytes.len() as u32).to_le_bytes())?;
+            for (topic, counts) in topic_bytes {
+                writer.write_all(&(topic.len() as u32).to_le_bytes())?;
+                writer.write_all(topic.as_bytes())?;
+                writer.write_all(&counts.messages.to_le_bytes())?;
+                writer.write_all(&counts.bytes.to_le_bytes())?;
+            }
+        }
+        writer.into_inner().map_err(|err| err.into_error())?;
+        fs::rename(&partial, entry_path)?;
+        Ok(())
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Reads a bag's metadata, and its exact per-topic byte counts if they were cached, from its entry. A missing,
+/// corrupt or truncated entry is a miss, and is overwritten when the bag is cached again.
+fn read_entry(
+    entry_path: &Path,
+    file_path: &Path,
+) -> Option<(BagMetadata, Option<BTreeMap<String, TopicBytes>>)> {
+    let mut reader = BufReader::new(File::open(entry_path).ok()?);
+    let storage = CachedStorage::read(&mut reader).ok()?;
+    let mut metadata = BagMetadata::from_storage(&storage).ok()?;
+    metadata.file_path = Some(file_path.to_path_buf());
+    Some((metadata, read_topic_bytes(&mut reader).ok()))
+}
+
+/// Reads the exact per-topic byte counts after an entry's ranges.
+fn read_topic_bytes(reader: &mut impl Read) -> io::Result<BTreeMap<String, TopicBytes>> {
+    let mut topic_bytes = BTreeMap::new();
+    for _ in 0..read_u32(reader)? {
+        let mut topic = vec![0; read_u32(reader)? as usize];
+        reader.read_exact(&mut topic)?;
+        let topic =
+            String::from_utf8(topic).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let counts = TopicBytes {
+            messages: read_u64(reader)?,
+            bytes: read_u64(reader)?,
+        };
+        topic_bytes.insert(topic, counts);
+    }
+    Ok(topic_bytes)
+}
+
+/// Reads the bag path and size at the start of an entry.
+fn read_header(reader: &mut impl Read) -> io::Result<(PathBuf, u64)> {
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a frost cache entry",
+        ));
+    }
+    let mut path = vec![0; read_u32(reader)? as usize];
+    reader.read_exact(&mut path)?;
+    let path = PathBuf::from(String::from_utf8_lossy(&path).into_owned());
+    Ok((path, read_u64(reader)?))
+}
+
+/// The cached ranges of a bag, reading as zeros elsewhere, which parsing metadata skips over.
+struct CachedStorage {
+    size: u64,
+    /// (offset, bytes), sorted by offset
+    ranges: Vec<(u64, Vec<u8>)>,
+}
+
+impl CachedStorage {
+    fn read(mut reader: impl Read) -> io::Result<Self> {
+        let (_, size) = read_header(&mut reader)?;
+        let count = read_u32(&mut reader)?;
+        let mut ranges = Vec::new();
+        for _ in 0..count {
+            let start = read_u64(&mut reader)?;
+            let mut bytes = Vec::new();
+            let len = read_u64(&mut reader)?;
+            (&mut reader).take(len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            ranges.push((start, bytes));
+        }
+        Ok(CachedStorage { size, ranges })
+    }
+}
+
+impl sealed::Sealed for CachedStorage {}
+
+impl BagStorage for CachedStorage {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.size)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        let end = match offset.checked_add(len as u64) {
+            Some(end) if end <= self.size => end,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{len} bytes at offset {offset} are past the end of the bag"),
+                ))
+            }
+        };
+        let mut buf = vec![0; len];
+        for (start, bytes) in self.ranges.iter() {
+            let range_end = start + bytes.len() as u64;
+            if range_end <= offset || *start >= end {
+                continue;
+            }
+            let from = offset.max(*start);
+            let to = end.min(range_end);
+            buf[(from - offset) as usize..(to - offset) as usize]
+                .copy_from_slice(&bytes[(from - start) as usize..(to - start) as usize]);
+        }
+        Ok(Cow::Owned(buf))
+    }
+}
@@ -0,0 +1,64 @@
+//! Aborting long operations, like loading, exporting or merging large bags, from another thread.
+//!
+//! A [CancellationToken] is handed to an operation through the query it runs,
+//! [Query::with_cancellation](crate::query::Query::with_cancellation), or to a loader like
+//! [DecompressedBag::from_file_with_cancellation](crate::DecompressedBag::from_file_with_cancellation), and cancelled from anywhere holding a clone of
+//! it, like a GUI's stop button or a service's shutdown handler. Operations check it between chunks or messages:
+//! - iterating over [DecompressedBag::read_messages](crate::DecompressedBag::read_messages) stops, leaving the
+//!   messages read so far, and [BagIter::was_cancelled](crate::query::BagIter::was_cancelled) tells them apart from
+//!   all of them
+//! - [loading](crate::DecompressedBag::from_file_filtered), [copying](crate::writer::BagWriter::copy_messages),
+//!   [exporting](crate::mcap::McapWriter::copy_messages) and [merging](crate::merge::Merge::write_to) fail with
+//!   [ErrorKind::Cancelled]. Writers keep the messages written before then, so finishing one still gives a valid,
+//!   partial file
+//!
+//! Example
+//! ```rust
+//! use frost::cancel::CancellationToken;
+//! use frost::errors::ErrorKind;
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let token = CancellationToken::new();
+//! let query = Query::all().with_cancellation(token.clone());
+//!
+//! // e.g. from a UI thread
+//! token.cancel();
+//! let result = DecompressedBag::from_file_filtered("./tests/fixtures/compressed_lz4.bag", &query);
+//! assert!(matches!(result.err().unwrap().kind(), ErrorKind::Cancelled));
+//! ```
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::errors::{Error, ErrorKind};
+
+/// A flag shared by an operation and whoever may cancel it, see the [module docs](self).
+///
+/// Clones share the same flag. Once cancelled, a token stays cancelled.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks every operation holding a clone of this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns an [ErrorKind::Cancelled] error if the token has been cancelled.
+    pub fn check(&self) -> Result<(), Error> {
+        match self.is_cancelled() {
+            true => Err(Error::new(ErrorKind::Cancelled)),
+            false => Ok(()),
+        }
+    }
+}
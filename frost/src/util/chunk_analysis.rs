@@ -0,0 +1,217 @@
+//! Finds chunks whose compression ratio or size is far from the rest of the bag's.
+//!
+//! Chunks of a healthy recording compress about equally well. A chunk compressing much better than its neighbours
+//! often holds stuck sensor values or zeroed buffers, and one compressing much worse often holds noise or corrupted data.
+//! [BagMetadata::chunk_anomalies] compares each chunk against the median of the chunks using the same compression,
+//! using only chunk metadata, so no chunk is read or decompressed.
+//!
+//! Example
+//! ```rust
+//! use frost::chunk_analysis::DEFAULT_FACTOR;
+//! use frost::BagMetadata;
+//!
+//! let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let analysis = metadata.chunk_anomalies(DEFAULT_FACTOR);
+//! for anomaly in &analysis.anomalies {
+//!     println!("{anomaly}");
+//! }
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::time::Time;
+use crate::{BagMetadata, ChunkMetadata};
+
+/// How many times larger or smaller than the norm a chunk's ratio or size must be to be flagged.
+pub const DEFAULT_FACTOR: f64 = 4.0;
+/// Groups of fewer chunks have no norm to compare against, and are not analyzed.
+pub const MIN_CHUNKS: usize = 4;
+
+/// How a chunk deviates from the norm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnomalyKind {
+    /// Compresses much better than the norm, e.g. stuck values.
+    HighRatio,
+    /// Compresses much worse than the norm, e.g. noise or corrupted data.
+    LowRatio,
+    /// Much larger uncompressed than the norm.
+    Large,
+    /// Much smaller uncompressed than the norm. The last chunk of a bag is usually small, so it is never flagged.
+    Small,
+}
+
+impl fmt::Display for AnomalyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AnomalyKind::HighRatio => "high compression ratio",
+            AnomalyKind::LowRatio => "low compression ratio",
+            AnomalyKind::Large => "large chunk",
+            AnomalyKind::Small => "small chunk",
+        };
+        f.pad(s)
+    }
+}
+
+/// A chunk flagged by [BagMetadata::chunk_anomalies].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkAnomaly {
+    pub kind: AnomalyKind,
+    /// The file offset of the chunk's header.
+    pub chunk_pos: u64,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub compression: String,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    /// How many times larger, or smaller, than the norm the chunk's ratio or size is; always at least 1.
+    pub deviation: f64,
+    /// The topics with messages in the chunk.
+    pub topics: BTreeSet<String>,
+}
+
+impl ChunkAnomaly {
+    /// The chunk's uncompressed size divided by its compressed size.
+    pub fn ratio(&self) -> f64 {
+        ratio(self.uncompressed_size, self.compressed_size)
+    }
+}
+
+impl fmt::Display for ChunkAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "chunk at {} ({} to {}): {}, {:.1}x the norm, ratio {:.2}, {} bytes uncompressed, topics: {}",
+            self.chunk_pos,
+            self.start_time,
+            self.end_time,
+            self.kind,
+            self.deviation,
+            self.ratio(),
+            self.uncompressed_size,
+            self.topics.iter().cloned().collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+/// The norms chunks were compared against and the chunks far from them, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkAnalysis {
+    pub chunk_count: usize,
+    /// Per compression with at least [MIN_CHUNKS] chunks, the median compression ratio.
+    pub median_ratios: BTreeMap<String, f64>,
+    /// The median uncompressed chunk size, if the bag has at least [MIN_CHUNKS] chunks.
+    pub median_size: Option<f64>,
+    /// Sorted by chunk position.
+    pub anomalies: Vec<ChunkAnomaly>,
+}
+
+fn ratio(uncompressed_size: u32, compressed_size: u32) -> f64 {
+    f64::from(uncompressed_size) / f64::from(compressed_size.max(1))
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Returns whether `value` is at least `norm`, and how many times larger or smaller than it `value` is.
+fn deviation(value: f64, norm: f64) -> (bool, f64) {
+    if value >= norm {
+        (true, value / norm.max(f64::MIN_POSITIVE))
+    } else {
+        (false, norm / value.max(f64::MIN_POSITIVE))
+    }
+}
+
+impl BagMetadata {
+    /// Flags chunks whose compression ratio or uncompressed size is at least `factor` times larger or smaller than the norm.
+    pub fn chunk_anomalies(&self, factor: f64) -> ChunkAnalysis {
+        let chunks: Vec<&ChunkMetadata> = self.chunk_metadata.values().collect();
+        let mut analysis = ChunkAnalysis {
+            chunk_count: chunks.len(),
+            ..Default::default()
+        };
+        let mut flagged: Vec<(AnomalyKind, f64, &ChunkMetadata)> = Vec::new();
+
+        let mut by_compression: BTreeMap<&str, Vec<&ChunkMetadata>> = BTreeMap::new();
+        for chunk in chunks.iter() {
+            by_compression
+                .entry(&chunk.compression)
+                .or_default()
+                .push(chunk);
+        }
+        for (compression, group) in by_compression {
+            // uncompressed chunks all have a ratio of 1
+            if group.len() < MIN_CHUNKS || compression == "none" {
+                continue;
+            }
+            let norm = median(
+                group
+                    .iter()
+                    .map(|chunk| ratio(chunk.uncompressed_size, chunk.compressed_size))
+                    .collect(),
+            );
+            analysis.median_ratios.insert(compression.to_string(), norm);
+            for chunk in group {
+                let (higher, times) =
+                    deviation(ratio(chunk.uncompressed_size, chunk.compressed_size), norm);
+                if times >= factor {
+                    let kind = if higher {
+                        AnomalyKind::HighRatio
+                    } else {
+                        AnomalyKind::LowRatio
+                    };
+                    flagged.push((kind, times, chunk));
+                }
+            }
+        }
+
+        if chunks.len() >= MIN_CHUNKS {
+            let norm = median(
+                chunks
+                    .iter()
+                    .map(|chunk| f64::from(chunk.uncompressed_size))
+                    .collect(),
+            );
+            analysis.median_size = Some(norm);
+            for (i, chunk) in chunks.iter().enumerate() {
+                let (higher, times) = deviation(f64::from(chunk.uncompressed_size), norm);
+                if times < factor {
+                    continue;
+                }
+                if higher {
+                    flagged.push((AnomalyKind::Large, times, chunk));
+                } else if i + 1 < chunks.len() {
+                    flagged.push((AnomalyKind::Small, times, chunk));
+                }
+            }
+        }
+
+        flagged.sort_by_key(|(kind, _, chunk)| (chunk.chunk_header_pos, *kind));
+        analysis.anomalies = flagged
+            .into_iter()
+            .map(|(kind, deviation, chunk)| ChunkAnomaly {
+                kind,
+                chunk_pos: chunk.chunk_header_pos,
+                start_time: chunk.start_time,
+                end_time: chunk.end_time,
+                compression: chunk.compression.clone(),
+                compressed_size: chunk.compressed_size,
+                uncompressed_size: chunk.uncompressed_size,
+                deviation,
+                topics: chunk
+                    .message_counts
+                    .keys()
+                    .filter_map(|id| self.connection_data.get(id))
+                    .map(|connection| connection.topic.clone())
+                    .collect(),
+            })
+            .collect();
+        analysis
+    }
+}
@@ -0,0 +1,103 @@
+//! Where code which waits, like [following a recording](crate::tail::Tail::follow), gets the time from.
+//!
+//! A [Clock] tells the time and sleeps. [SystemClock] uses the machine's monotonic clock and really sleeps, and a
+//! [MockClock] only moves forward when it is told to sleep or [advanced](MockClock::advance), so timing-sensitive code
+//! can be tested deterministically and without waiting. Implementing [Clock] for a test's own type also allows acting
+//! whenever the code under test waits, like appending to a bag it is following.
+//!
+//! Example
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use frost::clock::{Clock, MockClock};
+//!
+//! let clock = MockClock::new();
+//! let started = clock.now();
+//! clock.sleep(Duration::from_secs(3600));
+//! clock.advance(Duration::from_millis(500));
+//! assert_eq!(clock.now() - started, Duration::from_millis(3_600_500));
+//! assert_eq!(clock.sleeps(), 1);
+//! ```
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A source of time for code which waits, see the [module docs](self).
+pub trait Clock {
+    /// The current time.
+    fn now(&self) -> Instant;
+
+    /// Waits for `duration` to pass.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The machine's monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A clock which only moves forward when told to, returning from [sleep](Clock::sleep) immediately.
+///
+/// Clones share the same time, so a test can keep one and hand another to the code it tests.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    start: Instant,
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    elapsed: Duration,
+    sleeps: usize,
+}
+
+impl MockClock {
+    /// A clock starting at the current time.
+    pub fn new() -> Self {
+        MockClock {
+            start: Instant::now(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Moves the clock forward by `duration`, without counting as a sleep.
+    pub fn advance(&self, duration: Duration) {
+        self.state.lock().unwrap().elapsed += duration;
+    }
+
+    /// How far the clock has moved since it was created.
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed
+    }
+
+    /// How many times [sleep](Clock::sleep) was called.
+    pub fn sleeps(&self) -> usize {
+        self.state.lock().unwrap().sleeps
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.elapsed += duration;
+        state.sleeps += 1;
+    }
+}
@@ -0,0 +1,192 @@
+//! Chunk compression formats, looked up by the name stored in each chunk's header.
+//!
+//! `none` and `lz4` are built in, along with `bz2` and `zstd` when the `bz2` and `zstd` features are enabled.
+//! Other formats can be supported by [register]ing a [ChunkDecompressor] before loading a bag,
+//! which also lets a [BagWriter](crate::writer::BagWriter) write them with [Compression::Custom](crate::writer::Compression::Custom).
+//!
+//! Example
+//! ```rust
+//! use std::io;
+//! use std::sync::Arc;
+//! use frost::compression::{self, ChunkContext, ChunkDecompressor};
+//!
+//! /// Stores chunks with their bytes reversed.
+//! struct Reversed;
+//!
+//! impl ChunkDecompressor for Reversed {
+//!     fn name(&self) -> &'static str {
+//!         "reversed"
+//!     }
+//!
+//!     fn decompress(&self, data: &[u8], _chunk: &ChunkContext) -> io::Result<Vec<u8>> {
+//!         Ok(data.iter().rev().copied().collect())
+//!     }
+//!
+//!     fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+//!         Ok(data.iter().rev().copied().collect())
+//!     }
+//! }
+//!
+//! compression::register(Arc::new(Reversed));
+//! assert!(compression::names().contains(&"reversed"));
+//! ```
+use std::io::{self, Read, Write};
+use std::sync::{Arc, RwLock};
+
+#[cfg(feature = "zstd")]
+use crate::dictionary;
+
+static DECOMPRESSORS: RwLock<Vec<Arc<dyn ChunkDecompressor>>> = RwLock::new(Vec::new());
+
+/// Details of the chunk being decompressed, from its header.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkContext<'a> {
+    /// The size of the chunk's data once decompressed.
+    pub uncompressed_size: usize,
+    /// The id of the zstd dictionary the chunk was compressed with, if any.
+    pub dict_id: Option<&'a str>,
+}
+
+/// A chunk compression format, see the [module docs](self).
+pub trait ChunkDecompressor: Send + Sync {
+    /// The name stored in the `compression` field of chunk headers.
+    fn name(&self) -> &'static str;
+
+    fn decompress(&self, data: &[u8], chunk: &ChunkContext) -> io::Result<Vec<u8>>;
+
+    /// Compresses a chunk's data for a [BagWriter](crate::writer::BagWriter).
+    /// Formats which can only be read don't need to implement this.
+    fn compress(&self, _data: &[u8]) -> io::Result<Vec<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "writing '{}' compressed chunks is not supported",
+                self.name()
+            ),
+        ))
+    }
+}
+
+struct NoCompression;
+
+impl ChunkDecompressor for NoCompression {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn decompress(&self, data: &[u8], _chunk: &ChunkContext) -> io::Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+}
+
+struct Lz4;
+
+impl ChunkDecompressor for Lz4 {
+    fn name(&self) -> &'static str {
+        "lz4"
+    }
+
+    fn decompress(&self, data: &[u8], chunk: &ChunkContext) -> io::Result<Vec<u8>> {
+        // chunks are stored in the lz4 frame format
+        let mut decompressed = Vec::with_capacity(chunk.uncompressed_size);
+        lz4_flex::frame::FrameDecoder::new(data).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let frame_info = lz4_flex::frame::FrameInfo::new().content_checksum(true);
+        let mut encoder = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, Vec::new());
+        encoder.write_all(data)?;
+        encoder.finish().map_err(io::Error::from)
+    }
+}
+
+#[cfg(feature = "bz2")]
+struct Bz2;
+
+#[cfg(feature = "bz2")]
+impl ChunkDecompressor for Bz2 {
+    fn name(&self) -> &'static str {
+        "bz2"
+    }
+
+    fn decompress(&self, data: &[u8], chunk: &ChunkContext) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::with_capacity(chunk.uncompressed_size);
+        bzip2::read::BzDecoder::new(data).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::best());
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+}
+
+#[cfg(feature = "zstd")]
+struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl ChunkDecompressor for Zstd {
+    fn name(&self) -> &'static str {
+        "zstd"
+    }
+
+    fn decompress(&self, data: &[u8], chunk: &ChunkContext) -> io::Result<Vec<u8>> {
+        dictionary::decompress(data, chunk.dict_id, chunk.uncompressed_size)
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        dictionary::compress(data, None)
+    }
+}
+
+fn builtins() -> Vec<Arc<dyn ChunkDecompressor>> {
+    vec![
+        Arc::new(NoCompression),
+        Arc::new(Lz4),
+        #[cfg(feature = "bz2")]
+        Arc::new(Bz2),
+        #[cfg(feature = "zstd")]
+        Arc::new(Zstd),
+    ]
+}
+
+/// Makes a compression format available to readers and writers, replacing any registered format of the same name.
+///
+/// Registered formats take precedence over the built in ones, so e.g. `lz4` can be swapped for a faster implementation.
+pub fn register(decompressor: Arc<dyn ChunkDecompressor>) {
+    let mut decompressors = DECOMPRESSORS.write().unwrap();
+    decompressors.retain(|existing| existing.name() != decompressor.name());
+    decompressors.push(decompressor);
+}
+
+/// Returns the compression format stored in chunk headers as `name`, if it is built in or registered.
+pub fn lookup(name: &str) -> Option<Arc<dyn ChunkDecompressor>> {
+    let registered = DECOMPRESSORS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|decompressor| decompressor.name() == name)
+        .cloned();
+    registered.or_else(|| {
+        builtins()
+            .into_iter()
+            .find(|decompressor| decompressor.name() == name)
+    })
+}
+
+/// The names of every built in and registered compression format.
+pub fn names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = builtins().iter().map(|d| d.name()).collect();
+    for decompressor in DECOMPRESSORS.read().unwrap().iter() {
+        if !names.contains(&decompressor.name()) {
+            names.push(decompressor.name());
+        }
+    }
+    names
+}
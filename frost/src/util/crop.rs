@@ -0,0 +1,159 @@
+//! Coarsely cropping a bag to a time range by copying whole chunks, without decompressing anything.
+//!
+//! [BagMetadata::crop_chunks] keeps every chunk whose time range overlaps the crop, copying its compressed (or encrypted)
+//! record byte for byte, and writes new index records pointing at the copies. The result starts and ends on chunk boundaries,
+//! so it can hold up to a chunk's worth of messages on either side of the range; rewrite it with
+//! [BagWriter::copy_messages](crate::writer::BagWriter::copy_messages) for an exact crop.
+//! As only the kept chunks are read, cropping a minute out of a 100 GB recording reads about that minute.
+//!
+//! Example
+//! ```rust,no_run
+//! use std::fs::File;
+//! use std::io::BufWriter;
+//!
+//! use frost::storage::FileStorage;
+//! use frost::time::Time;
+//! use frost::BagMetadata;
+//!
+//! let storage = FileStorage::open("robot.bag").unwrap();
+//! let metadata = BagMetadata::from_storage(&storage).unwrap();
+//! let start = Time { secs: 1700000000, nsecs: 0 };
+//! let end = Time { secs: 1700000060, nsecs: 0 };
+//! let mut out = BufWriter::new(File::create("cropped.bag").unwrap());
+//! let report = metadata.crop_chunks(&storage, start, end, &mut out).unwrap();
+//! println!("kept {} of {} chunks", report.chunk_count, metadata.chunk_count());
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::errors::Error;
+use crate::storage::BagStorage;
+use crate::time::Time;
+use crate::writer::{
+    connection_record, time_bytes, write_bag_header, write_record, HeaderBuilder, VERSION_LINE,
+};
+use crate::{BagMetadata, ChunkHeaderLoc, ChunkMetadata, ConnectionID, IndexData, OpCode};
+
+/// What [BagMetadata::crop_chunks] wrote.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CropReport {
+    pub chunk_count: usize,
+    pub message_count: usize,
+    /// The receive times of the first and last messages written, which can be outside the crop.
+    pub start: Option<Time>,
+    pub end: Option<Time>,
+    /// The bytes of chunk records copied.
+    pub copied_bytes: u64,
+}
+
+impl BagMetadata {
+    /// Writes the chunks of the bag in `storage` overlapping `start..=end` to `writer` as a new bag, see the [crop module](self).
+    ///
+    /// `self` must have been read from `storage`. Connections keep their ids, as the copied chunks refer to them.
+    pub fn crop_chunks<W: Write + Seek>(
+        &self,
+        storage: &dyn BagStorage,
+        start: Time,
+        end: Time,
+        writer: &mut W,
+    ) -> Result<CropReport, Error> {
+        let mut report = CropReport::default();
+        let file_start = writer.stream_position()?;
+        writer.write_all(VERSION_LINE)?;
+        write_bag_header(writer, 0, 0, 0)?;
+
+        let kept: Vec<&ChunkMetadata> = self
+            .chunk_metadata
+            .values()
+            .filter(|chunk| chunk.end_time >= start && chunk.start_time <= end)
+            .collect();
+        let kept_pos: BTreeSet<ChunkHeaderLoc> =
+            kept.iter().map(|chunk| chunk.chunk_header_pos).collect();
+        let mut entries: BTreeMap<(ChunkHeaderLoc, ConnectionID), Vec<&IndexData>> =
+            BTreeMap::new();
+        for data in self.index_data.values().flatten() {
+            if kept_pos.contains(&data.chunk_header_pos) {
+                entries
+                    .entry((data.chunk_header_pos, data.conn_id))
+                    .or_default()
+                    .push(data);
+            }
+        }
+
+        let mut chunk_infos = Vec::new();
+        let mut connections: BTreeSet<ConnectionID> = BTreeSet::new();
+        for chunk in kept {
+            let new_pos = writer.stream_position()? - file_start;
+            let len =
+                chunk.chunk_data_pos + u64::from(chunk.compressed_size) - chunk.chunk_header_pos;
+            writer.write_all(&storage.read_at(chunk.chunk_header_pos, len as usize)?)?;
+
+            for conn_id in chunk.message_counts.keys() {
+                let entries = entries
+                    .remove(&(chunk.chunk_header_pos, *conn_id))
+                    .unwrap_or_default();
+                let mut data = Vec::with_capacity(entries.len() * 12);
+                for entry in entries {
+                    data.extend_from_slice(&time_bytes(&entry.time));
+                    data.extend_from_slice(&entry.offset.to_le_bytes());
+                }
+                let header = HeaderBuilder::op(OpCode::IndexDataHeader)
+                    .field(b"ver", &1u32.to_le_bytes())
+                    .field(b"conn", &conn_id.to_le_bytes())
+                    .field(b"count", &((data.len() / 12) as u32).to_le_bytes())
+                    .finish();
+                write_record(writer, &header, &data)?;
+                report.message_count += data.len() / 12;
+                connections.insert(*conn_id);
+            }
+
+            report.chunk_count += 1;
+            report.copied_bytes += len;
+            report.start = Some(
+                report
+                    .start
+                    .map_or(chunk.start_time, |t| t.min(chunk.start_time)),
+            );
+            report.end = Some(report.end.map_or(chunk.end_time, |t| t.max(chunk.end_time)));
+            chunk_infos.push((new_pos, chunk));
+        }
+
+        let index_pos = writer.stream_position()? - file_start;
+        let connections: Vec<_> = connections
+            .iter()
+            .filter_map(|id| self.connection_data.get(id).map(|data| (*id, data)))
+            .collect();
+        for (id, connection) in connections.iter() {
+            let (header, data) = connection_record(*id, connection);
+            write_record(writer, &header, &data)?;
+        }
+        for (new_pos, chunk) in chunk_infos.iter() {
+            let header = HeaderBuilder::op(OpCode::ChunkInfoHeader)
+                .field(b"ver", &1u32.to_le_bytes())
+                .field(b"chunk_pos", &new_pos.to_le_bytes())
+                .field(b"start_time", &time_bytes(&chunk.start_time))
+                .field(b"end_time", &time_bytes(&chunk.end_time))
+                .field(b"count", &(chunk.message_counts.len() as u32).to_le_bytes())
+                .finish();
+            let mut data = Vec::with_capacity(chunk.message_counts.len() * 8);
+            for (conn_id, count) in chunk.message_counts.iter() {
+                data.extend_from_slice(&conn_id.to_le_bytes());
+                data.extend_from_slice(&count.to_le_bytes());
+            }
+            write_record(writer, &header, &data)?;
+        }
+        let end_pos = writer.stream_position()?;
+
+        writer.seek(SeekFrom::Start(file_start + VERSION_LINE.len() as u64))?;
+        write_bag_header(
+            writer,
+            index_pos,
+            connections.len() as u32,
+            chunk_infos.len() as u32,
+        )?;
+        writer.seek(SeekFrom::Start(end_pos))?;
+        writer.flush()?;
+
+        Ok(report)
+    }
+}
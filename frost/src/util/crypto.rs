@@ -0,0 +1,102 @@
+//! Chunk-level encryption at rest.
+//!
+//! Encrypted chunks carry two extra chunk header fields, `encryption=aes-256-gcm` and `key_id=<id>`.
+//! Their data is a random 96-bit nonce followed by the AES-GCM ciphertext of the (possibly compressed) chunk.
+//! Connection and index records are left in plaintext, so [crate::BagMetadata] and topic/time queries
+//! work without a key; only loading message data requires one.
+//!
+//! Writing and reading encrypted chunks requires the `encryption` feature.
+use std::fmt;
+
+use crate::errors::{EncryptionError, Error};
+
+/// The `encryption` chunk header value written by frost.
+pub const AES_256_GCM: &str = "aes-256-gcm";
+
+#[cfg(feature = "encryption")]
+const NONCE_LEN: usize = 12;
+
+/// A 256-bit AES key and the id recorded in the headers of the chunks it encrypts.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey {
+    id: String,
+    key: [u8; 32],
+}
+
+impl EncryptionKey {
+    pub fn new<S: Into<String>>(id: S, key: [u8; 32]) -> Self {
+        EncryptionKey { id: id.into(), key }
+    }
+
+    /// The id stored in chunk headers, used to pick the right key when reading.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// The encryption fields of a chunk header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ChunkEncryption {
+    pub(crate) algorithm: String,
+    pub(crate) key_id: String,
+}
+
+#[cfg(feature = "encryption")]
+pub(crate) fn encrypt(key: &EncryptionKey, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+    use aes_gcm::Aes256Gcm;
+
+    let cipher = Aes256Gcm::new(&key.key.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| Error::from(EncryptionError::Authentication))?;
+
+    let mut data = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// Decrypts a chunk's data with the key matching its header's key id.
+pub(crate) fn decrypt(
+    encryption: &ChunkEncryption,
+    keys: &[EncryptionKey],
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    if encryption.algorithm != AES_256_GCM {
+        return Err(EncryptionError::UnsupportedAlgorithm(encryption.algorithm.clone()).into());
+    }
+    let key = keys
+        .iter()
+        .find(|key| key.id == encryption.key_id)
+        .ok_or_else(|| EncryptionError::MissingKey(encryption.key_id.clone()))?;
+
+    #[cfg(feature = "encryption")]
+    {
+        use aes_gcm::aead::{Aead, KeyInit};
+        use aes_gcm::{Aes256Gcm, Nonce};
+
+        if data.len() < NONCE_LEN {
+            return Err(EncryptionError::Authentication.into());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        Aes256Gcm::new(&key.key.into())
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| EncryptionError::Authentication.into())
+    }
+    #[cfg(not(feature = "encryption"))]
+    {
+        let _ = (key, data);
+        Err(EncryptionError::UnsupportedAlgorithm(encryption.algorithm.clone()).into())
+    }
+}
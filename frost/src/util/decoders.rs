@@ -0,0 +1,107 @@
+//! Custom decoding for messages whose payloads aren't plain rosmsg, like proprietary compressed data inside a
+//! `std_msgs/ByteMultiArray`.
+//!
+//! A decoder registered for a topic or a message type is called by [MessageView::decode] instead of decoding the
+//! message with the definition recorded with its connection, so everything built on it, like `frost echo`, exports
+//! and [Query::with_field](crate::query::Query::with_field), sees the decoded [Value]. Decoders registered for a
+//! topic take precedence over those registered for the topic's type. A decoder can start from the standard decoding
+//! with [MessageView::decode_standard] and only replace the fields it understands.
+//!
+//! [MessageView::instantiate] deserializes the recorded bytes and is not affected by decoders.
+//!
+//! Example
+//! ```rust
+//! use frost::decoders;
+//! use frost::dynamic::Value;
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! // /chatter's strings are stored reversed by some in-house recorder
+//! decoders::register_topic("/chatter", |msg_view| {
+//!     let value = msg_view.decode_standard()?;
+//!     let data = value.get("data").and_then(Value::as_str).unwrap_or_default();
+//!     Ok(Value::Message(vec![(
+//!         "data".to_string(),
+//!         Value::String(data.chars().rev().collect()),
+//!     )]))
+//! });
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let query = Query::new().with_topics(["/chatter"]);
+//! let msg_view = bag.read_messages(&query).unwrap().next().unwrap();
+//! assert!(msg_view.decode().unwrap().get("data").unwrap().as_str().unwrap().ends_with("_oof"));
+//! # decoders::unregister_topic("/chatter");
+//! ```
+use std::sync::{Arc, RwLock};
+
+use crate::dynamic::Value;
+use crate::errors::Error;
+use crate::msgs::MessageView;
+
+/// A function decoding a message in place of the standard rosmsg decoding, see the [module docs](self).
+pub type Decoder = Arc<dyn Fn(&MessageView) -> Result<Value, Error> + Send + Sync>;
+
+#[derive(PartialEq, Eq)]
+enum Key {
+    Topic(String),
+    Type(String),
+}
+
+static DECODERS: RwLock<Vec<(Key, Decoder)>> = RwLock::new(Vec::new());
+
+fn register(key: Key, decoder: Decoder) {
+    let mut decoders = DECODERS.write().unwrap();
+    decoders.retain(|(existing, _)| *existing != key);
+    decoders.push((key, decoder));
+}
+
+fn unregister(key: Key) -> bool {
+    let mut decoders = DECODERS.write().unwrap();
+    let len = decoders.len();
+    decoders.retain(|(existing, _)| *existing != key);
+    decoders.len() != len
+}
+
+fn find(key: &Key) -> Option<Decoder> {
+    DECODERS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(existing, _)| existing == key)
+        .map(|(_, decoder)| decoder.clone())
+}
+
+/// Decodes the messages on `topic` with `decoder`, replacing any decoder registered for the topic.
+pub fn register_topic<F>(topic: &str, decoder: F)
+where
+    F: Fn(&MessageView) -> Result<Value, Error> + Send + Sync + 'static,
+{
+    register(Key::Topic(topic.to_string()), Arc::new(decoder));
+}
+
+/// Decodes the messages of type `data_type`, e.g. `std_msgs/ByteMultiArray`, with `decoder`, replacing any decoder
+/// registered for the type.
+pub fn register_type<F>(data_type: &str, decoder: F)
+where
+    F: Fn(&MessageView) -> Result<Value, Error> + Send + Sync + 'static,
+{
+    register(Key::Type(data_type.to_string()), Arc::new(decoder));
+}
+
+/// Goes back to decoding the messages on `topic` normally, returning false if it had no decoder.
+pub fn unregister_topic(topic: &str) -> bool {
+    unregister(Key::Topic(topic.to_string()))
+}
+
+/// Goes back to decoding messages of type `data_type` normally, returning false if it had no decoder.
+pub fn unregister_type(data_type: &str) -> bool {
+    unregister(Key::Type(data_type.to_string()))
+}
+
+/// Returns the decoder for messages on `topic` of type `data_type`, if either has one registered.
+pub fn lookup(topic: &str, data_type: &str) -> Option<Decoder> {
+    if DECODERS.read().unwrap().is_empty() {
+        return None;
+    }
+    find(&Key::Topic(topic.to_string())).or_else(|| find(&Key::Type(data_type.to_string())))
+}
@@ -0,0 +1,116 @@
+//! Dropping consecutive duplicate messages, like latched topics republished at a high rate with the same contents.
+//!
+//! A [Dedup] compares each message on the topics it was set up for with the previous message on the same topic,
+//! either by its serialized bytes or by selected fields, ignoring others like a header's stamp or sequence number.
+//! It filters messages while iterating over a bag, and [Retention::drop_duplicates](crate::writer::Retention::drop_duplicates)
+//! applies the same rules when rewriting one.
+//!
+//! Example
+//! ```rust
+//! use frost::dedup::Dedup;
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//!
+//! // every message on /array holds the same data
+//! let mut dedup = Dedup::new().with_topic("/array");
+//! let arrays = bag
+//!     .read_messages(&Query::new().with_topics(["/array"]))
+//!     .unwrap()
+//!     .filter(|msg_view| !dedup.is_duplicate(msg_view));
+//! assert_eq!(arrays.count(), 1);
+//! ```
+use std::collections::{BTreeMap, HashMap};
+
+use crate::dynamic::Value;
+use crate::msgs::MessageView;
+
+/// What makes two messages on a topic duplicates, see [Dedup].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DedupKey {
+    /// Identical serialized bytes.
+    Payload,
+    /// Identical values at these `.` separated field paths, see [Value::get_path].
+    Fields(Vec<String>),
+}
+
+/// The last message seen on a topic, as compared by its [DedupKey].
+#[derive(PartialEq)]
+enum Seen {
+    Payload(Vec<u8>),
+    Fields(Vec<Value>),
+}
+
+/// Finds consecutive duplicate messages on some topics, see the [module docs](self).
+#[derive(Default)]
+pub struct Dedup {
+    keys: BTreeMap<String, DedupKey>,
+    last: HashMap<String, Seen>,
+}
+
+impl Dedup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops messages on `topic` identical to the previous one.
+    pub fn with_topic<S: Into<String>>(self, topic: S) -> Self {
+        self.with_key(topic, DedupKey::Payload)
+    }
+
+    /// Drops messages on `topic` whose `fields` are identical to those of the previous one, whatever their other
+    /// fields are.
+    pub fn with_fields<S, F, I>(self, topic: S, fields: I) -> Self
+    where
+        S: Into<String>,
+        F: Into<String>,
+        I: IntoIterator<Item = F>,
+    {
+        let fields = fields.into_iter().map(Into::into).collect();
+        self.with_key(topic, DedupKey::Fields(fields))
+    }
+
+    /// Compares messages on `topic` by `key`, replacing any key set for the topic.
+    pub fn with_key<S: Into<String>>(mut self, topic: S, key: DedupKey) -> Self {
+        self.keys.insert(topic.into(), key);
+        self
+    }
+
+    pub(crate) fn from_keys(keys: BTreeMap<String, DedupKey>) -> Self {
+        Dedup {
+            keys,
+            last: HashMap::new(),
+        }
+    }
+
+    /// Returns true if the message is a duplicate of the last message passed in on its topic, remembering it
+    /// otherwise. Messages on other topics, and messages which fail to decode or are missing a field, are never
+    /// duplicates.
+    pub fn is_duplicate(&mut self, msg_view: &MessageView) -> bool {
+        let seen = match self.keys.get(msg_view.topic) {
+            None => return false,
+            Some(DedupKey::Payload) => Seen::Payload(msg_view.data_bytes().to_vec()),
+            Some(DedupKey::Fields(fields)) => {
+                let values = msg_view.decode().ok().and_then(|value| {
+                    fields
+                        .iter()
+                        .map(|path| value.get_path(path).cloned())
+                        .collect::<Option<Vec<Value>>>()
+                });
+                match values {
+                    Some(values) => Seen::Fields(values),
+                    None => {
+                        self.last.remove(msg_view.topic);
+                        return false;
+                    }
+                }
+            }
+        };
+        if self.last.get(msg_view.topic) == Some(&seen) {
+            return true;
+        }
+        self.last.insert(msg_view.topic.to_string(), seen);
+        false
+    }
+}
@@ -0,0 +1,241 @@
+//! Summarizing `diagnostic_msgs/DiagnosticArray` messages into a per-component status report.
+//!
+//! Each status is attributed to its component (the status `name`) from the time its array was received
+//! until the component's next status, or until the last array for its final status. The report gives how long
+//! each component spent at each level and when it first warned and failed.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::diagnostics::DiagnosticLevel;
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("robot.bag").unwrap();
+//! let report = bag.diagnostics(&Query::new().with_topics(["/diagnostics"])).unwrap();
+//! for (name, component) in &report.components {
+//!     if let Some((time, message)) = &component.first_error {
+//!         println!("{name} failed at {time}: {message}");
+//!     }
+//!     println!("{name} was in error for {:?}", component.time_in(DiagnosticLevel::Error));
+//! }
+//! ```
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::dynamic::{required, required_f64, Value};
+use crate::errors::{DecodeError, Error};
+use crate::query::Query;
+use crate::time::Time;
+use crate::DecompressedBag;
+
+const DIAGNOSTIC_ARRAY_TYPE: &str = "diagnostic_msgs/DiagnosticArray";
+const DIAGNOSTIC_STATUS_TYPE: &str = "diagnostic_msgs/DiagnosticStatus";
+
+/// The level of a `diagnostic_msgs/DiagnosticStatus`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiagnosticLevel {
+    Ok,
+    Warn,
+    Error,
+    Stale,
+}
+
+impl DiagnosticLevel {
+    pub const ALL: [DiagnosticLevel; 4] = [
+        DiagnosticLevel::Ok,
+        DiagnosticLevel::Warn,
+        DiagnosticLevel::Error,
+        DiagnosticLevel::Stale,
+    ];
+
+    /// Returns the level of a status's `level` byte, or `None` if it is not one of the defined levels.
+    pub fn from_byte(level: i64) -> Option<DiagnosticLevel> {
+        match level {
+            0 => Some(DiagnosticLevel::Ok),
+            1 => Some(DiagnosticLevel::Warn),
+            2 => Some(DiagnosticLevel::Error),
+            3 => Some(DiagnosticLevel::Stale),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticLevel::Ok => write!(f, "OK"),
+            DiagnosticLevel::Warn => write!(f, "WARN"),
+            DiagnosticLevel::Error => write!(f, "ERROR"),
+            DiagnosticLevel::Stale => write!(f, "STALE"),
+        }
+    }
+}
+
+/// The history of one diagnostic component, see [DiagnosticsReport].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComponentSummary {
+    /// The `hardware_id` of the component's latest status.
+    pub hardware_id: String,
+    /// The number of statuses reported for the component.
+    pub status_count: usize,
+    pub first_seen: Time,
+    pub last_seen: Time,
+    /// The level and message of the component's latest status.
+    pub last_level: DiagnosticLevel,
+    pub last_message: String,
+    /// The time and message of the first status at each level.
+    pub first_warn: Option<(Time, String)>,
+    pub first_error: Option<(Time, String)>,
+    pub first_stale: Option<(Time, String)>,
+    durations: [Duration; 4],
+}
+
+impl ComponentSummary {
+    /// Returns how long the component spent at `level`.
+    pub fn time_in(&self, level: DiagnosticLevel) -> Duration {
+        self.durations[level as usize]
+    }
+
+    /// Returns the most severe level the component reached, with `Stale` ranked above `Error`.
+    pub fn worst_level(&self) -> DiagnosticLevel {
+        if self.first_stale.is_some() {
+            DiagnosticLevel::Stale
+        } else if self.first_error.is_some() {
+            DiagnosticLevel::Error
+        } else if self.first_warn.is_some() {
+            DiagnosticLevel::Warn
+        } else {
+            DiagnosticLevel::Ok
+        }
+    }
+}
+
+/// Per-component summaries of diagnostics, keyed by component name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiagnosticsReport {
+    pub components: BTreeMap<String, ComponentSummary>,
+    /// The number of `diagnostic_msgs/DiagnosticArray` messages read.
+    pub message_count: usize,
+    /// The receive times of the first and last arrays, or `None` if there were none.
+    pub start: Option<Time>,
+    pub end: Option<Time>,
+}
+
+impl DiagnosticsReport {
+    /// Returns the components that were ever not OK, most severe first.
+    pub fn failures(&self) -> Vec<(&str, &ComponentSummary)> {
+        let mut failures: Vec<_> = self
+            .components
+            .iter()
+            .filter(|(_, component)| component.worst_level() != DiagnosticLevel::Ok)
+            .map(|(name, component)| (name.as_str(), component))
+            .collect();
+        failures.sort_by_key(|(_, component)| std::cmp::Reverse(component.worst_level()));
+        failures
+    }
+}
+
+/// Builds a [DiagnosticsReport] from arrays pushed in receive time order.
+#[derive(Debug, Default)]
+pub struct DiagnosticsAggregator {
+    report: DiagnosticsReport,
+}
+
+impl DiagnosticsAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the statuses of a `diagnostic_msgs/DiagnosticArray` decoded with [crate::dynamic], received at `time`.
+    pub fn push_value(&mut self, time: Time, value: &Value) -> Result<(), DecodeError> {
+        let Value::Array(statuses) = required(value, DIAGNOSTIC_ARRAY_TYPE, "status")? else {
+            return Err(DecodeError::InvalidField(format!(
+                "{DIAGNOSTIC_ARRAY_TYPE}.status is not an array"
+            )));
+        };
+        let report = &mut self.report;
+        report.message_count += 1;
+        report.start.get_or_insert(time);
+        report.end = Some(time);
+
+        for status in statuses {
+            let string = |field| {
+                required(status, DIAGNOSTIC_STATUS_TYPE, field)
+                    .map(|value| value.as_str().unwrap_or_default().to_string())
+            };
+            let raw_level = required_f64(status, DIAGNOSTIC_STATUS_TYPE, "level")? as i64;
+            let level = DiagnosticLevel::from_byte(raw_level).ok_or_else(|| {
+                DecodeError::InvalidField(format!(
+                    "{DIAGNOSTIC_STATUS_TYPE}.level {raw_level} is not a known level"
+                ))
+            })?;
+            let name = string("name")?;
+            let message = string("message")?;
+            let hardware_id = string("hardware_id")?;
+
+            let component = report
+                .components
+                .entry(name)
+                .or_insert_with(|| ComponentSummary {
+                    hardware_id: String::new(),
+                    status_count: 0,
+                    first_seen: time,
+                    last_seen: time,
+                    last_level: level,
+                    last_message: String::new(),
+                    first_warn: None,
+                    first_error: None,
+                    first_stale: None,
+                    durations: Default::default(),
+                });
+            if time > component.last_seen {
+                component.durations[component.last_level as usize] +=
+                    time.duration_since(&component.last_seen);
+            }
+            let first = match level {
+                DiagnosticLevel::Ok => None,
+                DiagnosticLevel::Warn => Some(&mut component.first_warn),
+                DiagnosticLevel::Error => Some(&mut component.first_error),
+                DiagnosticLevel::Stale => Some(&mut component.first_stale),
+            };
+            if let Some(first) = first {
+                first.get_or_insert_with(|| (time, message.clone()));
+            }
+            component.status_count += 1;
+            component.last_seen = time;
+            component.last_level = level;
+            component.last_message = message;
+            component.hardware_id = hardware_id;
+        }
+        Ok(())
+    }
+
+    /// Returns the report, attributing the time after each component's final status to that status's level.
+    pub fn finish(mut self) -> DiagnosticsReport {
+        if let Some(end) = self.report.end {
+            for component in self.report.components.values_mut() {
+                if end <= component.last_seen {
+                    continue;
+                }
+                component.durations[component.last_level as usize] +=
+                    end.duration_since(&component.last_seen);
+            }
+        }
+        self.report
+    }
+}
+
+impl DecompressedBag {
+    /// Summarizes the `diagnostic_msgs/DiagnosticArray` messages matching `query`, skipping messages of other types.
+    pub fn diagnostics(&self, query: &Query) -> Result<DiagnosticsReport, Error> {
+        let mut aggregator = DiagnosticsAggregator::new();
+        for msg_view in self.filter_messages(query)? {
+            if msg_view.connection().data_type != DIAGNOSTIC_ARRAY_TYPE {
+                continue;
+            }
+            aggregator.push_value(msg_view.time, &msg_view.decode()?)?;
+        }
+        Ok(aggregator.finish())
+    }
+}
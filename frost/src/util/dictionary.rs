@@ -0,0 +1,194 @@
+//! zstd dictionaries trained on the messages of many bags.
+//!
+//! Bags recorded across a fleet share the same topics, types and field layouts,
+//! so a dictionary trained on a sample of their messages lets zstd compress each chunk noticeably better.
+//! Chunks compressed with a dictionary record its id in a `dict_id` chunk header field,
+//! and the dictionary must be [register]ed before such a bag is loaded into a [DecompressedBag].
+//!
+//! Example
+//! ```rust
+//! use std::io::Cursor;
+//! use std::sync::Arc;
+//! use frost::dictionary::{self, DictionaryTrainer};
+//! use frost::query::Query;
+//! use frost::writer::BagWriter;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/decompressed.bag").unwrap();
+//! let mut trainer = DictionaryTrainer::new(4096);
+//! trainer.add_bag(&bag).unwrap();
+//! let dict = Arc::new(trainer.train().unwrap());
+//!
+//! let mut writer = BagWriter::new(Cursor::new(Vec::new()))
+//!     .unwrap()
+//!     .with_dictionary(dict.clone());
+//! writer.copy_messages(&bag, &Query::all()).unwrap();
+//! let bytes = writer.finish().unwrap().into_inner();
+//!
+//! dictionary::register(dict);
+//! let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+//! ```
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use sha2::{Digest, Sha256};
+
+use crate::errors::Error;
+use crate::query::Query;
+use crate::DecompressedBag;
+
+/// zstd's default compression level
+pub(crate) const LEVEL: i32 = 3;
+
+/// zstd recommends training on roughly 100 times as many bytes as the dictionary's size,
+/// so sampling a tenth of that per bag gets there with a fleet of ten or more bags
+const SAMPLE_BYTES_PER_DICT_BYTE: usize = 10;
+
+static DICTIONARIES: RwLock<Vec<Arc<Dictionary>>> = RwLock::new(Vec::new());
+
+/// A trained zstd dictionary, identified by a hash of its contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dictionary {
+    id: String,
+    bytes: Vec<u8>,
+}
+
+/// Samples messages from bags one at a time, so a fleet's worth of bags never has to be loaded at once.
+pub struct DictionaryTrainer {
+    max_size: usize,
+    samples: Vec<u8>,
+    sample_sizes: Vec<usize>,
+    bag_count: usize,
+}
+
+impl DictionaryTrainer {
+    /// Creates a trainer for a dictionary of at most `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        DictionaryTrainer {
+            max_size,
+            samples: Vec::new(),
+            sample_sizes: Vec::new(),
+            bag_count: 0,
+        }
+    }
+
+    /// Samples messages evenly across a bag, taking about 10 times the dictionary's size from it.
+    pub fn add_bag(&mut self, bag: &DecompressedBag) -> Result<(), Error> {
+        let budget = self.max_size * SAMPLE_BYTES_PER_DICT_BYTE;
+        let total: usize = bag
+            .read_messages(&Query::all())?
+            .map(|msg_view| msg_view.data_bytes().len())
+            .sum();
+        let stride = (total / budget.max(1)).max(1);
+        for msg_view in bag.read_messages(&Query::all())?.step_by(stride) {
+            self.samples.extend_from_slice(msg_view.data_bytes());
+            self.sample_sizes.push(msg_view.data_bytes().len());
+        }
+        self.bag_count += 1;
+        Ok(())
+    }
+
+    /// The number of bags sampled so far.
+    pub fn bag_count(&self) -> usize {
+        self.bag_count
+    }
+
+    /// The number of messages sampled so far.
+    pub fn sample_count(&self) -> usize {
+        self.sample_sizes.len()
+    }
+
+    pub fn train(&self) -> Result<Dictionary, Error> {
+        if self.sample_sizes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no messages to train a dictionary on",
+            )
+            .into());
+        }
+        let bytes = zstd::dict::from_continuous(&self.samples, &self.sample_sizes, self.max_size)?;
+        Ok(Dictionary::from_bytes(bytes))
+    }
+}
+
+impl Dictionary {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let id = Sha256::digest(&bytes)[..8]
+            .iter()
+            .fold(String::new(), |mut id, byte| {
+                let _ = write!(id, "{byte:02x}");
+                id
+            });
+        Dictionary { id, bytes }
+    }
+
+    /// Reads a dictionary saved with [Dictionary::save].
+    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
+        Ok(Self::from_bytes(fs::read(file_path)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, file_path: P) -> Result<(), Error> {
+        Ok(fs::write(file_path, &self.bytes)?)
+    }
+
+    /// The id stored in the headers of chunks compressed with this dictionary.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Makes a dictionary available for decompressing chunks when loading a [DecompressedBag].
+pub fn register(dictionary: Arc<Dictionary>) {
+    let mut dictionaries = DICTIONARIES.write().unwrap();
+    if !dictionaries
+        .iter()
+        .any(|existing| existing.id == dictionary.id)
+    {
+        dictionaries.push(dictionary);
+    }
+}
+
+pub(crate) fn lookup(id: &str) -> Option<Arc<Dictionary>> {
+    DICTIONARIES
+        .read()
+        .unwrap()
+        .iter()
+        .find(|dictionary| dictionary.id == id)
+        .cloned()
+}
+
+pub(crate) fn compress(data: &[u8], dictionary: Option<&Dictionary>) -> io::Result<Vec<u8>> {
+    match dictionary {
+        Some(dictionary) => {
+            zstd::bulk::Compressor::with_dictionary(LEVEL, &dictionary.bytes)?.compress(data)
+        }
+        None => zstd::bulk::compress(data, LEVEL),
+    }
+}
+
+pub(crate) fn decompress(
+    data: &[u8],
+    dict_id: Option<&str>,
+    uncompressed_size: usize,
+) -> io::Result<Vec<u8>> {
+    match dict_id {
+        Some(id) => {
+            let dictionary = lookup(id).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("zstd dictionary '{id}' has not been registered"),
+                )
+            })?;
+            zstd::bulk::Decompressor::with_dictionary(&dictionary.bytes)?
+                .decompress(data, uncompressed_size)
+        }
+        None => zstd::bulk::decompress(data, uncompressed_size),
+    }
+}
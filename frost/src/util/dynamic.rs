@@ -0,0 +1,1425 @@
+//! Decoding messages without generated types, using the message definitions stored in a bag's connections,
+//! and encoding [Value]s built at runtime back into messages with [Schema::encode].
+//!
+//! Example
+//! ```rust
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//!
+//! let query = Query::new().with_topics(["/chatter"]);
+//! for msg_view in bag.read_messages(&query).unwrap() {
+//!     let value = msg_view.decode().unwrap();
+//!     assert!(value.get("data").unwrap().as_str().unwrap().starts_with("foo_"));
+//! }
+//! ```
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+use crate::errors::{DecodeError, Error};
+use crate::time::Time;
+
+/// The line separating the definitions of dependencies in a connection's `message_definition`
+pub(crate) const DEFINITION_SEPARATOR: &str =
+    "================================================================================";
+
+/// A built-in ROS field type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    Bool,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+    String,
+    Time,
+    Duration,
+}
+
+impl Primitive {
+    pub(crate) fn from_name(name: &str) -> Option<Primitive> {
+        Some(match name {
+            "bool" => Primitive::Bool,
+            // `byte` and `char` are deprecated aliases
+            "int8" | "byte" => Primitive::Int8,
+            "uint8" | "char" => Primitive::UInt8,
+            "int16" => Primitive::Int16,
+            "uint16" => Primitive::UInt16,
+            "int32" => Primitive::Int32,
+            "uint32" => Primitive::UInt32,
+            "int64" => Primitive::Int64,
+            "uint64" => Primitive::UInt64,
+            "float32" => Primitive::Float32,
+            "float64" => Primitive::Float64,
+            "string" => Primitive::String,
+            "time" => Primitive::Time,
+            "duration" => Primitive::Duration,
+            _ => return None,
+        })
+    }
+
+    /// The name of the type in message definitions.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Primitive::Bool => "bool",
+            Primitive::Int8 => "int8",
+            Primitive::UInt8 => "uint8",
+            Primitive::Int16 => "int16",
+            Primitive::UInt16 => "uint16",
+            Primitive::Int32 => "int32",
+            Primitive::UInt32 => "uint32",
+            Primitive::Int64 => "int64",
+            Primitive::UInt64 => "uint64",
+            Primitive::Float32 => "float32",
+            Primitive::Float64 => "float64",
+            Primitive::String => "string",
+            Primitive::Time => "time",
+            Primitive::Duration => "duration",
+        }
+    }
+
+    /// The smallest number of bytes a value of this type is serialized to.
+    pub(crate) fn min_size(&self) -> usize {
+        match self {
+            Primitive::Bool | Primitive::Int8 | Primitive::UInt8 => 1,
+            Primitive::Int16 | Primitive::UInt16 => 2,
+            Primitive::Int32 | Primitive::UInt32 | Primitive::Float32 | Primitive::String => 4,
+            Primitive::Int64
+            | Primitive::UInt64
+            | Primitive::Float64
+            | Primitive::Time
+            | Primitive::Duration => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Primitive(Primitive),
+    /// A nested message, by its full `package/Name` type.
+    Message(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cardinality {
+    Single,
+    /// A fixed length array like `float64[9]`.
+    Fixed(usize),
+    /// A variable length array like `float64[]`.
+    Variable,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub field_type: FieldType,
+    pub cardinality: Cardinality,
+}
+
+/// A constant declared in a message definition, like `uint8 DEBUG=1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Constant {
+    pub name: String,
+    pub field_type: Primitive,
+    pub value: String,
+}
+
+/// The fields and constants of a single message type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageDefinition {
+    pub data_type: String,
+    pub fields: Vec<Field>,
+    pub constants: Vec<Constant>,
+}
+
+/// How deeply [Schema::fixed_size] looks into nested messages.
+const MAX_DEPTH: usize = 64;
+
+/// A step of the program a [Schema] decodes messages with.
+///
+/// Definitions are compiled when parsed into a flat program with nested messages inlined, so decoding doesn't look up
+/// definitions by type or walk the definition tree. Fields are decoded in order, pushing their values on a stack.
+/// Fields left out by a [projection](Schema::project) are skipped over without being decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Op {
+    /// Reads a single primitive.
+    Read(Primitive),
+    /// Reads a `uint8` array of the given length, or of a length read first.
+    Bytes(Option<usize>),
+    /// Reads an array of the given length, or of a length read first, running the next `body` ops for each element.
+    /// The elements are collected into an array unless they are being skipped.
+    Array {
+        len: Option<usize>,
+        body: usize,
+        /// The smallest size of an element, to catch corrupt lengths before allocating for them.
+        min_size: usize,
+        skip: bool,
+    },
+    /// Collects the last values into a message, with fields named by this range of the schema's `names`.
+    Message(Range<usize>),
+    /// Skips fields of a fixed size.
+    Skip(usize),
+    /// Skips a string.
+    SkipString,
+    /// Skips an array of elements of a fixed size, with the given length or a length read first.
+    SkipElements { len: Option<usize>, size: usize },
+}
+
+/// The fields of a message to decode, see [Schema::project].
+#[derive(Debug, Default)]
+struct Projection {
+    /// Whether every field is decoded.
+    all: bool,
+    fields: BTreeMap<String, Projection>,
+}
+
+impl Projection {
+    fn all() -> Self {
+        Projection {
+            all: true,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    fn from_paths<S: AsRef<str>>(paths: &[S]) -> Self {
+        let mut projection = Projection::default();
+        for path in paths {
+            let mut current = &mut projection;
+            for segment in path.as_ref().split('.') {
+                current = current.fields.entry(segment.to_string()).or_default();
+            }
+            current.all = true;
+        }
+        projection
+    }
+}
+
+/// Caps on the lengths a message's fields can claim, so a corrupt length field fails decoding with
+/// [DecodeError::LimitExceeded] rather than allocating, or looping over, whatever it claims.
+///
+/// Lengths are always checked against the bytes left in the message. These catch lengths which fit but are
+/// implausible, like a huge count of empty messages, and bound the work done on untrusted bags. Set them on a
+/// [Query](crate::query::Query) with [with_decode_limits](crate::query::Query::with_decode_limits) to apply them to
+/// both [MessageView::decode](crate::msgs::MessageView::decode) and
+/// [MessageView::instantiate](crate::msgs::MessageView::instantiate).
+///
+/// Example
+/// ```rust
+/// use frost::dynamic::{DecodeLimits, Schema};
+///
+/// let schema = Schema::parse("std_msgs/String", "string data").unwrap();
+/// let limits = DecodeLimits::new().with_max_string_len(4);
+/// assert!(schema.decode_with_limits(b"\x03\x00\x00\x00foo", &limits).is_ok());
+/// assert!(schema.decode_with_limits(b"\x05\x00\x00\x00fooba", &limits).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeLimits {
+    max_string_len: Option<usize>,
+    max_array_len: Option<usize>,
+    max_message_len: Option<usize>,
+}
+
+impl DecodeLimits {
+    /// No limits beyond the size of each message.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the bytes of each string.
+    pub fn with_max_string_len(mut self, len: usize) -> Self {
+        self.max_string_len = Some(len);
+        self
+    }
+
+    /// Limits the elements of each variable length array, including `uint8[]`.
+    pub fn with_max_array_len(mut self, len: usize) -> Self {
+        self.max_array_len = Some(len);
+        self
+    }
+
+    /// Limits the serialized size of each message.
+    pub fn with_max_message_len(mut self, len: usize) -> Self {
+        self.max_message_len = Some(len);
+        self
+    }
+
+    /// Whether any limit is set.
+    pub fn is_limited(&self) -> bool {
+        *self != Self::default()
+    }
+
+    fn check_message(&self, len: usize) -> Result<(), Error> {
+        check_limit("a message", len, "bytes", self.max_message_len, 0)
+    }
+
+    /// Reads the length of a string, checking it against the limit.
+    fn string_len(&self, reader: &mut Reader) -> Result<usize, Error> {
+        let pos = reader.pos;
+        let len = reader.u32()? as usize;
+        check_limit("a string", len, "bytes", self.max_string_len, pos)?;
+        Ok(len)
+    }
+
+    /// Returns the length of an array, reading it first if it isn't fixed, and checking it against the limit.
+    fn array_len(&self, len: Option<usize>, reader: &mut Reader) -> Result<usize, Error> {
+        if let Some(len) = len {
+            return Ok(len);
+        }
+        let pos = reader.pos;
+        let len = reader.u32()? as usize;
+        check_limit("an array", len, "elements", self.max_array_len, pos)?;
+        Ok(len)
+    }
+}
+
+fn check_limit(
+    what: &str,
+    len: usize,
+    unit: &str,
+    limit: Option<usize>,
+    pos: usize,
+) -> Result<(), Error> {
+    match limit {
+        Some(limit) if len > limit => Err(DecodeError::LimitExceeded(format!(
+            "{what} of {len} {unit} at byte {pos} is over the limit of {limit}"
+        ))
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// A message type and the definitions of every message type it depends on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schema {
+    data_type: String,
+    definitions: BTreeMap<String, MessageDefinition>,
+    program: Vec<Op>,
+    /// The field names of every message in `program`.
+    names: Vec<String>,
+}
+
+impl Schema {
+    /// Parses the `message_definition` of a connection with type `data_type`.
+    pub fn parse(data_type: &str, message_definition: &str) -> Result<Schema, Error> {
+        let mut definitions = BTreeMap::new();
+
+        let mut current_type = data_type.to_string();
+        let mut lines = Vec::new();
+        let mut source = message_definition.lines();
+        while let Some(line) = source.next() {
+            if line.trim() == DEFINITION_SEPARATOR {
+                let definition = parse_definition(&current_type, &lines)?;
+                definitions.insert(current_type, definition);
+                lines.clear();
+
+                let next = source.next().unwrap_or_default().trim();
+                current_type = next
+                    .strip_prefix("MSG:")
+                    .map(|data_type| data_type.trim().to_string())
+                    .ok_or_else(|| {
+                        DecodeError::InvalidDefinition(format!(
+                            "expected 'MSG: <type>' after a separator, got '{next}'"
+                        ))
+                    })?;
+            } else {
+                lines.push(line);
+            }
+        }
+        let definition = parse_definition(&current_type, &lines)?;
+        definitions.insert(current_type, definition);
+
+        let mut schema = Schema {
+            data_type: data_type.to_string(),
+            definitions,
+            program: Vec::new(),
+            names: Vec::new(),
+        };
+        for definition in schema.definitions.values() {
+            for field in definition.fields.iter() {
+                if let FieldType::Message(data_type) = &field.field_type {
+                    if !schema.definitions.contains_key(data_type) {
+                        return Err(DecodeError::UnknownType(data_type.clone()).into());
+                    }
+                }
+            }
+        }
+        schema.compile(&Projection::all())?;
+        Ok(schema)
+    }
+
+    /// Returns a schema which only decodes the fields at the given `.` separated paths, skipping over the others
+    /// without decoding them. For example, `["header.stamp", "height"]` decodes a `sensor_msgs/Image` into a
+    /// message with a header holding only its stamp, and the image's height, without copying the pixel data.
+    ///
+    /// Paths into arrays of messages select the fields of every element, and a path selecting a whole message
+    /// includes all of its fields. Fails if a path names a field which doesn't exist.
+    pub fn project<S: AsRef<str>>(&self, paths: &[S]) -> Result<Schema, Error> {
+        let mut schema = self.clone();
+        schema.compile(&Projection::from_paths(paths))?;
+        Ok(schema)
+    }
+
+    fn compile(&mut self, projection: &Projection) -> Result<(), Error> {
+        let mut compiler = Compiler {
+            schema: self,
+            program: Vec::new(),
+            names: Vec::new(),
+            parents: Vec::new(),
+            merge_start: 0,
+        };
+        compiler.message(&self.data_type, Some(projection))?;
+        let (program, names) = (compiler.program, compiler.names);
+        self.program = program;
+        self.names = names;
+        Ok(())
+    }
+
+    /// The size of every value of a type, if it is fixed. `depth` limits nesting, so definitions which contain
+    /// themselves are left for the compiler to reject, as are sizes which overflow.
+    fn fixed_size(&self, field_type: &FieldType, depth: usize) -> Option<usize> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match field_type {
+            FieldType::Primitive(Primitive::String) => None,
+            FieldType::Primitive(primitive) => Some(primitive.min_size()),
+            FieldType::Message(data_type) => {
+                let definition = self.definitions.get(data_type)?;
+                definition.fields.iter().try_fold(0usize, |size, field| {
+                    let count = match field.cardinality {
+                        Cardinality::Single => 1,
+                        Cardinality::Fixed(len) => len,
+                        Cardinality::Variable => return None,
+                    };
+                    let field_size = self.fixed_size(&field.field_type, depth + 1)?;
+                    size.checked_add(count.checked_mul(field_size)?)
+                })
+            }
+        }
+    }
+
+    /// The type of the messages this schema decodes.
+    pub fn data_type(&self) -> &str {
+        &self.data_type
+    }
+
+    /// The definition of the top level message type.
+    pub fn root(&self) -> &MessageDefinition {
+        &self.definitions[&self.data_type]
+    }
+
+    /// The definition of the top level message type or any type it depends on.
+    pub fn definition(&self, data_type: &str) -> Option<&MessageDefinition> {
+        self.definitions.get(data_type)
+    }
+
+    /// Decodes a serialized message, without its leading 4 byte length.
+    pub fn decode(&self, data: &[u8]) -> Result<Value, Error> {
+        self.decode_with_limits(data, &DecodeLimits::default())
+    }
+
+    /// Decodes a serialized message like [Schema::decode], failing once a length exceeds `limits`.
+    pub fn decode_with_limits(&self, data: &[u8], limits: &DecodeLimits) -> Result<Value, Error> {
+        limits.check_message(data.len())?;
+        let mut reader = Reader { data, pos: 0 };
+        let mut stack = Vec::new();
+        self.run(&self.program, &mut reader, &mut stack, limits)?;
+        Ok(stack
+            .pop()
+            .expect("the program ends by collecting the message"))
+    }
+
+    /// Walks a serialized message without decoding it, failing if it's too short or a length exceeds `limits`,
+    /// to vet messages before handing them to a decoder without limits of its own.
+    pub fn check_limits(&self, data: &[u8], limits: &DecodeLimits) -> Result<(), Error> {
+        self.unread_bytes(data, limits).map(|_| ())
+    }
+
+    /// Walks a serialized message like [Schema::check_limits], returning how many bytes are left after its last field.
+    pub(crate) fn unread_bytes(&self, data: &[u8], limits: &DecodeLimits) -> Result<usize, Error> {
+        limits.check_message(data.len())?;
+        let mut reader = Reader { data, pos: 0 };
+        self.walk(&self.program, &mut reader, limits)?;
+        Ok(reader.remaining())
+    }
+
+    fn run(
+        &self,
+        ops: &[Op],
+        reader: &mut Reader,
+        stack: &mut Vec<Value>,
+        limits: &DecodeLimits,
+    ) -> Result<(), Error> {
+        let mut i = 0;
+        while i < ops.len() {
+            match &ops[i] {
+                Op::Read(Primitive::String) => {
+                    let len = limits.string_len(reader)?;
+                    let value = String::from_utf8_lossy(reader.take(len)?).into_owned();
+                    stack.push(Value::String(value));
+                }
+                Op::Read(primitive) => stack.push(read_primitive(*primitive, reader)?),
+                Op::Bytes(len) => {
+                    let len = limits.array_len(*len, reader)?;
+                    stack.push(Value::Bytes(reader.take(len)?.to_vec()));
+                }
+                Op::Array {
+                    len,
+                    body,
+                    min_size,
+                    skip,
+                } => {
+                    let len = limits.array_len(*len, reader)?;
+                    if len.saturating_mul(*min_size) > reader.remaining() {
+                        return Err(DecodeError::UnexpectedEof.into());
+                    }
+                    let body_ops = &ops[i + 1..i + 1 + body];
+                    let start = stack.len();
+                    for _ in 0..len {
+                        self.run(body_ops, reader, stack, limits)?;
+                    }
+                    if !skip {
+                        let values = stack.split_off(start);
+                        stack.push(Value::Array(values));
+                    }
+                    i += body;
+                }
+                Op::Message(names) => {
+                    let values = stack.split_off(stack.len() - names.len());
+                    let fields = self.names[names.clone()].iter().cloned().zip(values);
+                    stack.push(Value::Message(fields.collect()));
+                }
+                Op::Skip(size) => {
+                    reader.take(*size)?;
+                }
+                Op::SkipString => {
+                    let len = limits.string_len(reader)?;
+                    reader.take(len)?;
+                }
+                Op::SkipElements { len, size } => {
+                    let len = limits.array_len(*len, reader)?;
+                    reader.take(len.checked_mul(*size).ok_or(DecodeError::UnexpectedEof)?)?;
+                }
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    /// Runs a program like [Schema::run], reading past values instead of collecting them.
+    fn walk(&self, ops: &[Op], reader: &mut Reader, limits: &DecodeLimits) -> Result<(), Error> {
+        let mut i = 0;
+        while i < ops.len() {
+            match &ops[i] {
+                Op::Read(Primitive::String) | Op::SkipString => {
+                    let len = limits.string_len(reader)?;
+                    reader.take(len)?;
+                }
+                Op::Read(primitive) => {
+                    reader.take(primitive.min_size())?;
+                }
+                Op::Bytes(len) => {
+                    let len = limits.array_len(*len, reader)?;
+                    reader.take(len)?;
+                }
+                Op::Array {
+                    len,
+                    body,
+                    min_size,
+                    ..
+                } => {
+                    let len = limits.array_len(*len, reader)?;
+                    if len.saturating_mul(*min_size) > reader.remaining() {
+                        return Err(DecodeError::UnexpectedEof.into());
+                    }
+                    let body_ops = &ops[i + 1..i + 1 + body];
+                    for _ in 0..len {
+                        self.walk(body_ops, reader, limits)?;
+                    }
+                    i += body;
+                }
+                Op::Message(_) => {}
+                Op::Skip(size) => {
+                    reader.take(*size)?;
+                }
+                Op::SkipElements { len, size } => {
+                    let len = limits.array_len(*len, reader)?;
+                    reader.take(len.checked_mul(*size).ok_or(DecodeError::UnexpectedEof)?)?;
+                }
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns a message of the top level type with every field zero, empty, or filled to its fixed length,
+    /// to set fields on before [encoding](Schema::encode) it.
+    pub fn default_value(&self) -> Value {
+        self.default_message(&self.data_type)
+    }
+
+    fn default_message(&self, data_type: &str) -> Value {
+        let fields = self
+            .definitions
+            .get(data_type)
+            .map(|definition| definition.fields.as_slice())
+            .unwrap_or_default();
+        Value::Message(
+            fields
+                .iter()
+                .map(|field| {
+                    let value = match (field.cardinality, &field.field_type) {
+                        (Cardinality::Single, field_type) => self.default_single(field_type),
+                        (Cardinality::Fixed(len), FieldType::Primitive(Primitive::UInt8)) => {
+                            Value::Bytes(vec![0; len])
+                        }
+                        (Cardinality::Fixed(len), field_type) => {
+                            Value::Array(vec![self.default_single(field_type); len])
+                        }
+                        (Cardinality::Variable, FieldType::Primitive(Primitive::UInt8)) => {
+                            Value::Bytes(Vec::new())
+                        }
+                        (Cardinality::Variable, _) => Value::Array(Vec::new()),
+                    };
+                    (field.name.clone(), value)
+                })
+                .collect(),
+        )
+    }
+
+    fn default_single(&self, field_type: &FieldType) -> Value {
+        let primitive = match field_type {
+            FieldType::Primitive(primitive) => primitive,
+            FieldType::Message(data_type) => return self.default_message(data_type),
+        };
+        match primitive {
+            Primitive::Bool => Value::Bool(false),
+            Primitive::Int8 => Value::I8(0),
+            Primitive::UInt8 => Value::U8(0),
+            Primitive::Int16 => Value::I16(0),
+            Primitive::UInt16 => Value::U16(0),
+            Primitive::Int32 => Value::I32(0),
+            Primitive::UInt32 => Value::U32(0),
+            Primitive::Int64 => Value::I64(0),
+            Primitive::UInt64 => Value::U64(0),
+            Primitive::Float32 => Value::F32(0.0),
+            Primitive::Float64 => Value::F64(0.0),
+            Primitive::String => Value::String(String::new()),
+            Primitive::Time => Value::Time(Time { secs: 0, nsecs: 0 }),
+            Primitive::Duration => Value::Duration { secs: 0, nsecs: 0 },
+        }
+    }
+
+    /// Serializes a message of the top level type, without a leading 4 byte length, as taken by
+    /// [BagWriter::write_raw](crate::writer::BagWriter::write_raw).
+    ///
+    /// `value` must have every field of the definition and no others, each with the [Value] variant [Schema::decode]
+    /// produces for its type, except that `uint8` arrays may also be arrays of [Value::U8].
+    /// Fails with a [DecodeError::InvalidField] naming the offending field otherwise.
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        self.encode_message(&self.data_type, value, &self.data_type, &mut data)?;
+        Ok(data)
+    }
+
+    fn encode_message(
+        &self,
+        data_type: &str,
+        value: &Value,
+        path: &str,
+        data: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let definition = self
+            .definitions
+            .get(data_type)
+            .ok_or_else(|| DecodeError::UnknownType(data_type.to_string()))?;
+        let Value::Message(fields) = value else {
+            return Err(mismatch(path, data_type, value));
+        };
+        if let Some((name, _)) = fields
+            .iter()
+            .find(|(name, _)| !definition.fields.iter().any(|field| &field.name == name))
+        {
+            return Err(
+                DecodeError::InvalidField(format!("{data_type} has no {name} field")).into(),
+            );
+        }
+        for field in definition.fields.iter() {
+            let path = format!("{path}.{}", field.name);
+            let value = value
+                .get(&field.name)
+                .ok_or_else(|| DecodeError::InvalidField(format!("{path} is missing")))?;
+            match field.cardinality {
+                Cardinality::Single => self.encode_single(&field.field_type, value, &path, data)?,
+                Cardinality::Fixed(len) => {
+                    self.encode_array(&field.field_type, Some(len), value, &path, data)?
+                }
+                Cardinality::Variable => {
+                    self.encode_array(&field.field_type, None, value, &path, data)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes an array, with its length first unless it has the `fixed_len` its definition requires.
+    fn encode_array(
+        &self,
+        field_type: &FieldType,
+        fixed_len: Option<usize>,
+        value: &Value,
+        path: &str,
+        data: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let len = match (field_type, value) {
+            (FieldType::Primitive(Primitive::UInt8), Value::Bytes(bytes)) => bytes.len(),
+            (_, Value::Array(values)) => values.len(),
+            _ => {
+                let expected = match fixed_len {
+                    Some(len) => format!("{}[{len}]", field_type_name(field_type)),
+                    None => format!("{}[]", field_type_name(field_type)),
+                };
+                return Err(mismatch(path, &expected, value));
+            }
+        };
+        match fixed_len {
+            Some(fixed_len) if fixed_len != len => {
+                return Err(DecodeError::InvalidField(format!(
+                    "{path} has {len} elements, expected {fixed_len}"
+                ))
+                .into())
+            }
+            Some(_) => {}
+            None => data.extend_from_slice(&length(len, path)?.to_le_bytes()),
+        }
+        match value {
+            Value::Bytes(bytes) => data.extend_from_slice(bytes),
+            Value::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.encode_single(field_type, value, &format!("{path}.{i}"), data)?;
+                }
+            }
+            _ => unreachable!("checked to be an array above"),
+        }
+        Ok(())
+    }
+
+    fn encode_single(
+        &self,
+        field_type: &FieldType,
+        value: &Value,
+        path: &str,
+        data: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let primitive = match field_type {
+            FieldType::Primitive(primitive) => primitive,
+            FieldType::Message(data_type) => {
+                return self.encode_message(data_type, value, path, data)
+            }
+        };
+        match (primitive, value) {
+            (Primitive::Bool, Value::Bool(v)) => data.push(u8::from(*v)),
+            (Primitive::Int8, Value::I8(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::UInt8, Value::U8(v)) => data.push(*v),
+            (Primitive::Int16, Value::I16(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::UInt16, Value::U16(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::Int32, Value::I32(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::UInt32, Value::U32(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::Int64, Value::I64(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::UInt64, Value::U64(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::Float32, Value::F32(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::Float64, Value::F64(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (Primitive::String, Value::String(s)) => {
+                data.extend_from_slice(&length(s.len(), path)?.to_le_bytes());
+                data.extend_from_slice(s.as_bytes());
+            }
+            (Primitive::Time, Value::Time(time)) => {
+                data.extend_from_slice(&time.secs.to_le_bytes());
+                data.extend_from_slice(&time.nsecs.to_le_bytes());
+            }
+            (Primitive::Duration, Value::Duration { secs, nsecs }) => {
+                data.extend_from_slice(&secs.to_le_bytes());
+                data.extend_from_slice(&nsecs.to_le_bytes());
+            }
+            _ => return Err(mismatch(path, &field_type_name(field_type), value)),
+        }
+        Ok(())
+    }
+}
+
+/// Compiles a [Schema]'s program, see [Op].
+struct Compiler<'a> {
+    schema: &'a Schema,
+    program: Vec<Op>,
+    names: Vec<String>,
+    /// The types of the messages being compiled, to catch definitions which contain themselves.
+    parents: Vec<&'a str>,
+    /// Ops before this can't be merged with, as they are the body of an array.
+    merge_start: usize,
+}
+
+impl<'a> Compiler<'a> {
+    /// Appends the ops decoding the `projection` of a message of type `data_type`, or skipping it if `None`.
+    fn message(
+        &mut self,
+        data_type: &'a str,
+        projection: Option<&Projection>,
+    ) -> Result<(), Error> {
+        if self.parents.contains(&data_type) {
+            return Err(
+                DecodeError::InvalidDefinition(format!("{data_type} contains itself")).into(),
+            );
+        }
+        let definition = self
+            .schema
+            .definitions
+            .get(data_type)
+            .ok_or_else(|| DecodeError::UnknownType(data_type.to_string()))?;
+        if let Some(projection) = projection {
+            if let Some(name) = projection
+                .fields
+                .keys()
+                .find(|name| !definition.fields.iter().any(|field| &field.name == *name))
+            {
+                return Err(
+                    DecodeError::InvalidField(format!("{data_type} has no {name} field")).into(),
+                );
+            }
+        }
+
+        let all = Projection::all();
+        let mut names = Vec::new();
+        self.parents.push(data_type);
+        for field in definition.fields.iter() {
+            let field_projection = projection.and_then(|projection| match projection.all {
+                true => Some(&all),
+                false => projection.fields.get(&field.name),
+            });
+            if field_projection.is_some() {
+                names.push(field.name.clone());
+            }
+            self.field(data_type, field, field_projection)?;
+        }
+        self.parents.pop();
+
+        if projection.is_some() {
+            let start = self.names.len();
+            self.names.extend(names);
+            self.program.push(Op::Message(start..self.names.len()));
+        }
+        Ok(())
+    }
+
+    fn field(
+        &mut self,
+        data_type: &str,
+        field: &'a Field,
+        projection: Option<&Projection>,
+    ) -> Result<(), Error> {
+        if let (FieldType::Primitive(_), Some(projection)) = (&field.field_type, projection) {
+            if !projection.all {
+                return Err(DecodeError::InvalidField(format!(
+                    "{data_type}.{} is not a message",
+                    field.name
+                ))
+                .into());
+            }
+        }
+
+        let len = match field.cardinality {
+            Cardinality::Single => return self.single(&field.field_type, projection),
+            Cardinality::Fixed(len) => Some(len),
+            Cardinality::Variable => None,
+        };
+        let skip = projection.is_none();
+        match (
+            &field.field_type,
+            self.schema.fixed_size(&field.field_type, 0),
+        ) {
+            (FieldType::Primitive(Primitive::UInt8), _) if !skip => {
+                self.program.push(Op::Bytes(len));
+            }
+            (_, Some(size)) if skip => match len {
+                Some(len) => {
+                    let size = len.checked_mul(size).ok_or_else(|| {
+                        DecodeError::InvalidDefinition(format!(
+                            "{data_type}.{} is too large",
+                            field.name
+                        ))
+                    })?;
+                    self.skip(size)?;
+                }
+                None => self.program.push(Op::SkipElements { len, size }),
+            },
+            (field_type, _) => {
+                let min_size = match field_type {
+                    FieldType::Primitive(primitive) => primitive.min_size(),
+                    FieldType::Message(_) => 0,
+                };
+                let start = self.program.len();
+                self.program.push(Op::Array {
+                    len,
+                    body: 0,
+                    min_size,
+                    skip,
+                });
+                self.single(field_type, projection)?;
+                let len = self.program.len() - start - 1;
+                if let Op::Array { body, .. } = &mut self.program[start] {
+                    *body = len;
+                }
+                self.merge_start = self.program.len();
+            }
+        }
+        Ok(())
+    }
+
+    fn single(
+        &mut self,
+        field_type: &'a FieldType,
+        projection: Option<&Projection>,
+    ) -> Result<(), Error> {
+        match (field_type, projection) {
+            (FieldType::Message(data_type), _) => return self.message(data_type, projection),
+            (FieldType::Primitive(primitive), Some(_)) => self.program.push(Op::Read(*primitive)),
+            (FieldType::Primitive(Primitive::String), None) => self.program.push(Op::SkipString),
+            (FieldType::Primitive(primitive), None) => self.skip(primitive.min_size())?,
+        }
+        Ok(())
+    }
+
+    /// Appends an op skipping `size` bytes, merged with a previous skip.
+    fn skip(&mut self, size: usize) -> Result<(), Error> {
+        let mergeable = self.program.len() > self.merge_start;
+        match self.program.last_mut() {
+            Some(Op::Skip(skipped)) if mergeable => {
+                *skipped = skipped.checked_add(size).ok_or_else(|| {
+                    DecodeError::InvalidDefinition("skipped fields are too large".to_string())
+                })?;
+            }
+            _ => self.program.push(Op::Skip(size)),
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn read_primitive(primitive: Primitive, reader: &mut Reader) -> Result<Value, Error> {
+    Ok(match primitive {
+        Primitive::Bool => Value::Bool(reader.array::<1>()?[0] != 0),
+        Primitive::Int8 => Value::I8(i8::from_le_bytes(reader.array()?)),
+        Primitive::UInt8 => Value::U8(reader.array::<1>()?[0]),
+        Primitive::Int16 => Value::I16(i16::from_le_bytes(reader.array()?)),
+        Primitive::UInt16 => Value::U16(u16::from_le_bytes(reader.array()?)),
+        Primitive::Int32 => Value::I32(i32::from_le_bytes(reader.array()?)),
+        Primitive::UInt32 => Value::U32(reader.u32()?),
+        Primitive::Int64 => Value::I64(i64::from_le_bytes(reader.array()?)),
+        Primitive::UInt64 => Value::U64(u64::from_le_bytes(reader.array()?)),
+        Primitive::Float32 => Value::F32(f32::from_le_bytes(reader.array()?)),
+        Primitive::Float64 => Value::F64(f64::from_le_bytes(reader.array()?)),
+        Primitive::String => {
+            let len = reader.u32()? as usize;
+            Value::String(String::from_utf8_lossy(reader.take(len)?).into_owned())
+        }
+        Primitive::Time => Value::Time(Time {
+            secs: reader.u32()?,
+            nsecs: reader.u32()?,
+        }),
+        Primitive::Duration => Value::Duration {
+            secs: i32::from_le_bytes(reader.array()?),
+            nsecs: i32::from_le_bytes(reader.array()?),
+        },
+    })
+}
+
+/// Returns a length as the `uint32` it is serialized as.
+fn length(len: usize, path: &str) -> Result<u32, Error> {
+    u32::try_from(len).map_err(|_| {
+        DecodeError::InvalidField(format!("{path} is too long to serialize, {len} elements")).into()
+    })
+}
+
+fn mismatch(path: &str, expected: &str, value: &Value) -> Error {
+    DecodeError::InvalidField(format!("{path} should be {expected}, got {}", value.kind())).into()
+}
+
+pub(crate) fn field_type_name(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Primitive(primitive) => primitive.name().to_string(),
+        FieldType::Message(data_type) => data_type.clone(),
+    }
+}
+
+fn parse_definition(data_type: &str, lines: &[&str]) -> Result<MessageDefinition, Error> {
+    let package = data_type.split_once('/').map(|(package, _)| package);
+    let mut fields = Vec::new();
+    let mut constants = Vec::new();
+
+    for line in lines {
+        let (type_name, name) = match parse_definition_line(data_type, line)? {
+            None => continue,
+            Some(DefinitionLine::Constant {
+                type_name,
+                name,
+                value,
+            }) => {
+                let field_type = Primitive::from_name(type_name).ok_or_else(|| {
+                    DecodeError::InvalidDefinition(format!(
+                        "constant '{name}' in {data_type} has non-primitive type '{type_name}'"
+                    ))
+                })?;
+                constants.push(Constant {
+                    name: name.to_string(),
+                    field_type,
+                    value: value.to_string(),
+                });
+                continue;
+            }
+            Some(DefinitionLine::Field { type_name, name }) => (type_name, name),
+        };
+
+        let (base_type, cardinality) = match type_name.split_once('[') {
+            Some((base_type, len)) => {
+                let len = len.trim_end_matches(']');
+                let cardinality = if len.is_empty() {
+                    Cardinality::Variable
+                } else {
+                    Cardinality::Fixed(len.parse().map_err(|_| {
+                        DecodeError::InvalidDefinition(format!(
+                            "invalid array length '{len}' for {name} in {data_type}"
+                        ))
+                    })?)
+                };
+                (base_type, cardinality)
+            }
+            None => (type_name, Cardinality::Single),
+        };
+        let field_type = match Primitive::from_name(base_type) {
+            Some(primitive) => FieldType::Primitive(primitive),
+            None => FieldType::Message(resolve_type(base_type, package)),
+        };
+        fields.push(Field {
+            name: name.to_string(),
+            field_type,
+            cardinality,
+        });
+    }
+
+    Ok(MessageDefinition {
+        data_type: data_type.to_string(),
+        fields,
+        constants,
+    })
+}
+
+/// A line of a message definition, see [parse_definition_line].
+pub(crate) enum DefinitionLine<'a> {
+    /// `<type> <name>=<value>`
+    Constant {
+        type_name: &'a str,
+        name: &'a str,
+        value: &'a str,
+    },
+    /// `<type> <name>`
+    Field { type_name: &'a str, name: &'a str },
+}
+
+/// Parses a line of the definition of `data_type`, or returns `None` for blank lines and comments.
+///
+/// Trailing comments are stripped, except from the values of string constants, which keep everything after the `=`.
+/// Anything following a field's name, like a ROS 2 default value, is left out.
+pub(crate) fn parse_definition_line<'a>(
+    data_type: &str,
+    line: &'a str,
+) -> Result<Option<DefinitionLine<'a>>, Error> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let invalid = || {
+        DecodeError::InvalidDefinition(format!(
+            "expected '<type> <name>' in {data_type}, got '{line}'"
+        ))
+    };
+    let (type_name, rest) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+    let rest = rest.trim();
+
+    if let Some((name, _)) = strip_comment(rest).split_once('=') {
+        let (_, value) = rest.split_once('=').ok_or_else(invalid)?;
+        // string constants keep everything after the `=`, including `#`
+        let value = match type_name {
+            "string" => value.trim(),
+            _ => strip_comment(value),
+        };
+        return Ok(Some(DefinitionLine::Constant {
+            type_name,
+            name: name.trim(),
+            value,
+        }));
+    }
+    let name = strip_comment(rest)
+        .split_whitespace()
+        .next()
+        .ok_or_else(invalid)?;
+    Ok(Some(DefinitionLine::Field { type_name, name }))
+}
+
+fn strip_comment(s: &str) -> &str {
+    s.split_once('#').map_or(s, |(s, _)| s).trim()
+}
+
+/// Resolves a field's message type to its full `package/Name`.
+fn resolve_type(type_name: &str, package: Option<&str>) -> String {
+    if type_name.contains('/') {
+        type_name.to_string()
+    } else if type_name == "Header" {
+        "std_msgs/Header".to_string()
+    } else {
+        match package {
+            Some(package) => format!("{package}/{type_name}"),
+            None => type_name.to_string(),
+        }
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.remaining() {
+            return Err(DecodeError::UnexpectedEof.into());
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.take(N)?.try_into().expect("took exactly N bytes"))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+/// A dynamically decoded message or field.
+///
+/// Serializing a `Value` (e.g. with `serde_json`) produces messages as maps in field order,
+/// `time` and `duration` as `{"secs": .., "nsecs": ..}`, and `uint8[]` as an array of numbers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Time(Time),
+    Duration {
+        secs: i32,
+        nsecs: i32,
+    },
+    /// A `uint8[]` or `char[]` array.
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    /// A message's fields, in the order they are defined.
+    Message(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the field of a message with the given name.
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        match self {
+            Value::Message(fields) => fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns a nested field by a `.` separated path, where numeric segments index into arrays,
+    /// e.g. `status.0.temperature`.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                Value::Array(values) => values.get(segment.parse::<usize>().ok()?),
+                _ => value.get(segment),
+            })
+    }
+
+    /// Returns the value of any numeric field as an `f64`, and times and durations in seconds.
+    pub fn as_f64(&self) -> Option<f64> {
+        Some(match *self {
+            Value::I8(v) => v as f64,
+            Value::U8(v) => v as f64,
+            Value::I16(v) => v as f64,
+            Value::U16(v) => v as f64,
+            Value::I32(v) => v as f64,
+            Value::U32(v) => v as f64,
+            Value::I64(v) => v as f64,
+            Value::U64(v) => v as f64,
+            Value::F32(v) => v as f64,
+            Value::F64(v) => v,
+            Value::Time(time) => f64::from(time),
+            Value::Duration { secs, nsecs } => secs as f64 + nsecs as f64 * crate::time::NS_TO_S,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the field of a message with the given name, to change it.
+    pub fn get_mut(&mut self, field: &str) -> Option<&mut Value> {
+        match self {
+            Value::Message(fields) => fields
+                .iter_mut()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns a nested field by a `.` separated path to change it, see [Value::get_path].
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut Value> {
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                Value::Array(values) => values.get_mut(segment.parse::<usize>().ok()?),
+                _ => value.get_mut(segment),
+            })
+    }
+
+    /// Describes the variant, for errors.
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "a bool",
+            Value::I8(_) => "an int8",
+            Value::U8(_) => "a uint8",
+            Value::I16(_) => "an int16",
+            Value::U16(_) => "a uint16",
+            Value::I32(_) => "an int32",
+            Value::U32(_) => "a uint32",
+            Value::I64(_) => "an int64",
+            Value::U64(_) => "a uint64",
+            Value::F32(_) => "a float32",
+            Value::F64(_) => "a float64",
+            Value::String(_) => "a string",
+            Value::Time(_) => "a time",
+            Value::Duration { .. } => "a duration",
+            Value::Bytes(_) => "bytes",
+            Value::Array(_) => "an array",
+            Value::Message(_) => "a message",
+        }
+    }
+}
+
+/// Returns a message's field, failing with a [DecodeError::InvalidField] naming the message type if it is missing.
+pub(crate) fn required<'a>(
+    value: &'a Value,
+    data_type: &str,
+    field: &str,
+) -> Result<&'a Value, DecodeError> {
+    value
+        .get(field)
+        .ok_or_else(|| DecodeError::InvalidField(format!("{data_type} has no {field} field")))
+}
+
+/// Returns a numeric field of a message as an `f64`, see [required].
+pub(crate) fn required_f64(
+    value: &Value,
+    data_type: &str,
+    field: &str,
+) -> Result<f64, DecodeError> {
+    required(value, data_type, field)?
+        .as_f64()
+        .ok_or_else(|| DecodeError::InvalidField(format!("{data_type}.{field} is not a number")))
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::I8(v) => serializer.serialize_i8(*v),
+            Value::U8(v) => serializer.serialize_u8(*v),
+            Value::I16(v) => serializer.serialize_i16(*v),
+            Value::U16(v) => serializer.serialize_u16(*v),
+            Value::I32(v) => serializer.serialize_i32(*v),
+            Value::U32(v) => serializer.serialize_u32(*v),
+            Value::I64(v) => serializer.serialize_i64(*v),
+            Value::U64(v) => serializer.serialize_u64(*v),
+            Value::F32(v) => serializer.serialize_f32(*v),
+            Value::F64(v) => serializer.serialize_f64(*v),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::Time(time) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("secs", &time.secs)?;
+                map.serialize_entry("nsecs", &time.nsecs)?;
+                map.end()
+            }
+            Value::Duration { secs, nsecs } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("secs", secs)?;
+                map.serialize_entry("nsecs", nsecs)?;
+                map.end()
+            }
+            // byte strings in binary formats like CBOR, arrays of numbers in text formats like JSON
+            Value::Bytes(bytes) if !serializer.is_human_readable() => {
+                serializer.serialize_bytes(bytes)
+            }
+            Value::Bytes(bytes) => {
+                let mut seq = serializer.serialize_seq(Some(bytes.len()))?;
+                for byte in bytes {
+                    seq.serialize_element(byte)?;
+                }
+                seq.end()
+            }
+            Value::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Value::Message(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, value) in fields {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_definition_line, Cardinality, DefinitionLine, FieldType, Primitive, Schema};
+
+    const DEFINITION: &str = "\
+# a comment
+Header header
+uint8 DEBUG=1 # also a comment
+string NAME=a # not a comment
+geometry_msgs/Point[] points
+float64[9] covariance
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y
+float64 z
+";
+
+    #[test]
+    fn test_parse_definition() {
+        let schema = Schema::parse("test_msgs/Test", DEFINITION).unwrap();
+        let root = schema.root();
+        assert_eq!(root.fields.len(), 3);
+        assert_eq!(
+            root.fields[0].field_type,
+            FieldType::Message("std_msgs/Header".into())
+        );
+        assert_eq!(root.fields[1].cardinality, Cardinality::Variable);
+        assert_eq!(
+            root.fields[2].field_type,
+            FieldType::Primitive(Primitive::Float64)
+        );
+        assert_eq!(root.fields[2].cardinality, Cardinality::Fixed(9));
+        assert_eq!(root.constants[0].value, "1");
+        assert_eq!(root.constants[1].value, "a # not a comment");
+        assert_eq!(
+            schema
+                .definition("geometry_msgs/Point")
+                .unwrap()
+                .fields
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_parse_definition_line() {
+        let parse = |line| parse_definition_line("test_msgs/Test", line).unwrap();
+        for line in ["", "  ", "# a comment"] {
+            assert!(parse(line).is_none());
+        }
+        for (line, expected) in [
+            ("int32 x", "x"),
+            ("int32 x # a=b", "x"),
+            // a ROS 2 default value
+            ("string<=8 name \"frost\" # comment", "name"),
+        ] {
+            match parse(line) {
+                Some(DefinitionLine::Field { name, .. }) => assert_eq!(name, expected, "{line}"),
+                _ => panic!("{line} is not a field"),
+            }
+        }
+        for (line, expected) in [
+            ("uint8 DEBUG = 1 # comment", "1"),
+            ("string NAME=a # not a comment", "a # not a comment"),
+        ] {
+            match parse(line) {
+                Some(DefinitionLine::Constant { value, .. }) => {
+                    assert_eq!(value, expected, "{line}")
+                }
+                _ => panic!("{line} is not a constant"),
+            }
+        }
+        for line in ["int32", "int32 # x"] {
+            assert!(parse_definition_line("test_msgs/Test", line).is_err());
+        }
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        assert!(Schema::parse("test_msgs/Test", "geometry_msgs/Point point").is_err());
+    }
+
+    #[test]
+    fn test_recursive_type() {
+        let definition = "\
+test_msgs/Node root
+================================================================================
+MSG: test_msgs/Node
+int32 value
+test_msgs/Tree[] children
+";
+        let err = Schema::parse("test_msgs/Tree", definition).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid message definition: test_msgs/Tree contains itself"
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Writing messages as a stream of records for other programs to consume, as `frost echo` does.
+//!
+//! Every format writes one record per message, back to back, so readers can decode them one at a time:
+//! - [EchoFormat::Json]: a JSON object per line.
+//! - `Cbor`: a CBOR sequence (RFC 8742) of maps, each a complete data item, with the `cbor` feature.
+//! - `MsgPack`: a MessagePack stream of maps, each a complete value, with the `msgpack` feature.
+//! - [EchoFormat::Raw]: the serialized message as stored in the bag, framed as below.
+//!
+//! JSON, CBOR, and MessagePack records are maps of `topic`, `time` (a map of `secs` and `nsecs`), `type`, and
+//! `message`, the message decoded with [crate::dynamic]. `uint8[]` fields are byte strings in CBOR and MessagePack,
+//! and arrays of numbers in JSON.
+//!
+//! Raw records skip decoding, and are, with integers little endian:
+//! ```text
+//! u32 topic length | topic | u32 secs | u32 nsecs | u32 message length | message
+//! ```
+//!
+//! Example
+//! ```rust
+//! use frost::echo::{EchoFormat, EchoWriter};
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let mut writer = EchoWriter::new(Vec::new(), EchoFormat::Json);
+//! for msg_view in bag.read_messages(&Query::new().with_topics(["/chatter"])).unwrap() {
+//!     writer.write_message(&msg_view).unwrap();
+//! }
+//!
+//! let bytes = writer.into_inner();
+//! let line = bytes.split(|&byte| byte == b'\n').next().unwrap();
+//! let record: serde_json::Value = serde_json::from_slice(line).unwrap();
+//! assert_eq!(record["topic"], "/chatter");
+//! ```
+use std::io::Write;
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer};
+
+use crate::dynamic::Value;
+use crate::errors::{Error, ErrorKind};
+use crate::msgs::MessageView;
+use crate::query::FilteredIter;
+use crate::time::Time;
+
+/// How [EchoWriter] encodes messages, see the [module docs](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EchoFormat {
+    Json,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+    Raw,
+}
+
+impl FromStr for EchoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(EchoFormat::Json),
+            #[cfg(feature = "cbor")]
+            "cbor" => Ok(EchoFormat::Cbor),
+            #[cfg(not(feature = "cbor"))]
+            "cbor" => Err("cbor output needs frost built with the cbor feature".to_string()),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(EchoFormat::MsgPack),
+            #[cfg(not(feature = "msgpack"))]
+            "msgpack" => {
+                Err("msgpack output needs frost built with the msgpack feature".to_string())
+            }
+            "raw" => Ok(EchoFormat::Raw),
+            other => Err(format!(
+                "unknown echo format '{other}', expected json, cbor, msgpack, or raw"
+            )),
+        }
+    }
+}
+
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+fn invalid_data<E: std::fmt::Display>(err: E) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+}
+
+fn serialize_time<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+    Value::Time(*time).serialize(serializer)
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    topic: &'a str,
+    #[serde(serialize_with = "serialize_time")]
+    time: Time,
+    #[serde(rename = "type")]
+    data_type: &'a str,
+    message: Value,
+}
+
+impl<'a> Record<'a> {
+    fn new(msg_view: &MessageView<'a>) -> Result<Self, Error> {
+        Ok(Record {
+            topic: msg_view.topic,
+            time: msg_view.time,
+            data_type: &msg_view.connection().data_type,
+            message: msg_view.decode()?,
+        })
+    }
+}
+
+/// Writes messages to `W` as records of an [EchoFormat].
+pub struct EchoWriter<W: Write> {
+    writer: W,
+    format: EchoFormat,
+    /// each record is encoded here first, so a failed encoding never leaves a partial record in `writer`
+    buf: Vec<u8>,
+}
+
+impl<W: Write> EchoWriter<W> {
+    pub fn new(writer: W, format: EchoFormat) -> Self {
+        EchoWriter {
+            writer,
+            format,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn format(&self) -> EchoFormat {
+        self.format
+    }
+
+    /// Writes a message's record, decoding it unless the format is [EchoFormat::Raw].
+    pub fn write_message(&mut self, msg_view: &MessageView) -> Result<(), Error> {
+        self.buf.clear();
+        match self.format {
+            EchoFormat::Json => {
+                serde_json::to_writer(&mut self.buf, &Record::new(msg_view)?)
+                    .map_err(std::io::Error::from)?;
+                self.buf.push(b'\n');
+            }
+            #[cfg(feature = "cbor")]
+            EchoFormat::Cbor => ciborium::into_writer(&Record::new(msg_view)?, &mut self.buf)
+                .map_err(invalid_data)?,
+            // maps rather than rmp_serde's default of arrays, so records are self-describing
+            #[cfg(feature = "msgpack")]
+            EchoFormat::MsgPack => Record::new(msg_view)?
+                .serialize(&mut rmp_serde::Serializer::new(&mut self.buf).with_struct_map())
+                .map_err(invalid_data)?,
+            EchoFormat::Raw => {
+                let topic = msg_view.topic.as_bytes();
+                self.buf
+                    .extend_from_slice(&(topic.len() as u32).to_le_bytes());
+                self.buf.extend_from_slice(topic);
+                self.buf
+                    .extend_from_slice(&msg_view.time.secs.to_le_bytes());
+                self.buf
+                    .extend_from_slice(&msg_view.time.nsecs.to_le_bytes());
+                // already prefixed with its length
+                self.buf.extend_from_slice(msg_view.raw_bytes()?);
+            }
+        }
+        self.writer.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    /// Writes the record of each message, returning how many were written.
+    ///
+    /// Fails with [ErrorKind::Cancelled] if the query was cancelled before every message was written, leaving the
+    /// records written so far.
+    pub fn write_messages(&mut self, mut messages: FilteredIter) -> Result<usize, Error> {
+        let mut count = 0;
+        for msg_view in messages.by_ref() {
+            self.write_message(&msg_view)?;
+            count += 1;
+        }
+        if messages.was_cancelled() {
+            return Err(Error::new(ErrorKind::Cancelled));
+        }
+        Ok(count)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,315 @@
+//! Editing a bag's connections in place, rewriting only the index at the end of the bag.
+//!
+//! An indexed bag ends with a connection record per connection and a chunk info record per chunk, which is all that
+//! rosbag and frost read connections from. An [IndexEditor] changes connections, like a wrong type or a missing
+//! latching flag, and rewrites just that region, so fixing a 50 GB bag writes a few kilobytes. The copies of the
+//! connection records inside chunks are left as they were recorded; players read indexed bags through the index.
+//!
+//! Edits are crash safe: before the index is overwritten, the old one is saved to a journal next to the bag
+//! (`<bag>.frost-journal`), which is removed once the new index has been written and read back. A bag left with a
+//! journal by an interrupted edit can't be edited again until [recover] restores its old index.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::edit::IndexEditor;
+//!
+//! let mut editor = IndexEditor::open("robot.bag").unwrap();
+//! editor.set_latching("/map", true).unwrap();
+//! let report = editor.commit().unwrap();
+//! println!("edited {} connections", report.edited_connections);
+//! ```
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::errors::Error;
+#[cfg(feature = "md5")]
+use crate::schemas;
+use crate::writer::{connection_record, time_bytes, write_record, HeaderBuilder, VERSION_LINE};
+use crate::{BagHeader, BagMetadata, ConnectionData, ConnectionID, OpCode};
+
+const JOURNAL_MAGIC: &[u8] = b"frost-journal 1\n";
+
+/// What [IndexEditor::commit] changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditReport {
+    pub edited_connections: usize,
+    /// Where the index starts, which edits don't move.
+    pub index_pos: u64,
+    /// The size of the index before and after the edit.
+    pub old_index_len: u64,
+    pub new_index_len: u64,
+}
+
+/// Edits the connections of an indexed bag file in place, see [the module docs](self).
+pub struct IndexEditor {
+    path: PathBuf,
+    metadata: BagMetadata,
+    index_pos: u64,
+    connections: BTreeMap<ConnectionID, ConnectionData>,
+}
+
+impl IndexEditor {
+    /// Reads the index of the bag at `path`.
+    ///
+    /// Fails if an interrupted edit left a journal, or if the bag's index can't be rewritten without reading chunks,
+    /// like a bag with connection ids reused by different connections.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let journal = journal_path(&path);
+        if journal.exists() {
+            return Err(invalid(format!(
+                "{} was left by an interrupted edit, recover the bag before editing it",
+                journal.display()
+            )));
+        }
+
+        let metadata = BagMetadata::from_file(&path)?;
+        if let Some(warning) = metadata.warnings.first() {
+            return Err(invalid(format!("can't edit the index in place: {warning}")));
+        }
+        let index_pos = read_index_pos(&mut File::open(&path)?)?;
+        if let Some(chunk) = metadata
+            .chunk_metadata
+            .values()
+            .find(|chunk| chunk.chunk_header_pos >= index_pos)
+        {
+            return Err(invalid(format!(
+                "the chunk at byte {} is after the index at byte {index_pos}",
+                chunk.chunk_header_pos
+            )));
+        }
+        let connections = metadata.connection_data.clone();
+        Ok(IndexEditor {
+            path,
+            metadata,
+            index_pos,
+            connections,
+        })
+    }
+
+    /// The connections as they will be written, keyed by id.
+    pub fn connections(&self) -> &BTreeMap<ConnectionID, ConnectionData> {
+        &self.connections
+    }
+
+    /// Calls `edit` on every connection of `topic`, returning how many there were.
+    ///
+    /// Fails if the topic has no connections. Changes to the connection id are ignored, as chunks refer to it.
+    pub fn edit_topic<F>(&mut self, topic: &str, mut edit: F) -> Result<usize, Error>
+    where
+        F: FnMut(&mut ConnectionData),
+    {
+        let mut count = 0;
+        for (id, connection) in self.connections.iter_mut() {
+            if connection.topic == topic {
+                edit(connection);
+                connection.connection_id = *id;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Err(invalid(format!("no connections on topic {topic}")));
+        }
+        Ok(count)
+    }
+
+    /// Sets the message type, md5sum and definition of the connections on `topic`, so decoding uses the new type.
+    ///
+    /// `message_definition` is the full definition as rosbag records it, with those of the types it depends on
+    /// appended. Fails if its md5sum doesn't match `md5sum`. Requires the `md5` feature.
+    #[cfg(feature = "md5")]
+    pub fn set_type(
+        &mut self,
+        topic: &str,
+        data_type: &str,
+        md5sum: &str,
+        message_definition: &str,
+    ) -> Result<usize, Error> {
+        match schemas::definition_md5sum(data_type, message_definition) {
+            Some(computed) if computed == md5sum => {}
+            Some(computed) => {
+                return Err(invalid(format!(
+                    "the definition of {data_type} has md5sum {computed}, not {md5sum}"
+                )))
+            }
+            None => {
+                return Err(invalid(format!(
+                    "the definition of {data_type} is missing the definitions of types it uses"
+                )))
+            }
+        }
+        self.edit_topic(topic, |connection| {
+            connection.data_type = data_type.to_string();
+            connection.md5sum = md5sum.to_string();
+            connection.message_definition = message_definition.to_string();
+        })
+    }
+
+    /// Marks the connections on `topic` as latching or not.
+    pub fn set_latching(&mut self, topic: &str, latching: bool) -> Result<usize, Error> {
+        self.edit_topic(topic, |connection| connection.latching = latching)
+    }
+
+    /// Rewrites the bag's index with the edited connections.
+    pub fn commit(self) -> Result<EditReport, Error> {
+        let edited: Vec<ConnectionID> = self
+            .connections
+            .iter()
+            .filter(|(id, connection)| {
+                self.metadata
+                    .connection_data
+                    .get(id)
+                    .map_or(true, |old| !same_fields(old, connection))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        let mut report = EditReport {
+            edited_connections: edited.len(),
+            index_pos: self.index_pos,
+            old_index_len: self.metadata.num_bytes - self.index_pos,
+            new_index_len: self.metadata.num_bytes - self.index_pos,
+        };
+        if edited.is_empty() {
+            return Ok(report);
+        }
+
+        let index = self.index();
+        report.new_index_len = index.len() as u64;
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        let mut old_index = Vec::new();
+        file.seek(SeekFrom::Start(self.index_pos))?;
+        file.read_to_end(&mut old_index)?;
+        write_journal(&self.path, self.index_pos, &old_index)?;
+
+        let written = overwrite_index(&mut file, self.index_pos, &index).and_then(|()| {
+            let metadata = BagMetadata::from_file(&self.path)?;
+            let read_back = metadata.connection_data.len() == self.connections.len()
+                && self.connections.iter().all(|(id, connection)| {
+                    metadata
+                        .connection_data
+                        .get(id)
+                        .map_or(false, |read| same_fields(read, connection))
+                });
+            if !read_back {
+                return Err(invalid(
+                    "the rewritten index doesn't read back as edited".to_string(),
+                ));
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            overwrite_index(&mut file, self.index_pos, &old_index)?;
+            fs::remove_file(journal_path(&self.path))?;
+            return Err(e);
+        }
+        fs::remove_file(journal_path(&self.path))?;
+        Ok(report)
+    }
+
+    /// The connection and chunk info records of the edited index.
+    fn index(&self) -> Vec<u8> {
+        let mut index = Vec::new();
+        for (id, connection) in self.connections.iter() {
+            let (header, data) = connection_record(*id, connection);
+            write_record(&mut index, &header, &data).expect("writing to a Vec");
+        }
+        for chunk in self.metadata.chunk_metadata.values() {
+            let header = HeaderBuilder::op(OpCode::ChunkInfoHeader)
+                .field(b"ver", &1u32.to_le_bytes())
+                .field(b"chunk_pos", &chunk.chunk_header_pos.to_le_bytes())
+                .field(b"start_time", &time_bytes(&chunk.start_time))
+                .field(b"end_time", &time_bytes(&chunk.end_time))
+                .field(b"count", &(chunk.message_counts.len() as u32).to_le_bytes())
+                .finish();
+            let mut data = Vec::with_capacity(chunk.message_counts.len() * 8);
+            for (conn_id, count) in chunk.message_counts.iter() {
+                data.extend_from_slice(&conn_id.to_le_bytes());
+                data.extend_from_slice(&count.to_le_bytes());
+            }
+            write_record(&mut index, &header, &data).expect("writing to a Vec");
+        }
+        index
+    }
+}
+
+/// Restores the index of a bag whose edit was interrupted, returning false if there was nothing to recover.
+pub fn recover<P: AsRef<Path>>(path: P) -> Result<bool, Error> {
+    let path = path.as_ref();
+    let journal_path = journal_path(path);
+    let journal = match fs::read(&journal_path) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let index_pos = journal
+        .strip_prefix(JOURNAL_MAGIC)
+        .and_then(|rest| rest.get(..8))
+        .map(|pos| u64::from_le_bytes(pos.try_into().unwrap()))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a frost journal", journal_path.display()),
+            )
+        })?;
+    let old_index = &journal[JOURNAL_MAGIC.len() + 8..];
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    overwrite_index(&mut file, index_pos, old_index)?;
+    fs::remove_file(&journal_path)?;
+    Ok(true)
+}
+
+/// Compares the fields an [IndexEditor] edits, and not the recorded header fields they are written over.
+fn same_fields(a: &ConnectionData, b: &ConnectionData) -> bool {
+    a.topic == b.topic
+        && a.data_type == b.data_type
+        && a.md5sum == b.md5sum
+        && a.message_definition == b.message_definition
+        && a.caller_id == b.caller_id
+        && a.latching == b.latching
+}
+
+fn journal_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".frost-journal");
+    path.with_file_name(name)
+}
+
+/// Writes the journal under a temporary name first, so a journal only exists once it is complete.
+fn write_journal(path: &Path, index_pos: u64, old_index: &[u8]) -> io::Result<()> {
+    let journal = journal_path(path);
+    let mut partial = journal.clone().into_os_string();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+
+    let mut file = File::create(&partial)?;
+    file.write_all(JOURNAL_MAGIC)?;
+    file.write_all(&index_pos.to_le_bytes())?;
+    file.write_all(old_index)?;
+    file.sync_all()?;
+    fs::rename(&partial, &journal)
+}
+
+fn overwrite_index(file: &mut File, index_pos: u64, index: &[u8]) -> Result<(), Error> {
+    file.seek(SeekFrom::Start(index_pos))?;
+    file.write_all(index)?;
+    file.set_len(index_pos + index.len() as u64)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+pub(crate) fn read_index_pos(file: &mut File) -> Result<u64, Error> {
+    file.seek(SeekFrom::Start(VERSION_LINE.len() as u64))?;
+    let mut len = [0u8; 4];
+    file.read_exact(&mut len)?;
+    let mut header = vec![0u8; u32::from_le_bytes(len) as usize];
+    file.read_exact(&mut header)?;
+    Ok(BagHeader::from(&header)?.index_pos)
+}
+
+fn invalid(message: String) -> Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message).into()
+}
@@ -0,0 +1,333 @@
+//! Incrementally ingesting the bags dropped into a directory into a database, resuming where ingestion stopped.
+//!
+//! An [Ingest] ties together what an ingestion daemon needs:
+//! - a catalog of the bags waiting in a directory, [Ingest::pending]: finished `.bag` files, not the `.active` files
+//!   still being recorded, which haven't been completely ingested, in the order they were modified
+//! - [Checkpoints], a JSON file of cursors counting how many of each bag's messages have been committed, keyed by the
+//!   bag's path and reset when its size or modification time changes
+//! - reading on another thread, which loads only the chunks the query needs and sends owned [Record]s in batches over
+//!   a bounded channel, so reading the next batch overlaps with writing the last one
+//!
+//! The sink is called with each batch, and the bag's checkpoint is saved once it returns, so after a crash or an
+//! error ingestion resumes from the last committed batch, skipping the messages before it without decoding them.
+//! A crash between a batch being committed and its checkpoint being saved replays that batch, so sinks should write
+//! records idempotently, keyed by [Record::bag] and [Record::sequence], which makes ingestion effectively
+//! exactly-once. Messages are numbered in [filter_messages](crate::DecompressedBag::filter_messages) order, so keep the
+//! query the same for as long as a checkpoint file is used.
+//!
+//! Example
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use frost::clock::{Clock, SystemClock};
+//! use frost::etl::Ingest;
+//! use frost::query::Query;
+//!
+//! let mut ingest = Ingest::open("/var/lib/ingest/checkpoints.json")
+//!     .unwrap()
+//!     .with_query(Query::new().with_topics(["/odom", "/diagnostics"]))
+//!     .with_decoding();
+//! loop {
+//!     for bag in ingest.pending("/data/drop").unwrap() {
+//!         let report = ingest
+//!             .ingest_bag(&bag, |records| {
+//!                 for record in records {
+//!                     // e.g. INSERT ... ON CONFLICT (bag, sequence) DO NOTHING, in one transaction
+//!                     println!("{} {} {}", record.sequence, record.topic, record.time);
+//!                 }
+//!                 Ok(())
+//!             })
+//!             .unwrap();
+//!         println!("{}: {} new records", bag.display(), report.records);
+//!     }
+//!     SystemClock.sleep(Duration::from_secs(30));
+//! }
+//! ```
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dynamic::Value;
+use crate::errors::{Error, ErrorKind};
+use crate::query::Query;
+use crate::time::Time;
+use crate::DecompressedBag;
+
+/// The number of records handed to the sink at once, unless set with [Ingest::with_batch_size].
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+/// How many batches the reading thread gets ahead of the sink.
+const BATCHES_AHEAD: usize = 2;
+
+/// A message read for ingestion, owning its data so it can be sent between threads.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// The canonical path of the bag the message is from.
+    pub bag: Arc<Path>,
+    /// The message's position among the bag's messages matching the query, from 0.
+    pub sequence: u64,
+    pub topic: String,
+    pub data_type: String,
+    /// When the message was received.
+    pub time: Time,
+    /// The serialized message.
+    pub data: Vec<u8>,
+    /// The decoded message, if decoding was turned on with [Ingest::with_decoding].
+    pub value: Option<Value>,
+}
+
+/// How far ingestion of a bag has got, see the [module docs](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The size of the bag when it was ingested.
+    pub size: u64,
+    /// The bag's modification time when it was ingested, in nanoseconds since the epoch.
+    pub modified_nanos: u64,
+    /// The number of records committed.
+    pub committed: u64,
+    /// True once every record has been committed.
+    pub complete: bool,
+}
+
+impl Checkpoint {
+    /// A checkpoint for the bag at `path` in its current state, with nothing committed yet.
+    fn start(path: &Path) -> Result<Self, Error> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Checkpoint {
+            size: metadata.len(),
+            modified_nanos: u64::try_from(modified.as_nanos()).unwrap_or(u64::MAX),
+            committed: 0,
+            complete: false,
+        })
+    }
+
+    fn is_same_bag(&self, other: &Checkpoint) -> bool {
+        self.size == other.size && self.modified_nanos == other.modified_nanos
+    }
+}
+
+/// The checkpoints of the bags ingested so far, saved as a JSON file, see the [module docs](self).
+#[derive(Debug)]
+pub struct Checkpoints {
+    path: PathBuf,
+    bags: BTreeMap<PathBuf, Checkpoint>,
+}
+
+impl Checkpoints {
+    /// Reads the checkpoints saved at `path`, or starts with none if there is no file there yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let bags = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Checkpoints { path, bags })
+    }
+
+    /// The checkpoint of the bag at `bag`, unless there is none or the bag was modified since.
+    pub fn get<P: AsRef<Path>>(&self, bag: P) -> Result<Option<Checkpoint>, Error> {
+        let bag = fs::canonicalize(bag)?;
+        let current = Checkpoint::start(&bag)?;
+        Ok(self
+            .bags
+            .get(&bag)
+            .filter(|checkpoint| checkpoint.is_same_bag(&current))
+            .copied())
+    }
+
+    /// Writes the checkpoints to a temporary file next to the checkpoint file and renames it over it, so a crash
+    /// leaves either the old or the new checkpoints.
+    fn save(&self) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(&self.bags)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, json)?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// What [Ingest::ingest_bag] did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IngestReport {
+    /// The records committed before this run, which were skipped.
+    pub resumed_from: u64,
+    /// The records committed in this run.
+    pub records: u64,
+    /// True if every record of the bag has now been committed.
+    pub complete: bool,
+}
+
+/// Ingests bags into a sink with checkpoints, see the [module docs](self).
+pub struct Ingest {
+    checkpoints: Checkpoints,
+    query: Query,
+    batch_size: usize,
+    decode: bool,
+}
+
+impl Ingest {
+    /// Starts ingesting with the checkpoints saved at `checkpoint_path`, see [Checkpoints::open].
+    pub fn open<P: AsRef<Path>>(checkpoint_path: P) -> Result<Self, Error> {
+        Ok(Ingest {
+            checkpoints: Checkpoints::open(checkpoint_path)?,
+            query: Query::all(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            decode: false,
+        })
+    }
+
+    /// Only ingests the messages matching `query`. Defaults to [Query::all].
+    pub fn with_query(mut self, query: Query) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Sets how many records the sink is given at once, and so how many are replayed after a crash at most.
+    /// Defaults to [DEFAULT_BATCH_SIZE].
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Also decodes each message into [Record::value], on the reading thread.
+    pub fn with_decoding(mut self) -> Self {
+        self.decode = true;
+        self
+    }
+
+    pub fn checkpoints(&self) -> &Checkpoints {
+        &self.checkpoints
+    }
+
+    /// Lists the finished bags in `dir` which haven't been completely ingested, oldest first.
+    pub fn pending<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>, Error> {
+        let mut bags = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().map_or(true, |ext| ext != "bag") {
+                continue;
+            }
+            let complete = self
+                .checkpoints
+                .get(&path)?
+                .map_or(false, |checkpoint| checkpoint.complete);
+            if !complete {
+                let modified = fs::metadata(&path)?.modified()?;
+                bags.push((modified, path));
+            }
+        }
+        bags.sort();
+        Ok(bags.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// Hands the records of the bag at `bag` which haven't been committed yet to `sink`, a batch at a time, saving
+    /// the bag's checkpoint after each batch is committed, see the [module docs](self).
+    ///
+    /// Stops at the first error reading the bag or from `sink`, after saving the batches committed before it.
+    pub fn ingest_bag<P, F>(&mut self, bag: P, mut sink: F) -> Result<IngestReport, Error>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&[Record]) -> Result<(), Error>,
+    {
+        let path = fs::canonicalize(bag)?;
+        let mut checkpoint = match self.checkpoints.get(&path)? {
+            Some(checkpoint) => checkpoint,
+            None => Checkpoint::start(&path)?,
+        };
+        let mut report = IngestReport {
+            resumed_from: checkpoint.committed,
+            records: 0,
+            complete: checkpoint.complete,
+        };
+        if checkpoint.complete {
+            return Ok(report);
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(BATCHES_AHEAD);
+        let (query, batch_size, decode) = (&self.query, self.batch_size, self.decode);
+        let (bag, skip): (Arc<Path>, _) = (Arc::from(path.as_path()), checkpoint.committed);
+        std::thread::scope(|scope| {
+            scope.spawn(move || read_batches(bag, query, skip, batch_size, decode, sender));
+            for batch in receiver {
+                let batch = batch?;
+                if batch.is_empty() {
+                    checkpoint.complete = true;
+                } else {
+                    sink(&batch)?;
+                    checkpoint.committed += batch.len() as u64;
+                    report.records += batch.len() as u64;
+                }
+                self.checkpoints.bags.insert(path.clone(), checkpoint);
+                self.checkpoints.save()?;
+            }
+            report.complete = checkpoint.complete;
+            Ok(report)
+        })
+    }
+}
+
+/// Sends the records of `bag` after the first `skip` in batches, then an empty batch once they have all been sent,
+/// until `sender`'s receiver is dropped.
+fn read_batches(
+    bag: Arc<Path>,
+    query: &Query,
+    skip: u64,
+    batch_size: usize,
+    decode: bool,
+    sender: mpsc::SyncSender<Result<Vec<Record>, Error>>,
+) {
+    let result = (|| {
+        let loaded = DecompressedBag::from_file_filtered(&*bag, query)?;
+        let mut messages = loaded.filter_messages(query)?;
+        if skip > 0 {
+            messages.nth(usize::try_from(skip - 1).unwrap_or(usize::MAX));
+        }
+        let mut batch = Vec::with_capacity(batch_size);
+        for (sequence, msg_view) in (skip..).zip(messages.by_ref()) {
+            batch.push(Record {
+                bag: bag.clone(),
+                sequence,
+                topic: msg_view.topic.to_string(),
+                data_type: msg_view.data_type().to_string(),
+                time: msg_view.time,
+                data: msg_view.data_bytes().to_vec(),
+                value: if decode {
+                    Some(msg_view.decode()?)
+                } else {
+                    None
+                },
+            });
+            if batch.len() == batch_size {
+                let full = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                if sender.send(Ok(full)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        if !batch.is_empty() && sender.send(Ok(batch)).is_err() {
+            return Ok(());
+        }
+        // an empty batch would mark the bag complete
+        if messages.was_cancelled() {
+            return Err(Error::new(ErrorKind::Cancelled));
+        }
+        let _ = sender.send(Ok(Vec::new()));
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = sender.send(Err(e));
+    }
+}
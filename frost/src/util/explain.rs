@@ -0,0 +1,464 @@
+//! Diagnosing messages that fail to decode, see [MessageView::explain](crate::msgs::MessageView::explain).
+//!
+//! An [Explanation] traces a message field by field against the definition recorded with its connection, stopping
+//! at the first field the remaining bytes can't hold, and sets that beside the result of instantiating the message as
+//! a Rust type. Where the two disagree, the definition the Rust type was generated from differs from the recorded one.
+//!
+//! Example
+//! ```rust
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! // not the std_msgs/String recorded on /chatter
+//! #[derive(serde::Deserialize)]
+//! struct Counter {
+//!     count: u64,
+//!     total: u64,
+//! }
+//! impl frost::msgs::Msg for Counter {}
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let query = Query::new().with_topics(["/chatter"]);
+//! let msg_view = bag.read_messages(&query).unwrap().next().unwrap();
+//!
+//! let explanation = msg_view.explain::<Counter>();
+//! // the recorded definition decodes the message, a Counter doesn't
+//! assert!(explanation.divergence.is_none());
+//! assert!(explanation.instantiate_error.is_some());
+//! assert_eq!(explanation.fields[0].path, "data");
+//! println!("{explanation}");
+//! ```
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Range;
+
+use crate::dynamic::{
+    field_type_name, read_primitive, Cardinality, FieldType, Primitive, Reader, Schema, Value,
+};
+use crate::errors::MessageContext;
+
+/// How many of the last fields traced an [Explanation] keeps.
+const MAX_FIELDS: usize = 64;
+/// How many bytes [Explanation::hexdump] shows.
+const HEXDUMP_LEN: usize = 256;
+/// How many elements of a primitive array, or characters of a string, a [TracedField] shows.
+const MAX_VALUE_ELEMENTS: usize = 8;
+
+/// A field read while tracing a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracedField {
+    /// The `.` separated path of the field, with the indices of message array elements, like `points.2.x`.
+    pub path: String,
+    /// The field's type as written in definitions, like `float64[]`.
+    pub field_type: String,
+    /// The bytes of the field in the message, without its leading 4 byte length.
+    pub range: Range<usize>,
+    /// The field's value, shortened for long strings and arrays.
+    pub value: String,
+}
+
+/// Where tracing a message against its recorded definition failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The path of the field that couldn't be read, or the message type if its definition doesn't parse.
+    pub path: String,
+    pub field_type: String,
+    /// The offset of the field in the message, without its leading 4 byte length.
+    pub offset: usize,
+    pub reason: String,
+}
+
+/// A field-by-field diagnosis of a message, from [MessageView::explain](crate::msgs::MessageView::explain).
+///
+/// Its [Display](fmt::Display) is a report of the message, the traced fields, where they diverged from the bytes and
+/// a [hexdump](Explanation::hexdump) around it.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    /// Where the message is in its bag.
+    pub context: MessageContext,
+    /// The md5sum of the message's connection.
+    pub md5sum: String,
+    /// The `ros_type` and `md5sum` of the Rust type, if known.
+    pub expected_type: Option<&'static str>,
+    pub expected_md5sum: Option<&'static str>,
+    /// The error instantiating the message as the Rust type, if it failed.
+    pub instantiate_error: Option<String>,
+    /// The last fields traced, in order.
+    pub fields: Vec<TracedField>,
+    /// How many fields were traced before [Explanation::fields].
+    pub omitted_fields: usize,
+    /// The first field of the recorded definition the message couldn't hold.
+    pub divergence: Option<Divergence>,
+    /// The bytes left after the last field, when every field was traced.
+    pub trailing_bytes: usize,
+    /// The message, without its leading 4 byte length.
+    pub data: Vec<u8>,
+}
+
+impl Explanation {
+    /// Traces `data` against `schema`, the recorded definition, or explains why the definition doesn't parse.
+    pub(crate) fn new(
+        context: MessageContext,
+        md5sum: String,
+        schema: Result<&Schema, &str>,
+        data: &[u8],
+    ) -> Self {
+        let mut fields = VecDeque::new();
+        let mut omitted_fields = 0;
+        let mut trailing_bytes = 0;
+        let divergence = match schema {
+            Ok(schema) => {
+                let mut tracer = Tracer {
+                    schema,
+                    reader: Reader { data, pos: 0 },
+                    fields: VecDeque::new(),
+                    omitted_fields: 0,
+                };
+                let divergence = tracer.message(schema.data_type(), "").err();
+                if divergence.is_none() {
+                    trailing_bytes = tracer.reader.remaining();
+                }
+                fields = tracer.fields;
+                omitted_fields = tracer.omitted_fields;
+                divergence
+            }
+            Err(reason) => Some(Divergence {
+                path: context.data_type.clone(),
+                field_type: context.data_type.clone(),
+                offset: 0,
+                reason: format!("the recorded definition doesn't parse: {reason}"),
+            }),
+        };
+        Explanation {
+            context,
+            md5sum,
+            expected_type: None,
+            expected_md5sum: None,
+            instantiate_error: None,
+            fields: fields.into(),
+            omitted_fields,
+            divergence,
+            trailing_bytes,
+            data: data.to_vec(),
+        }
+    }
+
+    /// Returns a hexdump of up to 256 bytes of the message, starting a little before the divergence if there is one,
+    /// with offsets from the start of the message after its leading 4 byte length.
+    pub fn hexdump(&self) -> String {
+        let start = match &self.divergence {
+            Some(divergence) => divergence.offset.saturating_sub(HEXDUMP_LEN / 2) / 16 * 16,
+            None => 0,
+        };
+        let end = self.data.len().min(start + HEXDUMP_LEN);
+        let mut dump = String::new();
+        for (line, bytes) in self.data[start.min(end)..end].chunks(16).enumerate() {
+            let hex: Vec<String> = (0..16)
+                .map(|i| match bytes.get(i) {
+                    Some(byte) => format!("{byte:02x}"),
+                    None => "  ".to_string(),
+                })
+                .collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7e => byte as char,
+                    _ => '.',
+                })
+                .collect();
+            dump.push_str(&format!(
+                "{:08x}  {}  {}  |{ascii}|\n",
+                start + line * 16,
+                hex[..8].join(" "),
+                hex[8..].join(" ")
+            ));
+        }
+        if end < self.data.len() {
+            dump.push_str(&format!("... {} more bytes\n", self.data.len() - end));
+        }
+        dump
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.context)?;
+        writeln!(
+            f,
+            "recorded as {} ({}), {} bytes",
+            self.context.data_type,
+            self.md5sum,
+            self.data.len()
+        )?;
+        if self.expected_type.is_some() || self.expected_md5sum.is_some() {
+            writeln!(
+                f,
+                "instantiating as {} ({})",
+                self.expected_type.unwrap_or("an unknown type"),
+                self.expected_md5sum.unwrap_or("unknown md5sum")
+            )?;
+        }
+        match &self.instantiate_error {
+            Some(error) => writeln!(f, "instantiating failed: {error}")?,
+            None => writeln!(f, "instantiating succeeded")?,
+        }
+
+        writeln!(f, "\nfields traced with the recorded definition:")?;
+        if self.omitted_fields > 0 {
+            writeln!(f, "  ... {} earlier fields", self.omitted_fields)?;
+        }
+        for field in &self.fields {
+            writeln!(
+                f,
+                "  {:>6}..{:<6} {} {} = {}",
+                field.range.start, field.range.end, field.field_type, field.path, field.value
+            )?;
+        }
+        match &self.divergence {
+            Some(divergence) => writeln!(
+                f,
+                "diverged at byte {}, {} {}: {}",
+                divergence.offset, divergence.field_type, divergence.path, divergence.reason
+            )?,
+            None if self.trailing_bytes > 0 => writeln!(
+                f,
+                "every field was read, leaving {} bytes unread",
+                self.trailing_bytes
+            )?,
+            None => writeln!(f, "every field was read, and every byte")?,
+        }
+        write!(f, "\n{}", self.hexdump())
+    }
+}
+
+struct Tracer<'a> {
+    schema: &'a Schema,
+    reader: Reader<'a>,
+    fields: VecDeque<TracedField>,
+    omitted_fields: usize,
+}
+
+impl<'a> Tracer<'a> {
+    fn push(&mut self, path: String, field_type: String, start: usize, value: String) {
+        if self.fields.len() == MAX_FIELDS {
+            self.fields.pop_front();
+            self.omitted_fields += 1;
+        }
+        self.fields.push_back(TracedField {
+            path,
+            field_type,
+            range: start..self.reader.pos,
+            value,
+        });
+    }
+
+    fn message(&mut self, data_type: &str, path: &str) -> Result<(), Divergence> {
+        let definition = self
+            .schema
+            .definition(data_type)
+            .ok_or_else(|| Divergence {
+                path: path.to_string(),
+                field_type: data_type.to_string(),
+                offset: self.reader.pos,
+                reason: "the type has no definition".to_string(),
+            })?;
+        for field in &definition.fields {
+            let path = match path {
+                "" => field.name.clone(),
+                path => format!("{path}.{}", field.name),
+            };
+            let element_type = field_type_name(&field.field_type);
+            let start = self.reader.pos;
+            let (len, type_name) = match field.cardinality {
+                Cardinality::Single => {
+                    self.single(&field.field_type, &path)?;
+                    continue;
+                }
+                Cardinality::Fixed(len) => (len, format!("{element_type}[{len}]")),
+                Cardinality::Variable => {
+                    let type_name = format!("{element_type}[]");
+                    (self.length(&path, &type_name)?, type_name)
+                }
+            };
+            match &field.field_type {
+                FieldType::Primitive(primitive) => {
+                    self.primitives(*primitive, len, type_name, &path, start)?
+                }
+                FieldType::Message(data_type) => {
+                    let min_size = self.min_size(data_type);
+                    self.fits(len, min_size, &path, &type_name, start)?;
+                    if min_size == 0 {
+                        continue;
+                    }
+                    for i in 0..len {
+                        self.message(data_type, &format!("{path}.{i}"))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn single(&mut self, field_type: &FieldType, path: &str) -> Result<(), Divergence> {
+        let primitive = match field_type {
+            FieldType::Primitive(primitive) => *primitive,
+            FieldType::Message(data_type) => return self.message(data_type, path),
+        };
+        let start = self.reader.pos;
+        if primitive == Primitive::String {
+            let len = self.length(path, "string")?;
+            let bytes = self.reader.take(len).map_err(|_| Divergence {
+                path: path.to_string(),
+                field_type: "string".to_string(),
+                offset: start,
+                reason: format!(
+                    "its length is {len} bytes, but only {} are left",
+                    self.reader.remaining()
+                ),
+            })?;
+            let value = String::from_utf8_lossy(bytes);
+            let mut shown: String = value.chars().take(MAX_VALUE_ELEMENTS * 8).collect();
+            if shown.len() < value.len() {
+                shown.push_str("...");
+            }
+            self.push(
+                path.to_string(),
+                "string".to_string(),
+                start,
+                format!("{shown:?}"),
+            );
+            return Ok(());
+        }
+        let value = self.primitive(primitive, path, primitive.name())?;
+        self.push(path.to_string(), primitive.name().to_string(), start, value);
+        Ok(())
+    }
+
+    /// Reads a primitive array of `len` elements as a single field.
+    fn primitives(
+        &mut self,
+        primitive: Primitive,
+        len: usize,
+        type_name: String,
+        path: &str,
+        start: usize,
+    ) -> Result<(), Divergence> {
+        self.fits(len, primitive.min_size(), path, &type_name, start)?;
+        let mut values = Vec::new();
+        for i in 0..len {
+            let element_path = format!("{path}.{i}");
+            let value = if primitive == Primitive::String {
+                let len = self.length(&element_path, "string")?;
+                self.reader.take(len).map_err(|_| Divergence {
+                    path: element_path.clone(),
+                    field_type: "string".to_string(),
+                    offset: self.reader.pos - 4,
+                    reason: format!(
+                        "its length is {len} bytes, but only {} are left",
+                        self.reader.remaining()
+                    ),
+                })?;
+                None
+            } else {
+                Some(self.primitive(primitive, &element_path, primitive.name())?)
+            };
+            if i < MAX_VALUE_ELEMENTS {
+                values.push(value.unwrap_or_else(|| "..".to_string()));
+            }
+        }
+        if len > MAX_VALUE_ELEMENTS {
+            values.push("...".to_string());
+        }
+        let value = format!("[{}] ({len} elements)", values.join(", "));
+        self.push(path.to_string(), type_name, start, value);
+        Ok(())
+    }
+
+    fn primitive(
+        &mut self,
+        primitive: Primitive,
+        path: &str,
+        type_name: &str,
+    ) -> Result<String, Divergence> {
+        let offset = self.reader.pos;
+        let value = read_primitive(primitive, &mut self.reader).map_err(|_| Divergence {
+            path: path.to_string(),
+            field_type: type_name.to_string(),
+            offset,
+            reason: format!(
+                "it needs {} bytes, but only {} are left",
+                primitive.min_size(),
+                self.reader.remaining()
+            ),
+        })?;
+        Ok(match value {
+            Value::Time(time) => format!("{}.{:09}", time.secs, time.nsecs),
+            Value::Duration { secs, nsecs } => format!("{secs}s {nsecs}ns"),
+            value => serde_json::to_string(&value).unwrap_or_default(),
+        })
+    }
+
+    /// Reads the length of a string or variable length array.
+    fn length(&mut self, path: &str, type_name: &str) -> Result<usize, Divergence> {
+        let offset = self.reader.pos;
+        self.reader
+            .u32()
+            .map(|len| len as usize)
+            .map_err(|_| Divergence {
+                path: path.to_string(),
+                field_type: type_name.to_string(),
+                offset,
+                reason: format!(
+                    "its 4 byte length doesn't fit, only {} bytes are left",
+                    self.reader.remaining()
+                ),
+            })
+    }
+
+    /// Checks that `len` elements of at least `min_size` bytes fit in the rest of the message, for an array starting
+    /// at `start`.
+    fn fits(
+        &self,
+        len: usize,
+        min_size: usize,
+        path: &str,
+        type_name: &str,
+        start: usize,
+    ) -> Result<(), Divergence> {
+        if len.saturating_mul(min_size) <= self.reader.remaining() {
+            return Ok(());
+        }
+        Err(Divergence {
+            path: path.to_string(),
+            field_type: type_name.to_string(),
+            offset: start,
+            reason: format!(
+                "its {len} elements need at least {} bytes, but only {} are left",
+                len.saturating_mul(min_size),
+                self.reader.remaining()
+            ),
+        })
+    }
+
+    /// The smallest number of bytes a message of `data_type` is serialized to.
+    fn min_size(&self, data_type: &str) -> usize {
+        let Some(definition) = self.schema.definition(data_type) else {
+            return 0;
+        };
+        definition
+            .fields
+            .iter()
+            .map(|field| {
+                let size = match &field.field_type {
+                    FieldType::Primitive(primitive) => primitive.min_size(),
+                    FieldType::Message(data_type) => self.min_size(data_type),
+                };
+                match field.cardinality {
+                    Cardinality::Single => size,
+                    Cardinality::Fixed(len) => len.saturating_mul(size),
+                    Cardinality::Variable => 4,
+                }
+            })
+            .fold(0, usize::saturating_add)
+    }
+}
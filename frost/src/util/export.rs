@@ -0,0 +1,3 @@
+//! Shared building blocks for commands that write parts of bags out as files, like `frost images` and
+//! `frost split-topics`.
+pub mod naming;
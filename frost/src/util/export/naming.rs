@@ -0,0 +1,355 @@
+//! Naming exported files from a template like `{topic}/{stamp_ns}.{ext}`.
+//!
+//! A [NameTemplate] is a path relative to an output directory, with placeholders filled in for each file:
+//! - `{topic}`: the message's topic without its leading `/`, so each topic gets its own directory.
+//! - `{type}`: the message's type, like `sensor_msgs_Image`.
+//! - `{stamp_ns}`: the receive time in nanoseconds since the epoch.
+//! - `{stamp}`: the receive time as seconds with 9 decimals.
+//! - `{seq}`: how many files were named for the same topic before this one, counting from 0.
+//! - `{ext}`: the extension of the exporter's format, like `png`.
+//!
+//! Exporters may offer others, like the `{stem}` and `{group}` of `frost split-topics`. `/` in values other than
+//! `{topic}` is replaced with `_`, and `{{` and `}}` are literal braces.
+//!
+//! A [FileNamer] renders the template for each file and handles collisions: a name it already gave out is never
+//! reused, getting a `_1`, `_2`, ... suffix before its extension instead, and names of existing files are
+//! overwritten, suffixed, or refused depending on its [Collision].
+//!
+//! Example
+//! ```rust
+//! use frost::export::naming::{FileNamer, NameFields};
+//! use frost::time::Time;
+//!
+//! let dir = std::env::temp_dir().join("frost-naming-example");
+//! let mut namer = FileNamer::new(&dir, "{topic}/{stamp_ns}.{ext}".parse().unwrap());
+//! let time = Time { secs: 1, nsecs: 5 };
+//! let fields = NameFields::message("/camera/image", "sensor_msgs/Image", time).with_ext("png");
+//! assert_eq!(
+//!     namer.name(&fields).unwrap(),
+//!     dir.join("camera/image/1000000005.png")
+//! );
+//! // two messages received at the same time
+//! assert_eq!(
+//!     namer.name(&fields).unwrap(),
+//!     dir.join("camera/image/1000000005_1.png")
+//! );
+//! ```
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::errors::Error;
+use crate::msgs::MessageView;
+use crate::time::Time;
+
+/// The placeholders of [NameFields::message] with `{ext}`, and `{seq}`, for exporters writing a file per message.
+pub const MESSAGE_PLACEHOLDERS: &[&str] = &["topic", "type", "stamp_ns", "stamp", "seq", "ext"];
+
+/// A piece of a [NameTemplate].
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A file name template, see [the module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameTemplate {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl NameTemplate {
+    /// The names of the template's placeholders, in order.
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder(name) => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Fails naming the first placeholder that isn't in `available`, so exporters can reject templates up front
+    /// rather than on the first file.
+    pub fn check(&self, available: &[&str]) -> Result<(), Error> {
+        match self.placeholders().find(|name| !available.contains(name)) {
+            Some(name) => Err(invalid(format!(
+                "unknown placeholder {{{name}}} in name template '{}', expected one of {}",
+                self.source,
+                available
+                    .iter()
+                    .map(|name| format!("{{{name}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Renders the template into a relative path.
+    fn render(&self, fields: &NameFields, seq: u64) -> Result<PathBuf, Error> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Placeholder(name) if name == "seq" => rendered.push_str(&seq.to_string()),
+                Part::Placeholder(name) if name == "topic" => {
+                    let topic = fields.get(name)?;
+                    rendered.push_str(topic.trim_start_matches('/'));
+                }
+                Part::Placeholder(name) => rendered.push_str(&fields.get(name)?.replace('/', "_")),
+            }
+        }
+
+        let mut path = PathBuf::new();
+        for component in Path::new(&rendered).components() {
+            match component {
+                Component::Normal(name) => path.push(name),
+                Component::CurDir => {}
+                _ => {
+                    let source = &self.source;
+                    return Err(invalid(format!(
+                        "name template '{source}' rendered '{rendered}', outside the output directory"
+                    )));
+                }
+            }
+        }
+        if path.as_os_str().is_empty() {
+            return Err(invalid(format!(
+                "name template '{}' rendered an empty name",
+                self.source
+            )));
+        }
+        Ok(path)
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("unmatched '{{' in name template '{s}'"));
+                    }
+                    if name.is_empty()
+                        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(format!(
+                            "invalid placeholder {{{name}}} in name template '{s}'"
+                        ));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(name));
+                }
+                '}' => return Err(format!("unmatched '}}' in name template '{s}'")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        if Path::new(s).has_root() {
+            return Err(format!(
+                "name template '{s}' must be relative to the output directory"
+            ));
+        }
+        Ok(NameTemplate {
+            source: s.to_string(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The values of a template's placeholders for one file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NameFields {
+    values: BTreeMap<String, String>,
+}
+
+impl NameFields {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `{topic}`, `{type}`, `{stamp_ns}`, and `{stamp}` of a message.
+    pub fn message(topic: &str, data_type: &str, time: Time) -> Self {
+        Self::new()
+            .with("topic", topic)
+            .with("type", data_type)
+            .with(
+                "stamp_ns",
+                (u64::from(time.secs) * 1_000_000_000 + u64::from(time.nsecs)).to_string(),
+            )
+            .with("stamp", format!("{}.{:09}", time.secs, time.nsecs))
+    }
+
+    /// The fields of [NameFields::message] for a message read from a bag.
+    pub fn for_message(msg_view: &MessageView) -> Self {
+        Self::message(
+            msg_view.topic,
+            &msg_view.connection().data_type,
+            msg_view.time,
+        )
+    }
+
+    /// Sets `{ext}`.
+    pub fn with_ext(self, ext: &str) -> Self {
+        self.with("ext", ext)
+    }
+
+    /// Sets a placeholder.
+    pub fn with<S: Into<String>>(mut self, name: &str, value: S) -> Self {
+        self.values.insert(name.to_string(), value.into());
+        self
+    }
+
+    fn get(&self, name: &str) -> Result<&str, Error> {
+        self.values
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| invalid(format!("no value for {{{name}}} in the name template")))
+    }
+}
+
+/// What a [FileNamer] does when a name is taken by a file it didn't name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collision {
+    /// Write over the existing file.
+    Overwrite,
+    /// Add a `_1`, `_2`, ... suffix before the extension until the name is free.
+    #[default]
+    Suffix,
+    /// Fail with an [io::ErrorKind::AlreadyExists] error.
+    Fail,
+}
+
+impl FromStr for Collision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(Collision::Overwrite),
+            "suffix" => Ok(Collision::Suffix),
+            "fail" => Ok(Collision::Fail),
+            other => Err(format!(
+                "unknown collision handling '{other}', expected overwrite, suffix, or fail"
+            )),
+        }
+    }
+}
+
+/// Names the files of an export from a [NameTemplate], see [the module docs](self).
+#[derive(Debug)]
+pub struct FileNamer {
+    root: PathBuf,
+    template: NameTemplate,
+    collision: Collision,
+    /// The names given out so far.
+    named: HashSet<PathBuf>,
+    /// The `{seq}` of the next file per topic.
+    seqs: HashMap<String, u64>,
+}
+
+impl FileNamer {
+    /// Names files under `root` with `template`, suffixing the names of existing files.
+    pub fn new<P: Into<PathBuf>>(root: P, template: NameTemplate) -> Self {
+        FileNamer {
+            root: root.into(),
+            template,
+            collision: Collision::default(),
+            named: HashSet::new(),
+            seqs: HashMap::new(),
+        }
+    }
+
+    pub fn with_collision(mut self, collision: Collision) -> Self {
+        self.collision = collision;
+        self
+    }
+
+    /// The directory files are named in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn template(&self) -> &NameTemplate {
+        &self.template
+    }
+
+    /// Returns the path of the next file, creating its parent directories.
+    pub fn name(&mut self, fields: &NameFields) -> Result<PathBuf, Error> {
+        let topic = fields.values.get("topic").cloned().unwrap_or_default();
+        let seq = self.seqs.get(&topic).copied().unwrap_or_default();
+        let path = self.root.join(self.template.render(fields, seq)?);
+
+        let taken = |path: &Path| {
+            self.named.contains(path) || (self.collision != Collision::Overwrite && path.exists())
+        };
+        let mut free = path.clone();
+        let mut suffix = 0;
+        while taken(&free) {
+            if self.collision == Collision::Fail && !self.named.contains(&free) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", free.display()),
+                )
+                .into());
+            }
+            suffix += 1;
+            free = suffixed(&path, suffix);
+        }
+
+        if let Some(parent) = free.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        self.seqs.insert(topic, seq + 1);
+        self.named.insert(free.clone());
+        Ok(free)
+    }
+}
+
+/// Adds `_<suffix>` to a path's file name, before its extension.
+fn suffixed(path: &Path, suffix: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}_{suffix}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_{suffix}"),
+    };
+    path.with_file_name(name)
+}
+
+fn invalid(message: String) -> Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message).into()
+}
@@ -0,0 +1,92 @@
+//! Routes messages from a single pass over a bag to per-topic consumers.
+//!
+//! Extracting many topics with one [DecompressedBag::read_messages] call per topic reads the bag once per topic.
+//! A [Fanout] reads it once, handing each message to the handlers registered for its topic in receive time order.
+//! Consumers which want a channel can send into one from their handler.
+//!
+//! Example
+//! ```rust
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let mut chatter = Vec::new();
+//! let mut arrays = 0;
+//! let counts = bag
+//!     .fanout(&Query::all())
+//!     .unwrap()
+//!     .on_topic("/chatter", |msg| {
+//!         chatter.push(msg.raw_bytes()?.len());
+//!         Ok(())
+//!     })
+//!     .on_topic("/array", |_| {
+//!         arrays += 1;
+//!         Ok(())
+//!     })
+//!     .run()
+//!     .unwrap();
+//!
+//! assert_eq!(chatter.len(), 100);
+//! assert_eq!(arrays, 100);
+//! assert_eq!(counts["/chatter"], 100);
+//! ```
+use std::collections::BTreeMap;
+
+use crate::errors::{Error, ErrorKind};
+use crate::msgs::MessageView;
+use crate::query::{FilteredIter, Query};
+use crate::DecompressedBag;
+
+type Handler<'a, 'f> = Box<dyn FnMut(&MessageView<'a>) -> Result<(), Error> + 'f>;
+
+/// Hands each message matching a query to the handlers registered for its topic, see the [module docs](self).
+pub struct Fanout<'a, 'f> {
+    messages: FilteredIter<'a>,
+    handlers: BTreeMap<String, Vec<Handler<'a, 'f>>>,
+}
+
+impl<'a, 'f> Fanout<'a, 'f> {
+    /// Calls `handler` with every message on `topic`. A topic can have several handlers, which are called in the order they were added.
+    pub fn on_topic<S, F>(mut self, topic: S, handler: F) -> Self
+    where
+        S: Into<String>,
+        F: FnMut(&MessageView<'a>) -> Result<(), Error> + 'f,
+    {
+        self.handlers
+            .entry(topic.into())
+            .or_default()
+            .push(Box::new(handler));
+        self
+    }
+
+    /// Reads the messages, stopping at the first error returned by a handler, or with [ErrorKind::Cancelled] if the
+    /// query is cancelled, see [Query::with_cancellation].
+    ///
+    /// Returns how many messages were handed to each topic's handlers. Messages on topics without a handler are skipped.
+    pub fn run(mut self) -> Result<BTreeMap<String, usize>, Error> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for msg in self.messages.by_ref() {
+            let Some(handlers) = self.handlers.get_mut(msg.topic) else {
+                continue;
+            };
+            for handler in handlers.iter_mut() {
+                handler(&msg)?;
+            }
+            *counts.entry(msg.topic.to_string()).or_default() += 1;
+        }
+        if self.messages.was_cancelled() {
+            return Err(Error::new(ErrorKind::Cancelled));
+        }
+        Ok(counts)
+    }
+}
+
+impl DecompressedBag {
+    /// Prepares a single pass over the messages matching `query`, to be routed to handlers per topic, see [Fanout].
+    pub fn fanout<'f>(&self, query: &Query) -> Result<Fanout<'_, 'f>, Error> {
+        Ok(Fanout {
+            messages: self.filter_messages(query)?,
+            handlers: BTreeMap::new(),
+        })
+    }
+}
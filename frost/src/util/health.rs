@@ -0,0 +1,220 @@
+//! Summarizing numeric signals, like battery voltage or CPU temperature, with thresholds in a single pass over a bag.
+//!
+//! A [HealthConfig] lists signals as a topic and a field path (see [Value::get_path]), with optional bounds.
+//! Each signal is summarized by its minimum, maximum, and mean, and by the stretches of time it spent out of bounds.
+//! Configs are read from YAML files like the one below with the `yaml` feature, or deserialized with any serde format.
+//!
+//! ```yaml
+//! signals:
+//!   - name: battery voltage
+//!     topic: /battery_state
+//!     field: voltage
+//!     min: 22.0
+//!   - topic: /cpu
+//!     field: cores.0.temperature
+//!     max: 85
+//! ```
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::health::HealthConfig;
+//! use frost::DecompressedBag;
+//!
+//! let config: HealthConfig = serde_json::from_str(
+//!     r#"{"signals": [{"topic": "/battery_state", "field": "voltage", "min": 22.0}]}"#,
+//! )
+//! .unwrap();
+//! let bag = DecompressedBag::from_file("robot.bag").unwrap();
+//! let report = bag.health_report(&config).unwrap();
+//! for signal in &report.signals {
+//!     println!("{}: {} violations", signal.name, signal.violations.len());
+//! }
+//! ```
+use std::collections::{BTreeSet, HashMap};
+#[cfg(feature = "yaml")]
+use std::io;
+#[cfg(feature = "yaml")]
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dynamic::Value;
+use crate::errors::Error;
+use crate::query::Query;
+use crate::time::Time;
+use crate::DecompressedBag;
+
+/// The signals to summarize, usually read from YAML.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HealthConfig {
+    pub signals: Vec<SignalConfig>,
+}
+
+/// A numeric field of the messages on a topic, with optional inclusive bounds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignalConfig {
+    /// Defaults to `<topic>/<field>`.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub topic: String,
+    /// A `.` separated field path, see [Value::get_path].
+    pub field: String,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+impl SignalConfig {
+    fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}", self.topic, self.field))
+    }
+
+    fn in_bounds(&self, value: f64) -> bool {
+        self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value <= max)
+    }
+}
+
+impl HealthConfig {
+    /// Requires the `yaml` feature.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        Ok(
+            serde_yaml::from_str(yaml)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        )
+    }
+
+    /// Requires the `yaml` feature.
+    #[cfg(feature = "yaml")]
+    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
+        Self::from_yaml(&std::fs::read_to_string(file_path)?)
+    }
+}
+
+/// A stretch of consecutive samples outside a signal's bounds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// The receive times of the first and last samples out of bounds.
+    pub start: Time,
+    pub end: Time,
+    pub samples: usize,
+    /// The value furthest outside the bounds.
+    pub worst: f64,
+}
+
+impl Violation {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(&self.start)
+    }
+}
+
+/// The summary of one [SignalConfig]. The statistics are `None` if the signal had no samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignalSummary {
+    pub name: String,
+    pub config: SignalConfig,
+    pub count: usize,
+    /// Messages on the topic where the field was missing or not a number.
+    pub missing: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub violations: Vec<Violation>,
+    sum: f64,
+    in_violation: bool,
+}
+
+impl SignalSummary {
+    fn new(config: &SignalConfig) -> Self {
+        SignalSummary {
+            name: config.name(),
+            config: config.clone(),
+            count: 0,
+            missing: 0,
+            min: None,
+            max: None,
+            mean: None,
+            violations: Vec::new(),
+            sum: 0.0,
+            in_violation: false,
+        }
+    }
+
+    fn push(&mut self, time: Time, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        self.mean = Some(self.sum / self.count as f64);
+
+        if self.config.in_bounds(value) {
+            self.in_violation = false;
+            return;
+        }
+        let excess = |v: f64| {
+            let below = self.config.min.map_or(0.0, |min| min - v);
+            let above = self.config.max.map_or(0.0, |max| v - max);
+            below.max(above)
+        };
+        match self.violations.last_mut() {
+            Some(violation) if self.in_violation => {
+                violation.end = time;
+                violation.samples += 1;
+                if excess(value) > excess(violation.worst) {
+                    violation.worst = value;
+                }
+            }
+            _ => self.violations.push(Violation {
+                start: time,
+                end: time,
+                samples: 1,
+                worst: value,
+            }),
+        }
+        self.in_violation = true;
+    }
+}
+
+/// The summaries of a [HealthConfig]'s signals, in the config's order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HealthReport {
+    pub signals: Vec<SignalSummary>,
+}
+
+impl HealthReport {
+    /// Returns whether no signal left its bounds.
+    pub fn is_healthy(&self) -> bool {
+        self.signals
+            .iter()
+            .all(|signal| signal.violations.is_empty())
+    }
+}
+
+impl DecompressedBag {
+    /// Summarizes the signals of `config` over the whole bag, see the [health module](self).
+    pub fn health_report(&self, config: &HealthConfig) -> Result<HealthReport, Error> {
+        let mut signals: Vec<_> = config.signals.iter().map(SignalSummary::new).collect();
+        let mut by_topic: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, signal) in config.signals.iter().enumerate() {
+            by_topic.entry(&signal.topic).or_default().push(i);
+        }
+        let topics: BTreeSet<&str> = by_topic.keys().copied().collect();
+
+        for msg_view in self.read_messages(&Query::new().with_topics(topics))? {
+            let value = msg_view.decode()?;
+            for &i in by_topic.get(msg_view.topic).into_iter().flatten() {
+                let signal = &mut signals[i];
+                match value.get_path(&signal.config.field).and_then(Value::as_f64) {
+                    Some(sample) => signal.push(msg_view.time, sample),
+                    None => signal.missing += 1,
+                }
+            }
+        }
+        Ok(HealthReport { signals })
+    }
+}
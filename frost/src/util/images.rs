@@ -0,0 +1,202 @@
+//! Converting `sensor_msgs/Image` messages into [DynamicImage]s, enabled with the `images` feature.
+//!
+//! Supported encodings are `rgb8`, `rgba8`, `bgr8`, `bgra8`, `mono8`, `mono16`, their OpenCV style aliases
+//! (`8UC1`, `8UC3`, `8UC4`, `16UC1`), and the 8 bit bayer patterns, which are demosaiced into RGB.
+//! Row padding (a `step` wider than the pixels) and big endian 16 bit images are handled.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("camera.bag").unwrap();
+//! let query = Query::new().with_topics(["/camera/image_raw"]);
+//! for (i, msg_view) in bag.read_messages(&query).unwrap().enumerate() {
+//!     let image = msg_view.decode_image().unwrap();
+//!     println!("{i}: {}x{}", image.width(), image.height());
+//! }
+//! ```
+use image::{DynamicImage, ImageBuffer, Pixel, Rgb, RgbImage};
+
+use crate::dynamic::{required, Value};
+use crate::errors::{DecodeError, Error};
+use crate::msgs::MessageView;
+
+const IMAGE_TYPE: &str = "sensor_msgs/Image";
+
+/// The fields of a decoded `sensor_msgs/Image`, borrowing its pixel data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawImage<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub encoding: &'a str,
+    pub is_bigendian: bool,
+    /// The length of a row in bytes, including any padding.
+    pub step: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> RawImage<'a> {
+    /// Reads the fields of a `sensor_msgs/Image` decoded with [crate::dynamic].
+    pub fn from_value(value: &'a Value) -> Result<Self, DecodeError> {
+        let u32_field = |field| match required(value, IMAGE_TYPE, field)? {
+            Value::U32(v) => Ok(*v),
+            _ => Err(DecodeError::InvalidField(format!(
+                "{IMAGE_TYPE}.{field} is not a uint32"
+            ))),
+        };
+        let encoding = required(value, IMAGE_TYPE, "encoding")?
+            .as_str()
+            .ok_or_else(|| {
+                DecodeError::InvalidField(format!("{IMAGE_TYPE}.encoding is not a string"))
+            })?;
+        let is_bigendian = match required(value, IMAGE_TYPE, "is_bigendian")? {
+            Value::U8(v) => *v != 0,
+            Value::Bool(v) => *v,
+            _ => {
+                return Err(DecodeError::InvalidField(format!(
+                    "{IMAGE_TYPE}.is_bigendian is not a uint8"
+                )))
+            }
+        };
+        let data = match required(value, IMAGE_TYPE, "data")? {
+            Value::Bytes(bytes) => bytes.as_slice(),
+            _ => {
+                return Err(DecodeError::InvalidField(format!(
+                    "{IMAGE_TYPE}.data is not a uint8[]"
+                )))
+            }
+        };
+
+        Ok(RawImage {
+            width: u32_field("width")?,
+            height: u32_field("height")?,
+            encoding,
+            is_bigendian,
+            step: u32_field("step")?,
+            data,
+        })
+    }
+
+    /// Converts the pixels into an image, see the [module docs](self) for supported encodings.
+    pub fn to_image(&self) -> Result<DynamicImage, DecodeError> {
+        Ok(match self.encoding {
+            "rgb8" => {
+                DynamicImage::ImageRgb8(self.convert(3, |px, out| out.extend_from_slice(px))?)
+            }
+            // cv_bridge treats 3 and 4 channel OpenCV images as BGR(A)
+            "bgr8" | "8UC3" => DynamicImage::ImageRgb8(
+                self.convert(3, |px, out| out.extend_from_slice(&[px[2], px[1], px[0]]))?,
+            ),
+            "rgba8" => {
+                DynamicImage::ImageRgba8(self.convert(4, |px, out| out.extend_from_slice(px))?)
+            }
+            "bgra8" | "8UC4" => DynamicImage::ImageRgba8(self.convert(4, |px, out| {
+                out.extend_from_slice(&[px[2], px[1], px[0], px[3]])
+            })?),
+            "mono8" | "8UC1" => {
+                DynamicImage::ImageLuma8(self.convert(1, |px, out| out.extend_from_slice(px))?)
+            }
+            "mono16" | "16UC1" => {
+                let big_endian = self.is_bigendian;
+                DynamicImage::ImageLuma16(self.convert(2, |px, out| {
+                    let bytes = [px[0], px[1]];
+                    out.push(if big_endian {
+                        u16::from_be_bytes(bytes)
+                    } else {
+                        u16::from_le_bytes(bytes)
+                    })
+                })?)
+            }
+            "bayer_rggb8" => DynamicImage::ImageRgb8(self.demosaic(*b"rggb")?),
+            "bayer_bggr8" => DynamicImage::ImageRgb8(self.demosaic(*b"bggr")?),
+            "bayer_gbrg8" => DynamicImage::ImageRgb8(self.demosaic(*b"gbrg")?),
+            "bayer_grbg8" => DynamicImage::ImageRgb8(self.demosaic(*b"grbg")?),
+            other => return Err(DecodeError::UnsupportedEncoding(other.to_string())),
+        })
+    }
+
+    /// Returns the pixel bytes of a row, checking the data is as large as the dimensions say.
+    fn row(&self, y: u32, bytes_per_pixel: u32) -> Result<&'a [u8], DecodeError> {
+        let row_len = self.width as usize * bytes_per_pixel as usize;
+        if (self.step as usize) < row_len {
+            return Err(DecodeError::InvalidField(format!(
+                "{IMAGE_TYPE}.step of {} is shorter than a {} pixel {} row",
+                self.step, self.width, self.encoding
+            )));
+        }
+        let start = y as usize * self.step as usize;
+        self.data.get(start..start + row_len).ok_or_else(|| {
+            DecodeError::InvalidField(format!(
+                "{IMAGE_TYPE}.data has {} bytes, too few for {} rows of {} bytes",
+                self.data.len(),
+                self.height,
+                self.step
+            ))
+        })
+    }
+
+    /// Builds an image row by row, with `pixel` pushing the channels of each pixel from its bytes.
+    fn convert<P, F>(
+        &self,
+        bytes_per_pixel: u32,
+        pixel: F,
+    ) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, DecodeError>
+    where
+        P: Pixel,
+        F: Fn(&[u8], &mut Vec<P::Subpixel>),
+    {
+        let mut buf = Vec::with_capacity(
+            self.width as usize * self.height as usize * usize::from(P::CHANNEL_COUNT),
+        );
+        for y in 0..self.height {
+            for px in self
+                .row(y, bytes_per_pixel)?
+                .chunks_exact(bytes_per_pixel as usize)
+            {
+                pixel(px, &mut buf);
+            }
+        }
+        Ok(ImageBuffer::from_raw(self.width, self.height, buf)
+            .expect("every pixel pushes one value per channel"))
+    }
+
+    /// Fills each pixel from its 2x2 cell of the bayer `pattern`, averaging the two greens.
+    fn demosaic(&self, pattern: [u8; 4]) -> Result<RgbImage, DecodeError> {
+        if self.width < 2 || self.height < 2 {
+            return Err(DecodeError::InvalidField(format!(
+                "a {}x{} bayer image is smaller than one 2x2 cell",
+                self.width, self.height
+            )));
+        }
+        let rows = (0..self.height)
+            .map(|y| self.row(y, 1))
+            .collect::<Result<Vec<_>, _>>()?;
+        // positions within a cell, in row major order
+        let position = |channel| pattern.iter().position(|c| *c == channel).unwrap();
+        let last_green = pattern.iter().rposition(|c| *c == b'g').unwrap();
+        let (red, green, blue) = (position(b'r'), position(b'g'), position(b'b'));
+        Ok(RgbImage::from_fn(self.width, self.height, |x, y| {
+            // pixels in a trailing odd row or column share the previous cell
+            let (cx, cy) = (
+                (x & !1).min((self.width - 2) & !1) as usize,
+                (y & !1).min((self.height - 2) & !1) as usize,
+            );
+            let sample = |i: usize| rows[cy + i / 2][cx + i % 2];
+            let green = (u16::from(sample(green)) + u16::from(sample(last_green))) / 2;
+            Rgb([sample(red), green as u8, sample(blue)])
+        }))
+    }
+}
+
+/// Converts a `sensor_msgs/Image` decoded with [crate::dynamic], see [RawImage::to_image].
+pub fn to_image(value: &Value) -> Result<DynamicImage, DecodeError> {
+    RawImage::from_value(value)?.to_image()
+}
+
+impl<'a> MessageView<'a> {
+    /// Decodes a `sensor_msgs/Image` message into an image, see the [images module](self).
+    pub fn decode_image(&self) -> Result<DynamicImage, Error> {
+        Ok(to_image(&self.decode()?)?)
+    }
+}
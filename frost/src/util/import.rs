@@ -0,0 +1,423 @@
+//! Importing MCAP files and rosbag2 bags into ROS 1 bags, for analysis stacks which are still ROS 1 based.
+//!
+//! Messages already serialized for ROS 1, like those of files written by [McapWriter](crate::mcap::McapWriter), are
+//! copied as they are. CDR messages recorded with ROS 2 are converted with a `Ros2Type`, see `frost::ros2` for how
+//! their types are translated. Their definitions come from the MCAP schemas or rosbag2's `message_definitions`
+//! table, or, for bags recorded before rosbag2 stored them, from `.msg` files added with
+//! `Importer::with_message_sources`. Messages which can't be converted are skipped and counted in the
+//! [ImportReport]. Converting them needs the `ros2` feature, without which they are skipped.
+//!
+//! rosbag2's default sqlite3 storage (`.db3` files) needs the `rosbag2` feature, while its MCAP storage is read like
+//! any other MCAP file. Packet captures of ROS 1 traffic are imported with [Importer::import_pcap].
+//!
+//! Example
+//! ```rust
+//! use std::io::Cursor;
+//! use frost::import::Importer;
+//! use frost::mcap::{McapReader, McapWriter};
+//! use frost::query::Query;
+//! use frost::writer::BagWriter;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let mut mcap = McapWriter::new(Vec::new()).unwrap();
+//! mcap.copy_messages(&bag, &Query::all()).unwrap();
+//! let mcap = mcap.finish().unwrap();
+//!
+//! let mut importer = Importer::new(BagWriter::new(Cursor::new(Vec::new())).unwrap());
+//! importer
+//!     .import_mcap(McapReader::new(mcap.as_slice()).unwrap())
+//!     .unwrap();
+//! let (_, report) = importer.finish().unwrap();
+//! assert_eq!(report.message_count, 300);
+//! assert!(report.skipped.is_empty());
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, Write};
+#[cfg(feature = "ros2")]
+use std::sync::Arc;
+
+use crate::errors::Error;
+use crate::mcap::{McapChannel, McapReader, McapSchema};
+use crate::pcap::PcapReader;
+#[cfg(feature = "ros2")]
+use crate::ros2::{MessageSources, Ros2Type};
+#[cfg(feature = "md5")]
+use crate::schemas::definition_md5sum;
+use crate::time::Time;
+use crate::util::parsing::from_hex;
+use crate::writer::BagWriter;
+use crate::{ConnectionData, ConnectionID};
+
+/// What was imported by an [Importer].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Messages written to the bag.
+    pub message_count: usize,
+    /// Messages which couldn't be converted, by topic.
+    pub skipped: BTreeMap<String, Skipped>,
+    /// Problems with inputs which didn't stop the import, like connections a capture lost packets of.
+    pub warnings: Vec<String>,
+}
+
+/// Messages on a topic which couldn't be converted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Skipped {
+    pub message_count: usize,
+    /// Why the first of them was skipped.
+    pub reason: String,
+}
+
+/// How the messages of a channel or rosbag2 topic are written.
+#[derive(Clone)]
+enum Conversion {
+    Copy(ConnectionID),
+    #[cfg(feature = "ros2")]
+    Cdr(ConnectionID, Arc<Ros2Type>),
+    Skip(String),
+}
+
+/// A message type as an input describes it.
+struct TypeInfo<'a> {
+    topic: &'a str,
+    data_type: &'a str,
+    message_encoding: &'a str,
+    /// `(encoding, definition)`, if the input has one
+    definition: Option<(&'a str, &'a str)>,
+    metadata: &'a BTreeMap<String, String>,
+}
+
+/// Writes the messages of MCAP files and rosbag2 bags into a [BagWriter], see the [module docs](self).
+pub struct Importer<W: Write + Seek> {
+    writer: BagWriter<W>,
+    #[cfg(feature = "ros2")]
+    sources: MessageSources,
+    /// translated types, by ROS 2 type and definition
+    #[cfg(feature = "ros2")]
+    types: HashMap<(String, String), Result<Arc<Ros2Type>, String>>,
+    report: ImportReport,
+}
+
+impl<W: Write + Seek> Importer<W> {
+    pub fn new(writer: BagWriter<W>) -> Self {
+        Importer {
+            writer,
+            #[cfg(feature = "ros2")]
+            sources: MessageSources::new(),
+            #[cfg(feature = "ros2")]
+            types: HashMap::new(),
+            report: ImportReport::default(),
+        }
+    }
+
+    /// Sets the `.msg` files used for types whose definitions an input doesn't store.
+    #[cfg(feature = "ros2")]
+    pub fn with_message_sources(mut self, sources: MessageSources) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Imports every message of an MCAP file.
+    pub fn import_mcap<R: Read>(&mut self, mut reader: McapReader<R>) -> Result<(), Error> {
+        let mut conversions: HashMap<u16, Conversion> = HashMap::new();
+        reader.read_messages(|channel, schema, message| {
+            let conversion = match conversions.get(&channel.id) {
+                Some(conversion) => conversion.clone(),
+                None => {
+                    let conversion = self.mcap_conversion(channel, schema);
+                    conversions.insert(channel.id, conversion.clone());
+                    conversion
+                }
+            };
+            let time = nanos_to_time(message.log_time);
+            self.write(&channel.topic, &conversion, time, message.data)
+        })?;
+        Ok(())
+    }
+
+    /// Imports the TCPROS messages of a packet capture, see [crate::pcap]. Connections the capture lost packets of
+    /// are added to the report's warnings.
+    pub fn import_pcap<R: Read>(&mut self, mut reader: PcapReader<R>) -> Result<(), Error> {
+        let mut conversions: HashMap<u32, Conversion> = HashMap::new();
+        reader.read_messages(|connection, time, data| {
+            let conversion = conversions
+                .entry(connection.connection_id)
+                .or_insert_with(|| Conversion::Copy(self.writer.add_connection(connection)))
+                .clone();
+            self.write(&connection.topic, &conversion, time, data)
+        })?;
+        self.report
+            .warnings
+            .extend(reader.warnings().iter().map(ToString::to_string));
+        Ok(())
+    }
+
+    fn mcap_conversion(
+        &mut self,
+        channel: &McapChannel,
+        schema: Option<&McapSchema>,
+    ) -> Conversion {
+        let definition = match schema {
+            Some(schema) => match std::str::from_utf8(&schema.data) {
+                Ok(definition) if !definition.is_empty() => {
+                    Some((schema.encoding.as_str(), definition))
+                }
+                _ => None,
+            },
+            None => None,
+        };
+        self.conversion(&TypeInfo {
+            topic: &channel.topic,
+            data_type: schema.map_or("", |schema| schema.name.as_str()),
+            message_encoding: &channel.message_encoding,
+            definition,
+            metadata: &channel.metadata,
+        })
+    }
+
+    fn conversion(&mut self, info: &TypeInfo) -> Conversion {
+        match info.message_encoding {
+            "ros1" => {
+                let Some(("ros1msg", definition)) = info.definition else {
+                    return Conversion::Skip(format!(
+                        "no ros1msg definition for {}",
+                        info.data_type
+                    ));
+                };
+                let md5sum = info.metadata.get("md5sum").cloned();
+                #[cfg(feature = "md5")]
+                let md5sum = md5sum.or_else(|| definition_md5sum(info.data_type, definition));
+                let md5sum = md5sum.unwrap_or_else(|| "*".to_string());
+                // names which aren't UTF-8, as recorded, see `McapWriter::add_connection`
+                let header_fields = [("topic", "topic_hex"), ("type", "type_hex")]
+                    .into_iter()
+                    .filter_map(|(field, key)| {
+                        let bytes = from_hex(info.metadata.get(key)?)?;
+                        Some((field.to_string(), bytes))
+                    })
+                    .collect();
+                let connection = ConnectionData {
+                    connection_id: 0,
+                    topic: info.topic.to_string(),
+                    data_type: info.data_type.to_string(),
+                    md5sum,
+                    message_definition: definition.to_string(),
+                    caller_id: info.metadata.get("callerid").cloned(),
+                    latching: info
+                        .metadata
+                        .get("latching")
+                        .map_or(false, |latching| latching == "1"),
+                    header_fields,
+                };
+                Conversion::Copy(self.writer.add_connection(&connection))
+            }
+            #[cfg(not(feature = "ros2"))]
+            "cdr" => Conversion::Skip(
+                "converting ROS 2 messages needs frost built with the ros2 feature".to_string(),
+            ),
+            #[cfg(feature = "ros2")]
+            "cdr" => {
+                let definition = match info.definition {
+                    Some(("ros2msg", definition)) => Some(definition.to_string()),
+                    _ => self.sources.definition(info.data_type),
+                };
+                let Some(definition) = definition else {
+                    return Conversion::Skip(format!(
+                        "no ros2msg definition for {}, add its .msg files",
+                        info.data_type
+                    ));
+                };
+                let ros2_type = self
+                    .types
+                    .entry((info.data_type.to_string(), definition))
+                    .or_insert_with_key(|(data_type, definition)| {
+                        Ros2Type::from_definition(data_type, definition)
+                            .map(Arc::new)
+                            .map_err(|err| format!("cannot translate {data_type}: {err}"))
+                    })
+                    .clone();
+                let ros2_type = match ros2_type {
+                    Ok(ros2_type) => ros2_type,
+                    Err(reason) => return Conversion::Skip(reason),
+                };
+                let latching = info
+                    .metadata
+                    .get("offered_qos_profiles")
+                    .map_or(false, |profiles| transient_local(profiles));
+                let connection = ConnectionData {
+                    connection_id: 0,
+                    topic: info.topic.to_string(),
+                    data_type: ros2_type.data_type().to_string(),
+                    md5sum: ros2_type.md5sum().to_string(),
+                    message_definition: ros2_type.message_definition().to_string(),
+                    caller_id: None,
+                    latching,
+                    header_fields: Vec::new(),
+                };
+                Conversion::Cdr(self.writer.add_connection(&connection), ros2_type)
+            }
+            other => Conversion::Skip(format!("unsupported message encoding '{other}'")),
+        }
+    }
+
+    fn write(
+        &mut self,
+        topic: &str,
+        conversion: &Conversion,
+        time: Time,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let reason = match conversion {
+            Conversion::Copy(conn_id) => {
+                self.writer.write_raw(*conn_id, time, data)?;
+                self.report.message_count += 1;
+                return Ok(());
+            }
+            #[cfg(feature = "ros2")]
+            Conversion::Cdr(conn_id, ros2_type) => match ros2_type.to_ros1(data) {
+                Ok(data) => {
+                    self.writer.write_raw(*conn_id, time, &data)?;
+                    self.report.message_count += 1;
+                    return Ok(());
+                }
+                Err(err) => format!("cannot convert a {} message: {err}", ros2_type.data_type()),
+            },
+            Conversion::Skip(reason) => reason.clone(),
+        };
+        self.report
+            .skipped
+            .entry(topic.to_string())
+            .or_insert(Skipped {
+                message_count: 0,
+                reason,
+            })
+            .message_count += 1;
+        Ok(())
+    }
+
+    /// What was imported so far.
+    pub fn report(&self) -> &ImportReport {
+        &self.report
+    }
+
+    /// Finishes the bag, returning the underlying writer and what was imported.
+    pub fn finish(self) -> Result<(W, ImportReport), Error> {
+        Ok((self.writer.finish()?, self.report))
+    }
+}
+
+#[cfg(feature = "rosbag2")]
+impl<W: Write + Seek> Importer<W> {
+    /// Imports every message of a rosbag2 sqlite3 (`.db3`) file.
+    pub fn import_db3<P: AsRef<std::path::Path>>(&mut self, file_path: P) -> Result<(), Error> {
+        use rusqlite::{Connection, OpenFlags};
+
+        let sqlite = |err: rusqlite::Error| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid rosbag2 database: {err}"),
+            )
+        };
+        let db = Connection::open_with_flags(file_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(sqlite)?;
+
+        // stored since ROS 2 Iron
+        let mut definitions: HashMap<String, (String, String)> = HashMap::new();
+        if let Ok(mut statement) = db.prepare(
+            "SELECT topic_type, encoding, encoded_message_definition FROM message_definitions",
+        ) {
+            let rows = statement
+                .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+                .map_err(sqlite)?;
+            for row in rows {
+                let (data_type, definition) = row.map_err(sqlite)?;
+                definitions.insert(data_type, definition);
+            }
+        }
+        // only stored since ROS 2 Foxy
+        let mut profiles: HashMap<i64, String> = HashMap::new();
+        if let Ok(mut statement) = db.prepare("SELECT id, offered_qos_profiles FROM topics") {
+            let rows = statement
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(sqlite)?;
+            for row in rows {
+                let (id, profile) = row.map_err(sqlite)?;
+                profiles.insert(id, profile);
+            }
+        }
+
+        let mut topics: HashMap<i64, (String, Conversion)> = HashMap::new();
+        let mut statement = db
+            .prepare("SELECT id, name, type, serialization_format FROM topics")
+            .map_err(sqlite)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .map_err(sqlite)?;
+        for row in rows {
+            let (id, topic, data_type, message_encoding) = row.map_err(sqlite)?;
+            let mut metadata = BTreeMap::new();
+            if let Some(profile) = profiles.remove(&id) {
+                metadata.insert("offered_qos_profiles".to_string(), profile);
+            }
+            let definition = definitions
+                .get(&data_type)
+                .filter(|(_, definition)| !definition.is_empty())
+                .map(|(encoding, definition)| (encoding.as_str(), definition.as_str()));
+            let conversion = self.conversion(&TypeInfo {
+                topic: &topic,
+                data_type: &data_type,
+                message_encoding: &message_encoding,
+                definition,
+                metadata: &metadata,
+            });
+            topics.insert(id, (topic, conversion));
+        }
+
+        let mut statement = db
+            .prepare("SELECT topic_id, timestamp, data FROM messages ORDER BY timestamp")
+            .map_err(sqlite)?;
+        let mut rows = statement.query([]).map_err(sqlite)?;
+        while let Some(row) = rows.next().map_err(sqlite)? {
+            let topic_id: i64 = row.get(0).map_err(sqlite)?;
+            let timestamp: i64 = row.get(1).map_err(sqlite)?;
+            let data = row
+                .get_ref(2)
+                .map_err(sqlite)?
+                .as_blob()
+                .map_err(|err| sqlite(err.into()))?;
+            let Some((topic, conversion)) = topics.get(&topic_id) else {
+                continue;
+            };
+            let (topic, conversion) = (topic.clone(), conversion.clone());
+            self.write(&topic, &conversion, nanos_to_time(timestamp as u64), data)?;
+        }
+        Ok(())
+    }
+}
+
+fn nanos_to_time(nanos: u64) -> Time {
+    Time {
+        secs: (nanos / 1_000_000_000) as u32,
+        nsecs: (nanos % 1_000_000_000) as u32,
+    }
+}
+
+/// Whether rosbag2's YAML QoS profiles of a topic are transient local, the ROS 2 equivalent of latching.
+#[cfg(feature = "ros2")]
+fn transient_local(profiles: &str) -> bool {
+    let Ok(serde_yaml::Value::Sequence(profiles)) = serde_yaml::from_str(profiles) else {
+        return false;
+    };
+    profiles.iter().any(|profile| match &profile["durability"] {
+        serde_yaml::Value::Number(durability) => durability.as_u64() == Some(1),
+        serde_yaml::Value::String(durability) => durability == "transient_local",
+        _ => false,
+    })
+}
@@ -0,0 +1,250 @@
+//! How a bag's topics are spread over its chunks, for diagnosing recorder chunking pathologies and rendering
+//! storage and time heatmaps.
+//!
+//! [BagMetadata::chunk_layout] maps each chunk, in file order, to the messages and bytes each topic has in it and the
+//! time it spans, using only the index, so no chunk is read or decompressed. Bytes are those of the message records
+//! in the uncompressed chunk, measured between the offsets the index gives. [ChunkLayout] serializes for external UIs,
+//! and [ChunkLayout::to_svg] renders a simple heatmap, as `frost layout --svg` does.
+//!
+//! Example
+//! ```rust
+//! use frost::BagMetadata;
+//!
+//! let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let layout = metadata.chunk_layout();
+//! for chunk in &layout.chunks {
+//!     let share = chunk.byte_share("/chatter");
+//!     println!("chunk at {}: {:.0}% /chatter", chunk.chunk_pos, share * 100.0);
+//! }
+//! assert!(layout.to_svg().starts_with("<svg"));
+//! ```
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::{Serialize, Serializer};
+
+use crate::time::Time;
+use crate::BagMetadata;
+
+/// A topic's messages in a chunk, see [ChunkUsage::topics].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TopicShare {
+    pub messages: u64,
+    /// The size of the topic's message records in the uncompressed chunk.
+    pub bytes: u64,
+}
+
+/// What a chunk holds, from [BagMetadata::chunk_layout]. Serializes with times in seconds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChunkUsage {
+    /// The position of the chunk's record in the bag.
+    pub chunk_pos: u64,
+    #[serde(serialize_with = "serialize_time")]
+    pub start_time: Time,
+    #[serde(serialize_with = "serialize_time")]
+    pub end_time: Time,
+    pub compression: String,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    /// The topics with messages in the chunk.
+    pub topics: BTreeMap<String, TopicShare>,
+}
+
+impl ChunkUsage {
+    /// The fraction of the chunk's message bytes which are on `topic`, from 0 to 1.
+    pub fn byte_share(&self, topic: &str) -> f64 {
+        let total: u64 = self.topics.values().map(|share| share.bytes).sum();
+        share(self.topics.get(topic).map_or(0, |share| share.bytes), total)
+    }
+
+    /// The fraction of the chunk's messages which are on `topic`, from 0 to 1.
+    pub fn message_share(&self, topic: &str) -> f64 {
+        let total: u64 = self.topics.values().map(|share| share.messages).sum();
+        share(
+            self.topics.get(topic).map_or(0, |share| share.messages),
+            total,
+        )
+    }
+}
+
+/// Every chunk of a bag and the topics in it, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ChunkLayout {
+    /// The chunks in the order they are stored in the bag.
+    pub chunks: Vec<ChunkUsage>,
+    /// Every topic with messages, sorted.
+    pub topics: Vec<String>,
+}
+
+impl BagMetadata {
+    /// Maps each chunk to the topics stored in it, see the [module docs](crate::layout).
+    pub fn chunk_layout(&self) -> ChunkLayout {
+        // the offsets of every message in each chunk, to measure them by
+        let mut offsets: BTreeMap<u64, Vec<(u32, &str)>> = BTreeMap::new();
+        for (conn_id, index) in self.index_data.iter() {
+            let Some(connection) = self.connection_data.get(conn_id) else {
+                continue;
+            };
+            for data in index {
+                offsets
+                    .entry(data.chunk_header_pos)
+                    .or_default()
+                    .push((data.offset, connection.topic.as_str()));
+            }
+        }
+
+        let chunks: Vec<ChunkUsage> = self
+            .chunk_metadata
+            .values()
+            .map(|chunk| {
+                let mut topics: BTreeMap<String, TopicShare> = BTreeMap::new();
+                let mut messages = offsets.remove(&chunk.chunk_header_pos).unwrap_or_default();
+                messages.sort_unstable();
+                let ends = messages
+                    .iter()
+                    .skip(1)
+                    .map(|(offset, _)| *offset)
+                    .chain([chunk.uncompressed_size]);
+                for ((offset, topic), end) in messages.iter().zip(ends) {
+                    let share = topics.entry(topic.to_string()).or_default();
+                    share.messages += 1;
+                    share.bytes += u64::from(end.saturating_sub(*offset));
+                }
+                ChunkUsage {
+                    chunk_pos: chunk.chunk_header_pos,
+                    start_time: chunk.start_time,
+                    end_time: chunk.end_time,
+                    compression: chunk.compression.clone(),
+                    compressed_size: chunk.compressed_size,
+                    uncompressed_size: chunk.uncompressed_size,
+                    topics,
+                }
+            })
+            .collect();
+        let mut topics: Vec<String> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.topics.keys().cloned())
+            .collect();
+        topics.sort();
+        topics.dedup();
+        ChunkLayout { chunks, topics }
+    }
+}
+
+const SVG_WIDTH: f64 = 960.0;
+const LABEL_WIDTH: f64 = 200.0;
+const ROW_HEIGHT: f64 = 18.0;
+const TIME_HEIGHT: f64 = 160.0;
+
+impl ChunkLayout {
+    /// Renders the layout as an SVG image of two panels sharing the bag's storage as their x axis, with chunks as
+    /// wide as they are compressed:
+    /// - a heatmap with a row per topic, shaded by the topic's share of each chunk's bytes
+    /// - the time each chunk spans, from the bag's start at the top to its end at the bottom, where chunks
+    ///   overlapping in time or recorded out of order stand out
+    ///
+    /// Hovering over a cell shows the chunk's details.
+    pub fn to_svg(&self) -> String {
+        let plot_width = SVG_WIDTH - LABEL_WIDTH;
+        let heatmap_height = ROW_HEIGHT * self.topics.len() as f64;
+        let time_top = heatmap_height + ROW_HEIGHT;
+        let height = time_top + TIME_HEIGHT + ROW_HEIGHT;
+
+        let total: u64 = self
+            .chunks
+            .iter()
+            .map(|chunk| u64::from(chunk.compressed_size))
+            .sum();
+        let start = self.chunks.iter().map(|chunk| chunk.start_time).min();
+        let end = self.chunks.iter().map(|chunk| chunk.end_time).max();
+        let (start, span) = match (start, end) {
+            (Some(start), Some(end)) => (f64::from(start), f64::from(end) - f64::from(start)),
+            _ => (0.0, 0.0),
+        };
+        let time_y = |time: Time| match span > 0.0 {
+            true => time_top + (f64::from(time) - start) / span * TIME_HEIGHT,
+            false => time_top,
+        };
+
+        let mut svg = String::new();
+        // writing to a String can't fail
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{SVG_WIDTH}" height="{height}" font-family="monospace" font-size="12">"#
+        );
+        for (row, topic) in self.topics.iter().enumerate() {
+            let _ = writeln!(
+                svg,
+                r#"<text x="4" y="{:.1}">{}</text>"#,
+                row as f64 * ROW_HEIGHT + ROW_HEIGHT - 5.0,
+                escape(topic)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r#"<text x="4" y="{:.1}">time</text>"#,
+            time_top + ROW_HEIGHT - 5.0
+        );
+
+        let mut x = LABEL_WIDTH;
+        for chunk in self.chunks.iter() {
+            let width = share(u64::from(chunk.compressed_size), total) * plot_width;
+            let details = format!(
+                "chunk at {}: {:.3} to {:.3}, {} bytes {} ({} uncompressed)",
+                chunk.chunk_pos,
+                f64::from(chunk.start_time),
+                f64::from(chunk.end_time),
+                chunk.compressed_size,
+                chunk.compression,
+                chunk.uncompressed_size,
+            );
+            for (row, topic) in self.topics.iter().enumerate() {
+                let Some(topic_share) = chunk.topics.get(topic) else {
+                    continue;
+                };
+                let _ = writeln!(
+                    svg,
+                    r##"<rect x="{x:.2}" y="{:.1}" width="{width:.2}" height="{ROW_HEIGHT}" fill="#d62728" fill-opacity="{:.3}"><title>{}: {} messages, {} bytes ({:.0}%) of the {}</title></rect>"##,
+                    row as f64 * ROW_HEIGHT,
+                    chunk.byte_share(topic).max(0.02),
+                    escape(topic),
+                    topic_share.messages,
+                    topic_share.bytes,
+                    chunk.byte_share(topic) * 100.0,
+                    details
+                );
+            }
+            let top = time_y(chunk.start_time);
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{x:.2}" y="{top:.2}" width="{width:.2}" height="{:.2}" fill="#1f77b4"><title>{details}</title></rect>"##,
+                (time_y(chunk.end_time) - top).max(1.0)
+            );
+            x += width;
+        }
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{LABEL_WIDTH}" y="0" width="{plot_width}" height="{:.1}" fill="none" stroke="#999"/>"##,
+            time_top + TIME_HEIGHT
+        );
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn share(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => part as f64 / total as f64,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn serialize_time<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(f64::from(time))
+}
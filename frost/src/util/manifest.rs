@@ -0,0 +1,266 @@
+//! Tamper-evident manifests for bags, enabled with the `manifest` feature.
+//!
+//! A [Manifest] records the SHA-256 of every chunk's data and of everything else in the bag
+//! (the bag header, connections, and index), so any modified byte can be attributed to a chunk or the metadata.
+//! Bags are hashed as they are read, a block at a time, so they never have to fit in memory.
+//!
+//! Manifests are stored alongside the bag as JSON and can be authenticated with an HMAC-SHA256 key,
+//! so a manifest rewritten to match a tampered bag is detected too. An HMAC is a message authentication code,
+//! not a public key signature: the same secret key creates and checks it, so anyone able to verify a manifest
+//! can also forge one. Only share the key with parties trusted not to modify bags.
+use std::path::Path;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::Error;
+use crate::storage::{BagStorage, FileStorage};
+use crate::util::parsing::{from_hex, to_hex};
+use crate::BagMetadata;
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// How many bytes are read from storage at a time while hashing.
+const HASH_BLOCK: usize = 1024 * 1024;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The digest of a single chunk's data.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkDigest {
+    /// Offset of the chunk record in the bag.
+    pub chunk_pos: u64,
+    /// Hex encoded SHA-256 of the chunk's (compressed) data.
+    pub sha256: String,
+}
+
+/// Per-chunk and metadata hashes of a bag, see the [module docs](self).
+///
+/// Example
+/// ```rust
+/// use frost::manifest::Manifest;
+///
+/// let key = b"a secret shared with whoever verifies the bag";
+/// let manifest = Manifest::from_file("./tests/fixtures/compressed_lz4.bag")
+///     .unwrap()
+///     .sign(key);
+///
+/// let verification = manifest
+///     .verify_file("./tests/fixtures/compressed_lz4.bag", Some(key))
+///     .unwrap();
+/// assert!(verification.is_valid());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// Size of the bag in bytes.
+    pub bag_size: u64,
+    /// Hex encoded SHA-256 of every byte of the bag outside of chunk data.
+    pub metadata_sha256: String,
+    pub chunks: Vec<ChunkDigest>,
+    /// Hex encoded HMAC-SHA256 of the manifest's JSON without this field, see the [module docs](self) for why this
+    /// is a MAC rather than a public key signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Whether a manifest's signature could be checked, see [Verification].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// The HMAC matches the key.
+    Valid,
+    /// The HMAC does not match the key, so the manifest was modified or signed with another key.
+    Invalid,
+    /// A key was given but the manifest is not signed.
+    Missing,
+    /// The manifest is signed but no key was given.
+    NotChecked,
+    /// Neither a key nor a signature were present.
+    Unsigned,
+}
+
+/// The result of checking a bag against a [Manifest].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    pub size_matches: bool,
+    pub metadata_matches: bool,
+    /// Positions of chunks whose data no longer matches the manifest.
+    pub modified_chunks: Vec<u64>,
+    /// Positions of chunks in the manifest which are not in the bag.
+    pub missing_chunks: Vec<u64>,
+    /// Positions of chunks in the bag which are not in the manifest.
+    pub unexpected_chunks: Vec<u64>,
+    pub signature: SignatureStatus,
+}
+
+impl Verification {
+    /// Returns true if the bag is unmodified and the signature, if checked, is valid.
+    pub fn is_valid(&self) -> bool {
+        self.size_matches
+            && self.metadata_matches
+            && self.modified_chunks.is_empty()
+            && self.missing_chunks.is_empty()
+            && self.unexpected_chunks.is_empty()
+            && !matches!(
+                self.signature,
+                SignatureStatus::Invalid | SignatureStatus::Missing
+            )
+    }
+}
+
+impl Manifest {
+    /// Hashes a bag's chunks and metadata.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_storage(&bytes)
+    }
+
+    /// Hashes the chunks and metadata of the bag at a file path, reading it a block at a time.
+    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
+        Self::from_storage(&FileStorage::open(file_path)?)
+    }
+
+    /// Hashes the chunks and metadata of a bag in any [BagStorage], reading it a block at a time.
+    pub fn from_storage(storage: &dyn BagStorage) -> Result<Self, Error> {
+        let metadata = BagMetadata::from_storage(storage)?;
+        let bag_size = storage.size()?;
+
+        let mut chunks = Vec::with_capacity(metadata.chunk_metadata.len());
+        let mut metadata_hasher = Sha256::new();
+        let mut pos = 0;
+        for chunk in metadata.chunk_metadata.values() {
+            let start = chunk.chunk_data_pos;
+            let end = start + u64::from(chunk.compressed_size);
+            hash_range(&mut metadata_hasher, storage, pos, start)?;
+            let mut chunk_hasher = Sha256::new();
+            hash_range(&mut chunk_hasher, storage, start, end)?;
+            chunks.push(ChunkDigest {
+                chunk_pos: chunk.chunk_header_pos,
+                sha256: to_hex(&chunk_hasher.finalize()),
+            });
+            pos = end;
+        }
+        hash_range(&mut metadata_hasher, storage, pos, bag_size)?;
+
+        Ok(Manifest {
+            version: MANIFEST_VERSION,
+            bag_size,
+            metadata_sha256: to_hex(&metadata_hasher.finalize()),
+            chunks,
+            signature: None,
+        })
+    }
+
+    /// Reads a manifest previously written with [Manifest::to_json].
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json).map_err(std::io::Error::from)?)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a manifest is always serializable")
+    }
+
+    /// Authenticates the manifest with an HMAC-SHA256 key, replacing any previous one, see the
+    /// [module docs](self).
+    pub fn sign(mut self, key: &[u8]) -> Self {
+        self.signature = Some(to_hex(&self.mac(key).finalize().into_bytes()));
+        self
+    }
+
+    /// Checks a bag against the manifest, and the manifest's HMAC if a key is given.
+    pub fn verify(&self, bytes: &[u8], key: Option<&[u8]>) -> Result<Verification, Error> {
+        self.verify_storage(&bytes, key)
+    }
+
+    /// Checks the bag at a file path against the manifest, see [Manifest::verify].
+    pub fn verify_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        key: Option<&[u8]>,
+    ) -> Result<Verification, Error> {
+        self.verify_storage(&FileStorage::open(file_path)?, key)
+    }
+
+    /// Checks a bag in any [BagStorage] against the manifest, reading it a block at a time, see [Manifest::verify].
+    pub fn verify_storage(
+        &self,
+        storage: &dyn BagStorage,
+        key: Option<&[u8]>,
+    ) -> Result<Verification, Error> {
+        let actual = Manifest::from_storage(storage)?;
+
+        let mut modified_chunks = Vec::new();
+        let mut missing_chunks = Vec::new();
+        for expected in self.chunks.iter() {
+            match actual
+                .chunks
+                .iter()
+                .find(|chunk| chunk.chunk_pos == expected.chunk_pos)
+            {
+                Some(chunk) if chunk.sha256 != expected.sha256 => {
+                    modified_chunks.push(expected.chunk_pos)
+                }
+                Some(_) => {}
+                None => missing_chunks.push(expected.chunk_pos),
+            }
+        }
+        let unexpected_chunks = actual
+            .chunks
+            .iter()
+            .filter(|chunk| {
+                !self
+                    .chunks
+                    .iter()
+                    .any(|expected| expected.chunk_pos == chunk.chunk_pos)
+            })
+            .map(|chunk| chunk.chunk_pos)
+            .collect();
+
+        let signature = match (key, &self.signature) {
+            (Some(key), Some(signature)) => match from_hex(signature) {
+                Some(signature) if self.mac(key).verify_slice(&signature).is_ok() => {
+                    SignatureStatus::Valid
+                }
+                _ => SignatureStatus::Invalid,
+            },
+            (Some(_), None) => SignatureStatus::Missing,
+            (None, Some(_)) => SignatureStatus::NotChecked,
+            (None, None) => SignatureStatus::Unsigned,
+        };
+
+        Ok(Verification {
+            size_matches: actual.bag_size == self.bag_size,
+            metadata_matches: actual.metadata_sha256 == self.metadata_sha256,
+            modified_chunks,
+            missing_chunks,
+            unexpected_chunks,
+            signature,
+        })
+    }
+
+    fn mac(&self, key: &[u8]) -> HmacSha256 {
+        let unsigned = Manifest {
+            signature: None,
+            ..self.clone()
+        };
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(&serde_json::to_vec(&unsigned).expect("a manifest is always serializable"));
+        mac
+    }
+}
+
+/// Adds the bytes of `storage` from `start` up to `end` to `hasher`, a block at a time.
+fn hash_range(
+    hasher: &mut Sha256,
+    storage: &dyn BagStorage,
+    start: u64,
+    end: u64,
+) -> Result<(), Error> {
+    let mut pos = start;
+    while pos < end {
+        let len = (end - pos).min(HASH_BLOCK as u64) as usize;
+        hasher.update(storage.read_at(pos, len)?);
+        pos += len as u64;
+    }
+    Ok(())
+}
@@ -0,0 +1,220 @@
+//! Exporting `nav_msgs/OccupancyGrid` messages as map_server maps: an image plus YAML metadata.
+//!
+//! Cells are written like `map_saver` does, with the image's top row at the map's largest y:
+//! free cells (occupancy up to 25) are white (254), occupied cells (65 and up) are black (0),
+//! and unknown or uncertain cells are grey (205). PGM is always available, PNG needs the `images` feature.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::maps::{MapImageFormat, OccupancyGrid};
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("mapping.bag").unwrap();
+//! let query = Query::new().with_topics(["/map"]);
+//! let latest = bag.read_messages(&query).unwrap().last().unwrap();
+//! let grid = OccupancyGrid::from_value(&latest.decode().unwrap()).unwrap();
+//! // writes map.pgm and map.yaml
+//! grid.save("map", MapImageFormat::Pgm).unwrap();
+//! ```
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::dynamic::{required, required_f64, Value};
+use crate::errors::{DecodeError, Error};
+
+const OCCUPANCY_GRID_TYPE: &str = "nav_msgs/OccupancyGrid";
+
+/// `map_saver`'s default thresholds, in percent occupancy
+const FREE_THRESHOLD: i8 = 25;
+const OCCUPIED_THRESHOLD: i8 = 65;
+
+const FREE: u8 = 254;
+const OCCUPIED: u8 = 0;
+const UNKNOWN: u8 = 205;
+
+/// The image format of an exported map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapImageFormat {
+    Pgm,
+    /// Requires the `images` feature.
+    #[cfg(feature = "images")]
+    Png,
+}
+
+impl MapImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MapImageFormat::Pgm => "pgm",
+            #[cfg(feature = "images")]
+            MapImageFormat::Png => "png",
+        }
+    }
+}
+
+impl fmt::Display for MapImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl FromStr for MapImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pgm" => Ok(MapImageFormat::Pgm),
+            #[cfg(feature = "images")]
+            "png" => Ok(MapImageFormat::Png),
+            #[cfg(not(feature = "images"))]
+            "png" => Err("png maps require the images feature".to_string()),
+            other => Err(format!(
+                "unknown map format '{other}', expected 'pgm' or 'png'"
+            )),
+        }
+    }
+}
+
+/// A decoded `nav_msgs/OccupancyGrid`, see the [module docs](self).
+#[derive(Clone, Debug, PartialEq)]
+pub struct OccupancyGrid {
+    /// The size of a cell in meters.
+    pub resolution: f32,
+    pub width: u32,
+    pub height: u32,
+    /// The x, y, and yaw of the map's bottom left cell in the map frame.
+    pub origin: [f64; 3],
+    /// Occupancy in percent from 0 to 100, or -1 if unknown, in row major order starting at the origin.
+    pub data: Vec<i8>,
+}
+
+impl OccupancyGrid {
+    /// Reads a `nav_msgs/OccupancyGrid` decoded with [crate::dynamic].
+    pub fn from_value(value: &Value) -> Result<Self, DecodeError> {
+        let info = required(value, OCCUPANCY_GRID_TYPE, "info")?;
+        let pose = required(info, "nav_msgs/MapMetaData", "origin")?;
+        let position = required(pose, "geometry_msgs/Pose", "position")?;
+        let orientation = required(pose, "geometry_msgs/Pose", "orientation")?;
+        let (qx, qy, qz, qw) = (
+            required_f64(orientation, "geometry_msgs/Quaternion", "x")?,
+            required_f64(orientation, "geometry_msgs/Quaternion", "y")?,
+            required_f64(orientation, "geometry_msgs/Quaternion", "z")?,
+            required_f64(orientation, "geometry_msgs/Quaternion", "w")?,
+        );
+        let yaw = (2.0 * (qw * qz + qx * qy)).atan2(1.0 - 2.0 * (qy * qy + qz * qz));
+
+        let data = match required(value, OCCUPANCY_GRID_TYPE, "data")? {
+            Value::Array(values) => values
+                .iter()
+                .map(|value| match value {
+                    Value::I8(v) => Some(*v),
+                    _ => None,
+                })
+                .collect::<Option<Vec<i8>>>(),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            DecodeError::InvalidField(format!("{OCCUPANCY_GRID_TYPE}.data is not an int8[]"))
+        })?;
+
+        let dimension = |field| match required(info, "nav_msgs/MapMetaData", field)? {
+            Value::U32(v) => Ok(*v),
+            _ => Err(DecodeError::InvalidField(format!(
+                "nav_msgs/MapMetaData.{field} is not a uint32"
+            ))),
+        };
+        let grid = OccupancyGrid {
+            resolution: required_f64(info, "nav_msgs/MapMetaData", "resolution")? as f32,
+            width: dimension("width")?,
+            height: dimension("height")?,
+            origin: [
+                required_f64(position, "geometry_msgs/Point", "x")?,
+                required_f64(position, "geometry_msgs/Point", "y")?,
+                yaw,
+            ],
+            data,
+        };
+        if grid.data.len() != grid.width as usize * grid.height as usize {
+            return Err(DecodeError::InvalidField(format!(
+                "{OCCUPANCY_GRID_TYPE}.data has {} cells, but the map is {}x{}",
+                grid.data.len(),
+                grid.width,
+                grid.height
+            )));
+        }
+        Ok(grid)
+    }
+
+    /// Returns the map as 8 bit grey pixels, top row first.
+    pub fn pixels(&self) -> Vec<u8> {
+        let width = self.width as usize;
+        if width == 0 {
+            return Vec::new();
+        }
+        self.data
+            .chunks_exact(width)
+            .rev()
+            .flatten()
+            .map(|&occupancy| match occupancy {
+                0..=FREE_THRESHOLD => FREE,
+                OCCUPIED_THRESHOLD..=i8::MAX => OCCUPIED,
+                _ => UNKNOWN,
+            })
+            .collect()
+    }
+
+    /// Encodes the map as a binary PGM image.
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut pgm = format!(
+            "P5\n# CREATOR: frost {:.3} m/pix\n{} {}\n255\n",
+            self.resolution, self.width, self.height
+        )
+        .into_bytes();
+        pgm.extend(self.pixels());
+        pgm
+    }
+
+    /// Encodes the map as a greyscale PNG image.
+    #[cfg(feature = "images")]
+    pub fn to_png(&self) -> Result<Vec<u8>, Error> {
+        let image = image::GrayImage::from_raw(self.width, self.height, self.pixels())
+            .expect("one pixel per cell");
+        let mut png = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        Ok(png.into_inner())
+    }
+
+    /// Returns map_server's YAML metadata for the map, saved as `image_file`.
+    pub fn to_yaml(&self, image_file: &str) -> String {
+        let [x, y, yaw] = self.origin;
+        format!(
+            "image: {image_file}\nresolution: {:.6}\norigin: [{x:.6}, {y:.6}, {yaw:.6}]\nnegate: 0\noccupied_thresh: 0.65\nfree_thresh: 0.196\n",
+            self.resolution
+        )
+    }
+
+    /// Writes the map to `<path>.<format>` and its metadata to `<path>.yaml`, returning their paths.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: MapImageFormat,
+    ) -> Result<(PathBuf, PathBuf), Error> {
+        let image_path = path.as_ref().with_extension(format.extension());
+        let yaml_path = path.as_ref().with_extension("yaml");
+        let image = match format {
+            MapImageFormat::Pgm => self.to_pgm(),
+            #[cfg(feature = "images")]
+            MapImageFormat::Png => self.to_png()?,
+        };
+        std::fs::write(&image_path, image)?;
+        let image_file = image_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        std::fs::write(&yaml_path, self.to_yaml(&image_file))?;
+        Ok((image_path, yaml_path))
+    }
+}
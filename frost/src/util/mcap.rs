@@ -0,0 +1,764 @@
+//! Writing bags as [MCAP](https://mcap.dev) files, which ROS 2 tooling like Foxglove and rosbag2 reads, and reading
+//! MCAP files' messages back with [McapReader], which [crate::import] converts into bags.
+//!
+//! Messages keep their ROS 1 serialization: channels have the `ros1` message encoding and schemas are the
+//! connections' `ros1msg` definitions, the MCAP profile for ROS 1 data. Messages are written in chunks, optionally
+//! compressed, with message and chunk indexes and statistics, so readers can seek by time without scanning the file.
+//! ROS 1 doesn't record publish times, so both the log and publish times of messages are their receive times.
+//! CRCs are left as zero, which the format defines as not computed.
+//! MCAP names are UTF-8, so topics and types recorded as other bytes, see
+//! [NonUtf8Name](crate::BagWarning::NonUtf8Name), are written with U+FFFD in place of those bytes, and as recorded, in
+//! hex, in the `topic_hex` and `type_hex` channel metadata, which [crate::import] restores.
+//!
+//! Example
+//! ```rust
+//! use frost::mcap::{McapCompression, McapWriter};
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let mut writer = McapWriter::new(Vec::new())
+//!     .unwrap()
+//!     .with_compression(McapCompression::Lz4);
+//! assert_eq!(writer.copy_messages(&bag, &Query::all()).unwrap(), 300);
+//! let mcap = writer.finish().unwrap();
+//! assert!(mcap.starts_with(b"\x89MCAP0\r\n"));
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::compression::{self, ChunkContext};
+use crate::errors::Error;
+use crate::query::Query;
+use crate::time::Time;
+use crate::util::parsing::to_hex;
+use crate::{ConnectionData, ConnectionID, DecompressedBag};
+
+pub(crate) const MAGIC: &[u8] = b"\x89MCAP0\r\n";
+const DEFAULT_CHUNK_THRESHOLD: usize = 768 * 1024;
+
+pub(crate) const OP_HEADER: u8 = 0x01;
+pub(crate) const OP_FOOTER: u8 = 0x02;
+pub(crate) const OP_SCHEMA: u8 = 0x03;
+pub(crate) const OP_CHANNEL: u8 = 0x04;
+pub(crate) const OP_MESSAGE: u8 = 0x05;
+pub(crate) const OP_CHUNK: u8 = 0x06;
+pub(crate) const OP_MESSAGE_INDEX: u8 = 0x07;
+pub(crate) const OP_CHUNK_INDEX: u8 = 0x08;
+pub(crate) const OP_STATISTICS: u8 = 0x0B;
+pub(crate) const OP_SUMMARY_OFFSET: u8 = 0x0E;
+pub(crate) const OP_DATA_END: u8 = 0x0F;
+
+/// Compression of the chunks written by a [McapWriter]. MCAP readers support lz4 and zstd.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum McapCompression {
+    None,
+    Lz4,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl McapCompression {
+    /// The name stored in chunk records, empty for uncompressed chunks.
+    fn as_str(&self) -> &'static str {
+        match self {
+            McapCompression::None => "",
+            McapCompression::Lz4 => "lz4",
+            #[cfg(feature = "zstd")]
+            McapCompression::Zstd => "zstd",
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            McapCompression::None => Ok(data.to_vec()),
+            // both are stored in their frame formats, like rosbag chunks
+            _ => compression::lookup(self.as_str())
+                .expect("lz4 and zstd are built in")
+                .compress(data),
+        }
+    }
+}
+
+impl fmt::Display for McapCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            McapCompression::None => f.write_str("none"),
+            other => f.write_str(other.as_str()),
+        }
+    }
+}
+
+impl FromStr for McapCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(McapCompression::None),
+            "lz4" => Ok(McapCompression::Lz4),
+            #[cfg(feature = "zstd")]
+            "zstd" => Ok(McapCompression::Zstd),
+            #[cfg(feature = "zstd")]
+            other => Err(format!(
+                "unsupported MCAP compression '{other}', expected none, lz4, or zstd"
+            )),
+            #[cfg(not(feature = "zstd"))]
+            other => Err(format!(
+                "unsupported MCAP compression '{other}', expected none or lz4"
+            )),
+        }
+    }
+}
+
+/// Channels are split like ROS 1 connections, by publisher and latching as well as topic and type.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ChannelKey {
+    /// as recorded, so topics which only differ in bytes which aren't UTF-8 get their own channels
+    topic: Vec<u8>,
+    schema_id: u16,
+    caller_id: Option<String>,
+    latching: bool,
+}
+
+/// Writes messages into an MCAP file, see the [module docs](self).
+pub struct McapWriter<W: Write> {
+    writer: W,
+    /// bytes written to `writer` so far, which records in the summary refer to
+    position: u64,
+    compression: McapCompression,
+    chunk_threshold: usize,
+    /// keyed by type, md5sum, and definition
+    schema_ids: HashMap<(String, String, String), u16>,
+    channel_ids: HashMap<ChannelKey, u16>,
+    /// the contents of the schema and channel records, repeated in the summary
+    schema_records: Vec<Vec<u8>>,
+    channel_records: Vec<Vec<u8>>,
+    chunk_index_records: Vec<Vec<u8>>,
+    /// messages written per channel, also their next sequence number
+    message_counts: BTreeMap<u16, u64>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    /// the uncompressed records of the chunk being built
+    chunk_buf: Vec<u8>,
+    /// `(log time, offset in chunk_buf)` of the chunk's messages, per channel
+    chunk_index: BTreeMap<u16, Vec<(u64, u64)>>,
+    chunk_start_time: Option<u64>,
+    chunk_end_time: Option<u64>,
+}
+
+impl McapWriter<BufWriter<File>> {
+    /// Creates (or truncates) an MCAP file at a file path.
+    pub fn create<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
+        let file = File::create(file_path)?;
+        McapWriter::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write> McapWriter<W> {
+    /// Starts writing an MCAP file into `writer`.
+    pub fn new(writer: W) -> Result<Self, Error> {
+        let mut mcap = McapWriter {
+            writer,
+            position: 0,
+            compression: McapCompression::None,
+            chunk_threshold: DEFAULT_CHUNK_THRESHOLD,
+            schema_ids: HashMap::new(),
+            channel_ids: HashMap::new(),
+            schema_records: Vec::new(),
+            channel_records: Vec::new(),
+            chunk_index_records: Vec::new(),
+            message_counts: BTreeMap::new(),
+            start_time: None,
+            end_time: None,
+            chunk_buf: Vec::new(),
+            chunk_index: BTreeMap::new(),
+            chunk_start_time: None,
+            chunk_end_time: None,
+        };
+        mcap.write_bytes(MAGIC)?;
+        let mut header = Vec::new();
+        put_str(&mut header, "ros1");
+        put_str(&mut header, concat!("frost ", env!("CARGO_PKG_VERSION")));
+        mcap.write_record(OP_HEADER, &header)?;
+        Ok(mcap)
+    }
+
+    /// Sets the compression of chunks written from now on. Defaults to [McapCompression::None].
+    pub fn with_compression(mut self, compression: McapCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the uncompressed size at which chunks are written, which is also the most a reader has to decompress to
+    /// read a single message.
+    pub fn with_chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_threshold = bytes;
+        self
+    }
+
+    /// Adds a channel for a ROS 1 connection, along with its schema, returning the channel's id.
+    /// Connections which only differ in their ids share a channel.
+    pub fn add_connection(&mut self, connection: &ConnectionData) -> Result<u16, Error> {
+        let schema_key = (
+            connection.data_type.clone(),
+            connection.md5sum.clone(),
+            connection.message_definition.clone(),
+        );
+        let schema_id = match self.schema_ids.get(&schema_key) {
+            Some(id) => *id,
+            None => {
+                // 0 means a channel has no schema, so ids start at 1
+                let id = u16_id(self.schema_records.len() + 1, "schemas")?;
+                let mut record = Vec::new();
+                put_u16(&mut record, id);
+                put_str(&mut record, &connection.data_type);
+                put_str(&mut record, "ros1msg");
+                put_bytes(&mut record, connection.message_definition.as_bytes());
+                self.write_record(OP_SCHEMA, &record)?;
+                self.schema_records.push(record);
+                self.schema_ids.insert(schema_key, id);
+                id
+            }
+        };
+
+        let channel_key = ChannelKey {
+            topic: connection.raw_topic().to_vec(),
+            schema_id,
+            caller_id: connection.caller_id.clone(),
+            latching: connection.latching,
+        };
+        if let Some(id) = self.channel_ids.get(&channel_key) {
+            return Ok(*id);
+        }
+        let id = u16_id(self.channel_records.len(), "channels")?;
+        // MCAP topics and schema names are UTF-8, so names which aren't are also kept as recorded, in hex
+        let topic_hex = std::str::from_utf8(connection.raw_topic())
+            .is_err()
+            .then(|| to_hex(connection.raw_topic()));
+        let type_hex = std::str::from_utf8(connection.raw_data_type())
+            .is_err()
+            .then(|| to_hex(connection.raw_data_type()));
+        let mut metadata = vec![("md5sum", connection.md5sum.as_str())];
+        if let Some(topic_hex) = &topic_hex {
+            metadata.push(("topic_hex", topic_hex));
+        }
+        if let Some(type_hex) = &type_hex {
+            metadata.push(("type_hex", type_hex));
+        }
+        if let Some(caller_id) = &connection.caller_id {
+            metadata.push(("callerid", caller_id));
+        }
+        if connection.latching {
+            metadata.push(("latching", "1"));
+        }
+        metadata.sort();
+        let mut record = Vec::new();
+        put_u16(&mut record, id);
+        put_u16(&mut record, schema_id);
+        put_str(&mut record, &connection.topic);
+        put_str(&mut record, "ros1");
+        let mut map = Vec::new();
+        for (key, value) in metadata {
+            put_str(&mut map, key);
+            put_str(&mut map, value);
+        }
+        put_bytes(&mut record, &map);
+        // written straight into the data section, so it comes before the chunks of the channel's messages
+        self.write_record(OP_CHANNEL, &record)?;
+        self.channel_records.push(record);
+        self.channel_ids.insert(channel_key, id);
+        Ok(id)
+    }
+
+    /// Writes an already ROS 1 serialized message (without the leading length) received at `time`.
+    pub fn write_raw(&mut self, channel_id: u16, time: Time, data: &[u8]) -> Result<(), Error> {
+        if usize::from(channel_id) >= self.channel_records.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("channel {channel_id} was not added to the writer"),
+            )
+            .into());
+        }
+        let log_time = u64::from(time.secs) * 1_000_000_000 + u64::from(time.nsecs);
+        let count = self.message_counts.entry(channel_id).or_default();
+        let sequence = *count as u32;
+        *count += 1;
+
+        let offset = self.chunk_buf.len() as u64;
+        let mut record = Vec::with_capacity(22 + data.len());
+        put_u16(&mut record, channel_id);
+        put_u32(&mut record, sequence);
+        put_u64(&mut record, log_time);
+        put_u64(&mut record, log_time);
+        record.extend_from_slice(data);
+        put_record(&mut self.chunk_buf, OP_MESSAGE, &record);
+
+        self.chunk_index
+            .entry(channel_id)
+            .or_default()
+            .push((log_time, offset));
+        for (start, end) in [
+            (&mut self.chunk_start_time, &mut self.chunk_end_time),
+            (&mut self.start_time, &mut self.end_time),
+        ] {
+            *start = Some(start.map_or(log_time, |t| t.min(log_time)));
+            *end = Some(end.map_or(log_time, |t| t.max(log_time)));
+        }
+
+        if self.chunk_buf.len() >= self.chunk_threshold {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Copies every message matching `query` from `bag`, returning how many were written.
+    ///
+    /// Fails with [ErrorKind::Cancelled](crate::errors::ErrorKind::Cancelled) if the query is cancelled, keeping the
+    /// messages copied until then, see [crate::cancel].
+    pub fn copy_messages(&mut self, bag: &DecompressedBag, query: &Query) -> Result<usize, Error> {
+        let mut channels: HashMap<ConnectionID, u16> = HashMap::new();
+        let mut count = 0;
+        for msg_view in bag.filter_messages(query)? {
+            let channel_id = match channels.get(&msg_view.conn_id) {
+                Some(id) => *id,
+                None => {
+                    let id = self.add_connection(msg_view.connection())?;
+                    channels.insert(msg_view.conn_id, id);
+                    id
+                }
+            };
+            self.write_raw(channel_id, msg_view.time, msg_view.data_bytes())?;
+            count += 1;
+        }
+        // the iterator stops early once the query is cancelled
+        query.check_cancelled()?;
+        Ok(count)
+    }
+
+    /// Writes the buffered chunk and the summary, returning the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.flush_chunk()?;
+        let mut data_end = Vec::new();
+        put_u32(&mut data_end, 0);
+        self.write_record(OP_DATA_END, &data_end)?;
+
+        let statistics = self.statistics();
+        let summary_start = self.position;
+        let mut groups = Vec::new();
+        for (opcode, records) in [
+            (OP_SCHEMA, std::mem::take(&mut self.schema_records)),
+            (OP_CHANNEL, std::mem::take(&mut self.channel_records)),
+            (OP_STATISTICS, vec![statistics]),
+            (
+                OP_CHUNK_INDEX,
+                std::mem::take(&mut self.chunk_index_records),
+            ),
+        ] {
+            if records.is_empty() {
+                continue;
+            }
+            let group_start = self.position;
+            for record in records.iter() {
+                self.write_record(opcode, record)?;
+            }
+            groups.push((opcode, group_start, self.position - group_start));
+        }
+
+        let summary_offset_start = self.position;
+        for (opcode, start, length) in groups {
+            let mut record = vec![opcode];
+            put_u64(&mut record, start);
+            put_u64(&mut record, length);
+            self.write_record(OP_SUMMARY_OFFSET, &record)?;
+        }
+
+        let mut footer = Vec::new();
+        put_u64(&mut footer, summary_start);
+        put_u64(&mut footer, summary_offset_start);
+        put_u32(&mut footer, 0);
+        self.write_record(OP_FOOTER, &footer)?;
+        self.write_bytes(MAGIC)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn statistics(&self) -> Vec<u8> {
+        let mut record = Vec::new();
+        put_u64(&mut record, self.message_counts.values().sum());
+        put_u16(&mut record, self.schema_ids.len() as u16);
+        put_u32(&mut record, self.channel_ids.len() as u32);
+        // attachments and metadata
+        put_u32(&mut record, 0);
+        put_u32(&mut record, 0);
+        put_u32(&mut record, self.chunk_index_records.len() as u32);
+        put_u64(&mut record, self.start_time.unwrap_or_default());
+        put_u64(&mut record, self.end_time.unwrap_or_default());
+        let mut counts = Vec::new();
+        for (channel_id, count) in self.message_counts.iter() {
+            put_u16(&mut counts, *channel_id);
+            put_u64(&mut counts, *count);
+        }
+        put_bytes(&mut record, &counts);
+        record
+    }
+
+    fn flush_chunk(&mut self) -> Result<(), Error> {
+        if self.chunk_buf.is_empty() {
+            return Ok(());
+        }
+        let start_time = self.chunk_start_time.take().unwrap_or_default();
+        let end_time = self.chunk_end_time.take().unwrap_or_default();
+        let compressed = self.compression.compress(&self.chunk_buf)?;
+
+        let chunk_start = self.position;
+        let mut chunk = Vec::with_capacity(compressed.len() + 64);
+        put_u64(&mut chunk, start_time);
+        put_u64(&mut chunk, end_time);
+        put_u64(&mut chunk, self.chunk_buf.len() as u64);
+        put_u32(&mut chunk, 0);
+        put_str(&mut chunk, self.compression.as_str());
+        put_u64(&mut chunk, compressed.len() as u64);
+        chunk.extend_from_slice(&compressed);
+        self.write_record(OP_CHUNK, &chunk)?;
+        let chunk_length = self.position - chunk_start;
+
+        let message_index_start = self.position;
+        let mut message_index_offsets = Vec::new();
+        for (channel_id, entries) in std::mem::take(&mut self.chunk_index) {
+            put_u16(&mut message_index_offsets, channel_id);
+            put_u64(&mut message_index_offsets, self.position);
+            let mut index = Vec::with_capacity(entries.len() * 16);
+            for (log_time, offset) in entries {
+                put_u64(&mut index, log_time);
+                put_u64(&mut index, offset);
+            }
+            let mut record = Vec::new();
+            put_u16(&mut record, channel_id);
+            put_bytes(&mut record, &index);
+            self.write_record(OP_MESSAGE_INDEX, &record)?;
+        }
+
+        let mut chunk_index = Vec::new();
+        put_u64(&mut chunk_index, start_time);
+        put_u64(&mut chunk_index, end_time);
+        put_u64(&mut chunk_index, chunk_start);
+        put_u64(&mut chunk_index, chunk_length);
+        put_bytes(&mut chunk_index, &message_index_offsets);
+        put_u64(&mut chunk_index, self.position - message_index_start);
+        put_str(&mut chunk_index, self.compression.as_str());
+        put_u64(&mut chunk_index, compressed.len() as u64);
+        put_u64(&mut chunk_index, self.chunk_buf.len() as u64);
+        self.chunk_index_records.push(chunk_index);
+        self.chunk_buf.clear();
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_record(&mut self, opcode: u8, content: &[u8]) -> io::Result<()> {
+        self.write_bytes(&[opcode])?;
+        self.write_bytes(&(content.len() as u64).to_le_bytes())?;
+        self.write_bytes(content)
+    }
+}
+
+fn u16_id(id: usize, what: &str) -> io::Result<u16> {
+    u16::try_from(id).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("an MCAP file holds at most {} {what}", u16::MAX),
+        )
+    })
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Strings, byte arrays, and maps are all prefixed with their length in bytes as a u32.
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_bytes(buf, s.as_bytes());
+}
+
+fn put_record(buf: &mut Vec<u8>, opcode: u8, content: &[u8]) {
+    buf.push(opcode);
+    put_u64(buf, content.len() as u64);
+    buf.extend_from_slice(content);
+}
+
+/// A schema record, describing the messages of the channels using it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct McapSchema {
+    pub id: u16,
+    pub name: String,
+    /// How `data` is encoded, like `ros1msg` or `ros2msg`.
+    pub encoding: String,
+    pub data: Vec<u8>,
+}
+
+/// A channel record, the MCAP equivalent of a connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct McapChannel {
+    pub id: u16,
+    /// 0 if the channel has no schema.
+    pub schema_id: u16,
+    pub topic: String,
+    /// How messages are serialized, like `ros1` or `cdr`.
+    pub message_encoding: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// A message record, borrowed from the chunk or record it was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct McapMessage<'a> {
+    pub channel_id: u16,
+    pub sequence: u32,
+    /// Nanoseconds since the epoch at which the message was recorded.
+    pub log_time: u64,
+    pub publish_time: u64,
+    pub data: &'a [u8],
+}
+
+/// Reads the messages of an MCAP file in the order they were written, with the schemas and channels they refer to.
+///
+/// Only the data section is read, decompressing chunks as they come, so files don't need to be indexed or even
+/// finished.
+pub struct McapReader<R: Read> {
+    reader: R,
+    schemas: HashMap<u16, McapSchema>,
+    channels: HashMap<u16, McapChannel>,
+}
+
+impl McapReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
+        McapReader::new(BufReader::new(File::open(file_path)?))
+    }
+}
+
+impl<R: Read> McapReader<R> {
+    /// Starts reading an MCAP file, failing if it doesn't start with the MCAP magic.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not an MCAP file").into());
+        }
+        Ok(McapReader {
+            reader,
+            schemas: HashMap::new(),
+            channels: HashMap::new(),
+        })
+    }
+
+    /// The schemas read so far.
+    pub fn schemas(&self) -> impl Iterator<Item = &McapSchema> {
+        self.schemas.values()
+    }
+
+    /// The channels read so far.
+    pub fn channels(&self) -> impl Iterator<Item = &McapChannel> {
+        self.channels.values()
+    }
+
+    /// Calls `on_message` with every message, its channel, and the channel's schema, if it has one, returning how
+    /// many messages were read. Stops at the end of the data section, or at the end of a file which wasn't finished.
+    pub fn read_messages<F>(&mut self, mut on_message: F) -> Result<usize, Error>
+    where
+        F: FnMut(&McapChannel, Option<&McapSchema>, McapMessage) -> Result<(), Error>,
+    {
+        let mut count = 0;
+        let mut content = Vec::new();
+        loop {
+            let mut prefix = [0; 9];
+            match self.reader.read_exact(&mut prefix) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err.into()),
+            }
+            let opcode = prefix[0];
+            let len = u64::from_le_bytes(prefix[1..].try_into().expect("8 bytes"));
+            content.clear();
+            (&mut self.reader).take(len).read_to_end(&mut content)?;
+            if content.len() as u64 != len {
+                break;
+            }
+            match opcode {
+                OP_DATA_END | OP_FOOTER => break,
+                OP_CHUNK => {
+                    let records = decompress_chunk(&content)?;
+                    let mut records = records.as_slice();
+                    while !records.is_empty() {
+                        let mut reader = Cursor::new(records);
+                        let opcode = reader.u8()?;
+                        let len = reader.u64()? as usize;
+                        let record = reader.take(len)?;
+                        records = reader.rest();
+                        count += self.handle(opcode, record, &mut on_message)?;
+                    }
+                }
+                opcode => count += self.handle(opcode, &content, &mut on_message)?,
+            }
+        }
+        Ok(count)
+    }
+
+    /// Handles a record from the data section or a chunk, returning 1 if it was a message.
+    fn handle<F>(&mut self, opcode: u8, content: &[u8], on_message: &mut F) -> Result<usize, Error>
+    where
+        F: FnMut(&McapChannel, Option<&McapSchema>, McapMessage) -> Result<(), Error>,
+    {
+        let mut reader = Cursor::new(content);
+        match opcode {
+            OP_SCHEMA => {
+                let schema = McapSchema {
+                    id: reader.u16()?,
+                    name: reader.string()?,
+                    encoding: reader.string()?,
+                    data: reader.bytes()?.to_vec(),
+                };
+                self.schemas.insert(schema.id, schema);
+            }
+            OP_CHANNEL => {
+                let id = reader.u16()?;
+                let schema_id = reader.u16()?;
+                let topic = reader.string()?;
+                let message_encoding = reader.string()?;
+                let mut map = Cursor::new(reader.bytes()?);
+                let mut metadata = BTreeMap::new();
+                while !map.rest().is_empty() {
+                    metadata.insert(map.string()?, map.string()?);
+                }
+                let channel = McapChannel {
+                    id,
+                    schema_id,
+                    topic,
+                    message_encoding,
+                    metadata,
+                };
+                self.channels.insert(id, channel);
+            }
+            OP_MESSAGE => {
+                let message = McapMessage {
+                    channel_id: reader.u16()?,
+                    sequence: reader.u32()?,
+                    log_time: reader.u64()?,
+                    publish_time: reader.u64()?,
+                    data: reader.rest(),
+                };
+                let channel = self.channels.get(&message.channel_id).ok_or_else(|| {
+                    invalid(&format!(
+                        "message on unknown channel {}",
+                        message.channel_id
+                    ))
+                })?;
+                on_message(channel, self.schemas.get(&channel.schema_id), message)?;
+                return Ok(1);
+            }
+            _ => {}
+        }
+        Ok(0)
+    }
+}
+
+fn decompress_chunk(content: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut reader = Cursor::new(content);
+    reader.take(16)?;
+    let uncompressed_size = reader.u64()? as usize;
+    reader.u32()?;
+    let compression = reader.string()?;
+    let len = reader.u64()? as usize;
+    let records = reader.take(len)?;
+    if compression.is_empty() {
+        return Ok(records.to_vec());
+    }
+    let decompressor = compression::lookup(&compression)
+        .ok_or_else(|| invalid(&format!("unsupported chunk compression '{compression}'")))?;
+    let chunk = ChunkContext {
+        uncompressed_size,
+        dict_id: None,
+    };
+    Ok(decompressor.decompress(records, &chunk)?)
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid MCAP file: {reason}"),
+    )
+}
+
+/// Reads the little endian fields of a record.
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Cursor { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], io::Error> {
+        if len > self.data.len() {
+            return Err(invalid("record ends early"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn u8(&mut self) -> Result<u8, io::Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, io::Error> {
+        Ok(u16::from_le_bytes(
+            self.take(2)?.try_into().expect("2 bytes"),
+        ))
+    }
+
+    fn u32(&mut self) -> Result<u32, io::Error> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64, io::Error> {
+        Ok(u64::from_le_bytes(
+            self.take(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], io::Error> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, io::Error> {
+        Ok(String::from_utf8_lossy(self.bytes()?).into_owned())
+    }
+}
@@ -0,0 +1,234 @@
+//! Merging bags recorded on several machines into one, ordered by a chosen clock.
+//!
+//! Each machine stamps receive times with its own clock, so merging by receive time interleaves messages wrongly
+//! when the clocks disagree. A [Merge] orders messages by a [TimeSource], shifted by a per-bag offset correcting
+//! that bag's clock, and can rewrite receive times to the corrected times so players replay the merged bag in order.
+//! Message data, including header stamps, is always copied unchanged.
+//!
+//! Bags whose recordings overlap, like those of a primary and a backup recorder, hold the same messages twice.
+//! [Merge::with_dedup_tolerance] keeps one copy of each, giving their union.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::merge::{Merge, TimeSource};
+//! use frost::writer::BagWriter;
+//! use frost::DecompressedBag;
+//!
+//! let robot = DecompressedBag::from_file("robot.bag").unwrap();
+//! let base = DecompressedBag::from_file("base_station.bag").unwrap();
+//!
+//! let mut writer = BagWriter::create("merged.bag").unwrap();
+//! // the base station's clock runs 250ms ahead of the robot's
+//! Merge::new()
+//!     .with_time_source(TimeSource::Header)
+//!     .with_rewritten_times()
+//!     .add_bag(&robot, 0.0)
+//!     .add_bag(&base, -0.25)
+//!     .write_to(&mut writer)
+//!     .unwrap();
+//! writer.finish().unwrap();
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{Seek, Write};
+use std::str::FromStr;
+
+use crate::dynamic::{Cardinality, FieldType, Schema};
+use crate::errors::Error;
+use crate::msgs::MessageView;
+use crate::query::Query;
+use crate::time::{self, Time};
+use crate::writer::BagWriter;
+use crate::{ConnectionID, DecompressedBag};
+
+/// The clock messages are ordered by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeSource {
+    /// The time the recorder received the message.
+    #[default]
+    Receive,
+    /// The stamp of the message's `std_msgs/Header`, which is usually closer to when the data was measured.
+    /// Messages without a header, or with a zero stamp, fall back to their receive time.
+    Header,
+}
+
+impl fmt::Display for TimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSource::Receive => f.pad("receive"),
+            TimeSource::Header => f.pad("header"),
+        }
+    }
+}
+
+impl FromStr for TimeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "receive" => Ok(TimeSource::Receive),
+            "header" => Ok(TimeSource::Header),
+            other => Err(format!(
+                "unknown time source '{other}', expected receive or header"
+            )),
+        }
+    }
+}
+
+/// Adds `offset_ns` to `time`, saturating at the range of [Time].
+pub fn shift(time: Time, offset_ns: i64) -> Time {
+    let ns = i128::from(time.secs) * 1_000_000_000 + i128::from(time.nsecs) + i128::from(offset_ns);
+    let max = i128::from(u32::MAX) * 1_000_000_000 + 999_999_999;
+    let ns = ns.clamp(0, max);
+    Time {
+        secs: (ns / 1_000_000_000) as u32,
+        nsecs: (ns % 1_000_000_000) as u32,
+    }
+}
+
+/// Returns the header stamp of a message whose first field is a `std_msgs/Header`, if it is nonzero.
+fn header_stamp(msg_view: &MessageView, has_header: bool) -> Option<Time> {
+    if !has_header {
+        return None;
+    }
+    // a header starts with its uint32 seq, followed by its stamp
+    let stamp = Time::from_le_bytes(msg_view.data_bytes().get(4..12)?).ok()?;
+    (stamp != time::ZERO).then_some(stamp)
+}
+
+fn starts_with_header(data_type: &str, message_definition: &str) -> bool {
+    let Ok(schema) = Schema::parse(data_type, message_definition) else {
+        return false;
+    };
+    matches!(
+        schema.root().fields.first(),
+        Some(field) if field.cardinality == Cardinality::Single
+            && matches!(&field.field_type, FieldType::Message(t) if t == "std_msgs/Header")
+    )
+}
+
+struct MergeInput<'a> {
+    bag: &'a DecompressedBag,
+    offset_ns: i64,
+}
+
+/// Merges bags into a [BagWriter], see the [module docs](self).
+#[derive(Default)]
+pub struct Merge<'a> {
+    inputs: Vec<MergeInput<'a>>,
+    time_source: TimeSource,
+    rewrite_times: bool,
+    query: Option<Query>,
+    dedup_tolerance_ns: Option<i64>,
+}
+
+impl<'a> Merge<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the clock messages are ordered by. Defaults to [TimeSource::Receive].
+    pub fn with_time_source(mut self, time_source: TimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Writes each message with its corrected time as its receive time, instead of its original receive time.
+    pub fn with_rewritten_times(mut self) -> Self {
+        self.rewrite_times = true;
+        self
+    }
+
+    /// Only merges the messages matching `query`. Its time range applies to the original receive times.
+    pub fn with_query(mut self, query: Query) -> Self {
+        self.query = Some(query);
+        self
+    }
+
+    /// Drops messages identical to a message from another bag on the same topic whose corrected time is within
+    /// `tolerance_secs` of theirs, keeping the earliest. Messages are compared by their serialized bytes, and
+    /// repeated messages within one bag are always kept.
+    pub fn with_dedup_tolerance(mut self, tolerance_secs: f64) -> Self {
+        self.dedup_tolerance_ns = Some((tolerance_secs * 1e9).round() as i64);
+        self
+    }
+
+    /// Adds a bag whose clock is corrected by adding `offset_secs`, which may be negative.
+    pub fn add_bag(mut self, bag: &'a DecompressedBag, offset_secs: f64) -> Self {
+        self.inputs.push(MergeInput {
+            bag,
+            offset_ns: (offset_secs * 1e9).round() as i64,
+        });
+        self
+    }
+
+    /// Returns the corrected time of a message from the `input`th bag.
+    fn key(&self, input: &MergeInput, msg_view: &MessageView, has_header: bool) -> Time {
+        let time = match self.time_source {
+            TimeSource::Receive => msg_view.time,
+            TimeSource::Header => header_stamp(msg_view, has_header).unwrap_or(msg_view.time),
+        };
+        shift(time, input.offset_ns)
+    }
+
+    /// Writes the messages of every bag to `writer` ordered by their corrected times, returning how many were written.
+    ///
+    /// Messages with equal times keep the order of the bags they came from. Identical connections are shared.
+    /// Duplicates dropped by [Merge::with_dedup_tolerance] aren't counted.
+    /// Fails with [ErrorKind::Cancelled](crate::errors::ErrorKind::Cancelled) if the [Merge::with_query] query is
+    /// cancelled, keeping the messages written until then, see [crate::cancel].
+    pub fn write_to<W: Write + Seek>(&self, writer: &mut BagWriter<W>) -> Result<usize, Error> {
+        let all = Query::all();
+        let query = self.query.as_ref().unwrap_or(&all);
+
+        let mut messages = Vec::new();
+        for (i, input) in self.inputs.iter().enumerate() {
+            let mut has_header: HashMap<ConnectionID, bool> = HashMap::new();
+            for msg_view in input.bag.filter_messages(query)? {
+                let has_header = *has_header.entry(msg_view.conn_id).or_insert_with(|| {
+                    let connection = msg_view.connection();
+                    self.time_source == TimeSource::Header
+                        && starts_with_header(&connection.data_type, &connection.message_definition)
+                });
+                messages.push((self.key(input, &msg_view, has_header), i, msg_view));
+            }
+        }
+        query.check_cancelled()?;
+        // stable, so messages from the same bag at the same time keep their order
+        messages.sort_by_key(|(key, i, _)| (*key, *i));
+
+        let mut conn_ids: HashMap<(usize, ConnectionID), ConnectionID> = HashMap::new();
+        // the messages written within the tolerance of the current one, per topic
+        let mut recent: HashMap<&str, VecDeque<_>> = HashMap::new();
+        let mut count = 0;
+        for (key, i, msg_view) in messages.iter() {
+            query.check_cancelled()?;
+            if let Some(tolerance_ns) = self.dedup_tolerance_ns {
+                let window = recent.entry(msg_view.topic).or_default();
+                let since = shift(*key, -tolerance_ns);
+                while window.front().map_or(false, |(time, _, _)| *time < since) {
+                    window.pop_front();
+                }
+                let data = msg_view.data_bytes();
+                if window
+                    .iter()
+                    .any(|(_, input, bytes)| input != i && *bytes == data)
+                {
+                    continue;
+                }
+                window.push_back((*key, *i, data));
+            }
+            let conn_id = *conn_ids
+                .entry((*i, msg_view.conn_id))
+                .or_insert_with(|| writer.add_connection(msg_view.connection()));
+            let time = if self.rewrite_times {
+                *key
+            } else {
+                msg_view.time
+            };
+            writer.write_copied(msg_view, conn_id, time)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+use std::io::{self, Read, Seek};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use crate::ChunkHeaderLoc;
+
+/// Receives events while bags are being processed, e.g. to export Prometheus metrics
+/// from a long-running service.
+///
+/// Every method has a no-op default so implementors only need to handle the events they care about.
+///
+/// Example
+/// ```rust
+/// use std::sync::Arc;
+/// use frost::metrics::{self, Counters};
+///
+/// let counters = Arc::new(Counters::default());
+/// metrics::set_recorder(counters.clone());
+///
+/// // ... load and iterate over bags ...
+///
+/// let snapshot = counters.snapshot();
+/// println!("{} messages yielded", snapshot.messages_yielded);
+/// # metrics::clear_recorder();
+/// ```
+pub trait Recorder: Send + Sync {
+    /// Called with the number of bytes read from a bag's underlying storage.
+    fn bytes_read(&self, _bytes: u64) {}
+
+    /// Called after a chunk has been loaded into memory, including chunks with `none` compression.
+    fn chunk_decompressed(&self, _compression: &str, _uncompressed_bytes: u64, _elapsed: Duration) {
+    }
+
+    /// Called every time a message is yielded from a [BagIter](crate::query::BagIter).
+    fn message_yielded(&self, _topic: &str) {}
+}
+
+static RECORDER: RwLock<Option<Arc<dyn Recorder>>> = RwLock::new(None);
+
+/// Installs a process-wide recorder, replacing any previously installed one.
+pub fn set_recorder(recorder: Arc<dyn Recorder>) {
+    if let Ok(mut current) = RECORDER.write() {
+        *current = Some(recorder);
+    }
+}
+
+/// Removes the process-wide recorder, if any.
+pub fn clear_recorder() {
+    if let Ok(mut current) = RECORDER.write() {
+        *current = None;
+    }
+}
+
+pub(crate) fn recorder() -> Option<Arc<dyn Recorder>> {
+    RECORDER.read().ok()?.clone()
+}
+
+/// A [Recorder] which keeps running totals in atomic counters.
+#[derive(Debug, Default)]
+pub struct Counters {
+    bytes_read: AtomicU64,
+    chunks_decompressed: AtomicU64,
+    decompressed_bytes: AtomicU64,
+    decompression_nanos: AtomicU64,
+    messages_yielded: AtomicU64,
+}
+
+/// Point-in-time totals taken from [Counters].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub bytes_read: u64,
+    pub chunks_decompressed: u64,
+    pub decompressed_bytes: u64,
+    pub decompression_time: Duration,
+    pub messages_yielded: u64,
+}
+
+impl Counters {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            chunks_decompressed: self.chunks_decompressed.load(Ordering::Relaxed),
+            decompressed_bytes: self.decompressed_bytes.load(Ordering::Relaxed),
+            decompression_time: Duration::from_nanos(
+                self.decompression_nanos.load(Ordering::Relaxed),
+            ),
+            messages_yielded: self.messages_yielded.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Recorder for Counters {
+    fn bytes_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn chunk_decompressed(&self, _compression: &str, uncompressed_bytes: u64, elapsed: Duration) {
+        self.chunks_decompressed.fetch_add(1, Ordering::Relaxed);
+        self.decompressed_bytes
+            .fetch_add(uncompressed_bytes, Ordering::Relaxed);
+        self.decompression_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn message_yielded(&self, _topic: &str) {
+        self.messages_yielded.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Statistics about reading one bag through a [Query](crate::query::Query), to check that chunk pruning and caching
+/// work for it, see [Query::with_stats](crate::query::Query::with_stats).
+///
+/// Clones share the same statistics, which are filled in as the bag is loaded with
+/// [DecompressedBag::from_file_filtered](crate::DecompressedBag::from_file_filtered) and as the query's
+/// [BagIter](crate::query::BagIter)s are consumed, so they can be read at any point. Chunks are told apart by their
+/// position, so give the queries of different bags their own handles.
+///
+/// Example
+/// ```rust
+/// use frost::metrics::QueryStats;
+/// use frost::query::Query;
+/// use frost::DecompressedBag;
+///
+/// let stats = QueryStats::new();
+/// let query = Query::new().with_topics(["/chatter"]).with_stats(stats.clone());
+/// let bag = DecompressedBag::from_file_filtered("./tests/fixtures/compressed_lz4.bag", &query).unwrap();
+/// assert_eq!(bag.read_messages(&query).unwrap().count(), 100);
+///
+/// let snapshot = stats.snapshot();
+/// assert_eq!(snapshot.chunks_decompressed, 1);
+/// assert_eq!(snapshot.chunks_read, 1);
+/// // the chunk was decompressed for this query, rather than already loaded
+/// assert_eq!(snapshot.chunk_cache_hits, 0);
+/// assert_eq!(snapshot.messages_yielded, 100);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct QueryStats {
+    state: Arc<Mutex<QueryStatsState>>,
+}
+
+#[derive(Debug, Default)]
+struct QueryStatsState {
+    chunks_pruned: u64,
+    decompressed: HashSet<ChunkHeaderLoc>,
+    decompressed_bytes: u64,
+    decompression_time: Duration,
+    read: HashSet<ChunkHeaderLoc>,
+    messages_yielded: u64,
+    elapsed: Duration,
+}
+
+/// Point-in-time totals taken from [QueryStats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryStatsSnapshot {
+    /// Chunks which weren't loaded because none of their messages match the query.
+    pub chunks_pruned: u64,
+    /// Chunks decompressed while loading the bag for the query.
+    pub chunks_decompressed: u64,
+    /// The uncompressed bytes of those chunks.
+    pub decompressed_bytes: u64,
+    /// The time spent reading and decompressing them.
+    pub decompression_time: Duration,
+    /// Chunks messages were read from.
+    pub chunks_read: u64,
+    /// Chunks messages were read from which were already in memory, rather than decompressed for the query.
+    pub chunk_cache_hits: u64,
+    pub messages_yielded: u64,
+    /// The time spent loading the bag and in its [BagIter](crate::query::BagIter)s, but not in the code consuming
+    /// their messages.
+    pub elapsed: Duration,
+}
+
+impl QueryStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> QueryStatsSnapshot {
+        let state = self.state.lock().unwrap();
+        QueryStatsSnapshot {
+            chunks_pruned: state.chunks_pruned,
+            chunks_decompressed: state.decompressed.len() as u64,
+            decompressed_bytes: state.decompressed_bytes,
+            decompression_time: state.decompression_time,
+            chunks_read: state.read.len() as u64,
+            chunk_cache_hits: state.read.difference(&state.decompressed).count() as u64,
+            messages_yielded: state.messages_yielded,
+            elapsed: state.elapsed,
+        }
+    }
+
+    pub(crate) fn chunk_decompressed(
+        &self,
+        chunk_loc: ChunkHeaderLoc,
+        uncompressed_bytes: u64,
+        elapsed: Duration,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if state.decompressed.insert(chunk_loc) {
+            state.decompressed_bytes += uncompressed_bytes;
+        }
+        state.decompression_time += elapsed;
+    }
+
+    pub(crate) fn chunks_pruned(&self, count: usize) {
+        self.state.lock().unwrap().chunks_pruned += count as u64;
+    }
+
+    pub(crate) fn message_yielded(&self, chunk_loc: ChunkHeaderLoc, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.read.insert(chunk_loc);
+        state.messages_yielded += 1;
+        state.elapsed += elapsed;
+    }
+
+    pub(crate) fn add_elapsed(&self, elapsed: Duration) {
+        self.state.lock().unwrap().elapsed += elapsed;
+    }
+}
+
+/// Wraps a reader and counts how many bytes were actually read (seeks are not counted).
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    pub(crate) count: u64,
+}
+
+impl<R> CountingReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        CountingReader { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
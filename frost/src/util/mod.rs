@@ -1,4 +1,56 @@
+pub mod accounting;
+pub mod batch;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod cancel;
+pub mod chunk_analysis;
+pub mod clock;
+pub mod compression;
+pub mod crop;
+pub mod crypto;
+pub mod decoders;
+pub mod dedup;
+pub mod diagnostics;
+#[cfg(feature = "zstd")]
+pub mod dictionary;
+pub mod dynamic;
+pub mod echo;
+pub mod edit;
+pub mod etl;
+pub mod explain;
+pub mod export;
+pub mod fanout;
+pub mod health;
+#[cfg(feature = "images")]
+pub mod images;
+pub mod import;
+pub mod layout;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod maps;
+pub mod mcap;
+pub mod merge;
+pub mod metrics;
+#[cfg(feature = "mount")]
+pub mod mount;
 pub mod msgs;
+pub mod ordering;
+pub mod output;
 pub mod parsing;
+pub mod pcap;
+pub mod plugins;
 pub mod query;
+#[cfg(feature = "ros2")]
+pub mod ros2;
+pub mod rosout;
+pub mod scan;
+pub mod schemas;
+pub mod skew;
+pub mod storage;
+pub mod summary;
+pub mod tail;
 pub mod time;
+pub mod topic_search;
+pub mod topic_tree;
+pub mod trajectory;
+pub mod triage;
@@ -0,0 +1,385 @@
+//! A read-only FUSE filesystem exposing a bag's messages as files, enabled with the `mount` feature.
+//!
+//! Every topic is a directory, nested by namespace so `/camera/image_raw` is `camera/image_raw/`,
+//! holding one file per message named by its index and receive time, like `000042_1665952859.012345678.json`.
+//! Files are either the message decoded with [crate::dynamic] as JSON, or its raw serialized bytes.
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use fuser::{FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory};
+use fuser::{ReplyEntry, Request, FUSE_ROOT_ID};
+
+use crate::dynamic::{DecodeLimits, Schema};
+use crate::errors::Error;
+use crate::msgs::MessageView;
+use crate::query::Query;
+use crate::time::{self, Time};
+use crate::{ChunkHeaderLoc, ConnectionID, DecompressedBag};
+
+/// the bag never changes, so the kernel can cache attributes for as long as it likes
+const TTL: Duration = Duration::from_secs(3600);
+/// rendered files kept around so `getattr` and the `read`s that follow don't decode a message repeatedly
+const MAX_CACHED_FILES: usize = 256;
+
+/// How messages are presented as files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    /// Pretty printed JSON of the decoded message.
+    Json,
+    /// The serialized message, as stored in the bag.
+    Raw,
+}
+
+impl FileFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Json => "json",
+            FileFormat::Raw => "bin",
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(FileFormat::Json),
+            "raw" => Ok(FileFormat::Raw),
+            other => Err(format!(
+                "unknown file format '{other}', expected 'json' or 'raw'"
+            )),
+        }
+    }
+}
+
+/// Where a message is in the bag, so a [MessageView] can be recreated without borrowing the bag.
+struct MessageLoc {
+    conn_id: ConnectionID,
+    time: Time,
+    chunk_loc: ChunkHeaderLoc,
+    start_index: usize,
+    end_index: usize,
+}
+
+enum Node {
+    Dir {
+        parent: u64,
+        children: BTreeMap<String, u64>,
+    },
+    File {
+        parent: u64,
+        message: MessageLoc,
+    },
+}
+
+/// The filesystem served by [mount].
+pub struct BagFs {
+    bag: DecompressedBag,
+    format: FileFormat,
+    /// indexed by inode - 1
+    nodes: Vec<Node>,
+    schemas: HashMap<ConnectionID, Schema>,
+    cache: HashMap<u64, Vec<u8>>,
+    uid: u32,
+    gid: u32,
+}
+
+impl BagFs {
+    pub fn new(bag: DecompressedBag, format: FileFormat) -> Result<Self, Error> {
+        let mut nodes = vec![Node::Dir {
+            parent: FUSE_ROOT_ID,
+            children: BTreeMap::new(),
+        }];
+
+        let mut topics = bag.metadata.topics();
+        topics.sort_unstable();
+        topics.dedup();
+        for topic in topics {
+            let mut dir = FUSE_ROOT_ID;
+            for segment in topic.split('/').filter(|segment| !segment.is_empty()) {
+                dir = child_dir(&mut nodes, dir, segment);
+            }
+
+            let query = Query::new().with_topics([topic]);
+            for (i, msg_view) in bag.read_messages(&query)?.enumerate() {
+                let name = format!(
+                    "{i:06}_{}.{:09}.{}",
+                    msg_view.time.secs,
+                    msg_view.time.nsecs,
+                    format.extension()
+                );
+                nodes.push(Node::File {
+                    parent: dir,
+                    message: MessageLoc {
+                        conn_id: msg_view.conn_id,
+                        time: msg_view.time,
+                        chunk_loc: msg_view.chunk_loc,
+                        start_index: msg_view.start_index,
+                        end_index: msg_view.end_index,
+                    },
+                });
+                let ino = nodes.len() as u64;
+                if let Node::Dir { children, .. } = &mut nodes[dir as usize - 1] {
+                    children.insert(name, ino);
+                }
+            }
+        }
+
+        let mut schemas = HashMap::new();
+        if format == FileFormat::Json {
+            for (conn_id, connection) in bag.metadata.connection_data.iter() {
+                let schema = Schema::parse(&connection.data_type, &connection.message_definition)?;
+                schemas.insert(*conn_id, schema);
+            }
+        }
+
+        Ok(BagFs {
+            bag,
+            format,
+            nodes,
+            schemas,
+            cache: HashMap::new(),
+            uid: 0,
+            gid: 0,
+        })
+    }
+
+    fn node(&self, ino: u64) -> Option<&Node> {
+        self.nodes.get((ino as usize).checked_sub(1)?)
+    }
+
+    fn message_view<'a>(&'a self, message: &MessageLoc) -> MessageView<'a> {
+        MessageView {
+            topic: &self.bag.metadata.connection_data[&message.conn_id].topic,
+            time: message.time,
+            conn_id: message.conn_id,
+            bag: &self.bag,
+            chunk_loc: message.chunk_loc,
+            start_index: message.start_index,
+            end_index: message.end_index,
+            fields: None,
+            limits: DecodeLimits::default(),
+        }
+    }
+
+    /// Renders the contents of a message file.
+    fn contents(&mut self, ino: u64) -> Result<&[u8], Error> {
+        if !self.cache.contains_key(&ino) {
+            let message = match self.node(ino) {
+                Some(Node::File { message, .. }) => message,
+                _ => return Ok(&[]),
+            };
+            let msg_view = self.message_view(message);
+            let contents = match self.format {
+                FileFormat::Raw => msg_view.data_bytes().to_vec(),
+                FileFormat::Json => {
+                    let value = self.schemas[&message.conn_id].decode(msg_view.data_bytes())?;
+                    let mut json =
+                        serde_json::to_vec_pretty(&value).map_err(std::io::Error::from)?;
+                    json.push(b'\n');
+                    json
+                }
+            };
+            if self.cache.len() >= MAX_CACHED_FILES {
+                self.cache.clear();
+            }
+            self.cache.insert(ino, contents);
+        }
+        Ok(&self.cache[&ino])
+    }
+
+    fn attr(&mut self, ino: u64) -> Option<FileAttr> {
+        let (kind, perm, size, time) = match self.node(ino)? {
+            Node::Dir { .. } => (
+                FileType::Directory,
+                0o555,
+                0,
+                self.bag.metadata.start_time().unwrap_or(time::ZERO),
+            ),
+            Node::File { message, .. } => {
+                let time = message.time;
+                (
+                    FileType::RegularFile,
+                    0o444,
+                    self.contents(ino).ok()?.len() as u64,
+                    time,
+                )
+            }
+        };
+        let time = UNIX_EPOCH + Duration::from(time);
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: (size + 511) / 512,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        })
+    }
+}
+
+/// Returns the inode of the directory `name` in `parent`, creating it if needed.
+fn child_dir(nodes: &mut Vec<Node>, parent: u64, name: &str) -> u64 {
+    if let Node::Dir { children, .. } = &nodes[parent as usize - 1] {
+        if let Some(ino) = children.get(name) {
+            return *ino;
+        }
+    }
+    nodes.push(Node::Dir {
+        parent,
+        children: BTreeMap::new(),
+    });
+    let ino = nodes.len() as u64;
+    if let Node::Dir { children, .. } = &mut nodes[parent as usize - 1] {
+        children.insert(name.to_string(), ino);
+    }
+    ino
+}
+
+impl Filesystem for BagFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let ino = match (self.node(parent), name.to_str()) {
+            (Some(Node::Dir { children, .. }), Some(name)) => children.get(name).copied(),
+            _ => None,
+        };
+        match ino.and_then(|ino| self.attr(ino)) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.contents(ino) {
+            Ok(contents) => {
+                let start = (offset as usize).min(contents.len());
+                let end = (start + size as usize).min(contents.len());
+                reply.data(&contents[start..end]);
+            }
+            Err(e) => {
+                eprintln!("failed to read inode {ino}: {e}");
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let (parent, children) = match self.node(ino) {
+            Some(Node::Dir { parent, children }) => (*parent, children),
+            Some(Node::File { .. }) => return reply.error(libc::ENOTDIR),
+            None => return reply.error(libc::ENOENT),
+        };
+        let entries = [
+            (ino, FileType::Directory, "."),
+            (parent, FileType::Directory, ".."),
+        ]
+        .into_iter()
+        .chain(children.iter().map(|(name, child)| {
+            let kind = match self.nodes[*child as usize - 1] {
+                Node::Dir { .. } => FileType::Directory,
+                Node::File { .. } => FileType::RegularFile,
+            };
+            (*child, kind, name.as_str())
+        }));
+        for (i, (ino, kind, name)) in entries.enumerate().skip(offset as usize) {
+            if reply.add(ino, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Mounts a bag read-only at `mountpoint`, blocking until it is unmounted (e.g. with `fusermount -u`).
+pub fn mount<P: AsRef<Path>>(
+    bag: DecompressedBag,
+    mountpoint: P,
+    format: FileFormat,
+) -> Result<(), Error> {
+    let owner = std::fs::metadata(mountpoint.as_ref())?;
+    let mut fs = BagFs::new(bag, format)?;
+    fs.uid = owner.uid();
+    fs.gid = owner.gid();
+
+    let options = [
+        MountOption::RO,
+        MountOption::FSName("frost".to_string()),
+        MountOption::Subtype("rosbag".to_string()),
+    ];
+    Ok(fuser::mount2(fs, mountpoint, &options)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BagFs, FileFormat, Node};
+    use crate::DecompressedBag;
+    use fuser::FUSE_ROOT_ID;
+
+    const COMPRESSED_LZ4: &[u8] = include_bytes!("../../tests/fixtures/compressed_lz4.bag");
+
+    fn child(fs: &BagFs, parent: u64, name: &str) -> u64 {
+        match fs.node(parent) {
+            Some(Node::Dir { children, .. }) => children[name],
+            _ => panic!("{parent} is not a directory"),
+        }
+    }
+
+    #[test]
+    fn test_message_files() {
+        for (format, expected) in [
+            (FileFormat::Json, &b"{\n  \"data\": \"foo_0\"\n}\n"[..]),
+            (FileFormat::Raw, &b"\x05\0\0\0foo_0"[..]),
+        ] {
+            let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+            let mut fs = BagFs::new(bag, format).unwrap();
+
+            let chatter = child(&fs, FUSE_ROOT_ID, "chatter");
+            match fs.node(chatter) {
+                Some(Node::Dir { children, .. }) => assert_eq!(children.len(), 100),
+                _ => panic!("chatter is not a directory"),
+            }
+
+            let name = format!("000000_0.000001000.{}", format.extension());
+            let file = child(&fs, chatter, &name);
+            assert_eq!(fs.contents(file).unwrap(), expected);
+            assert_eq!(fs.attr(file).unwrap().size, expected.len() as u64);
+        }
+    }
+}
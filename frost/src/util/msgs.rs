@@ -1,18 +1,77 @@
+use std::sync::Arc;
+
 use serde;
 use serde::de;
 use serde_rosmsg;
 
-use crate::errors::Error;
-use crate::{ChunkHeaderLoc, DecompressedBag};
+use crate::decoders;
+use crate::dynamic::{DecodeLimits, Value};
+use crate::errors::{DecodeError, Error, MessageContext};
+use crate::explain::Explanation;
+use crate::schemas::SchemaCache;
+use crate::time::Time;
+use crate::{ChunkHeaderLoc, ConnectionID, DecompressedBag};
+
+/// A Rust type messages can be [instantiated](MessageView::instantiate) as, implemented by generated code or,
+/// with the `derive` feature, by `#[derive(frost::Msg)]`.
+pub trait Msg {
+    /// The ROS type of the message, like `std_msgs/String`, if known.
+    fn ros_type() -> Option<&'static str>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// The md5sum of the message's definition, if known.
+    fn md5sum() -> Option<&'static str>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+#[cfg(feature = "std-msgs")]
+mod generated {
+    include!("std_msgs.rs");
+}
 
-pub trait Msg {}
+/// Rust types for the standard `std_msgs` package, enabled with the `std-msgs` feature.
+///
+/// These are generated by `frost_codegen` (see `scripts/generate_std_msgs.sh`), so simple uses
+/// don't need to set up code-generation in their own build script.
+///
+/// Example
+/// ```rust
+/// use frost::msgs::std_msgs;
+/// use frost::query::Query;
+/// use frost::DecompressedBag;
+///
+/// let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+///
+/// let query = Query::new().with_topics(["/chatter"]);
+/// for msg_view in bag.read_messages(&query).unwrap() {
+///     let msg = msg_view.instantiate::<std_msgs::String>().unwrap();
+///     assert!(msg.data.starts_with("foo_"));
+/// }
+/// ```
+#[cfg(feature = "std-msgs")]
+pub use generated::msgs::std_msgs;
 
 pub struct MessageView<'a> {
     pub topic: &'a str,
+    /// The time at which the message was received.
+    pub time: Time,
+    pub(crate) conn_id: ConnectionID,
     pub(crate) bag: &'a DecompressedBag,
     pub(crate) chunk_loc: ChunkHeaderLoc,
     pub(crate) start_index: usize,
     pub(crate) end_index: usize,
+    /// The fields to decode, from [Query::with_fields](crate::query::Query::with_fields).
+    pub(crate) fields: Option<Arc<[String]>>,
+    /// The limits to decode within, from [Query::with_decode_limits](crate::query::Query::with_decode_limits).
+    pub(crate) limits: DecodeLimits,
 }
 
 impl<'a> MessageView<'a> {
@@ -30,12 +89,177 @@ impl<'a> MessageView<'a> {
         Ok(&self.chunk_bytes()[self.start_index..self.end_index])
     }
 
+    /// Returns the serialized message without its leading 4 byte length, as stored in a message data record
+    pub(crate) fn data_bytes(&self) -> &'a [u8] {
+        &self.chunk_bytes()[(self.start_index + 4)..self.end_index]
+    }
+
+    /// Returns the metadata of the connection the message was recorded on
+    pub(crate) fn connection(&self) -> &'a crate::ConnectionData {
+        self.bag
+            .metadata
+            .connection_data
+            .get(&self.conn_id)
+            .expect("messages always belong to a known connection")
+    }
+
+    /// The type declared by the message's connection, e.g. `std_msgs/String`.
+    pub fn data_type(&self) -> &'a str {
+        &self.connection().data_type
+    }
+
+    /// Decodes the message using the definition recorded with its connection, see [crate::dynamic], or with the
+    /// decoder registered for its topic or type, see [crate::decoders]. Errors carry the message's [MessageContext].
+    /// Only the fields the query selected with [Query::with_fields](crate::query::Query::with_fields) are decoded,
+    /// within the query's [DecodeLimits]. The parsed definition is kept in [SchemaCache::global].
+    pub fn decode(&self) -> Result<Value, Error> {
+        match decoders::lookup(self.topic, self.data_type()) {
+            Some(decoder) => decoder(self).map_err(|e| match e.context() {
+                Some(_) => e,
+                None => e.with_context(self.context()),
+            }),
+            None => self.decode_standard(),
+        }
+    }
+
+    /// Decodes the message using the definition recorded with its connection, ignoring any registered
+    /// [decoder](crate::decoders).
+    pub fn decode_standard(&self) -> Result<Value, Error> {
+        let connection = self.connection();
+        SchemaCache::global()
+            .get_projected(
+                &connection.data_type,
+                &connection.md5sum,
+                &connection.message_definition,
+                self.fields.as_deref().unwrap_or_default(),
+            )?
+            .decode_with_limits(self.data_bytes(), &self.limits)
+            .map_err(|e| e.with_context(self.context()))
+    }
+
     /// Turns a `MessageView` into a Rust struct
+    ///
+    /// Fails if the [Msg::ros_type] or [Msg::md5sum] of `T` are known and differ from those of the message's
+    /// connection. Connections written with a `*` md5sum match any md5sum. When the query set [DecodeLimits], the
+    /// message is first checked against them with the definition recorded with its connection. Errors deserializing
+    /// the message carry its [MessageContext].
     pub fn instantiate<'de, T>(&self) -> Result<T, Error>
     where
         T: Msg,
         T: de::Deserialize<'de>,
     {
-        serde_rosmsg::from_slice(self.raw_bytes()?).map_err(|e| e.into())
+        self.check_instantiable::<T>()?;
+        if self.limits.is_limited() {
+            let connection = self.connection();
+            SchemaCache::global()
+                .get(
+                    &connection.data_type,
+                    &connection.md5sum,
+                    &connection.message_definition,
+                )?
+                .check_limits(self.data_bytes(), &self.limits)
+                .map_err(|e| e.with_context(self.context()))?;
+        }
+        serde_rosmsg::from_slice(self.raw_bytes()?)
+            .map_err(|e| Error::from(e).with_context(self.context()))
+    }
+
+    /// Turns a `MessageView` into a Rust struct like [MessageView::instantiate], also failing if the definition
+    /// recorded with its connection doesn't account for every byte of the message. Bytes left after the last field,
+    /// whether by the definition or by `T`, fail with [DecodeError::TrailingBytes], catching definitions which
+    /// drifted from the data even when their md5sums aren't checked.
+    pub fn instantiate_strict<'de, T>(&self) -> Result<T, Error>
+    where
+        T: Msg,
+        T: de::Deserialize<'de>,
+    {
+        self.check_instantiable::<T>()?;
+        let connection = self.connection();
+        let data = self.data_bytes();
+        let unread = SchemaCache::global()
+            .get(
+                &connection.data_type,
+                &connection.md5sum,
+                &connection.message_definition,
+            )?
+            .unread_bytes(data, &self.limits)
+            .map_err(|e| e.with_context(self.context()))?;
+        let trailing =
+            |len| Error::from(DecodeError::TrailingBytes(len)).with_context(self.context());
+        if unread > 0 {
+            return Err(trailing(unread));
+        }
+        let mut deserializer =
+            serde_rosmsg::Deserializer::new(std::io::Cursor::new(data), data.len() as u32);
+        let msg = T::deserialize(&mut deserializer)
+            .map_err(|e| Error::from(e).with_context(self.context()))?;
+        if !deserializer.is_fully_read() {
+            let read = deserializer.into_inner().position() as usize;
+            return Err(trailing(data.len() - read));
+        }
+        Ok(msg)
+    }
+
+    /// Fails if `T` is known to be another type than the message's.
+    fn check_instantiable<T: Msg>(&self) -> Result<(), Error> {
+        let connection = self.connection();
+        if let Some(ros_type) = T::ros_type() {
+            if ros_type != connection.data_type {
+                return Err(DecodeError::InvalidField(format!(
+                    "cannot instantiate a {ros_type} from a {} message on {}",
+                    connection.data_type, self.topic
+                ))
+                .into());
+            }
+        }
+        if let Some(md5sum) = T::md5sum() {
+            if connection.md5sum != "*" && md5sum != connection.md5sum {
+                return Err(DecodeError::InvalidField(format!(
+                    "cannot instantiate a message with md5sum {md5sum} from one with md5sum {} on {}",
+                    connection.md5sum, self.topic
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Diagnoses why the message does or doesn't [instantiate](MessageView::instantiate) as `T`, tracing it field by
+    /// field against the definition recorded with its connection, see [Explanation].
+    pub fn explain<'de, T>(&self) -> Explanation
+    where
+        T: Msg,
+        T: de::Deserialize<'de>,
+    {
+        let connection = self.connection();
+        let schema = SchemaCache::global()
+            .get(
+                &connection.data_type,
+                &connection.md5sum,
+                &connection.message_definition,
+            )
+            .map_err(|e| e.to_string());
+        let mut explanation = Explanation::new(
+            self.context(),
+            connection.md5sum.clone(),
+            schema.as_deref().map_err(String::as_str),
+            self.data_bytes(),
+        );
+        explanation.expected_type = T::ros_type();
+        explanation.expected_md5sum = T::md5sum();
+        explanation.instantiate_error = self.instantiate::<T>().err().map(|e| e.to_string());
+        explanation
+    }
+
+    /// Where the message is in the bag, for errors decoding it.
+    fn context(&self) -> MessageContext {
+        let connection = self.connection();
+        MessageContext {
+            topic: self.topic.to_string(),
+            data_type: connection.data_type.clone(),
+            time: self.time,
+            chunk_pos: self.chunk_loc,
+            offset: self.start_index,
+        }
     }
 }
@@ -0,0 +1,93 @@
+//! Checks that messages were recorded in receive time order.
+//!
+//! Players which replay a bag's chunks and messages in file order assume receive times only move forward.
+//! Recorders under load, or tools which append messages late, can break that assumption.
+//! [BagMetadata::time_order_report] finds where it is broken using only the index, without reading messages.
+//!
+//! Out of order bags can be fixed by rewriting them: [DecompressedBag::read_messages](crate::DecompressedBag::read_messages)
+//! yields messages in receive time order, so [BagWriter::copy_messages](crate::writer::BagWriter::copy_messages) writes them sorted.
+//! Messages written one by one can be sorted with [BagWriter::with_reorder_window](crate::writer::BagWriter::with_reorder_window).
+//! Either way only receive times are used, the stamps in message headers are left untouched.
+//!
+//! Example
+//! ```rust
+//! use frost::BagMetadata;
+//!
+//! let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let report = metadata.time_order_report();
+//! assert!(report.is_monotonic());
+//! assert_eq!(report.message_count, 300);
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::time::Time;
+use crate::{BagMetadata, ConnectionID};
+
+/// Where receive times go backwards in a bag, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeOrderReport {
+    /// The number of messages checked.
+    pub message_count: usize,
+    /// Per topic, how many messages were received before the message written ahead of them on the same connection.
+    /// Topics without any are left out.
+    pub out_of_order_by_topic: BTreeMap<String, usize>,
+    /// How many messages were received before the message written ahead of them in the same chunk, on any connection.
+    pub out_of_order_in_chunks: usize,
+    /// How many chunks start before an earlier chunk in the file ends.
+    pub overlapping_chunks: usize,
+    /// The furthest a message's receive time goes back from the message written ahead of it on the same connection.
+    pub max_step_back: Duration,
+}
+
+impl TimeOrderReport {
+    /// Returns true if replaying the bag in file order would never go back in time.
+    pub fn is_monotonic(&self) -> bool {
+        self.out_of_order_by_topic.is_empty()
+            && self.out_of_order_in_chunks == 0
+            && self.overlapping_chunks == 0
+    }
+}
+
+impl BagMetadata {
+    /// Checks whether messages were written in receive time order, see [TimeOrderReport].
+    pub fn time_order_report(&self) -> TimeOrderReport {
+        let mut report = TimeOrderReport::default();
+
+        let mut entries: Vec<_> = self.index_data.values().flatten().collect();
+        entries.sort_by_key(|data| (data.chunk_header_pos, data.offset));
+        report.message_count = entries.len();
+
+        let mut last_by_connection: HashMap<ConnectionID, Time> = HashMap::new();
+        let mut last_in_chunk = None;
+        for data in entries {
+            if let Some(last) = last_by_connection.insert(data.conn_id, data.time) {
+                if data.time < last {
+                    let topic = self
+                        .connection_data
+                        .get(&data.conn_id)
+                        .map_or_else(String::new, |connection| connection.topic.clone());
+                    *report.out_of_order_by_topic.entry(topic).or_default() += 1;
+                    report.max_step_back =
+                        report.max_step_back.max(last.duration_since(&data.time));
+                }
+            }
+            if let Some((chunk, last)) = last_in_chunk {
+                if chunk == data.chunk_header_pos && data.time < last {
+                    report.out_of_order_in_chunks += 1;
+                }
+            }
+            last_in_chunk = Some((data.chunk_header_pos, data.time));
+        }
+
+        let mut latest_end: Option<Time> = None;
+        for chunk in self.chunk_metadata.values() {
+            if latest_end.map_or(false, |end| chunk.start_time < end) {
+                report.overlapping_chunks += 1;
+            }
+            latest_end = Some(latest_end.map_or(chunk.end_time, |end| end.max(chunk.end_time)));
+        }
+
+        report
+    }
+}
@@ -0,0 +1,223 @@
+//! Writing the files a command outputs safely: without clobbering existing files, atomically, or not at all.
+//!
+//! An [OutputPolicy] creates each file a command writes as an [Output]. The file is written next to its destination
+//! under a temporary name and renamed into place by [Output::commit], so a failed or interrupted write never leaves
+//! a partial file behind, nor replaces the previous one. Existing files are only replaced
+//! [with force](OutputPolicy::with_force). In a [dry run](OutputPolicy::with_dry_run), outputs are written to a sink
+//! which only counts bytes, so a command can report what it would write, and how large it would be, without touching
+//! the destination.
+//!
+//! Example
+//! ```rust
+//! use frost::output::OutputPolicy;
+//! use frost::query::Query;
+//! use frost::writer::BagWriter;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let dir = tempfile::tempdir().unwrap();
+//! let path = dir.path().join("copy.bag");
+//!
+//! let policy = OutputPolicy::new();
+//! let mut writer = BagWriter::new(policy.create(&path).unwrap()).unwrap();
+//! writer.copy_messages(&bag, &Query::all()).unwrap();
+//! let size = writer.finish().unwrap().commit().unwrap();
+//! assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+//!
+//! // the copy exists now, so writing it again needs force
+//! assert!(policy.create(&path).is_err());
+//! assert!(policy.with_force().create(&path).is_ok());
+//! ```
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::errors::Error;
+
+/// How outputs are created, see the [module docs](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutputPolicy {
+    force: bool,
+    dry_run: bool,
+}
+
+impl OutputPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces existing files, instead of failing.
+    pub fn with_force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    /// Discards what is written, only counting its size.
+    pub fn with_dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Starts writing the file at `path`, failing if it already exists unless forced.
+    pub fn create<P: AsRef<Path>>(&self, path: P) -> Result<Output, Error> {
+        let path = path.as_ref().to_path_buf();
+        check_free(&path, self.force)?;
+        let target = match self.dry_run {
+            true => Target::DryRun { pos: 0, len: 0 },
+            false => {
+                let (partial, file) = partial_file(&path)?;
+                Target::Partial {
+                    path: partial,
+                    file: BufWriter::new(file),
+                }
+            }
+        };
+        Ok(Output {
+            path,
+            force: self.force,
+            target,
+        })
+    }
+}
+
+fn check_free(path: &Path, force: bool) -> io::Result<()> {
+    if !force && path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already exists, refusing to overwrite it",
+                path.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+enum Target {
+    Partial {
+        path: PathBuf,
+        file: BufWriter<File>,
+    },
+    DryRun {
+        pos: u64,
+        len: u64,
+    },
+    Committed,
+}
+
+/// A file being written, which only appears at its path once [committed](Output::commit).
+///
+/// Dropping an output without committing it removes what was written.
+pub struct Output {
+    path: PathBuf,
+    force: bool,
+    target: Target,
+}
+
+impl Output {
+    /// The path the file is written to once committed.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self.target, Target::DryRun { .. })
+    }
+
+    /// Moves the written file to its path, returning its size in bytes. In a dry run, only returns the size.
+    pub fn commit(mut self) -> Result<u64, Error> {
+        let len = match &mut self.target {
+            Target::Partial { path, file } => {
+                file.flush()?;
+                file.get_ref().sync_all()?;
+                let len = file.get_ref().metadata()?.len();
+                // the destination may have been created while writing
+                check_free(&self.path, self.force)?;
+                fs::rename(path, &self.path)?;
+                len
+            }
+            Target::DryRun { len, .. } => *len,
+            Target::Committed => unreachable!("an output is only committed once"),
+        };
+        // until here, dropping the output removes the partial file
+        self.target = Target::Committed;
+        Ok(len)
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.target {
+            Target::Partial { file, .. } => file.write(buf),
+            Target::DryRun { pos, len } => {
+                *pos += buf.len() as u64;
+                *len = (*len).max(*pos);
+                Ok(buf.len())
+            }
+            Target::Committed => unreachable!("committing consumes the output"),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.target {
+            Target::Partial { file, .. } => file.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Seek for Output {
+    fn seek(&mut self, seek: SeekFrom) -> io::Result<u64> {
+        match &mut self.target {
+            Target::Partial { file, .. } => file.seek(seek),
+            Target::DryRun { pos, len } => {
+                let new_pos = match seek {
+                    SeekFrom::Start(offset) => i128::from(offset),
+                    SeekFrom::End(offset) => i128::from(*len) + i128::from(offset),
+                    SeekFrom::Current(offset) => i128::from(*pos) + i128::from(offset),
+                };
+                *pos = u64::try_from(new_pos).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "seek before the start")
+                })?;
+                Ok(*pos)
+            }
+            Target::Committed => unreachable!("committing consumes the output"),
+        }
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if let Target::Partial { path, .. } = &self.target {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Creates a new file next to `path` to write it under, like `out.bag.1234-0.partial`.
+fn partial_file(path: &Path) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    loop {
+        let partial = path.with_file_name(format!(
+            "{name}.{}-{}.partial",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&partial)
+        {
+            Ok(file) => return Ok((partial, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
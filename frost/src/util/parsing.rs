@@ -1,3 +1,4 @@
+use std::fmt::Write as _;
 use std::io::Read;
 
 use crate::errors::ParseError;
@@ -63,3 +64,22 @@ pub fn get_lengthed_bytes(reader: &mut impl Read) -> Result<Vec<u8>, ParseError>
 
     Ok(bytes)
 }
+
+/// Formats `bytes` as lowercase hex.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Parses lowercase or uppercase hex, or returns None if it isn't hex.
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,654 @@
+//! Reading ROS 1 messages out of packet captures of TCPROS traffic, which [crate::import] writes into bags, rescuing
+//! data from network captures when the recorder failed. Experimental.
+//!
+//! Captures in the pcap and pcapng formats, as written by tcpdump and Wireshark, are read over Ethernet, loopback,
+//! Linux cooked and raw IP links. TCP streams are reassembled, and each TCPROS subscription becomes a connection:
+//! its type, md5sum and definition come from the connection header the publisher sends in-band, and its messages are
+//! timestamped with the capture time of the packet which completed them.
+//!
+//! Only connections whose handshake was captured can be read, since TCPROS has no framing to resynchronize on.
+//! Service calls, UDPROS and other traffic are ignored. A connection the capture lost packets of is read up to the
+//! gap and reported by [PcapReader::warnings]. Every captured subscription is read, so a publisher with several
+//! subscribers in the capture has its messages repeated once per subscriber.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::pcap::PcapReader;
+//!
+//! let mut reader = PcapReader::open("robot.pcap").unwrap();
+//! let count = reader
+//!     .read_messages(|connection, time, _data| {
+//!         println!("{time} {} [{}]", connection.topic, connection.data_type);
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! println!("{count} messages");
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use crate::errors::Error;
+use crate::time::Time;
+use crate::ConnectionData;
+
+const PCAP_MICROS: u32 = 0xa1b2c3d4;
+const PCAP_NANOS: u32 = 0xa1b23c4d;
+const PCAPNG_SECTION: u32 = 0x0a0d0d0a;
+const PCAPNG_BYTE_ORDER: u32 = 0x1a2b3c4d;
+const PCAPNG_INTERFACE: u32 = 1;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+const LINKTYPE_NULL: u16 = 0;
+const LINKTYPE_ETHERNET: u16 = 1;
+const LINKTYPE_RAW: u16 = 101;
+const LINKTYPE_LINUX_SLL: u16 = 113;
+const LINKTYPE_LINUX_SLL2: u16 = 276;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_ACK: u8 = 0x10;
+
+/// Longer connection headers mean a stream isn't TCPROS, like XML-RPC's HTTP, whose `POST` reads as ~1.4 GB.
+const MAX_HEADER_LEN: usize = 16 * 1024 * 1024;
+/// Out of order bytes held back for a gap before the capture is deemed to have lost it.
+const MAX_PENDING_LEN: usize = 64 * 1024 * 1024;
+
+/// A problem with a capture which frost worked around while reading it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PcapWarning {
+    /// The capture lost packets of a connection, whose messages after the gap couldn't be read.
+    MissingPackets { topic: String, caller_id: String },
+    /// A publisher refused a subscription with an error, so it has no messages.
+    Refused { topic: String, error: String },
+}
+
+impl std::fmt::Display for PcapWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PcapWarning::MissingPackets { topic, caller_id } => write!(
+                f,
+                "the capture lost packets of {topic} from {caller_id}, later messages on that connection were dropped"
+            ),
+            PcapWarning::Refused { topic, error } => {
+                write!(f, "a subscription to {topic} was refused: {error}")
+            }
+        }
+    }
+}
+
+/// `(source, destination)` of a TCP stream.
+type StreamKey = (SocketAddr, SocketAddr);
+
+/// The fields of a TCPROS connection header, in order.
+type HeaderFields = Vec<(String, Vec<u8>)>;
+
+enum StreamState {
+    /// Waiting for the connection header.
+    Header,
+    /// Reading the messages of a connection, by its index in [PcapReader::connections].
+    Messages(usize),
+    /// Nothing more is read, after the header of a subscriber, or from streams which aren't TCPROS.
+    Done,
+}
+
+/// One direction of a TCP connection.
+struct Stream {
+    /// Whether the sender accepted the connection, so is a publisher if it's TCPROS.
+    server: bool,
+    next_seq: u32,
+    buf: Vec<u8>,
+    /// Segments received ahead of `next_seq`.
+    pending: Vec<(u32, Vec<u8>)>,
+    pending_len: usize,
+    state: StreamState,
+    header: Option<HeaderFields>,
+}
+
+/// Reads TCPROS messages from a pcap or pcapng capture, see the [module docs](self).
+pub struct PcapReader<R: Read> {
+    reader: R,
+    format: Format,
+    streams: HashMap<StreamKey, Stream>,
+    connections: Vec<ConnectionData>,
+    warnings: Vec<PcapWarning>,
+}
+
+enum Format {
+    Pcap {
+        little_endian: bool,
+        nanos: bool,
+        link_type: u16,
+    },
+    Pcapng {
+        little_endian: bool,
+        /// `(link type, timestamp ticks per second)` of each interface
+        interfaces: Vec<(u16, u64)>,
+    },
+}
+
+impl PcapReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
+        PcapReader::new(BufReader::new(File::open(file_path)?))
+    }
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Starts reading a capture, failing if it's neither a pcap nor a pcapng file.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let format = if u32::from_le_bytes(magic) == PCAPNG_SECTION {
+            let mut section = [0; 8];
+            reader.read_exact(&mut section)?;
+            let little_endian = match section[4..8].try_into().expect("4 bytes") {
+                order if u32::from_le_bytes(order) == PCAPNG_BYTE_ORDER => true,
+                order if u32::from_be_bytes(order) == PCAPNG_BYTE_ORDER => false,
+                _ => return Err(invalid("bad pcapng byte order magic").into()),
+            };
+            let len = read_u32(&section[..4], little_endian) as usize;
+            skip(&mut reader, len.saturating_sub(12))?;
+            Format::Pcapng {
+                little_endian,
+                interfaces: Vec::new(),
+            }
+        } else {
+            let (little_endian, nanos) = match magic {
+                magic if u32::from_le_bytes(magic) == PCAP_MICROS => (true, false),
+                magic if u32::from_be_bytes(magic) == PCAP_MICROS => (false, false),
+                magic if u32::from_le_bytes(magic) == PCAP_NANOS => (true, true),
+                magic if u32::from_be_bytes(magic) == PCAP_NANOS => (false, true),
+                _ => return Err(invalid("not a pcap or pcapng file").into()),
+            };
+            let mut header = [0; 20];
+            reader.read_exact(&mut header)?;
+            Format::Pcap {
+                little_endian,
+                nanos,
+                link_type: read_u32(&header[16..], little_endian) as u16,
+            }
+        };
+        Ok(PcapReader {
+            reader,
+            format,
+            streams: HashMap::new(),
+            connections: Vec::new(),
+            warnings: Vec::new(),
+        })
+    }
+
+    /// The TCPROS connections read so far, with their index as their `connection_id`.
+    pub fn connections(&self) -> &[ConnectionData] {
+        &self.connections
+    }
+
+    /// Problems found with the capture so far.
+    pub fn warnings(&self) -> &[PcapWarning] {
+        &self.warnings
+    }
+
+    /// Calls `on_message` with every TCPROS message, its connection, and the time it was captured at, returning how
+    /// many messages were read. Messages are serialized without their leading length, like [BagWriter::write_raw]
+    /// takes them. Stops at the end of the capture, or of a capture which was cut off.
+    ///
+    /// [BagWriter::write_raw]: crate::writer::BagWriter::write_raw
+    pub fn read_messages<F>(&mut self, mut on_message: F) -> Result<usize, Error>
+    where
+        F: FnMut(&ConnectionData, Time, &[u8]) -> Result<(), Error>,
+    {
+        let mut count = 0;
+        let mut packet = Vec::new();
+        while let Some((time, link_type)) = self.next_packet(&mut packet)? {
+            let Some(segment) = tcp_segment(link_type, &packet) else {
+                continue;
+            };
+            count += self.handle(segment, time, &mut on_message)?;
+        }
+        for stream in self.streams.values() {
+            if let (StreamState::Messages(index), false) =
+                (&stream.state, stream.pending.is_empty())
+            {
+                self.warnings
+                    .push(missing_packets(&self.connections[*index]));
+            }
+        }
+        Ok(count)
+    }
+
+    /// Reads the next packet into `packet`, returning its capture time and link type, or `None` at the end.
+    fn next_packet(&mut self, packet: &mut Vec<u8>) -> Result<Option<(Time, u16)>, Error> {
+        loop {
+            match &mut self.format {
+                Format::Pcap {
+                    little_endian,
+                    nanos,
+                    link_type,
+                } => {
+                    let mut header = [0; 16];
+                    if !read_or_eof(&mut self.reader, &mut header)? {
+                        return Ok(None);
+                    }
+                    let secs = read_u32(&header, *little_endian);
+                    let fraction = read_u32(&header[4..], *little_endian);
+                    let len = read_u32(&header[8..], *little_endian) as usize;
+                    packet.clear();
+                    if !read_exact_or_eof(&mut self.reader, packet, len)? {
+                        return Ok(None);
+                    }
+                    let nsecs = if *nanos {
+                        fraction
+                    } else {
+                        fraction.saturating_mul(1000)
+                    };
+                    return Ok(Some((Time { secs, nsecs }, *link_type)));
+                }
+                Format::Pcapng {
+                    little_endian,
+                    interfaces,
+                } => {
+                    let mut header = [0; 8];
+                    if !read_or_eof(&mut self.reader, &mut header)? {
+                        return Ok(None);
+                    }
+                    let block_type = read_u32(&header, *little_endian);
+                    if block_type == PCAPNG_SECTION {
+                        // a new section, which may change the byte order
+                        let mut order = [0; 4];
+                        if !read_or_eof(&mut self.reader, &mut order)? {
+                            return Ok(None);
+                        }
+                        *little_endian = u32::from_le_bytes(order) == PCAPNG_BYTE_ORDER;
+                        interfaces.clear();
+                        let len = read_u32(&header[4..], *little_endian) as usize;
+                        skip(&mut self.reader, len.saturating_sub(12))?;
+                        continue;
+                    }
+                    let len = read_u32(&header[4..], *little_endian) as usize;
+                    let mut body = Vec::new();
+                    if len < 12 || !read_exact_or_eof(&mut self.reader, &mut body, len - 8)? {
+                        return Ok(None);
+                    }
+                    let body = &body[..body.len() - 4];
+                    match block_type {
+                        PCAPNG_INTERFACE if body.len() >= 8 => {
+                            let link_type = read_u16(body, *little_endian);
+                            let resolution = interface_resolution(&body[8..], *little_endian);
+                            interfaces.push((link_type, resolution));
+                        }
+                        PCAPNG_ENHANCED_PACKET if body.len() >= 20 => {
+                            let interface = read_u32(body, *little_endian) as usize;
+                            let Some(&(link_type, resolution)) = interfaces.get(interface) else {
+                                continue;
+                            };
+                            let ticks = (u64::from(read_u32(&body[4..], *little_endian)) << 32)
+                                | u64::from(read_u32(&body[8..], *little_endian));
+                            let captured = read_u32(&body[12..], *little_endian) as usize;
+                            let Some(data) = body[20..].get(..captured) else {
+                                continue;
+                            };
+                            packet.clear();
+                            packet.extend_from_slice(data);
+                            let nanos =
+                                (u128::from(ticks) * 1_000_000_000 / u128::from(resolution)) as u64;
+                            let time = Time {
+                                secs: (nanos / 1_000_000_000) as u32,
+                                nsecs: (nanos % 1_000_000_000) as u32,
+                            };
+                            return Ok(Some((time, link_type)));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles a TCP segment, returning how many messages it completed.
+    fn handle<F>(
+        &mut self,
+        segment: TcpSegment,
+        time: Time,
+        on_message: &mut F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(&ConnectionData, Time, &[u8]) -> Result<(), Error>,
+    {
+        let key = (segment.source, segment.destination);
+        if segment.flags & TCP_SYN != 0 {
+            // a new connection, possibly reusing the ports of an old one
+            self.streams.insert(
+                key,
+                Stream {
+                    server: segment.flags & TCP_ACK != 0,
+                    next_seq: segment.seq.wrapping_add(1),
+                    buf: Vec::new(),
+                    pending: Vec::new(),
+                    pending_len: 0,
+                    state: StreamState::Header,
+                    header: None,
+                },
+            );
+            return Ok(0);
+        }
+        // streams whose handshake wasn't captured are ignored
+        let Some(stream) = self.streams.get_mut(&key) else {
+            return Ok(0);
+        };
+        let mut count = 0;
+        if !segment.payload.is_empty() && !matches!(stream.state, StreamState::Done) {
+            let mut seq = segment.seq;
+            let mut payload = segment.payload;
+            // retransmitted bytes were already read
+            let behind = stream.next_seq.wrapping_sub(seq) as i32;
+            if behind > 0 {
+                payload = payload.get(behind as usize..).unwrap_or_default();
+                seq = stream.next_seq;
+            }
+            if seq != stream.next_seq {
+                stream.pending_len += payload.len();
+                stream.pending.push((seq, payload.to_vec()));
+            } else if !payload.is_empty() {
+                stream.buf.extend_from_slice(payload);
+                stream.next_seq = stream.next_seq.wrapping_add(payload.len() as u32);
+                while let Some(i) = stream
+                    .pending
+                    .iter()
+                    .position(|(seq, _)| stream.next_seq.wrapping_sub(*seq) as i32 >= 0)
+                {
+                    let (seq, data) = stream.pending.swap_remove(i);
+                    stream.pending_len -= data.len();
+                    let behind = stream.next_seq.wrapping_sub(seq) as usize;
+                    if let Some(data) = data.get(behind..) {
+                        stream.buf.extend_from_slice(data);
+                        stream.next_seq = stream.next_seq.wrapping_add(data.len() as u32);
+                    }
+                }
+            }
+            count = self.read_stream(key, time, on_message)?;
+        }
+
+        let Some(stream) = self.streams.get_mut(&key) else {
+            return Ok(count);
+        };
+        if stream.pending_len > MAX_PENDING_LEN {
+            if let StreamState::Messages(index) = stream.state {
+                self.warnings
+                    .push(missing_packets(&self.connections[index]));
+            }
+            stream.state = StreamState::Done;
+        }
+        if matches!(stream.state, StreamState::Done) {
+            stream.buf = Vec::new();
+            stream.pending = Vec::new();
+            stream.pending_len = 0;
+        }
+        if segment.flags & (TCP_FIN | TCP_RST) != 0 && !stream.server {
+            // the publisher's stream still needs the subscriber's header until it has its own
+            let reverse = (key.1, key.0);
+            let needed = self.streams.get(&reverse).map_or(false, |reverse| {
+                matches!(reverse.state, StreamState::Header)
+            });
+            if !needed {
+                self.streams.remove(&key);
+            }
+        } else if segment.flags & (TCP_FIN | TCP_RST) != 0 {
+            self.streams.remove(&key);
+        }
+        Ok(count)
+    }
+
+    /// Reads the connection header and messages buffered in a stream, returning how many messages were read.
+    fn read_stream<F>(
+        &mut self,
+        key: StreamKey,
+        time: Time,
+        on_message: &mut F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(&ConnectionData, Time, &[u8]) -> Result<(), Error>,
+    {
+        let mut count = 0;
+        let mut consumed = 0;
+        loop {
+            let stream = self.streams.get_mut(&key).expect("stream being read");
+            let Some(len) = stream.buf.get(consumed..consumed + 4) else {
+                break;
+            };
+            let len = u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize;
+            if matches!(stream.state, StreamState::Header) && len > MAX_HEADER_LEN {
+                stream.state = StreamState::Done;
+                break;
+            }
+            let Some(record) = stream.buf.get(consumed + 4..consumed + 4 + len) else {
+                break;
+            };
+            consumed += 4 + len;
+            match stream.state {
+                StreamState::Header => {
+                    let header = parse_header(record);
+                    stream.state = StreamState::Done;
+                    if !stream.server {
+                        stream.header = header;
+                    } else if let Some(index) =
+                        header.and_then(|header| self.add_connection(key, header))
+                    {
+                        self.streams.get_mut(&key).expect("stream being read").state =
+                            StreamState::Messages(index);
+                    }
+                }
+                StreamState::Messages(index) => {
+                    on_message(&self.connections[index], time, record)?;
+                    count += 1;
+                }
+                StreamState::Done => break,
+            }
+        }
+        if let Some(stream) = self.streams.get_mut(&key) {
+            stream.buf.drain(..consumed.min(stream.buf.len()));
+        }
+        Ok(count)
+    }
+
+    /// Adds the connection a publisher's header describes, or returns `None` if it isn't a topic subscription.
+    fn add_connection(&mut self, key: StreamKey, header: HeaderFields) -> Option<usize> {
+        let field = |header: &HeaderFields, name: &str| {
+            header
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| String::from_utf8_lossy(value).into_owned())
+        };
+        let subscriber = self
+            .streams
+            .get(&(key.1, key.0))
+            .and_then(|stream| stream.header.as_ref());
+        if subscriber.map_or(false, |subscriber| field(subscriber, "service").is_some()) {
+            return None;
+        }
+        let topic = field(&header, "topic")
+            .or_else(|| subscriber.and_then(|subscriber| field(subscriber, "topic")))?;
+        if let Some(error) = field(&header, "error") {
+            self.warnings.push(PcapWarning::Refused { topic, error });
+            return None;
+        }
+        let connection = ConnectionData {
+            connection_id: self.connections.len() as u32,
+            topic,
+            data_type: field(&header, "type")?,
+            md5sum: field(&header, "md5sum")?,
+            message_definition: field(&header, "message_definition")?,
+            caller_id: field(&header, "callerid"),
+            latching: field(&header, "latching").map_or(false, |latching| latching == "1"),
+            header_fields: header,
+        };
+        self.connections.push(connection);
+        Some(self.connections.len() - 1)
+    }
+}
+
+fn missing_packets(connection: &ConnectionData) -> PcapWarning {
+    PcapWarning::MissingPackets {
+        topic: connection.topic.clone(),
+        caller_id: connection.caller_id.clone().unwrap_or_default(),
+    }
+}
+
+/// Parses the `name=value` fields of a connection header, or returns `None` if it isn't one.
+fn parse_header(mut record: &[u8]) -> Option<HeaderFields> {
+    let mut fields = Vec::new();
+    while !record.is_empty() {
+        let len = u32::from_le_bytes(record.get(..4)?.try_into().expect("4 bytes")) as usize;
+        let field = record.get(4..4 + len)?;
+        let separator = field.iter().position(|&b| b == b'=')?;
+        let name = std::str::from_utf8(&field[..separator]).ok()?;
+        fields.push((name.to_string(), field[separator + 1..].to_vec()));
+        record = &record[4 + len..];
+    }
+    (!fields.is_empty()).then_some(fields)
+}
+
+/// Returns the timestamp ticks per second of a pcapng interface from its options, microseconds by default.
+fn interface_resolution(mut options: &[u8], little_endian: bool) -> u64 {
+    while options.len() >= 4 {
+        let code = read_u16(options, little_endian);
+        let len = read_u16(&options[2..], little_endian) as usize;
+        let Some(value) = options.get(4..4 + len) else {
+            break;
+        };
+        // if_tsresol, a negative power of 10, or of 2 with the high bit set
+        if code == 9 && len == 1 {
+            let exponent = u32::from(value[0] & 0x7f);
+            let resolution = match value[0] & 0x80 {
+                0 => 10u64.checked_pow(exponent),
+                _ => 2u64.checked_pow(exponent),
+            };
+            return resolution.unwrap_or(1_000_000).max(1);
+        }
+        if code == 0 {
+            break;
+        }
+        options = options.get(4 + (len + 3) / 4 * 4..).unwrap_or_default();
+    }
+    1_000_000
+}
+
+struct TcpSegment<'a> {
+    source: SocketAddr,
+    destination: SocketAddr,
+    seq: u32,
+    flags: u8,
+    payload: &'a [u8],
+}
+
+/// Returns the TCP segment in a packet, or `None` for other packets, including fragmented ones.
+fn tcp_segment(link_type: u16, packet: &[u8]) -> Option<TcpSegment<'_>> {
+    let ip = match link_type {
+        LINKTYPE_NULL => packet.get(4..)?,
+        LINKTYPE_RAW => packet,
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = u16::from_be_bytes(packet.get(12..14)?.try_into().ok()?);
+            let mut at = 14;
+            // VLAN tags
+            while ethertype == 0x8100 || ethertype == 0x88a8 {
+                ethertype = u16::from_be_bytes(packet.get(at + 2..at + 4)?.try_into().ok()?);
+                at += 4;
+            }
+            packet.get(at..)?
+        }
+        LINKTYPE_LINUX_SLL => packet.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => packet.get(20..)?,
+        _ => return None,
+    };
+    let (source, destination, tcp) = match ip.first()? >> 4 {
+        4 => {
+            let header_len = usize::from(ip[0] & 0x0f) * 4;
+            let total_len = usize::from(u16::from_be_bytes(ip.get(2..4)?.try_into().ok()?));
+            let fragment = u16::from_be_bytes(ip.get(6..8)?.try_into().ok()?);
+            if fragment & 0x3fff != 0 || *ip.get(9)? != 6 {
+                return None;
+            }
+            let source: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let destination: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            (
+                IpAddr::from(Ipv4Addr::from(source)),
+                IpAddr::from(Ipv4Addr::from(destination)),
+                // the capture may be cut off, or padded past the end of the packet
+                ip.get(header_len..total_len.min(ip.len()))?,
+            )
+        }
+        6 => {
+            if *ip.get(6)? != 6 {
+                return None;
+            }
+            let payload_len = usize::from(u16::from_be_bytes(ip.get(4..6)?.try_into().ok()?));
+            let source: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let destination: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            (
+                IpAddr::from(Ipv6Addr::from(source)),
+                IpAddr::from(Ipv6Addr::from(destination)),
+                ip.get(40..(40 + payload_len).min(ip.len()))?,
+            )
+        }
+        _ => return None,
+    };
+    let header_len = usize::from(tcp.get(12)? >> 4) * 4;
+    Some(TcpSegment {
+        source: SocketAddr::new(source, u16::from_be_bytes(tcp.get(0..2)?.try_into().ok()?)),
+        destination: SocketAddr::new(
+            destination,
+            u16::from_be_bytes(tcp.get(2..4)?.try_into().ok()?),
+        ),
+        seq: u32::from_be_bytes(tcp.get(4..8)?.try_into().ok()?),
+        flags: *tcp.get(13)?,
+        payload: tcp.get(header_len..)?,
+    })
+}
+
+fn read_u16(bytes: &[u8], little_endian: bool) -> u16 {
+    let bytes = bytes[..2].try_into().expect("2 bytes");
+    match little_endian {
+        true => u16::from_le_bytes(bytes),
+        false => u16::from_be_bytes(bytes),
+    }
+}
+
+fn read_u32(bytes: &[u8], little_endian: bool) -> u32 {
+    let bytes = bytes[..4].try_into().expect("4 bytes");
+    match little_endian {
+        true => u32::from_le_bytes(bytes),
+        false => u32::from_be_bytes(bytes),
+    }
+}
+
+/// Fills `buf`, returning `false` if the reader ended first.
+fn read_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool, io::Error> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads `len` bytes into the empty `buf`, returning `false` if the reader ended first.
+fn read_exact_or_eof(
+    reader: &mut impl Read,
+    buf: &mut Vec<u8>,
+    len: usize,
+) -> Result<bool, io::Error> {
+    reader.take(len as u64).read_to_end(buf)?;
+    Ok(buf.len() == len)
+}
+
+fn skip(reader: &mut impl Read, len: usize) -> Result<(), io::Error> {
+    io::copy(&mut reader.take(len as u64), &mut io::sink())?;
+    Ok(())
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid capture: {reason}"),
+    )
+}
@@ -0,0 +1,188 @@
+//! Extending the `frost` CLI with subcommands shipped as separate executables.
+//!
+//! Running `frost NAME ARGS...`, where `NAME` is not a built-in command, runs the first executable named
+//! `frost-plugin-NAME` on the `PATH` with `ARGS...`, and exits with its exit code. `frost plugins` lists the plugins found.
+//! A plugin can be written in any language; when asked for its description with [DESCRIBE_FLAG], it should print a
+//! one-line description and exit.
+//!
+//! Rust plugins implement [Plugin] and hand it to [main], which takes care of the flag, loading the bag, and errors.
+//!
+//! Example
+//! ```rust,no_run
+//! // built as a binary named `frost-plugin-count`, run with `frost count robot.bag`
+//! use std::io::Write;
+//!
+//! use frost::errors::Error;
+//! use frost::plugins::Plugin;
+//! use frost::query::Query;
+//! use frost::DecompressedBag;
+//!
+//! struct Count;
+//!
+//! impl Plugin for Count {
+//!     fn description(&self) -> &str {
+//!         "Print the number of messages in a bag"
+//!     }
+//!
+//!     fn run(&self, bag: &DecompressedBag, _args: &[String], out: &mut dyn Write) -> Result<(), Error> {
+//!         let count = bag.read_messages(&Query::all())?.count();
+//!         writeln!(out, "{count}")?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! fn main() {
+//!     frost::plugins::main(Count)
+//! }
+//! ```
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use crate::errors::Error;
+use crate::DecompressedBag;
+
+/// The file name prefix of plugin executables.
+pub const PREFIX: &str = "frost-plugin-";
+/// The only argument passed to a plugin when asking for its description.
+pub const DESCRIBE_FLAG: &str = "--frost-plugin-describe";
+
+/// An exporter or analyzer run on a bag as a `frost` subcommand, see the [module docs](self).
+pub trait Plugin {
+    /// A one-line description shown by `frost plugins`.
+    fn description(&self) -> &str;
+
+    /// Runs the plugin on a bag with the arguments that followed the bag's path.
+    fn run(&self, bag: &DecompressedBag, args: &[String], out: &mut dyn Write)
+        -> Result<(), Error>;
+}
+
+/// Runs `plugin` as the `main` of a plugin executable invoked as `frost-plugin-NAME FILE [ARGS...]`.
+///
+/// Errors are printed to stderr and exit with 1.
+pub fn main<P: Plugin>(plugin: P) -> ! {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let code = match args.first().map(String::as_str) {
+        Some(DESCRIBE_FLAG) => {
+            println!("{}", plugin.description());
+            0
+        }
+        None | Some("-h") | Some("--help") => {
+            eprintln!("{}\n\nusage: FILE [ARGS...]", plugin.description());
+            2
+        }
+        Some(file_path) => match run_plugin(&plugin, file_path, &args[1..]) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("error: {e}");
+                1
+            }
+        },
+    };
+    std::process::exit(code)
+}
+
+fn run_plugin<P: Plugin>(plugin: &P, file_path: &str, args: &[String]) -> Result<(), Error> {
+    let bag = DecompressedBag::from_file(file_path)?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    plugin.run(&bag, args, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// A plugin executable found on disk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExternalPlugin {
+    /// The subcommand name, i.e. the file name without [PREFIX] (and `.exe` on Windows).
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl ExternalPlugin {
+    /// Asks the plugin for its description, returning `None` if it fails or prints nothing.
+    pub fn description(&self) -> Option<String> {
+        let output = Command::new(&self.path).arg(DESCRIBE_FLAG).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let line = stdout.lines().next()?.trim();
+        (!line.is_empty()).then(|| line.to_string())
+    }
+
+    /// Runs the plugin with `args`, inheriting stdin, stdout, and stderr, and waits for it to exit.
+    pub fn run<I, S>(&self, args: I) -> io::Result<ExitStatus>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        Command::new(&self.path)
+            .args(args.into_iter().map(Into::into))
+            .status()
+    }
+}
+
+/// Finds the plugins on the `PATH`, see [discover_in].
+pub fn discover() -> Vec<ExternalPlugin> {
+    match std::env::var_os("PATH") {
+        Some(path) => discover_in(std::env::split_paths(&path)),
+        None => Vec::new(),
+    }
+}
+
+/// Finds the plugin executables in `dirs`, sorted by name. Like a shell, the first directory with a plugin wins.
+pub fn discover_in<I, P>(dirs: I) -> Vec<ExternalPlugin>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut plugins = BTreeMap::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = plugin_name(&path) else {
+                continue;
+            };
+            if !plugins.contains_key(&name) && is_executable(&path) {
+                plugins.insert(name.clone(), ExternalPlugin { name, path });
+            }
+        }
+    }
+    plugins.into_values().collect()
+}
+
+/// Returns the plugin named `name` on the `PATH`, if any.
+pub fn find(name: &str) -> Option<ExternalPlugin> {
+    discover().into_iter().find(|plugin| plugin.name == name)
+}
+
+fn plugin_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let name = file_name.strip_prefix(PREFIX)?;
+    let name = if cfg!(windows) {
+        name.strip_suffix(".exe")?
+    } else {
+        name
+    };
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
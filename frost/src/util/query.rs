@@ -1,16 +1,78 @@
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::iter::FusedIterator;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::cancel::CancellationToken;
+use crate::dynamic::{DecodeLimits, Value};
 use crate::errors::Error;
-use crate::time::Time;
-use crate::{ConnectionID, DecompressedBag, IndexData, MessageDataHeader};
+use crate::time::{self, Time};
+use crate::{
+    BagMetadata, ChunkHeaderLoc, ConnectionID, DecompressedBag, IndexData, MessageDataHeader,
+};
 
+use super::metrics::{self, QueryStats, Recorder};
 use super::{msgs::MessageView, parsing::parse_le_u32_at};
 
+/// A point in time used to filter a [Query].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryTime {
+    /// An absolute bag time.
+    Absolute(Time),
+    /// An offset from the start time of the bag being queried.
+    FromStart(Duration),
+}
+
+impl QueryTime {
+    fn resolve(&self, bag_start: Time) -> Time {
+        match self {
+            QueryTime::Absolute(time) => *time,
+            QueryTime::FromStart(offset) => bag_start.checked_add(*offset).unwrap_or(time::MAX),
+        }
+    }
+}
+
+impl From<Time> for QueryTime {
+    fn from(time: Time) -> Self {
+        QueryTime::Absolute(time)
+    }
+}
+
+impl From<Duration> for QueryTime {
+    fn from(offset: Duration) -> Self {
+        QueryTime::FromStart(offset)
+    }
+}
+
+fn resolve_bound(bound: &Bound<QueryTime>, bag_start: Time) -> Bound<Time> {
+    match bound {
+        Bound::Included(time) => Bound::Included(time.resolve(bag_start)),
+        Bound::Excluded(time) => Bound::Excluded(time.resolve(bag_start)),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// A filter on message contents, see [Query::with_predicate].
+type Predicate = Arc<dyn Fn(&MessageView) -> bool + Send + Sync>;
+
+/// Selects which messages are returned by [DecompressedBag::read_messages].
+///
+/// Time filters compare against the time a message was received.
+/// [Query::with_start_time] and [Query::with_end_time] are both inclusive, so consecutive windows built with them
+/// count messages landing exactly on a shared boundary twice.
+/// Use [Query::with_end_exclusive] or a half-open [Query::with_time_range] to partition a bag exactly.
 pub struct Query {
     topics: Option<Vec<String>>,
     types: Option<Vec<String>>,
-    start_time: Option<Time>,
-    end_time: Option<Time>,
+    start_time: Bound<QueryTime>,
+    end_time: Bound<QueryTime>,
+    fields: Option<Vec<String>>,
+    limits: DecodeLimits,
+    predicates: Vec<Predicate>,
+    cancellation: Option<CancellationToken>,
+    stats: Option<QueryStats>,
 }
 
 impl Query {
@@ -19,8 +81,13 @@ impl Query {
         Query {
             topics: None,
             types: None,
-            start_time: None,
-            end_time: None,
+            start_time: Bound::Unbounded,
+            end_time: Bound::Unbounded,
+            fields: None,
+            limits: DecodeLimits::default(),
+            predicates: Vec::new(),
+            cancellation: None,
+            stats: None,
         }
     }
 
@@ -49,34 +116,233 @@ impl Query {
         self
     }
 
-    /// Query a bag with messages filtered after a start time.
+    /// Only decode the fields at these `.` separated paths with [MessageView::decode], skipping over the others,
+    /// see [Schema::project](crate::dynamic::Schema::project). Messages are still matched and returned whole by
+    /// every other method.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::query::Query;
+    /// use frost::DecompressedBag;
+    ///
+    /// let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    ///
+    /// // only the array's layout, without its data
+    /// let query = Query::new().with_topics(["/array"]).with_fields(["layout.data_offset"]);
+    /// for msg_view in bag.read_messages(&query).unwrap() {
+    ///     let value = msg_view.decode().unwrap();
+    ///     assert!(value.get("data").is_none());
+    ///     assert!(value.get_path("layout.data_offset").is_some());
+    /// }
+    /// ```
+    pub fn with_fields<S, I>(mut self, fields: I) -> Self
+    where
+        S: AsRef<str>,
+        I: IntoIterator<Item = S>,
+    {
+        self.fields = Some(fields.into_iter().map(|s| s.as_ref().into()).collect());
+        self
+    }
+
+    /// Fail [MessageView::decode] and [MessageView::instantiate] on messages with lengths over these limits, rather
+    /// than trusting whatever lengths a corrupt or hostile bag claims.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::dynamic::DecodeLimits;
+    /// use frost::query::Query;
+    /// use frost::DecompressedBag;
+    ///
+    /// let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    ///
+    /// let query = Query::new()
+    ///     .with_topics(["/chatter"])
+    ///     .with_decode_limits(DecodeLimits::new().with_max_string_len(4));
+    /// for msg_view in bag.read_messages(&query).unwrap() {
+    ///     // "foo_0" and onwards are over the limit
+    ///     assert!(msg_view.decode().is_err());
+    /// }
+    /// ```
+    pub fn with_decode_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Only match messages for which `predicate` returns true, filtering on their contents. Predicates added
+    /// by repeated calls must all match.
+    ///
+    /// Predicates run on the messages matching the rest of the query as they are read, so a query with predicates is
+    /// read with [DecompressedBag::filter_messages], as [BagIter] knows how many messages it will yield up front.
+    /// Everything reading messages through a query, like
+    /// [BagWriter::copy_messages](crate::writer::BagWriter::copy_messages), is filtered too, but counts and estimates
+    /// computed from the index alone, like [BagMetadata::count_messages], ignore predicates.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::query::Query;
+    /// use frost::DecompressedBag;
+    ///
+    /// let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    ///
+    /// let query = Query::new().with_topics(["/chatter"]).with_predicate(|msg_view| {
+    ///     let value = msg_view.decode().unwrap();
+    ///     value.get("data").and_then(|data| data.as_str()) == Some("foo_7")
+    /// });
+    /// assert_eq!(bag.filter_messages(&query).unwrap().count(), 1);
+    /// ```
+    pub fn with_predicate<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&MessageView) -> bool + Send + Sync + 'static,
+    {
+        self.predicates.push(Arc::new(predicate));
+        self
+    }
+
+    /// Only match messages with a field at the `.` separated `path`, like `header.frame_id` or `status.status`, for
+    /// which `predicate` returns true, see [Query::with_predicate]. Messages which fail to decode don't match.
+    ///
+    /// Example
+    /// ```rust
+    /// use frost::dynamic::Value;
+    /// use frost::query::Query;
+    /// use frost::DecompressedBag;
+    ///
+    /// let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    ///
+    /// // times in the first ten seconds
+    /// let query = Query::new().with_field("data", |value| {
+    ///     matches!(value, Value::Time(time) if time.secs < 10)
+    /// });
+    /// assert_eq!(bag.filter_messages(&query).unwrap().count(), 10);
+    /// ```
+    pub fn with_field<F>(self, path: &str, predicate: F) -> Self
+    where
+        F: Fn(&Value) -> bool + Send + Sync + 'static,
+    {
+        let path = path.to_string();
+        self.with_predicate(move |msg_view| {
+            msg_view
+                .decode()
+                .ok()
+                .and_then(|value| value.get_path(&path).map(&predicate))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Returns true if the query filters on message contents, with [Query::with_predicate] or [Query::with_field].
+    pub fn has_predicates(&self) -> bool {
+        !self.predicates.is_empty()
+    }
+
+    /// Lets everything reading messages through the query be stopped with `token`, see [crate::cancel].
+    ///
+    /// Once cancelled, [BagIter]s stop yielding messages (see [BagIter::was_cancelled]), and loading, copying and merging fail with
+    /// [ErrorKind::Cancelled](crate::errors::ErrorKind::Cancelled).
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Returns true if the query was given a [CancellationToken] which has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map_or(false, CancellationToken::is_cancelled)
+    }
+
+    /// Fails with [ErrorKind::Cancelled](crate::errors::ErrorKind::Cancelled) if the query has been cancelled.
+    pub(crate) fn check_cancelled(&self) -> Result<(), Error> {
+        self.cancellation
+            .as_ref()
+            .map_or(Ok(()), CancellationToken::check)
+    }
+
+    pub(crate) fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// Collects statistics about loading a bag and reading its messages through the query into `stats`, see
+    /// [QueryStats].
+    ///
+    /// Without it, nothing is collected, so iterating costs nothing extra.
+    pub fn with_stats(mut self, stats: QueryStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    pub(crate) fn stats(&self) -> Option<&QueryStats> {
+        self.stats.as_ref()
+    }
+
+    /// Query a bag with messages filtered after a start time (inclusive).
     pub fn with_start_time(mut self, start_time: Time) -> Self {
-        self.start_time = Some(start_time);
+        self.start_time = Bound::Included(start_time.into());
         self
     }
 
-    /// Query a bag with messages filtered before an end time.
+    /// Query a bag with messages filtered before an end time (inclusive).
     pub fn with_end_time(mut self, end_time: Time) -> Self {
-        self.end_time = Some(end_time);
+        self.end_time = Bound::Included(end_time.into());
         self
     }
-}
 
-impl Default for Query {
-    fn default() -> Self {
-        Self::new()
+    /// Query a bag with messages filtered before an end time (exclusive).
+    pub fn with_end_exclusive(mut self, end_time: Time) -> Self {
+        self.end_time = Bound::Excluded(end_time.into());
+        self
     }
-}
 
-pub struct BagIter<'a> {
-    bag: &'a DecompressedBag,
-    index_data: Vec<IndexData>,
-    current_index: usize,
-}
-impl<'a> BagIter<'a> {
-    pub(crate) fn new(bag: &'a DecompressedBag, query: &Query) -> Result<Self, Error> {
-        let topic_to_connection_ids = bag.metadata.topic_to_connection_ids();
-        let ids_from_topics: HashSet<ConnectionID> = match &query.topics {
+    /// Query a bag with messages whose time falls in a range, replacing any previous start and end times.
+    ///
+    /// The range's bounds are honored exactly, so `start..end` includes `start` but excludes `end`,
+    /// while `start..=end` includes both.
+    /// Bounds can either be absolute [Time]s or [Duration] offsets from the start of the bag.
+    ///
+    /// Example
+    /// ```rust
+    /// use std::time::Duration;
+    /// use frost::query::Query;
+    /// use frost::time::Time;
+    ///
+    /// let start = Time { secs: 1665952859, nsecs: 0 };
+    /// let end = Time { secs: 1665952869, nsecs: 0 };
+    /// let query = Query::new().with_time_range(start..end);
+    ///
+    /// // the first ten seconds of the bag, including a message at exactly ten seconds
+    /// let query = Query::new().with_time_range(..=Duration::from_secs(10));
+    /// ```
+    pub fn with_time_range<T, R>(mut self, range: R) -> Self
+    where
+        T: Copy + Into<QueryTime>,
+        R: RangeBounds<T>,
+    {
+        fn convert<T: Copy + Into<QueryTime>>(bound: Bound<&T>) -> Bound<QueryTime> {
+            match bound {
+                Bound::Included(time) => Bound::Included((*time).into()),
+                Bound::Excluded(time) => Bound::Excluded((*time).into()),
+                Bound::Unbounded => Bound::Unbounded,
+            }
+        }
+        self.start_time = convert(range.start_bound());
+        self.end_time = convert(range.end_bound());
+        self
+    }
+
+    /// Returns the index entries of every message the query matches, in no particular order.
+    ///
+    /// Only the bag's index is read, so this is cheap compared to reading the messages themselves.
+    pub(crate) fn matching_index_data<'a>(
+        &self,
+        metadata: &'a BagMetadata,
+    ) -> impl Iterator<Item = &'a IndexData> {
+        self.matching_streams(metadata).into_iter().flatten()
+    }
+
+    /// Returns the index entries the query matches on each matching connection, sorted by receive time like the
+    /// connections' indexes, which are cut down to the query's time range by binary search.
+    fn matching_streams<'a>(&self, metadata: &'a BagMetadata) -> Vec<&'a [IndexData]> {
+        let topic_to_connection_ids = metadata.topic_to_connection_ids();
+        let ids_from_topics: HashSet<ConnectionID> = match &self.topics {
             Some(topics) => topics
                 .iter()
                 .flat_map(|topic| topic_to_connection_ids.get(topic).cloned())
@@ -88,8 +354,8 @@ impl<'a> BagIter<'a> {
                 .cloned()
                 .collect(),
         };
-        let types_to_connection_ids = bag.metadata.type_to_connection_ids();
-        let ids_from_types: HashSet<ConnectionID> = match &query.types {
+        let types_to_connection_ids = metadata.type_to_connection_ids();
+        let ids_from_types: HashSet<ConnectionID> = match &self.types {
             Some(types) => types
                 .iter()
                 .flat_map(|ty| types_to_connection_ids.get(ty).cloned())
@@ -101,34 +367,171 @@ impl<'a> BagIter<'a> {
                 .cloned()
                 .collect(),
         };
-        let ids: HashSet<ConnectionID> = ids_from_topics
+        let mut ids: Vec<ConnectionID> = ids_from_topics
             .intersection(&ids_from_types)
             .cloned()
             .collect();
-        let mut index_data: Vec<IndexData> = ids
-            .iter()
-            .flat_map(|id| bag.metadata.index_data.get(id).unwrap().clone())
-            .filter(|data| {
-                if let Some(start_time) = query.start_time {
-                    if data.time < start_time {
-                        return false;
-                    }
-                }
-                if let Some(end_time) = query.end_time {
-                    if data.time > end_time {
-                        return false;
-                    }
-                }
-                true
+        ids.sort_unstable();
+        let bag_start = metadata.start_time().unwrap_or(time::ZERO);
+        let start = resolve_bound(&self.start_time, bag_start);
+        let end = resolve_bound(&self.end_time, bag_start);
+        ids.into_iter()
+            .filter_map(|id| metadata.index_data.get(&id))
+            .map(|index| {
+                let first = match start {
+                    Bound::Included(start) => index.partition_point(|data| data.time < start),
+                    Bound::Excluded(start) => index.partition_point(|data| data.time <= start),
+                    Bound::Unbounded => 0,
+                };
+                let last = match end {
+                    Bound::Included(end) => index.partition_point(|data| data.time <= end),
+                    Bound::Excluded(end) => index.partition_point(|data| data.time < end),
+                    Bound::Unbounded => index.len(),
+                };
+                &index[first..last.max(first)]
             })
+            .filter(|stream| !stream.is_empty())
+            .collect()
+    }
+}
+
+/// Merges the time sorted index entries of several connections into one sequence sorted by receive time, holding
+/// only the next entry of each connection, so memory grows with the number of connections rather than of messages.
+/// Entries received at the same time come in the order they were written in.
+pub(crate) struct IndexMerge<'a> {
+    streams: Vec<&'a [IndexData]>,
+    /// the next entry of each stream which has any left, earliest first, and the stream it is from
+    heads: BinaryHeap<Reverse<(Time, ChunkHeaderLoc, u32, usize)>>,
+    remaining: usize,
+}
+
+impl<'a> IndexMerge<'a> {
+    pub(crate) fn new(streams: Vec<&'a [IndexData]>) -> Self {
+        let heads = streams
+            .iter()
+            .enumerate()
+            .filter_map(|(i, stream)| Some(Reverse(merge_key(stream.first()?, i))))
             .collect();
-        index_data.sort_by(|a, b| a.time.cmp(&b.time));
+        let remaining = streams.iter().map(|stream| stream.len()).sum();
+        IndexMerge {
+            streams,
+            heads,
+            remaining,
+        }
+    }
+}
+
+fn merge_key(data: &IndexData, stream: usize) -> (Time, ChunkHeaderLoc, u32, usize) {
+    (data.time, data.chunk_header_pos, data.offset, stream)
+}
+
+impl<'a> Iterator for IndexMerge<'a> {
+    type Item = &'a IndexData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((.., i)) = self.heads.pop()?;
+        let (data, rest) = self.streams[i].split_first()?;
+        self.streams[i] = rest;
+        if let Some(next) = rest.first() {
+            self.heads.push(Reverse(merge_key(next, i)));
+        }
+        self.remaining -= 1;
+        Some(data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for IndexMerge<'a> {}
+
+/// The expected cost of running a [Query], from [DecompressedBag::estimate] or [BagMetadata::estimate].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryEstimate {
+    /// The number of messages the query matches.
+    pub message_count: usize,
+    /// The number of chunks holding at least one matching message.
+    pub chunk_count: usize,
+    /// The bytes of those chunks as stored in the bag, which have to be read.
+    pub compressed_bytes: u64,
+    /// The bytes of those chunks once decompressed.
+    pub uncompressed_bytes: u64,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        Ok(BagIter {
+/// Iterates over a bag's messages in receive time order, see [DecompressedBag::read_messages].
+///
+/// The matching messages of each connection are merged from their indexes as they are read.
+pub struct BagIter<'a> {
+    bag: &'a DecompressedBag,
+    entries: IndexMerge<'a>,
+    recorder: Option<Arc<dyn Recorder>>,
+    fields: Option<Arc<[String]>>,
+    limits: DecodeLimits,
+    cancellation: Option<CancellationToken>,
+    stats: Option<QueryStats>,
+}
+impl<'a> BagIter<'a> {
+    pub(crate) fn new(bag: &'a DecompressedBag, query: &Query) -> Result<Self, Error> {
+        let started = Instant::now();
+        let entries = IndexMerge::new(query.matching_streams(&bag.metadata));
+        let iter = BagIter {
             bag,
-            index_data,
-            current_index: 0,
-        })
+            entries,
+            recorder: metrics::recorder(),
+            fields: query.fields.as_deref().map(Arc::from),
+            limits: query.limits,
+            cancellation: query.cancellation.clone(),
+            stats: query.stats.clone(),
+        };
+        if let Some(stats) = &iter.stats {
+            stats.add_elapsed(started.elapsed());
+        }
+        Ok(iter)
+    }
+
+    /// The statistics of the query the iterator was created with, see [Query::with_stats].
+    pub fn stats(&self) -> Option<&QueryStats> {
+        self.stats.as_ref()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map_or(false, CancellationToken::is_cancelled)
+    }
+
+    /// Returns true if the query was cancelled before every message was read, so the messages read so far are only
+    /// part of those matching it, see [Query::with_cancellation].
+    pub fn was_cancelled(&self) -> bool {
+        self.is_cancelled() && self.entries.len() > 0
+    }
+
+    /// Reads the next message without counting it as yielded.
+    fn read_next(&mut self) -> Option<MessageView<'a>> {
+        if self.is_cancelled() {
+            return None;
+        }
+        let data = self.entries.next()?;
+        let mut msg_view = message_view(self.bag, data, self.fields.clone())?;
+        msg_view.limits = self.limits;
+        Some(msg_view)
+    }
+
+    /// Records `msg_view` being yielded, `started` being when reading it began if the query has stats.
+    fn yielded(&self, msg_view: &MessageView, started: Option<Instant>) {
+        if let Some(recorder) = &self.recorder {
+            recorder.message_yielded(msg_view.topic);
+        }
+        if let (Some(stats), Some(started)) = (&self.stats, started) {
+            stats.message_yielded(msg_view.chunk_loc, started.elapsed());
+        }
     }
 }
 
@@ -136,80 +539,186 @@ impl<'a> Iterator for BagIter<'a> {
     type Item = MessageView<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_index >= self.index_data.len() {
-            None
-        } else {
-            let data = self.index_data.get(self.current_index)?;
-
-            let topic = &self
-                .bag
-                .metadata
-                .connection_data
-                .get(&data.conn_id)
-                .unwrap()
-                .topic;
-
-            let chunk_bytes = self.bag.chunk_bytes.get(&data.chunk_header_pos)?;
-
-            let mut pos = data.offset;
-
-            let header_len = parse_le_u32_at(chunk_bytes, pos).unwrap() as usize;
-            pos += 4;
-            let header_start = pos;
-            let header_end = header_start + header_len;
-
-            MessageDataHeader::from(&chunk_bytes[header_start..header_end])
-                .expect("Failed to read MessageDataHeader");
-            pos = header_end;
-
-            let data_len = parse_le_u32_at(chunk_bytes, pos).unwrap() as usize;
-            // serde_rosmsg wants the data_len included, so don't pos += 4;
-            let data_start = pos;
-            let data_end = data_start + data_len + 4; // add extra 4 for data_len
-
-            self.current_index += 1;
-
-            Some(MessageView {
-                topic,
-                chunk_loc: data.chunk_header_pos,
-                bag: self.bag,
-                start_index: data_start,
-                end_index: data_end,
-            })
+        let started = self.stats.is_some().then(Instant::now);
+        let msg_view = self.read_next()?;
+        self.yielded(&msg_view, started);
+        Some(msg_view)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len();
+        (remaining, Some(remaining))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // skip over messages without parsing them
+        if n > 0 {
+            self.entries.nth(n - 1);
         }
+        self.next()
     }
 }
 
+/// Once its query is cancelled, a [BagIter] has no messages left, and [BagIter::was_cancelled] tells whether any
+/// were left unread.
+impl<'a> ExactSizeIterator for BagIter<'a> {
+    fn len(&self) -> usize {
+        if self.is_cancelled() {
+            return 0;
+        }
+        self.entries.len()
+    }
+}
+
+/// Once exhausted, a [BagIter] keeps returning `None`.
+impl<'a> FusedIterator for BagIter<'a> {}
+
+/// Iterates over a bag's messages in receive time order, checking the query's predicates as they are read, see
+/// [DecompressedBag::filter_messages].
+///
+/// Unlike a [BagIter], it can't know how many messages it will yield without decoding them, so it isn't an
+/// [ExactSizeIterator].
+pub struct FilteredIter<'a> {
+    messages: BagIter<'a>,
+    predicates: Vec<Predicate>,
+}
+
+impl<'a> FilteredIter<'a> {
+    pub(crate) fn new(bag: &'a DecompressedBag, query: &Query) -> Result<Self, Error> {
+        Ok(FilteredIter {
+            messages: BagIter::new(bag, query)?,
+            predicates: query.predicates.clone(),
+        })
+    }
+
+    /// The statistics of the query the iterator was created with, see [Query::with_stats].
+    pub fn stats(&self) -> Option<&QueryStats> {
+        self.messages.stats()
+    }
+
+    /// Returns true if the query was cancelled before every message was read, see [BagIter::was_cancelled].
+    pub fn was_cancelled(&self) -> bool {
+        self.messages.was_cancelled()
+    }
+}
+
+impl<'a> Iterator for FilteredIter<'a> {
+    type Item = MessageView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let started = self.messages.stats.is_some().then(Instant::now);
+        loop {
+            let mut msg_view = self.messages.read_next()?;
+            // predicates see the whole message, not just the fields the query selected
+            let fields = msg_view.fields.take();
+            let matches = self.predicates.iter().all(|predicate| predicate(&msg_view));
+            msg_view.fields = fields;
+            if matches {
+                self.messages.yielded(&msg_view, started);
+                return Some(msg_view);
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (remaining, upper) = self.messages.size_hint();
+        match self.predicates.is_empty() {
+            true => (remaining, upper),
+            false => (0, upper),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if self.predicates.is_empty() {
+            return self.messages.nth(n);
+        }
+        for _ in 0..n {
+            self.next()?;
+        }
+        self.next()
+    }
+}
+
+/// Once exhausted, a [FilteredIter] keeps returning `None`.
+impl<'a> FusedIterator for FilteredIter<'a> {}
+
+/// Returns a view of the message an index entry points at, or `None` if its chunk isn't loaded.
+pub(crate) fn message_view<'a>(
+    bag: &'a DecompressedBag,
+    data: &IndexData,
+    fields: Option<Arc<[String]>>,
+) -> Option<MessageView<'a>> {
+    let topic = &bag
+        .metadata
+        .connection_data
+        .get(&data.conn_id)
+        .unwrap()
+        .topic;
+
+    let chunk_bytes = bag.chunk_bytes.get(&data.chunk_header_pos)?;
+
+    let mut pos = data.offset as usize;
+
+    let header_len = parse_le_u32_at(chunk_bytes, pos).unwrap() as usize;
+    pos += 4;
+    let header_start = pos;
+    let header_end = header_start + header_len;
+
+    MessageDataHeader::from(&chunk_bytes[header_start..header_end])
+        .expect("Failed to read MessageDataHeader");
+    pos = header_end;
+
+    let data_len = parse_le_u32_at(chunk_bytes, pos).unwrap() as usize;
+    // serde_rosmsg wants the data_len included, so don't pos += 4;
+    let data_start = pos;
+    let data_end = data_start + data_len + 4; // add extra 4 for data_len
+
+    Some(MessageView {
+        topic,
+        time: data.time,
+        conn_id: data.conn_id,
+        chunk_loc: data.chunk_header_pos,
+        bag,
+        start_index: data_start,
+        end_index: data_end,
+        fields,
+        limits: DecodeLimits::default(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Query;
+    use super::{Query, QueryTime};
+    use crate::time::Time;
     use itertools::assert_equal;
     use itertools::sorted;
     use std::collections::{HashMap, HashSet};
+    use std::ops::Bound;
+    use std::time::Duration;
 
     #[test]
     fn test_contruction_with_topics() {
         let query = Query::new().with_topics(["/chatter", "/array"]);
         assert_equal(sorted(query.topics.unwrap()), ["/array", "/chatter"]);
-        assert_eq!(query.start_time, None);
-        assert_eq!(query.end_time, None);
+        assert_eq!(query.start_time, Bound::Unbounded);
+        assert_eq!(query.end_time, Bound::Unbounded);
 
         let query = Query::new().with_topics(["/chatter", "/array"]);
         assert_equal(sorted(query.topics.unwrap()), ["/array", "/chatter"]);
-        assert_eq!(query.start_time, None);
-        assert_eq!(query.end_time, None);
+        assert_eq!(query.start_time, Bound::Unbounded);
+        assert_eq!(query.end_time, Bound::Unbounded);
 
         let topics: HashSet<&str> = HashSet::from_iter(["/chatter", "/array"]);
         let query = Query::new().with_topics(topics);
         assert_equal(sorted(query.topics.unwrap()), ["/array", "/chatter"]);
-        assert_eq!(query.start_time, None);
-        assert_eq!(query.end_time, None);
+        assert_eq!(query.start_time, Bound::Unbounded);
+        assert_eq!(query.end_time, Bound::Unbounded);
 
         let topics: HashMap<&str, u32> = HashMap::from_iter([("/chatter", 0), ("/array", 0)]);
         let query = Query::new().with_topics(topics.keys());
         assert_equal(sorted(query.topics.unwrap()), ["/array", "/chatter"]);
-        assert_eq!(query.start_time, None);
-        assert_eq!(query.end_time, None);
+        assert_eq!(query.start_time, Bound::Unbounded);
+        assert_eq!(query.end_time, Bound::Unbounded);
 
         let topics = vec!["/chatter", "/array"];
         let query = Query::new().with_topics(&topics);
@@ -222,4 +731,47 @@ mod tests {
         let query = Query::new().with_topics(&topics);
         assert_equal(sorted(query.topics.unwrap()), ["/array", "/chatter"]);
     }
+
+    #[test]
+    fn test_construction_with_time_range() {
+        let start = Time { secs: 1, nsecs: 0 };
+        let end = Time { secs: 2, nsecs: 0 };
+
+        let query = Query::new().with_time_range(start..end);
+        assert_eq!(
+            query.start_time,
+            Bound::Included(QueryTime::Absolute(start))
+        );
+        assert_eq!(query.end_time, Bound::Excluded(QueryTime::Absolute(end)));
+
+        let query = Query::new().with_time_range(start..=end);
+        assert_eq!(
+            query.start_time,
+            Bound::Included(QueryTime::Absolute(start))
+        );
+        assert_eq!(query.end_time, Bound::Included(QueryTime::Absolute(end)));
+
+        let query = Query::new().with_start_time(start).with_end_time(end);
+        assert_eq!(
+            query.start_time,
+            Bound::Included(QueryTime::Absolute(start))
+        );
+        assert_eq!(query.end_time, Bound::Included(QueryTime::Absolute(end)));
+
+        let query = Query::new().with_start_time(start).with_end_exclusive(end);
+        assert_eq!(
+            query.start_time,
+            Bound::Included(QueryTime::Absolute(start))
+        );
+        assert_eq!(query.end_time, Bound::Excluded(QueryTime::Absolute(end)));
+
+        let query = Query::new()
+            .with_start_time(start)
+            .with_time_range(..Duration::from_secs(5));
+        assert_eq!(query.start_time, Bound::Unbounded);
+        assert_eq!(
+            query.end_time,
+            Bound::Excluded(QueryTime::FromStart(Duration::from_secs(5)))
+        );
+    }
 }
@@ -0,0 +1,407 @@
+//! Converting ROS 2 messages into ROS 1 messages, for importing MCAP files and rosbag2 bags recorded with ROS 2.
+//! Enabled with the `ros2` feature.
+//!
+//! ROS 2 (`ros2msg`) definitions are translated into ROS 1 definitions:
+//! - types lose their `/msg/` namespace, so `geometry_msgs/msg/Pose` is `geometry_msgs/Pose`
+//! - `builtin_interfaces/Time` and `Duration` fields are ROS 1 `time` and `duration` fields
+//! - `std_msgs/Header` gets back the `seq` field ROS 2 dropped, always 0
+//! - bounded strings and sequences are unbounded, `wstring`s are `string`s, and default values are dropped
+//!
+//! Messages are decoded from CDR with the translated definition and serialized again as ROS 1 messages, so messages
+//! of the common packages (`std_msgs`, `geometry_msgs`, `sensor_msgs`, ...), which are the same in both apart from
+//! the above, get the md5sums of their ROS 1 types and can be instantiated with ROS 1 generated types.
+//!
+//! Example
+//! ```rust
+//! use frost::ros2::Ros2Type;
+//!
+//! let ros2_type = Ros2Type::from_definition("std_msgs/msg/String", "string data").unwrap();
+//! assert_eq!(ros2_type.data_type(), "std_msgs/String");
+//! assert_eq!(ros2_type.md5sum(), "992ce8a1687cec8c8bd883ec73ca41d1");
+//!
+//! // "foo" in little endian CDR: the encapsulation header, then the length including a terminating null
+//! let cdr = b"\x00\x01\x00\x00\x04\x00\x00\x00foo\x00";
+//! assert_eq!(ros2_type.to_ros1(cdr).unwrap(), b"\x03\x00\x00\x00foo");
+//! ```
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::dynamic::{
+    parse_definition_line, Cardinality, DefinitionLine, FieldType, Primitive, Schema, Value,
+    DEFINITION_SEPARATOR,
+};
+use crate::errors::{DecodeError, Error};
+use crate::schemas::{definition_md5sum, find_msg_files};
+use crate::time::Time;
+
+/// The ROS 1 definition of `std_msgs/Header`, which ROS 2 headers are translated to.
+const ROS1_HEADER: &str = "uint32 seq\ntime stamp\nstring frame_id";
+
+/// Returns the ROS 1 name of a ROS 2 message type, like `geometry_msgs/Pose` for `geometry_msgs/msg/Pose`.
+pub fn ros1_type_name(ros2_type: &str) -> String {
+    match ros2_type.split('/').collect::<Vec<_>>().as_slice() {
+        [package, "msg", name] => format!("{package}/{name}"),
+        _ => ros2_type.to_string(),
+    }
+}
+
+/// A ROS 2 message type and its ROS 1 translation, see the [module docs](self).
+#[derive(Clone, Debug)]
+pub struct Ros2Type {
+    data_type: String,
+    md5sum: String,
+    message_definition: String,
+    schema: Schema,
+}
+
+impl Ros2Type {
+    /// Translates the `ros2msg` definition of `ros2_type`, with the definitions of the types it depends on after
+    /// it, separated like ROS 1 connection definitions, as MCAP schemas and rosbag2 bags store them.
+    pub fn from_definition(ros2_type: &str, definition: &str) -> Result<Self, Error> {
+        let data_type = ros1_type_name(ros2_type);
+        let mut sections = Vec::new();
+        let mut current_type = data_type.clone();
+        let mut lines = Vec::new();
+        let mut source = definition.lines();
+        while let Some(line) = source.next() {
+            if !is_separator(line) {
+                lines.push(line);
+                continue;
+            }
+            sections.push((current_type, std::mem::take(&mut lines)));
+            let next = source.next().unwrap_or_default().trim();
+            current_type = next
+                .strip_prefix("MSG:")
+                .map(|ros2_type| ros1_type_name(ros2_type.trim()))
+                .ok_or_else(|| {
+                    DecodeError::InvalidDefinition(format!(
+                        "expected 'MSG: <type>' after a separator, got '{next}'"
+                    ))
+                })?;
+        }
+        sections.push((current_type, lines));
+
+        let mut message_definition = String::new();
+        for (section_type, lines) in sections {
+            // both are primitives in ROS 1, unless they are the message itself
+            if section_type != data_type && is_builtin_time(&section_type) {
+                continue;
+            }
+            let text = if section_type == "std_msgs/Header" {
+                ROS1_HEADER.to_string()
+            } else {
+                translate_lines(&section_type, &lines)?
+            };
+            if section_type != data_type {
+                message_definition
+                    .push_str(&format!("\n{DEFINITION_SEPARATOR}\nMSG: {section_type}\n"));
+            }
+            message_definition.push_str(&text);
+        }
+        Self::from_ros1_definition(&data_type, message_definition)
+    }
+
+    fn from_ros1_definition(data_type: &str, message_definition: String) -> Result<Self, Error> {
+        let schema = Schema::parse(data_type, &message_definition)?;
+        let md5sum = definition_md5sum(data_type, &message_definition)
+            .ok_or_else(|| DecodeError::UnknownType(data_type.to_string()))?;
+        Ok(Ros2Type {
+            data_type: data_type.to_string(),
+            md5sum,
+            message_definition,
+            schema,
+        })
+    }
+
+    /// The ROS 1 type, like `geometry_msgs/Pose`.
+    pub fn data_type(&self) -> &str {
+        &self.data_type
+    }
+
+    /// The md5sum of the translated definition.
+    pub fn md5sum(&self) -> &str {
+        &self.md5sum
+    }
+
+    /// The translated ROS 1 definition, with those of its dependencies, as stored in connections.
+    pub fn message_definition(&self) -> &str {
+        &self.message_definition
+    }
+
+    /// The schema of the translated definition, which decodes the ROS 1 messages [Ros2Type::to_ros1] returns.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Decodes a CDR serialized message, including its 4 byte encapsulation header.
+    pub fn decode_cdr(&self, data: &[u8]) -> Result<Value, Error> {
+        let mut reader = CdrReader::new(data)?;
+        reader.message(&self.schema, &self.data_type)
+    }
+
+    /// Converts a CDR serialized message into a ROS 1 serialized message, without its leading length.
+    pub fn to_ros1(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.schema.encode(&self.decode_cdr(data)?)
+    }
+}
+
+/// ROS 2 `.msg` files, for rosbag2 bags recorded before they stored message definitions.
+#[derive(Clone, Debug, Default)]
+pub struct MessageSources {
+    /// by ROS 1 type name
+    sources: HashMap<String, String>,
+}
+
+impl MessageSources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the `.msg` files under `path`, found in `<package>/msg/<Name>.msg` like in a ROS 2 install's `share`
+    /// directory, returning how many were found.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, Error> {
+        let before = self.sources.len();
+        find_msg_files(path.as_ref(), &mut self.sources)?;
+        Ok(self.sources.len() - before)
+    }
+
+    /// Returns the definition of a type followed by those it depends on, as [Ros2Type::from_definition] takes,
+    /// or `None` if any of their `.msg` files weren't found.
+    pub fn definition(&self, ros2_type: &str) -> Option<String> {
+        let data_type = ros1_type_name(ros2_type);
+        let mut definition = self.sources.get(&data_type)?.clone();
+        let mut seen = vec![data_type.clone()];
+        let mut pending = vec![data_type];
+        while let Some(current) = pending.pop() {
+            for line in self.sources[&current].lines() {
+                let Some(dependency) = line_dependency(&current, line) else {
+                    continue;
+                };
+                if seen.contains(&dependency) {
+                    continue;
+                }
+                definition.push_str(&format!(
+                    "\n{DEFINITION_SEPARATOR}\nMSG: {dependency}\n{}",
+                    self.sources.get(&dependency)?
+                ));
+                seen.push(dependency.clone());
+                pending.push(dependency);
+            }
+        }
+        Some(definition)
+    }
+}
+
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 3 && line.chars().all(|c| c == '=')
+}
+
+fn is_builtin_time(data_type: &str) -> bool {
+    data_type == "builtin_interfaces/Time" || data_type == "builtin_interfaces/Duration"
+}
+
+/// Whether a ROS 2 type is a primitive, which have the ROS 1 names apart from `time` and `duration`.
+fn is_primitive(type_name: &str) -> bool {
+    type_name == "wstring"
+        || !matches!(type_name, "time" | "duration") && Primitive::from_name(type_name).is_some()
+}
+
+/// Returns the ROS 1 name of the message type a field line of `data_type` refers to, or `None` for primitives and
+/// constants.
+fn line_dependency(data_type: &str, line: &str) -> Option<String> {
+    let Ok(Some(DefinitionLine::Field { type_name, .. })) = parse_definition_line(data_type, line)
+    else {
+        return None;
+    };
+    let package = data_type.split_once('/').map_or("", |(package, _)| package);
+    let base_type = type_name.split('[').next().unwrap_or(type_name);
+    let base_type = base_type.split("<=").next().unwrap_or(base_type);
+    if is_primitive(base_type) {
+        return None;
+    }
+    let dependency = match base_type.contains('/') {
+        true => ros1_type_name(base_type),
+        false => format!("{package}/{base_type}"),
+    };
+    // translated into primitives, so their definitions aren't needed
+    (!is_builtin_time(&dependency)).then_some(dependency)
+}
+
+/// Translates the lines of a single type's definition, see the [module docs](self).
+fn translate_lines(data_type: &str, lines: &[&str]) -> Result<String, Error> {
+    let mut translated = Vec::new();
+    for line in lines {
+        let Some(parsed) = parse_definition_line(data_type, line)? else {
+            continue;
+        };
+        let (type_name, name, value) = match parsed {
+            DefinitionLine::Constant {
+                type_name,
+                name,
+                value,
+            } => (type_name, name, Some(value)),
+            DefinitionLine::Field { type_name, name } => (type_name, name, None),
+        };
+        let (base_type, array) = match type_name.find('[') {
+            Some(i) => type_name.split_at(i),
+            None => (type_name, ""),
+        };
+        let array = match array {
+            "" => "",
+            array if array.starts_with("[<=") => "[]",
+            array => array,
+        };
+        let base_type = match base_type.split("<=").next().unwrap_or(base_type) {
+            "wstring" => "string".to_string(),
+            base_type if is_primitive(base_type) => base_type.to_string(),
+            base_type => match ros1_type_name(base_type).as_str() {
+                "builtin_interfaces/Time" => "time".to_string(),
+                "builtin_interfaces/Duration" => "duration".to_string(),
+                other => other.to_string(),
+            },
+        };
+
+        match value {
+            Some(value) => translated.push(format!("{base_type}{array} {name}={value}")),
+            None => translated.push(format!("{base_type}{array} {name}")),
+        }
+    }
+    Ok(translated.join("\n"))
+}
+
+/// Reads CDR, aligning values to their size relative to the end of the encapsulation header.
+struct CdrReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    little_endian: bool,
+    /// 8 in classic CDR, 4 in XCDR2
+    max_align: usize,
+}
+
+impl<'a> CdrReader<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 4 {
+            return Err(DecodeError::UnexpectedEof.into());
+        }
+        let (little_endian, max_align) = match (data[0], data[1]) {
+            (0, 0x00) => (false, 8),
+            (0, 0x01) => (true, 8),
+            (0, 0x06) => (false, 4),
+            (0, 0x07) => (true, 4),
+            (a, b) => {
+                return Err(DecodeError::UnsupportedEncoding(format!(
+                    "CDR encapsulation {a:#04x}{b:02x}, expected plain CDR or XCDR2"
+                ))
+                .into())
+            }
+        };
+        Ok(CdrReader {
+            data: &data[4..],
+            pos: 0,
+            little_endian,
+            max_align,
+        })
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.data.len() - self.pos {
+            return Err(DecodeError::UnexpectedEof.into());
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Reads a value of `N` bytes, aligned to `N`, as little endian bytes.
+    fn aligned<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let align = N.min(self.max_align);
+        self.pos = (self.pos + align - 1) / align * align;
+        let mut bytes: [u8; N] = self.take(N)?.try_into().expect("took exactly N bytes");
+        if !self.little_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.aligned()?))
+    }
+
+    fn message(&mut self, schema: &Schema, data_type: &str) -> Result<Value, Error> {
+        let definition = schema
+            .definition(data_type)
+            .ok_or_else(|| DecodeError::UnknownType(data_type.to_string()))?;
+        let mut fields = Vec::with_capacity(definition.fields.len());
+        for field in definition.fields.iter() {
+            if data_type == "std_msgs/Header" && field.name == "seq" {
+                fields.push((field.name.clone(), Value::U32(0)));
+                continue;
+            }
+            let len = match field.cardinality {
+                Cardinality::Single => {
+                    let value = self.single(schema, &field.field_type)?;
+                    fields.push((field.name.clone(), value));
+                    continue;
+                }
+                Cardinality::Fixed(len) => len,
+                Cardinality::Variable => self.u32()? as usize,
+            };
+            let value = match &field.field_type {
+                FieldType::Primitive(Primitive::UInt8) => Value::Bytes(self.take(len)?.to_vec()),
+                field_type => {
+                    // every element is at least a byte, so a corrupt length fails here rather than allocating
+                    if len > self.data.len() - self.pos {
+                        return Err(DecodeError::UnexpectedEof.into());
+                    }
+                    let mut values = Vec::with_capacity(len);
+                    for _ in 0..len {
+                        values.push(self.single(schema, field_type)?);
+                    }
+                    Value::Array(values)
+                }
+            };
+            fields.push((field.name.clone(), value));
+        }
+        Ok(Value::Message(fields))
+    }
+
+    fn single(&mut self, schema: &Schema, field_type: &FieldType) -> Result<Value, Error> {
+        let primitive = match field_type {
+            FieldType::Primitive(primitive) => primitive,
+            FieldType::Message(data_type) => return self.message(schema, data_type),
+        };
+        Ok(match primitive {
+            Primitive::Bool => Value::Bool(self.take(1)?[0] != 0),
+            Primitive::Int8 => Value::I8(self.take(1)?[0] as i8),
+            Primitive::UInt8 => Value::U8(self.take(1)?[0]),
+            Primitive::Int16 => Value::I16(i16::from_le_bytes(self.aligned()?)),
+            Primitive::UInt16 => Value::U16(u16::from_le_bytes(self.aligned()?)),
+            Primitive::Int32 => Value::I32(i32::from_le_bytes(self.aligned()?)),
+            Primitive::UInt32 => Value::U32(self.u32()?),
+            Primitive::Int64 => Value::I64(i64::from_le_bytes(self.aligned()?)),
+            Primitive::UInt64 => Value::U64(u64::from_le_bytes(self.aligned()?)),
+            Primitive::Float32 => Value::F32(f32::from_le_bytes(self.aligned()?)),
+            Primitive::Float64 => Value::F64(f64::from_le_bytes(self.aligned()?)),
+            Primitive::String => {
+                let len = self.u32()? as usize;
+                let bytes = self.take(len)?;
+                // the length includes a terminating null
+                let bytes = bytes.strip_suffix(b"\0").unwrap_or(bytes);
+                Value::String(String::from_utf8_lossy(bytes).into_owned())
+            }
+            // builtin_interfaces/Time is an int32 sec and a uint32 nanosec
+            Primitive::Time => {
+                let secs = i32::from_le_bytes(self.aligned()?);
+                Value::Time(Time {
+                    secs: secs as u32,
+                    nsecs: self.u32()?,
+                })
+            }
+            Primitive::Duration => Value::Duration {
+                secs: i32::from_le_bytes(self.aligned()?),
+                nsecs: self.u32()? as i32,
+            },
+        })
+    }
+}
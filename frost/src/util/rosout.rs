@@ -0,0 +1,184 @@
+//! Reading `rosgraph_msgs/Log` messages, as published on `/rosout`, into log entries.
+//!
+//! Entries are timestamped with their header's stamp, falling back to the receive time if it is zero.
+//! Bags often record both `/rosout` and `/rosout_agg`, which repeats every entry, so
+//! [DecompressedBag::logs] drops entries that are identical to another entry.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::query::Query;
+//! use frost::rosout::LogLevel;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("robot.bag").unwrap();
+//! for entry in bag.logs(&Query::all(), LogLevel::Warn).unwrap() {
+//!     println!("{entry}");
+//! }
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer};
+
+use crate::dynamic::{required, required_f64, Value};
+use crate::errors::{DecodeError, Error};
+use crate::query::Query;
+use crate::time::Time;
+use crate::DecompressedBag;
+
+const LOG_TYPE: &str = "rosgraph_msgs/Log";
+
+/// The severity of a `rosgraph_msgs/Log` message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl LogLevel {
+    /// Returns the level of a message's `level` byte, or `None` if it is not one of the defined levels.
+    pub fn from_byte(level: i64) -> Option<LogLevel> {
+        match level {
+            1 => Some(LogLevel::Debug),
+            2 => Some(LogLevel::Info),
+            4 => Some(LogLevel::Warn),
+            8 => Some(LogLevel::Error),
+            16 => Some(LogLevel::Fatal),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Fatal => "FATAL",
+        };
+        // pads like `{:>5}` when a width is given
+        f.pad(name)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "fatal" => Ok(LogLevel::Fatal),
+            _ => Err(format!(
+                "unknown log level '{s}', expected debug, info, warn, error, or fatal"
+            )),
+        }
+    }
+}
+
+fn serialize_time<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(f64::from(time))
+}
+
+/// A decoded `rosgraph_msgs/Log` message. Serializes with `time` in seconds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LogEntry {
+    #[serde(serialize_with = "serialize_time")]
+    pub time: Time,
+    pub level: LogLevel,
+    /// The name of the node that logged the message.
+    pub node: String,
+    pub message: String,
+    pub file: String,
+    pub function: String,
+    pub line: u32,
+}
+
+impl LogEntry {
+    /// Reads a `rosgraph_msgs/Log` decoded with [crate::dynamic], received at `received`.
+    pub fn from_value(value: &Value, received: Time) -> Result<Self, DecodeError> {
+        let string = |field| {
+            required(value, LOG_TYPE, field)?
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    DecodeError::InvalidField(format!("{LOG_TYPE}.{field} is not a string"))
+                })
+        };
+        let raw_level = required_f64(value, LOG_TYPE, "level")? as i64;
+        let level = LogLevel::from_byte(raw_level).ok_or_else(|| {
+            DecodeError::InvalidField(format!("{LOG_TYPE}.level {raw_level} is not a known level"))
+        })?;
+        let time = match required(value, LOG_TYPE, "header")?.get("stamp") {
+            Some(Value::Time(stamp)) if *stamp != crate::time::ZERO => *stamp,
+            _ => received,
+        };
+        Ok(LogEntry {
+            time,
+            level,
+            node: string("name")?,
+            message: string("msg")?,
+            file: string("file")?,
+            function: string("function")?,
+            line: required_f64(value, LOG_TYPE, "line")? as u32,
+        })
+    }
+}
+
+impl fmt::Display for LogEntry {
+    /// Formats the entry like roscpp's console output with the node added: `[ WARN] [1665952859.000001] [/node]: message`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:>5}] [{}] [{}]: {}",
+            self.level, self.time, self.node, self.message
+        )
+    }
+}
+
+impl DecompressedBag {
+    /// Returns the `rosgraph_msgs/Log` entries matching `query` at or above `min_level`, ordered by time,
+    /// see the [rosout module](self).
+    pub fn logs(&self, query: &Query, min_level: LogLevel) -> Result<Vec<LogEntry>, Error> {
+        let mut entries = Vec::new();
+        for msg_view in self.filter_messages(query)? {
+            if msg_view.connection().data_type != LOG_TYPE {
+                continue;
+            }
+            let entry = LogEntry::from_value(&msg_view.decode()?, msg_view.time)?;
+            if entry.level >= min_level {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| {
+            (
+                a.time,
+                a.level,
+                &a.node,
+                &a.message,
+                &a.file,
+                &a.function,
+                a.line,
+            )
+                .cmp(&(
+                    b.time,
+                    b.level,
+                    &b.node,
+                    &b.message,
+                    &b.file,
+                    &b.function,
+                    b.line,
+                ))
+        });
+        entries.dedup();
+        Ok(entries)
+    }
+}
@@ -0,0 +1,145 @@
+//! Converting `sensor_msgs/LaserScan` messages into Cartesian points in the scan's frame.
+//!
+//! A [LaserScan] is read from a message decoded with [crate::dynamic], or filled in from a generated type's fields.
+//! Ranges outside the scan's `range_min`..=`range_max` (including the `NaN` and infinite values drivers use for no return)
+//! are dropped, and a [ScanFilter] can narrow the angles and ranges further.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::query::Query;
+//! use frost::scan::{LaserScan, ScanFilter};
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("robot.bag").unwrap();
+//! let filter = ScanFilter::new().with_angles(-1.0, 1.0).with_ranges(0.0, 10.0);
+//! for msg_view in bag.read_messages(&Query::new().with_topics(["/scan"])).unwrap() {
+//!     let scan = LaserScan::from_value(&msg_view.decode().unwrap()).unwrap();
+//!     for point in scan.points(&filter) {
+//!         println!("{} {}", point.x, point.y);
+//!     }
+//! }
+//! ```
+use crate::dynamic::{required, required_f64, Value};
+use crate::errors::DecodeError;
+
+const LASER_SCAN_TYPE: &str = "sensor_msgs/LaserScan";
+
+/// The fields of a `sensor_msgs/LaserScan` needed to place its ranges, in radians and meters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LaserScan {
+    pub angle_min: f32,
+    pub angle_max: f32,
+    pub angle_increment: f32,
+    pub range_min: f32,
+    pub range_max: f32,
+    pub ranges: Vec<f32>,
+    /// Empty if the scanner doesn't report intensities.
+    pub intensities: Vec<f32>,
+}
+
+/// A return of a [LaserScan], see [LaserScan::points].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanPoint {
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    pub range: f32,
+    pub intensity: Option<f32>,
+}
+
+/// Limits on which returns of a [LaserScan] become points. Both ends of each limit are inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanFilter {
+    min_angle: f32,
+    max_angle: f32,
+    min_range: f32,
+    max_range: f32,
+}
+
+impl ScanFilter {
+    /// Keeps every return within the scan's own range limits.
+    pub fn new() -> Self {
+        ScanFilter {
+            min_angle: f32::NEG_INFINITY,
+            max_angle: f32::INFINITY,
+            min_range: f32::NEG_INFINITY,
+            max_range: f32::INFINITY,
+        }
+    }
+
+    /// Keeps returns with angles from `min` to `max` radians.
+    pub fn with_angles(mut self, min: f32, max: f32) -> Self {
+        self.min_angle = min;
+        self.max_angle = max;
+        self
+    }
+
+    /// Keeps returns with ranges from `min` to `max` meters, on top of the scan's own limits.
+    pub fn with_ranges(mut self, min: f32, max: f32) -> Self {
+        self.min_range = min;
+        self.max_range = max;
+        self
+    }
+}
+
+impl Default for ScanFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LaserScan {
+    /// Reads the fields of a `sensor_msgs/LaserScan` decoded with [crate::dynamic].
+    pub fn from_value(value: &Value) -> Result<Self, DecodeError> {
+        let f32_array = |field| match required(value, LASER_SCAN_TYPE, field)? {
+            Value::Array(values) => values
+                .iter()
+                .map(|v| v.as_f64().map(|v| v as f32))
+                .collect::<Option<Vec<f32>>>()
+                .ok_or_else(|| {
+                    DecodeError::InvalidField(format!(
+                        "{LASER_SCAN_TYPE}.{field} is not a float32[]"
+                    ))
+                }),
+            _ => Err(DecodeError::InvalidField(format!(
+                "{LASER_SCAN_TYPE}.{field} is not a float32[]"
+            ))),
+        };
+        let f32_field = |field| required_f64(value, LASER_SCAN_TYPE, field).map(|v| v as f32);
+
+        Ok(LaserScan {
+            angle_min: f32_field("angle_min")?,
+            angle_max: f32_field("angle_max")?,
+            angle_increment: f32_field("angle_increment")?,
+            range_min: f32_field("range_min")?,
+            range_max: f32_field("range_max")?,
+            ranges: f32_array("ranges")?,
+            intensities: f32_array("intensities")?,
+        })
+    }
+
+    /// Returns the returns kept by `filter` as points, with x forward and y to the left as in ROS.
+    pub fn points(&self, filter: &ScanFilter) -> Vec<ScanPoint> {
+        let min_range = self.range_min.max(filter.min_range);
+        let max_range = self.range_max.min(filter.max_range);
+        self.ranges
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &range)| {
+                let angle = self.angle_min + i as f32 * self.angle_increment;
+                // comparisons are false for NaN, so NaN ranges are dropped too
+                let keep = range >= min_range
+                    && range <= max_range
+                    && angle >= filter.min_angle
+                    && angle <= filter.max_angle;
+                keep.then(|| ScanPoint {
+                    x: range * angle.cos(),
+                    y: range * angle.sin(),
+                    angle,
+                    range,
+                    intensity: self.intensities.get(i).copied(),
+                })
+            })
+            .collect()
+    }
+}
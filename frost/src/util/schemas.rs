@@ -0,0 +1,365 @@
+//! A cache of parsed message definitions, shared by every bag a process reads.
+//!
+//! Connections of the same type carry identical definition text, so decoding messages from thousands of bags would
+//! otherwise parse the same definitions again for every message. [MessageView::decode](crate::msgs::MessageView::decode)
+//! looks schemas up in [SchemaCache::global] by type and md5sum, parsing each definition once.
+//!
+//! Example
+//! ```rust
+//! use frost::query::Query;
+//! use frost::schemas::SchemaCache;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! for msg_view in bag.read_messages(&Query::all()).unwrap() {
+//!     msg_view.decode().unwrap();
+//! }
+//!
+//! let stats = SchemaCache::global().stats();
+//! assert_eq!(stats.misses, 3);
+//! assert_eq!(stats.hits, 297);
+//! ```
+use std::collections::HashMap;
+#[cfg(feature = "md5")]
+use std::collections::HashSet;
+#[cfg(feature = "md5")]
+use std::fs;
+#[cfg(feature = "md5")]
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[cfg(feature = "md5")]
+use md5::{Digest, Md5};
+
+use crate::dynamic::Schema;
+#[cfg(feature = "md5")]
+use crate::dynamic::{parse_definition_line, DefinitionLine, DEFINITION_SEPARATOR};
+use crate::errors::Error;
+
+/// Types which are serialized directly, rather than as nested messages, when computing md5sums.
+#[cfg(feature = "md5")]
+const BUILTIN_TYPES: [&str; 16] = [
+    "bool", "byte", "char", "int8", "uint8", "int16", "uint16", "int32", "uint32", "int64",
+    "uint64", "float32", "float64", "string", "time", "duration",
+];
+
+/// Parsed schemas by their type, md5sum, and the fields they are projected onto, if any.
+type Schemas = HashMap<(String, String, Vec<String>), Arc<Schema>>;
+
+/// How often [SchemaCache::get] found a parsed schema.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of cached schemas, including projections.
+    pub entries: usize,
+    pub hits: u64,
+    /// Lookups which parsed a definition, including those of connections without an md5sum, which aren't cached.
+    pub misses: u64,
+}
+
+/// Parsed schemas keyed by message type and md5sum, see the [module docs](self).
+#[derive(Debug, Default)]
+pub struct SchemaCache {
+    schemas: Mutex<Option<Schemas>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+static GLOBAL: SchemaCache = SchemaCache::new();
+
+impl SchemaCache {
+    pub const fn new() -> Self {
+        SchemaCache {
+            schemas: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The cache used when decoding messages.
+    pub fn global() -> &'static SchemaCache {
+        &GLOBAL
+    }
+
+    /// Returns the schema of a connection, parsing `message_definition` if no schema with the same type and md5sum
+    /// was seen before. Connections written with a `*` md5sum are parsed every time.
+    pub fn get(
+        &self,
+        data_type: &str,
+        md5sum: &str,
+        message_definition: &str,
+    ) -> Result<Arc<Schema>, Error> {
+        self.get_projected::<&str>(data_type, md5sum, message_definition, &[])
+    }
+
+    /// Like [SchemaCache::get], returning the schema [projected](Schema::project) onto the `fields` paths,
+    /// or the whole schema if there are none. Each projection is cached separately.
+    pub fn get_projected<S: AsRef<str>>(
+        &self,
+        data_type: &str,
+        md5sum: &str,
+        message_definition: &str,
+        fields: &[S],
+    ) -> Result<Arc<Schema>, Error> {
+        let project = |schema: &Schema| match fields.is_empty() {
+            true => Ok(schema.clone()),
+            false => schema.project(fields),
+        };
+        if md5sum == "*" {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(Arc::new(project(&Schema::parse(
+                data_type,
+                message_definition,
+            )?)?));
+        }
+
+        let key = (data_type.to_string(), md5sum.to_string(), Vec::new());
+        let projected_key = (
+            key.0.clone(),
+            key.1.clone(),
+            fields
+                .iter()
+                .map(|field| field.as_ref().to_string())
+                .collect(),
+        );
+        let full = {
+            let mut schemas = self.lock();
+            let schemas = schemas.get_or_insert_with(HashMap::new);
+            if let Some(schema) = schemas.get(&projected_key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(schema.clone());
+            }
+            schemas.get(&key).cloned()
+        };
+        // parsed without holding the lock, so other threads can keep decoding
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let full = match full {
+            Some(full) => full,
+            None => Arc::new(Schema::parse(data_type, message_definition)?),
+        };
+        let schema = match fields.is_empty() {
+            true => full.clone(),
+            false => Arc::new(project(&full)?),
+        };
+
+        let mut schemas = self.lock();
+        let schemas = schemas.get_or_insert_with(HashMap::new);
+        schemas.entry(key).or_insert(full);
+        Ok(schemas.entry(projected_key).or_insert(schema).clone())
+    }
+
+    /// Adds a schema under the given md5sum, replacing any with the same type and md5sum.
+    pub fn insert(&self, md5sum: &str, schema: Schema) {
+        let key = (
+            schema.data_type().to_string(),
+            md5sum.to_string(),
+            Vec::new(),
+        );
+        self.lock()
+            .get_or_insert_with(HashMap::new)
+            .insert(key, Arc::new(schema));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.lock().as_ref().map_or(0, HashMap::len),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes every schema and resets the stats.
+    pub fn clear(&self) {
+        *self.lock() = None;
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    /// Parses every `.msg` file under `path`, computing md5sums the way ROS does, and adds their schemas.
+    /// Returns how many were added.
+    ///
+    /// Files must be in a `msg` directory, inside a directory named after their package, like
+    /// `std_msgs/msg/Header.msg`. Types depending on a type missing from `path` are skipped. Requires the `md5`
+    /// feature.
+    #[cfg(feature = "md5")]
+    pub fn prewarm_dir<P: AsRef<Path>>(&self, path: P) -> Result<usize, Error> {
+        let mut sources = HashMap::new();
+        find_msg_files(path.as_ref(), &mut sources)?;
+
+        let mut md5sums = HashMap::new();
+        let mut count = 0;
+        let mut data_types: Vec<&String> = sources.keys().collect();
+        data_types.sort();
+        for data_type in data_types {
+            let Some(md5sum) = md5sum(data_type, &sources, &mut md5sums, &mut HashSet::new())
+            else {
+                continue;
+            };
+            let schema = Schema::parse(data_type, &full_definition(data_type, &sources))?;
+            self.insert(&md5sum, schema);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Schemas>> {
+        // the map is never left half updated, so it's still usable if another thread panicked
+        self.schemas
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Collects the text of `.msg` files under `dir` by their `package/Name` type.
+#[cfg(feature = "md5")]
+pub(crate) fn find_msg_files(
+    dir: &Path,
+    sources: &mut HashMap<String, String>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_msg_files(&path, sources)?;
+            continue;
+        }
+        if path
+            .extension()
+            .map_or(true, |extension| extension != "msg")
+            || dir.file_name().map_or(true, |name| name != "msg")
+        {
+            continue;
+        }
+        let (Some(package), Some(name)) = (
+            dir.parent()
+                .and_then(Path::file_name)
+                .and_then(|name| name.to_str()),
+            path.file_stem().and_then(|name| name.to_str()),
+        ) else {
+            continue;
+        };
+        sources.insert(format!("{package}/{name}"), fs::read_to_string(&path)?);
+    }
+    Ok(())
+}
+
+/// Returns the full type of a field's message type, or `None` for builtin types.
+#[cfg(feature = "md5")]
+fn dependency(type_name: &str, package: &str) -> Option<String> {
+    let base_type = type_name.split('[').next().unwrap_or(type_name);
+    if BUILTIN_TYPES.contains(&base_type) {
+        None
+    } else if base_type.contains('/') {
+        Some(base_type.to_string())
+    } else if base_type == "Header" {
+        Some("std_msgs/Header".to_string())
+    } else {
+        Some(format!("{package}/{base_type}"))
+    }
+}
+
+/// Computes the md5sum of a type like `genmsg`: constants, then fields, with nested message types replaced by their
+/// md5sum. `None` if a dependency is missing or circular.
+#[cfg(feature = "md5")]
+fn md5sum(
+    data_type: &str,
+    sources: &HashMap<String, String>,
+    md5sums: &mut HashMap<String, Option<String>>,
+    visiting: &mut HashSet<String>,
+) -> Option<String> {
+    if let Some(md5sum) = md5sums.get(data_type) {
+        return md5sum.clone();
+    }
+    if !visiting.insert(data_type.to_string()) {
+        return None;
+    }
+
+    let package = data_type.split_once('/').map_or("", |(package, _)| package);
+    let mut constants = Vec::new();
+    let mut fields = Vec::new();
+    let mut result = Some(());
+    for line in sources.get(data_type)?.lines() {
+        let (type_name, name) = match parse_definition_line(data_type, line).ok()? {
+            None => continue,
+            Some(DefinitionLine::Constant {
+                type_name,
+                name,
+                value,
+            }) => {
+                constants.push(format!("{type_name} {name}={value}"));
+                continue;
+            }
+            Some(DefinitionLine::Field { type_name, name }) => (type_name, name),
+        };
+        match dependency(type_name, package) {
+            None => fields.push(format!("{type_name} {name}")),
+            Some(dependency) => match md5sum(&dependency, sources, md5sums, visiting) {
+                Some(md5sum) => fields.push(format!("{md5sum} {name}")),
+                None => result = None,
+            },
+        }
+    }
+    visiting.remove(data_type);
+
+    let md5sum = result.map(|_| {
+        constants.extend(fields);
+        format!("{:x}", Md5::digest(constants.join("\n")))
+    });
+    md5sums.insert(data_type.to_string(), md5sum.clone());
+    md5sum
+}
+
+/// Computes the md5sum of a type from the full `message_definition` of a connection, as written by
+/// [full_definition]. `None` if the definition is missing a dependency.
+#[cfg(feature = "md5")]
+pub(crate) fn definition_md5sum(data_type: &str, message_definition: &str) -> Option<String> {
+    let mut sources = HashMap::new();
+    let mut current_type = data_type.to_string();
+    let mut text = String::new();
+    let mut lines = message_definition.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() != DEFINITION_SEPARATOR {
+            text.push_str(line);
+            text.push('\n');
+            continue;
+        }
+        sources.insert(current_type, std::mem::take(&mut text));
+        let next = lines.next().unwrap_or_default().trim();
+        current_type = next.strip_prefix("MSG:")?.trim().to_string();
+    }
+    sources.insert(current_type, text);
+    md5sum(
+        data_type,
+        &sources,
+        &mut HashMap::new(),
+        &mut HashSet::new(),
+    )
+}
+
+/// Returns a type's definition followed by those of its dependencies, as recorded in a connection.
+#[cfg(feature = "md5")]
+fn full_definition(data_type: &str, sources: &HashMap<String, String>) -> String {
+    let mut definition = sources[data_type].clone();
+    let mut seen = HashSet::from([data_type.to_string()]);
+    let mut pending = vec![data_type.to_string()];
+    while let Some(current) = pending.pop() {
+        let package = current.split_once('/').map_or("", |(package, _)| package);
+        let mut dependencies: Vec<String> = sources[&current]
+            .lines()
+            .filter_map(|line| match parse_definition_line(&current, line) {
+                Ok(Some(DefinitionLine::Field { type_name, .. })) => Some(type_name),
+                _ => None,
+            })
+            .filter_map(|type_name| dependency(type_name, package))
+            .filter(|dependency| seen.insert(dependency.clone()))
+            .collect();
+        for dependency in dependencies.iter() {
+            definition.push_str(&format!(
+                "\n{DEFINITION_SEPARATOR}\nMSG: {dependency}\n{}",
+                sources[dependency]
+            ));
+        }
+        dependencies.reverse();
+        pending.extend(dependencies);
+    }
+    definition
+}
@@ -0,0 +1,138 @@
+//! Estimating the clock offset between bags recorded on different machines.
+//!
+//! Messages published on one machine and recorded on both, like `/tf` or a shared `/clock`, are the same event seen by
+//! two clocks. [estimate_skew] matches them by topic and identical data, skipping data that repeats, and takes the
+//! median difference of their receive times per topic.
+//!
+//! Each difference also includes the network latency, which adds to it for topics published on the reference machine
+//! and subtracts from it for topics published on the other one. When topics went both ways the latency cancels out,
+//! NTP style, by taking the midpoint of the smallest and largest per-topic offsets. Otherwise the estimate is off by
+//! about one latency, which [SkewEstimate::latency_cancelled] reports.
+//!
+//! Example
+//! ```rust,no_run
+//! use frost::merge::Merge;
+//! use frost::skew::estimate_skew;
+//! use frost::writer::BagWriter;
+//! use frost::DecompressedBag;
+//!
+//! let robot = DecompressedBag::from_file("robot.bag").unwrap();
+//! let base = DecompressedBag::from_file("base_station.bag").unwrap();
+//! let estimate = estimate_skew(&robot, &base).unwrap();
+//!
+//! let mut writer = BagWriter::create("merged.bag").unwrap();
+//! Merge::new()
+//!     .add_bag(&robot, 0.0)
+//!     .add_bag(&base, estimate.offset.unwrap_or(0.0))
+//!     .write_to(&mut writer)
+//!     .unwrap();
+//! writer.finish().unwrap();
+//! ```
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+
+use crate::errors::Error;
+use crate::query::Query;
+use crate::time::Time;
+use crate::DecompressedBag;
+
+/// The offset measured from the messages of one topic found in both bags.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopicSkew {
+    pub topic: String,
+    /// How many messages were matched.
+    pub matches: usize,
+    /// The median of the reference's receive time minus the other bag's, in seconds.
+    pub offset: f64,
+    /// The median absolute deviation of the differences from `offset`, in seconds, a measure of jitter.
+    pub spread: f64,
+}
+
+/// The estimated offset between two bags' clocks, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SkewEstimate {
+    /// Topics with at least one matched message, sorted by name.
+    pub topics: Vec<TopicSkew>,
+    /// Seconds to add to the other bag's times to match the reference's clock, as taken by
+    /// [Merge::add_bag](crate::merge::Merge::add_bag). `None` if no messages matched.
+    pub offset: Option<f64>,
+    /// Whether topics were published on both machines, so that latency cancelled out of `offset`.
+    /// Decided by whether per-topic offsets differ by more than their spread.
+    pub latency_cancelled: bool,
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Receive times of the messages on each topic, keyed by a hash of their data, or `None` for data seen more than once.
+fn fingerprints(bag: &DecompressedBag) -> Result<HashMap<(String, u64), Option<Time>>, Error> {
+    let mut times = HashMap::new();
+    for msg_view in bag.read_messages(&Query::all())? {
+        let mut hasher = DefaultHasher::new();
+        msg_view.data_bytes().hash(&mut hasher);
+        times
+            .entry((msg_view.topic.to_string(), hasher.finish()))
+            .and_modify(|time| *time = None)
+            .or_insert(Some(msg_view.time));
+    }
+    Ok(times)
+}
+
+/// Estimates the seconds to add to `other`'s times to match the clock of `reference`.
+pub fn estimate_skew(
+    reference: &DecompressedBag,
+    other: &DecompressedBag,
+) -> Result<SkewEstimate, Error> {
+    let reference = fingerprints(reference)?;
+    let other = fingerprints(other)?;
+
+    let mut differences: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for (key, time) in reference.iter() {
+        if let (Some(reference_time), Some(Some(other_time))) = (time, other.get(key)) {
+            differences
+                .entry(&key.0)
+                .or_default()
+                .push(f64::from(reference_time) - f64::from(other_time));
+        }
+    }
+
+    let topics: Vec<TopicSkew> = differences
+        .into_iter()
+        .map(|(topic, differences)| {
+            let offset = median(differences.clone());
+            let spread = median(differences.iter().map(|d| (d - offset).abs()).collect());
+            TopicSkew {
+                topic: topic.to_string(),
+                matches: differences.len(),
+                offset,
+                spread,
+            }
+        })
+        .collect();
+
+    let (Some(lowest), Some(highest)) = (
+        topics.iter().min_by(|a, b| a.offset.total_cmp(&b.offset)),
+        topics.iter().max_by(|a, b| a.offset.total_cmp(&b.offset)),
+    ) else {
+        return Ok(SkewEstimate::default());
+    };
+    let latency_cancelled = highest.offset - lowest.offset > lowest.spread + highest.spread;
+    let offset = if latency_cancelled {
+        (lowest.offset + highest.offset) / 2.0
+    } else {
+        median(topics.iter().map(|topic| topic.offset).collect())
+    };
+    Ok(SkewEstimate {
+        topics,
+        offset: Some(offset),
+        latency_cancelled,
+    })
+}
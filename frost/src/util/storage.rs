@@ -0,0 +1,425 @@
+//! Where a bag's bytes come from.
+//!
+//! Parsing only ever asks a [BagStorage] for byte ranges, so supporting a new kind of storage
+//! (e.g. HTTP range requests or an object store) means implementing [ByteSource], wrapping it in a [CustomStorage],
+//! and calling [BagMetadata::from_storage](crate::BagMetadata::from_storage) or
+//! [DecompressedBag::from_storage](crate::DecompressedBag::from_storage), without touching the parsers.
+//! Files, byte slices, streams like stdin (see [StreamStorage]), and (with the `mmap` feature) memory mapped files are
+//! supported out of the box.
+//!
+//! [BagStorage] itself is sealed, so it can gain methods without breaking code outside of frost, as it is part of
+//! [frost::stable::v1](crate::stable::v1).
+//!
+//! Example
+//! ```rust
+//! use frost::storage::FileStorage;
+//! use frost::BagMetadata;
+//!
+//! let storage = FileStorage::open("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let metadata = BagMetadata::from_storage(&storage).unwrap();
+//! assert_eq!(metadata.message_count(), 300);
+//! ```
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// reads smaller than this are rounded up, since parsing metadata makes many small reads of record headers
+const READ_AHEAD: usize = 64 * 1024;
+
+/// How much of a stream [StreamStorage] keeps in memory before spooling it to a temporary file.
+pub const DEFAULT_MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Random access to the bytes of a bag, see the [module docs](self).
+///
+/// Sealed: implement [ByteSource] for other kinds of storage.
+pub trait BagStorage: sealed::Sealed {
+    /// The total size of the bag in bytes.
+    fn size(&self) -> io::Result<u64>;
+
+    /// Reads `len` bytes starting at `offset`, failing if fewer are available.
+    /// In-memory storage can return a borrowed slice to avoid a copy.
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>>;
+
+    /// The path of the bag on the local filesystem, if it has one.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+pub(crate) mod sealed {
+    /// Keeps [BagStorage](super::BagStorage) from being implemented outside of frost.
+    pub trait Sealed {}
+}
+
+/// Random access to the bytes of a bag in storage frost doesn't support itself, read through a [CustomStorage].
+pub trait ByteSource {
+    /// The total size of the bag in bytes.
+    fn size(&self) -> io::Result<u64>;
+
+    /// Reads `len` bytes starting at `offset`, failing if fewer are available.
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>>;
+
+    /// The path of the bag on the local filesystem, if it has one.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// A bag read from a [ByteSource], see the [module docs](self).
+pub struct CustomStorage<S> {
+    source: S,
+}
+
+impl<S: ByteSource> CustomStorage<S> {
+    pub fn new(source: S) -> Self {
+        CustomStorage { source }
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: ByteSource> sealed::Sealed for CustomStorage<S> {}
+
+impl<S: ByteSource> BagStorage for CustomStorage<S> {
+    fn size(&self) -> io::Result<u64> {
+        self.source.size()
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        self.source.read_at(offset, len)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.source.path()
+    }
+}
+
+impl sealed::Sealed for &[u8] {}
+
+impl BagStorage for &[u8] {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        slice_at(self, offset, len)
+    }
+}
+
+impl sealed::Sealed for Vec<u8> {}
+
+impl BagStorage for Vec<u8> {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        slice_at(self, offset, len)
+    }
+}
+
+fn slice_at(bytes: &[u8], offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+    let start = usize::try_from(offset).map_err(|_| out_of_range(offset, len))?;
+    start
+        .checked_add(len)
+        .and_then(|end| bytes.get(start..end))
+        .map(Cow::Borrowed)
+        .ok_or_else(|| out_of_range(offset, len))
+}
+
+fn out_of_range(offset: u64, len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("{len} bytes at offset {offset} are past the end of the bag"),
+    )
+}
+
+/// A bag in a file on the local filesystem.
+pub struct FileStorage {
+    path: PathBuf,
+    file: Mutex<File>,
+    size: u64,
+}
+
+impl FileStorage {
+    pub fn open<P: AsRef<Path>>(file_path: P) -> io::Result<Self> {
+        let file = File::open(file_path.as_ref())?;
+        let size = file.metadata()?.len();
+        Ok(FileStorage {
+            path: file_path.as_ref().to_path_buf(),
+            file: Mutex::new(file),
+            size,
+        })
+    }
+}
+
+impl sealed::Sealed for FileStorage {}
+
+impl BagStorage for FileStorage {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.size)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0; len];
+        file.read_exact(&mut buf)?;
+        Ok(Cow::Owned(buf))
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// A bag read from a stream which can't seek, like stdin or a pipe from `curl`.
+///
+/// A bag's index is at its end, so the whole stream is read when the storage is created. Streams up to a memory
+/// limit are kept in memory, and larger ones are spooled to a temporary file, which is removed when the storage is
+/// dropped.
+pub struct StreamStorage {
+    spool: Spool,
+    size: u64,
+}
+
+enum Spool {
+    Memory(Vec<u8>),
+    File { path: PathBuf, file: Mutex<File> },
+}
+
+impl StreamStorage {
+    /// Reads `reader` to its end, keeping up to [DEFAULT_MEMORY_LIMIT] bytes in memory.
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+        Self::with_memory_limit(reader, DEFAULT_MEMORY_LIMIT)
+    }
+
+    /// Reads `reader` to its end, spooling it to a temporary file if it's longer than `memory_limit` bytes.
+    pub fn with_memory_limit<R: Read>(reader: R, memory_limit: usize) -> io::Result<Self> {
+        let mut reader = reader;
+        let mut bytes = Vec::new();
+        let read = (&mut reader)
+            .take(memory_limit as u64 + 1)
+            .read_to_end(&mut bytes)?;
+        if read <= memory_limit {
+            return Ok(StreamStorage {
+                size: bytes.len() as u64,
+                spool: Spool::Memory(bytes),
+            });
+        }
+
+        let (path, mut file) = temp_file()?;
+        // remove the file if spooling fails
+        let spool = Spool::File {
+            path,
+            file: Mutex::new(file.try_clone()?),
+        };
+        file.write_all(&bytes)?;
+        let size = bytes.len() as u64 + io::copy(&mut reader, &mut file)?;
+        Ok(StreamStorage { spool, size })
+    }
+
+    /// Reads stdin to its end, see [StreamStorage::from_reader].
+    pub fn stdin() -> io::Result<Self> {
+        Self::from_reader(io::stdin().lock())
+    }
+
+    /// Whether the stream was spooled to a temporary file, rather than kept in memory.
+    pub fn is_spooled(&self) -> bool {
+        matches!(self.spool, Spool::File { .. })
+    }
+}
+
+/// Creates a new file in the system's temporary directory.
+fn temp_file() -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "frost-{}-{}.bag",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+impl sealed::Sealed for StreamStorage {}
+
+impl BagStorage for StreamStorage {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.size)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        match &self.spool {
+            Spool::Memory(bytes) => slice_at(bytes, offset, len),
+            Spool::File { file, .. } => {
+                let mut file = file.lock().unwrap();
+                file.seek(SeekFrom::Start(offset))?;
+                let mut buf = vec![0; len];
+                file.read_exact(&mut buf)?;
+                Ok(Cow::Owned(buf))
+            }
+        }
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if let Spool::File { path, .. } = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// A bag in a memory mapped file, enabled with the `mmap` feature.
+///
+/// Reads borrow from the mapping, so the kernel pages in only the parts of the bag which are used.
+/// As with any memory mapped file, the file must not be truncated or modified while it is mapped.
+#[cfg(feature = "mmap")]
+pub struct MmapStorage {
+    path: PathBuf,
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapStorage {
+    pub fn open<P: AsRef<Path>>(file_path: P) -> io::Result<Self> {
+        let file = File::open(file_path.as_ref())?;
+        // SAFETY: the mapping is read-only, and callers are told not to modify the file while it is mapped
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(MmapStorage {
+            path: file_path.as_ref().to_path_buf(),
+            map,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl sealed::Sealed for MmapStorage {}
+
+#[cfg(feature = "mmap")]
+impl BagStorage for MmapStorage {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.map.len() as u64)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        slice_at(&self.map, offset, len)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// Adapts a [BagStorage] to [Read] + [Seek] for the record parsers, reading ahead to batch small reads.
+pub(crate) struct StorageReader<'a> {
+    storage: &'a dyn BagStorage,
+    size: u64,
+    pos: u64,
+    buf: Vec<u8>,
+    buf_start: u64,
+}
+
+impl<'a> StorageReader<'a> {
+    pub(crate) fn new(storage: &'a dyn BagStorage) -> io::Result<Self> {
+        Ok(StorageReader {
+            storage,
+            size: storage.size()?,
+            pos: 0,
+            buf: Vec::new(),
+            buf_start: 0,
+        })
+    }
+}
+
+impl<'a> Read for StorageReader<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size || out.is_empty() {
+            return Ok(0);
+        }
+        let buf_end = self.buf_start + self.buf.len() as u64;
+        if self.pos < self.buf_start || self.pos >= buf_end {
+            let len = (self.size - self.pos).min(out.len().max(READ_AHEAD) as u64) as usize;
+            self.buf = self.storage.read_at(self.pos, len)?.into_owned();
+            self.buf_start = self.pos;
+        }
+        let start = (self.pos - self.buf_start) as usize;
+        let n = out.len().min(self.buf.len() - start);
+        out[..n].copy_from_slice(&self.buf[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<'a> Seek for StorageReader<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => add_signed(self.size, offset),
+            SeekFrom::Current(offset) => add_signed(self.pos, offset),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+fn add_signed(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.unsigned_abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use super::{BagStorage, StorageReader};
+
+    #[test]
+    fn test_reader_seeks_across_read_ahead() {
+        let bytes: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let mut reader = StorageReader::new(&bytes).unwrap();
+
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2, 3]);
+
+        reader.seek(SeekFrom::Current(100_000)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, bytes[100_004..100_008]);
+
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, bytes[199_998..]);
+
+        assert!(bytes.read_at(199_999, 2).is_err());
+    }
+}
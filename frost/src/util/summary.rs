@@ -0,0 +1,547 @@
+//! Renders the summary `frost info` prints as Markdown or HTML tables, for notebooks and generated reports, or exactly
+//! as `rosbag info` prints it, for scripts written to parse rosbag's output.
+//!
+//! Example
+//! ```rust
+//! use frost::BagMetadata;
+//!
+//! let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let markdown = metadata.to_markdown();
+//! assert!(markdown.contains("| /chatter | 100 | std_msgs/String |"));
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Local, TimeZone};
+use itertools::Itertools;
+
+use crate::time::Time;
+use crate::BagMetadata;
+
+/// Printed in place of times [Time::as_rfc3339] can't format.
+pub const INVALID_TIME: &str = "invalid time";
+
+/// Formats a byte count like `1.50 MB (1572864 bytes)`.
+pub fn human_bytes(bytes: u64) -> String {
+    let units = ["bytes", "KB", "MB", "GB"];
+
+    let mut unit = units[0];
+    let mut remainder = bytes as f64;
+
+    for u in units {
+        unit = u;
+        if remainder < 1024.0 {
+            break;
+        }
+        remainder /= 1024.0;
+    }
+
+    if unit == "bytes" {
+        format!("{bytes} bytes")
+    } else {
+        format!("{remainder:.2} {unit} ({bytes} bytes)")
+    }
+}
+
+struct Summary {
+    fields: Vec<(&'static str, String)>,
+    /// (type, md5sum)
+    types: Vec<(String, String)>,
+    /// (topic, message count, type)
+    topics: Vec<(String, usize, String)>,
+}
+
+fn format_time(time: Option<Time>) -> String {
+    match time {
+        Some(time) => format!(
+            "{} ({:.6})",
+            time.as_rfc3339().as_deref().unwrap_or(INVALID_TIME),
+            f64::from(time)
+        ),
+        None => "-".to_string(),
+    }
+}
+
+fn summarize(metadata: &BagMetadata) -> Summary {
+    let mut fields = Vec::new();
+    if let Some(path) = &metadata.file_path {
+        fields.push(("path", path.to_string_lossy().into_owned()));
+    }
+    fields.push(("version", metadata.version.clone()));
+    fields.push((
+        "duration",
+        format!("{:.2}s", metadata.duration().as_secs_f64()),
+    ));
+    fields.push(("start", format_time(metadata.start_time())));
+    fields.push(("end", format_time(metadata.end_time())));
+    fields.push(("size", human_bytes(metadata.num_bytes)));
+    fields.push(("messages", metadata.message_count().to_string()));
+
+    let compression_info = metadata.compression_info();
+    let total_chunks: usize = compression_info.iter().map(|info| info.chunk_count).sum();
+    let compression = compression_info
+        .iter()
+        .map(|info| {
+            format!(
+                "{} [{}/{} chunks; {:.2}%]",
+                info.name,
+                info.chunk_count,
+                total_chunks,
+                100f64 * info.total_compressed as f64 / info.total_uncompressed as f64
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    fields.push(("compression", compression));
+
+    let types: BTreeSet<(String, String)> = metadata
+        .connection_data
+        .values()
+        .map(|data| (data.data_type.clone(), data.md5sum.clone()))
+        .collect();
+
+    let topic_counts = metadata.topic_message_counts();
+    let topics: BTreeSet<(String, usize, String)> = metadata
+        .topics_and_types()
+        .into_iter()
+        .map(|(topic, data_type)| {
+            (
+                topic.to_string(),
+                topic_counts.get(topic).copied().unwrap_or(0),
+                data_type.to_string(),
+            )
+        })
+        .collect();
+
+    Summary {
+        fields,
+        types: types.into_iter().collect(),
+        topics: topics.into_iter().collect(),
+    }
+}
+
+fn markdown_table(out: &mut String, header: &[&str], rows: Vec<Vec<String>>) {
+    let escape = |cell: &str| cell.replace('|', "\\|");
+    out.push_str(&format!("| {} |\n", header.join(" | ")));
+    out.push_str(&format!("|{}\n", " --- |".repeat(header.len())));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| escape(cell)).collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_table(out: &mut String, header: &[&str], rows: Vec<Vec<String>>) {
+    out.push_str("<table>\n<thead><tr>");
+    for cell in header {
+        out.push_str(&format!("<th>{}</th>", escape_html(cell)));
+    }
+    out.push_str("</tr></thead>\n<tbody>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            out.push_str(&format!("<td>{}</td>", escape_html(&cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+}
+
+impl Summary {
+    fn render(self, table: fn(&mut String, &[&str], Vec<Vec<String>>)) -> String {
+        let mut out = String::new();
+        table(
+            &mut out,
+            &["field", "value"],
+            self.fields
+                .into_iter()
+                .map(|(name, value)| vec![name.to_string(), value])
+                .collect(),
+        );
+        out.push('\n');
+        table(
+            &mut out,
+            &["type", "md5sum"],
+            self.types
+                .into_iter()
+                .map(|(data_type, md5sum)| vec![data_type, md5sum])
+                .collect(),
+        );
+        out.push('\n');
+        table(
+            &mut out,
+            &["topic", "messages", "type"],
+            self.topics
+                .into_iter()
+                .map(|(topic, count, data_type)| vec![topic, count.to_string(), data_type])
+                .collect(),
+        );
+        out
+    }
+}
+
+/// Formats a byte count like rosbag, e.g. `11.5 KB`.
+fn rosbag_size(bytes: f64) -> String {
+    let mut size = bytes;
+    for suffix in ["KB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"] {
+        size /= 1024.0;
+        if size < 1024.0 {
+            return format!("{size:.1} {suffix}");
+        }
+    }
+    format!("{size:.1} YB")
+}
+
+/// Formats seconds since the epoch in the local timezone like rosbag, e.g. `Oct 16 2022 20:40:59.00`.
+fn rosbag_time(secs: f64) -> String {
+    let fraction = format!("{:.2}", secs - secs.trunc());
+    let time = Local
+        .timestamp_opt(secs as i64, 0)
+        .single()
+        .map_or_else(String::new, |time| {
+            time.format("%b %d %Y %H:%M:%S").to_string()
+        });
+    format!("{time}{}", &fraction[1..])
+}
+
+/// Formats a duration in seconds like rosbag, e.g. `1:39s (99s)`.
+fn rosbag_duration(duration: f64) -> String {
+    let secs = duration % 60.0;
+    let mins = (duration / 60.0) as u64;
+    let hours = mins / 60;
+    if hours > 0 {
+        format!(
+            "{hours}hr {}:{:02}s ({}s)",
+            mins % 60,
+            secs as u64,
+            duration as u64
+        )
+    } else if mins > 0 {
+        format!("{mins}:{:02}s ({}s)", secs as u64, duration as u64)
+    } else {
+        format!("{duration:.1}s")
+    }
+}
+
+/// Formats a scalar like Python's `str`, which rosbag uses to print keys.
+#[cfg(feature = "yaml")]
+fn python_str(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::Bool(true) => "True".to_string(),
+        serde_yaml::Value::Bool(false) => "False".to_string(),
+        serde_yaml::Value::Number(number) if number.is_f64() => {
+            let float = number.as_f64().unwrap_or_default();
+            if float != 0.0 && !(1e-4..1e16).contains(&float.abs()) {
+                // like `1e-06`, with at least two exponent digits
+                let formatted = format!("{float:e}");
+                let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+                let (sign, digits) = exponent
+                    .strip_prefix('-')
+                    .map_or(("+", exponent), |digits| ("-", digits));
+                format!("{mantissa}e{sign}{digits:0>2}")
+            } else if float.fract() == 0.0 {
+                format!("{float:.1}")
+            } else {
+                float.to_string()
+            }
+        }
+        serde_yaml::Value::Number(number) => number.to_string(),
+        serde_yaml::Value::String(string) => string.clone(),
+        serde_yaml::Value::Null => "None".to_string(),
+        other => serde_yaml::to_string(other).unwrap_or_default(),
+    }
+}
+
+/// Renders a YAML value like rosbag's `print_yaml`: lists as `- ` items, mappings as `key: value` lines.
+#[cfg(feature = "yaml")]
+fn rosbag_yaml_value(value: &serde_yaml::Value, indent: usize) -> String {
+    let indent_str = "  ".repeat(indent);
+    match value {
+        serde_yaml::Value::Sequence(items) => items
+            .iter()
+            .map(|item| format!("{indent_str}- {}\n", rosbag_yaml_value(item, indent + 1)))
+            .collect(),
+        serde_yaml::Value::Mapping(mapping) => mapping
+            .iter()
+            .map(|(key, value)| format!("{}: {}", python_str(key), python_str(value)))
+            .join(&format!("\n{indent_str}")),
+        other => format!("{indent_str}{}", python_str(other)),
+    }
+}
+
+/// The bag's contents, as rosbag gathers them for `rosbag info`.
+struct RosbagInfo {
+    start: f64,
+    end: f64,
+    /// (name, chunk count, compressed bytes, uncompressed bytes), most used first.
+    compressions: Vec<(String, usize, u64, u64)>,
+    /// (type, md5sum) of the first connection of each type.
+    types: BTreeMap<String, String>,
+    /// (topic, (message count, type of its first connection, connection count))
+    topics: BTreeMap<String, (usize, String, usize)>,
+}
+
+impl RosbagInfo {
+    /// `None` for bags without chunks, which rosbag only prints the path, version, and size of.
+    fn new(metadata: &BagMetadata) -> Option<Self> {
+        let start = f64::from(metadata.start_time()?);
+        let end = f64::from(metadata.end_time()?);
+
+        let compressions = metadata
+            .compression_info()
+            .into_iter()
+            .map(|info| {
+                (
+                    info.name,
+                    info.chunk_count,
+                    info.total_compressed,
+                    info.total_uncompressed,
+                )
+            })
+            .sorted_by(|a, b| (b.1, &b.0).cmp(&(a.1, &a.0)))
+            .collect();
+
+        let mut types = BTreeMap::new();
+        let mut topics = BTreeMap::new();
+        let topic_counts = metadata.topic_message_counts();
+        for data in metadata.connection_data.values() {
+            types
+                .entry(data.data_type.clone())
+                .or_insert_with(|| data.md5sum.clone());
+            topics
+                .entry(data.topic.clone())
+                .or_insert_with(|| {
+                    let count = topic_counts.get(&data.topic).copied().unwrap_or(0);
+                    (count, data.data_type.clone(), 0)
+                })
+                .2 += 1;
+        }
+
+        Some(RosbagInfo {
+            start,
+            end,
+            compressions,
+            types,
+            topics,
+        })
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.compressions.iter().map(|info| info.1).sum()
+    }
+
+    /// Whether any chunk is compressed, when rosbag also prints the total sizes.
+    fn is_compressed(&self) -> bool {
+        self.compressions.iter().any(|info| info.0 != "none")
+    }
+
+    fn compressed_bytes(&self) -> u64 {
+        self.compressions.iter().map(|info| info.2).sum()
+    }
+
+    fn uncompressed_bytes(&self) -> u64 {
+        self.compressions.iter().map(|info| info.3).sum()
+    }
+}
+
+impl BagMetadata {
+    /// Renders the bag's info exactly as `rosbag info` prints it, including times in the local timezone and
+    /// trailing spaces, with a final newline.
+    pub fn to_rosbag_info(&self) -> String {
+        let mut rows: Vec<(&str, String)> = Vec::new();
+        if let Some(path) = &self.file_path {
+            rows.push(("path", path.to_string_lossy().into_owned()));
+        }
+        rows.push(("version", self.version.clone()));
+
+        match RosbagInfo::new(self) {
+            None => rows.push(("size", rosbag_size(self.num_bytes as f64))),
+            Some(info) => {
+                let duration = info.end - info.start;
+                rows.push(("duration", rosbag_duration(duration)));
+                rows.push((
+                    "start",
+                    format!("{} ({:.2})", rosbag_time(info.start), info.start),
+                ));
+                rows.push((
+                    "end",
+                    format!("{} ({:.2})", rosbag_time(info.end), info.end),
+                ));
+                rows.push(("size", rosbag_size(self.num_bytes as f64)));
+                rows.push(("messages", self.message_count().to_string()));
+
+                let chunk_count = info.chunk_count();
+                let compressions = info
+                    .compressions
+                    .iter()
+                    .map(
+                        |(name, count, compressed, uncompressed)| match name.as_str() {
+                            "none" => format!("{name} [{count}/{chunk_count} chunks]"),
+                            _ => format!(
+                                "{name} [{count}/{chunk_count} chunks; {:.2}%]",
+                                100.0 * *compressed as f64 / *uncompressed as f64
+                            ),
+                        },
+                    )
+                    .join(", ");
+                rows.push(("compression", compressions));
+
+                if info.is_compressed() {
+                    let uncompressed = info.uncompressed_bytes() as f64;
+                    let compressed = info.compressed_bytes() as f64;
+                    let uncompressed_size = rosbag_size(uncompressed);
+                    let compressed_size = rosbag_size(compressed);
+                    let size_len = uncompressed_size.len().max(compressed_size.len());
+                    if duration > 0.0 {
+                        let uncompressed_rate = rosbag_size(uncompressed / duration);
+                        let compressed_rate = rosbag_size(compressed / duration);
+                        let rate_len = uncompressed_rate.len().max(compressed_rate.len());
+                        rows.push((
+                            "uncompressed",
+                            format!(
+                                "{uncompressed_size:>size_len$} @ {uncompressed_rate:>rate_len$}/s"
+                            ),
+                        ));
+                        rows.push((
+                            "compressed",
+                            format!(
+                                "{compressed_size:>size_len$} @ {compressed_rate:>rate_len$}/s ({:.2}%)",
+                                100.0 * compressed / uncompressed
+                            ),
+                        ));
+                    } else {
+                        rows.push(("uncompressed", format!("{uncompressed_size:>size_len$}")));
+                        rows.push(("compressed", format!("{compressed_size:>size_len$}")));
+                    }
+                }
+
+                for (i, (data_type, md5sum)) in info.types.iter().enumerate() {
+                    let field = if i == 0 { "types" } else { "" };
+                    rows.push((field, format!("{data_type} [{md5sum}]")));
+                }
+
+                let topic_len = info.topics.keys().map(String::len).max().unwrap_or(0);
+                let count_len = info
+                    .topics
+                    .values()
+                    .map(|(count, _, _)| count.to_string().len())
+                    .max()
+                    .unwrap_or(0);
+                let type_len = info
+                    .topics
+                    .values()
+                    .map(|(_, data_type, _)| data_type.len())
+                    .max()
+                    .unwrap_or(0);
+                for (i, (topic, (count, data_type, connections))) in info.topics.iter().enumerate()
+                {
+                    let field = if i == 0 { "topics" } else { "" };
+                    let unit = if *count > 1 { "msgs" } else { "msg " };
+                    // rosbag leaves room for frequencies, which it only prints with `--freq`
+                    let mut row = format!(
+                        "{topic:<topic_len$}   {count:>count_len$} {unit}    : {data_type:<type_len$}"
+                    );
+                    if *connections > 1 {
+                        row.push_str(&format!(" ({connections} connections)"));
+                    }
+                    rows.push((field, row));
+                }
+            }
+        }
+
+        let width = rows.iter().map(|(field, _)| field.len()).max().unwrap_or(0) + 1;
+        let mut out = String::new();
+        for (field, value) in rows {
+            let field = if field.is_empty() {
+                String::new()
+            } else {
+                format!("{field}:")
+            };
+            out.push_str(&format!("{field:<width$} {value}\n"));
+        }
+        format!("{}\n", out.trim_end())
+    }
+
+    /// Renders the bag's info exactly as `rosbag info --yaml` prints it.
+    pub fn to_rosbag_yaml(&self) -> String {
+        let mut out = String::new();
+        if let Some(path) = &self.file_path {
+            out.push_str(&format!("path: {}\n", path.to_string_lossy()));
+        }
+        out.push_str(&format!("version: {}\n", self.version));
+
+        let Some(info) = RosbagInfo::new(self) else {
+            out.push_str(&format!("size: {}\nindexed: False\n", self.num_bytes));
+            return out;
+        };
+        out.push_str(&format!("duration: {:.6}\n", info.end - info.start));
+        out.push_str(&format!("start: {:.6}\n", info.start));
+        out.push_str(&format!("end: {:.6}\n", info.end));
+        out.push_str(&format!("size: {}\n", self.num_bytes));
+        out.push_str(&format!("messages: {}\n", self.message_count()));
+        out.push_str("indexed: True\n");
+        let main_compression = info.compressions.first().map_or("none", |info| &info.0);
+        out.push_str(&format!("compression: {main_compression}\n"));
+        if info.is_compressed() {
+            out.push_str(&format!("uncompressed: {}\n", info.uncompressed_bytes()));
+            out.push_str(&format!("compressed: {}\n", info.compressed_bytes()));
+        }
+
+        if !info.types.is_empty() {
+            out.push_str("types:\n");
+        }
+        for (data_type, md5sum) in info.types.iter() {
+            out.push_str(&format!("    - type: {data_type}\n      md5: {md5sum}\n"));
+        }
+        if !info.topics.is_empty() {
+            out.push_str("topics:\n");
+        }
+        for (topic, (count, data_type, connections)) in info.topics.iter() {
+            out.push_str(&format!(
+                "    - topic: {topic}\n      type: {data_type}\n      messages: {count}\n"
+            ));
+            if *connections > 1 {
+                out.push_str(&format!("      connections: {connections}\n"));
+            }
+        }
+        out
+    }
+
+    /// Looks a key up in [BagMetadata::to_rosbag_yaml] and renders its value exactly as `rosbag info --yaml --key`
+    /// prints it, with a final newline. Keys are attributes and indexes like `messages` or `topics[0].type`, and
+    /// `None` is returned for keys the YAML doesn't have. Requires the `yaml` feature.
+    #[cfg(feature = "yaml")]
+    pub fn rosbag_yaml_key(&self, key: &str) -> Option<String> {
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&self.to_rosbag_yaml()).ok()?;
+        let mut value = &yaml;
+        for part in key.split('.') {
+            let mut indexes = part.split('[');
+            value = value.as_mapping()?.get(indexes.next()?.trim())?;
+            for index in indexes {
+                let index: isize = index.strip_suffix(']')?.trim().parse().ok()?;
+                let items = value.as_sequence()?;
+                let index = match index < 0 {
+                    true => items.len().checked_sub(index.unsigned_abs())?,
+                    false => index as usize,
+                };
+                value = items.get(index)?;
+            }
+        }
+        Some(format!("{}\n", rosbag_yaml_value(value, 0)))
+    }
+
+    /// Renders the bag's summary, types, and topics as Markdown tables.
+    pub fn to_markdown(&self) -> String {
+        summarize(self).render(markdown_table)
+    }
+
+    /// Renders the bag's summary, types, and topics as HTML tables, escaping any markup in names.
+    pub fn to_html(&self) -> String {
+        summarize(self).render(html_table)
+    }
+}
@@ -0,0 +1,281 @@
+//! Following a bag while it is recorded, like `tail -f`, as `frost tail` does.
+//!
+//! While recording, `rosbag record` writes to a `.active` file without an index: chunks are appended as they are
+//! closed, and the index is only written once recording stops. A [Tail] reads the records appended since its last
+//! [poll](Tail::poll) and returns the messages of the chunks completed since as a [DecompressedBag], so they can be
+//! queried like any other bag. A chunk the recorder is still writing into is picked up once it has been closed.
+//!
+//! This is best-effort: without an index, connections are only known from the records inside the chunks, and
+//! messages on connections which haven't been seen are skipped.
+//!
+//! [Tail::follow] polls until the recording stops, waiting on a [Clock] in between, which tests can replace with a
+//! [MockClock](crate::clock::MockClock).
+//!
+//! Example
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use frost::clock::SystemClock;
+//! use frost::query::Query;
+//! use frost::tail::Tail;
+//!
+//! let mut tail = Tail::open("robot.bag.active").unwrap();
+//! let query = Query::new().with_topics(["/rosout"]);
+//! tail.follow(&SystemClock, Duration::from_millis(500), |bag| {
+//!     for msg_view in bag.read_messages(&query)? {
+//!         println!("{}: {}", msg_view.time, msg_view.topic);
+//!     }
+//!     Ok(())
+//! })
+//! .unwrap();
+//! ```
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::edit::read_index_pos;
+use crate::errors::Error;
+use crate::time::{self, Time};
+use crate::util::parsing::parse_le_u32_at;
+use crate::writer::VERSION_LINE;
+use crate::{
+    decompress_chunk, read_header_op, version_check, BagHeader, BagMetadata, ChunkHeader,
+    ChunkHeaderLoc, ChunkMetadata, ConnectionData, ConnectionHeader, ConnectionID, DecompressedBag,
+    IndexData, MessageDataHeader, OpCode, ParseError,
+};
+
+/// Reads the chunks appended to a bag as it is recorded, see [the module docs](self).
+pub struct Tail {
+    path: PathBuf,
+    file: File,
+    version: Option<String>,
+    /// the position of the first record which hasn't been read
+    pos: u64,
+    /// the size of the file when it was last polled
+    len: u64,
+    index_pos: u64,
+    connections: BTreeMap<ConnectionID, ConnectionData>,
+}
+
+impl Tail {
+    /// Starts following the bag at `path` from its beginning.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        Ok(Tail {
+            path,
+            file,
+            version: None,
+            pos: 0,
+            len: 0,
+            index_pos: 0,
+            connections: BTreeMap::new(),
+        })
+    }
+
+    /// Reads the chunks completed since the last poll, returning their messages, or None if there are none yet.
+    pub fn poll(&mut self) -> Result<Option<DecompressedBag>, Error> {
+        self.len = self.file.metadata()?.len();
+        if self.version.is_none() {
+            if self.len < VERSION_LINE.len() as u64 {
+                return Ok(None);
+            }
+            self.file.seek(SeekFrom::Start(0))?;
+            self.version = Some(version_check(&mut self.file)?);
+            self.pos = VERSION_LINE.len() as u64;
+        }
+
+        let mut chunk_metadata = BTreeMap::new();
+        let mut chunk_bytes = BTreeMap::new();
+        let mut index_data: BTreeMap<ConnectionID, Vec<IndexData>> = BTreeMap::new();
+        while let Some((header, data_pos, data_len)) = self.next_record()? {
+            match read_header_op(&header)? {
+                OpCode::BagHeader => self.index_pos = BagHeader::from(&header)?.index_pos,
+                OpCode::ChunkHeader => {
+                    let chunk = ChunkHeader::from(&header, self.pos, data_pos, data_len)?;
+                    // the recorder fills in the sizes of a chunk once it closes it
+                    if data_len == 0 || chunk.uncompressed_size == 0 {
+                        break;
+                    }
+                    let mut metadata = ChunkMetadata {
+                        compression: chunk.compression,
+                        dict_id: chunk.dict_id,
+                        encryption: chunk.encryption,
+                        uncompressed_size: chunk.uncompressed_size,
+                        compressed_size: chunk.compressed_size,
+                        chunk_header_pos: chunk.chunk_header_pos,
+                        chunk_data_pos: chunk.chunk_data_pos,
+                        start_time: time::ZERO,
+                        end_time: time::ZERO,
+                        connection_count: 0,
+                        message_counts: BTreeMap::new(),
+                    };
+                    let bytes =
+                        decompress_chunk(&metadata, &self.read_at(data_pos, data_len)?, &[])?;
+                    self.index_chunk(&mut metadata, &bytes, &mut index_data)?;
+                    chunk_bytes.insert(metadata.chunk_header_pos, bytes);
+                    chunk_metadata.insert(metadata.chunk_header_pos, metadata);
+                }
+                OpCode::ConnectionHeader => {
+                    let data = self.read_at(data_pos, data_len)?;
+                    self.add_connection(&header, &data)?;
+                }
+                // the index, which is rebuilt from the chunks
+                OpCode::IndexDataHeader | OpCode::ChunkInfoHeader => {}
+                OpCode::MessageData => {
+                    eprintln!("unexpected `MessageData` op at the record level");
+                    return Err(ParseError::InvalidOpCode.into());
+                }
+            }
+            self.pos = data_pos + data_len as u64;
+        }
+        // the recorder writes the index position into the bag header when it stops
+        if self.pos > VERSION_LINE.len() as u64 {
+            self.index_pos = read_index_pos(&mut self.file)?;
+        }
+
+        if chunk_metadata.is_empty() {
+            return Ok(None);
+        }
+        index_data.retain(|conn_id, _| self.connections.contains_key(conn_id));
+        for index in index_data.values_mut() {
+            index.sort_by_key(|data| data.time);
+        }
+        Ok(Some(DecompressedBag {
+            metadata: BagMetadata {
+                file_path: Some(self.path.clone()),
+                version: self.version.clone().unwrap_or_default(),
+                chunk_metadata,
+                connection_data: self.connections.clone(),
+                index_data,
+                num_bytes: self.pos,
+                warnings: Vec::new(),
+            },
+            chunk_bytes,
+        }))
+    }
+
+    /// Polls until the bag is [finished](Tail::is_finished), passing the messages of each poll to `on_bag` and
+    /// sleeping on `clock` for `interval` whenever nothing new has been completed.
+    pub fn follow(
+        &mut self,
+        clock: &dyn Clock,
+        interval: Duration,
+        mut on_bag: impl FnMut(DecompressedBag) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        loop {
+            let bag = self.poll()?;
+            if self.is_finished() {
+                return bag.map_or(Ok(()), on_bag);
+            }
+            match bag {
+                Some(bag) => on_bag(bag)?,
+                None => clock.sleep(interval),
+            }
+        }
+    }
+
+    /// True once the recorder has written the bag's index and every record has been read, so the bag won't grow.
+    pub fn is_finished(&self) -> bool {
+        self.index_pos != 0 && self.pos >= self.index_pos && self.pos >= self.len
+    }
+
+    /// The number of bytes of the bag read so far.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Reads the header of the record at the current position, returning it with the position and length of the
+    /// record's data, or None if the record hasn't been completely written yet.
+    fn next_record(&mut self) -> Result<Option<(Vec<u8>, u64, u32)>, Error> {
+        if self.pos + 4 > self.len {
+            return Ok(None);
+        }
+        let header_len = parse_le_u32_at(&self.read_at(self.pos, 4)?, 0)?;
+        let header_pos = self.pos + 4;
+        if header_pos + header_len as u64 + 4 > self.len {
+            return Ok(None);
+        }
+        let header = self.read_at(header_pos, header_len)?;
+        let data_pos = header_pos + header_len as u64 + 4;
+        let data_len = parse_le_u32_at(&self.read_at(data_pos - 4, 4)?, 0)?;
+        if data_pos + data_len as u64 > self.len {
+            return Ok(None);
+        }
+        Ok(Some((header, data_pos, data_len)))
+    }
+
+    fn read_at(&mut self, pos: u64, len: u32) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0u8; len as usize];
+        self.file.seek(SeekFrom::Start(pos))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn add_connection(&mut self, header: &[u8], data: &[u8]) -> Result<(), Error> {
+        let connection_header = ConnectionHeader::from(header)?;
+        let connection = ConnectionData::from(
+            data,
+            connection_header.connection_id,
+            connection_header.topic,
+        )?;
+        self.connections
+            .insert(connection_header.connection_id, connection);
+        Ok(())
+    }
+
+    /// Indexes the messages of a decompressed chunk, as the index data records following it would have.
+    fn index_chunk(
+        &mut self,
+        metadata: &mut ChunkMetadata,
+        bytes: &[u8],
+        index_data: &mut BTreeMap<ConnectionID, Vec<IndexData>>,
+    ) -> Result<(), Error> {
+        let chunk_header_pos: ChunkHeaderLoc = metadata.chunk_header_pos;
+        let mut start_time: Option<Time> = None;
+        let mut end_time: Option<Time> = None;
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let header_pos = offset + 4;
+            let data_pos = header_pos + parse_le_u32_at(bytes, offset)? as usize + 4;
+            let data_end = data_pos + parse_le_u32_at(bytes, data_pos - 4)? as usize;
+            let (Some(header), Some(data)) = (
+                bytes.get(header_pos..data_pos - 4),
+                bytes.get(data_pos..data_end),
+            ) else {
+                eprintln!(
+                    "record at {offset} extends past the end of the chunk at {chunk_header_pos}"
+                );
+                return Err(ParseError::UnexpectedEOF.into());
+            };
+
+            match read_header_op(header)? {
+                OpCode::ConnectionHeader => self.add_connection(header, data)?,
+                OpCode::MessageData => {
+                    let message = MessageDataHeader::from(header)?;
+                    index_data.entry(message.conn).or_default().push(IndexData {
+                        conn_id: message.conn,
+                        chunk_header_pos,
+                        time: message.time,
+                        offset: offset as u32,
+                    });
+                    *metadata.message_counts.entry(message.conn).or_default() += 1;
+                    start_time = Some(start_time.map_or(message.time, |t| t.min(message.time)));
+                    end_time = Some(end_time.map_or(message.time, |t| t.max(message.time)));
+                }
+                op => {
+                    eprintln!("unexpected {op:?} op in the chunk at {chunk_header_pos}");
+                    return Err(ParseError::InvalidOpCode.into());
+                }
+            }
+            offset = data_end;
+        }
+        metadata.start_time = start_time.unwrap_or(time::ZERO);
+        metadata.end_time = end_time.unwrap_or(time::ZERO);
+        metadata.connection_count = metadata.message_counts.len() as u32;
+        Ok(())
+    }
+}
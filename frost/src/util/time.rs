@@ -1,9 +1,10 @@
 use std::fmt;
 use std::time::Duration;
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
 
 use crate::errors::ParseError;
+use crate::stable;
 
 use super::parsing;
 
@@ -62,17 +63,61 @@ impl Time {
     fn new(secs: u32, nsecs: u32) -> Time {
         Time { secs, nsecs }
     }
-    pub fn from(buf: &[u8]) -> Result<Time, ParseError> {
+    /// Parses a time as stored in bags, seconds then nanoseconds as little-endian `u32`s.
+    pub fn from_le_bytes(buf: &[u8]) -> Result<Time, ParseError> {
         let secs = parsing::parse_le_u32(buf)?;
         let nsecs = parsing::parse_le_u32_at(buf, 4)?;
         Ok(Time { secs, nsecs })
     }
+    #[deprecated(
+        since = "0.5.0",
+        note = "shadows `From::from`, use `Time::from_le_bytes` instead"
+    )]
+    pub fn from(buf: &[u8]) -> Result<Time, ParseError> {
+        stable::deprecated_use(&stable::TIME_FROM);
+        Time::from_le_bytes(buf)
+    }
+    /// Returns the time from `earlier` to `self`, or zero if `earlier` is later, like [std::time::Instant::duration_since].
+    pub fn duration_since(&self, earlier: &Time) -> Duration {
+        Duration::from(self).saturating_sub(Duration::from(earlier))
+    }
+    #[deprecated(
+        since = "0.5.0",
+        note = "panics if `other` is later, use `Time::duration_since` instead"
+    )]
     pub fn dur(&self, other: &Time) -> Duration {
+        stable::deprecated_use(&stable::TIME_DUR);
         Duration::from(self) - Duration::from(other)
     }
+    /// Returns the time `dur` after `self`, or `None` if it can not be represented.
+    pub fn checked_add(&self, dur: Duration) -> Option<Time> {
+        let total = Duration::from(self).checked_add(dur)?;
+        Some(Time {
+            secs: u32::try_from(total.as_secs()).ok()?,
+            nsecs: total.subsec_nanos(),
+        })
+    }
+    /// Returns the seconds from `start` to `self`, like `37.2` for a message 37.2s into a bag starting at `start`,
+    /// negative if `self` is earlier.
+    pub fn relative_to(&self, start: &Time) -> f64 {
+        let ns = (i64::from(self.secs) - i64::from(start.secs)) * 1_000_000_000
+            + (i64::from(self.nsecs) - i64::from(start.nsecs));
+        ns as f64 * NS_TO_S
+    }
+    /// Returns the time in UTC, or `None` if `nsecs` is a second or more, as in garbage times of corrupt bags.
     pub fn as_datetime(&self) -> Option<DateTime<Utc>> {
+        // chrono reads nanoseconds over a second as a leap second
+        if self.nsecs >= 1_000_000_000 {
+            return None;
+        }
         Utc.timestamp_opt(self.secs as i64, self.nsecs).single()
     }
+    /// Formats the time as RFC 3339 in UTC with nanoseconds, e.g. `2022-10-16T20:40:59.000001000Z`, or `None` if it
+    /// is out of range like [Time::as_datetime].
+    pub fn as_rfc3339(&self) -> Option<String> {
+        self.as_datetime()
+            .map(|datetime| datetime.to_rfc3339_opts(SecondsFormat::Nanos, true))
+    }
 }
 
 impl Ord for Time {
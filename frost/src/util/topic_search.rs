@@ -0,0 +1,104 @@
+//! Finding topics by partial or misspelled names, for "did you mean" errors and topic pickers.
+//!
+//! [BagMetadata::find_topic] ranks a bag's topics against a query: the exact topic first, then topics starting with
+//! the query, then those containing it, then fuzzy matches, which contain the query's characters in order or are a
+//! few typos away from it. Only exact matches are case-sensitive. Within each [MatchKind], topics closer to the query
+//! come first.
+//!
+//! Example
+//! ```rust
+//! use frost::topic_search::MatchKind;
+//! use frost::BagMetadata;
+//!
+//! let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let matches = metadata.find_topic("/chater");
+//! assert_eq!(matches[0].topic, "/chatter");
+//! assert_eq!(matches[0].kind, MatchKind::Fuzzy);
+//!
+//! let matches = metadata.find_topic("/t");
+//! assert_eq!(matches[0].topic, "/time");
+//! assert_eq!(matches[0].kind, MatchKind::Prefix);
+//! ```
+use crate::BagMetadata;
+
+/// How a topic matched a query, from the best kind of match to the worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    Substring,
+    Fuzzy,
+}
+
+/// A topic found by [BagMetadata::find_topic].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopicMatch {
+    pub topic: String,
+    pub kind: MatchKind,
+    /// The number of characters to insert, delete, replace or swap with their neighbour to turn the query into the
+    /// topic, ignoring case.
+    pub distance: usize,
+}
+
+impl BagMetadata {
+    /// Returns the topics matching `query`, best first, see the [module docs](self).
+    pub fn find_topic(&self, query: &str) -> Vec<TopicMatch> {
+        let lowercase_query = query.to_lowercase();
+        let max_typos = (lowercase_query.chars().count() / 3).max(1);
+        let mut matches: Vec<_> = self
+            .topics()
+            .into_iter()
+            .filter_map(|topic| {
+                let lowercase_topic = topic.to_lowercase();
+                let distance = edit_distance(&lowercase_query, &lowercase_topic);
+                let kind = if topic == query {
+                    MatchKind::Exact
+                } else if lowercase_topic.starts_with(&lowercase_query) {
+                    MatchKind::Prefix
+                } else if lowercase_topic.contains(&lowercase_query) {
+                    MatchKind::Substring
+                } else if distance <= max_typos
+                    || is_subsequence(&lowercase_query, &lowercase_topic)
+                {
+                    MatchKind::Fuzzy
+                } else {
+                    return None;
+                };
+                Some(TopicMatch {
+                    topic: topic.to_string(),
+                    kind,
+                    distance,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| (a.kind, a.distance, &a.topic).cmp(&(b.kind, b.distance, &b.topic)));
+        matches
+    }
+}
+
+/// Whether the characters of `query` appear in `text` in the same order.
+fn is_subsequence(query: &str, text: &str) -> bool {
+    let mut text = text.chars();
+    query.chars().all(|c| text.any(|t| t == c))
+}
+
+/// The edit distance between `a` and `b`, in characters, counting swapping two adjacent characters as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j - 1] + cost)
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,144 @@
+//! Topics grouped by namespace, for bags with too many topics to make sense of as a flat list.
+//!
+//! Example
+//! ```rust
+//! use frost::BagMetadata;
+//!
+//! let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let tree = metadata.topic_tree();
+//! assert_eq!(tree.topic_count(), 3);
+//! assert_eq!(tree.total_message_count(), 300);
+//! assert_eq!(tree.get("/chatter").unwrap().message_count(), Some(100));
+//! ```
+use std::collections::BTreeMap;
+
+use crate::BagMetadata;
+
+/// A namespace segment of a topic name, see the [module docs](self).
+///
+/// A node is a topic, a namespace, or both, as in a bag with `/camera` and `/camera/info`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopicTree {
+    name: String,
+    topic: Option<String>,
+    message_count: usize,
+    children: BTreeMap<String, TopicTree>,
+}
+
+impl TopicTree {
+    /// Builds a tree from topic names and their message counts.
+    pub fn new<'a, I: IntoIterator<Item = (&'a str, usize)>>(topics: I) -> Self {
+        let mut root = TopicTree::default();
+        for (topic, count) in topics {
+            let mut node = &mut root;
+            for segment in topic.split('/').filter(|segment| !segment.is_empty()) {
+                node = node
+                    .children
+                    .entry(segment.to_string())
+                    .or_insert_with(|| TopicTree {
+                        name: segment.to_string(),
+                        ..Default::default()
+                    });
+            }
+            node.topic = Some(topic.to_string());
+            node.message_count += count;
+        }
+        root
+    }
+
+    /// The last segment of the namespace, which is empty for the root.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The full topic name, if this node is a topic and not only a namespace.
+    pub fn topic(&self) -> Option<&str> {
+        self.topic.as_deref()
+    }
+
+    /// The number of messages on this node's topic, if it is one.
+    pub fn message_count(&self) -> Option<usize> {
+        self.topic.as_ref().map(|_| self.message_count)
+    }
+
+    /// The number of messages on this node's topic and every topic beneath it.
+    pub fn total_message_count(&self) -> usize {
+        self.message_count
+            + self
+                .children
+                .values()
+                .map(|child| child.total_message_count())
+                .sum::<usize>()
+    }
+
+    /// The number of topics at or beneath this node.
+    pub fn topic_count(&self) -> usize {
+        usize::from(self.topic.is_some())
+            + self
+                .children
+                .values()
+                .map(|child| child.topic_count())
+                .sum::<usize>()
+    }
+
+    /// The nodes directly beneath this one, sorted by name.
+    pub fn children(&self) -> impl Iterator<Item = &TopicTree> {
+        self.children.values()
+    }
+
+    /// Returns the node for a topic or namespace relative to this one, like `/camera` or `camera/left`.
+    pub fn get(&self, path: &str) -> Option<&TopicTree> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |node, segment| node.children.get(segment))
+    }
+}
+
+impl BagMetadata {
+    /// Returns the bag's topics grouped by namespace, with message counts per topic and subtree.
+    pub fn topic_tree(&self) -> TopicTree {
+        let counts = self.topic_message_counts();
+        TopicTree::new(counts.iter().map(|(topic, count)| (topic.as_str(), *count)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopicTree;
+
+    #[test]
+    fn test_nested_namespaces() {
+        let tree = TopicTree::new([
+            ("/camera", 1),
+            ("/camera/left/image_raw", 10),
+            ("/camera/right/image_raw", 20),
+            ("/tf", 5),
+        ]);
+
+        assert_eq!(tree.topic_count(), 4);
+        assert_eq!(tree.total_message_count(), 36);
+        assert_eq!(
+            tree.children()
+                .map(|child| child.name())
+                .collect::<Vec<_>>(),
+            ["camera", "tf"]
+        );
+
+        let camera = tree.get("/camera").unwrap();
+        assert_eq!(camera.topic(), Some("/camera"));
+        assert_eq!(camera.message_count(), Some(1));
+        assert_eq!(camera.topic_count(), 3);
+        assert_eq!(camera.total_message_count(), 31);
+
+        let left = camera.get("left").unwrap();
+        assert_eq!(left.topic(), None);
+        assert_eq!(left.message_count(), None);
+        assert_eq!(left.total_message_count(), 10);
+        assert_eq!(
+            left.get("image_raw").unwrap().topic(),
+            Some("/camera/left/image_raw")
+        );
+
+        assert!(tree.get("/camera/center").is_none());
+    }
+}
@@ -0,0 +1,324 @@
+//! Extracting a pose trajectory from odometry, pose, or tf topics, with its length, speed, and stops.
+//!
+//! Poses are read from `nav_msgs/Odometry`, `geometry_msgs/PoseStamped`, `geometry_msgs/PoseWithCovarianceStamped`,
+//! and `tf2_msgs/TFMessage` messages, and are timestamped with their header's stamp, falling back to the receive time
+//! if the stamp is zero. Transforms are not chained: following a tf topic keeps the transforms whose
+//! `child_frame_id` is the requested frame, in whatever parent frame they were published in.
+//!
+//! Example
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use frost::DecompressedBag;
+//!
+//! let bag = DecompressedBag::from_file("drive.bag").unwrap();
+//! let trajectory = bag.trajectory("/odom", None).unwrap();
+//! println!("{:.1}m at {:.2}m/s", trajectory.length(), trajectory.average_speed());
+//! let stops = trajectory.stops(0.05, Duration::from_secs(2));
+//! for stop in &stops {
+//!     println!("stopped for {:?} at {}, {}", stop.duration(), stop.x, stop.y);
+//! }
+//! std::fs::write("drive.geojson", trajectory.to_geojson(&stops)).unwrap();
+//! ```
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::dynamic::{required, required_f64, Value};
+use crate::errors::{DecodeError, Error};
+use crate::query::Query;
+use crate::time::Time;
+use crate::DecompressedBag;
+
+const ODOMETRY_TYPE: &str = "nav_msgs/Odometry";
+const POSE_STAMPED_TYPE: &str = "geometry_msgs/PoseStamped";
+const POSE_WITH_COVARIANCE_STAMPED_TYPE: &str = "geometry_msgs/PoseWithCovarianceStamped";
+const TF_MESSAGE_TYPE: &str = "tf2_msgs/TFMessage";
+
+/// A position and heading at a point in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrajectoryPose {
+    pub time: Time,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// Rotation about z in radians.
+    pub yaw: f64,
+}
+
+impl TrajectoryPose {
+    fn distance(&self, other: &TrajectoryPose) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2))
+            .sqrt()
+    }
+}
+
+/// A stretch of a [Trajectory] spent below a speed, see [Trajectory::stops].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stop {
+    pub start: Time,
+    pub end: Time,
+    /// The position at the start of the stop.
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Stop {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(&self.start)
+    }
+}
+
+/// The file format of an exported trajectory, see [Trajectory::to_csv] and [Trajectory::to_geojson].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrajectoryFormat {
+    /// One `time,x,y,z,yaw` row per pose.
+    Csv,
+    /// A `LineString` of the poses and a `Point` per stop, in the trajectory's frame rather than WGS84.
+    GeoJson,
+}
+
+impl fmt::Display for TrajectoryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrajectoryFormat::Csv => write!(f, "csv"),
+            TrajectoryFormat::GeoJson => write!(f, "geojson"),
+        }
+    }
+}
+
+impl FromStr for TrajectoryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(TrajectoryFormat::Csv),
+            "geojson" => Ok(TrajectoryFormat::GeoJson),
+            other => Err(format!(
+                "unknown trajectory format '{other}', expected 'csv' or 'geojson'"
+            )),
+        }
+    }
+}
+
+/// Poses in time order, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trajectory {
+    /// The frame the poses are in, from the first pose's header.
+    pub frame_id: String,
+    pub poses: Vec<TrajectoryPose>,
+}
+
+impl Trajectory {
+    /// Adds the poses in a message decoded with [crate::dynamic], keeping only transforms or odometry
+    /// for `child_frame` if given. Following a `tf2_msgs/TFMessage` requires a `child_frame`.
+    pub fn push_value(
+        &mut self,
+        data_type: &str,
+        value: &Value,
+        received: Time,
+        child_frame: Option<&str>,
+    ) -> Result<(), DecodeError> {
+        let (header, pose) = match data_type {
+            ODOMETRY_TYPE => {
+                if child_frame.is_some()
+                    && required(value, data_type, "child_frame_id")?.as_str() != child_frame
+                {
+                    return Ok(());
+                }
+                let pose = required(value, data_type, "pose")?;
+                (
+                    required(value, data_type, "header")?,
+                    required(pose, "geometry_msgs/PoseWithCovariance", "pose")?,
+                )
+            }
+            POSE_STAMPED_TYPE => (
+                required(value, data_type, "header")?,
+                required(value, data_type, "pose")?,
+            ),
+            POSE_WITH_COVARIANCE_STAMPED_TYPE => {
+                let pose = required(value, data_type, "pose")?;
+                (
+                    required(value, data_type, "header")?,
+                    required(pose, "geometry_msgs/PoseWithCovariance", "pose")?,
+                )
+            }
+            TF_MESSAGE_TYPE => {
+                let child_frame = child_frame.ok_or_else(|| {
+                    DecodeError::InvalidField(format!(
+                        "a child frame is needed to follow {TF_MESSAGE_TYPE}"
+                    ))
+                })?;
+                let Value::Array(transforms) = required(value, data_type, "transforms")? else {
+                    return Err(DecodeError::InvalidField(format!(
+                        "{TF_MESSAGE_TYPE}.transforms is not an array"
+                    )));
+                };
+                for transform in transforms {
+                    let transform_type = "geometry_msgs/TransformStamped";
+                    if required(transform, transform_type, "child_frame_id")?.as_str()
+                        != Some(child_frame)
+                    {
+                        continue;
+                    }
+                    let inner = required(transform, transform_type, "transform")?;
+                    self.push(
+                        required(transform, transform_type, "header")?,
+                        required(inner, "geometry_msgs/Transform", "translation")?,
+                        required(inner, "geometry_msgs/Transform", "rotation")?,
+                        received,
+                    )?;
+                }
+                return Ok(());
+            }
+            other => return Err(DecodeError::UnsupportedEncoding(other.to_string())),
+        };
+        self.push(
+            header,
+            required(pose, "geometry_msgs/Pose", "position")?,
+            required(pose, "geometry_msgs/Pose", "orientation")?,
+            received,
+        )
+    }
+
+    fn push(
+        &mut self,
+        header: &Value,
+        position: &Value,
+        orientation: &Value,
+        received: Time,
+    ) -> Result<(), DecodeError> {
+        let time = match header.get("stamp") {
+            Some(Value::Time(stamp)) if *stamp != crate::time::ZERO => *stamp,
+            _ => received,
+        };
+        if self.poses.is_empty() {
+            self.frame_id = header
+                .get("frame_id")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+        }
+        let (qx, qy, qz, qw) = (
+            required_f64(orientation, "geometry_msgs/Quaternion", "x")?,
+            required_f64(orientation, "geometry_msgs/Quaternion", "y")?,
+            required_f64(orientation, "geometry_msgs/Quaternion", "z")?,
+            required_f64(orientation, "geometry_msgs/Quaternion", "w")?,
+        );
+        self.poses.push(TrajectoryPose {
+            time,
+            x: required_f64(position, "geometry_msgs/Point", "x")?,
+            y: required_f64(position, "geometry_msgs/Point", "y")?,
+            z: required_f64(position, "geometry_msgs/Point", "z")?,
+            yaw: (2.0 * (qw * qz + qx * qy)).atan2(1.0 - 2.0 * (qy * qy + qz * qz)),
+        });
+        Ok(())
+    }
+
+    /// Returns the time between the first and last poses.
+    pub fn duration(&self) -> Duration {
+        match (self.poses.first(), self.poses.last()) {
+            (Some(first), Some(last)) => last.time.duration_since(&first.time),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns the distance travelled in meters, summed between consecutive poses.
+    pub fn length(&self) -> f64 {
+        self.poses
+            .windows(2)
+            .map(|pair| pair[0].distance(&pair[1]))
+            .sum()
+    }
+
+    /// Returns the length over the duration in meters per second, or 0 for a trajectory with no duration.
+    pub fn average_speed(&self) -> f64 {
+        let secs = self.duration().as_secs_f64();
+        if secs > 0.0 {
+            self.length() / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the stretches of at least `min_duration` moving no faster than `max_speed` meters per second.
+    pub fn stops(&self, max_speed: f64, min_duration: Duration) -> Vec<Stop> {
+        let mut stops = Vec::new();
+        let mut current: Option<Stop> = None;
+        for pair in self.poses.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            let secs = to.time.duration_since(&from.time).as_secs_f64();
+            if secs <= 0.0 {
+                continue;
+            }
+            if from.distance(to) / secs <= max_speed {
+                current
+                    .get_or_insert(Stop {
+                        start: from.time,
+                        end: to.time,
+                        x: from.x,
+                        y: from.y,
+                    })
+                    .end = to.time;
+            } else if let Some(stop) = current.take() {
+                stops.push(stop);
+            }
+        }
+        stops.extend(current);
+        stops.retain(|stop| stop.duration() >= min_duration);
+        stops
+    }
+
+    /// Returns the poses as CSV with a `time,x,y,z,yaw` header.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time,x,y,z,yaw\n");
+        for pose in &self.poses {
+            csv.push_str(&format!(
+                "{}.{:09},{},{},{},{}\n",
+                pose.time.secs, pose.time.nsecs, pose.x, pose.y, pose.z, pose.yaw
+            ));
+        }
+        csv
+    }
+
+    /// Returns a GeoJSON `FeatureCollection` of the path and of `stops`, e.g. found with [Trajectory::stops].
+    pub fn to_geojson(&self, stops: &[Stop]) -> String {
+        let coordinates: Vec<_> = self.poses.iter().map(|pose| [pose.x, pose.y]).collect();
+        let mut features = vec![json!({
+            "type": "Feature",
+            "geometry": {"type": "LineString", "coordinates": coordinates},
+            "properties": {
+                "frame_id": self.frame_id,
+                "length": self.length(),
+                "duration": self.duration().as_secs_f64(),
+                "average_speed": self.average_speed(),
+            },
+        })];
+        features.extend(stops.iter().map(|stop| {
+            json!({
+                "type": "Feature",
+                "geometry": {"type": "Point", "coordinates": [stop.x, stop.y]},
+                "properties": {
+                    "start": f64::from(stop.start),
+                    "end": f64::from(stop.end),
+                    "duration": stop.duration().as_secs_f64(),
+                },
+            })
+        }));
+        json!({"type": "FeatureCollection", "features": features}).to_string()
+    }
+}
+
+impl DecompressedBag {
+    /// Extracts the trajectory published on `topic`, see the [trajectory module](self).
+    pub fn trajectory(&self, topic: &str, child_frame: Option<&str>) -> Result<Trajectory, Error> {
+        let mut trajectory = Trajectory::default();
+        for msg_view in self.read_messages(&Query::new().with_topics([topic]))? {
+            let data_type = &msg_view.connection().data_type;
+            trajectory.push_value(data_type, &msg_view.decode()?, msg_view.time, child_frame)?;
+        }
+        trajectory.poses.sort_by_key(|pose| pose.time);
+        Ok(trajectory)
+    }
+}
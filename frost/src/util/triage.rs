@@ -0,0 +1,378 @@
+//! A scored verdict on whether a recording is usable, for operators who need an answer rather than a set of reports.
+//!
+//! [BagMetadata::triage] runs the checks below using only the index, so no chunk is read or decompressed, and lists
+//! what they find as [Issue]s in plain language, most severe first:
+//!
+//! - truncation: a bag which doesn't parse, usually because the recorder was killed before writing its index, see
+//!   [TriageReport::from_file]
+//! - definition conflicts: a type recorded with several md5sums, or a topic recorded with several types
+//! - gaps: a topic going silent for at least [GAP_PERIODS] of its usual periods, and [MIN_GAP] in any case, or
+//!   stopping that long before the end of the recording
+//! - frequency: a topic which dropped more than [MAX_DROPPED] of its messages, estimated from its median period
+//! - time order and duplicate connection ids, see [BagMetadata::time_order_report] and [BagMetadata::warnings]
+//! - compression anomalies, see [BagMetadata::chunk_anomalies]
+//!
+//! The [score](TriageReport::score) starts at 100 and loses [Severity::penalty] points per issue, and the
+//! [verdict](TriageReport::verdict) follows from it.
+//!
+//! Example
+//! ```rust
+//! use frost::triage::{TriageReport, Verdict};
+//!
+//! let report = TriageReport::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! assert_eq!(report.score(), 100);
+//! assert_eq!(report.verdict(), Verdict::Usable);
+//!
+//! // a bag cut short by a crash
+//! let bytes = std::fs::read("./tests/fixtures/compressed_lz4.bag").unwrap();
+//! let report = TriageReport::from_bytes(&bytes[..bytes.len() / 2]).unwrap();
+//! assert_eq!(report.verdict(), Verdict::Unusable);
+//! println!("{}", report.issues[0]);
+//! ```
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::chunk_analysis::DEFAULT_FACTOR;
+use crate::errors::{Error, ErrorKind, ParseError};
+use crate::time::Time;
+use crate::{BagMetadata, BagWarning};
+
+/// How many of a topic's usual periods it must go silent for to be reported as a gap.
+pub const GAP_PERIODS: u32 = 10;
+/// The shortest silence reported as a gap, however fast the topic is.
+pub const MIN_GAP: Duration = Duration::from_secs(1);
+/// The share of its messages a topic may drop before it is reported.
+pub const MAX_DROPPED: f64 = 0.05;
+/// Topics with fewer messages have no usual period, and are not checked for gaps or drops.
+const MIN_MESSAGES: usize = 10;
+
+/// How much an [Issue] affects the recording's usability, from the least to the most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Parts of the recording are missing or suspicious, but the rest is fine.
+    Warning,
+    /// Some messages may not decode, or may be attributed to the wrong type.
+    Error,
+    /// The recording can't be used as is.
+    Critical,
+}
+
+impl Severity {
+    /// The points an issue of this severity takes off the score.
+    pub fn penalty(&self) -> u32 {
+        match self {
+            Severity::Warning => 10,
+            Severity::Error => 25,
+            Severity::Critical => 100,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Critical => "critical",
+        };
+        f.pad(s)
+    }
+}
+
+/// A problem found by [BagMetadata::triage].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// What is wrong, and what it means for the recording, in plain language.
+    pub description: String,
+}
+
+impl Issue {
+    fn new(severity: Severity, description: String) -> Self {
+        Issue {
+            severity,
+            description,
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.description)
+    }
+}
+
+/// Whether a recording is usable, from [TriageReport::verdict].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Verdict {
+    /// A score of at least 80.
+    Usable,
+    /// A score of at least 50.
+    UsableWithCaveats,
+    /// A score below 50, or any critical issue.
+    Unusable,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Verdict::Usable => "usable",
+            Verdict::UsableWithCaveats => "usable with caveats",
+            Verdict::Unusable => "unusable",
+        };
+        f.pad(s)
+    }
+}
+
+/// The issues found in a bag, see the [module docs](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TriageReport {
+    /// Most severe first.
+    pub issues: Vec<Issue>,
+}
+
+impl TriageReport {
+    /// Triages the bag at `file_path`, reporting a bag which doesn't parse as truncated rather than failing. Fails if
+    /// the file can't be read, or isn't a bag at all.
+    pub fn from_file<P>(file_path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        Self::from_metadata(BagMetadata::from_file(file_path))
+    }
+
+    /// Triages a bag in memory, like [TriageReport::from_file].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_metadata(BagMetadata::from_bytes(bytes))
+    }
+
+    fn from_metadata(metadata: Result<BagMetadata, Error>) -> Result<Self, Error> {
+        let error = match metadata {
+            Ok(metadata) => return Ok(metadata.triage()),
+            Err(error) => error,
+        };
+        let description = match error.kind() {
+            ErrorKind::Parse(ParseError::UnindexedBag) => {
+                "the bag has no index, as the recorder never closed it; `rosbag reindex` can rebuild it".to_string()
+            }
+            ErrorKind::Parse(e) => format!(
+                "the bag is truncated or corrupt ({e:?}), as when the recorder is killed or the disk fills up; \
+                 `rosbag reindex` may recover the messages written before it"
+            ),
+            _ => return Err(error),
+        };
+        Ok(TriageReport {
+            issues: vec![Issue::new(Severity::Critical, description)],
+        })
+    }
+
+    /// 100 for a bag without issues, down to 0.
+    pub fn score(&self) -> u32 {
+        let penalty: u32 = self.issues.iter().map(|i| i.severity.penalty()).sum();
+        100u32.saturating_sub(penalty)
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        let critical = self.issues.iter().any(|i| i.severity == Severity::Critical);
+        match self.score() {
+            _ if critical => Verdict::Unusable,
+            80.. => Verdict::Usable,
+            50.. => Verdict::UsableWithCaveats,
+            _ => Verdict::Unusable,
+        }
+    }
+}
+
+impl BagMetadata {
+    /// Checks the bag for the issues listed in the [triage module](crate::triage), using only its index.
+    pub fn triage(&self) -> TriageReport {
+        let mut issues = Vec::new();
+        if self.message_count() == 0 {
+            issues.push(Issue::new(
+                Severity::Critical,
+                "the bag has no messages".to_string(),
+            ));
+        }
+        self.definition_conflicts(&mut issues);
+        self.topic_timing(&mut issues);
+
+        let order = self.time_order_report();
+        if !order.is_monotonic() {
+            let count =
+                order.out_of_order_by_topic.values().sum::<usize>() + order.out_of_order_in_chunks;
+            issues.push(Issue::new(
+                Severity::Warning,
+                format!(
+                    "{count} messages were written out of time order, going back up to {:.3}s, which players may \
+                     replay late; `frost retain` rewrites the bag sorted",
+                    order.max_step_back.as_secs_f64()
+                ),
+            ));
+        }
+        for warning in self.warnings() {
+            match warning {
+                BagWarning::DuplicateConnectionId { .. } => issues.push(Issue::new(
+                    Severity::Warning,
+                    format!("{warning}, so its messages may be counted against the wrong topic"),
+                )),
+                BagWarning::NonUtf8Name { .. } => issues.push(Issue::new(
+                    Severity::Warning,
+                    format!(
+                        "{warning}, so tools matching topics by name may not find it; frost keeps the recorded bytes \
+                         when copying it"
+                    ),
+                )),
+            }
+        }
+
+        let anomalies = self.chunk_anomalies(DEFAULT_FACTOR).anomalies;
+        if !anomalies.is_empty() {
+            let topics: BTreeSet<&str> = anomalies
+                .iter()
+                .flat_map(|a| a.topics.iter().map(String::as_str))
+                .collect();
+            issues.push(Issue::new(
+                Severity::Warning,
+                format!(
+                    "{} chunks compress far better or worse than the rest, which can mean stuck sensors or corrupted \
+                     data, on {}; `frost check --analyze` lists them",
+                    anomalies.len(),
+                    topics.into_iter().collect::<Vec<_>>().join(", ")
+                ),
+            ));
+        }
+
+        // stable, so issues of a severity keep the order of the checks
+        issues.sort_by_key(|issue| Reverse(issue.severity));
+        TriageReport { issues }
+    }
+
+    /// Types recorded with several md5sums, and topics recorded with several types.
+    fn definition_conflicts(&self, issues: &mut Vec<Issue>) {
+        let mut md5sums = BTreeMap::<&str, BTreeSet<&str>>::new();
+        let mut types = BTreeMap::<&str, BTreeSet<&str>>::new();
+        for connection in self.connection_data.values() {
+            if connection.md5sum != "*" {
+                md5sums
+                    .entry(&connection.data_type)
+                    .or_default()
+                    .insert(&connection.md5sum);
+            }
+            types
+                .entry(&connection.topic)
+                .or_default()
+                .insert(&connection.data_type);
+        }
+        for (data_type, md5sums) in md5sums.into_iter().filter(|(_, m)| m.len() > 1) {
+            issues.push(Issue::new(
+                Severity::Error,
+                format!(
+                    "{data_type} was recorded with {} different definitions (md5sums {}), so code built against one \
+                     can't decode the others",
+                    md5sums.len(),
+                    md5sums.into_iter().collect::<Vec<_>>().join(", ")
+                ),
+            ));
+        }
+        for (topic, types) in types.into_iter().filter(|(_, t)| t.len() > 1) {
+            issues.push(Issue::new(
+                Severity::Error,
+                format!(
+                    "{topic} was recorded with several types ({}), so tools expecting one type will fail on it",
+                    types.into_iter().collect::<Vec<_>>().join(", ")
+                ),
+            ));
+        }
+    }
+
+    /// Gaps, topics stopping early, and dropped messages, from each topic's receive times.
+    fn topic_timing(&self, issues: &mut Vec<Issue>) {
+        let Some(end) = self.end_time() else {
+            return;
+        };
+        let mut times = BTreeMap::<&str, Vec<Time>>::new();
+        for (conn_id, index) in self.index_data.iter() {
+            let Some(connection) = self.connection_data.get(conn_id) else {
+                continue;
+            };
+            times
+                .entry(&connection.topic)
+                .or_default()
+                .extend(index.iter().map(|data| data.time));
+        }
+
+        for (topic, mut times) in times {
+            if times.len() < MIN_MESSAGES {
+                continue;
+            }
+            times.sort();
+            let mut periods: Vec<Duration> = times
+                .windows(2)
+                .map(|w| w[1].duration_since(&w[0]))
+                .collect();
+            periods.sort();
+            let period = periods[periods.len() / 2];
+            if period.is_zero() {
+                continue;
+            }
+            let min_gap = (period * GAP_PERIODS).max(MIN_GAP);
+
+            let gaps: Vec<(Time, Duration)> = times
+                .windows(2)
+                .map(|w| (w[0], w[1].duration_since(&w[0])))
+                .filter(|(_, gap)| *gap >= min_gap)
+                .collect();
+            if let Some((at, longest)) = gaps.iter().max_by_key(|(_, gap)| *gap) {
+                let start = self.start_time().unwrap_or(*at);
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    format!(
+                        "{topic} went silent {}, for up to {:.1}s from {:.1}s into the recording, although it usually \
+                         publishes every {:.3}s",
+                        match gaps.len() {
+                            1 => "once".to_string(),
+                            count => format!("{count} times"),
+                        },
+                        longest.as_secs_f64(),
+                        at.relative_to(&start),
+                        period.as_secs_f64()
+                    ),
+                ));
+            }
+
+            let last = *times.last().expect("topics have messages");
+            let silence = end.duration_since(&last);
+            if silence >= min_gap {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    format!(
+                        "{topic} stopped publishing {:.1}s before the end of the recording",
+                        silence.as_secs_f64()
+                    ),
+                ));
+            }
+
+            // gaps are reported above, the rest of the missing periods are drops
+            let dropped: f64 = periods
+                .iter()
+                .filter(|p| **p < min_gap)
+                .map(|p| (p.as_secs_f64() / period.as_secs_f64()).round() - 1.0)
+                .sum();
+            let expected = times.len() as f64 + dropped;
+            if dropped / expected > MAX_DROPPED {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    format!(
+                        "{topic} dropped about {:.0}% of its messages, arriving at {:.1} Hz instead of {:.1} Hz",
+                        dropped / expected * 100.0,
+                        (times.len() - 1) as f64 / last.duration_since(&times[0]).as_secs_f64(),
+                        1.0 / period.as_secs_f64()
+                    ),
+                ));
+            }
+        }
+    }
+}
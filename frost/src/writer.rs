@@ -0,0 +1,1005 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "zstd")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::compression;
+use crate::crypto::{self, EncryptionKey};
+use crate::dedup::{Dedup, DedupKey};
+#[cfg(feature = "zstd")]
+use crate::dictionary::{self, Dictionary};
+use crate::errors::Error;
+use crate::msgs::MessageView;
+use crate::query::Query;
+use crate::summary::human_bytes;
+use crate::time::Time;
+use crate::{CompressionInfo, ConnectionData, ConnectionID, DecompressedBag, OpCode};
+
+/// rosbag's default threshold for starting a new chunk
+const DEFAULT_CHUNK_THRESHOLD: usize = 768 * 1024;
+/// The bag header record is padded so it can be rewritten in place once the index position is known
+const BAG_HEADER_RECORD_LEN: usize = 4096;
+pub(crate) const VERSION_LINE: &[u8] = b"#ROSBAG V2.0\n";
+/// Message types whose data is already compressed, which [BagWriter::with_auto_compression] never compresses again
+pub const COMPRESSED_TYPES: &[&str] = &[
+    "sensor_msgs/CompressedImage",
+    "theora_image_transport/Packet",
+    "ffmpeg_image_transport_msgs/FFMPEGPacket",
+    "foxglove_msgs/CompressedVideo",
+];
+/// Entropy, in bits per byte, above which [BagWriter::with_auto_compression] leaves a chunk uncompressed.
+/// Text sits around 5, and compressed data close to 8.
+pub const INCOMPRESSIBLE_ENTROPY: f64 = 7.5;
+/// How many evenly spaced windows of a chunk [BagWriter::with_auto_compression] samples, and their size
+const ENTROPY_SAMPLES: usize = 16;
+const ENTROPY_SAMPLE_LEN: usize = 4096;
+
+/// Compression used for the chunks written by a [BagWriter].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Lz4,
+    #[cfg(feature = "bz2")]
+    Bz2,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// A format [register](crate::compression::register)ed under this name.
+    Custom(&'static str),
+}
+
+impl Compression {
+    /// The name stored in chunk headers.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Lz4 => "lz4",
+            #[cfg(feature = "bz2")]
+            Compression::Bz2 => "bz2",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+            Compression::Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "lz4" => Ok(Compression::Lz4),
+            #[cfg(feature = "bz2")]
+            "bz2" => Ok(Compression::Bz2),
+            #[cfg(feature = "zstd")]
+            "zstd" => Ok(Compression::Zstd),
+            other => match compression::lookup(other) {
+                Some(decompressor) => Ok(Compression::Custom(decompressor.name())),
+                None => Err(format!(
+                    "unsupported compression '{other}', expected one of: {}",
+                    compression::names().join(", ")
+                )),
+            },
+        }
+    }
+}
+
+/// Builds the `<len><name>=<value>` fields of a record header.
+#[derive(Default)]
+pub(crate) struct HeaderBuilder {
+    buf: Vec<u8>,
+}
+
+impl HeaderBuilder {
+    pub(crate) fn op(op: OpCode) -> Self {
+        HeaderBuilder::default().field(b"op", &[op as u8])
+    }
+
+    pub(crate) fn field(mut self, name: &[u8], value: &[u8]) -> Self {
+        self.buf
+            .extend_from_slice(&((name.len() + 1 + value.len()) as u32).to_le_bytes());
+        self.buf.extend_from_slice(name);
+        self.buf.push(b'=');
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub(crate) fn time_bytes(time: &Time) -> [u8; 8] {
+    let mut buf = [0u8; 8];
+    buf[..4].copy_from_slice(&time.secs.to_le_bytes());
+    buf[4..].copy_from_slice(&time.nsecs.to_le_bytes());
+    buf
+}
+
+/// Lengths and intra-chunk offsets are stored as u32s, unlike file positions which are u64s.
+fn u32_len(len: usize, what: &str) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{what} of {len} bytes does not fit in a rosbag's 32 bit length"),
+        )
+    })
+}
+
+fn unknown_connection(conn_id: ConnectionID) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("connection {conn_id} was not added to the writer"),
+    )
+}
+
+pub(crate) fn write_record(writer: &mut impl Write, header: &[u8], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&u32_len(header.len(), "record header")?.to_le_bytes())?;
+    writer.write_all(header)?;
+    writer.write_all(&u32_len(data.len(), "record data")?.to_le_bytes())?;
+    writer.write_all(data)
+}
+
+pub(crate) fn connection_record(
+    id: ConnectionID,
+    connection: &ConnectionData,
+) -> (Vec<u8>, Vec<u8>) {
+    let header = HeaderBuilder::op(OpCode::ConnectionHeader)
+        .field(b"conn", &id.to_le_bytes())
+        .field(b"topic", connection.raw_topic())
+        .finish();
+
+    let latching: &[u8] = if connection.latching { b"1" } else { b"0" };
+    let mut known: Vec<(&str, Option<&[u8]>)> = vec![
+        ("topic", Some(connection.raw_topic())),
+        ("type", Some(connection.raw_data_type())),
+        ("md5sum", Some(connection.md5sum.as_bytes())),
+        (
+            "message_definition",
+            Some(connection.message_definition.as_bytes()),
+        ),
+        (
+            "callerid",
+            connection.caller_id.as_deref().map(str::as_bytes),
+        ),
+        // rosbag leaves out latching=0, but keeps it when recorded that way
+        ("latching", connection.latching.then_some(latching)),
+    ];
+
+    let mut data = HeaderBuilder::default();
+    for (name, value) in connection.header_fields.iter() {
+        let value = match known.iter_mut().find(|(known, _)| known == name) {
+            Some((_, known)) if name == "latching" => {
+                known.take();
+                if (value.as_slice() == b"1") == connection.latching {
+                    value.as_slice()
+                } else {
+                    latching
+                }
+            }
+            Some((_, known)) => match known.take() {
+                Some(value) => value,
+                // a field cleared since it was read
+                None => continue,
+            },
+            None => value.as_slice(),
+        };
+        data = data.field(name.as_bytes(), value);
+    }
+    for (name, value) in known {
+        if let Some(value) = value {
+            data = data.field(name.as_bytes(), value);
+        }
+    }
+    (header, data.finish())
+}
+
+fn source_compression(msg_view: &MessageView) -> io::Result<Compression> {
+    let name = msg_view
+        .bag
+        .metadata
+        .chunk_metadata
+        .get(&msg_view.chunk_loc)
+        .map_or("none", |chunk| chunk.compression.as_str());
+    name.parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn same_connection(a: &ConnectionData, b: &ConnectionData) -> bool {
+    a.topic == b.topic
+        && a.data_type == b.data_type
+        && a.md5sum == b.md5sum
+        && a.message_definition == b.message_definition
+        && a.caller_id == b.caller_id
+        && a.latching == b.latching
+        && a.header_fields == b.header_fields
+}
+
+/// Per-topic rules for thinning out a bag while rewriting it, see [BagWriter::copy_retained].
+///
+/// Topics without a rule keep all of their messages. Duplicates are dropped before counting messages to keep every
+/// `n`th of.
+///
+/// Example
+/// ```rust
+/// use frost::dedup::DedupKey;
+/// use frost::writer::Retention;
+///
+/// // keep every 10th camera frame, only changes to the map, and drop debug output entirely
+/// let retention = Retention::new()
+///     .keep_every("/camera/image_raw", 10)
+///     .drop_duplicates("/map", DedupKey::Payload)
+///     .drop_topic("/debug");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Retention {
+    every: BTreeMap<String, usize>,
+    dedup: BTreeMap<String, DedupKey>,
+}
+
+impl Retention {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops every message on `topic`.
+    pub fn drop_topic<S: Into<String>>(self, topic: S) -> Self {
+        self.keep_every(topic, 0)
+    }
+
+    /// Keeps only the first of every `n` messages on `topic`. An `n` of 0 drops the topic.
+    pub fn keep_every<S: Into<String>>(mut self, topic: S, n: usize) -> Self {
+        self.every.insert(topic.into(), n);
+        self
+    }
+
+    /// Drops messages on `topic` which are duplicates of the previous one by `key`, see [crate::dedup].
+    pub fn drop_duplicates<S: Into<String>>(mut self, topic: S, key: DedupKey) -> Self {
+        self.dedup.insert(topic.into(), key);
+        self
+    }
+
+    /// Returns true if the `index`th message on `topic` should be kept.
+    fn keeps(&self, topic: &str, index: usize) -> bool {
+        match self.every.get(topic) {
+            Some(0) => false,
+            Some(n) => index % n == 0,
+            None => true,
+        }
+    }
+}
+
+/// Why [BagWriter::with_auto_compression] chose a chunk's compression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecisionReason {
+    /// Every message in the chunk is of one of the [COMPRESSED_TYPES].
+    CompressedTypes,
+    /// The sampled entropy was above [INCOMPRESSIBLE_ENTROPY].
+    HighEntropy,
+    /// The sampled entropy was low enough to be worth compressing.
+    LowEntropy,
+}
+
+/// The compression [BagWriter::with_auto_compression] chose for a chunk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompressionDecision {
+    pub compression: Compression,
+    pub reason: DecisionReason,
+    /// The Shannon entropy of the sampled bytes in bits per byte, if they were sampled.
+    pub entropy: Option<f64>,
+    /// The uncompressed size of the chunk.
+    pub size: usize,
+}
+
+/// The messages a [BagWriter] wrote on a topic, see [WriterStats::topics].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopicStats {
+    pub data_type: String,
+    pub messages: u64,
+    /// The size of the messages' serialized data.
+    pub bytes: u64,
+}
+
+/// What a [BagWriter] has written, like the summary [BagMetadata](crate::BagMetadata) gives of a bag read back, see
+/// [BagWriter::stats] and [BagWriter::finish_with_stats].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriterStats {
+    pub chunks: usize,
+    pub messages: u64,
+    /// The size of the written chunks before compression.
+    pub uncompressed_bytes: u64,
+    /// The size of the written chunks' data, after compression.
+    pub written_bytes: u64,
+    /// The earliest and latest receive times written.
+    pub start_time: Option<Time>,
+    pub end_time: Option<Time>,
+    pub topics: BTreeMap<String, TopicStats>,
+    /// Chunk counts and sizes per compression, like [BagMetadata::compression_info](crate::BagMetadata::compression_info).
+    pub compression: Vec<CompressionInfo>,
+    /// The decisions of [BagWriter::with_auto_compression], one per chunk in order, if it is enabled.
+    pub decisions: Vec<CompressionDecision>,
+}
+
+impl WriterStats {
+    /// The time between the earliest and latest receive times written.
+    pub fn duration(&self) -> Duration {
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) => end.duration_since(&start),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The written size of the chunks' data as a fraction of their uncompressed size.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.uncompressed_bytes == 0 {
+            return 1.0;
+        }
+        self.written_bytes as f64 / self.uncompressed_bytes as f64
+    }
+
+    fn add_chunk(&mut self, compression: Compression, uncompressed: usize, written: usize) {
+        let (uncompressed, written) = (uncompressed as u64, written as u64);
+        self.chunks += 1;
+        self.uncompressed_bytes += uncompressed;
+        self.written_bytes += written;
+        let index = match self
+            .compression
+            .iter()
+            .position(|info| info.name == compression.as_str())
+        {
+            Some(index) => index,
+            None => {
+                self.compression.push(CompressionInfo {
+                    name: compression.as_str().to_string(),
+                    chunk_count: 0,
+                    total_compressed: 0,
+                    total_uncompressed: 0,
+                });
+                self.compression.len() - 1
+            }
+        };
+        let info = &mut self.compression[index];
+        info.chunk_count += 1;
+        info.total_compressed += written;
+        info.total_uncompressed += uncompressed;
+    }
+
+    fn add_message(&mut self, connection: &ConnectionData, time: Time, len: usize) {
+        self.messages += 1;
+        self.start_time = Some(self.start_time.map_or(time, |t| t.min(time)));
+        self.end_time = Some(self.end_time.map_or(time, |t| t.max(time)));
+        let topic = self
+            .topics
+            .entry(connection.topic.clone())
+            .or_insert_with(|| TopicStats {
+                data_type: connection.data_type.clone(),
+                ..TopicStats::default()
+            });
+        topic.messages += 1;
+        topic.bytes += len as u64;
+    }
+}
+
+impl fmt::Display for WriterStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{0: <13}{1}", "chunks:", self.chunks)?;
+        writeln!(f, "{0: <13}{1}", "messages:", self.messages)?;
+        writeln!(
+            f,
+            "{0: <13}{1:.6}s",
+            "duration:",
+            self.duration().as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "{0: <13}{1} ({2} uncompressed, {3:.2}%)",
+            "size:",
+            human_bytes(self.written_bytes),
+            human_bytes(self.uncompressed_bytes),
+            100.0 * self.compression_ratio()
+        )?;
+        let name_width = self.compression.iter().map(|info| info.name.len()).max();
+        for (i, info) in self.compression.iter().enumerate() {
+            writeln!(
+                f,
+                "{0: <13}{1: <width$} [{2}/{3} chunks; {4:.2}%]",
+                if i == 0 { "compression:" } else { "" },
+                info.name,
+                info.chunk_count,
+                self.chunks,
+                100.0 * info.total_compressed as f64 / info.total_uncompressed.max(1) as f64,
+                width = name_width.unwrap_or_default()
+            )?;
+        }
+        let topic_width = self.topics.keys().map(String::len).max();
+        for (i, (topic, stats)) in self.topics.iter().enumerate() {
+            writeln!(
+                f,
+                "{0: <13}{1: <width$} {2: >6} msgs {3: >12} : {4}",
+                if i == 0 { "topics:" } else { "" },
+                topic,
+                stats.messages,
+                human_bytes(stats.bytes),
+                stats.data_type,
+                width = topic_width.unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct ChunkInfo {
+    chunk_header_pos: u64,
+    start_time: Time,
+    end_time: Time,
+    message_counts: BTreeMap<ConnectionID, u32>,
+}
+
+/// Writes a `ROSBAG V2.0` bag.
+///
+/// Messages are buffered into chunks which are compressed and written once they grow past the chunk size.
+/// The index at the end of the bag is only written by [BagWriter::finish], so a bag is not readable until then.
+///
+/// Example
+/// ```rust,no_run
+/// use frost::query::Query;
+/// use frost::writer::{BagWriter, Compression};
+/// use frost::DecompressedBag;
+///
+/// let bag = DecompressedBag::from_file("in.bag").unwrap();
+///
+/// let mut writer = BagWriter::create("chatter.bag")
+///     .unwrap()
+///     .with_compression(Compression::Lz4);
+/// writer
+///     .copy_messages(&bag, &Query::new().with_topics(["/chatter"]))
+///     .unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct BagWriter<W: Write + Seek> {
+    writer: W,
+    /// the compression set by [BagWriter::with_compression]
+    compression: Compression,
+    encryption: Option<EncryptionKey>,
+    #[cfg(feature = "zstd")]
+    dictionary: Option<Arc<Dictionary>>,
+    chunk_threshold: usize,
+    /// whether copied messages are compressed like the chunks they were copied from
+    source_compression: bool,
+    /// whether chunks which look incompressible are written uncompressed
+    auto_compression: bool,
+    stats: WriterStats,
+    connections: BTreeMap<ConnectionID, ConnectionData>,
+    /// connections which have had a connection record written into a chunk
+    written_connections: BTreeSet<ConnectionID>,
+    chunk_infos: Vec<ChunkInfo>,
+    /// uncompressed contents of the chunk being built
+    chunk_buf: Vec<u8>,
+    /// compression wanted by the messages in the chunk being built, which all share one
+    chunk_codec: Option<Compression>,
+    chunk_index: BTreeMap<ConnectionID, Vec<(Time, u32)>>,
+    chunk_start_time: Option<Time>,
+    chunk_end_time: Option<Time>,
+    reorder_window: Option<Duration>,
+    /// messages held back to be sorted, keyed by receive time and then the order they were written in
+    pending: BTreeMap<(Time, u64), (ConnectionID, Compression, Vec<u8>)>,
+    pending_seq: u64,
+}
+
+impl BagWriter<BufWriter<File>> {
+    /// Creates (or truncates) a bag at a file path.
+    pub fn create<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
+        let file = File::create(file_path)?;
+        BagWriter::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> BagWriter<W> {
+    /// Starts writing a bag into `writer`.
+    pub fn new(mut writer: W) -> Result<Self, Error> {
+        writer.write_all(VERSION_LINE)?;
+        write_bag_header(&mut writer, 0, 0, 0)?;
+
+        Ok(BagWriter {
+            writer,
+            compression: Compression::None,
+            encryption: None,
+            #[cfg(feature = "zstd")]
+            dictionary: None,
+            chunk_threshold: DEFAULT_CHUNK_THRESHOLD,
+            source_compression: false,
+            auto_compression: false,
+            stats: WriterStats::default(),
+            connections: BTreeMap::new(),
+            written_connections: BTreeSet::new(),
+            chunk_infos: Vec::new(),
+            chunk_buf: Vec::new(),
+            chunk_codec: None,
+            chunk_index: BTreeMap::new(),
+            chunk_start_time: None,
+            chunk_end_time: None,
+            reorder_window: None,
+            pending: BTreeMap::new(),
+            pending_seq: 0,
+        })
+    }
+
+    /// Sets the compression of chunks written from now on. Defaults to [Compression::None].
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Compresses messages copied from a bag with the compression of the chunk they were copied from,
+    /// starting a new chunk whenever it changes, so rewritten bags keep their original compression.
+    ///
+    /// Messages written with [BagWriter::write_raw] use the compression set by [BagWriter::with_compression].
+    /// zstd chunks are recompressed without a dictionary, unless one is set with [BagWriter::with_dictionary].
+    pub fn with_source_compression(mut self) -> Self {
+        self.source_compression = true;
+        self
+    }
+
+    /// Writes chunks which are unlikely to compress well uncompressed, saving the time compressing them would take,
+    /// and compresses the others with the compression set by [BagWriter::with_compression].
+    ///
+    /// Chunks holding only [COMPRESSED_TYPES] are never compressed, and others are if the entropy of a sample of
+    /// their bytes is at most [INCOMPRESSIBLE_ENTROPY]. Each decision is recorded in [BagWriter::stats].
+    pub fn with_auto_compression(mut self) -> Self {
+        self.auto_compression = true;
+        self
+    }
+
+    /// Encrypts chunks written from now on with AES-256-GCM, recording the key's id in their headers.
+    ///
+    /// Only message data is encrypted; connections and the index stay readable without the key.
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption(mut self, key: EncryptionKey) -> Self {
+        self.encryption = Some(key);
+        self
+    }
+
+    /// Compresses chunks written from now on with zstd using a trained dictionary, see [crate::dictionary].
+    #[cfg(feature = "zstd")]
+    pub fn with_dictionary(mut self, dictionary: Arc<Dictionary>) -> Self {
+        self.compression = Compression::Zstd;
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    /// Sets the uncompressed size at which a chunk is written out. Defaults to 768 KiB, like rosbag.
+    pub fn with_chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_threshold = bytes;
+        self
+    }
+
+    /// Sorts messages given to [BagWriter::write_raw] by receive time, for sources which deliver them out of order.
+    ///
+    /// Messages are held back until one received more than `window` later is written, or until [BagWriter::finish],
+    /// so the window bounds both how late a message can arrive and still be sorted, and how many messages are buffered.
+    /// `Duration::MAX` sorts the whole bag in memory. Messages received at the same time keep the order they were written in,
+    /// and their data, including any header stamps, is written unchanged.
+    pub fn with_reorder_window(mut self, window: Duration) -> Self {
+        self.reorder_window = Some(window);
+        self
+    }
+
+    /// Adds a connection to the bag, returning the id to write its messages with.
+    ///
+    /// The connection's own id is ignored. If an identical connection was already added, its id is returned.
+    pub fn add_connection(&mut self, connection: &ConnectionData) -> ConnectionID {
+        if let Some((id, _)) = self
+            .connections
+            .iter()
+            .find(|(_, existing)| same_connection(existing, connection))
+        {
+            return *id;
+        }
+        let id = self.connections.len() as ConnectionID;
+        self.connections.insert(
+            id,
+            ConnectionData {
+                connection_id: id,
+                topic: connection.topic.clone(),
+                data_type: connection.data_type.clone(),
+                md5sum: connection.md5sum.clone(),
+                message_definition: connection.message_definition.clone(),
+                caller_id: connection.caller_id.clone(),
+                latching: connection.latching,
+                header_fields: connection.header_fields.clone(),
+            },
+        );
+        id
+    }
+
+    /// Writes an already serialized message (without the leading length) received at `time`.
+    pub fn write_raw(
+        &mut self,
+        conn_id: ConnectionID,
+        time: Time,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.write_compressed(conn_id, time, self.compression, data)
+    }
+
+    /// Writes a message like [BagWriter::write_raw], into a chunk compressed with `compression`.
+    fn write_compressed(
+        &mut self,
+        conn_id: ConnectionID,
+        time: Time,
+        compression: Compression,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let Some(window) = self.reorder_window else {
+            return self.write_message(conn_id, time, compression, data);
+        };
+        if !self.connections.contains_key(&conn_id) {
+            return Err(unknown_connection(conn_id).into());
+        }
+        self.pending.insert(
+            (time, self.pending_seq),
+            (conn_id, compression, data.to_vec()),
+        );
+        self.pending_seq += 1;
+
+        let latest = self.pending.keys().next_back().map(|(time, _)| *time);
+        while let Some(&key) = self.pending.keys().next() {
+            let (received, _) = key;
+            if !received
+                .checked_add(window)
+                .zip(latest)
+                .map_or(false, |(deadline, latest)| deadline < latest)
+            {
+                break;
+            }
+            let (conn_id, compression, data) = self.pending.remove(&key).unwrap();
+            self.write_message(conn_id, received, compression, &data)?;
+        }
+        Ok(())
+    }
+
+    fn write_message(
+        &mut self,
+        conn_id: ConnectionID,
+        time: Time,
+        compression: Compression,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        if !self.connections.contains_key(&conn_id) {
+            return Err(unknown_connection(conn_id).into());
+        }
+        // chunks are not shared across compressions
+        if self.chunk_codec.map_or(false, |codec| codec != compression) {
+            self.flush_chunk()?;
+        }
+        self.chunk_codec = Some(compression);
+        let connection = &self.connections[&conn_id];
+
+        if self.written_connections.insert(conn_id) {
+            let (header, data) = connection_record(conn_id, connection);
+            write_record(&mut self.chunk_buf, &header, &data)?;
+        }
+
+        let offset = u32_len(self.chunk_buf.len(), "chunk")?;
+        let header = HeaderBuilder::op(OpCode::MessageData)
+            .field(b"conn", &conn_id.to_le_bytes())
+            .field(b"time", &time_bytes(&time))
+            .finish();
+        write_record(&mut self.chunk_buf, &header, data)?;
+        self.stats.add_message(connection, time, data.len());
+
+        self.chunk_index
+            .entry(conn_id)
+            .or_default()
+            .push((time, offset));
+        self.chunk_start_time = Some(self.chunk_start_time.map_or(time, |t| t.min(time)));
+        self.chunk_end_time = Some(self.chunk_end_time.map_or(time, |t| t.max(time)));
+
+        if self.chunk_buf.len() >= self.chunk_threshold {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Copies every message matching `query` from `bag`, returning how many were written.
+    ///
+    /// Fails with [ErrorKind::Cancelled](crate::errors::ErrorKind::Cancelled) if the query is cancelled, keeping the
+    /// messages copied until then, see [crate::cancel].
+    pub fn copy_messages(&mut self, bag: &DecompressedBag, query: &Query) -> Result<usize, Error> {
+        self.copy_filtered(bag, query, |_| true)
+    }
+
+    /// Copies the messages matching `query` from `bag` which are kept by `retention`, returning how many were written.
+    pub fn copy_retained(
+        &mut self,
+        bag: &DecompressedBag,
+        query: &Query,
+        retention: &Retention,
+    ) -> Result<usize, Error> {
+        let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+        let mut dedup = Dedup::from_keys(retention.dedup.clone());
+        self.copy_filtered(bag, query, |msg_view| {
+            if dedup.is_duplicate(msg_view) {
+                return false;
+            }
+            let index = seen.entry(msg_view.topic.to_string()).or_default();
+            let keep = retention.keeps(msg_view.topic, *index);
+            *index += 1;
+            keep
+        })
+    }
+
+    fn copy_filtered<F>(
+        &mut self,
+        bag: &DecompressedBag,
+        query: &Query,
+        mut keep: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(&MessageView) -> bool,
+    {
+        let mut conn_ids = BTreeMap::new();
+        let mut count = 0;
+        for msg_view in bag.filter_messages(query)? {
+            if !keep(&msg_view) {
+                continue;
+            }
+            let conn_id = match conn_ids.get(&msg_view.conn_id) {
+                Some(id) => *id,
+                None => {
+                    let id = self.add_connection(msg_view.connection());
+                    conn_ids.insert(msg_view.conn_id, id);
+                    id
+                }
+            };
+            self.write_copied(&msg_view, conn_id, msg_view.time)?;
+            count += 1;
+        }
+        // the iterator stops early once the query is cancelled
+        query.check_cancelled()?;
+        Ok(count)
+    }
+
+    /// Writes a message copied from another bag on `conn_id`, following [BagWriter::with_source_compression].
+    pub(crate) fn write_copied(
+        &mut self,
+        msg_view: &MessageView,
+        conn_id: ConnectionID,
+        time: Time,
+    ) -> Result<(), Error> {
+        let compression = match self.source_compression {
+            true => source_compression(msg_view)?,
+            false => self.compression,
+        };
+        self.write_compressed(conn_id, time, compression, msg_view.data_bytes())
+    }
+
+    /// What has been written so far. The last chunk is only written by [BagWriter::finish], see
+    /// [BagWriter::finish_with_stats].
+    pub fn stats(&self) -> &WriterStats {
+        &self.stats
+    }
+
+    /// Chooses the compression of the chunk being built, following [BagWriter::with_auto_compression].
+    fn chunk_compression(&mut self) -> Compression {
+        let configured = self.chunk_codec.unwrap_or(self.compression);
+        if !self.auto_compression || configured == Compression::None {
+            return configured;
+        }
+        let precompressed = self.chunk_index.keys().all(|conn_id| {
+            self.connections.get(conn_id).map_or(false, |connection| {
+                COMPRESSED_TYPES.contains(&connection.data_type.as_str())
+            })
+        });
+        let (compression, reason, entropy) = if precompressed {
+            (Compression::None, DecisionReason::CompressedTypes, None)
+        } else {
+            let entropy = sampled_entropy(&self.chunk_buf);
+            if entropy > INCOMPRESSIBLE_ENTROPY {
+                (
+                    Compression::None,
+                    DecisionReason::HighEntropy,
+                    Some(entropy),
+                )
+            } else {
+                (configured, DecisionReason::LowEntropy, Some(entropy))
+            }
+        };
+        self.stats.decisions.push(CompressionDecision {
+            compression,
+            reason,
+            entropy,
+            size: self.chunk_buf.len(),
+        });
+        compression
+    }
+
+    fn flush_chunk(&mut self) -> Result<(), Error> {
+        if self.chunk_index.is_empty() {
+            return Ok(());
+        }
+
+        let chunk_header_pos = self.writer.stream_position()?;
+
+        let compression = self.chunk_compression();
+        let compressed = match compression {
+            Compression::None => None,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Some(dictionary::compress(
+                &self.chunk_buf,
+                self.dictionary.as_deref(),
+            )?),
+            other => {
+                let compressor = compression::lookup(other.as_str()).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("compression '{other}' has not been registered"),
+                    )
+                })?;
+                Some(compressor.compress(&self.chunk_buf)?)
+            }
+        };
+        let data = compressed.as_deref().unwrap_or(&self.chunk_buf);
+        let mut header = HeaderBuilder::op(OpCode::ChunkHeader)
+            .field(b"compression", compression.as_str().as_bytes())
+            .field(
+                b"size",
+                &u32_len(self.chunk_buf.len(), "chunk")?.to_le_bytes(),
+            );
+        #[cfg(feature = "zstd")]
+        if let (Compression::Zstd, Some(dictionary)) = (compression, &self.dictionary) {
+            header = header.field(b"dict_id", dictionary.id().as_bytes());
+        }
+        match &self.encryption {
+            Some(key) => {
+                let encrypted = encrypt_chunk(key, data)?;
+                header = header
+                    .field(b"encryption", crypto::AES_256_GCM.as_bytes())
+                    .field(b"key_id", key.id().as_bytes());
+                write_record(&mut self.writer, &header.finish(), &encrypted)?;
+            }
+            None => write_record(&mut self.writer, &header.finish(), data)?,
+        }
+        self.stats
+            .add_chunk(compression, self.chunk_buf.len(), data.len());
+
+        let mut message_counts = BTreeMap::new();
+        for (conn_id, entries) in std::mem::take(&mut self.chunk_index) {
+            let header = HeaderBuilder::op(OpCode::IndexDataHeader)
+                .field(b"ver", &1u32.to_le_bytes())
+                .field(b"conn", &conn_id.to_le_bytes())
+                .field(b"count", &(entries.len() as u32).to_le_bytes())
+                .finish();
+            let mut data = Vec::with_capacity(entries.len() * 12);
+            for (time, offset) in entries.iter() {
+                data.extend_from_slice(&time_bytes(time));
+                data.extend_from_slice(&offset.to_le_bytes());
+            }
+            write_record(&mut self.writer, &header, &data)?;
+            message_counts.insert(conn_id, entries.len() as u32);
+        }
+
+        self.chunk_infos.push(ChunkInfo {
+            chunk_header_pos,
+            start_time: self.chunk_start_time.take().unwrap_or(crate::time::ZERO),
+            end_time: self.chunk_end_time.take().unwrap_or(crate::time::ZERO),
+            message_counts,
+        });
+        self.chunk_buf.clear();
+        self.chunk_codec = None;
+        Ok(())
+    }
+
+    /// Writes any buffered messages and the bag's index, returning the underlying writer.
+    pub fn finish(self) -> Result<W, Error> {
+        self.finish_with_stats().map(|(writer, _)| writer)
+    }
+
+    /// Like [BagWriter::finish], also returning the [BagWriter::stats] of the whole bag.
+    pub fn finish_with_stats(mut self) -> Result<(W, WriterStats), Error> {
+        for ((time, _), (conn_id, compression, data)) in std::mem::take(&mut self.pending) {
+            self.write_message(conn_id, time, compression, &data)?;
+        }
+        self.flush_chunk()?;
+
+        let index_pos = self.writer.stream_position()?;
+        for (id, connection) in self.connections.iter() {
+            let (header, data) = connection_record(*id, connection);
+            write_record(&mut self.writer, &header, &data)?;
+        }
+        for info in self.chunk_infos.iter() {
+            let header = HeaderBuilder::op(OpCode::ChunkInfoHeader)
+                .field(b"ver", &1u32.to_le_bytes())
+                .field(b"chunk_pos", &info.chunk_header_pos.to_le_bytes())
+                .field(b"start_time", &time_bytes(&info.start_time))
+                .field(b"end_time", &time_bytes(&info.end_time))
+                .field(b"count", &(info.message_counts.len() as u32).to_le_bytes())
+                .finish();
+            let mut data = Vec::with_capacity(info.message_counts.len() * 8);
+            for (conn_id, count) in info.message_counts.iter() {
+                data.extend_from_slice(&conn_id.to_le_bytes());
+                data.extend_from_slice(&count.to_le_bytes());
+            }
+            write_record(&mut self.writer, &header, &data)?;
+        }
+        let end_pos = self.writer.stream_position()?;
+
+        self.writer
+            .seek(SeekFrom::Start(VERSION_LINE.len() as u64))?;
+        write_bag_header(
+            &mut self.writer,
+            index_pos,
+            self.connections.len() as u32,
+            self.chunk_infos.len() as u32,
+        )?;
+        self.writer.seek(SeekFrom::Start(end_pos))?;
+        self.writer.flush()?;
+
+        Ok((self.writer, self.stats))
+    }
+}
+
+/// The Shannon entropy in bits per byte of evenly spaced samples of `data`, or of all of it if it is small.
+fn sampled_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    let mut count_window = |window: &[u8]| {
+        for byte in window {
+            counts[*byte as usize] += 1;
+        }
+    };
+    if data.len() <= ENTROPY_SAMPLES * ENTROPY_SAMPLE_LEN {
+        count_window(data);
+    } else {
+        let stride = (data.len() - ENTROPY_SAMPLE_LEN) / (ENTROPY_SAMPLES - 1);
+        for i in 0..ENTROPY_SAMPLES {
+            let start = i * stride;
+            count_window(&data[start..start + ENTROPY_SAMPLE_LEN]);
+        }
+    }
+    let total: u64 = counts.iter().sum();
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+pub(crate) fn write_bag_header(
+    writer: &mut impl Write,
+    index_pos: u64,
+    conn_count: u32,
+    chunk_count: u32,
+) -> io::Result<()> {
+    let header = HeaderBuilder::op(OpCode::BagHeader)
+        .field(b"index_pos", &index_pos.to_le_bytes())
+        .field(b"conn_count", &conn_count.to_le_bytes())
+        .field(b"chunk_count", &chunk_count.to_le_bytes())
+        .finish();
+    let padding = vec![b' '; BAG_HEADER_RECORD_LEN - 8 - header.len()];
+    write_record(writer, &header, &padding)
+}
+
+#[cfg(feature = "encryption")]
+fn encrypt_chunk(key: &EncryptionKey, data: &[u8]) -> Result<Vec<u8>, Error> {
+    crypto::encrypt(key, data)
+}
+
+// a key can only be set with the `encryption` feature enabled
+#[cfg(not(feature = "encryption"))]
+fn encrypt_chunk(_key: &EncryptionKey, _data: &[u8]) -> Result<Vec<u8>, Error> {
+    unreachable!()
+}
@@ -0,0 +1,23 @@
+"""Prints every message of a bag as a JSON line of its topic, receive time in nanoseconds, and the sha256 of its
+serialized data, in the order rosbag reads them. Used by the rosbag compatibility tests, see test_rosbag_compat.rs."""
+import argparse
+import hashlib
+import json
+
+import rosbag
+
+
+def main():
+    parser = argparse.ArgumentParser("Dump the messages of a bag")
+    parser.add_argument("bag")
+    args = parser.parse_args()
+
+    with rosbag.Bag(args.bag) as bag:
+        for topic, raw, t in bag.read_messages(raw=True):
+            # raw messages are (datatype, data, md5sum, position, pytype)
+            data = raw[1]
+            print(json.dumps({"topic": topic, "time": t.to_nsec(), "sha256": hashlib.sha256(data).hexdigest()}))
+
+
+if __name__ == "__main__":
+    main()
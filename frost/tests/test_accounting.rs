@@ -0,0 +1,83 @@
+use std::process::Command;
+
+use frost::accounting::TopicBytes;
+#[cfg(feature = "cache")]
+use frost::cache::MetadataCache;
+use frost::storage::FileStorage;
+use frost::BagMetadata;
+#[cfg(feature = "cache")]
+use tempfile::tempdir;
+
+const COMPRESSED: &str = "./tests/fixtures/compressed_lz4.bag";
+const DECOMPRESSED: &str = "./tests/fixtures/decompressed.bag";
+
+fn counts(messages: u64, bytes: u64) -> TopicBytes {
+    TopicBytes { messages, bytes }
+}
+
+#[test]
+fn exact_topic_bytes() {
+    for path in [COMPRESSED, DECOMPRESSED] {
+        let metadata = BagMetadata::from_file(path).unwrap();
+        let exact = metadata
+            .exact_topic_bytes(&FileStorage::open(path).unwrap())
+            .unwrap();
+        // 100 std_msgs/Time, and strings "foo_0" to "foo_99" with their lengths
+        assert_eq!(exact["/time"], counts(100, 800));
+        assert_eq!(exact["/chatter"], counts(100, 990));
+        assert_eq!(exact["/array"].messages, 100);
+
+        let estimated = metadata.estimated_topic_bytes();
+        assert_eq!(estimated.len(), 3);
+        for (topic, counts) in exact.iter() {
+            assert_eq!(estimated[topic].messages, counts.messages);
+            assert!(estimated[topic].bytes > counts.bytes);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "cache")]
+fn cached_exact_topic_bytes() {
+    let dir = tempdir().unwrap();
+    let cache = MetadataCache::new(dir.path());
+    cache.load(COMPRESSED).unwrap();
+    let metadata_only = cache.entries().unwrap()[0].cached_bytes;
+
+    let exact = cache.exact_topic_bytes(COMPRESSED).unwrap();
+    assert_eq!(exact["/time"], counts(100, 800));
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].cached_bytes > metadata_only);
+
+    // read back from the entry, which still holds the metadata
+    assert_eq!(cache.exact_topic_bytes(COMPRESSED).unwrap(), exact);
+    assert_eq!(cache.load(COMPRESSED).unwrap().message_count(), 300);
+    assert_eq!(
+        cache.entries().unwrap()[0].cached_bytes,
+        entries[0].cached_bytes
+    );
+}
+
+#[test]
+fn sizes_command() {
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["sizes", "--exact", COMPRESSED])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("/time            100  800 bytes\n"),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("total            300  6.83 KB (6990 bytes)\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["sizes", COMPRESSED])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("(estimated from the index"));
+}
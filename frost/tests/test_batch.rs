@@ -0,0 +1,91 @@
+use std::process::Command;
+
+use frost::batch::Batch;
+use frost::BagMetadata;
+use tempfile::tempdir;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+const MISSING_PATH: &str = "./tests/fixtures/missing.bag";
+
+#[test]
+fn reports_in_order() {
+    let items: Vec<u64> = (0..32).collect();
+    let mut seen = Vec::new();
+    let summary = Batch::new(4).run(
+        &items,
+        |&item| {
+            // later items finish first
+            std::thread::sleep(std::time::Duration::from_millis(32 - item));
+            Ok(item * 2)
+        },
+        |&item, result| seen.push((item, result.unwrap())),
+    );
+    assert_eq!(seen, items.iter().map(|&i| (i, i * 2)).collect::<Vec<_>>());
+    assert_eq!((summary.succeeded, summary.failed), (32, 0));
+    assert!(summary.is_success());
+}
+
+#[test]
+fn isolates_failures() {
+    let items = [FILE_PATH, MISSING_PATH, "panic", FILE_PATH];
+    for jobs in [1, 3] {
+        let mut results = Vec::new();
+        let summary = Batch::new(jobs).run(
+            &items,
+            |&path| {
+                if path == "panic" {
+                    panic!("bad bag");
+                }
+                BagMetadata::from_file(path).map(|metadata| metadata.message_count())
+            },
+            |_, result| results.push(result.map_err(|e| e.to_string())),
+        );
+        assert_eq!((summary.succeeded, summary.failed), (2, 2));
+        assert!(!summary.is_success());
+        assert_eq!(results[0], Ok(300));
+        assert!(results[1].is_err());
+        assert_eq!(results[2], Err("panicked: bad bag".to_string()));
+        assert_eq!(results[3], Ok(300));
+    }
+}
+
+#[test]
+fn defaults_to_a_job_per_cpu() {
+    assert!(Batch::new(0).jobs() >= 1);
+    assert_eq!(Batch::new(3).jobs(), 3);
+    assert_eq!(Batch::default().jobs(), 1);
+}
+
+#[test]
+fn check_carries_on_past_bad_bags() {
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["check", "--jobs", "2", FILE_PATH, MISSING_PATH])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(&format!("{FILE_PATH}:\n")), "{stdout}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(MISSING_PATH), "{stderr}");
+    assert!(stderr.contains("1 of 2 bags failed"), "{stderr}");
+}
+
+#[test]
+fn exports_many_bags() {
+    let dir = tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args([
+            "export",
+            "-j",
+            "0",
+            FILE_PATH,
+            "./tests/fixtures/decompressed.bag",
+        ])
+        .arg("-o")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(dir.path().join("compressed_lz4.mcap").is_file());
+    assert!(dir.path().join("decompressed.mcap").is_file());
+}
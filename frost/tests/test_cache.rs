@@ -0,0 +1,80 @@
+#![cfg(feature = "cache")]
+
+use std::fs;
+
+use frost::cache::MetadataCache;
+use frost::query::Query;
+use frost::BagMetadata;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+fn assert_same_metadata(actual: &BagMetadata, expected: &BagMetadata) {
+    assert_eq!(actual.file_path, expected.file_path);
+    assert_eq!(actual.num_bytes, expected.num_bytes);
+    assert_eq!(actual.start_time(), expected.start_time());
+    assert_eq!(actual.end_time(), expected.end_time());
+    assert_eq!(
+        actual.topic_message_counts(),
+        expected.topic_message_counts()
+    );
+    assert_eq!(actual.chunk_count(), expected.chunk_count());
+    assert_eq!(
+        format!("{:?}", actual.connection_data),
+        format!("{:?}", expected.connection_data)
+    );
+    assert_eq!(
+        actual.byte_ranges(&Query::all()),
+        expected.byte_ranges(&Query::all())
+    );
+}
+
+#[test]
+fn load_caches_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MetadataCache::new(dir.path().join("cache"));
+    assert!(cache.entries().unwrap().is_empty());
+
+    for bytes in [DECOMPRESSED, COMPRESSED_LZ4] {
+        let path = dir.path().join("test.bag");
+        fs::write(&path, bytes).unwrap();
+        let expected = BagMetadata::from_file(&path).unwrap();
+
+        let miss = cache.load(&path).unwrap();
+        let hit = cache.load(&path).unwrap();
+        assert_same_metadata(&miss, &expected);
+        assert_same_metadata(&hit, &expected);
+    }
+
+    // rewriting the bag left a stale entry behind
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries
+        .iter()
+        .all(|entry| entry.bag_path == fs::canonicalize(dir.path().join("test.bag")).unwrap()));
+    assert!(entries
+        .iter()
+        .any(|entry| entry.bag_bytes == COMPRESSED_LZ4.len() as u64));
+    assert!(entries
+        .iter()
+        .all(|entry| entry.cached_bytes < entry.bag_bytes));
+
+    assert_eq!(cache.clear().unwrap(), 2);
+    assert!(cache.entries().unwrap().is_empty());
+}
+
+#[test]
+fn load_ignores_corrupt_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = MetadataCache::new(dir.path());
+    let path = "./tests/fixtures/compressed_lz4.bag";
+    cache.load(path).unwrap();
+
+    let entry = fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+    let bytes = fs::read(entry.path()).unwrap();
+    fs::write(entry.path(), &bytes[..bytes.len() / 2]).unwrap();
+
+    let metadata = cache.load(path).unwrap();
+    assert_same_metadata(&metadata, &BagMetadata::from_file(path).unwrap());
+    assert_eq!(fs::read(entry.path()).unwrap(), bytes);
+}
@@ -0,0 +1,99 @@
+use std::io::Cursor;
+
+use frost::cancel::CancellationToken;
+use frost::echo::{EchoFormat, EchoWriter};
+use frost::errors::ErrorKind;
+use frost::mcap::McapWriter;
+use frost::merge::Merge;
+use frost::query::Query;
+use frost::DecompressedBag;
+
+mod common;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+#[test]
+fn stops_iterating() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let token = CancellationToken::new();
+    let query = Query::all().with_cancellation(token.clone());
+
+    let mut messages = bag.read_messages(&query).unwrap();
+    assert_eq!(messages.len(), 300);
+    let read = messages.by_ref().take(10).count();
+    assert!(!messages.was_cancelled());
+    token.cancel();
+    assert!(query.is_cancelled());
+    assert_eq!(messages.len(), 0);
+    assert!(messages.next().is_none());
+    assert!(messages.was_cancelled());
+    assert_eq!(read, 10);
+    assert_eq!(bag.read_messages(&query).unwrap().count(), 0);
+    assert!(bag.filter_messages(&query).unwrap().was_cancelled());
+
+    // cancelling once every message was read doesn't make them partial
+    let token = CancellationToken::new();
+    let query = Query::all().with_cancellation(token.clone());
+    let mut messages = bag.read_messages(&query).unwrap();
+    assert_eq!(messages.by_ref().count(), 300);
+    token.cancel();
+    assert!(!messages.was_cancelled());
+}
+
+#[test]
+fn fails_long_operations() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let token = CancellationToken::new();
+    let query = Query::all().with_cancellation(token.clone());
+    assert!(token.check().is_ok());
+    token.cancel();
+    let cancelled = |error: frost::errors::Error| {
+        assert!(matches!(error.kind(), ErrorKind::Cancelled), "{error}");
+        assert_eq!(error.to_string(), "cancelled");
+    };
+
+    cancelled(
+        DecompressedBag::from_file_filtered(FILE_PATH, &query)
+            .err()
+            .unwrap(),
+    );
+
+    cancelled(
+        DecompressedBag::from_file_with_cancellation(FILE_PATH, &token)
+            .err()
+            .unwrap(),
+    );
+    let bytes = std::fs::read(FILE_PATH).unwrap();
+    cancelled(
+        DecompressedBag::from_bytes_with_cancellation(&bytes, &token)
+            .err()
+            .unwrap(),
+    );
+    assert!(
+        DecompressedBag::from_file_with_cancellation(FILE_PATH, &CancellationToken::new()).is_ok()
+    );
+
+    let mut echo = EchoWriter::new(Vec::new(), EchoFormat::Json);
+    cancelled(
+        echo.write_messages(bag.filter_messages(&query).unwrap())
+            .unwrap_err(),
+    );
+    let fanout = bag.fanout(&query).unwrap().on_topic("/chatter", |_| Ok(()));
+    cancelled(fanout.run().unwrap_err());
+
+    let mut writer = common::writer();
+    cancelled(writer.copy_messages(&bag, &query).unwrap_err());
+    // what was written before cancelling is still a valid bag
+    let bytes = writer.finish().unwrap().into_inner();
+    assert!(DecompressedBag::from_bytes(&bytes)
+        .unwrap()
+        .metadata
+        .is_empty());
+
+    let mut mcap = McapWriter::new(Cursor::new(Vec::new())).unwrap();
+    cancelled(mcap.copy_messages(&bag, &query).unwrap_err());
+
+    let mut writer = common::writer();
+    let merge = Merge::new().with_query(query).add_bag(&bag, 0.0);
+    cancelled(merge.write_to(&mut writer).unwrap_err());
+}
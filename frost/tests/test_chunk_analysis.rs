@@ -0,0 +1,69 @@
+use frost::chunk_analysis::{AnomalyKind, DEFAULT_FACTOR};
+use frost::time::Time;
+use frost::writer::Compression;
+use frost::BagMetadata;
+
+mod common;
+use common::{string, string_connection};
+
+/// Writes 1 KiB messages into 4 KiB lz4 chunks: noise from a seeded generator, except for `stuck` messages of one repeated byte.
+fn write_bag(stuck: std::ops::Range<u32>) -> Vec<u8> {
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(4096);
+    let scan = writer.add_connection(&string_connection("/scan"));
+
+    let mut state: u64 = 42;
+    for i in 0..40 {
+        let text: Vec<u8> = (0..1024)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                if stuck.contains(&i) {
+                    b'0'
+                } else {
+                    b' ' + (state >> 33) as u8 % 95
+                }
+            })
+            .collect();
+        writer
+            .write_raw(scan, Time { secs: i, nsecs: 0 }, &string(&text))
+            .unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn stuck_values() {
+    let metadata = BagMetadata::from_bytes(&write_bag(12..20)).unwrap();
+    let analysis = metadata.chunk_anomalies(DEFAULT_FACTOR);
+    assert!(analysis.chunk_count >= 8, "{analysis:?}");
+    assert!(analysis.median_ratios["lz4"] < 2.0, "{analysis:?}");
+    assert!(analysis.median_size.is_some());
+
+    assert!(!analysis.anomalies.is_empty());
+    for anomaly in &analysis.anomalies {
+        assert_eq!(anomaly.kind, AnomalyKind::HighRatio);
+        assert!(anomaly.deviation >= DEFAULT_FACTOR);
+        assert!(anomaly.start_time >= Time { secs: 12, nsecs: 0 });
+        assert!(anomaly.end_time < Time { secs: 20, nsecs: 0 });
+        assert_eq!(anomaly.topics.iter().collect::<Vec<_>>(), ["/scan"]);
+        assert!(anomaly.to_string().contains("high compression ratio"));
+    }
+}
+
+#[test]
+fn healthy_bags() {
+    let metadata = BagMetadata::from_bytes(&write_bag(0..0)).unwrap();
+    let analysis = metadata.chunk_anomalies(DEFAULT_FACTOR);
+    assert!(analysis.anomalies.is_empty(), "{analysis:?}");
+
+    // the fixtures have a single chunk, which has no norm to compare against
+    let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    let analysis = metadata.chunk_anomalies(DEFAULT_FACTOR);
+    assert_eq!(analysis.chunk_count, 1);
+    assert_eq!(analysis.median_size, None);
+    assert!(analysis.median_ratios.is_empty());
+    assert!(analysis.anomalies.is_empty());
+}
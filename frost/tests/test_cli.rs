@@ -0,0 +1,261 @@
+//! Golden tests of `frost`'s output, which scripts parse, so changing it should be deliberate.
+//!
+//! A test failing on purpose is fixed by reviewing its new snapshot with `cargo insta review`, or by rerunning it with
+//! `INSTA_UPDATE=always` and checking the diff of `tests/snapshots`.
+use assert_cmd::Command;
+use insta::assert_snapshot;
+
+const COMPRESSED: &str = "tests/fixtures/compressed_lz4.bag";
+const DECOMPRESSED: &str = "tests/fixtures/decompressed.bag";
+
+/// Runs frost, asserting it exits with `code`, and returns its stdout.
+fn frost(args: &[&str], code: i32) -> String {
+    let output = Command::cargo_bin("frost")
+        .unwrap()
+        .args(args)
+        // times are printed in the local timezone
+        .env("TZ", "UTC")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(code), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn info() {
+    assert_snapshot!("info_compressed", frost(&["info", COMPRESSED], 0));
+    assert_snapshot!("info_decompressed", frost(&["info", DECOMPRESSED], 0));
+    assert_snapshot!("info_minimal", frost(&["info", "--minimal", COMPRESSED], 0));
+}
+
+#[test]
+fn info_yaml() {
+    assert_snapshot!(frost(&["info", "--yaml", COMPRESSED], 0));
+    #[cfg(feature = "yaml")]
+    assert_snapshot!(
+        "info_key",
+        frost(&["info", "--key", "topics[1]", COMPRESSED], 0)
+    );
+}
+
+#[test]
+fn info_compat() {
+    assert_snapshot!(frost(&["info", "--compat", "rosbag", COMPRESSED], 0));
+}
+
+#[test]
+fn topics_and_types() {
+    assert_snapshot!("topics", frost(&["topics", COMPRESSED], 0));
+    assert_snapshot!("types", frost(&["types", COMPRESSED], 0));
+}
+
+#[test]
+fn check() {
+    assert_snapshot!(frost(&["check", "--analyze", COMPRESSED], 0));
+    assert_snapshot!("check_many", frost(&["check", COMPRESSED, DECOMPRESSED], 0));
+}
+
+#[test]
+fn echo() {
+    let output = frost(&["echo", "--topic", "/time", COMPRESSED], 0);
+    let lines: Vec<_> = output.lines().take(3).collect();
+    assert_snapshot!(lines.join("\n"));
+}
+
+#[test]
+fn time_range() {
+    let output = frost(&["echo", "--start", "10", "--end", "19.5", COMPRESSED], 0);
+    assert_eq!(output.lines().count(), 30);
+    assert!(output.starts_with(r#"{"topic":"/chatter","time":{"secs":10,"#));
+    assert!(!output.contains(r#""time":{"secs":20,"#));
+
+    let dir = tempfile::tempdir().unwrap();
+    let mcap = dir.path().join("cropped.mcap");
+    let output = frost(
+        &[
+            "export",
+            "--start",
+            "10",
+            "--end",
+            "19.5",
+            "-o",
+            mcap.to_str().unwrap(),
+            COMPRESSED,
+        ],
+        0,
+    );
+    assert!(output.starts_with("30 messages"), "{output}");
+
+    frost(&["echo", "--start", "5", "--end", "1", COMPRESSED], 1);
+
+    // past the last time a bag can hold
+    assert_eq!(frost(&["echo", "--start", "1e30", COMPRESSED], 0), "");
+    let output = frost(&["echo", "--end", "1e30", COMPRESSED], 0);
+    assert!(
+        output.starts_with(r#"{"topic":"/chatter","time":{"secs":0,"#),
+        "{output}"
+    );
+}
+
+#[test]
+fn trajectory_stop_time() {
+    for secs in ["-1", "inf", "1e30"] {
+        let args = [
+            "trajectory",
+            "--topic",
+            "/odom",
+            "--stop-time",
+            secs,
+            COMPRESSED,
+        ];
+        frost(&args, 1);
+    }
+}
+
+#[test]
+fn topic_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let include = dir.path().join("include.txt");
+    let exclude = dir.path().join("exclude.txt");
+    // only regexes with the regex feature, and topic names without it
+    let chatter = if cfg!(feature = "regex") {
+        "/ch.*"
+    } else {
+        "/chatter"
+    };
+    std::fs::write(
+        &include,
+        format!("# chatter and time\n{chatter}\n\n  /time\n"),
+    )
+    .unwrap();
+    std::fs::write(&exclude, "/time\n").unwrap();
+    let (include, exclude) = (include.to_str().unwrap(), exclude.to_str().unwrap());
+
+    let output = frost(
+        &[
+            "echo",
+            "--topics-from",
+            include,
+            "--exclude-from",
+            exclude,
+            COMPRESSED,
+        ],
+        0,
+    );
+    assert_eq!(output.lines().count(), 100);
+    assert!(output.lines().all(|line| line.contains("\"/chatter\"")));
+
+    let output_bag = dir.path().join("retained.bag");
+    let output = frost(
+        &[
+            "retain",
+            "--exclude-from",
+            exclude,
+            "-o",
+            output_bag.to_str().unwrap(),
+            COMPRESSED,
+        ],
+        0,
+    );
+    assert!(output.starts_with("kept 200 of 300 messages"), "{output}");
+
+    // an invalid pattern is reported with its line
+    std::fs::write(dir.path().join("invalid.txt"), "/ok\n/(\n").unwrap();
+    let invalid = dir.path().join("invalid.txt");
+    let output = Command::cargo_bin("frost")
+        .unwrap()
+        .args([
+            "echo",
+            "--topics-from",
+            invalid.to_str().unwrap(),
+            COMPRESSED,
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("invalid.txt:2"));
+}
+
+#[test]
+fn topic_completions() {
+    let complete = |args: &[&str]| {
+        let output = frost(&[&["--bpaf-complete-rev=1"], args].concat(), 0);
+        output
+            .lines()
+            .filter(|line| line.starts_with('/'))
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        complete(&["echo", COMPRESSED, "--topic", "/"]),
+        [
+            "/array\tstd_msgs/Float64MultiArray",
+            "/chatter\tstd_msgs/String",
+            "/time\tstd_msgs/Time"
+        ]
+    );
+    assert_eq!(
+        complete(&["trajectory", COMPRESSED, "--topic", "/t"]),
+        ["/time"]
+    );
+    // without a bag there is nothing to suggest
+    assert!(complete(&["echo", "--topic", "/"]).is_empty());
+}
+
+#[test]
+fn outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_bag = dir.path().join("retained.bag");
+    let output_bag = output_bag.to_str().unwrap();
+    let retain = |extra: &[&str], code| {
+        let mut args = vec!["retain", "--drop", "/time", "-o", output_bag];
+        args.extend_from_slice(extra);
+        args.push(COMPRESSED);
+        frost(&args, code)
+    };
+
+    let output = retain(&["--dry-run"], 0);
+    assert!(output.contains("would write"), "{output}");
+    assert!(!std::path::Path::new(output_bag).exists());
+
+    let output = retain(&[], 0);
+    assert!(
+        output.contains(&format!("wrote {output_bag}, ")),
+        "{output}"
+    );
+    // refuses to overwrite it, even in a dry run
+    assert_eq!(retain(&[], 1), "");
+    assert_eq!(retain(&["--dry-run"], 1), "");
+    retain(&["--force"], 0);
+}
+
+#[test]
+fn unknown_topics_fail() {
+    for args in [
+        &["echo", "--topic", "/chater", COMPRESSED][..],
+        &["trajectory", "--topic", "/chater", COMPRESSED],
+    ] {
+        let output = Command::cargo_bin("frost")
+            .unwrap()
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains(
+                "no topic /chater in tests/fixtures/compressed_lz4.bag, did you mean /chatter?"
+            ),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn missing_bags_fail() {
+    assert_eq!(frost(&["info", "tests/fixtures/missing.bag"], 1), "");
+    assert_eq!(frost(&["topics", "tests/fixtures/missing.bag"], 1), "");
+}
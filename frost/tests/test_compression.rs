@@ -0,0 +1,69 @@
+use std::io::{self};
+use std::sync::Arc;
+
+use frost::compression::{self, ChunkContext, ChunkDecompressor};
+use frost::query::Query;
+use frost::writer::Compression;
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+/// Stores chunks with every byte inverted.
+struct Inverted;
+
+impl ChunkDecompressor for Inverted {
+    fn name(&self) -> &'static str {
+        "inverted"
+    }
+
+    fn decompress(&self, data: &[u8], chunk: &ChunkContext) -> io::Result<Vec<u8>> {
+        assert_eq!(data.len(), chunk.uncompressed_size);
+        Ok(data.iter().map(|byte| !byte).collect())
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(data.iter().map(|byte| !byte).collect())
+    }
+}
+
+#[test]
+fn custom_compression() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+
+    assert!("inverted".parse::<Compression>().is_err());
+    compression::register(Arc::new(Inverted));
+    assert!(compression::names().contains(&"inverted"));
+    assert!(frost::capabilities().compressions.contains(&"inverted"));
+
+    let compression: Compression = "inverted".parse().unwrap();
+    assert_eq!(compression, Compression::Custom("inverted"));
+
+    let mut writer = common::writer().with_compression(compression);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let out = writer.finish().unwrap().into_inner();
+
+    let metadata = BagMetadata::from_bytes(&out).unwrap();
+    for info in metadata.compression_info() {
+        assert_eq!(info.name, "inverted");
+    }
+
+    let out_bag = DecompressedBag::from_bytes(&out).unwrap();
+    for topic in ["/chatter", "/array", "/time"] {
+        let query = Query::new().with_topics([topic]);
+        let original = bag.read_messages(&query).unwrap();
+        let copied = out_bag.read_messages(&query).unwrap();
+        for (a, b) in original.zip(copied) {
+            assert_eq!(a.raw_bytes().unwrap(), b.raw_bytes().unwrap());
+        }
+    }
+}
+
+#[test]
+fn unregistered_compression() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer().with_compression(Compression::Custom("missing"));
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    assert!(writer.finish().is_err());
+}
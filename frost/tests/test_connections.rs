@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+
+use frost::import::Importer;
+use frost::mcap::{McapReader, McapWriter};
+use frost::query::Query;
+use frost::writer::Compression;
+use frost::{BagMetadata, BagWarning, DecompressedBag};
+
+mod common;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+
+/// Rewrites /chatter followed by /array into uncompressed chunks of a few messages each,
+/// then patches every `conn` field so /array reuses the connection id of /chatter, as if the id was recycled.
+fn reused_id_bag() -> (Vec<u8>, u32) {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::None)
+        .with_chunk_size(1024);
+    for topic in ["/chatter", "/array"] {
+        writer
+            .copy_messages(&bag, &Query::new().with_topics([topic]))
+            .unwrap();
+    }
+    let mut bytes = writer.finish().unwrap().into_inner();
+
+    let metadata = BagMetadata::from_bytes(&bytes).unwrap();
+    let id_of = |topic: &str| {
+        metadata
+            .connection_data
+            .values()
+            .find(|data| data.topic == topic)
+            .unwrap()
+            .connection_id
+    };
+    let (chatter_id, array_id) = (id_of("/chatter"), id_of("/array"));
+
+    let mut needle = b"conn=".to_vec();
+    needle.extend_from_slice(&array_id.to_le_bytes());
+    let mut patched = 0;
+    let mut i = 0;
+    while i + needle.len() <= bytes.len() {
+        if bytes[i..i + needle.len()] == needle[..] {
+            bytes[i + 5..i + 9].copy_from_slice(&chatter_id.to_le_bytes());
+            patched += 1;
+        }
+        i += 1;
+    }
+    assert!(patched > 100);
+    (bytes, chatter_id)
+}
+
+#[test]
+fn reused_connection_id_metadata() {
+    let (bytes, chatter_id) = reused_id_bag();
+    let metadata = BagMetadata::from_bytes(&bytes).unwrap();
+
+    assert_eq!(metadata.connection_count(), 2);
+    assert_eq!(
+        metadata.warnings(),
+        &[BagWarning::DuplicateConnectionId {
+            connection_id: chatter_id,
+            synthetic_id: chatter_id + 1,
+            topic: "/array".to_string(),
+        }]
+    );
+    assert_eq!(metadata.topics(), vec!["/chatter", "/array"]);
+    assert_eq!(metadata.message_count(), 200);
+    // chunk infos can't tell the two apart, so both topics may be in any chunk of the reused id
+    assert_eq!(
+        metadata.chunks_for_topic("/array").len(),
+        metadata.chunk_count()
+    );
+}
+
+#[test]
+fn reused_connection_id_messages() {
+    let (bytes, _) = reused_id_bag();
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+
+    assert_eq!(bag.metadata.warnings().len(), 1);
+    assert_eq!(
+        bag.metadata.topic_message_counts(),
+        BTreeMap::from([("/array".to_string(), 100), ("/chatter".to_string(), 100)])
+    );
+
+    for topic in ["/chatter", "/array"] {
+        let query = Query::new().with_topics([topic]);
+        let messages: Vec<_> = bag.read_messages(&query).unwrap().collect();
+        assert_eq!(messages.len(), 100, "{topic}");
+        assert!(messages.iter().all(|msg| msg.topic == topic), "{topic}");
+    }
+}
+
+/// Returns the data of the connection records in a bag's index section, which follows its chunks.
+fn connection_records(bytes: &[u8]) -> Vec<Vec<u8>> {
+    let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()) as usize;
+    let mut records = Vec::new();
+    let mut i = b"#ROSBAG V2.0\n".len();
+    while i < bytes.len() {
+        let header = &bytes[i + 4..i + 4 + u32_at(i)];
+        let data_pos = i + 4 + header.len();
+        let data = &bytes[data_pos + 4..data_pos + 4 + u32_at(data_pos)];
+        if header.windows(4).any(|field| field == b"op=\x07") {
+            records.push(data.to_vec());
+        }
+        i = data_pos + 4 + data.len();
+    }
+    records
+}
+
+fn field(name: &str, value: &[u8]) -> (String, Vec<u8>) {
+    (name.to_string(), value.to_vec())
+}
+
+#[test]
+fn connection_header_round_trip() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let chatter = bag
+        .metadata
+        .connection_data
+        .values()
+        .find(|data| data.topic == "/chatter")
+        .unwrap();
+    // recorded by another tool: out of rosbag's order, with latching=0 and a field frost doesn't know
+    let header_fields = vec![
+        field("callerid", b"/talker"),
+        field("latching", b"0"),
+        field("md5sum", chatter.md5sum.as_bytes()),
+        field("message_definition", chatter.message_definition.as_bytes()),
+        field("tcp_nodelay", b"1"),
+        field("topic", b"/chatter"),
+        field("type", chatter.data_type.as_bytes()),
+    ];
+    let connection = frost::ConnectionData {
+        caller_id: Some("/talker".to_string()),
+        latching: false,
+        header_fields: header_fields.clone(),
+        ..chatter.clone()
+    };
+
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&connection);
+    for msg_view in bag
+        .read_messages(&Query::new().with_topics(["/chatter"]))
+        .unwrap()
+    {
+        writer
+            .write_raw(conn_id, msg_view.time, msg_view.raw_bytes().unwrap())
+            .unwrap();
+    }
+    let original = writer.finish().unwrap().into_inner();
+
+    let original_bag = DecompressedBag::from_bytes(&original).unwrap();
+    let read = original_bag
+        .metadata
+        .connection_data
+        .values()
+        .next()
+        .unwrap();
+    assert_eq!(read.header_fields, header_fields);
+    assert_eq!(read.caller_id.as_deref(), Some("/talker"));
+    assert!(!read.latching);
+
+    let mut writer = common::writer();
+    writer.copy_messages(&original_bag, &Query::all()).unwrap();
+    let copied = writer.finish().unwrap().into_inner();
+    let records = connection_records(&original);
+    assert_eq!(records.len(), 1);
+    assert_eq!(connection_records(&copied), records);
+
+    // fields changed in code are substituted in place
+    let mut renamed = frost::ConnectionData {
+        topic: "/chatter_relay".to_string(),
+        latching: true,
+        caller_id: None,
+        ..connection
+    };
+    renamed.header_fields = read.header_fields.clone();
+    let mut writer = common::writer();
+    writer.add_connection(&renamed);
+    let out = writer.finish().unwrap().into_inner();
+    let metadata = BagMetadata::from_bytes(&out).unwrap();
+    let written = metadata.connection_data.values().next().unwrap();
+    assert_eq!(written.topic, "/chatter_relay");
+    assert!(written.latching);
+    assert_eq!(written.caller_id, None);
+    assert_eq!(
+        written
+            .header_fields
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "latching",
+            "md5sum",
+            "message_definition",
+            "tcp_nodelay",
+            "topic",
+            "type"
+        ]
+    );
+}
+
+#[test]
+fn fixture_connection_records_round_trip() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let mut writer = common::writer();
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let copied = writer.finish().unwrap().into_inner();
+
+    let mut original = connection_records(DECOMPRESSED);
+    let mut copied = connection_records(&copied);
+    original.sort();
+    copied.sort();
+    assert_eq!(original.len(), 3);
+    assert_eq!(copied, original);
+}
+
+#[test]
+fn non_utf8_names_round_trip() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let chatter = bag
+        .metadata
+        .connection_data
+        .values()
+        .find(|data| data.topic == "/chatter")
+        .unwrap();
+    // "/café" and "/cafè" in Latin-1, which are the same once invalid UTF-8 is replaced
+    let latin1 = |topic: &[u8]| frost::ConnectionData {
+        topic: String::from_utf8_lossy(topic).into_owned(),
+        header_fields: vec![field("topic", topic), field("type", b"std_msgs/String")],
+        ..chatter.clone()
+    };
+    let mut writer = common::writer();
+    for topic in [b"/caf\xe9", b"/caf\xe8"] {
+        let conn_id = writer.add_connection(&latin1(topic));
+        for msg_view in bag
+            .read_messages(&Query::new().with_topics(["/chatter"]))
+            .unwrap()
+        {
+            writer
+                .write_raw(conn_id, msg_view.time, msg_view.raw_bytes().unwrap())
+                .unwrap();
+        }
+    }
+    let original = writer.finish().unwrap().into_inner();
+
+    let raw_topics = |bag: &DecompressedBag| {
+        let mut topics: Vec<Vec<u8>> = bag
+            .metadata
+            .connection_data
+            .values()
+            .map(|data| data.raw_topic().to_vec())
+            .collect();
+        topics.sort();
+        topics
+    };
+    let original_bag = DecompressedBag::from_bytes(&original).unwrap();
+    assert_eq!(raw_topics(&original_bag), [b"/caf\xe8", b"/caf\xe9"]);
+    let warnings = original_bag.metadata.warnings();
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings.iter().all(|warning| matches!(
+        warning,
+        BagWarning::NonUtf8Name { topic, .. } if topic == "/caf\u{FFFD}"
+    )));
+    assert_eq!(original_bag.metadata.topics(), ["/caf\u{FFFD}"]);
+
+    let mut writer = common::writer();
+    writer.copy_messages(&original_bag, &Query::all()).unwrap();
+    let copied = writer.finish().unwrap().into_inner();
+    let mut records = connection_records(&original);
+    let mut copied = connection_records(&copied);
+    records.sort();
+    copied.sort();
+    assert_eq!(copied, records);
+
+    let mut mcap = McapWriter::new(Vec::new()).unwrap();
+    mcap.copy_messages(&original_bag, &Query::all()).unwrap();
+    let mcap = mcap.finish().unwrap();
+    let mut importer = Importer::new(common::writer());
+    importer
+        .import_mcap(McapReader::new(mcap.as_slice()).unwrap())
+        .unwrap();
+    let (imported, report) = importer.finish().unwrap();
+    assert_eq!(report.message_count, 200);
+    let imported = DecompressedBag::from_bytes(&imported.into_inner()).unwrap();
+    assert_eq!(raw_topics(&imported), raw_topics(&original_bag));
+}
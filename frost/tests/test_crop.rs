@@ -0,0 +1,140 @@
+use std::io::Cursor;
+
+use frost::query::Query;
+use frost::time::{self, Time};
+use frost::writer::Compression;
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+/// Rewrites the fixture's /chatter messages one per second into small lz4 chunks.
+fn write_chunked() -> Vec<u8> {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let connection = bag
+        .metadata
+        .connection_data
+        .values()
+        .find(|data| data.topic == "/chatter")
+        .unwrap();
+
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(256);
+    let conn_id = writer.add_connection(connection);
+    for (secs, msg_view) in bag
+        .read_messages(&Query::new().with_topics(["/chatter"]))
+        .unwrap()
+        .enumerate()
+    {
+        let time = Time {
+            secs: secs as u32,
+            nsecs: 0,
+        };
+        writer
+            .write_raw(conn_id, time, msg_view.raw_bytes().unwrap())
+            .unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn crop(bytes: &[u8], start: Time, end: Time) -> (frost::crop::CropReport, Vec<u8>) {
+    let metadata = BagMetadata::from_bytes(bytes).unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let report = metadata.crop_chunks(&bytes, start, end, &mut out).unwrap();
+    (report, out.into_inner())
+}
+
+#[test]
+fn crop_chunks() {
+    let bytes = write_chunked();
+    let metadata = BagMetadata::from_bytes(&bytes).unwrap();
+    assert!(metadata.chunk_count() > 10);
+
+    let start = Time { secs: 40, nsecs: 0 };
+    let end = Time { secs: 50, nsecs: 0 };
+    let (report, cropped) = crop(&bytes, start, end);
+    assert!(report.chunk_count < metadata.chunk_count());
+    assert!(report.start.unwrap() <= start && report.end.unwrap() >= end);
+
+    let cropped_metadata = BagMetadata::from_bytes(&cropped).unwrap();
+    assert_eq!(cropped_metadata.chunk_count(), report.chunk_count);
+    assert_eq!(cropped_metadata.message_count(), report.message_count);
+    assert_eq!(cropped_metadata.start_time(), report.start);
+    assert_eq!(cropped_metadata.end_time(), report.end);
+    assert_eq!(cropped_metadata.topics(), ["/chatter"]);
+    // chunks are copied as they are, still compressed
+    let info = cropped_metadata.compression_info();
+    assert_eq!(info.len(), 1);
+    assert_eq!(info[0].name, "lz4");
+    assert!(report.copied_bytes > info[0].total_compressed);
+    assert!(report.copied_bytes < cropped.len() as u64);
+
+    // the messages in the crop are the original messages of the kept chunks
+    let original = DecompressedBag::from_bytes(&bytes).unwrap();
+    let cropped = DecompressedBag::from_bytes(&cropped).unwrap();
+    let expected: Vec<_> = original
+        .read_messages(&Query::all())
+        .unwrap()
+        .filter(|msg_view| {
+            msg_view.time >= report.start.unwrap() && msg_view.time <= report.end.unwrap()
+        })
+        .map(|msg_view| (msg_view.time, msg_view.raw_bytes().unwrap().to_vec()))
+        .collect();
+    let actual: Vec<_> = cropped
+        .read_messages(&Query::all())
+        .unwrap()
+        .map(|msg_view| (msg_view.time, msg_view.raw_bytes().unwrap().to_vec()))
+        .collect();
+    assert_eq!(actual, expected);
+    assert!(actual.iter().any(|(time, _)| *time == start));
+    assert!(actual.iter().any(|(time, _)| *time == end));
+}
+
+#[test]
+fn crop_outside_and_whole() {
+    let bytes = write_chunked();
+    let (report, cropped) = crop(
+        &bytes,
+        Time {
+            secs: 1000,
+            nsecs: 0,
+        },
+        time::MAX,
+    );
+    assert_eq!(report.chunk_count, 0);
+    assert_eq!(report.start, None);
+    let cropped = BagMetadata::from_bytes(&cropped).unwrap();
+    assert_eq!(cropped.message_count(), 0);
+    assert!(cropped.topics().is_empty());
+
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ] {
+        let (report, cropped) = crop(bytes, time::ZERO, time::MAX);
+        assert_eq!(report.message_count, 300, "{name}");
+        let cropped = DecompressedBag::from_bytes(&cropped).unwrap();
+        let original = DecompressedBag::from_bytes(bytes).unwrap();
+        assert_eq!(
+            cropped.metadata.topic_message_counts(),
+            original.metadata.topic_message_counts(),
+            "{name}"
+        );
+        let messages = |bag: &DecompressedBag| {
+            let mut messages: Vec<_> = bag
+                .read_messages(&Query::all())
+                .unwrap()
+                .map(|msg_view| {
+                    let data = msg_view.raw_bytes().unwrap().to_vec();
+                    (msg_view.topic.to_string(), msg_view.time, data)
+                })
+                .collect();
+            messages.sort();
+            messages
+        };
+        assert_eq!(messages(&cropped), messages(&original), "{name}");
+    }
+}
@@ -0,0 +1,74 @@
+use std::io;
+
+use frost::decoders;
+use frost::dynamic::Value;
+use frost::query::Query;
+use frost::DecompressedBag;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn decode_first(bag: &DecompressedBag, topic: &str) -> Result<Value, frost::errors::Error> {
+    let query = Query::new().with_topics([topic]);
+    bag.read_messages(&query).unwrap().next().unwrap().decode()
+}
+
+// decoders are global, so each test registers them on its own topics and types
+
+#[test]
+fn topics_take_precedence_over_types() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let standard = decode_first(&bag, "/chatter").unwrap();
+
+    decoders::register_type("std_msgs/String", |msg_view| {
+        assert_eq!(msg_view.data_type(), "std_msgs/String");
+        let mut value = msg_view.decode_standard()?;
+        if let Some(Value::String(data)) = value.get_path_mut("data") {
+            *data = data.to_uppercase();
+        }
+        Ok(value)
+    });
+    let by_type = decode_first(&bag, "/chatter").unwrap();
+    assert_eq!(
+        by_type.get("data").unwrap().as_str().unwrap(),
+        standard
+            .get("data")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_uppercase()
+    );
+    // predicates see the custom decoding too
+    let query = Query::new()
+        .with_topics(["/chatter"])
+        .with_field("data", |data| data.as_str().unwrap().starts_with("FOO_"));
+    assert_eq!(bag.filter_messages(&query).unwrap().count(), 100);
+
+    decoders::register_topic("/chatter", |msg_view| {
+        Ok(Value::U32(msg_view.raw_bytes()?.len() as u32))
+    });
+    assert!(matches!(
+        decode_first(&bag, "/chatter").unwrap(),
+        Value::U32(_)
+    ));
+
+    assert!(decoders::unregister_topic("/chatter"));
+    assert!(decoders::unregister_type("std_msgs/String"));
+    assert!(!decoders::unregister_type("std_msgs/String"));
+    assert_eq!(decode_first(&bag, "/chatter").unwrap(), standard);
+}
+
+#[test]
+fn errors_carry_the_message_context() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    decoders::register_topic("/time", |_| {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "unknown encoding").into())
+    });
+    let error = decode_first(&bag, "/time").unwrap_err();
+    decoders::unregister_topic("/time");
+
+    assert!(error.to_string().contains("unknown encoding"), "{error}");
+    let context = error.context().unwrap();
+    assert_eq!(context.topic, "/time");
+    assert_eq!(context.data_type, "std_msgs/Time");
+    assert!(decode_first(&bag, "/time").is_ok());
+}
@@ -0,0 +1,103 @@
+use frost::dedup::{Dedup, DedupKey};
+use frost::query::Query;
+use frost::time::Time;
+use frost::writer::Retention;
+use frost::DecompressedBag;
+
+mod common;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+/// A bag with a std_msgs/String message on /chatter for each of `data`, a second apart.
+fn chatter(data: &[&str]) -> DecompressedBag {
+    let fixture = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let connection = fixture
+        .metadata
+        .connection_data
+        .values()
+        .find(|connection| connection.topic == "/chatter")
+        .unwrap();
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(connection);
+    for (secs, data) in data.iter().enumerate() {
+        let time = Time {
+            secs: secs as u32 + 1,
+            nsecs: 0,
+        };
+        writer
+            .write_raw(conn_id, time, &common::string(data))
+            .unwrap();
+    }
+    DecompressedBag::from_bytes(&writer.finish().unwrap().into_inner()).unwrap()
+}
+
+fn deduplicated(bag: &DecompressedBag, mut dedup: Dedup) -> Vec<String> {
+    bag.read_messages(&Query::all())
+        .unwrap()
+        .filter(|msg_view| !dedup.is_duplicate(msg_view))
+        .map(|msg_view| {
+            let value = msg_view.decode().unwrap();
+            value.get("data").unwrap().as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn drops_consecutive_duplicates() {
+    let bag = chatter(&["a", "a", "b", "a", "a"]);
+    assert_eq!(
+        deduplicated(&bag, Dedup::new().with_topic("/chatter")),
+        ["a", "b", "a"]
+    );
+    assert_eq!(
+        deduplicated(&bag, Dedup::new().with_fields("/chatter", ["data"])),
+        ["a", "b", "a"]
+    );
+    // other topics and missing fields are never duplicates
+    assert_eq!(
+        deduplicated(&bag, Dedup::new().with_topic("/time")).len(),
+        5
+    );
+    assert_eq!(
+        deduplicated(&bag, Dedup::new().with_fields("/chatter", ["missing"])).len(),
+        5
+    );
+}
+
+#[test]
+fn compares_selected_fields() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let query = Query::new().with_topics(["/array"]);
+    let count = |mut dedup: Dedup| {
+        bag.read_messages(&query)
+            .unwrap()
+            .filter(|msg_view| !dedup.is_duplicate(msg_view))
+            .count()
+    };
+    assert_eq!(count(Dedup::new()), 100);
+    assert_eq!(count(Dedup::new().with_topic("/array")), 1);
+    assert_eq!(
+        count(Dedup::new().with_fields("/array", ["layout.data_offset"])),
+        1
+    );
+}
+
+#[test]
+fn rewrites_without_duplicates() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let retention = Retention::new()
+        .drop_duplicates("/array", DedupKey::Payload)
+        .drop_duplicates("/chatter", DedupKey::Fields(vec!["data".to_string()]))
+        .keep_every("/chatter", 10);
+    let mut writer = common::writer();
+    let count = writer
+        .copy_retained(&bag, &Query::all(), &retention)
+        .unwrap();
+    assert_eq!(count, 1 + 10 + 100);
+
+    let written = DecompressedBag::from_bytes(&writer.finish().unwrap().into_inner()).unwrap();
+    let counts = written.metadata.topic_message_counts();
+    assert_eq!(counts["/array"], 1);
+    assert_eq!(counts["/chatter"], 10);
+    assert_eq!(counts["/time"], 100);
+}
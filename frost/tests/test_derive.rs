@@ -0,0 +1,93 @@
+#![cfg(feature = "derive")]
+
+use frost::msgs::Msg;
+use frost::query::Query;
+use frost::time::Time;
+use frost::DecompressedBag;
+use serde::Deserialize;
+
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+#[derive(Debug, Deserialize, frost::Msg)]
+#[ros(type = "std_msgs/String", md5sum = "992ce8a1687cec8c8bd883ec73ca41d1")]
+struct Chatter {
+    data: String,
+}
+
+#[derive(Debug, Deserialize, frost::Msg)]
+#[ros(type = "std_msgs/Time")]
+struct Stamp {
+    data: (u32, u32),
+}
+
+/// Same layout as a `std_msgs/String`, without a type to check.
+#[derive(Debug, Deserialize, frost::Msg)]
+struct Text {
+    data: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, frost::Msg)]
+#[ros(md5sum = "00000000000000000000000000000000")]
+struct Stale {
+    data: String,
+}
+
+#[test]
+fn derived_msgs() {
+    assert_eq!(Chatter::ros_type(), Some("std_msgs/String"));
+    assert_eq!(Chatter::md5sum(), Some("992ce8a1687cec8c8bd883ec73ca41d1"));
+    assert_eq!(Text::ros_type(), None);
+    assert_eq!(Text::md5sum(), None);
+
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let query = Query::new().with_topics(["/chatter"]);
+    for (i, msg_view) in bag.read_messages(&query).unwrap().enumerate() {
+        assert_eq!(
+            msg_view.instantiate::<Chatter>().unwrap().data,
+            format!("foo_{i}")
+        );
+        assert_eq!(
+            msg_view.instantiate::<Text>().unwrap().data,
+            format!("foo_{i}")
+        );
+    }
+
+    let query = Query::new().with_topics(["/time"]);
+    let msg_view = bag.read_messages(&query).unwrap().nth(1).unwrap();
+    let stamp = msg_view.instantiate::<Stamp>().unwrap();
+    assert_eq!(
+        Time {
+            secs: stamp.data.0,
+            nsecs: stamp.data.1
+        },
+        Time {
+            secs: 1,
+            nsecs: 2000
+        }
+    );
+}
+
+#[test]
+fn mismatched_types() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let query = Query::new().with_topics(["/time"]);
+    let msg_view = bag.read_messages(&query).unwrap().next().unwrap();
+    assert_eq!(
+        msg_view.instantiate::<Chatter>().unwrap_err().to_string(),
+        "invalid field: cannot instantiate a std_msgs/String from a std_msgs/Time message on /time"
+    );
+
+    let query = Query::new().with_topics(["/chatter"]);
+    let msg_view = bag.read_messages(&query).unwrap().next().unwrap();
+    assert_eq!(
+        msg_view.instantiate::<Stale>().unwrap_err().to_string(),
+        "invalid field: cannot instantiate a message with md5sum 00000000000000000000000000000000 \
+         from one with md5sum 992ce8a1687cec8c8bd883ec73ca41d1 on /chatter"
+    );
+}
+
+#[test]
+fn reports_the_feature() {
+    assert!(frost::capabilities().features.contains(&"derive"));
+}
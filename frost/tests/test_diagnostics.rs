@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use frost::diagnostics::{DiagnosticLevel, DiagnosticsAggregator};
+use frost::dynamic::Value;
+use frost::errors::DecodeError;
+use frost::query::Query;
+use frost::time::Time;
+use frost::{ConnectionData, DecompressedBag};
+
+mod common;
+use common::string;
+
+const DIAGNOSTIC_ARRAY_DEFINITION: &str = "\
+Header header
+DiagnosticStatus[] status
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: diagnostic_msgs/DiagnosticStatus
+byte OK=0
+byte WARN=1
+byte ERROR=2
+byte STALE=3
+byte level
+string name
+string message
+string hardware_id
+KeyValue[] values
+================================================================================
+MSG: diagnostic_msgs/KeyValue
+string key
+string value
+";
+
+fn time(secs: u32) -> Time {
+    Time { secs, nsecs: 0 }
+}
+
+fn array(statuses: &[(i8, &str, &str)]) -> Value {
+    let statuses = statuses
+        .iter()
+        .map(|&(level, name, message)| {
+            Value::Message(vec![
+                ("level".to_string(), Value::I8(level)),
+                ("name".to_string(), Value::String(name.to_string())),
+                ("message".to_string(), Value::String(message.to_string())),
+                ("hardware_id".to_string(), Value::String("hw".to_string())),
+                ("values".to_string(), Value::Array(vec![])),
+            ])
+        })
+        .collect();
+    Value::Message(vec![("status".to_string(), Value::Array(statuses))])
+}
+
+fn serialize_array(statuses: &[(i8, &str, &str)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0u32.to_le_bytes()); // seq
+    data.extend_from_slice(&[0; 8]); // stamp
+    data.extend(string(""));
+    data.extend_from_slice(&(statuses.len() as u32).to_le_bytes());
+    for &(level, name, message) in statuses {
+        data.push(level as u8);
+        data.extend(string(name));
+        data.extend(string(message));
+        data.extend(string("hw"));
+        data.extend_from_slice(&0u32.to_le_bytes()); // values
+    }
+    data
+}
+
+#[test]
+fn time_at_each_level() {
+    let mut aggregator = DiagnosticsAggregator::new();
+    aggregator
+        .push_value(time(10), &array(&[(0, "lidar", "ok"), (0, "motors", "ok")]))
+        .unwrap();
+    aggregator
+        .push_value(time(12), &array(&[(1, "lidar", "low rate")]))
+        .unwrap();
+    aggregator
+        .push_value(
+            time(15),
+            &array(&[(2, "lidar", "no data"), (0, "motors", "ok")]),
+        )
+        .unwrap();
+    aggregator
+        .push_value(time(16), &array(&[(2, "lidar", "still no data")]))
+        .unwrap();
+    aggregator
+        .push_value(time(20), &array(&[(0, "lidar", "recovered")]))
+        .unwrap();
+    let report = aggregator.finish();
+
+    assert_eq!(report.message_count, 5);
+    assert_eq!((report.start, report.end), (Some(time(10)), Some(time(20))));
+
+    let lidar = &report.components["lidar"];
+    assert_eq!(lidar.status_count, 5);
+    assert_eq!(lidar.time_in(DiagnosticLevel::Ok), Duration::from_secs(2));
+    assert_eq!(lidar.time_in(DiagnosticLevel::Warn), Duration::from_secs(3));
+    assert_eq!(
+        lidar.time_in(DiagnosticLevel::Error),
+        Duration::from_secs(5)
+    );
+    assert_eq!(lidar.first_warn, Some((time(12), "low rate".to_string())));
+    assert_eq!(lidar.first_error, Some((time(15), "no data".to_string())));
+    assert_eq!(lidar.first_stale, None);
+    assert_eq!(lidar.last_level, DiagnosticLevel::Ok);
+    assert_eq!(lidar.last_message, "recovered");
+    assert_eq!(lidar.worst_level(), DiagnosticLevel::Error);
+
+    // the motors' last status lasts until the last array
+    let motors = &report.components["motors"];
+    assert_eq!(motors.time_in(DiagnosticLevel::Ok), Duration::from_secs(10));
+    assert_eq!(motors.worst_level(), DiagnosticLevel::Ok);
+
+    let failures = report.failures();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, "lidar");
+}
+
+#[test]
+fn invalid_arrays() {
+    let mut aggregator = DiagnosticsAggregator::new();
+    let err = aggregator
+        .push_value(time(1), &array(&[(7, "lidar", "?")]))
+        .unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+
+    let err = aggregator
+        .push_value(time(1), &Value::Message(vec![]))
+        .unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+}
+
+#[test]
+fn from_bag() {
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&ConnectionData {
+        md5sum: "60810da900de1dd6ddd437c3503511da".to_string(),
+        ..common::connection(
+            "/diagnostics",
+            "diagnostic_msgs/DiagnosticArray",
+            DIAGNOSTIC_ARRAY_DEFINITION,
+        )
+    });
+    for (secs, statuses) in [
+        (1, &[(0, "camera", "ok")][..]),
+        (2, &[(3, "camera", "stale")][..]),
+        (4, &[(0, "camera", "ok"), (2, "gps", "no fix")][..]),
+    ] {
+        writer
+            .write_raw(conn_id, time(secs), &serialize_array(statuses))
+            .unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    let report = bag.diagnostics(&Query::all()).unwrap();
+    assert_eq!(report.message_count, 3);
+    let camera = &report.components["camera"];
+    assert_eq!(
+        camera.time_in(DiagnosticLevel::Stale),
+        Duration::from_secs(2)
+    );
+    assert_eq!(camera.first_stale, Some((time(2), "stale".to_string())));
+    assert_eq!(camera.hardware_id, "hw");
+    assert_eq!(
+        report
+            .failures()
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>(),
+        ["camera", "gps"]
+    );
+
+    let other_topic = bag
+        .diagnostics(&Query::new().with_topics(["/diagnostics_agg"]))
+        .unwrap();
+    assert_eq!(other_topic.message_count, 0);
+    assert!(other_topic.components.is_empty());
+}
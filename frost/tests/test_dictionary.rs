@@ -0,0 +1,65 @@
+#![cfg(feature = "zstd")]
+
+use std::sync::Arc;
+
+use frost::dictionary::{self, DictionaryTrainer};
+use frost::query::Query;
+use frost::writer::Compression;
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+use common::msgs::std_msgs;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+fn check_chatter(bytes: &[u8]) {
+    let bag = DecompressedBag::from_bytes(bytes).unwrap();
+    let query = Query::new().with_topics(["/chatter"]);
+    let mut count = 0;
+    for (i, msg_view) in bag.read_messages(&query).unwrap().enumerate() {
+        let msg = msg_view.instantiate::<std_msgs::String>().unwrap();
+        assert_eq!(msg.data, format!("foo_{i}"));
+        count += 1;
+    }
+    assert_eq!(count, 100);
+}
+
+#[test]
+fn zstd_without_dictionary() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer().with_compression(Compression::Zstd);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let out = writer.finish().unwrap().into_inner();
+
+    let metadata = BagMetadata::from_bytes(&out).unwrap();
+    assert_eq!(metadata.compression_info()[0].name, "zstd");
+    check_chatter(&out);
+}
+
+#[test]
+fn trained_dictionary_round_trip() {
+    let mut trainer = DictionaryTrainer::new(1024);
+    for bytes in [DECOMPRESSED, COMPRESSED_LZ4] {
+        trainer
+            .add_bag(&DecompressedBag::from_bytes(bytes).unwrap())
+            .unwrap();
+    }
+    assert_eq!(trainer.bag_count(), 2);
+    assert_eq!(trainer.sample_count(), 600);
+    let dict = Arc::new(trainer.train().unwrap());
+    assert!(dict.as_bytes().len() <= 1024);
+
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let mut writer = common::writer().with_dictionary(dict.clone());
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let out = writer.finish().unwrap().into_inner();
+
+    // the dictionary is needed to load messages, but not metadata
+    let metadata = BagMetadata::from_bytes(&out).unwrap();
+    assert_eq!(metadata.message_count(), 300);
+    assert!(DecompressedBag::from_bytes(&out).is_err());
+
+    dictionary::register(dict);
+    check_chatter(&out);
+}
@@ -0,0 +1,433 @@
+use frost::dynamic::{DecodeLimits, Schema, Value};
+use frost::errors::{DecodeError, ErrorKind};
+use frost::query::Query;
+use frost::time::Time;
+use frost::DecompressedBag;
+
+mod common;
+use common::msgs::std_msgs;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+#[test]
+#[allow(clippy::approx_constant)]
+fn decode_matches_fixtures() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+
+        let query = Query::new().with_topics(["/chatter"]);
+        for (i, msg_view) in bag.read_messages(&query).unwrap().enumerate() {
+            let value = msg_view.decode().unwrap();
+            assert_eq!(
+                value,
+                Value::Message(vec![("data".into(), Value::String(format!("foo_{i}")))]),
+                "{name}"
+            );
+        }
+
+        let query = Query::new().with_topics(["/time"]);
+        for (i, msg_view) in bag.read_messages(&query).unwrap().enumerate() {
+            let value = msg_view.decode().unwrap();
+            assert_eq!(
+                value.get("data"),
+                Some(&Value::Time(Time {
+                    secs: i as u32,
+                    nsecs: 1000 + i as u32 * 1000
+                })),
+                "{name}"
+            );
+        }
+
+        let query = Query::new().with_topics(["/array"]);
+        for msg_view in bag.read_messages(&query).unwrap() {
+            let value = msg_view.decode().unwrap();
+            let data: Vec<f64> = match value.get("data") {
+                Some(Value::Array(values)) => values.iter().flat_map(Value::as_f64).collect(),
+                other => panic!("{name}: unexpected data {other:?}"),
+            };
+            assert_eq!(data, [3.14; 3], "{name}");
+            assert!(value.get("layout").unwrap().get("dim").is_some(), "{name}");
+        }
+    }
+}
+
+#[test]
+fn decode_to_json() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let query = Query::new().with_topics(["/time"]);
+    let msg_view = bag.read_messages(&query).unwrap().nth(1).unwrap();
+    let json = serde_json::to_string(&msg_view.decode().unwrap()).unwrap();
+    assert_eq!(json, r#"{"data":{"secs":1,"nsecs":2000}}"#);
+}
+
+#[test]
+#[allow(clippy::approx_constant)]
+fn get_path() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let query = Query::new().with_topics(["/array"]);
+    let value = bag
+        .read_messages(&query)
+        .unwrap()
+        .next()
+        .unwrap()
+        .decode()
+        .unwrap();
+
+    assert_eq!(value.get_path("data.2"), Some(&Value::F64(3.14)));
+    assert_eq!(value.get_path("layout.data_offset"), Some(&Value::U32(0)));
+    assert_eq!(value.get_path("data.3"), None);
+    assert_eq!(value.get_path("data.x"), None);
+    assert_eq!(value.get_path("layout.missing"), None);
+}
+
+#[test]
+fn encode_round_trips_fixtures() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    for msg_view in bag.read_messages(&Query::all()).unwrap() {
+        let connection = bag
+            .metadata
+            .connection_data
+            .values()
+            .find(|data| data.topic == msg_view.topic)
+            .unwrap();
+        let schema = Schema::parse(&connection.data_type, &connection.message_definition).unwrap();
+        let data = schema.encode(&msg_view.decode().unwrap()).unwrap();
+        assert_eq!(
+            data,
+            msg_view.raw_bytes().unwrap()[4..],
+            "{}",
+            msg_view.topic
+        );
+    }
+}
+
+const STAMPED_POINTS: &str = "\
+Header header
+geometry_msgs/Point[] points
+float64[2] range
+uint8[] data
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y
+float64 z
+";
+
+#[test]
+fn encode_built_values() {
+    let schema = Schema::parse("test_msgs/StampedPoints", STAMPED_POINTS).unwrap();
+    let mut value = schema.default_value();
+    assert_eq!(
+        value.get("range"),
+        Some(&Value::Array(vec![Value::F64(0.0); 2]))
+    );
+    assert_eq!(value.get("data"), Some(&Value::Bytes(Vec::new())));
+
+    *value.get_path_mut("header.frame_id").unwrap() = Value::String("map".into());
+    *value.get_path_mut("range.1").unwrap() = Value::F64(10.0);
+    let point = Value::Message(vec![
+        ("x".into(), Value::F64(1.0)),
+        ("y".into(), Value::F64(2.0)),
+        ("z".into(), Value::F64(3.0)),
+    ]);
+    *value.get_mut("points").unwrap() = Value::Array(vec![point.clone(), point]);
+    // uint8 arrays can also be built element by element
+    *value.get_mut("data").unwrap() = Value::Array(vec![Value::U8(7), Value::U8(8)]);
+
+    let data = schema.encode(&value).unwrap();
+    assert_eq!(data.len(), 4 + 8 + 4 + 3 + 4 + 2 * 24 + 16 + 4 + 2);
+    let decoded = schema.decode(&data).unwrap();
+    assert_eq!(
+        decoded.get_path("header.frame_id").unwrap().as_str(),
+        Some("map")
+    );
+    assert_eq!(decoded.get_path("points.1.z"), Some(&Value::F64(3.0)));
+    assert_eq!(decoded.get_path("range.1"), Some(&Value::F64(10.0)));
+    assert_eq!(decoded.get("data"), Some(&Value::Bytes(vec![7, 8])));
+}
+
+#[test]
+fn encode_validates() {
+    let schema = Schema::parse("test_msgs/StampedPoints", STAMPED_POINTS).unwrap();
+    let error = |edit: fn(&mut Value)| {
+        let mut value = schema.default_value();
+        edit(&mut value);
+        schema.encode(&value).unwrap_err().to_string()
+    };
+
+    assert_eq!(
+        error(|value| *value.get_path_mut("header.seq").unwrap() = Value::I32(1)),
+        "invalid field: test_msgs/StampedPoints.header.seq should be uint32, got an int32"
+    );
+    assert_eq!(
+        error(|value| *value.get_mut("range").unwrap() = Value::Array(vec![Value::F64(1.0)])),
+        "invalid field: test_msgs/StampedPoints.range has 1 elements, expected 2"
+    );
+    assert_eq!(
+        error(|value| *value.get_mut("points").unwrap() = Value::Array(vec![Value::F64(1.0)])),
+        "invalid field: test_msgs/StampedPoints.points.0 should be geometry_msgs/Point, got a float64"
+    );
+    assert_eq!(
+        error(|value| match value {
+            Value::Message(fields) => fields.retain(|(name, _)| name != "data"),
+            _ => unreachable!(),
+        }),
+        "invalid field: test_msgs/StampedPoints.data is missing"
+    );
+    assert_eq!(
+        error(|value| match value {
+            Value::Message(fields) => fields.push(("extra".into(), Value::Bool(true))),
+            _ => unreachable!(),
+        }),
+        "invalid field: test_msgs/StampedPoints has no extra field"
+    );
+}
+
+#[test]
+fn project_fields() {
+    let schema = Schema::parse("test_msgs/StampedPoints", STAMPED_POINTS).unwrap();
+    let mut value = schema.default_value();
+    *value.get_path_mut("header.seq").unwrap() = Value::U32(7);
+    *value.get_path_mut("header.frame_id").unwrap() = Value::String("map".into());
+    let point = |x: f64| {
+        Value::Message(vec![
+            ("x".into(), Value::F64(x)),
+            ("y".into(), Value::F64(x * 2.0)),
+            ("z".into(), Value::F64(x * 3.0)),
+        ])
+    };
+    *value.get_mut("points").unwrap() = Value::Array(vec![point(1.0), point(2.0)]);
+    *value.get_mut("data").unwrap() = Value::Bytes(vec![1; 1024]);
+    let data = schema.encode(&value).unwrap();
+
+    let projected = schema
+        .project(&["header.stamp", "points.y", "range"])
+        .unwrap();
+    assert_eq!(
+        projected.decode(&data).unwrap(),
+        Value::Message(vec![
+            (
+                "header".into(),
+                Value::Message(vec![(
+                    "stamp".into(),
+                    Value::Time(Time { secs: 0, nsecs: 0 })
+                )])
+            ),
+            (
+                "points".into(),
+                Value::Array(vec![
+                    Value::Message(vec![("y".into(), Value::F64(2.0))]),
+                    Value::Message(vec![("y".into(), Value::F64(4.0))]),
+                ])
+            ),
+            ("range".into(), Value::Array(vec![Value::F64(0.0); 2])),
+        ])
+    );
+
+    // a whole message, and fields after skipped arrays
+    let projected = schema.project(&["header", "data"]).unwrap();
+    let decoded = projected.decode(&data).unwrap();
+    assert_eq!(decoded.get("header"), value.get("header"));
+    assert_eq!(decoded.get("data"), value.get("data"));
+    assert_eq!(decoded.get("points"), None);
+
+    // truncated messages still fail, even within skipped fields
+    assert!(projected.decode(&data[..data.len() - 1]).is_err());
+    assert!(schema
+        .project(&["header.seq"])
+        .unwrap()
+        .decode(&data[..30])
+        .is_err());
+}
+
+#[test]
+fn project_skips_variable_sized_arrays() {
+    let definition = "string[] names\ngeometry_msgs/Pose[] poses\nint32 value\n\
+        ================================================================================\n\
+        MSG: geometry_msgs/Pose\nstring frame\nfloat64[2] xy\n";
+    let schema = Schema::parse("test_msgs/Named", definition).unwrap();
+    let mut value = schema.default_value();
+    *value.get_mut("names").unwrap() =
+        Value::Array(vec![Value::String("a".into()), Value::String("bcd".into())]);
+    let pose = Value::Message(vec![
+        ("frame".into(), Value::String("odom".into())),
+        ("xy".into(), Value::Array(vec![Value::F64(1.0); 2])),
+    ]);
+    *value.get_mut("poses").unwrap() = Value::Array(vec![pose.clone(), pose]);
+    *value.get_mut("value").unwrap() = Value::I32(-5);
+    let data = schema.encode(&value).unwrap();
+
+    let projected = schema.project(&["value"]).unwrap();
+    assert_eq!(
+        projected.decode(&data).unwrap(),
+        Value::Message(vec![("value".into(), Value::I32(-5))])
+    );
+    let projected = schema.project(&["poses.xy", "value"]).unwrap();
+    assert_eq!(
+        projected.decode(&data).unwrap().get_path("poses.1.xy.1"),
+        Some(&Value::F64(1.0))
+    );
+}
+
+#[test]
+fn fixed_array_sizes_overflowing() {
+    let foo = "================================================================================\n\
+        MSG: pkg/Foo\nfloat64[4611686018427387904] x\n";
+    let schema = Schema::parse("pkg/Bar", &format!("Foo[2] f\n{foo}")).unwrap();
+    assert!(schema.decode(&[0; 16]).is_err());
+
+    // skipping over the array needs its size
+    let schema = Schema::parse("pkg/Bar", &format!("Foo[2] f\nint32 value\n{foo}")).unwrap();
+    let error = schema.project(&["value"]).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::Decode(DecodeError::InvalidDefinition(_))
+    ));
+}
+
+#[test]
+fn project_unknown_fields() {
+    let schema = Schema::parse("test_msgs/StampedPoints", STAMPED_POINTS).unwrap();
+    for (paths, error) in [
+        (
+            ["header.missing"],
+            "invalid field: std_msgs/Header has no missing field",
+        ),
+        (
+            ["range.x"],
+            "invalid field: test_msgs/StampedPoints.range is not a message",
+        ),
+        (
+            ["nope"],
+            "invalid field: test_msgs/StampedPoints has no nope field",
+        ),
+    ] {
+        assert_eq!(schema.project(&paths).unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn query_with_fields() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let query = Query::new()
+        .with_topics(["/array"])
+        .with_fields(["layout.dim.label"]);
+    let mut count = 0;
+    for msg_view in bag.read_messages(&query).unwrap() {
+        let value = msg_view.decode().unwrap();
+        assert_eq!(value.get("data"), None);
+        assert_eq!(value.get_path("layout.data_offset"), None);
+        assert!(value.get_path("layout.dim").is_some());
+        count += 1;
+    }
+    assert_eq!(count, 100);
+}
+
+fn is_limit_exceeded(error: frost::errors::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::Decode(DecodeError::LimitExceeded(_))
+    )
+}
+
+#[test]
+fn decode_limits() {
+    let schema = Schema::parse(
+        "test_msgs/Limits",
+        "string name\nuint8[] data\nfloat64[] values",
+    )
+    .unwrap();
+    let mut data = Vec::new();
+    for field in [&b"frost"[..], &[1, 2, 3]] {
+        data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        data.extend_from_slice(field);
+    }
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&[0; 16]);
+
+    assert_eq!(
+        schema
+            .decode_with_limits(&data, &DecodeLimits::new())
+            .unwrap(),
+        schema.decode(&data).unwrap()
+    );
+    let limits = DecodeLimits::new()
+        .with_max_string_len(5)
+        .with_max_array_len(3)
+        .with_max_message_len(data.len());
+    assert!(schema.decode_with_limits(&data, &limits).is_ok());
+    assert!(schema.check_limits(&data, &limits).is_ok());
+
+    for limits in [
+        DecodeLimits::new().with_max_string_len(4),
+        DecodeLimits::new().with_max_array_len(2),
+        DecodeLimits::new().with_max_array_len(1),
+        DecodeLimits::new().with_max_message_len(data.len() - 1),
+    ] {
+        let error = schema.decode_with_limits(&data, &limits).unwrap_err();
+        assert!(is_limit_exceeded(error));
+        let error = schema.check_limits(&data, &limits).unwrap_err();
+        assert!(is_limit_exceeded(error));
+    }
+
+    let error = schema
+        .decode_with_limits(&data, &DecodeLimits::new().with_max_array_len(2))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "decode limit exceeded: an array of 3 elements at byte 9 is over the limit of 2"
+    );
+}
+
+#[test]
+fn decode_limits_catch_corrupt_lengths() {
+    // a claimed count of empty messages fits in any message
+    let schema = Schema::parse("test_msgs/Empties", "test_msgs/Empty[] empties\n================================================================================\nMSG: test_msgs/Empty\n").unwrap();
+    let data = u32::MAX.to_le_bytes();
+    let limits = DecodeLimits::new().with_max_array_len(1024);
+    assert!(is_limit_exceeded(
+        schema.decode_with_limits(&data, &limits).unwrap_err()
+    ));
+    assert!(is_limit_exceeded(
+        schema.check_limits(&data, &limits).unwrap_err()
+    ));
+
+    // lengths past the end of the message fail regardless of limits
+    let schema = Schema::parse("std_msgs/String", "string data").unwrap();
+    let data = [0xff, 0xff, 0xff, 0x7f, b'a'];
+    for limits in [DecodeLimits::new(), limits] {
+        assert!(schema.decode_with_limits(&data, &limits).is_err());
+        assert!(schema.check_limits(&data, &limits).is_err());
+    }
+}
+
+#[test]
+fn query_with_decode_limits() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    // "foo_0" to "foo_9" fit, "foo_10" onwards don't
+    let query = Query::new()
+        .with_topics(["/chatter"])
+        .with_decode_limits(DecodeLimits::new().with_max_string_len(5));
+    for (i, msg_view) in bag.read_messages(&query).unwrap().enumerate() {
+        let decoded = msg_view.decode();
+        let instantiated = msg_view.instantiate::<std_msgs::String>();
+        if i < 10 {
+            assert!(decoded.is_ok());
+            assert_eq!(instantiated.unwrap().data, format!("foo_{i}"));
+        } else {
+            assert!(is_limit_exceeded(decoded.unwrap_err()));
+            assert!(is_limit_exceeded(instantiated.unwrap_err()));
+        }
+    }
+}
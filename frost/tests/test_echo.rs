@@ -0,0 +1,113 @@
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+use std::collections::BTreeMap;
+
+use frost::echo::{EchoFormat, EchoWriter};
+use frost::query::Query;
+use frost::DecompressedBag;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn echo(format: EchoFormat, query: &Query) -> Vec<u8> {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let mut writer = EchoWriter::new(Vec::new(), format);
+    for msg_view in bag.read_messages(query).unwrap() {
+        writer.write_message(&msg_view).unwrap();
+    }
+    writer.into_inner()
+}
+
+/// The records of a query as JSON lines, which the other formats are compared against.
+fn json_records(query: &Query) -> Vec<serde_json::Value> {
+    String::from_utf8(echo(EchoFormat::Json, query))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+/// Messages received at the same time may be read in either order, so records are compared per topic.
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+fn by_topic(records: Vec<serde_json::Value>) -> BTreeMap<String, Vec<serde_json::Value>> {
+    let mut topics: BTreeMap<String, Vec<serde_json::Value>> = BTreeMap::new();
+    for record in records {
+        let topic = record["topic"].as_str().unwrap().to_string();
+        topics.entry(topic).or_default().push(record);
+    }
+    topics
+}
+
+#[test]
+fn json_lines() {
+    let records = json_records(&Query::new().with_topics(["/chatter"]));
+    assert_eq!(records.len(), 100);
+    assert_eq!(records[0]["topic"], "/chatter");
+    assert_eq!(records[0]["type"], "std_msgs/String");
+    assert_eq!(records[0]["message"]["data"], "foo_0");
+    assert!(records[0]["time"]["secs"].is_u64());
+    assert!(records[0]["time"]["nsecs"].is_u64());
+}
+
+#[test]
+#[cfg(feature = "cbor")]
+fn cbor_sequence() {
+    let query = Query::new().with_topics(["/chatter", "/array"]);
+    let bytes = echo(EchoFormat::Cbor, &query);
+    let mut reader = bytes.as_slice();
+    let mut records = Vec::new();
+    while !reader.is_empty() {
+        records.push(ciborium::from_reader::<serde_json::Value, _>(&mut reader).unwrap());
+    }
+    assert_eq!(by_topic(records), by_topic(json_records(&query)));
+}
+
+#[test]
+#[cfg(feature = "msgpack")]
+fn msgpack_stream() {
+    let query = Query::new().with_topics(["/chatter", "/array"]);
+    let bytes = echo(EchoFormat::MsgPack, &query);
+    let mut reader = bytes.as_slice();
+    let mut records = Vec::new();
+    while !reader.is_empty() {
+        records.push(rmp_serde::from_read::<_, serde_json::Value>(&mut reader).unwrap());
+    }
+    assert_eq!(by_topic(records), by_topic(json_records(&query)));
+}
+
+#[test]
+fn raw_frames() {
+    let query = Query::new().with_topics(["/chatter"]);
+    let bytes = echo(EchoFormat::Raw, &query);
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+
+    let read_u32 =
+        |bytes: &[u8], at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let mut at = 0;
+    for msg_view in bag.read_messages(&query).unwrap() {
+        let topic_len = read_u32(&bytes, at) as usize;
+        at += 4;
+        assert_eq!(&bytes[at..at + topic_len], b"/chatter");
+        at += topic_len;
+        assert_eq!(read_u32(&bytes, at), msg_view.time.secs);
+        assert_eq!(read_u32(&bytes, at + 4), msg_view.time.nsecs);
+        at += 8;
+        let len = read_u32(&bytes, at) as usize;
+        at += 4;
+        assert_eq!(&bytes[at..at + len], &msg_view.raw_bytes().unwrap()[4..]);
+        at += len;
+    }
+    assert_eq!(at, bytes.len());
+}
+
+#[test]
+fn format_from_str() {
+    #[cfg(feature = "msgpack")]
+    assert_eq!("msgpack".parse(), Ok(EchoFormat::MsgPack));
+    #[cfg(feature = "cbor")]
+    assert_eq!("cbor".parse(), Ok(EchoFormat::Cbor));
+    #[cfg(not(feature = "cbor"))]
+    assert!("cbor"
+        .parse::<EchoFormat>()
+        .unwrap_err()
+        .contains("cbor feature"));
+    assert!("yaml".parse::<EchoFormat>().is_err());
+}
@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use frost::edit::{self, IndexEditor};
+#[cfg(feature = "md5")]
+use frost::query::Query;
+use frost::BagMetadata;
+#[cfg(feature = "md5")]
+use frost::DecompressedBag;
+use tempfile::{tempdir, TempDir};
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+/// A copy of the fixture to edit.
+fn copy() -> (TempDir, PathBuf) {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("edited.bag");
+    std::fs::copy(FILE_PATH, &path).unwrap();
+    (dir, path)
+}
+
+fn journal(path: &Path) -> PathBuf {
+    path.with_file_name("edited.bag.frost-journal")
+}
+
+#[test]
+#[cfg(feature = "md5")]
+fn edits_connections_in_place() {
+    let (_dir, path) = copy();
+    let original = std::fs::read(&path).unwrap();
+
+    let mut editor = IndexEditor::open(&path).unwrap();
+    // the same fields as std_msgs/String, so the same md5sum
+    let (md5sum, definition) = ("992ce8a1687cec8c8bd883ec73ca41d1", "string data\n");
+    assert!(editor
+        .set_type(
+            "/chatter",
+            "test_msgs/Chatter",
+            "0123456789abcdef0123456789abcdef",
+            definition
+        )
+        .is_err());
+    assert_eq!(
+        editor
+            .set_type("/chatter", "test_msgs/Chatter", md5sum, definition)
+            .unwrap(),
+        1
+    );
+    assert_eq!(editor.set_latching("/time", true).unwrap(), 1);
+    let report = editor.commit().unwrap();
+    assert_eq!(report.edited_connections, 2);
+    assert!(!journal(&path).exists());
+
+    // only the index changed
+    let edited = std::fs::read(&path).unwrap();
+    let index_pos = report.index_pos as usize;
+    assert_eq!(edited[..index_pos], original[..index_pos]);
+    assert_eq!(edited.len() as u64, report.index_pos + report.new_index_len);
+    assert_eq!(
+        original.len() as u64,
+        report.index_pos + report.old_index_len
+    );
+
+    let metadata = BagMetadata::from_file(&path).unwrap();
+    assert!(metadata
+        .topics_and_types()
+        .contains(&("/chatter", "test_msgs/Chatter")));
+    for connection in IndexEditor::open(&path).unwrap().connections().values() {
+        match connection.topic.as_str() {
+            "/chatter" => {
+                assert_eq!(connection.md5sum, md5sum);
+                assert_eq!(connection.message_definition, definition);
+            }
+            topic => assert_eq!(connection.latching, topic == "/time", "{topic}"),
+        }
+    }
+    // messages are still read through the index
+    let bag = DecompressedBag::from_file(&path).unwrap();
+    assert_eq!(bag.read_messages(&Query::all()).unwrap().count(), 300);
+    let query = Query::new().with_topics(["/chatter"]);
+    let msg_view = bag.read_messages(&query).unwrap().next().unwrap();
+    assert_eq!(msg_view.data_type(), "test_msgs/Chatter");
+    assert!(msg_view.decode().is_ok());
+}
+
+#[test]
+fn leaves_unedited_bags_alone() {
+    let (_dir, path) = copy();
+    let original = std::fs::read(&path).unwrap();
+
+    let mut editor = IndexEditor::open(&path).unwrap();
+    assert!(editor.set_latching("/missing", true).is_err());
+    // already not latching
+    editor.set_latching("/time", false).unwrap();
+    assert_eq!(editor.commit().unwrap().edited_connections, 0);
+    assert_eq!(std::fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn recovers_interrupted_edits() {
+    let (_dir, path) = copy();
+    let original = std::fs::read(&path).unwrap();
+    let mut editor = IndexEditor::open(&path).unwrap();
+    editor.set_latching("/time", true).unwrap();
+    let report = editor.commit().unwrap();
+
+    // as if the edit was interrupted after overwriting the index
+    let mut journal_bytes = b"frost-journal 1\n".to_vec();
+    journal_bytes.extend_from_slice(&report.index_pos.to_le_bytes());
+    journal_bytes.extend_from_slice(&original[report.index_pos as usize..]);
+    std::fs::write(journal(&path), journal_bytes).unwrap();
+
+    assert!(IndexEditor::open(&path).is_err());
+    assert!(edit::recover(&path).unwrap());
+    assert_eq!(std::fs::read(&path).unwrap(), original);
+    assert!(!journal(&path).exists());
+    assert!(!edit::recover(&path).unwrap());
+    assert!(IndexEditor::open(&path).is_ok());
+}
+
+#[test]
+fn edit_command() {
+    let (_dir, path) = copy();
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["edit", "--topic", "/time", "--latching", "true"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("edited 1 connections"), "{stdout}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["edit", "--topic", "/time", "--type", "std_msgs/Time"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let definition = path.with_file_name("Chatter.definition");
+    std::fs::write(&definition, "string data\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["edit", "--topic", "/chatter", "--type", "test_msgs/Chatter"])
+        .args([
+            "--md5sum",
+            "992ce8a1687cec8c8bd883ec73ca41d1",
+            "--definition",
+        ])
+        .arg(&definition)
+        .arg(&path)
+        .output()
+        .unwrap();
+    // checking the definition against the md5sum needs the md5 feature
+    assert_eq!(output.status.success(), cfg!(feature = "md5"), "{output:?}");
+    assert_eq!(
+        BagMetadata::from_file(&path)
+            .unwrap()
+            .topics_and_types()
+            .contains(&("/chatter", "test_msgs/Chatter")),
+        cfg!(feature = "md5")
+    );
+}
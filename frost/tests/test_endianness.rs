@@ -0,0 +1,89 @@
+//! Bags and messages are little-endian whatever the host, which `cross test --target s390x-unknown-linux-gnu` checks
+//! on a big-endian one in CI.
+
+use frost::dynamic::{Schema, Value};
+use frost::query::Query;
+use frost::time::Time;
+use frost::DecompressedBag;
+
+mod common;
+
+const DEFINITION: &str =
+    "int16 a\nuint32 b\nfloat32 c\nfloat64 d\nint64 e\ntime f\nduration g\nuint16[] h";
+
+fn little_endian_message() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&[0xfe, 0xff]);
+    data.extend_from_slice(&[0x04, 0x03, 0x02, 0x01]);
+    data.extend_from_slice(&[0x00, 0x00, 0xc0, 0x3f]);
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0xc0]);
+    data.extend_from_slice(&[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+    data.extend_from_slice(&[0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]);
+    data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x00, 0x01, 0x00, 0x00]);
+    data.extend_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01]);
+    data
+}
+
+#[test]
+fn decodes_and_encodes_little_endian() {
+    let schema = Schema::parse("test_msgs/Numbers", DEFINITION).unwrap();
+    let data = little_endian_message();
+    let value = schema.decode(&data).unwrap();
+    assert_eq!(
+        value,
+        Value::Message(vec![
+            ("a".into(), Value::I16(-2)),
+            ("b".into(), Value::U32(0x01020304)),
+            ("c".into(), Value::F32(1.5)),
+            ("d".into(), Value::F64(-2.5)),
+            ("e".into(), Value::I64(0x0102030405060708)),
+            ("f".into(), Value::Time(Time { secs: 1, nsecs: 2 })),
+            (
+                "g".into(),
+                Value::Duration {
+                    secs: -1,
+                    nsecs: 256
+                }
+            ),
+            (
+                "h".into(),
+                Value::Array(vec![Value::U16(1), Value::U16(256)])
+            ),
+        ])
+    );
+    assert_eq!(schema.encode(&value).unwrap(), data);
+}
+
+#[test]
+fn writes_little_endian_records() {
+    let time = Time {
+        secs: 0x01020304,
+        nsecs: 0x05060708,
+    };
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&common::connection(
+        "/numbers",
+        "test_msgs/Numbers",
+        DEFINITION,
+    ));
+    writer
+        .write_raw(conn_id, time, &little_endian_message())
+        .unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    // the bag header record's header length follows the version line
+    let header_len = u32::from_le_bytes(bytes[13..17].try_into().unwrap());
+    assert!(header_len > 0 && header_len < 4096, "{header_len}");
+    // the message's receive time in its record header
+    let mut field = b"time=".to_vec();
+    field.extend_from_slice(&[0x04, 0x03, 0x02, 0x01, 0x08, 0x07, 0x06, 0x05]);
+    assert!(bytes.windows(field.len()).any(|window| window == field));
+
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    let msg_view = bag.read_messages(&Query::all()).unwrap().next().unwrap();
+    assert_eq!(msg_view.time, time);
+    assert_eq!(
+        msg_view.decode().unwrap().get("b"),
+        Some(&Value::U32(0x01020304))
+    );
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use frost::cancel::CancellationToken;
+use frost::errors::{Error, ErrorKind};
+use frost::etl::{Ingest, Record};
+use frost::query::Query;
+use tempfile::tempdir;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn store(table: &mut BTreeMap<u64, (String, Vec<u8>)>, records: &[Record]) {
+    for record in records {
+        table.insert(record.sequence, (record.topic.clone(), record.data.clone()));
+    }
+}
+
+#[test]
+fn resumes_after_a_failure() {
+    let dir = tempdir().unwrap();
+    let bag = dir.path().join("run.bag");
+    fs::copy(FILE_PATH, &bag).unwrap();
+    let checkpoints = dir.path().join("checkpoints.json");
+
+    let mut table = BTreeMap::new();
+    let mut batches = 0;
+    let result = Ingest::open(&checkpoints)
+        .unwrap()
+        .with_batch_size(64)
+        .ingest_bag(&bag, |records| {
+            if batches == 2 {
+                return Err(Error::from(io::Error::new(
+                    io::ErrorKind::Other,
+                    "database went away",
+                )));
+            }
+            batches += 1;
+            store(&mut table, records);
+            Ok(())
+        });
+    assert!(result.is_err());
+    assert_eq!(table.len(), 128);
+
+    let mut ingest = Ingest::open(&checkpoints).unwrap().with_batch_size(64);
+    assert_eq!(
+        ingest.checkpoints().get(&bag).unwrap().unwrap().committed,
+        128
+    );
+    let report = ingest
+        .ingest_bag(&bag, |records| {
+            assert!(records
+                .iter()
+                .all(|record| !table.contains_key(&record.sequence)));
+            store(&mut table, records);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        (report.resumed_from, report.records, report.complete),
+        (128, 172, true)
+    );
+    assert_eq!(
+        table.keys().copied().collect::<Vec<_>>(),
+        (0..300).collect::<Vec<_>>()
+    );
+
+    let report = ingest.ingest_bag(&bag, |_| panic!("nothing left")).unwrap();
+    assert_eq!((report.records, report.complete), (0, true));
+}
+
+#[test]
+fn stops_when_cancelled() {
+    let dir = tempdir().unwrap();
+    let checkpoints = dir.path().join("checkpoints.json");
+    let token = CancellationToken::new();
+    let mut ingest = Ingest::open(&checkpoints)
+        .unwrap()
+        .with_query(Query::all().with_cancellation(token.clone()))
+        .with_batch_size(1);
+    let result = ingest.ingest_bag(FILE_PATH, |_| {
+        token.cancel();
+        Ok(())
+    });
+    assert!(matches!(result.unwrap_err().kind(), ErrorKind::Cancelled));
+    assert!(
+        !ingest
+            .checkpoints()
+            .get(FILE_PATH)
+            .unwrap()
+            .unwrap()
+            .complete
+    );
+}
+
+#[test]
+fn decodes_the_queried_messages() {
+    let dir = tempdir().unwrap();
+    let mut records = Vec::new();
+    Ingest::open(dir.path().join("checkpoints.json"))
+        .unwrap()
+        .with_query(Query::new().with_topics(["/chatter"]))
+        .with_decoding()
+        .ingest_bag(FILE_PATH, |batch| {
+            records.extend_from_slice(batch);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(records.len(), 100);
+    assert!(records.windows(2).all(|pair| pair[0].time <= pair[1].time));
+    assert!(records
+        .iter()
+        .all(|record| record.topic == "/chatter" && record.data_type == "std_msgs/String"));
+    assert!(records.iter().all(|record| record.value.is_some()));
+}
+
+#[test]
+fn lists_pending_bags() {
+    let dir = tempdir().unwrap();
+    let drop = dir.path().join("drop");
+    fs::create_dir(&drop).unwrap();
+    for name in ["a.bag", "b.bag", "c.bag.active"] {
+        fs::copy(FILE_PATH, drop.join(name)).unwrap();
+    }
+    fs::write(drop.join("notes.txt"), "not a bag").unwrap();
+
+    let mut ingest = Ingest::open(dir.path().join("checkpoints.json")).unwrap();
+    let pending = ingest.pending(&drop).unwrap();
+    let mut names: Vec<_> = pending
+        .iter()
+        .map(|path| path.file_name().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a.bag", "b.bag"]);
+
+    ingest.ingest_bag(drop.join("a.bag"), |_| Ok(())).unwrap();
+    assert_eq!(ingest.pending(&drop).unwrap(), [drop.join("b.bag")]);
+
+    // a bag which changed since it was ingested is ingested again from the start
+    let mut bytes = fs::read(drop.join("a.bag")).unwrap();
+    bytes.extend_from_slice(&[0; 16]);
+    fs::write(drop.join("a.bag"), bytes).unwrap();
+    assert_eq!(ingest.pending(&drop).unwrap().len(), 2);
+    assert_eq!(ingest.checkpoints().get(drop.join("a.bag")).unwrap(), None);
+}
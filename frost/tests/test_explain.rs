@@ -0,0 +1,220 @@
+use frost::errors::{DecodeError, ErrorKind};
+use frost::explain::Explanation;
+use frost::query::Query;
+use frost::time::Time;
+use frost::DecompressedBag;
+
+mod common;
+use common::msgs::std_msgs;
+
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+const SCAN: &str = "uint32 count\nstring name\ngeometry_msgs/Point[] points
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y";
+
+/// The layout of [SCAN], without its points.
+#[allow(dead_code)]
+#[derive(Debug, serde::Deserialize)]
+struct Scan {
+    count: u32,
+    name: String,
+}
+impl frost::msgs::Msg for Scan {}
+
+fn scan(count: u32, name: &[u8], points: u32, data: &[f64]) -> Vec<u8> {
+    let mut bytes = count.to_le_bytes().to_vec();
+    bytes.extend(common::string(name));
+    bytes.extend_from_slice(&points.to_le_bytes());
+    for value in data {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+/// A bag of a single message recorded with `message_definition`.
+fn bag_of(message_definition: &str, data: &[u8]) -> DecompressedBag {
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&common::connection(
+        "/scan",
+        "test_msgs/Scan",
+        message_definition,
+    ));
+    writer
+        .write_raw(conn_id, Time { secs: 1, nsecs: 0 }, data)
+        .unwrap();
+    let bag = writer.finish().unwrap().into_inner();
+    DecompressedBag::from_bytes(&bag).unwrap()
+}
+
+/// Explains a single message recorded with `message_definition`.
+fn explain(message_definition: &str, data: &[u8]) -> Explanation {
+    let bag = bag_of(message_definition, data);
+    let msg_view = bag.read_messages(&Query::all()).unwrap().next().unwrap();
+    msg_view.explain::<Scan>()
+}
+
+#[test]
+fn explains_a_mismatched_type() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let query = Query::new().with_topics(["/chatter"]);
+    let msg_view = bag.read_messages(&query).unwrap().next().unwrap();
+
+    // a std_msgs/String is too short for a std_msgs/Time
+    let explanation = msg_view.explain::<std_msgs::Time>();
+    assert!(explanation.instantiate_error.is_some());
+    assert_eq!(explanation.divergence, None);
+    assert_eq!(explanation.trailing_bytes, 0);
+    assert_eq!(explanation.fields.len(), 1);
+    assert_eq!(explanation.fields[0].path, "data");
+    assert_eq!(explanation.fields[0].field_type, "string");
+    assert_eq!(explanation.fields[0].range, 0..9);
+    assert_eq!(explanation.fields[0].value, "\"foo_0\"");
+    assert_eq!(explanation.context.topic, "/chatter");
+
+    let explanation = msg_view.explain::<std_msgs::String>();
+    assert_eq!(explanation.instantiate_error, None);
+    assert!(explanation.to_string().contains("instantiating succeeded"));
+}
+
+#[test]
+fn locates_divergence() {
+    // the name claims more bytes than are left
+    let mut data = scan(3, b"lidar", 0, &[]);
+    data[4] = 0xff;
+    let explanation = explain(SCAN, &data);
+    let divergence = explanation.divergence.clone().unwrap();
+    assert_eq!(divergence.path, "name");
+    assert_eq!(divergence.field_type, "string");
+    assert_eq!(divergence.offset, 4);
+    assert_eq!(
+        divergence.reason,
+        "its length is 255 bytes, but only 9 are left"
+    );
+    assert_eq!(explanation.fields[0].path, "count");
+    assert_eq!(explanation.fields[0].value, "3");
+    assert!(explanation.instantiate_error.is_some());
+
+    let report = explanation.to_string();
+    assert!(report.contains("diverged at byte 4, string name: its length is 255 bytes"));
+    assert!(report.contains(
+        "00000000  03 00 00 00 ff 00 00 00  6c 69 64 61 72 00 00 00  |........lidar...|"
+    ));
+
+    // the second point is cut short
+    let data = scan(3, b"lidar", 2, &[1.0, 2.0, 3.0]);
+    let divergence = explain(SCAN, &data).divergence.unwrap();
+    assert_eq!(divergence.path, "points");
+    assert_eq!(divergence.field_type, "geometry_msgs/Point[]");
+    assert_eq!(divergence.offset, 13);
+    assert_eq!(
+        divergence.reason,
+        "its 2 elements need at least 32 bytes, but only 24 are left"
+    );
+
+    // a string within a point is cut short
+    let definition = SCAN.replace("float64 y", "string frame");
+    let mut data = scan(3, b"lidar", 1, &[1.0]);
+    data.extend_from_slice(&4u32.to_le_bytes());
+    data.extend_from_slice(b"map");
+    let divergence = explain(&definition, &data).divergence.unwrap();
+    assert_eq!(divergence.path, "points.0.frame");
+    assert_eq!(divergence.offset, 25);
+    assert_eq!(
+        divergence.reason,
+        "its length is 4 bytes, but only 3 are left"
+    );
+
+    // a count no message could hold
+    let data = scan(3, b"lidar", u32::MAX, &[]);
+    let divergence = explain(SCAN, &data).divergence.unwrap();
+    assert_eq!(divergence.path, "points");
+    assert_eq!(divergence.field_type, "geometry_msgs/Point[]");
+    assert!(divergence
+        .reason
+        .starts_with("its 4294967295 elements need"));
+}
+
+#[test]
+fn reports_unread_bytes() {
+    let explanation = explain("uint32 count", &scan(3, b"lidar", 0, &[]));
+    assert_eq!(explanation.divergence, None);
+    assert_eq!(explanation.trailing_bytes, 13);
+    assert!(explanation
+        .to_string()
+        .contains("every field was read, leaving 13 bytes unread"));
+}
+
+#[test]
+fn strict_instantiation() {
+    let trailing = |definition: &str, data: &[u8]| {
+        let bag = bag_of(definition, data);
+        let msg_view = bag.read_messages(&Query::all()).unwrap().next().unwrap();
+        let lenient = msg_view.instantiate::<Scan>().map(|_| ());
+        match msg_view.instantiate_strict::<Scan>() {
+            Ok(_) => Ok(lenient.is_ok()),
+            Err(e) => match e.kind() {
+                ErrorKind::Decode(DecodeError::TrailingBytes(len)) => Err(*len),
+                _ => panic!("{e}"),
+            },
+        }
+    };
+
+    // the definition and the struct agree with the data
+    let data = scan(3, b"lidar", 0, &[])[..13].to_vec();
+    assert_eq!(trailing("uint32 count\nstring name", &data), Ok(true));
+
+    // the recorded definition lost a field the struct still has
+    assert_eq!(trailing("uint32 count", &data), Err(9));
+
+    // the struct lacks the points
+    assert_eq!(trailing(SCAN, &scan(3, b"lidar", 0, &[])), Err(4));
+
+    let bag = bag_of("uint32 count", &data);
+    let msg_view = bag.read_messages(&Query::all()).unwrap().next().unwrap();
+    let err = msg_view.instantiate_strict::<Scan>().unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("message is longer than its definition, by 9 bytes"));
+    assert_eq!(err.context().unwrap().topic, "/scan");
+}
+
+#[test]
+fn keeps_the_last_fields() {
+    let points: Vec<f64> = (0..200).map(f64::from).collect();
+    let explanation = explain(SCAN, &scan(3, b"lidar", 100, &points));
+    assert_eq!(explanation.divergence, None);
+    assert_eq!(explanation.fields.len() + explanation.omitted_fields, 202);
+    let last = explanation.fields.last().unwrap();
+    assert_eq!(last.path, "points.99.y");
+    assert_eq!(last.value, "199.0");
+    // bounded to 256 bytes
+    let hexdump = explanation.hexdump();
+    assert_eq!(hexdump.lines().count(), 17);
+    assert!(hexdump.ends_with("... 1361 more bytes\n"));
+}
+
+#[test]
+fn summarizes_primitive_arrays() {
+    let mut data = 10u32.to_le_bytes().to_vec();
+    data.extend((0..10u8).collect::<Vec<_>>());
+    let explanation = explain("uint8[] data", &data);
+    assert_eq!(explanation.fields.len(), 1);
+    assert_eq!(explanation.fields[0].field_type, "uint8[]");
+    assert_eq!(
+        explanation.fields[0].value,
+        "[0, 1, 2, 3, 4, 5, 6, 7, ...] (10 elements)"
+    );
+}
+
+#[test]
+fn explains_unparsable_definitions() {
+    let explanation = explain("not a definition", &[]);
+    let divergence = explanation.divergence.unwrap();
+    assert_eq!(divergence.path, "test_msgs/Scan");
+    assert!(divergence
+        .reason
+        .starts_with("the recorded definition doesn't parse"));
+}
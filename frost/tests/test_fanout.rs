@@ -0,0 +1,100 @@
+use std::io;
+use std::sync::mpsc;
+
+use frost::errors::Error;
+use frost::query::Query;
+use frost::time::Time;
+use frost::DecompressedBag;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+#[test]
+fn matches_separate_reads() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let topics = ["/chatter", "/array", "/time"];
+
+        let (sender, receiver) = mpsc::channel();
+        let mut times = Vec::new();
+        let mut fanout = bag.fanout(&Query::all()).unwrap();
+        for topic in topics {
+            let sender = sender.clone();
+            fanout = fanout.on_topic(topic, move |msg| {
+                sender
+                    .send((msg.topic.to_string(), msg.raw_bytes()?.to_vec()))
+                    .unwrap();
+                Ok(())
+            });
+        }
+        let counts = fanout
+            .on_topic("/time", |msg| {
+                times.push(msg.time);
+                Ok(())
+            })
+            .run()
+            .unwrap();
+        drop(sender);
+
+        let received: Vec<(String, Vec<u8>)> = receiver.iter().collect();
+        assert_eq!(received.len(), 300, "{name}");
+        for topic in topics {
+            assert_eq!(counts[topic], 100, "{name} {topic}");
+
+            let expected: Vec<Vec<u8>> = bag
+                .read_messages(&Query::new().with_topics([topic]))
+                .unwrap()
+                .map(|msg| msg.raw_bytes().unwrap().to_vec())
+                .collect();
+            let fanned: Vec<Vec<u8>> = received
+                .iter()
+                .filter(|(t, _)| t == topic)
+                .map(|(_, bytes)| bytes.clone())
+                .collect();
+            assert_eq!(fanned, expected, "{name} {topic}");
+        }
+
+        assert_eq!(times.len(), 100, "{name}");
+        assert!(times.windows(2).all(|w| w[0] <= w[1]), "{name}");
+    }
+}
+
+#[test]
+fn skips_topics_without_handlers() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut seen = 0;
+    let counts = bag
+        .fanout(&Query::new().with_topics(["/chatter", "/array"]))
+        .unwrap()
+        .on_topic("/array", |_| {
+            seen += 1;
+            Ok(())
+        })
+        .on_topic("/time", |_| panic!("/time is not in the query"))
+        .run()
+        .unwrap();
+    assert_eq!(seen, 100);
+    assert_eq!(counts.len(), 1);
+    assert_eq!(counts["/array"], 100);
+}
+
+#[test]
+fn stops_at_handler_error() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut last: Option<Time> = None;
+    let result = bag
+        .fanout(&Query::all())
+        .unwrap()
+        .on_topic("/chatter", |msg| {
+            last = Some(msg.time);
+            Err(Error::from(io::Error::new(io::ErrorKind::Other, "full")))
+        })
+        .run();
+    assert!(result.is_err());
+    assert!(last.is_some());
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "yaml")]
+
+use std::time::Duration;
+
+use frost::health::HealthConfig;
+use frost::time::Time;
+use frost::DecompressedBag;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+const CONFIG: &str = "
+signals:
+  - name: array element
+    topic: /array
+    field: data.1
+    max: 3.0
+  - topic: /time
+    field: data
+    min: 10
+    max: 89.5
+  - topic: /chatter
+    field: data
+";
+
+#[test]
+#[allow(clippy::approx_constant)]
+fn health_report() {
+    let config = HealthConfig::from_yaml(CONFIG).unwrap();
+    assert_eq!(config.signals[1].min, Some(10.0));
+    assert_eq!(config.signals[2].max, None);
+
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let report = bag.health_report(&config).unwrap();
+        assert!(!report.is_healthy(), "{name}");
+        let [array, time, chatter] = &report.signals[..] else {
+            panic!("{name}: expected 3 signals");
+        };
+
+        // every /array sample is 3.14, so they form a single violation
+        assert_eq!(array.name, "array element", "{name}");
+        assert_eq!(array.count, 100, "{name}");
+        assert!((array.mean.unwrap() - 3.14).abs() < 1e-9, "{name}");
+        assert_eq!(array.violations.len(), 1, "{name}");
+        assert_eq!(array.violations[0].samples, 100, "{name}");
+
+        // /time counts up from 0 to 99 seconds, leaving its bounds at both ends
+        assert_eq!(time.name, "/time/data", "{name}");
+        assert!(time.min.unwrap() < 1e-5, "{name}");
+        assert!((time.max.unwrap() - 99.0001).abs() < 1e-9, "{name}");
+        assert_eq!(time.violations.len(), 2, "{name}");
+        assert_eq!(time.violations[0].samples, 10, "{name}");
+        assert_eq!(time.violations[0].worst, time.min.unwrap(), "{name}");
+        assert_eq!(time.violations[1].samples, 10, "{name}");
+        assert!((time.violations[1].worst - 99.0001).abs() < 1e-9, "{name}");
+        let receive_start = time.violations[0].start;
+        assert!(receive_start > Time { secs: 0, nsecs: 0 }, "{name}");
+        assert!(time.violations[0].duration() > Duration::ZERO, "{name}");
+
+        // strings are not numeric
+        assert_eq!(chatter.count, 0, "{name}");
+        assert_eq!(chatter.missing, 100, "{name}");
+        assert_eq!(chatter.mean, None, "{name}");
+        assert!(chatter.violations.is_empty(), "{name}");
+    }
+}
+
+#[test]
+fn invalid_configs() {
+    assert!(HealthConfig::from_yaml("signals: [{topic: /a}]").is_err());
+    assert!(HealthConfig::from_yaml("signals: [{topic: /a, field: x, maximum: 1}]").is_err());
+    assert!(HealthConfig::from_file("/nonexistent/health.yaml").is_err());
+}
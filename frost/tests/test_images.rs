@@ -0,0 +1,158 @@
+#![cfg(feature = "images")]
+
+use frost::dynamic::Value;
+use frost::errors::{DecodeError, ErrorKind};
+use frost::images::{self, RawImage};
+use frost::query::Query;
+use frost::time::Time;
+use frost::{ConnectionData, DecompressedBag};
+use image::{DynamicImage, GenericImageView};
+
+mod common;
+use common::string;
+
+const IMAGE_DEFINITION: &str = "\
+std_msgs/Header header
+uint32 height
+uint32 width
+string encoding
+uint8 is_bigendian
+uint32 step
+uint8[] data
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+";
+
+fn image_value(width: u32, height: u32, encoding: &str, step: u32, data: Vec<u8>) -> Value {
+    Value::Message(vec![
+        ("height".to_string(), Value::U32(height)),
+        ("width".to_string(), Value::U32(width)),
+        ("encoding".to_string(), Value::String(encoding.to_string())),
+        ("is_bigendian".to_string(), Value::U8(0)),
+        ("step".to_string(), Value::U32(step)),
+        ("data".to_string(), Value::Bytes(data)),
+    ])
+}
+
+fn serialize_image(width: u32, height: u32, encoding: &str, step: u32, data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&7u32.to_le_bytes()); // seq
+    buf.extend_from_slice(&[0; 8]); // stamp
+    buf.extend(string("camera"));
+    buf.extend_from_slice(&height.to_le_bytes());
+    buf.extend_from_slice(&width.to_le_bytes());
+    buf.extend(string(encoding));
+    buf.push(0);
+    buf.extend_from_slice(&step.to_le_bytes());
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+    buf
+}
+
+#[test]
+fn color_and_mono_encodings() {
+    let rgb = images::to_image(&image_value(2, 1, "rgb8", 6, vec![1, 2, 3, 4, 5, 6])).unwrap();
+    assert_eq!(rgb.to_rgb8().into_raw(), [1, 2, 3, 4, 5, 6]);
+
+    let bgr = images::to_image(&image_value(2, 1, "bgr8", 6, vec![1, 2, 3, 4, 5, 6])).unwrap();
+    assert_eq!(bgr.to_rgb8().into_raw(), [3, 2, 1, 6, 5, 4]);
+
+    let bgra = images::to_image(&image_value(1, 1, "bgra8", 4, vec![1, 2, 3, 4])).unwrap();
+    assert!(matches!(bgra, DynamicImage::ImageRgba8(_)));
+    assert_eq!(bgra.to_rgba8().into_raw(), [3, 2, 1, 4]);
+
+    // rows padded to 4 bytes
+    let mono =
+        images::to_image(&image_value(3, 2, "mono8", 4, vec![1, 2, 3, 0, 4, 5, 6, 0])).unwrap();
+    assert!(matches!(mono, DynamicImage::ImageLuma8(_)));
+    assert_eq!(mono.to_luma8().into_raw(), [1, 2, 3, 4, 5, 6]);
+
+    let value = image_value(2, 1, "mono16", 4, vec![0x01, 0x02, 0x03, 0x04]);
+    let mono16 = images::to_image(&value).unwrap();
+    assert_eq!(mono16.to_luma16().into_raw(), [0x0201, 0x0403]);
+    let mut raw = RawImage::from_value(&value).unwrap();
+    raw.is_bigendian = true;
+    assert_eq!(
+        raw.to_image().unwrap().to_luma16().into_raw(),
+        [0x0102, 0x0304]
+    );
+}
+
+#[test]
+fn bayer_encodings() {
+    // one 2x2 cell, with red 10, greens 20 and 40, and blue 50
+    for (encoding, cell) in [
+        ("bayer_rggb8", [10, 20, 40, 50]),
+        ("bayer_bggr8", [50, 20, 40, 10]),
+        ("bayer_gbrg8", [20, 50, 10, 40]),
+        ("bayer_grbg8", [20, 10, 50, 40]),
+    ] {
+        let image = images::to_image(&image_value(2, 2, encoding, 2, cell.to_vec())).unwrap();
+        assert_eq!(image.dimensions(), (2, 2), "{encoding}");
+        for pixel in image.to_rgb8().pixels() {
+            assert_eq!(pixel.0, [10, 30, 50], "{encoding}");
+        }
+    }
+
+    // a trailing odd column shares the cell before it
+    let image = images::to_image(&image_value(
+        3,
+        2,
+        "bayer_rggb8",
+        3,
+        vec![10, 20, 11, 40, 50, 41],
+    ))
+    .unwrap();
+    assert_eq!(image.to_rgb8().get_pixel(2, 1).0, [10, 30, 50]);
+}
+
+#[test]
+fn invalid_images() {
+    let err = images::to_image(&image_value(1, 1, "yuv422", 2, vec![0, 0])).unwrap_err();
+    assert!(matches!(err, DecodeError::UnsupportedEncoding(e) if e == "yuv422"));
+
+    let err = images::to_image(&image_value(2, 2, "rgb8", 6, vec![0; 11])).unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+
+    let err = images::to_image(&image_value(2, 1, "rgb8", 5, vec![0; 6])).unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+
+    let err = images::to_image(&Value::Message(vec![])).unwrap_err();
+    assert!(
+        matches!(err, DecodeError::InvalidField(e) if e == "sensor_msgs/Image has no encoding field")
+    );
+}
+
+#[test]
+fn decode_image_from_bag() {
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&ConnectionData {
+        md5sum: "060021388200f6f0f447d0fcd9c64743".to_string(),
+        ..common::connection("/camera/image_raw", "sensor_msgs/Image", IMAGE_DEFINITION)
+    });
+    let pixels: Vec<u8> = (0..12).collect();
+    let time = Time { secs: 1, nsecs: 0 };
+    writer
+        .write_raw(conn_id, time, &serialize_image(2, 2, "rgb8", 6, &pixels))
+        .unwrap();
+    writer
+        .write_raw(conn_id, time, &serialize_image(1, 1, "32FC1", 4, &[0; 4]))
+        .unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    let mut messages = bag.read_messages(&Query::all()).unwrap();
+
+    let image = messages.next().unwrap().decode_image().unwrap();
+    assert_eq!(image.dimensions(), (2, 2));
+    assert_eq!(image.to_rgb8().into_raw(), pixels);
+
+    let err = messages.next().unwrap().decode_image().unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::Decode(DecodeError::UnsupportedEncoding(_))
+    ));
+}
@@ -0,0 +1,305 @@
+#![cfg(feature = "ros2")]
+
+use std::collections::BTreeMap;
+
+use frost::import::Importer;
+use frost::mcap::{McapReader, McapWriter};
+use frost::query::Query;
+use frost::ros2::{MessageSources, Ros2Type};
+use frost::DecompressedBag;
+
+mod common;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+const MAGIC: &[u8] = b"\x89MCAP0\r\n";
+
+const POINT_STAMPED: &str = "std_msgs/Header header
+geometry_msgs/Point point
+================================================================================
+MSG: std_msgs/Header
+builtin_interfaces/Time stamp
+string frame_id
+================================================================================
+MSG: builtin_interfaces/Time
+int32 sec
+uint32 nanosec
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y
+float64 z";
+
+/// A little endian CDR `geometry_msgs/msg/PointStamped` at 1.000000002 in the `map` frame.
+fn point_stamped_cdr(x: f64) -> Vec<u8> {
+    let mut cdr = vec![0, 1, 0, 0];
+    cdr.extend_from_slice(&1i32.to_le_bytes());
+    cdr.extend_from_slice(&2u32.to_le_bytes());
+    cdr.extend_from_slice(&4u32.to_le_bytes());
+    cdr.extend_from_slice(b"map\0");
+    for value in [x, 2.0, 3.0] {
+        cdr.extend_from_slice(&value.to_le_bytes());
+    }
+    cdr
+}
+
+/// The ROS 1 serialization of [point_stamped_cdr], without its leading length.
+fn point_stamped_ros1(x: f64) -> Vec<u8> {
+    let mut ros1 = Vec::new();
+    for value in [0u32, 1, 2, 3] {
+        ros1.extend_from_slice(&value.to_le_bytes());
+    }
+    ros1.extend_from_slice(b"map");
+    for value in [x, 2.0, 3.0] {
+        ros1.extend_from_slice(&value.to_le_bytes());
+    }
+    ros1
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn put_record(buf: &mut Vec<u8>, opcode: u8, content: &[u8]) {
+    buf.push(opcode);
+    buf.extend_from_slice(&(content.len() as u64).to_le_bytes());
+    buf.extend_from_slice(content);
+}
+
+/// An unchunked MCAP file of CDR messages on `/point`, with the `ros2msg` schema `definition`.
+fn cdr_mcap(definition: &str, messages: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let mut mcap = MAGIC.to_vec();
+    let mut header = Vec::new();
+    put_string(&mut header, "ros2");
+    put_string(&mut header, "");
+    put_record(&mut mcap, 0x01, &header);
+
+    let mut schema = 1u16.to_le_bytes().to_vec();
+    put_string(&mut schema, "geometry_msgs/msg/PointStamped");
+    put_string(&mut schema, "ros2msg");
+    put_string(&mut schema, definition);
+    put_record(&mut mcap, 0x03, &schema);
+
+    let mut channel = 1u16.to_le_bytes().to_vec();
+    channel.extend_from_slice(&1u16.to_le_bytes());
+    put_string(&mut channel, "/point");
+    put_string(&mut channel, "cdr");
+    channel.extend_from_slice(&0u32.to_le_bytes());
+    put_record(&mut mcap, 0x04, &channel);
+
+    for (sequence, (log_time, data)) in messages.iter().enumerate() {
+        let mut message = 1u16.to_le_bytes().to_vec();
+        message.extend_from_slice(&(sequence as u32).to_le_bytes());
+        message.extend_from_slice(&log_time.to_le_bytes());
+        message.extend_from_slice(&log_time.to_le_bytes());
+        message.extend_from_slice(data);
+        put_record(&mut mcap, 0x05, &message);
+    }
+    put_record(&mut mcap, 0x0F, &[0; 12]);
+    mcap
+}
+
+#[test]
+fn translates_ros2_types() {
+    let ros2_type =
+        Ros2Type::from_definition("geometry_msgs/msg/PointStamped", POINT_STAMPED).unwrap();
+    assert_eq!(ros2_type.data_type(), "geometry_msgs/PointStamped");
+    // the md5sum of the ROS 1 type
+    assert_eq!(ros2_type.md5sum(), "c63aecb41bfdfd6b7e1fac37c7cbe7bf");
+    assert!(ros2_type
+        .message_definition()
+        .contains("MSG: std_msgs/Header\nuint32 seq\ntime stamp\nstring frame_id"));
+    assert!(!ros2_type
+        .message_definition()
+        .contains("builtin_interfaces"));
+    assert_eq!(
+        ros2_type.to_ros1(&point_stamped_cdr(1.0)).unwrap(),
+        point_stamped_ros1(1.0)
+    );
+}
+
+#[test]
+fn translates_bounded_and_defaulted_fields() {
+    let definition = "string<=8 name \"frost\"\nint32[<=4] values\nwstring label\nuint8 MODE=2";
+    let ros2_type = Ros2Type::from_definition("demo_msgs/msg/Bounded", definition).unwrap();
+    assert_eq!(ros2_type.data_type(), "demo_msgs/Bounded");
+    assert_eq!(
+        ros2_type.message_definition(),
+        "string name\nint32[] values\nstring label\nuint8 MODE=2"
+    );
+}
+
+#[test]
+fn big_endian_cdr() {
+    let ros2_type = Ros2Type::from_definition("std_msgs/msg/UInt32", "uint32 data").unwrap();
+    assert_eq!(
+        ros2_type
+            .to_ros1(b"\x00\x00\x00\x00\x00\x00\x01\x02")
+            .unwrap(),
+        258u32.to_le_bytes()
+    );
+}
+
+#[test]
+fn truncated_cdr_errors() {
+    let ros2_type =
+        Ros2Type::from_definition("geometry_msgs/msg/PointStamped", POINT_STAMPED).unwrap();
+    let cdr = point_stamped_cdr(1.0);
+    assert!(ros2_type.to_ros1(&cdr[..cdr.len() - 1]).is_err());
+}
+
+#[test]
+fn message_sources() {
+    let dir = tempfile::tempdir().unwrap();
+    for (package, name, text) in [
+        (
+            "geometry_msgs",
+            "PointStamped",
+            "std_msgs/Header header\nPoint point",
+        ),
+        ("geometry_msgs", "Point", "float64 x\nfloat64 y\nfloat64 z"),
+        (
+            "std_msgs",
+            "Header",
+            "builtin_interfaces/Time stamp\nstring frame_id",
+        ),
+    ] {
+        let msg_dir = dir.path().join(package).join("msg");
+        std::fs::create_dir_all(&msg_dir).unwrap();
+        std::fs::write(msg_dir.join(format!("{name}.msg")), text).unwrap();
+    }
+    let mut sources = MessageSources::new();
+    assert_eq!(sources.add_dir(dir.path()).unwrap(), 3);
+
+    let definition = sources
+        .definition("geometry_msgs/msg/PointStamped")
+        .unwrap();
+    let ros2_type =
+        Ros2Type::from_definition("geometry_msgs/msg/PointStamped", &definition).unwrap();
+    assert_eq!(ros2_type.md5sum(), "c63aecb41bfdfd6b7e1fac37c7cbe7bf");
+    assert!(sources.definition("geometry_msgs/msg/Pose").is_none());
+}
+
+#[test]
+fn imports_ros1_mcap() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let mut mcap = McapWriter::new(Vec::new()).unwrap().with_chunk_size(1024);
+    mcap.copy_messages(&bag, &Query::all()).unwrap();
+    let mcap = mcap.finish().unwrap();
+
+    let mut importer = Importer::new(common::writer());
+    importer
+        .import_mcap(McapReader::new(mcap.as_slice()).unwrap())
+        .unwrap();
+    let (imported, report) = importer.finish().unwrap();
+    assert_eq!(report.message_count, 300);
+    assert!(report.skipped.is_empty());
+
+    let imported = DecompressedBag::from_bytes(&imported.into_inner()).unwrap();
+    // messages at the same time may be interleaved differently across topics
+    let messages = |bag: &DecompressedBag| {
+        let mut messages = BTreeMap::<String, Vec<_>>::new();
+        for msg_view in bag.read_messages(&Query::all()).unwrap() {
+            messages
+                .entry(msg_view.topic.to_string())
+                .or_default()
+                .push((msg_view.time, msg_view.raw_bytes().unwrap().to_vec()));
+        }
+        messages
+    };
+    assert_eq!(messages(&imported), messages(&bag));
+    for original in bag.metadata.connection_data.values() {
+        let imported = imported
+            .metadata
+            .connection_data
+            .values()
+            .find(|imported| imported.topic == original.topic)
+            .unwrap();
+        assert_eq!(original.data_type, imported.data_type);
+        assert_eq!(original.md5sum, imported.md5sum);
+        assert_eq!(original.message_definition, imported.message_definition);
+    }
+}
+
+#[test]
+fn imports_cdr_mcap() {
+    let mcap = cdr_mcap(
+        POINT_STAMPED,
+        &[
+            (1_000_000_000, point_stamped_cdr(1.0)),
+            (2_500_000_000, point_stamped_cdr(4.0)),
+        ],
+    );
+    let mut importer = Importer::new(common::writer());
+    importer
+        .import_mcap(McapReader::new(mcap.as_slice()).unwrap())
+        .unwrap();
+    let (imported, report) = importer.finish().unwrap();
+    assert_eq!(report.message_count, 2);
+
+    let bag = DecompressedBag::from_bytes(&imported.into_inner()).unwrap();
+    let connection = bag.metadata.connection_data.values().next().unwrap();
+    assert_eq!(connection.topic, "/point");
+    assert_eq!(connection.data_type, "geometry_msgs/PointStamped");
+    assert_eq!(connection.md5sum, "c63aecb41bfdfd6b7e1fac37c7cbe7bf");
+
+    let messages: Vec<_> = bag
+        .read_messages(&Query::all())
+        .unwrap()
+        .map(|msg_view| (msg_view.time, msg_view.raw_bytes().unwrap()[4..].to_vec()))
+        .collect();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].0.secs, 1);
+    assert_eq!(messages[1].0.nsecs, 500_000_000);
+    assert_eq!(messages[0].1, point_stamped_ros1(1.0));
+    assert_eq!(messages[1].1, point_stamped_ros1(4.0));
+}
+
+#[test]
+fn skips_unconvertible_messages() {
+    // without a definition, and no .msg files to fall back on
+    let mcap = cdr_mcap("", &[(1_000_000_000, point_stamped_cdr(1.0))]);
+    let mut importer = Importer::new(common::writer());
+    importer
+        .import_mcap(McapReader::new(mcap.as_slice()).unwrap())
+        .unwrap();
+    let report = importer.report();
+    assert_eq!(report.message_count, 0);
+    assert_eq!(report.skipped["/point"].message_count, 1);
+    assert!(report.skipped["/point"].reason.contains(".msg files"));
+
+    // a corrupt message is skipped, the rest are imported
+    let mcap = cdr_mcap(
+        POINT_STAMPED,
+        &[
+            (1_000_000_000, point_stamped_cdr(1.0)[..10].to_vec()),
+            (2_000_000_000, point_stamped_cdr(1.0)),
+        ],
+    );
+    let mut importer = Importer::new(common::writer());
+    importer
+        .import_mcap(McapReader::new(mcap.as_slice()).unwrap())
+        .unwrap();
+    let (_, report) = importer.finish().unwrap();
+    assert_eq!(report.message_count, 1);
+    assert_eq!(report.skipped["/point"].message_count, 1);
+}
+
+#[test]
+fn rejects_non_mcap() {
+    assert!(McapReader::new(&b"#ROSBAG V2.0\n"[..]).is_err());
+}
+
+#[test]
+fn reports_import_formats() {
+    let capabilities = frost::capabilities();
+    assert!(capabilities.formats.contains(&"MCAP"));
+    assert_eq!(
+        capabilities.formats.contains(&"rosbag2 sqlite3"),
+        cfg!(feature = "rosbag2")
+    );
+    assert_eq!(
+        capabilities.features.contains(&"rosbag2"),
+        cfg!(feature = "rosbag2")
+    );
+}
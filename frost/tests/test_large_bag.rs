@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+use std::io::{self};
+
+use frost::query::Query;
+use frost::storage::{ByteSource, CustomStorage};
+use frost::writer::Compression;
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+/// `#ROSBAG V2.0\n` followed by the bag header record, which is padded to 4096 bytes
+const FIRST_RECORD_POS: usize = 13 + 4096;
+/// the largest chunk a u32 data length can describe, rounded down
+const FILLER_CHUNK_SIZE: u32 = 0xFFFF_0000;
+
+/// A bag with gigabytes of zeros between its records, without allocating them.
+struct SparseStorage {
+    size: u64,
+    segments: Vec<(u64, Vec<u8>)>,
+}
+
+impl ByteSource for SparseStorage {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.size)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        let mut buf = vec![0; len];
+        let end = offset + len as u64;
+        for (start, bytes) in self.segments.iter() {
+            let seg_end = start + bytes.len() as u64;
+            let (from, to) = (offset.max(*start), end.min(seg_end));
+            if from < to {
+                buf[(from - offset) as usize..(to - offset) as usize]
+                    .copy_from_slice(&bytes[(from - start) as usize..(to - start) as usize]);
+            }
+        }
+        Ok(Cow::Owned(buf))
+    }
+}
+
+fn field(name: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut buf = ((name.len() + 1 + value.len()) as u32)
+        .to_le_bytes()
+        .to_vec();
+    buf.extend_from_slice(name);
+    buf.push(b'=');
+    buf.extend_from_slice(value);
+    buf
+}
+
+fn record_header(fields: &[Vec<u8>]) -> Vec<u8> {
+    let header = fields.concat();
+    let mut buf = (header.len() as u32).to_le_bytes().to_vec();
+    buf.extend_from_slice(&header);
+    buf
+}
+
+/// Overwrites the u64 or u32 value of the first `name=` field found after `from`.
+fn patch_field(bytes: &mut [u8], from: usize, name: &[u8], value: &[u8]) {
+    let mut needle = name.to_vec();
+    needle.push(b'=');
+    let pos = from
+        + bytes[from..]
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap()
+        + needle.len();
+    bytes[pos..pos + value.len()].copy_from_slice(value);
+}
+
+fn read_u64(bytes: &[u8], name: &[u8]) -> u64 {
+    let mut needle = name.to_vec();
+    needle.push(b'=');
+    let pos = bytes
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .unwrap()
+        + needle.len();
+    u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap())
+}
+
+/// Rewrites a single chunk bag so two empty filler chunks of almost 4 GiB each come before its real chunk,
+/// putting the real chunk, connections, and index beyond the reach of a u32 file offset.
+fn large_bag() -> (CustomStorage<SparseStorage>, Vec<u8>) {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer().with_compression(Compression::Lz4);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let small = writer.finish().unwrap().into_inner();
+    assert_eq!(BagMetadata::from_bytes(&small).unwrap().chunk_count(), 1);
+
+    let start_time = 1u32.to_le_bytes().repeat(2);
+    let filler_header = record_header(&[
+        field(b"op", &[0x05]),
+        field(b"compression", b"none"),
+        field(b"size", &FILLER_CHUNK_SIZE.to_le_bytes()),
+    ]);
+    let filler_len = (filler_header.len() + 4) as u64 + u64::from(FILLER_CHUNK_SIZE);
+    let shift = 2 * filler_len;
+
+    let mut rest = small[FIRST_RECORD_POS..].to_vec();
+    let index_pos = read_u64(&small[..FIRST_RECORD_POS], b"index_pos");
+    let info_from = (index_pos as usize) - FIRST_RECORD_POS;
+    let chunk_pos = read_u64(&rest[info_from..], b"chunk_pos");
+    patch_field(
+        &mut rest,
+        info_from,
+        b"chunk_pos",
+        &(chunk_pos + shift).to_le_bytes(),
+    );
+    for i in 0..2u64 {
+        let mut info = record_header(&[
+            field(b"op", &[0x06]),
+            field(b"ver", &1u32.to_le_bytes()),
+            field(
+                b"chunk_pos",
+                &(FIRST_RECORD_POS as u64 + i * filler_len).to_le_bytes(),
+            ),
+            field(b"start_time", &start_time),
+            field(b"end_time", &start_time),
+            field(b"count", &0u32.to_le_bytes()),
+        ]);
+        info.extend_from_slice(&0u32.to_le_bytes());
+        rest.extend_from_slice(&info);
+    }
+
+    let mut head = small[..FIRST_RECORD_POS].to_vec();
+    patch_field(
+        &mut head,
+        0,
+        b"index_pos",
+        &(index_pos + shift).to_le_bytes(),
+    );
+    patch_field(&mut head, 0, b"chunk_count", &3u32.to_le_bytes());
+
+    let mut segments = vec![(0, head)];
+    for i in 0..2u64 {
+        let mut filler = filler_header.clone();
+        filler.extend_from_slice(&FILLER_CHUNK_SIZE.to_le_bytes());
+        segments.push((FIRST_RECORD_POS as u64 + i * filler_len, filler));
+    }
+    let size = FIRST_RECORD_POS as u64 + shift + rest.len() as u64;
+    segments.push((FIRST_RECORD_POS as u64 + shift, rest));
+
+    (CustomStorage::new(SparseStorage { size, segments }), small)
+}
+
+#[test]
+fn offsets_beyond_4_gib() {
+    let (storage, small) = large_bag();
+    assert!(storage.source().size > u64::from(u32::MAX));
+
+    let metadata = BagMetadata::from_storage(&storage).unwrap();
+    let expected = BagMetadata::from_bytes(&small).unwrap();
+
+    assert_eq!(metadata.num_bytes, storage.source().size);
+    assert_eq!(metadata.chunk_count(), 3);
+    assert_eq!(metadata.message_count(), 300);
+    assert_eq!(
+        metadata.topic_message_counts(),
+        expected.topic_message_counts()
+    );
+    assert_eq!(metadata.topics_and_types(), expected.topics_and_types());
+    assert_eq!(metadata.end_time(), expected.end_time());
+
+    let total_compressed: u64 = metadata
+        .compression_info()
+        .iter()
+        .map(|info| info.total_compressed)
+        .sum();
+    assert!(total_compressed > 2 * u64::from(FILLER_CHUNK_SIZE));
+
+    // every message is indexed against the real chunk past 8 GiB, not a filler chunk
+    let estimate = metadata.estimate(&Query::all());
+    let expected_estimate = expected.estimate(&Query::all());
+    assert_eq!(estimate, expected_estimate);
+}
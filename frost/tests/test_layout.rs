@@ -0,0 +1,96 @@
+use std::process::Command;
+
+use frost::query::Query;
+use frost::{BagMetadata, DecompressedBag};
+use tempfile::tempdir;
+
+mod common;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+#[test]
+fn chunk_layout() {
+    let metadata = BagMetadata::from_file(FILE_PATH).unwrap();
+    let layout = metadata.chunk_layout();
+    assert_eq!(layout.topics, ["/array", "/chatter", "/time"]);
+    assert_eq!(layout.chunks.len(), 1);
+
+    let chunk = &layout.chunks[0];
+    assert_eq!(chunk.chunk_pos, 4117);
+    assert_eq!(chunk.compression, "lz4");
+    assert!(chunk.topics.values().all(|share| share.messages == 100));
+    let bytes: u64 = chunk.topics.values().map(|share| share.bytes).sum();
+    assert!(bytes <= u64::from(chunk.uncompressed_size));
+    let shares: f64 = layout.topics.iter().map(|t| chunk.byte_share(t)).sum();
+    assert!((shares - 1.0).abs() < 1e-9);
+    assert!((chunk.message_share("/time") - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(chunk.byte_share("/missing"), 0.0);
+
+    let svg = layout.to_svg();
+    assert!(svg.starts_with("<svg"));
+    assert!(layout
+        .topics
+        .iter()
+        .all(|topic| svg.contains(topic.as_str())));
+}
+
+#[test]
+fn chunk_layout_of_many_chunks() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let mut writer = common::writer().with_chunk_size(1024);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("chunked.bag");
+    std::fs::write(&path, bytes).unwrap();
+    let layout = BagMetadata::from_file(&path).unwrap().chunk_layout();
+    assert!(layout.chunks.len() > 1);
+    assert!(layout
+        .chunks
+        .windows(2)
+        .all(|pair| pair[0].chunk_pos < pair[1].chunk_pos));
+    for topic in layout.topics.iter() {
+        let messages: u64 = layout
+            .chunks
+            .iter()
+            .filter_map(|chunk| chunk.topics.get(topic))
+            .map(|share| share.messages)
+            .sum();
+        assert_eq!(messages, 100, "{topic}");
+    }
+}
+
+#[test]
+fn layout_command() {
+    let dir = tempdir().unwrap();
+    let svg_path = dir.path().join("layout.svg");
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["layout", "--svg"])
+        .arg(&svg_path)
+        .arg(FILE_PATH)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("4117"));
+    assert!(std::fs::read_to_string(&svg_path)
+        .unwrap()
+        .contains("/chatter"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["layout", "--relative-time", FILE_PATH])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("t+0.000s - t+99.000s"), "{stdout}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["layout", "--format", "json", FILE_PATH])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["chunks"][0]["topics"]["/time"]["messages"], 100);
+}
@@ -0,0 +1,137 @@
+#![cfg(feature = "manifest")]
+
+use frost::manifest::{Manifest, SignatureStatus};
+use frost::time::Time;
+use frost::writer::Compression;
+use tempfile::tempdir;
+
+mod common;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+const KEY: &[u8] = b"evidence locker";
+
+#[test]
+fn unmodified_bags_verify() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let manifest = Manifest::from_bytes(bytes).unwrap();
+        assert_eq!(manifest.chunks.len(), 1, "{name}");
+        assert_eq!(manifest.bag_size, bytes.len() as u64, "{name}");
+
+        let verification = manifest.verify(bytes, None).unwrap();
+        assert!(verification.is_valid(), "{name}");
+        assert_eq!(verification.signature, SignatureStatus::Unsigned, "{name}");
+
+        // a key was given, but the manifest isn't signed
+        let verification = manifest.verify(bytes, Some(KEY)).unwrap();
+        assert!(!verification.is_valid(), "{name}");
+        assert_eq!(verification.signature, SignatureStatus::Missing, "{name}");
+
+        let signed = Manifest::from_json(&manifest.sign(KEY).to_json()).unwrap();
+        let verification = signed.verify(bytes, Some(KEY)).unwrap();
+        assert!(verification.is_valid(), "{name}");
+        assert_eq!(verification.signature, SignatureStatus::Valid, "{name}");
+
+        let verification = signed.verify(bytes, None).unwrap();
+        assert!(verification.is_valid(), "{name}");
+        assert_eq!(
+            verification.signature,
+            SignatureStatus::NotChecked,
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn tampering_is_detected() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let manifest = Manifest::from_bytes(bytes).unwrap().sign(KEY);
+        let chunk_pos = manifest.chunks[0].chunk_pos;
+
+        // flip a byte of message data inside the chunk
+        let mut tampered = bytes.to_vec();
+        tampered[chunk_pos as usize + 100] ^= 1;
+        let verification = manifest.verify(&tampered, Some(KEY)).unwrap();
+        assert!(!verification.is_valid(), "{name}");
+        assert_eq!(verification.modified_chunks, [chunk_pos], "{name}");
+        assert!(verification.metadata_matches, "{name}");
+
+        // flip a byte of the index at the end of the bag
+        let mut tampered = bytes.to_vec();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        let verification = manifest.verify(&tampered, Some(KEY)).unwrap();
+        assert!(!verification.is_valid(), "{name}");
+        assert!(verification.modified_chunks.is_empty(), "{name}");
+        assert!(!verification.metadata_matches, "{name}");
+
+        // a manifest regenerated for a tampered bag can't be re-signed without the key
+        let mut forged = Manifest::from_bytes(&tampered).unwrap();
+        forged.signature = manifest.signature.clone();
+        let verification = forged.verify(&tampered, Some(KEY)).unwrap();
+        assert_eq!(verification.signature, SignatureStatus::Invalid, "{name}");
+        assert!(!verification.is_valid(), "{name}");
+
+        let verification = manifest
+            .clone()
+            .sign(b"another key")
+            .verify(bytes, Some(KEY))
+            .unwrap();
+        assert_eq!(verification.signature, SignatureStatus::Invalid, "{name}");
+    }
+}
+
+#[test]
+fn hashes_files_a_block_at_a_time() {
+    // a chunk spanning several blocks
+    let mut writer = common::writer().with_compression(Compression::None);
+    let conn_id = writer.add_connection(&common::connection(
+        "/blob",
+        "std_msgs/UInt8MultiArray",
+        "uint8[] data",
+    ));
+    let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| i as u8).collect();
+    writer
+        .write_raw(conn_id, Time { secs: 1, nsecs: 0 }, &data)
+        .unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("blob.bag");
+    std::fs::write(&path, &bytes).unwrap();
+    let manifest = Manifest::from_file(&path).unwrap();
+    assert_eq!(manifest, Manifest::from_bytes(&bytes).unwrap());
+    assert!(manifest.verify_file(&path, None).unwrap().is_valid());
+
+    let mut tampered = bytes.clone();
+    let middle = tampered.len() / 2;
+    tampered[middle] ^= 0xff;
+    std::fs::write(&path, &tampered).unwrap();
+    let verification = manifest.verify_file(&path, None).unwrap();
+    assert_eq!(verification.modified_chunks, [manifest.chunks[0].chunk_pos]);
+    assert!(verification.metadata_matches);
+
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(
+            Manifest::from_file(&path).unwrap(),
+            Manifest::from_bytes(bytes).unwrap(),
+            "{name}"
+        );
+    }
+}
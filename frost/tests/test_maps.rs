@@ -0,0 +1,183 @@
+use frost::dynamic::Value;
+use frost::errors::DecodeError;
+use frost::maps::{MapImageFormat, OccupancyGrid};
+use frost::query::Query;
+use frost::time::Time;
+use frost::{ConnectionData, DecompressedBag};
+use tempfile::tempdir;
+
+mod common;
+
+const OCCUPANCY_GRID_DEFINITION: &str = "\
+Header header
+MapMetaData info
+int8[] data
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: nav_msgs/MapMetaData
+time map_load_time
+float32 resolution
+uint32 width
+uint32 height
+geometry_msgs/Pose origin
+================================================================================
+MSG: geometry_msgs/Pose
+Point position
+Quaternion orientation
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y
+float64 z
+================================================================================
+MSG: geometry_msgs/Quaternion
+float64 x
+float64 y
+float64 z
+float64 w
+";
+
+/// A 3x2 map whose bottom row is free, unknown, and occupied, and whose top row is uncertain, barely free, and barely occupied.
+const CELLS: [i8; 6] = [0, -1, 100, 50, 25, 65];
+
+fn grid() -> OccupancyGrid {
+    OccupancyGrid {
+        resolution: 0.05,
+        width: 3,
+        height: 2,
+        origin: [-1.5, 2.0, 0.0],
+        data: CELLS.to_vec(),
+    }
+}
+
+fn serialize_grid() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0u32.to_le_bytes()); // seq
+    data.extend_from_slice(&[0; 8]); // stamp
+    data.extend_from_slice(&3u32.to_le_bytes());
+    data.extend_from_slice(b"map");
+    data.extend_from_slice(&[0; 8]); // map_load_time
+    data.extend_from_slice(&0.05f32.to_le_bytes());
+    data.extend_from_slice(&3u32.to_le_bytes());
+    data.extend_from_slice(&2u32.to_le_bytes());
+    // position, then a quarter turn about z
+    let half_sqrt = std::f64::consts::FRAC_1_SQRT_2;
+    for value in [-1.5, 2.0, 0.0, 0.0, 0.0, half_sqrt, half_sqrt] {
+        data.extend_from_slice(&f64::to_le_bytes(value));
+    }
+    data.extend_from_slice(&(CELLS.len() as u32).to_le_bytes());
+    data.extend(CELLS.iter().map(|cell| *cell as u8));
+    data
+}
+
+#[test]
+fn pixels_match_map_saver() {
+    // the top row of the image is the last row of the map
+    assert_eq!(grid().pixels(), [205, 254, 0, 254, 205, 0]);
+
+    let pgm = grid().to_pgm();
+    let header = b"P5\n# CREATOR: frost 0.050 m/pix\n3 2\n255\n";
+    assert_eq!(&pgm[..header.len()], header);
+    assert_eq!(pgm[header.len()..], grid().pixels());
+
+    assert_eq!(
+        grid().to_yaml("map.pgm"),
+        "image: map.pgm\nresolution: 0.050000\norigin: [-1.500000, 2.000000, 0.000000]\nnegate: 0\noccupied_thresh: 0.65\nfree_thresh: 0.196\n"
+    );
+}
+
+#[test]
+fn from_bag_and_save() {
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&ConnectionData {
+        md5sum: "3381f2d731d4076ec5c71b0759edbe4e".to_string(),
+        latching: true,
+        ..common::connection("/map", "nav_msgs/OccupancyGrid", OCCUPANCY_GRID_DEFINITION)
+    });
+    writer
+        .write_raw(conn_id, Time { secs: 1, nsecs: 0 }, &serialize_grid())
+        .unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    let msg = bag.read_messages(&Query::all()).unwrap().next().unwrap();
+    let decoded = OccupancyGrid::from_value(&msg.decode().unwrap()).unwrap();
+    assert_eq!(decoded.data, CELLS);
+    assert_eq!((decoded.width, decoded.height), (3, 2));
+    assert_eq!(decoded.resolution, 0.05);
+    assert_eq!(decoded.origin[..2], [-1.5, 2.0]);
+    assert!((decoded.origin[2] - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+
+    let dir = tempdir().unwrap();
+    let (image_path, yaml_path) = decoded
+        .save(dir.path().join("office"), MapImageFormat::Pgm)
+        .unwrap();
+    assert_eq!(image_path, dir.path().join("office.pgm"));
+    assert_eq!(std::fs::read(&image_path).unwrap(), decoded.to_pgm());
+    let yaml = std::fs::read_to_string(yaml_path).unwrap();
+    assert!(yaml.starts_with("image: office.pgm\n"), "{yaml}");
+    assert!(yaml.contains("1.570796]"), "{yaml}");
+}
+
+#[cfg(feature = "images")]
+#[test]
+fn png() {
+    let png = grid().to_png().unwrap();
+    let image = image::load_from_memory(&png).unwrap().to_luma8();
+    assert_eq!(image.dimensions(), (3, 2));
+    assert_eq!(image.into_raw(), grid().pixels());
+    assert_eq!("png".parse::<MapImageFormat>(), Ok(MapImageFormat::Png));
+}
+
+#[test]
+fn invalid_grids() {
+    assert!("jpg".parse::<MapImageFormat>().is_err());
+
+    let err = OccupancyGrid::from_value(&Value::Message(vec![])).unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+
+    let message = |fields: Vec<(&str, Value)>| {
+        Value::Message(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    };
+    let xyz = |w: Option<f64>| {
+        let mut fields = vec![
+            ("x", Value::F64(0.0)),
+            ("y", Value::F64(0.0)),
+            ("z", Value::F64(0.0)),
+        ];
+        fields.extend(w.map(|w| ("w", Value::F64(w))));
+        message(fields)
+    };
+    let short = message(vec![
+        (
+            "info",
+            message(vec![
+                ("resolution", Value::F32(0.05)),
+                ("width", Value::U32(3)),
+                ("height", Value::U32(2)),
+                (
+                    "origin",
+                    message(vec![
+                        ("position", xyz(None)),
+                        ("orientation", xyz(Some(1.0))),
+                    ]),
+                ),
+            ]),
+        ),
+        ("data", Value::Array(vec![Value::I8(0); 5])),
+    ]);
+    let err = OccupancyGrid::from_value(&short).unwrap_err();
+    assert!(
+        matches!(&err, DecodeError::InvalidField(e) if e.contains("has 5 cells, but the map is 3x2")),
+        "{err:?}"
+    );
+}
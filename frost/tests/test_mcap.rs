@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use frost::mcap::{McapCompression, McapWriter};
+use frost::query::Query;
+use frost::DecompressedBag;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+const MAGIC: &[u8] = b"\x89MCAP0\r\n";
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn str_at(bytes: &[u8], at: usize) -> (String, usize) {
+    let len = u32_at(bytes, at) as usize;
+    let s = String::from_utf8(bytes[at + 4..at + 4 + len].to_vec()).unwrap();
+    (s, at + 4 + len)
+}
+
+/// `(log time, data)` of messages, per topic.
+type Messages = BTreeMap<String, Vec<(u64, Vec<u8>)>>;
+
+/// `(opcode, content)` of each record.
+fn records(mut bytes: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut records = Vec::new();
+    while !bytes.is_empty() {
+        let len = u64_at(bytes, 1) as usize;
+        records.push((bytes[0], &bytes[9..9 + len]));
+        bytes = &bytes[9 + len..];
+    }
+    records
+}
+
+/// The messages of an MCAP file and the names of its schemas.
+fn read_mcap(mcap: &[u8]) -> (Messages, Vec<String>) {
+    assert!(mcap.starts_with(MAGIC) && mcap.ends_with(MAGIC));
+    let data = &mcap[MAGIC.len()..mcap.len() - MAGIC.len()];
+
+    let mut schemas = Vec::new();
+    let mut topics = BTreeMap::new();
+    let mut messages = Messages::new();
+    let mut chunk_records = Vec::new();
+    for (opcode, content) in records(data) {
+        match opcode {
+            // the summary repeats schemas and channels
+            0x0F => break,
+            0x03 => {
+                let (name, at) = str_at(content, 2);
+                let (encoding, _) = str_at(content, at);
+                assert_eq!(encoding, "ros1msg");
+                schemas.push(name);
+            }
+            0x04 => {
+                let (topic, at) = str_at(content, 4);
+                let (encoding, _) = str_at(content, at);
+                assert_eq!(encoding, "ros1");
+                topics.insert(u16_at(content, 0), topic);
+            }
+            0x06 => {
+                let uncompressed_size = u64_at(content, 16) as usize;
+                let (compression, at) = str_at(content, 28);
+                let len = u64_at(content, at) as usize;
+                let compressed = &content[at + 8..at + 8 + len];
+                let mut uncompressed = Vec::new();
+                match compression.as_str() {
+                    "" => uncompressed.extend_from_slice(compressed),
+                    "lz4" => {
+                        lz4_flex::frame::FrameDecoder::new(compressed)
+                            .read_to_end(&mut uncompressed)
+                            .unwrap();
+                    }
+                    other => panic!("unexpected compression {other}"),
+                }
+                assert_eq!(uncompressed.len(), uncompressed_size);
+                chunk_records.push(uncompressed);
+            }
+            _ => {}
+        }
+    }
+    for chunk in chunk_records.iter() {
+        for (opcode, content) in records(chunk) {
+            assert_eq!(opcode, 0x05);
+            let topic = topics[&u16_at(content, 0)].clone();
+            let log_time = u64_at(content, 6);
+            assert_eq!(u64_at(content, 14), log_time);
+            messages
+                .entry(topic)
+                .or_default()
+                .push((log_time, content[22..].to_vec()));
+        }
+    }
+    (messages, schemas)
+}
+
+fn bag_messages(bag: &DecompressedBag) -> Messages {
+    let mut messages = Messages::new();
+    for msg_view in bag.read_messages(&Query::all()).unwrap() {
+        let time = u64::from(msg_view.time.secs) * 1_000_000_000 + u64::from(msg_view.time.nsecs);
+        messages
+            .entry(msg_view.topic.to_string())
+            .or_default()
+            .push((time, msg_view.raw_bytes().unwrap()[4..].to_vec()));
+    }
+    messages
+}
+
+#[test]
+fn exports_every_message() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    for compression in [McapCompression::None, McapCompression::Lz4] {
+        let mut writer = McapWriter::new(Vec::new())
+            .unwrap()
+            .with_compression(compression)
+            .with_chunk_size(1024);
+        assert_eq!(writer.copy_messages(&bag, &Query::all()).unwrap(), 300);
+        let (messages, mut schemas) = read_mcap(&writer.finish().unwrap());
+
+        assert_eq!(messages, bag_messages(&bag), "{compression}");
+        schemas.sort();
+        assert_eq!(
+            schemas,
+            [
+                "std_msgs/Float64MultiArray",
+                "std_msgs/String",
+                "std_msgs/Time"
+            ]
+        );
+    }
+}
+
+#[test]
+fn summary() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let mut writer = McapWriter::new(Vec::new()).unwrap().with_chunk_size(1024);
+    writer
+        .copy_messages(&bag, &Query::new().with_topics(["/chatter"]))
+        .unwrap();
+    let mcap = writer.finish().unwrap();
+
+    let footer = &mcap[mcap.len() - MAGIC.len() - 20..mcap.len() - MAGIC.len()];
+    let summary_start = u64_at(footer, 0) as usize;
+    let summary_offset_start = u64_at(footer, 8) as usize;
+    let summary = records(&mcap[summary_start..summary_offset_start]);
+
+    let (_, statistics) = summary.iter().find(|(opcode, _)| *opcode == 0x0B).unwrap();
+    assert_eq!(u64_at(statistics, 0), 100);
+    assert_eq!(u16_at(statistics, 8), 1);
+    assert_eq!(u32_at(statistics, 10), 1);
+    let chunk_indexes: Vec<_> = summary
+        .iter()
+        .filter(|(opcode, _)| *opcode == 0x08)
+        .collect();
+    assert_eq!(u32_at(statistics, 22) as usize, chunk_indexes.len());
+    assert!(chunk_indexes.len() > 1);
+
+    // chunk indexes point at chunk records
+    for (_, chunk_index) in chunk_indexes {
+        let chunk_start = u64_at(chunk_index, 16) as usize;
+        assert_eq!(mcap[chunk_start], 0x06);
+    }
+
+    // summary offsets point at the groups of summary records
+    let offsets = records(&mcap[summary_offset_start..mcap.len() - MAGIC.len() - 29]);
+    assert_eq!(offsets.len(), 4);
+    for (opcode, offset) in offsets {
+        assert_eq!(opcode, 0x0E);
+        let group_start = u64_at(offset, 1) as usize;
+        assert_eq!(mcap[group_start], offset[0]);
+    }
+}
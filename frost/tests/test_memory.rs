@@ -0,0 +1,78 @@
+use frost::query::Query;
+use frost::time::Time;
+use frost::writer::Compression;
+use frost::DecompressedBag;
+
+mod common;
+
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+/// Rewrites the fixture into many small chunks.
+fn chunked_bag() -> DecompressedBag {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(1024);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    DecompressedBag::from_bytes(&writer.finish().unwrap().into_inner()).unwrap()
+}
+
+#[test]
+fn memory_usage() {
+    let bag = chunked_bag();
+    assert!(bag.metadata.chunk_count() > 4);
+    assert!(bag.memory_usage() >= bag.metadata.uncompressed_size());
+}
+
+#[test]
+fn drop_chunks() {
+    let mut bag = chunked_bag();
+    let before = bag.memory_usage();
+    let half = Time { secs: 50, nsecs: 0 };
+
+    let freed = bag.drop_chunks(..half);
+    assert!(freed > 0 && freed < before);
+    assert_eq!(bag.memory_usage(), before - freed);
+
+    // chunks straddling the range are kept, along with their earlier messages
+    let remaining: Vec<Time> = bag
+        .read_messages(&Query::all())
+        .unwrap()
+        .map(|msg_view| msg_view.time)
+        .collect();
+    assert_eq!(remaining.len(), bag.count_messages(&Query::all()));
+    assert!(remaining.len() < 300);
+    let late = Query::new().with_start_time(half);
+    assert_eq!(
+        bag.count_messages(&late),
+        chunked_bag().count_messages(&late)
+    );
+
+    assert_eq!(bag.drop_chunks(..half), 0);
+    assert_eq!(bag.drop_chunks(..), before - freed);
+    assert_eq!(bag.read_messages(&Query::all()).unwrap().count(), 0);
+}
+
+#[test]
+fn retain_topics() {
+    let mut bag = chunked_bag();
+    let before = bag.memory_usage();
+
+    let freed = bag.retain_topics(&["/chatter"]);
+    assert_eq!(bag.memory_usage(), before - freed);
+    assert_eq!(bag.count_messages(&Query::all()), 100);
+
+    let expected = chunked_bag();
+    let chatter = Query::new().with_topics(["/chatter"]);
+    for (actual, expected) in bag
+        .read_messages(&Query::all())
+        .unwrap()
+        .zip(expected.read_messages(&chatter).unwrap())
+    {
+        assert_eq!(actual.topic, "/chatter");
+        assert_eq!(actual.raw_bytes().unwrap(), expected.raw_bytes().unwrap());
+    }
+
+    assert_eq!(bag.retain_topics::<&str>(&[]), before - freed);
+    assert_eq!(bag.memory_usage(), 0);
+}
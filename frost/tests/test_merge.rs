@@ -0,0 +1,220 @@
+use frost::merge::{shift, Merge, TimeSource};
+use frost::query::Query;
+use frost::time::{self, Time};
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+use common::connection;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+
+const STAMPED_DEFINITION: &str = "\
+Header header
+int32 value
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+";
+
+fn time(secs: f64) -> Time {
+    Time {
+        secs: secs as u32,
+        nsecs: ((secs.fract() * 1e9).round()) as u32,
+    }
+}
+
+fn serialize_stamped(stamp: Time, value: i32) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0u32.to_le_bytes()); // seq
+    data.extend_from_slice(&stamp.secs.to_le_bytes());
+    data.extend_from_slice(&stamp.nsecs.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // frame_id
+    data.extend_from_slice(&value.to_le_bytes());
+    data
+}
+
+/// Writes `/stamped` messages as `(received, stamp, value)`, and `/text` strings at `text_times`.
+fn write_bag(stamped: &[(f64, f64, i32)], text_times: &[f64]) -> Vec<u8> {
+    let mut writer = common::writer();
+    let stamped_id = writer.add_connection(&connection(
+        "/stamped",
+        "test_msgs/Stamped",
+        STAMPED_DEFINITION,
+    ));
+    let text_id = writer.add_connection(&connection("/text", "std_msgs/String", "string data\n"));
+    for (received, stamp, value) in stamped {
+        writer
+            .write_raw(
+                stamped_id,
+                time(*received),
+                &serialize_stamped(time(*stamp), *value),
+            )
+            .unwrap();
+    }
+    for (i, received) in text_times.iter().enumerate() {
+        let text = format!("text_{i}");
+        writer
+            .write_raw(text_id, time(*received), &common::string(text))
+            .unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// Returns the `(receive time, value)` of each `/stamped` message, with -1 for `/text` messages.
+fn merged(merge: Merge) -> Vec<(Time, i32)> {
+    let mut writer = common::writer();
+    merge.write_to(&mut writer).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    bag.read_messages(&Query::all())
+        .unwrap()
+        .map(|msg_view| {
+            let value = match msg_view.decode().unwrap().get("value") {
+                Some(frost::dynamic::Value::I32(value)) => *value,
+                _ => -1,
+            };
+            (msg_view.time, value)
+        })
+        .collect()
+}
+
+#[test]
+fn merge_with_offsets() {
+    // the second machine's clock is 100s ahead
+    let a = write_bag(&[(10.0, 10.0, 0), (11.0, 11.0, 1), (12.0, 12.0, 2)], &[]);
+    let b = write_bag(&[(110.5, 110.5, 100), (111.5, 111.5, 101)], &[]);
+    let a = DecompressedBag::from_bytes(&a).unwrap();
+    let b = DecompressedBag::from_bytes(&b).unwrap();
+
+    let messages = merged(
+        Merge::new()
+            .with_rewritten_times()
+            .add_bag(&a, 0.0)
+            .add_bag(&b, -100.0),
+    );
+    assert_eq!(
+        messages,
+        [
+            (time(10.0), 0),
+            (time(10.5), 100),
+            (time(11.0), 1),
+            (time(11.5), 101),
+            (time(12.0), 2)
+        ]
+    );
+
+    // without rewriting, receive times are kept as recorded
+    let messages = merged(Merge::new().add_bag(&a, 0.0).add_bag(&b, -100.0));
+    assert_eq!(messages.len(), 5);
+    assert_eq!(messages[3], (time(110.5), 100));
+}
+
+#[test]
+fn merge_by_header_stamp() {
+    // the first machine received its message 3s late, and the second machine's clock is 100s ahead
+    let a = write_bag(&[(13.0, 10.0, 0)], &[12.0]);
+    let b = write_bag(&[(111.2, 111.0, 100)], &[]);
+    let a = DecompressedBag::from_bytes(&a).unwrap();
+    let b = DecompressedBag::from_bytes(&b).unwrap();
+
+    let by_receive = merged(
+        Merge::new()
+            .with_rewritten_times()
+            .add_bag(&a, 0.0)
+            .add_bag(&b, -100.0),
+    );
+    assert_eq!(
+        by_receive,
+        [(time(11.2), 100), (time(12.0), -1), (time(13.0), 0)]
+    );
+
+    // messages without a header keep their receive time
+    let by_header = merged(
+        Merge::new()
+            .with_time_source(TimeSource::Header)
+            .with_rewritten_times()
+            .add_bag(&a, 0.0)
+            .add_bag(&b, -100.0),
+    );
+    assert_eq!(
+        by_header,
+        [(time(10.0), 0), (time(11.0), 100), (time(12.0), -1)]
+    );
+}
+
+#[test]
+fn merge_shares_connections() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let mut writer = common::writer();
+    let count = Merge::new()
+        .with_query(Query::new().with_topics(["/chatter", "/time"]))
+        .add_bag(&bag, 0.0)
+        .add_bag(&bag, 0.0)
+        .write_to(&mut writer)
+        .unwrap();
+    assert_eq!(count, 400);
+
+    let metadata = BagMetadata::from_bytes(&writer.finish().unwrap().into_inner()).unwrap();
+    assert_eq!(metadata.connection_count(), 2);
+    assert_eq!(metadata.message_count(), 400);
+    assert_eq!(metadata.start_time(), bag.metadata.start_time());
+}
+
+#[test]
+fn time_sources() {
+    assert_eq!("header".parse(), Ok(TimeSource::Header));
+    assert_eq!(TimeSource::Receive.to_string(), "receive");
+    assert!("stamp".parse::<TimeSource>().is_err());
+
+    assert_eq!(shift(time(1.5), -500_000_000), time(1.0));
+    assert_eq!(shift(time(1.0), -2_000_000_000), time::ZERO);
+    assert_eq!(shift(time::MAX, 1), time::MAX);
+}
+
+#[test]
+fn merge_drops_duplicates() {
+    // a backup recorder received the same messages a little later, and one the primary missed
+    let primary = write_bag(&[(10.0, 10.0, 0), (11.0, 11.0, 1), (11.0, 11.0, 1)], &[]);
+    let backup = write_bag(&[(10.02, 10.0, 0), (11.01, 11.0, 1), (12.0, 12.0, 2)], &[]);
+    let primary = DecompressedBag::from_bytes(&primary).unwrap();
+    let backup = DecompressedBag::from_bytes(&backup).unwrap();
+
+    // repeats within a bag are kept
+    let messages = merged(
+        Merge::new()
+            .with_dedup_tolerance(0.05)
+            .add_bag(&primary, 0.0)
+            .add_bag(&backup, 0.0),
+    );
+    assert_eq!(
+        messages,
+        [
+            (time(10.0), 0),
+            (time(11.0), 1),
+            (time(11.0), 1),
+            (time(12.0), 2)
+        ]
+    );
+
+    // copies further apart than the tolerance are kept
+    let messages = merged(
+        Merge::new()
+            .with_dedup_tolerance(0.015)
+            .add_bag(&primary, 0.0)
+            .add_bag(&backup, 0.0),
+    );
+    assert_eq!(messages.len(), 5);
+    assert_eq!(messages[1], (time(10.02), 0));
+
+    // by header stamps, the copies are at the same time
+    let messages = merged(
+        Merge::new()
+            .with_time_source(TimeSource::Header)
+            .with_dedup_tolerance(0.0)
+            .add_bag(&primary, 0.0)
+            .add_bag(&backup, 0.0),
+    );
+    assert_eq!(messages.len(), 4);
+}
@@ -0,0 +1,121 @@
+use frost::query::Query;
+use frost::time::Time;
+use frost::{BagMetadata, ChunkMetadata, DecompressedBag};
+
+mod common;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+#[test]
+fn counts_and_lookups() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let metadata = BagMetadata::from_bytes(bytes).unwrap();
+
+        assert_eq!(metadata.chunk_count(), 1, "{name}");
+        assert_eq!(metadata.connection_count(), 3, "{name}");
+        assert_eq!(metadata.message_count(), 300, "{name}");
+        assert!(!metadata.is_empty(), "{name}");
+
+        assert!(metadata.topic_exists("/chatter"), "{name}");
+        assert!(metadata.topic_exists("/array"), "{name}");
+        assert!(!metadata.topic_exists("/chat"), "{name}");
+        assert!(!metadata.topic_exists("std_msgs/String"), "{name}");
+    }
+}
+
+#[test]
+fn chunks_for_topic() {
+    // one topic after the other, in small chunks
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer().with_chunk_size(1024);
+    for topic in ["/chatter", "/array", "/time"] {
+        writer
+            .copy_messages(&bag, &Query::new().with_topics([topic]))
+            .unwrap();
+    }
+    let metadata = BagMetadata::from_bytes(&writer.finish().unwrap().into_inner()).unwrap();
+
+    let chunks: Vec<&ChunkMetadata> = metadata.chunks().collect();
+    assert_eq!(chunks.len(), metadata.chunk_count());
+    assert!(chunks
+        .windows(2)
+        .all(|pair| pair[0].position() < pair[1].position()));
+    assert!(chunks
+        .iter()
+        .all(|chunk| chunk.start_time() <= chunk.end_time()));
+
+    let counts = metadata.topic_message_counts();
+    let mut total = 0;
+    for topic in ["/chatter", "/array", "/time"] {
+        let id = metadata
+            .connection_data
+            .values()
+            .find(|data| data.topic == topic)
+            .unwrap()
+            .connection_id;
+        let topic_chunks = metadata.chunks_for_topic(topic);
+        assert!(topic_chunks.len() < chunks.len(), "{topic}");
+        let count: u32 = topic_chunks
+            .iter()
+            .map(|chunk| chunk.message_counts()[&id])
+            .sum();
+        assert_eq!(count as usize, counts[topic], "{topic}");
+        total += topic_chunks.len();
+    }
+    // chunks where one topic ends and the next begins hold both
+    assert!(total >= chunks.len());
+    assert!(metadata.chunks_for_topic("/missing").is_empty());
+}
+
+#[test]
+fn formats_times() {
+    let metadata = BagMetadata::from_bytes(COMPRESSED_LZ4).unwrap();
+    let start = metadata.start_time().unwrap();
+    assert_eq!(
+        start.as_rfc3339().as_deref(),
+        Some("1970-01-01T00:00:00.000001000Z")
+    );
+
+    // garbage from a corrupt bag
+    let garbage = Time {
+        secs: u32::MAX,
+        nsecs: 1_500_000_000,
+    };
+    assert_eq!(garbage.as_datetime(), None);
+    assert_eq!(garbage.as_rfc3339(), None);
+    let latest = Time {
+        secs: u32::MAX,
+        nsecs: 999_999_999,
+    };
+    assert_eq!(
+        latest.as_rfc3339().as_deref(),
+        Some("2106-02-07T06:28:15.999999999Z")
+    );
+}
+
+#[test]
+fn relative_times() {
+    let metadata = BagMetadata::from_bytes(COMPRESSED_LZ4).unwrap();
+    let start = metadata.start_time().unwrap();
+    let end = metadata.end_time().unwrap();
+    assert!((end.relative_to(&start) - 99.000099).abs() < 1e-9);
+    assert!((start.relative_to(&end) + 99.000099).abs() < 1e-9);
+    assert_eq!(start.relative_to(&start), 0.0);
+
+    // precise even for stamps far from the epoch
+    let start = Time {
+        secs: 1_700_000_000,
+        nsecs: 900_000_000,
+    };
+    let time = Time {
+        secs: 1_700_000_037,
+        nsecs: 100_000_001,
+    };
+    assert!((time.relative_to(&start) - 36.200000001).abs() < 1e-12);
+}
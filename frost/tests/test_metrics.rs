@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use frost::metrics::{self, Counters};
+use frost::query::Query;
+use frost::{BagMetadata, DecompressedBag};
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+// The recorder is process-wide, so everything is checked in a single test.
+#[test]
+fn counters_track_bag_processing() {
+    let counters = Arc::new(Counters::default());
+    metrics::set_recorder(counters.clone());
+
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let snapshot = counters.snapshot();
+    assert_eq!(snapshot.bytes_read, COMPRESSED_LZ4.len() as u64);
+    assert_eq!(snapshot.chunks_decompressed, 1);
+    assert!(snapshot.decompressed_bytes > COMPRESSED_LZ4.len() as u64);
+    assert_eq!(snapshot.messages_yielded, 0);
+
+    let query = Query::new().with_topics(["/chatter"]);
+    assert_eq!(bag.read_messages(&query).unwrap().count(), 100);
+    assert_eq!(counters.snapshot().messages_yielded, 100);
+
+    // metadata only reads headers and skips over chunk data
+    BagMetadata::from_bytes(DECOMPRESSED).unwrap();
+    let bytes_read = counters.snapshot().bytes_read - snapshot.bytes_read;
+    assert!(bytes_read > 0);
+    assert!(bytes_read < DECOMPRESSED.len() as u64);
+
+    metrics::clear_recorder();
+    DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    assert_eq!(counters.snapshot().chunks_decompressed, 1);
+}
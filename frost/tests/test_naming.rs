@@ -0,0 +1,159 @@
+use std::io::ErrorKind;
+use std::process::Command;
+
+use frost::errors;
+use frost::export::naming::{Collision, FileNamer, NameFields, NameTemplate, MESSAGE_PLACEHOLDERS};
+use frost::query::Query;
+use frost::time::Time;
+use frost::DecompressedBag;
+use tempfile::tempdir;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn template(s: &str) -> NameTemplate {
+    s.parse().unwrap()
+}
+
+fn io_kind(error: errors::Error) -> ErrorKind {
+    match error.kind() {
+        errors::ErrorKind::Io(e) => e.kind(),
+        other => panic!("expected an io error, got {other:?}"),
+    }
+}
+
+#[test]
+fn parses_templates() {
+    let name = template("{topic}/{{raw}}_{stamp_ns}.{ext}");
+    assert_eq!(
+        name.placeholders().collect::<Vec<_>>(),
+        ["topic", "stamp_ns", "ext"]
+    );
+    assert!(name.check(MESSAGE_PLACEHOLDERS).is_ok());
+    assert_eq!(
+        name.check(&["topic", "ext"]).unwrap_err().to_string(),
+        "unknown placeholder {stamp_ns} in name template '{topic}/{{raw}}_{stamp_ns}.{ext}', \
+         expected one of {topic}, {ext}"
+    );
+
+    for invalid in ["{topic", "topic}", "{}", "{a b}", "/abs/{seq}"] {
+        assert!(invalid.parse::<NameTemplate>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn names_messages() {
+    let dir = tempdir().unwrap();
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let mut namer = FileNamer::new(dir.path(), template("{topic}/{seq}_{type}_{stamp}.{ext}"));
+
+    let query = Query::new().with_topics(["/chatter", "/time"]);
+    let mut names: Vec<_> = bag
+        .read_messages(&query)
+        .unwrap()
+        .take(4)
+        .map(|msg_view| {
+            namer
+                .name(&NameFields::for_message(&msg_view).with_ext("json"))
+                .unwrap()
+        })
+        .collect();
+    // messages received at the same time on different topics come in no particular order
+    names.sort_by_key(|path| path.file_name().map(|name| name.to_owned()));
+    // sequences count per topic
+    assert_eq!(
+        names,
+        [
+            dir.path()
+                .join("chatter/0_std_msgs_String_0.000001000.json"),
+            dir.path().join("time/0_std_msgs_Time_0.000001000.json"),
+            dir.path()
+                .join("chatter/1_std_msgs_String_1.000002000.json"),
+            dir.path().join("time/1_std_msgs_Time_1.000002000.json"),
+        ]
+    );
+    // parent directories are created
+    assert!(dir.path().join("chatter").is_dir());
+    assert!(dir.path().join("time").is_dir());
+}
+
+#[test]
+fn suffixes_repeated_names() {
+    let dir = tempdir().unwrap();
+    let mut namer = FileNamer::new(dir.path(), template("{stamp_ns}.{ext}"));
+    let fields = NameFields::message("/a", "std_msgs/Empty", Time { secs: 2, nsecs: 0 });
+    let names: Vec<_> = (0..3)
+        .map(|_| namer.name(&fields.clone().with_ext("png")).unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            dir.path().join("2000000000.png"),
+            dir.path().join("2000000000_1.png"),
+            dir.path().join("2000000000_2.png"),
+        ]
+    );
+}
+
+#[test]
+fn handles_existing_files() {
+    let dir = tempdir().unwrap();
+    std::fs::write(dir.path().join("taken.txt"), "").unwrap();
+    let fields = NameFields::new().with("name", "taken").with_ext("txt");
+
+    let mut namer = FileNamer::new(dir.path(), template("{name}.{ext}"));
+    assert_eq!(namer.name(&fields).unwrap(), dir.path().join("taken_1.txt"));
+
+    let mut namer =
+        FileNamer::new(dir.path(), template("{name}.{ext}")).with_collision(Collision::Overwrite);
+    assert_eq!(namer.name(&fields).unwrap(), dir.path().join("taken.txt"));
+    // but never a name it gave out itself
+    assert_eq!(namer.name(&fields).unwrap(), dir.path().join("taken_1.txt"));
+
+    let mut namer =
+        FileNamer::new(dir.path(), template("{name}.{ext}")).with_collision(Collision::Fail);
+    assert_eq!(
+        io_kind(namer.name(&fields).unwrap_err()),
+        ErrorKind::AlreadyExists
+    );
+}
+
+#[test]
+fn stays_in_the_output_directory() {
+    let dir = tempdir().unwrap();
+    let mut namer = FileNamer::new(dir.path(), template("{name}/{topic}"));
+    let fields = NameFields::new().with("topic", "/a/b");
+
+    // `/` in values other than {topic} doesn't make directories
+    let path = namer.name(&fields.clone().with("name", "c/d")).unwrap();
+    assert_eq!(path, dir.path().join("c_d/a/b"));
+
+    let escape = namer.name(&fields.clone().with("name", ".."));
+    assert_eq!(io_kind(escape.unwrap_err()), ErrorKind::InvalidInput);
+    let missing = namer.name(&NameFields::new().with("name", "x"));
+    assert_eq!(io_kind(missing.unwrap_err()), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn split_topics_names() {
+    let dir = tempdir().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["split-topics", FILE_PATH, "--group", "text:/chatter"])
+        .args(["--group", "times:/time", "--name", "{group}/{stem}.{ext}"])
+        .arg("--output-dir")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(status.status.success(), "{status:?}");
+    for (group, count) in [("text", 100), ("times", 100)] {
+        let path = dir.path().join(group).join("compressed_lz4.bag");
+        let bag = DecompressedBag::from_file(path).unwrap();
+        assert_eq!(bag.read_messages(&Query::all()).unwrap().count(), count);
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["split-topics", FILE_PATH, "--group", "text:/chatter"])
+        .args(["--name", "{topic}.bag"])
+        .output()
+        .unwrap();
+    assert!(!status.status.success());
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use frost::query::Query;
+use frost::time::Time;
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+/// receive times in seconds, with a late message at 5s and a stalled one at 2s
+const SECS: [u32; 8] = [1, 3, 4, 6, 5, 7, 2, 8];
+
+fn write_unordered(reorder_window: Option<Duration>) -> Vec<u8> {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let connection = bag
+        .metadata
+        .connection_data
+        .values()
+        .find(|data| data.topic == "/chatter")
+        .unwrap();
+    let data = bag
+        .read_messages(&Query::new().with_topics(["/chatter"]))
+        .unwrap()
+        .next()
+        .unwrap()
+        .raw_bytes()
+        .unwrap()
+        .to_vec();
+
+    let mut writer = common::writer();
+    if let Some(window) = reorder_window {
+        writer = writer.with_reorder_window(window);
+    }
+    let conn_id = writer.add_connection(connection);
+    for secs in SECS {
+        writer
+            .write_raw(conn_id, Time { secs, nsecs: 0 }, &data)
+            .unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn fixtures_are_monotonic() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let report = BagMetadata::from_bytes(bytes).unwrap().time_order_report();
+        assert!(report.is_monotonic(), "{name}");
+        assert_eq!(report.message_count, 300, "{name}");
+        assert_eq!(report.max_step_back, Duration::ZERO, "{name}");
+    }
+}
+
+#[test]
+fn detects_out_of_order_messages() {
+    let report = BagMetadata::from_bytes(&write_unordered(None))
+        .unwrap()
+        .time_order_report();
+
+    assert!(!report.is_monotonic());
+    assert_eq!(report.message_count, 8);
+    assert_eq!(report.out_of_order_by_topic["/chatter"], 2);
+    assert_eq!(report.out_of_order_in_chunks, 2);
+    assert_eq!(report.overlapping_chunks, 0);
+    assert_eq!(report.max_step_back, Duration::from_secs(5));
+}
+
+#[test]
+fn reorder_window() {
+    // a 1s window sorts the message which is 1s late, but has already written 5s by the time 2s arrives
+    let bytes = write_unordered(Some(Duration::from_secs(1)));
+    let report = BagMetadata::from_bytes(&bytes).unwrap().time_order_report();
+    assert_eq!(report.out_of_order_by_topic["/chatter"], 1);
+    assert_eq!(report.max_step_back, Duration::from_secs(3));
+
+    let bytes = write_unordered(Some(Duration::MAX));
+    let report = BagMetadata::from_bytes(&bytes).unwrap().time_order_report();
+    assert!(report.is_monotonic());
+    assert_eq!(report.message_count, 8);
+}
+
+#[test]
+fn rewriting_sorts_by_receive_time() {
+    let bag = DecompressedBag::from_bytes(&write_unordered(None)).unwrap();
+    let mut writer = common::writer();
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let report = BagMetadata::from_bytes(&bytes).unwrap().time_order_report();
+    assert!(report.is_monotonic());
+}
@@ -0,0 +1,75 @@
+use std::io::Write;
+
+use frost::output::OutputPolicy;
+use frost::query::Query;
+use frost::writer::BagWriter;
+use frost::DecompressedBag;
+use tempfile::tempdir;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn copy(policy: OutputPolicy, path: &std::path::Path) -> u64 {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let mut writer = BagWriter::new(policy.create(path).unwrap()).unwrap();
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    writer.finish().unwrap().commit().unwrap()
+}
+
+#[test]
+fn outputs_are_atomic() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("out.bag");
+
+    // nothing appears until the output is committed, and dropping it cleans up
+    let mut output = OutputPolicy::new().create(&path).unwrap();
+    output.write_all(b"partial").unwrap();
+    assert!(!path.exists());
+    drop(output);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    let size = copy(OutputPolicy::new(), &path);
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+    let bag = DecompressedBag::from_file(&path).unwrap();
+    assert_eq!(bag.metadata.message_count(), 300);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn existing_outputs_need_force() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("out.bag");
+    std::fs::write(&path, b"previous").unwrap();
+
+    assert!(OutputPolicy::new().create(&path).is_err());
+    // the destination appearing while writing is caught when committing
+    let other = dir.path().join("other.bag");
+    let mut output = OutputPolicy::new().create(&other).unwrap();
+    output.write_all(b"new").unwrap();
+    std::fs::write(&other, b"raced").unwrap();
+    assert!(output.commit().is_err());
+    assert_eq!(std::fs::read(&other).unwrap(), b"raced");
+
+    copy(OutputPolicy::new().with_force(), &path);
+    assert_eq!(
+        DecompressedBag::from_file(&path)
+            .unwrap()
+            .metadata
+            .message_count(),
+        300
+    );
+}
+
+#[test]
+fn dry_runs_write_nothing() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("out.bag");
+
+    let policy = OutputPolicy::new().with_dry_run();
+    assert!(policy.create(&path).unwrap().is_dry_run());
+    let size = copy(policy, &path);
+    assert!(!path.exists());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    // the size is what would have been written
+    assert_eq!(copy(OutputPolicy::new(), &path), size);
+}
@@ -0,0 +1,352 @@
+use frost::import::Importer;
+use frost::pcap::{PcapReader, PcapWarning};
+use frost::query::Query;
+use frost::time::Time;
+use frost::DecompressedBag;
+
+mod common;
+
+const SUBSCRIBER: ([u8; 4], u16) = ([10, 0, 0, 2], 40000);
+const PUBLISHER: ([u8; 4], u16) = ([10, 0, 0, 1], 35000);
+const STRING_MD5: &str = "992ce8a1687cec8c8bd883ec73ca41d1";
+
+/// `(topic, capture time, data)` of messages.
+type Messages = Vec<(String, Time, Vec<u8>)>;
+
+/// `(seconds, source, destination, TCP flags, seq, payload)`
+type Segment = (u32, ([u8; 4], u16), ([u8; 4], u16), u8, u32, Vec<u8>);
+
+fn header(fields: &[(&str, &str)]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        let field = format!("{name}={value}");
+        body.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        body.extend_from_slice(field.as_bytes());
+    }
+    let mut record = (body.len() as u32).to_le_bytes().to_vec();
+    record.extend_from_slice(&body);
+    record
+}
+
+/// A serialized std_msgs/String with its leading length.
+fn string_message(data: &str) -> Vec<u8> {
+    let mut message = ((data.len() + 4) as u32).to_le_bytes().to_vec();
+    message.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    message.extend_from_slice(data.as_bytes());
+    message
+}
+
+/// An Ethernet frame of an IPv4 TCP segment.
+fn frame(
+    source: ([u8; 4], u16),
+    destination: ([u8; 4], u16),
+    flags: u8,
+    seq: u32,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut frame = vec![0; 12];
+    frame.extend_from_slice(&0x0800u16.to_be_bytes());
+    frame.extend_from_slice(&[0x45, 0]);
+    frame.extend_from_slice(&((20 + 20 + payload.len()) as u16).to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+    frame.extend_from_slice(&source.0);
+    frame.extend_from_slice(&destination.0);
+    frame.extend_from_slice(&source.1.to_be_bytes());
+    frame.extend_from_slice(&destination.1.to_be_bytes());
+    frame.extend_from_slice(&seq.to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn pcap(segments: &[Segment]) -> Vec<u8> {
+    let mut pcap = 0xa1b2c3d4u32.to_le_bytes().to_vec();
+    pcap.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    pcap.extend_from_slice(&65535u32.to_le_bytes());
+    pcap.extend_from_slice(&1u32.to_le_bytes());
+    for (secs, source, destination, flags, seq, payload) in segments {
+        let frame = frame(*source, *destination, *flags, *seq, payload);
+        pcap.extend_from_slice(&secs.to_le_bytes());
+        pcap.extend_from_slice(&500u32.to_le_bytes());
+        pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&frame);
+    }
+    pcap
+}
+
+fn pcapng(segments: &[Segment]) -> Vec<u8> {
+    let block = |block_type: u32, body: &[u8]| {
+        let len = (12 + body.len() + 3) / 4 * 4;
+        let mut block = block_type.to_le_bytes().to_vec();
+        block.extend_from_slice(&(len as u32).to_le_bytes());
+        block.extend_from_slice(body);
+        block.resize(len - 4, 0);
+        block.extend_from_slice(&(len as u32).to_le_bytes());
+        block
+    };
+    let mut pcapng = block(
+        0x0a0d0d0a,
+        &[
+            0x4d, 0x3c, 0x2b, 0x1a, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ],
+    );
+    // Ethernet, with nanosecond timestamps
+    pcapng.extend(block(
+        1,
+        &[1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0],
+    ));
+    for (secs, source, destination, flags, seq, payload) in segments {
+        let frame = frame(*source, *destination, *flags, *seq, payload);
+        let ticks = u64::from(*secs) * 1_000_000_000 + 500_000;
+        let mut body = 0u32.to_le_bytes().to_vec();
+        body.extend_from_slice(&((ticks >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(ticks as u32).to_le_bytes());
+        body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        body.extend_from_slice(&frame);
+        pcapng.extend(block(6, &body));
+    }
+    pcapng
+}
+
+/// A subscription to `/chatter` publishing "a", "b" and "c", with the second message split over two segments.
+fn chatter() -> Vec<Segment> {
+    let subscriber_header = header(&[
+        ("callerid", "/listener"),
+        ("topic", "/chatter"),
+        ("md5sum", STRING_MD5),
+        ("type", "std_msgs/String"),
+    ]);
+    let publisher_header = header(&[
+        ("callerid", "/talker"),
+        ("latching", "1"),
+        ("md5sum", STRING_MD5),
+        ("message_definition", "string data\n"),
+        ("type", "std_msgs/String"),
+    ]);
+    let mut published = publisher_header.clone();
+    published.extend(string_message("a"));
+    let b = string_message("b");
+    let (b1, b2) = b.split_at(3);
+    published.extend_from_slice(b1);
+    let split_at = published.len();
+    published.extend_from_slice(b2);
+    published.extend(string_message("c"));
+
+    let seq = |offset: usize| 1001 + offset as u32;
+    vec![
+        (1, SUBSCRIBER, PUBLISHER, 0x02, 5000, Vec::new()),
+        (1, PUBLISHER, SUBSCRIBER, 0x12, 1000, Vec::new()),
+        (1, SUBSCRIBER, PUBLISHER, 0x18, 5001, subscriber_header),
+        (
+            2,
+            PUBLISHER,
+            SUBSCRIBER,
+            0x18,
+            seq(0),
+            published[..split_at].to_vec(),
+        ),
+        (
+            3,
+            PUBLISHER,
+            SUBSCRIBER,
+            0x18,
+            seq(split_at),
+            published[split_at..].to_vec(),
+        ),
+    ]
+}
+
+fn read_all<R: std::io::Read>(mut reader: PcapReader<R>) -> (Messages, Vec<PcapWarning>) {
+    let mut messages = Vec::new();
+    reader
+        .read_messages(|connection, time, data| {
+            messages.push((connection.topic.clone(), time, data.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+    (messages, reader.warnings().to_vec())
+}
+
+fn data(messages: &Messages) -> Vec<Vec<u8>> {
+    messages.iter().map(|(_, _, data)| data.clone()).collect()
+}
+
+fn strings(values: &[&str]) -> Vec<Vec<u8>> {
+    values
+        .iter()
+        .map(|value| string_message(value)[4..].to_vec())
+        .collect()
+}
+
+#[test]
+fn reads_tcpros_messages() {
+    let capture = pcap(&chatter());
+    let mut reader = PcapReader::new(capture.as_slice()).unwrap();
+    let mut messages = Vec::new();
+    let count = reader
+        .read_messages(|connection, time, data| {
+            messages.push((connection.topic.clone(), time, data.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(count, 3);
+    assert_eq!(data(&messages), strings(&["a", "b", "c"]));
+    // timestamped with the packet which completed them
+    let times: Vec<_> = messages.iter().map(|(_, time, _)| *time).collect();
+    assert_eq!(
+        times,
+        [
+            Time {
+                secs: 2,
+                nsecs: 500_000
+            },
+            Time {
+                secs: 3,
+                nsecs: 500_000
+            },
+            Time {
+                secs: 3,
+                nsecs: 500_000
+            }
+        ]
+    );
+
+    let connection = &reader.connections()[0];
+    assert_eq!(connection.topic, "/chatter");
+    assert_eq!(connection.data_type, "std_msgs/String");
+    assert_eq!(connection.md5sum, STRING_MD5);
+    assert_eq!(connection.caller_id.as_deref(), Some("/talker"));
+    assert!(connection.latching);
+    assert!(reader.warnings().is_empty());
+}
+
+#[test]
+fn reads_pcapng() {
+    let (messages, _) = read_all(PcapReader::new(pcapng(&chatter()).as_slice()).unwrap());
+    assert_eq!(data(&messages), strings(&["a", "b", "c"]));
+    assert_eq!(
+        messages[0].1,
+        Time {
+            secs: 2,
+            nsecs: 500_000
+        }
+    );
+}
+
+#[test]
+fn reorders_and_drops_retransmitted_segments() {
+    let mut segments = chatter();
+    let last = segments.pop().unwrap();
+    let second = segments.pop().unwrap();
+    segments.push(last);
+    segments.push(second.clone());
+    segments.push(second);
+    let (messages, warnings) = read_all(PcapReader::new(pcap(&segments).as_slice()).unwrap());
+    assert_eq!(data(&messages), strings(&["a", "b", "c"]));
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn warns_about_lost_packets() {
+    let mut segments = chatter();
+    segments.remove(3);
+    let (messages, warnings) = read_all(PcapReader::new(pcap(&segments).as_slice()).unwrap());
+    assert!(messages.is_empty());
+    // the publisher's header was lost too, so nothing is known about the connection
+    assert!(warnings.is_empty());
+
+    let mut segments = chatter();
+    let (_, _, _, _, seq, payload) = segments.pop().unwrap();
+    segments.push((
+        4,
+        PUBLISHER,
+        SUBSCRIBER,
+        0x18,
+        seq + payload.len() as u32,
+        string_message("d"),
+    ));
+    let (messages, warnings) = read_all(PcapReader::new(pcap(&segments).as_slice()).unwrap());
+    assert_eq!(data(&messages), strings(&["a"]));
+    assert_eq!(
+        warnings,
+        [PcapWarning::MissingPackets {
+            topic: "/chatter".to_string(),
+            caller_id: "/talker".to_string()
+        }]
+    );
+}
+
+#[test]
+fn ignores_other_traffic() {
+    // XML-RPC, and a stream whose handshake wasn't captured
+    let http = b"POST /RPC2 HTTP/1.1\r\nContent-Length: 0\r\n\r\n".to_vec();
+    let mut segments = vec![
+        (1, SUBSCRIBER, ([10, 0, 0, 1], 11311), 0x02, 1, Vec::new()),
+        (1, SUBSCRIBER, ([10, 0, 0, 1], 11311), 0x18, 2, http),
+        (
+            1,
+            ([10, 0, 0, 3], 1234),
+            SUBSCRIBER,
+            0x18,
+            77,
+            string_message("x"),
+        ),
+    ];
+    segments.extend(chatter());
+    let (messages, _) = read_all(PcapReader::new(pcap(&segments).as_slice()).unwrap());
+    assert_eq!(data(&messages), strings(&["a", "b", "c"]));
+}
+
+#[test]
+fn ignores_services_and_refusals() {
+    let mut segments = chatter();
+    segments[2].5 = header(&[
+        ("callerid", "/client"),
+        ("service", "/add_two_ints"),
+        ("md5sum", "*"),
+    ]);
+    let (messages, _) = read_all(PcapReader::new(pcap(&segments).as_slice()).unwrap());
+    assert!(messages.is_empty());
+
+    let mut segments = chatter();
+    segments[3].5 = header(&[("error", "md5sums do not match"), ("topic", "/chatter")]);
+    segments.pop();
+    let (messages, warnings) = read_all(PcapReader::new(pcap(&segments).as_slice()).unwrap());
+    assert!(messages.is_empty());
+    assert_eq!(
+        warnings,
+        [PcapWarning::Refused {
+            topic: "/chatter".to_string(),
+            error: "md5sums do not match".to_string()
+        }]
+    );
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(PcapReader::new(&b"#ROSBAG V2.0\n"[..]).is_err());
+}
+
+#[test]
+fn imports_into_a_bag() {
+    let mut importer = Importer::new(common::writer());
+    importer
+        .import_pcap(PcapReader::new(pcap(&chatter()).as_slice()).unwrap())
+        .unwrap();
+    let (bag, report) = importer.finish().unwrap();
+    assert_eq!(report.message_count, 3);
+    assert!(report.warnings.is_empty());
+
+    let bag = DecompressedBag::from_bytes(&bag.into_inner()).unwrap();
+    let connection = bag.metadata.connection_data.values().next().unwrap();
+    assert_eq!(connection.topic, "/chatter");
+    assert!(connection.latching);
+    let messages: Vec<_> = bag
+        .read_messages(&Query::all())
+        .unwrap()
+        .map(|msg_view| msg_view.raw_bytes().unwrap()[4..].to_vec())
+        .collect();
+    assert_eq!(messages, strings(&["a", "b", "c"]));
+}
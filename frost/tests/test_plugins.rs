@@ -0,0 +1,80 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use frost::plugins::{self, DESCRIBE_FLAG};
+use tempfile::tempdir;
+
+/// A plugin that describes itself, or prints its arguments and exits with 3.
+fn write_plugin(dir: &Path, file_name: &str, description: &str, mode: u32) {
+    let path = dir.join(file_name);
+    fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = \"{DESCRIBE_FLAG}\" ]; then echo \"{description}\"; exit 0; fi\necho \"args: $*\"\nexit 3\n"
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+#[test]
+fn discover_in() {
+    let first = tempdir().unwrap();
+    let second = tempdir().unwrap();
+    write_plugin(first.path(), "frost-plugin-hello", "Say hello", 0o755);
+    write_plugin(first.path(), "frost-plugin-hidden", "Not executable", 0o644);
+    write_plugin(first.path(), "other-tool", "Not a plugin", 0o755);
+    write_plugin(second.path(), "frost-plugin-hello", "Shadowed", 0o755);
+    write_plugin(second.path(), "frost-plugin-export", "Export", 0o755);
+
+    let found = plugins::discover_in([first.path(), second.path(), Path::new("/nonexistent")]);
+    assert_eq!(
+        found
+            .iter()
+            .map(|plugin| plugin.name.as_str())
+            .collect::<Vec<_>>(),
+        ["export", "hello"]
+    );
+    assert_eq!(found[1].path, first.path().join("frost-plugin-hello"));
+    assert_eq!(found[1].description().as_deref(), Some("Say hello"));
+
+    let status = found[1].run(["a", "b"]).unwrap();
+    assert_eq!(status.code(), Some(3));
+}
+
+#[test]
+fn cli_runs_plugins() {
+    let dir = tempdir().unwrap();
+    write_plugin(dir.path(), "frost-plugin-hello", "Say hello", 0o755);
+    let frost = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_frost"))
+            .args(args)
+            .env("PATH", dir.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = frost(&["hello", "robot.bag", "--flag"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "args: robot.bag --flag\n"
+    );
+
+    let output = frost(&["plugins"]);
+    assert!(output.status.success());
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(listing.starts_with("hello  Say hello  ("), "{listing}");
+
+    // built-in commands take precedence, and unknown commands still fail
+    let output = frost(&["types", "tests/fixtures/decompressed.bag"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("std_msgs/String"));
+    let output = frost(&["goodbye"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
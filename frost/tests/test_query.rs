@@ -1,8 +1,13 @@
-use std::{fs::File, io::Write, path::PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{fs::File, io::Write, ops::Range, path::PathBuf, time::Duration};
 
+use frost::metrics::QueryStats;
 use frost::query::Query;
+use frost::time::Time;
+use frost::writer::Compression;
 
-use frost::{errors::ErrorKind, DecompressedBag};
+use frost::{errors::ErrorKind, BagMetadata, DecompressedBag};
 
 use tempfile::{tempdir, TempDir};
 
@@ -156,3 +161,544 @@ fn msg_reading_wrong_type() {
         )
     }
 }
+
+#[test]
+fn decode_errors_locate_the_message() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let query = Query::new().with_topics(["/chatter"]);
+    let msg_view = bag.read_messages(&query).unwrap().last().unwrap();
+    let err = msg_view.instantiate::<std_msgs::Time>().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Deserialization(_)));
+
+    let context = err.context().unwrap();
+    assert_eq!(context.topic, "/chatter");
+    assert_eq!(context.data_type, "std_msgs/String");
+    assert_eq!(context.time, msg_view.time);
+    // past the bag header
+    assert!(context.chunk_pos > 13);
+    assert!(err.to_string().ends_with(&format!(" (in the {context})")));
+    assert!(context
+        .to_string()
+        .starts_with("std_msgs/String message on /chatter at 99.0001, at byte "));
+}
+
+#[test]
+fn time_range_boundaries() {
+    // message i on each topic is received at secs=i, nsecs=1000 + i*1000
+    let time = |i: u32| Time {
+        secs: i,
+        nsecs: 1000 + i * 1000,
+    };
+
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let count = |query: Query| {
+            bag.read_messages(&query.with_topics(["/chatter"]))
+                .unwrap()
+                .count()
+        };
+
+        assert_eq!(
+            count(Query::new().with_time_range(time(0)..time(10))),
+            10,
+            "{name}"
+        );
+        assert_eq!(
+            count(Query::new().with_time_range(time(0)..=time(10))),
+            11,
+            "{name}"
+        );
+        assert_eq!(
+            count(Query::new().with_time_range(time(90)..)),
+            10,
+            "{name}"
+        );
+        assert_eq!(
+            count(Query::new().with_time_range(..time(10))),
+            10,
+            "{name}"
+        );
+        assert_eq!(
+            count(Query::new().with_time_range(time(5)..time(5))),
+            0,
+            "{name}"
+        );
+        assert_eq!(
+            count(Query::new().with_time_range(time(5)..=time(5))),
+            1,
+            "{name}"
+        );
+
+        // offsets are relative to the bag start, time(0)
+        let ten_secs = Duration::from_secs(10);
+        assert_eq!(
+            count(Query::new().with_time_range(..ten_secs)),
+            10,
+            "{name}"
+        );
+        let to_tenth = Duration::from(time(10)) - Duration::from(time(0));
+        assert_eq!(
+            count(Query::new().with_time_range(..to_tenth)),
+            10,
+            "{name}"
+        );
+        assert_eq!(
+            count(Query::new().with_time_range(..=to_tenth)),
+            11,
+            "{name}"
+        );
+        assert_eq!(
+            count(Query::new().with_time_range(to_tenth..)),
+            90,
+            "{name}"
+        );
+
+        // adjacent half-open windows partition the bag exactly
+        let total: usize = (0..10)
+            .map(|w| count(Query::new().with_time_range(time(w * 10)..time(w * 10 + 10))))
+            .sum();
+        assert_eq!(total, 100, "{name}");
+    }
+}
+
+#[test]
+fn start_end_time_boundaries() {
+    let time = |i: u32| Time {
+        secs: i,
+        nsecs: 1000 + i * 1000,
+    };
+
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let count = |query: Query| {
+            bag.read_messages(&query.with_topics(["/chatter"]))
+                .unwrap()
+                .count()
+        };
+
+        // both ends are inclusive
+        let query = Query::new().with_start_time(time(0)).with_end_time(time(9));
+        assert_eq!(count(query), 10, "{name}");
+        let query = Query::new().with_start_time(time(5)).with_end_time(time(5));
+        assert_eq!(count(query), 1, "{name}");
+
+        // so inclusive windows sharing a boundary double count it
+        let total: usize = (0..10)
+            .map(|w| {
+                let query = Query::new()
+                    .with_start_time(time(w * 10))
+                    .with_end_time(time(w * 10 + 10));
+                count(query)
+            })
+            .sum();
+        assert_eq!(total, 109, "{name}");
+
+        // while an exclusive end partitions the bag exactly
+        let total: usize = (0..10)
+            .map(|w| {
+                let query = Query::new()
+                    .with_start_time(time(w * 10))
+                    .with_end_exclusive(time(w * 10 + 10));
+                count(query)
+            })
+            .sum();
+        assert_eq!(total, 100, "{name}");
+    }
+}
+
+#[test]
+fn count_messages() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let metadata = BagMetadata::from_bytes(bytes).unwrap();
+
+        for query in [
+            Query::all(),
+            Query::new().with_topics(["/chatter"]),
+            Query::new().with_types(["std_msgs/Time", "std_msgs/String"]),
+            Query::new()
+                .with_topics(["/chatter"])
+                .with_types(["std_msgs/Time"]),
+            Query::new().with_time_range(..Duration::from_secs(10)),
+            Query::new().with_topics(["/missing"]),
+        ] {
+            let expected = bag.read_messages(&query).unwrap().count();
+            assert_eq!(bag.count_messages(&query), expected, "{name}");
+            assert_eq!(metadata.count_messages(&query), expected, "{name}");
+        }
+        assert_eq!(metadata.count_messages(&Query::all()), 300, "{name}");
+    }
+}
+
+#[test]
+fn estimate() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let metadata = BagMetadata::from_bytes(bytes).unwrap();
+        let total: u64 = metadata
+            .compression_info()
+            .iter()
+            .map(|info| info.total_compressed)
+            .sum();
+
+        let estimate = bag.estimate(&Query::all());
+        assert_eq!(estimate.message_count, 300, "{name}");
+        assert_eq!(estimate.chunk_count, metadata.chunk_count(), "{name}");
+        assert_eq!(estimate.compressed_bytes, total, "{name}");
+        assert!(
+            estimate.uncompressed_bytes >= estimate.compressed_bytes,
+            "{name}"
+        );
+
+        let estimate = bag.estimate(&Query::new().with_topics(["/missing"]));
+        assert_eq!(estimate.message_count, 0, "{name}");
+        assert_eq!(estimate.chunk_count, 0, "{name}");
+        assert_eq!(estimate.compressed_bytes, 0, "{name}");
+        assert_eq!(estimate.uncompressed_bytes, 0, "{name}");
+    }
+}
+
+#[test]
+fn bag_iter_after_exhaustion() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let mut iter = bag
+            .read_messages(&Query::new().with_topics(["/chatter"]))
+            .unwrap();
+        assert_eq!(iter.len(), 100, "{name}");
+        assert_eq!(iter.by_ref().count(), 100, "{name}");
+
+        assert_eq!(iter.len(), 0, "{name}");
+        assert_eq!(iter.size_hint(), (0, Some(0)), "{name}");
+        for _ in 0..3 {
+            assert!(iter.next().is_none(), "{name}");
+        }
+        assert!(iter.nth(5).is_none(), "{name}");
+        assert!(iter.fuse().next().is_none(), "{name}");
+
+        let mut iter = bag
+            .read_messages(&Query::new().with_topics(["/missing"]))
+            .unwrap();
+        assert!(iter.next().is_none(), "{name}");
+        assert!(iter.next().is_none(), "{name}");
+    }
+}
+
+#[test]
+fn merges_connections_in_time_order() {
+    // each topic written on its own, in small chunks, so the file isn't in time order
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::None)
+        .with_chunk_size(512);
+    for topic in ["/time", "/chatter", "/array"] {
+        writer
+            .copy_messages(&bag, &Query::new().with_topics([topic]))
+            .unwrap();
+    }
+    // and a message on each topic at the same time, after the rest, written in the same order
+    let tie = Time {
+        secs: 200,
+        nsecs: 0,
+    };
+    for topic in ["/time", "/chatter", "/array"] {
+        let msg_view = bag
+            .read_messages(&Query::new().with_topics([topic]))
+            .unwrap()
+            .next()
+            .unwrap();
+        let connection = bag
+            .metadata
+            .connection_data
+            .values()
+            .find(|data| data.topic == topic)
+            .unwrap();
+        let conn_id = writer.add_connection(connection);
+        writer
+            .write_raw(conn_id, tie, msg_view.raw_bytes().unwrap())
+            .unwrap();
+    }
+    let bag = DecompressedBag::from_bytes(&writer.finish().unwrap().into_inner()).unwrap();
+    assert!(bag.metadata.chunk_count() > 10);
+
+    let messages: Vec<(Time, String)> = bag
+        .read_messages(&Query::all())
+        .unwrap()
+        .map(|msg_view| (msg_view.time, msg_view.topic.to_string()))
+        .collect();
+    assert_eq!(messages.len(), 303);
+    assert!(messages.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    assert_eq!(
+        messages[300..]
+            .iter()
+            .map(|(_, topic)| topic.as_str())
+            .collect::<Vec<_>>(),
+        ["/time", "/chatter", "/array"]
+    );
+
+    let mut iter = bag.read_messages(&Query::all()).unwrap();
+    assert_eq!(iter.nth(150).unwrap().time, messages[150].0);
+    assert_eq!(iter.len(), 152);
+
+    // time ranges are cut from each connection's index
+    let start = messages[100].0;
+    let end = messages[200].0;
+    for query in [
+        Query::new().with_time_range(start..end),
+        Query::new().with_time_range(start..=end),
+        Query::new().with_time_range(..end),
+        Query::new().with_time_range(start..),
+    ] {
+        let times: Vec<Time> = bag
+            .read_messages(&query)
+            .unwrap()
+            .map(|msg_view| msg_view.time)
+            .collect();
+        assert_eq!(times.len(), bag.count_messages(&query));
+        assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
+
+#[test]
+fn query_stats() {
+    // each topic in its own chunks
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(512);
+    for topic in ["/time", "/chatter", "/array"] {
+        writer
+            .copy_messages(&bag, &Query::new().with_topics([topic]))
+            .unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+    let chunk_count = BagMetadata::from_bytes(&bytes).unwrap().chunk_count() as u64;
+
+    let stats = QueryStats::new();
+    let query = Query::new()
+        .with_topics(["/chatter"])
+        .with_stats(stats.clone());
+    let bag = DecompressedBag::from_storage_filtered(&bytes.as_slice(), &query).unwrap();
+    let loaded = stats.snapshot();
+    assert!(loaded.chunks_decompressed > 1);
+    assert_eq!(
+        loaded.chunks_pruned,
+        chunk_count - loaded.chunks_decompressed
+    );
+    assert!(loaded.decompressed_bytes > 0);
+    assert_eq!(loaded.chunks_read, 0);
+
+    let mut iter = bag.read_messages(&query).unwrap();
+    assert_eq!(iter.by_ref().take(10).count(), 10);
+    assert_eq!(iter.stats().unwrap().snapshot().messages_yielded, 10);
+    assert_eq!(iter.count(), 90);
+    let read = stats.snapshot();
+    assert_eq!(read.messages_yielded, 100);
+    assert_eq!(read.chunks_read, loaded.chunks_decompressed);
+    assert_eq!(read.chunk_cache_hits, 0);
+    assert!(read.elapsed >= loaded.elapsed);
+
+    // chunks loaded up front are cache hits
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    let stats = QueryStats::new();
+    let query = Query::new()
+        .with_topics(["/chatter"])
+        .with_stats(stats.clone());
+    assert_eq!(bag.read_messages(&query).unwrap().count(), 100);
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.chunks_decompressed, 0);
+    assert_eq!(snapshot.chunk_cache_hits, snapshot.chunks_read);
+    assert_eq!(snapshot.chunks_read, read.chunks_read);
+
+    assert!(bag.read_messages(&Query::all()).unwrap().stats().is_none());
+}
+
+#[test]
+fn bag_iter_adaptors() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        let query = Query::all();
+        let times: Vec<Time> = bag
+            .read_messages(&query)
+            .unwrap()
+            .map(|msg| msg.time)
+            .collect();
+        assert_eq!(times.len(), 300, "{name}");
+        let read = || bag.read_messages(&query).unwrap();
+
+        let taken: Vec<Time> = read().take(10).map(|msg| msg.time).collect();
+        assert_eq!(taken, times[..10], "{name}");
+        assert_eq!(read().take(10).len(), 10, "{name}");
+        assert_eq!(read().take(1000).count(), 300, "{name}");
+
+        let skipped: Vec<Time> = read().skip(290).map(|msg| msg.time).collect();
+        assert_eq!(skipped, times[290..], "{name}");
+        assert_eq!(read().skip(290).len(), 10, "{name}");
+        assert_eq!(read().skip(1000).count(), 0, "{name}");
+
+        let mut iter = read();
+        assert_eq!(iter.nth(1).unwrap().time, times[1], "{name}");
+        assert_eq!(iter.nth(8).unwrap().time, times[10], "{name}");
+        assert_eq!(iter.len(), 289, "{name}");
+
+        let stepped: Vec<Time> = read().step_by(100).map(|msg| msg.time).collect();
+        assert_eq!(stepped, [times[0], times[100], times[200]], "{name}");
+
+        // zip stops at the shorter side, and pairs messages in lockstep
+        let chatter = Query::new().with_topics(["/chatter"]);
+        let array = Query::new().with_topics(["/array"]);
+        let zipped = bag
+            .read_messages(&chatter)
+            .unwrap()
+            .zip(bag.read_messages(&array).unwrap().take(50));
+        assert_eq!(zipped.len(), 50, "{name}");
+        for (chatter, array) in zipped {
+            assert_eq!(chatter.topic, "/chatter", "{name}");
+            assert_eq!(array.topic, "/array", "{name}");
+        }
+
+        let with_self: Vec<(Time, Time)> = read()
+            .zip(read().skip(1))
+            .map(|(a, b)| (a.time, b.time))
+            .collect();
+        assert_eq!(with_self.len(), 299, "{name}");
+        assert!(with_self.iter().all(|(a, b)| a <= b), "{name}");
+
+        let chained: Vec<Time> = read()
+            .chain(read())
+            .skip(295)
+            .take(10)
+            .map(|msg| msg.time)
+            .collect();
+        assert_eq!(chained[..5], times[295..], "{name}");
+        assert_eq!(chained[5..], times[..5], "{name}");
+
+        assert_eq!(
+            read().last().map(|msg| msg.time),
+            times.last().copied(),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn byte_ranges() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(1024);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+    let metadata = BagMetadata::from_bytes(&bytes).unwrap();
+    assert!(metadata.chunk_count() > 4);
+
+    let total = |ranges: &[Range<u64>]| ranges.iter().map(|r| r.end - r.start).sum::<u64>();
+    let all = metadata.byte_ranges(&Query::all());
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0], 0..bytes.len() as u64);
+
+    // every chunk's data is left out, but the metadata can still be parsed from just the planned ranges
+    let none = metadata.byte_ranges(&Query::new().with_topics(["/missing"]));
+    assert_eq!(none.len(), metadata.chunk_count() + 1);
+    assert!(none.windows(2).all(|pair| pair[0].end < pair[1].start));
+    let mut sparse = vec![0; bytes.len()];
+    for range in none.iter() {
+        let range = range.start as usize..range.end as usize;
+        sparse[range.clone()].copy_from_slice(&bytes[range]);
+    }
+    let sparse = BagMetadata::from_bytes(&sparse).unwrap();
+    assert_eq!(
+        sparse.topic_message_counts(),
+        metadata.topic_message_counts()
+    );
+
+    let query = Query::new()
+        .with_topics(["/chatter"])
+        .with_end_time(Time { secs: 10, nsecs: 0 });
+    let ranges = metadata.byte_ranges(&query);
+    let estimate = metadata.estimate(&query);
+    assert!(estimate.chunk_count > 0 && estimate.chunk_count < metadata.chunk_count());
+    assert_eq!(total(&ranges), total(&none) + estimate.compressed_bytes);
+}
+
+#[test]
+fn predicates() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+
+    let even = |msg_view: &frost::msgs::MessageView| msg_view.time.secs % 2 == 0;
+    let query = Query::new()
+        .with_topics(["/chatter", "/time"])
+        .with_predicate(even)
+        .with_field("data", |value| value.as_str().is_some());
+    let messages: Vec<_> = bag.filter_messages(&query).unwrap().collect();
+    assert_eq!(messages.len(), 50);
+    assert!(messages
+        .iter()
+        .all(|msg_view| msg_view.topic == "/chatter" && msg_view.time.secs % 2 == 0));
+    let msg = messages[1].instantiate::<std_msgs::String>().unwrap();
+    assert_eq!(msg.data, "foo_2");
+
+    // predicates are checked as messages are read, so the iterator only knows an upper bound
+    let mut iter = bag.filter_messages(&query).unwrap();
+    assert_eq!(iter.size_hint(), (0, Some(200)));
+    assert_eq!(iter.nth(48).unwrap().time.secs, 96);
+    assert_eq!(iter.count(), 1);
+    assert!(bag.read_messages(&query).is_err());
+
+    // and only as far as the iterator is advanced
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let counting =
+        Query::new().with_predicate(move |_| counter.fetch_add(1, Ordering::SeqCst) >= 2);
+    assert!(bag.filter_messages(&counting).unwrap().next().is_some());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    assert_eq!(bag.count_messages(&query), 50);
+    assert_eq!(bag.metadata.count_messages(&query), 200);
+    assert!(query.has_predicates());
+    assert!(!Query::all().has_predicates());
+
+    // missing fields don't match
+    let query = Query::new().with_field("layout.dim", |_| true);
+    assert!(bag
+        .filter_messages(&query)
+        .unwrap()
+        .all(|msg_view| msg_view.topic == "/array"));
+
+    // and rewrites are filtered too
+    let mut writer = common::writer();
+    let query = Query::new().with_predicate(even);
+    assert_eq!(writer.copy_messages(&bag, &query).unwrap(), 150);
+    let out = writer.finish().unwrap().into_inner();
+    assert_eq!(BagMetadata::from_bytes(&out).unwrap().message_count(), 150);
+}
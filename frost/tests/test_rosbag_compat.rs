@@ -0,0 +1,262 @@
+//! Cross-checks frost against the rosbag tools on the shared fixtures, pinning down the compatibility users migrating
+//! from rosbag rely on: the same counts and metadata as `rosbag info` (and its exact output from
+//! `frost info --compat rosbag`), the same messages as `rosbag filter` down to their serialized bytes, and bags
+//! written by frost that rosbag reads back unchanged.
+//!
+//! These need ROS's `rosbag` command and Python package, so they are ignored by default. To run them:
+//! ```bash
+//! source ./scripts/setup_py.sh && setup_venv
+//! cargo test -p frost --test test_rosbag_compat -- --ignored
+//! ```
+//! `ROSBAG` and `PYTHON` override the commands used.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use frost::query::Query;
+use frost::{BagMetadata, DecompressedBag};
+use sha2::{Digest, Sha256};
+
+mod common;
+
+const FIXTURES: [&str; 2] = [
+    "./tests/fixtures/decompressed.bag",
+    "./tests/fixtures/compressed_lz4.bag",
+];
+
+/// Runs a command, returning its stdout, or panics explaining what is missing.
+fn run(program: &str, args: &[&str]) -> String {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .unwrap_or_else(|err| {
+            panic!("failed to run {program}, these tests need the rosbag tools: {err}")
+        });
+    assert!(
+        output.status.success(),
+        "{program} {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn rosbag(args: &[&str]) -> String {
+    run(
+        &env::var("ROSBAG").unwrap_or_else(|_| "rosbag".into()),
+        args,
+    )
+}
+
+/// `(topic, receive time in ns, sha256 of the data)` of each message.
+type Messages = Vec<(String, u64, String)>;
+
+/// The messages of a bag as rosbag reads them, in its order.
+fn rosbag_messages(path: &Path) -> Messages {
+    let python = env::var("PYTHON").unwrap_or_else(|_| "python3".into());
+    let script = "./tests/scripts/dump_messages.py";
+    run(&python, &[script, path.to_str().unwrap()])
+        .lines()
+        .map(|line| {
+            let message: serde_json::Value = serde_json::from_str(line).unwrap();
+            (
+                message["topic"].as_str().unwrap().to_string(),
+                message["time"].as_u64().unwrap(),
+                message["sha256"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn frost_messages(bag: &DecompressedBag, query: &Query) -> Messages {
+    bag.read_messages(query)
+        .unwrap()
+        .map(|msg_view| {
+            let time =
+                u64::from(msg_view.time.secs) * 1_000_000_000 + u64::from(msg_view.time.nsecs);
+            let data = &msg_view.raw_bytes().unwrap()[4..];
+            (
+                msg_view.topic.to_string(),
+                time,
+                format!("{:x}", Sha256::digest(data)),
+            )
+        })
+        .collect()
+}
+
+/// Both read messages in time order, but may break ties differently.
+fn assert_same_messages(mut frost: Messages, mut rosbag: Messages, context: &str) {
+    assert!(
+        frost.windows(2).all(|pair| pair[0].1 <= pair[1].1),
+        "{context}: frost is out of order"
+    );
+    frost.sort();
+    rosbag.sort();
+    assert_eq!(frost, rosbag, "{context}");
+}
+
+#[test]
+#[ignore = "needs rosbag, run with --ignored"]
+fn info_matches_rosbag() {
+    for path in FIXTURES {
+        let info: serde_yaml::Value =
+            serde_yaml::from_str(&rosbag(&["info", "--yaml", path])).unwrap();
+        let metadata = BagMetadata::from_file(path).unwrap();
+
+        assert_eq!(
+            info["messages"].as_u64(),
+            Some(metadata.message_count() as u64),
+            "{path}"
+        );
+        assert_eq!(
+            info["size"].as_u64(),
+            Some(std::fs::metadata(path).unwrap().len()),
+            "{path}"
+        );
+        let start = f64::from(metadata.start_time().unwrap());
+        let end = f64::from(metadata.end_time().unwrap());
+        assert!(
+            (info["start"].as_f64().unwrap() - start).abs() < 1e-3,
+            "{path}"
+        );
+        assert!((info["end"].as_f64().unwrap() - end).abs() < 1e-3, "{path}");
+
+        let compressions: Vec<String> = metadata
+            .compression_info()
+            .iter()
+            .map(|info| info.name.clone())
+            .collect();
+        assert_eq!(
+            info["compression"].as_str().map(str::to_string),
+            compressions.first().cloned(),
+            "{path}"
+        );
+
+        let rosbag_types: BTreeMap<String, String> = info["types"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|ty| {
+                (
+                    ty["type"].as_str().unwrap().to_string(),
+                    ty["md5"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        let frost_types: BTreeMap<String, String> = metadata
+            .connection_data
+            .values()
+            .map(|connection| (connection.data_type.clone(), connection.md5sum.clone()))
+            .collect();
+        assert_eq!(frost_types, rosbag_types, "{path}");
+
+        let rosbag_topics: BTreeMap<String, usize> = info["topics"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|topic| {
+                (
+                    topic["topic"].as_str().unwrap().to_string(),
+                    topic["messages"].as_u64().unwrap() as usize,
+                )
+            })
+            .collect();
+        assert_eq!(metadata.topic_message_counts(), rosbag_topics, "{path}");
+    }
+}
+
+#[test]
+#[ignore = "needs rosbag, run with --ignored"]
+fn compat_info_is_rosbag_info() {
+    for path in FIXTURES {
+        let frost = env!("CARGO_BIN_EXE_frost");
+        assert_eq!(
+            run(frost, &["info", "--compat", "rosbag", path]),
+            rosbag(&["info", path]),
+            "{path}"
+        );
+        assert_eq!(
+            run(frost, &["info", "--yaml", path]),
+            rosbag(&["info", "--yaml", path]),
+            "{path}"
+        );
+        for key in ["duration", "start", "messages", "topics[0].type", "types"] {
+            assert_eq!(
+                run(frost, &["info", "-y", "-k", key, path]),
+                rosbag(&["info", "-y", "-k", key, path]),
+                "{path} {key}"
+            );
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs rosbag, run with --ignored"]
+fn messages_match_rosbag() {
+    for path in FIXTURES {
+        let bag = DecompressedBag::from_file(path).unwrap();
+        assert_same_messages(
+            frost_messages(&bag, &Query::all()),
+            rosbag_messages(Path::new(path)),
+            path,
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs rosbag, run with --ignored"]
+fn query_matches_rosbag_filter() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in FIXTURES {
+        let filtered = dir.path().join("filtered.bag");
+        rosbag(&[
+            "filter",
+            path,
+            filtered.to_str().unwrap(),
+            "topic == '/chatter' and t.to_sec() >= 10",
+        ]);
+
+        let bag = DecompressedBag::from_file(path).unwrap();
+        let query = Query::new()
+            .with_topics(["/chatter"])
+            .with_start_time(frost::time::Time { secs: 10, nsecs: 0 });
+        let expected = frost_messages(&bag, &query);
+        assert!(!expected.is_empty());
+
+        // frost reads rosbag's output, and matches it with a query of its own
+        let filtered_bag = DecompressedBag::from_file(&filtered).unwrap();
+        assert_same_messages(
+            frost_messages(&filtered_bag, &Query::all()),
+            rosbag_messages(&filtered),
+            path,
+        );
+        assert_same_messages(expected, rosbag_messages(&filtered), path);
+    }
+}
+
+#[test]
+#[ignore = "needs rosbag, run with --ignored"]
+fn rosbag_reads_frost_output() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in FIXTURES {
+        let bag = DecompressedBag::from_file(path).unwrap();
+        let mut writer = common::writer();
+        writer.copy_messages(&bag, &Query::all()).unwrap();
+        let written = dir.path().join("written.bag");
+        std::fs::write(&written, writer.finish().unwrap().into_inner()).unwrap();
+
+        let info: serde_yaml::Value =
+            serde_yaml::from_str(&rosbag(&["info", "--yaml", written.to_str().unwrap()])).unwrap();
+        assert_eq!(
+            info["messages"].as_u64(),
+            Some(bag.metadata.message_count() as u64),
+            "{path}"
+        );
+        assert_same_messages(
+            frost_messages(&bag, &Query::all()),
+            rosbag_messages(&written),
+            path,
+        );
+    }
+}
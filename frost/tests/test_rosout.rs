@@ -0,0 +1,133 @@
+use frost::dynamic::Value;
+use frost::errors::DecodeError;
+use frost::query::Query;
+use frost::rosout::{LogEntry, LogLevel};
+use frost::time::Time;
+use frost::{ConnectionData, DecompressedBag};
+
+mod common;
+use common::string;
+
+const LOG_DEFINITION: &str = "\
+byte DEBUG=1
+byte INFO=2
+byte WARN=4
+byte ERROR=8
+byte FATAL=16
+Header header
+byte level
+string name
+string msg
+string file
+string function
+uint32 line
+string[] topics
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+";
+
+fn serialize_log(stamp: Time, level: u8, node: &str, msg: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0u32.to_le_bytes()); // seq
+    data.extend_from_slice(&stamp.secs.to_le_bytes());
+    data.extend_from_slice(&stamp.nsecs.to_le_bytes());
+    data.extend(string(""));
+    data.push(level);
+    data.extend(string(node));
+    data.extend(string(msg));
+    data.extend(string("driver.cpp"));
+    data.extend(string("spin"));
+    data.extend_from_slice(&42u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // topics
+    data
+}
+
+fn log_connection(topic: &str) -> ConnectionData {
+    ConnectionData {
+        md5sum: "acffd30cd6b6de30f120938c17c593fb".to_string(),
+        ..common::connection(topic, "rosgraph_msgs/Log", LOG_DEFINITION)
+    }
+}
+
+#[test]
+fn logs_from_bag() {
+    let mut writer = common::writer();
+    let rosout = writer.add_connection(&log_connection("/rosout"));
+    let rosout_agg = writer.add_connection(&log_connection("/rosout_agg"));
+
+    let stamp = |secs| Time { secs, nsecs: 5000 };
+    let received = Time { secs: 10, nsecs: 0 };
+    // /rosout repeats on /rosout_agg, and stamps are out of receive order
+    for conn_id in [rosout, rosout_agg] {
+        for (secs, level, msg) in [
+            (3, 8, "lost connection"),
+            (1, 2, "started"),
+            (2, 4, "slow loop"),
+        ] {
+            writer
+                .write_raw(
+                    conn_id,
+                    received,
+                    &serialize_log(stamp(secs), level, "/lidar", msg),
+                )
+                .unwrap();
+        }
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+
+    let all = bag.logs(&Query::all(), LogLevel::Debug).unwrap();
+    assert_eq!(
+        all.iter()
+            .map(|entry| entry.message.as_str())
+            .collect::<Vec<_>>(),
+        ["started", "slow loop", "lost connection"]
+    );
+    assert_eq!(all[0].time, stamp(1));
+    assert_eq!(all[0].file, "driver.cpp");
+    assert_eq!(all[0].function, "spin");
+    assert_eq!(all[0].line, 42);
+
+    let warnings = bag.logs(&Query::all(), LogLevel::Warn).unwrap();
+    assert_eq!(
+        warnings.iter().map(|entry| entry.level).collect::<Vec<_>>(),
+        [LogLevel::Warn, LogLevel::Error]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "[ WARN] [2.000005] [/lidar]: slow loop"
+    );
+
+    let json = serde_json::to_value(&warnings[1]).unwrap();
+    assert_eq!(json["level"], "error");
+    assert_eq!(json["node"], "/lidar");
+    assert_eq!(json["time"], 3.000005);
+
+    // a single topic has no repeats to drop
+    let query = Query::new().with_topics(["/rosout_agg"]);
+    assert_eq!(bag.logs(&query, LogLevel::Debug).unwrap().len(), 3);
+}
+
+#[test]
+fn levels() {
+    assert_eq!("warn".parse(), Ok(LogLevel::Warn));
+    assert_eq!("ERROR".parse(), Ok(LogLevel::Error));
+    assert!("verbose".parse::<LogLevel>().is_err());
+    assert!(LogLevel::Debug < LogLevel::Fatal);
+    assert_eq!(LogLevel::from_byte(16), Some(LogLevel::Fatal));
+    assert_eq!(LogLevel::from_byte(3), None);
+}
+
+#[test]
+fn invalid_logs() {
+    let received = Time { secs: 1, nsecs: 0 };
+    let err = LogEntry::from_value(&Value::Message(vec![]), received).unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+
+    let bad_level = Value::Message(vec![("level".to_string(), Value::I8(3))]);
+    let err = LogEntry::from_value(&bad_level, received).unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(e) if e.contains("level 3")));
+}
@@ -0,0 +1,147 @@
+use std::f32::consts::FRAC_PI_2;
+
+use frost::dynamic::Value;
+use frost::errors::DecodeError;
+use frost::query::Query;
+use frost::scan::{LaserScan, ScanFilter};
+use frost::time::Time;
+use frost::{ConnectionData, DecompressedBag};
+
+mod common;
+
+const LASER_SCAN_DEFINITION: &str = "\
+Header header
+float32 angle_min
+float32 angle_max
+float32 angle_increment
+float32 time_increment
+float32 scan_time
+float32 range_min
+float32 range_max
+float32[] ranges
+float32[] intensities
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+";
+
+/// Five returns a quarter turn apart, from straight back on the right to straight back on the left.
+fn scan() -> LaserScan {
+    LaserScan {
+        angle_min: -2.0 * FRAC_PI_2,
+        angle_max: 2.0 * FRAC_PI_2,
+        angle_increment: FRAC_PI_2,
+        range_min: 0.5,
+        range_max: 20.0,
+        ranges: vec![1.0, 2.0, 3.0, f32::NAN, 0.1],
+        intensities: vec![10.0, 20.0, 30.0, 40.0, 50.0],
+    }
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+}
+
+#[test]
+fn points_in_scan_frame() {
+    let points = scan().points(&ScanFilter::new());
+
+    // the NaN return and the one closer than range_min are dropped
+    assert_eq!(points.len(), 3);
+    assert_close(points[0].x, -1.0);
+    assert_close(points[0].y, 0.0);
+    assert_close(points[1].x, 0.0);
+    assert_close(points[1].y, -2.0);
+    assert_close(points[2].x, 3.0);
+    assert_close(points[2].y, 0.0);
+    assert_eq!(points[2].range, 3.0);
+    assert_close(points[2].angle, 0.0);
+    assert_eq!(points[1].intensity, Some(20.0));
+
+    let mut without_intensities = scan();
+    without_intensities.intensities.clear();
+    assert!(without_intensities
+        .points(&ScanFilter::default())
+        .iter()
+        .all(|point| point.intensity.is_none()));
+}
+
+#[test]
+fn filters() {
+    let ahead = scan().points(&ScanFilter::new().with_angles(-1.0, 1.0));
+    assert_eq!(ahead.len(), 1);
+    assert_eq!(ahead[0].range, 3.0);
+
+    let near = scan().points(&ScanFilter::new().with_ranges(0.0, 2.5));
+    assert_eq!(
+        near.iter().map(|point| point.range).collect::<Vec<_>>(),
+        [1.0, 2.0]
+    );
+
+    // the filter can't widen the scan's own limits
+    let wide = scan().points(&ScanFilter::new().with_ranges(0.0, 100.0));
+    assert_eq!(wide.len(), 3);
+}
+
+#[test]
+fn from_value() {
+    let value = Value::Message(vec![
+        ("angle_min".to_string(), Value::F32(-1.0)),
+        ("angle_max".to_string(), Value::F32(1.0)),
+        ("angle_increment".to_string(), Value::F32(1.0)),
+        ("range_min".to_string(), Value::F32(0.0)),
+        ("range_max".to_string(), Value::F32(5.0)),
+        (
+            "ranges".to_string(),
+            Value::Array(vec![Value::F32(1.0), Value::F32(2.0), Value::F32(3.0)]),
+        ),
+        ("intensities".to_string(), Value::Array(vec![])),
+    ]);
+    let scan = LaserScan::from_value(&value).unwrap();
+    assert_eq!(scan.ranges, [1.0, 2.0, 3.0]);
+    assert!(scan.intensities.is_empty());
+    assert_eq!(scan.points(&ScanFilter::new()).len(), 3);
+
+    let err = LaserScan::from_value(&Value::Message(vec![])).unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+}
+
+#[test]
+fn from_bag() {
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&ConnectionData {
+        md5sum: "90c7ef2dc6895d81024acba2ac42f369".to_string(),
+        ..common::connection("/scan", "sensor_msgs/LaserScan", LASER_SCAN_DEFINITION)
+    });
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&0u32.to_le_bytes()); // seq
+    data.extend_from_slice(&[0; 8]); // stamp
+    data.extend_from_slice(&5u32.to_le_bytes());
+    data.extend_from_slice(b"laser");
+    for value in [-FRAC_PI_2, FRAC_PI_2, FRAC_PI_2, 0.0, 0.1, 0.2, 30.0] {
+        data.extend_from_slice(&f32::to_le_bytes(value));
+    }
+    for values in [&[1.0f32, 2.0, 4.0][..], &[][..]] {
+        data.extend_from_slice(&(values.len() as u32).to_le_bytes());
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    writer
+        .write_raw(conn_id, Time { secs: 1, nsecs: 0 }, &data)
+        .unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    let msg = bag.read_messages(&Query::all()).unwrap().next().unwrap();
+    let points = LaserScan::from_value(&msg.decode().unwrap())
+        .unwrap()
+        .points(&ScanFilter::new());
+    assert_eq!(points.len(), 3);
+    assert_close(points[0].y, -1.0);
+    assert_close(points[1].x, 2.0);
+    assert_close(points[2].y, 4.0);
+}
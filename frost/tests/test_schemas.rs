@@ -0,0 +1,126 @@
+#[cfg(feature = "md5")]
+use std::fs;
+#[cfg(feature = "md5")]
+use std::path::Path;
+
+use frost::schemas::{CacheStats, SchemaCache};
+
+#[cfg(feature = "md5")]
+fn write_msg(root: &Path, data_type: &str, text: &str) {
+    let (package, name) = data_type.split_once('/').unwrap();
+    let dir = root.join(package).join("msg");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(format!("{name}.msg")), text).unwrap();
+}
+
+#[test]
+#[cfg(feature = "md5")]
+fn prewarm_from_msg_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    write_msg(
+        dir.path(),
+        "std_msgs/Header",
+        "# Standard metadata for higher-level stamped data types.\n\
+         uint32 seq\n\
+         #Two-integer timestamp that is expressed as:\n\
+         time stamp\n\
+         #Frame this data is associated with\n\
+         string frame_id\n",
+    );
+    write_msg(dir.path(), "std_msgs/String", "string data\n");
+    write_msg(
+        dir.path(),
+        "geometry_msgs/Point",
+        "# This contains the position of a point in free space\nfloat64 x\nfloat64 y\nfloat64 z\n",
+    );
+    write_msg(
+        dir.path(),
+        "geometry_msgs/PointStamped",
+        "# This represents a Point with reference coordinate frame and timestamp\n\
+         Header header\n\
+         Point point\n",
+    );
+    write_msg(
+        dir.path(),
+        "nav_msgs/Path",
+        "Header header\ngeometry_msgs/PoseStamped[] poses\n",
+    );
+    // not in a msg directory
+    fs::write(dir.path().join("Stray.msg"), "int32 data\n").unwrap();
+
+    let cache = SchemaCache::new();
+    assert_eq!(cache.prewarm_dir(dir.path()).unwrap(), 4);
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            entries: 4,
+            hits: 0,
+            misses: 0
+        }
+    );
+
+    // the definitions are never parsed, the md5sums computed from the files match those of ROS
+    for (data_type, md5sum) in [
+        ("std_msgs/Header", "2176decaecbce78abc3b96ef049fabed"),
+        ("std_msgs/String", "992ce8a1687cec8c8bd883ec73ca41d1"),
+        ("geometry_msgs/Point", "4a842b65f413084dc2b10fb484ea7f17"),
+        (
+            "geometry_msgs/PointStamped",
+            "c63aecb41bfdfd6b7e1fac37c7cbe7bf",
+        ),
+    ] {
+        let schema = cache.get(data_type, md5sum, "not a definition").unwrap();
+        assert_eq!(schema.data_type(), data_type);
+    }
+    assert_eq!(cache.stats().hits, 4);
+
+    let schema = cache
+        .get(
+            "geometry_msgs/PointStamped",
+            "c63aecb41bfdfd6b7e1fac37c7cbe7bf",
+            "",
+        )
+        .unwrap();
+    assert_eq!(
+        schema.definition("std_msgs/Header").unwrap().fields.len(),
+        3
+    );
+    assert_eq!(
+        schema
+            .definition("geometry_msgs/Point")
+            .unwrap()
+            .fields
+            .len(),
+        3
+    );
+}
+
+#[test]
+fn cache_hits() {
+    let cache = SchemaCache::new();
+    for _ in 0..3 {
+        cache
+            .get(
+                "std_msgs/String",
+                "992ce8a1687cec8c8bd883ec73ca41d1",
+                "string data\n",
+            )
+            .unwrap();
+    }
+    // connections written without an md5sum could have any definition
+    for _ in 0..2 {
+        cache.get("std_msgs/String", "*", "string data\n").unwrap();
+    }
+    assert!(cache.get("std_msgs/Bad", "0", "Missing field").is_err());
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            entries: 1,
+            hits: 2,
+            misses: 4
+        }
+    );
+
+    cache.clear();
+    assert_eq!(cache.stats(), CacheStats::default());
+}
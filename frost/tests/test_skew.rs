@@ -0,0 +1,92 @@
+use frost::skew::estimate_skew;
+use frost::time::Time;
+use frost::DecompressedBag;
+
+mod common;
+use common::{string, string_connection};
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+
+fn time(secs: f64) -> Time {
+    Time {
+        secs: secs as u32,
+        nsecs: ((secs.fract() * 1e9).round()) as u32,
+    }
+}
+
+/// Writes string messages given as `(topic, received, text)`.
+fn write_bag(messages: &[(&str, f64, String)]) -> DecompressedBag {
+    let mut writer = common::writer();
+    for (topic, received, text) in messages {
+        let conn_id = writer.add_connection(&string_connection(topic));
+        writer
+            .write_raw(conn_id, time(*received), &string(text))
+            .unwrap();
+    }
+    DecompressedBag::from_bytes(&writer.finish().unwrap().into_inner()).unwrap()
+}
+
+/// The other machine's clock is 100s ahead and the network adds 10ms.
+/// `/tf` is published on the reference machine, and `/cmd` on the other one when `both_ways`.
+fn recordings(both_ways: bool) -> (DecompressedBag, DecompressedBag) {
+    let (skew, latency) = (100.0, 0.01);
+    let mut reference = Vec::new();
+    let mut other = Vec::new();
+    for i in 0..20 {
+        let sent = 1000.0 + i as f64;
+        let jitter = if i % 2 == 0 { 0.001 } else { -0.001 };
+        reference.push(("/tf", sent, format!("tf_{i}")));
+        other.push(("/tf", sent + skew + latency + jitter, format!("tf_{i}")));
+        if both_ways {
+            other.push(("/cmd", sent + skew + 0.5, format!("cmd_{i}")));
+            reference.push(("/cmd", sent + 0.5 + latency + jitter, format!("cmd_{i}")));
+        }
+        // identical data repeats, so it can't be matched
+        reference.push(("/status", sent, "ok".to_string()));
+        other.push(("/status", sent + skew + latency, "ok".to_string()));
+    }
+    reference.sort_by(|a, b| a.1.total_cmp(&b.1));
+    other.sort_by(|a, b| a.1.total_cmp(&b.1));
+    (write_bag(&reference), write_bag(&other))
+}
+
+#[test]
+fn latency_cancels_both_ways() {
+    let (reference, other) = recordings(true);
+    let estimate = estimate_skew(&reference, &other).unwrap();
+    assert_eq!(
+        estimate
+            .topics
+            .iter()
+            .map(|topic| (topic.topic.as_str(), topic.matches))
+            .collect::<Vec<_>>(),
+        [("/cmd", 20), ("/tf", 20)]
+    );
+    assert!((estimate.topics[0].offset + 99.99).abs() < 1e-5);
+    assert!((estimate.topics[1].offset + 100.01).abs() < 1e-5);
+    assert!((estimate.topics[1].spread - 0.001).abs() < 1e-5);
+    assert!(estimate.latency_cancelled);
+    assert!((estimate.offset.unwrap() + 100.0).abs() < 1e-5);
+}
+
+#[test]
+fn one_way_includes_latency() {
+    let (reference, other) = recordings(false);
+    let estimate = estimate_skew(&reference, &other).unwrap();
+    assert_eq!(estimate.topics.len(), 1);
+    assert!(!estimate.latency_cancelled);
+    assert!((estimate.offset.unwrap() + 100.01).abs() < 1e-5);
+}
+
+#[test]
+fn unrelated_bags() {
+    let (reference, _) = recordings(true);
+    let fixture = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let estimate = estimate_skew(&reference, &fixture).unwrap();
+    assert!(estimate.topics.is_empty());
+    assert_eq!(estimate.offset, None);
+
+    // a bag against itself has no offset
+    let estimate = estimate_skew(&fixture, &fixture).unwrap();
+    assert_eq!(estimate.offset, Some(0.0));
+}
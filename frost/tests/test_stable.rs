@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use frost::stable::{self, v1, Deprecation, DEPRECATIONS};
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn version(s: &str) -> (u64, u64, u64) {
+    let parts: Vec<u64> = s
+        .split(['.', '-'])
+        .take(3)
+        .map(|part| part.parse().unwrap())
+        .collect();
+    (parts[0], parts[1], parts[2])
+}
+
+/// The first release allowed to break compatibility with `v`.
+fn next_breaking(v: (u64, u64, u64)) -> (u64, u64, u64) {
+    match v {
+        (0, minor, _) => (0, minor + 1, 0),
+        (major, _, _) => (major + 1, 0, 0),
+    }
+}
+
+#[test]
+fn deprecations_follow_the_policy() {
+    let current = version(env!("CARGO_PKG_VERSION"));
+    for deprecation in DEPRECATIONS {
+        let (since, removal) = (version(deprecation.since), version(deprecation.removal));
+        assert!(
+            since <= current,
+            "{} is deprecated since {}, a later release than this one",
+            deprecation.item,
+            deprecation.since
+        );
+        assert!(
+            removal >= next_breaking(since),
+            "{} may only be removed in a breaking release",
+            deprecation.item
+        );
+        assert!(
+            current < removal,
+            "{} is due for removal in {}",
+            deprecation.item,
+            deprecation.removal
+        );
+        assert!(
+            deprecation.item.starts_with("frost::")
+                && deprecation.replacement.starts_with("frost::")
+        );
+    }
+}
+
+/// Fails to compile if a signature in v1 changes.
+#[test]
+fn v1_signatures() {
+    let _: fn(PathBuf) -> Result<v1::BagMetadata, v1::Error> = v1::BagMetadata::from_file;
+    let _: fn(&[u8]) -> Result<v1::BagMetadata, v1::Error> = v1::BagMetadata::from_bytes;
+    let _: fn(PathBuf) -> Result<v1::DecompressedBag, v1::Error> = v1::DecompressedBag::from_file;
+    let _: fn(&[u8]) -> Result<v1::DecompressedBag, v1::Error> = v1::DecompressedBag::from_bytes;
+    let _: for<'a> fn(&'a v1::DecompressedBag, &v1::Query) -> Result<v1::BagIter<'a>, v1::Error> =
+        v1::DecompressedBag::read_messages;
+    let _: fn(&v1::BagMetadata) -> Vec<&str> = v1::BagMetadata::topics;
+    let _: fn(&v1::BagMetadata) -> usize = v1::BagMetadata::message_count;
+    let _: fn() -> v1::Query = v1::Query::all;
+    let _: fn(v1::Query, v1::Time) -> v1::Query = v1::Query::with_start_time;
+    let _: fn(v1::Query, v1::Time) -> v1::Query = v1::Query::with_end_time;
+    let _: fn(v1::Query, Vec<&'static str>) -> v1::Query = v1::Query::with_topics;
+    let _: fn(v1::Query, Vec<&'static str>) -> v1::Query = v1::Query::with_types;
+    let _: for<'a, 'b> fn(&'b v1::MessageView<'a>) -> Result<frost::dynamic::Value, v1::Error> =
+        |msg_view| msg_view.decode();
+    let _: fn(&v1::Time, &v1::Time) -> Duration = v1::Time::duration_since;
+    let _: fn(&[u8]) -> Result<v1::Time, frost::errors::ParseError> = v1::Time::from_le_bytes;
+    let _: fn() -> v1::Capabilities = v1::capabilities;
+    let _: fn(&v1::Error) -> &v1::ErrorKind = v1::Error::kind;
+    assert_eq!(stable::VERSION, 1);
+
+    let bag = v1::DecompressedBag::from_file(FILE_PATH).unwrap();
+    let count = bag
+        .read_messages(&v1::Query::new().with_topics(["/chatter"]))
+        .unwrap()
+        .count();
+    assert_eq!(count, 100);
+}
+
+#[test]
+#[allow(deprecated)]
+fn shims_forward_and_report_their_use() {
+    fn record(deprecation: &Deprecation) {
+        assert!(DEPRECATIONS.contains(deprecation));
+    }
+    stable::set_deprecation_handler(record);
+
+    let bytes = [1, 0, 0, 0, 2, 0, 0, 0];
+    let time = v1::Time::from(&bytes).unwrap();
+    assert_eq!(time, v1::Time::from_le_bytes(&bytes).unwrap());
+    assert_eq!((time.secs, time.nsecs), (1, 2));
+
+    let later = v1::Time { secs: 3, nsecs: 2 };
+    assert_eq!(later.dur(&time), Duration::from_secs(2));
+    assert_eq!(time.duration_since(&later), Duration::ZERO);
+
+    let used: Vec<_> = stable::deprecations_used()
+        .iter()
+        .map(|deprecation| deprecation.item)
+        .collect();
+    assert_eq!(used, ["frost::time::Time::from", "frost::time::Time::dur"]);
+    assert!(stable::deprecations_used()[0]
+        .to_string()
+        .contains("use frost::time::Time::from_le_bytes instead"));
+}
@@ -0,0 +1,97 @@
+use frost::time::Time;
+use frost::{ConnectionData, DecompressedBag};
+
+mod common;
+use common::{string, string_connection};
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn time(secs: u32, nsecs: u32) -> Time {
+    Time { secs, nsecs }
+}
+
+fn connection(caller_id: &str, latching: bool) -> ConnectionData {
+    ConnectionData {
+        caller_id: Some(caller_id.to_string()),
+        latching,
+        ..string_connection("/map")
+    }
+}
+
+fn data(state: &[frost::msgs::MessageView]) -> Vec<Vec<u8>> {
+    state
+        .iter()
+        .map(|msg_view| msg_view.raw_bytes().unwrap()[4..].to_vec())
+        .collect()
+}
+
+#[test]
+fn latest_message_per_topic() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    // /chatter is received at i seconds and (i + 1) microseconds
+    let state = bag.state_at(time(10, 500_000), &["/chatter", "/time"]);
+    assert_eq!(
+        state.keys().copied().collect::<Vec<_>>(),
+        ["/chatter", "/time"]
+    );
+    assert_eq!(state["/chatter"].len(), 1);
+    assert_eq!(state["/chatter"][0].time, time(10, 11_000));
+    assert_eq!(data(&state["/chatter"]), [string("foo_10")]);
+
+    // inclusive of messages received at exactly `time`
+    let state = bag.state_at(time(10, 11_000), &["/chatter"]);
+    assert_eq!(state["/chatter"][0].time, time(10, 11_000));
+    let state = bag.state_at(time(10, 10_999), &["/chatter"]);
+    assert_eq!(state["/chatter"][0].time, time(9, 10_000));
+
+    // after the end, the last message
+    let end = bag.metadata.end_time().unwrap();
+    let state = bag.state_at(time(u32::MAX, 0), &["/chatter"]);
+    assert_eq!(state["/chatter"][0].time, end);
+}
+
+#[test]
+fn leaves_out_topics_without_messages() {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    assert!(bag.state_at(time(0, 0), &["/chatter"]).is_empty());
+    assert!(bag.state_at(time(50, 0), &["/unknown"]).is_empty());
+    assert!(bag.state_at(time(50, 0), &[] as &[&str]).is_empty());
+}
+
+#[test]
+fn latched_publishers() {
+    let mut writer = common::writer();
+    let first = writer.add_connection(&connection("/first", true));
+    let second = writer.add_connection(&connection("/second", true));
+    let live = writer.add_connection(&connection("/live", false));
+    let other = writer.add_connection(&connection("/other", false));
+    writer
+        .write_raw(first, time(1, 0), &string("first"))
+        .unwrap();
+    writer
+        .write_raw(second, time(2, 0), &string("second"))
+        .unwrap();
+    writer.write_raw(live, time(3, 0), &string("live")).unwrap();
+    writer
+        .write_raw(other, time(4, 0), &string("other"))
+        .unwrap();
+    writer
+        .write_raw(first, time(5, 0), &string("first again"))
+        .unwrap();
+    let bag = writer.finish().unwrap().into_inner();
+    let bag = DecompressedBag::from_bytes(&bag).unwrap();
+
+    // every latched publisher's latest, but only the latest of the others
+    let state = bag.state_at(time(4, 0), &["/map"]);
+    assert_eq!(
+        data(&state["/map"]),
+        [string("first"), string("second"), string("other")]
+    );
+    let state = bag.state_at(time(10, 0), &["/map"]);
+    assert_eq!(
+        data(&state["/map"]),
+        [string("second"), string("other"), string("first again")]
+    );
+    let state = bag.state_at(time(1, 0), &["/map"]);
+    assert_eq!(data(&state["/map"]), [string("first")]);
+}
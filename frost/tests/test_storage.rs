@@ -0,0 +1,237 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::io::{self, Read};
+
+use frost::query::Query;
+use frost::storage::{ByteSource, CustomStorage, FileStorage, StreamStorage};
+use frost::time::Time;
+use frost::writer::Compression;
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+/// Serves an in-memory bag while recording each requested range, like a remote store would see.
+struct RangeStorage {
+    bytes: &'static [u8],
+    requests: RefCell<Vec<(u64, usize)>>,
+}
+
+impl ByteSource for RangeStorage {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.bytes.len() as u64)
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        self.requests.borrow_mut().push((offset, len));
+        let start = offset as usize;
+        Ok(Cow::Owned(self.bytes[start..start + len].to_vec()))
+    }
+}
+
+#[test]
+fn custom_storage() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ] {
+        let storage = CustomStorage::new(RangeStorage {
+            bytes,
+            requests: RefCell::new(Vec::new()),
+        });
+
+        let metadata = BagMetadata::from_storage(&storage).unwrap();
+        assert_eq!(metadata.message_count(), 300, "{name}");
+        assert_eq!(metadata.num_bytes, bytes.len() as u64, "{name}");
+        assert_eq!(metadata.file_path, None, "{name}");
+        assert!(!storage.source().requests.borrow().is_empty(), "{name}");
+
+        let bag = DecompressedBag::from_storage(&storage).unwrap();
+        let expected = DecompressedBag::from_bytes(bytes).unwrap();
+        for topic in ["/chatter", "/array", "/time"] {
+            let query = Query::new().with_topics([topic]);
+            let actual = bag.read_messages(&query).unwrap();
+            let expected = expected.read_messages(&query).unwrap();
+            for (a, b) in actual.zip(expected) {
+                assert_eq!(a.raw_bytes().unwrap(), b.raw_bytes().unwrap(), "{name}");
+            }
+        }
+    }
+}
+
+#[test]
+fn file_storage() {
+    let path = "./tests/fixtures/compressed_lz4.bag";
+    let storage = FileStorage::open(path).unwrap();
+
+    let metadata = BagMetadata::from_storage(&storage).unwrap();
+    assert_eq!(metadata.file_path.as_deref(), Some(path.as_ref()));
+    assert_eq!(metadata.num_bytes, COMPRESSED_LZ4.len() as u64);
+
+    let bag = DecompressedBag::from_storage(&storage).unwrap();
+    assert_eq!(bag.read_messages(&Query::all()).unwrap().count(), 300);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mmap_storage() {
+    let storage = frost::storage::MmapStorage::open("./tests/fixtures/decompressed.bag").unwrap();
+    let bag = DecompressedBag::from_storage(&storage).unwrap();
+    assert_eq!(bag.read_messages(&Query::all()).unwrap().count(), 300);
+}
+
+#[test]
+fn limited_storage() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ] {
+        let storage = CustomStorage::new(RangeStorage {
+            bytes,
+            requests: RefCell::new(Vec::new()),
+        });
+        let size = BagMetadata::from_bytes(bytes).unwrap().uncompressed_size();
+        assert!(size > 0, "{name}");
+
+        let bag = DecompressedBag::from_storage_with_limit(&storage, size).unwrap();
+        let expected = DecompressedBag::from_bytes(bytes).unwrap();
+        let raw = |bag: &DecompressedBag| -> Vec<Vec<u8>> {
+            bag.read_messages(&Query::all())
+                .unwrap()
+                .map(|msg_view| msg_view.raw_bytes().unwrap().to_vec())
+                .collect()
+        };
+        assert_eq!(raw(&bag), raw(&expected), "{name}");
+        assert_eq!(bag.metadata.uncompressed_size(), size, "{name}");
+
+        let Err(err) = DecompressedBag::from_storage_with_limit(&storage, size - 1) else {
+            panic!("{name} loaded over its limit");
+        };
+        assert!(
+            matches!(err.kind(), frost::errors::ErrorKind::Io(e) if e.kind() == io::ErrorKind::OutOfMemory),
+            "{name}"
+        );
+    }
+
+    let path = "./tests/fixtures/compressed_lz4.bag";
+    assert!(DecompressedBag::from_file_with_limit(path, u64::MAX).is_ok());
+    assert!(DecompressedBag::from_file_with_limit(path, 0).is_err());
+}
+
+#[test]
+fn stream_storage() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ] {
+        let expected = DecompressedBag::from_bytes(bytes).unwrap();
+        // a reader without Seek, like stdin
+        let in_memory =
+            StreamStorage::from_reader(io::Cursor::new(bytes).chain(io::empty())).unwrap();
+        let spooled = StreamStorage::with_memory_limit(bytes, 1024).unwrap();
+        assert!(!in_memory.is_spooled(), "{name}");
+        assert!(spooled.is_spooled(), "{name}");
+
+        for storage in [&in_memory, &spooled] {
+            let metadata = BagMetadata::from_storage(storage).unwrap();
+            assert_eq!(metadata.message_count(), 300, "{name}");
+            assert_eq!(metadata.num_bytes, bytes.len() as u64, "{name}");
+
+            let bag = DecompressedBag::from_storage(storage).unwrap();
+            for topic in ["/chatter", "/array", "/time"] {
+                let query = Query::new().with_topics([topic]);
+                let actual = bag.read_messages(&query).unwrap();
+                for (a, b) in actual.zip(expected.read_messages(&query).unwrap()) {
+                    assert_eq!(a.raw_bytes().unwrap(), b.raw_bytes().unwrap(), "{name}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn stream_storage_removes_spool() {
+    let before = spool_files();
+    let storage = StreamStorage::with_memory_limit(COMPRESSED_LZ4, 0).unwrap();
+    assert!(storage.is_spooled());
+    assert_eq!(spool_files().len(), before.len() + 1);
+    drop(storage);
+    assert_eq!(spool_files(), before);
+}
+
+fn spool_files() -> Vec<std::path::PathBuf> {
+    let prefix = format!("frost-{}-", std::process::id());
+    let mut files: Vec<_> = std::fs::read_dir(std::env::temp_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with(&prefix))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn filtered_storage() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(1024);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let bytes: &'static [u8] = Box::leak(writer.finish().unwrap().into_inner().into_boxed_slice());
+    let storage = CustomStorage::new(RangeStorage {
+        bytes,
+        requests: RefCell::new(Vec::new()),
+    });
+    let metadata = BagMetadata::from_bytes(bytes).unwrap();
+
+    let query = Query::new()
+        .with_topics(["/chatter"])
+        .with_end_time(Time { secs: 20, nsecs: 0 });
+    let filtered = DecompressedBag::from_storage_filtered(&storage, &query).unwrap();
+    let expected = DecompressedBag::from_bytes(bytes).unwrap();
+    let actual: Vec<&[u8]> = filtered
+        .read_messages(&query)
+        .unwrap()
+        .map(|msg_view| msg_view.raw_bytes().unwrap())
+        .collect();
+    let wanted: Vec<&[u8]> = expected
+        .read_messages(&query)
+        .unwrap()
+        .map(|msg_view| msg_view.raw_bytes().unwrap())
+        .collect();
+    assert!(!wanted.is_empty());
+    assert_eq!(actual, wanted);
+
+    // chunk data is what byte_ranges leaves out for a query without messages
+    let metadata_ranges = metadata.byte_ranges(&Query::new().with_topics(["/missing"]));
+    let chunk_data: Vec<(u64, usize)> = metadata_ranges
+        .windows(2)
+        .map(|pair| (pair[0].end, (pair[1].start - pair[0].end) as usize))
+        .collect();
+    let chunk_reads: Vec<(u64, usize)> = storage
+        .source()
+        .requests
+        .borrow()
+        .iter()
+        .filter(|request| chunk_data.contains(request))
+        .copied()
+        .collect();
+    let estimate = metadata.estimate(&query);
+    assert!(estimate.chunk_count < metadata.chunk_count());
+    assert_eq!(chunk_reads.len(), estimate.chunk_count);
+    assert_eq!(
+        chunk_reads.iter().map(|(_, len)| *len as u64).sum::<u64>(),
+        estimate.compressed_bytes
+    );
+    assert!(filtered.memory_usage() <= estimate.uncompressed_bytes);
+
+    let filtered =
+        DecompressedBag::from_file_filtered("./tests/fixtures/compressed_lz4.bag", &query).unwrap();
+    assert_eq!(filtered.count_messages(&query), 20);
+}
@@ -0,0 +1,129 @@
+use frost::BagMetadata;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+#[test]
+fn to_markdown() {
+    for (bytes, compression) in [(DECOMPRESSED, "none"), (COMPRESSED_LZ4, "lz4")] {
+        let metadata = BagMetadata::from_bytes(bytes).unwrap();
+        let markdown = metadata.to_markdown();
+
+        assert!(markdown.starts_with("| field | value |\n| --- | --- |\n"));
+        assert!(markdown.contains("| version | 2.0 |\n"));
+        assert!(markdown.contains("| messages | 300 |\n"));
+        assert!(markdown.contains(&format!("| compression | {compression} [")));
+        assert!(markdown.contains("| type | md5sum |\n| --- | --- |\n"));
+        assert!(markdown.contains("| std_msgs/String | 992ce8a1687cec8c8bd883ec73ca41d1 |\n"));
+        assert!(markdown.contains(
+            "| topic | messages | type |\n| --- | --- | --- |\n\
+             | /array | 100 | std_msgs/Float64MultiArray |\n\
+             | /chatter | 100 | std_msgs/String |\n\
+             | /time | 100 | std_msgs/Time |\n"
+        ));
+    }
+}
+
+#[test]
+fn to_html() {
+    for (bytes, _) in [(DECOMPRESSED, "none"), (COMPRESSED_LZ4, "lz4")] {
+        let metadata = BagMetadata::from_bytes(bytes).unwrap();
+        let html = metadata.to_html();
+
+        assert_eq!(html.matches("<table>").count(), 3);
+        assert!(html.contains("<tr><td>messages</td><td>300</td></tr>\n"));
+        assert!(html.contains("<tr><td>/chatter</td><td>100</td><td>std_msgs/String</td></tr>\n"));
+    }
+}
+
+#[test]
+fn to_rosbag_info() {
+    let metadata = BagMetadata::from_file("./tests/fixtures/compressed_lz4.bag").unwrap();
+    let info = metadata.to_rosbag_info();
+
+    // start and end are printed in the local timezone
+    let lines: Vec<&str> = info
+        .lines()
+        .filter(|line| !line.starts_with("start:") && !line.starts_with("end:"))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "path:         ./tests/fixtures/compressed_lz4.bag",
+            "version:      2.0",
+            "duration:     1:39s (99s)",
+            "size:         14.4 KB",
+            "messages:     300",
+            "compression:  lz4 [1/1 chunks; 20.55%]",
+            "uncompressed: 22.2 KB @ 0.2 KB/s",
+            "compressed:    4.6 KB @ 0.0 KB/s (20.55%)",
+            "types:        std_msgs/Float64MultiArray [4b7d974086d4060e7db4613a7e6c3ba4]",
+            "              std_msgs/String [992ce8a1687cec8c8bd883ec73ca41d1]",
+            "              std_msgs/Time [cd7166c74c552c311fbcc2fe5a7bc289]",
+            "topics:       /array     100 msgs    : std_msgs/Float64MultiArray",
+            "              /chatter   100 msgs    : std_msgs/String           ",
+            "              /time      100 msgs    : std_msgs/Time",
+        ]
+    );
+    assert!(info.contains(" (99.00)\n"));
+
+    let metadata = BagMetadata::from_bytes(DECOMPRESSED).unwrap();
+    let info = metadata.to_rosbag_info();
+    assert!(info.starts_with("version:     2.0\n"));
+    assert!(info.contains("\ncompression: none [1/1 chunks]\ntypes: "));
+}
+
+#[test]
+fn to_rosbag_yaml() {
+    let metadata = BagMetadata::from_bytes(COMPRESSED_LZ4).unwrap();
+    assert_eq!(
+        metadata.to_rosbag_yaml(),
+        "version: 2.0\n\
+         duration: 99.000099\n\
+         start: 0.000001\n\
+         end: 99.000100\n\
+         size: 14718\n\
+         messages: 300\n\
+         indexed: True\n\
+         compression: lz4\n\
+         uncompressed: 22767\n\
+         compressed: 4679\n\
+         types:\n    - type: std_msgs/Float64MultiArray\n      md5: 4b7d974086d4060e7db4613a7e6c3ba4\n\
+         \x20   - type: std_msgs/String\n      md5: 992ce8a1687cec8c8bd883ec73ca41d1\n\
+         \x20   - type: std_msgs/Time\n      md5: cd7166c74c552c311fbcc2fe5a7bc289\n\
+         topics:\n    - topic: /array\n      type: std_msgs/Float64MultiArray\n      messages: 100\n\
+         \x20   - topic: /chatter\n      type: std_msgs/String\n      messages: 100\n\
+         \x20   - topic: /time\n      type: std_msgs/Time\n      messages: 100\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "yaml")]
+fn rosbag_yaml_key() {
+    let metadata = BagMetadata::from_bytes(COMPRESSED_LZ4).unwrap();
+    let key = |key| metadata.rosbag_yaml_key(key);
+
+    assert_eq!(key("messages").as_deref(), Some("300\n"));
+    assert_eq!(key("duration").as_deref(), Some("99.000099\n"));
+    // printed like Python floats
+    assert_eq!(key("start").as_deref(), Some("1e-06\n"));
+    assert_eq!(key("version").as_deref(), Some("2.0\n"));
+    assert_eq!(key("indexed").as_deref(), Some("True\n"));
+    assert_eq!(key("topics[-1].type").as_deref(), Some("std_msgs/Time\n"));
+    assert_eq!(
+        key("topics[1]").as_deref(),
+        Some("topic: /chatter\ntype: std_msgs/String\nmessages: 100\n")
+    );
+    assert_eq!(
+        key("types").as_deref(),
+        Some(
+            "- type: std_msgs/Float64MultiArray\n  md5: 4b7d974086d4060e7db4613a7e6c3ba4\n\
+             - type: std_msgs/String\n  md5: 992ce8a1687cec8c8bd883ec73ca41d1\n\
+             - type: std_msgs/Time\n  md5: cd7166c74c552c311fbcc2fe5a7bc289\n\n"
+        )
+    );
+
+    assert_eq!(key("path"), None);
+    assert_eq!(key("topics[3]"), None);
+    assert_eq!(key("messages.count"), None);
+}
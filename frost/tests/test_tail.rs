@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use frost::clock::{Clock, MockClock};
+use frost::query::Query;
+use frost::tail::Tail;
+use frost::writer::Compression;
+use frost::DecompressedBag;
+use tempfile::tempdir;
+
+mod common;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+/// Where the bag header's index position is stored.
+fn index_pos_field(bytes: &[u8]) -> usize {
+    let field = b"index_pos=";
+    bytes.windows(field.len()).position(|w| w == field).unwrap() + field.len()
+}
+
+/// A bag of the fixture's messages in small chunks, and the position of its index.
+fn recording() -> (Vec<u8>, usize) {
+    let bag = DecompressedBag::from_file(FILE_PATH).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(1024);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let start = index_pos_field(&bytes);
+    let index_pos = u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap());
+    (bytes, index_pos as usize)
+}
+
+#[test]
+fn follows_a_growing_bag() {
+    let (bytes, index_pos) = recording();
+    // while recording, the bag header has no index position
+    let mut active = bytes.clone();
+    let field = b"index_pos=";
+    let start = active
+        .windows(field.len())
+        .position(|w| w == field)
+        .unwrap()
+        + field.len();
+    active[start..start + 8].fill(0);
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("recording.bag.active");
+    std::fs::write(&path, &active[..100]).unwrap();
+    let mut tail = Tail::open(&path).unwrap();
+    assert!(tail.poll().unwrap().is_none());
+
+    let mut count = 0;
+    let mut polls_with_messages = 0;
+    for len in (100..index_pos).step_by(997).chain([index_pos]) {
+        std::fs::write(&path, &active[..len]).unwrap();
+        if let Some(bag) = tail.poll().unwrap() {
+            let messages = bag.read_messages(&Query::all()).unwrap().count();
+            assert!(messages > 0);
+            count += messages;
+            polls_with_messages += 1;
+        }
+        assert!(tail.position() <= len as u64);
+        assert!(!tail.is_finished());
+    }
+    assert_eq!(count, 300);
+    assert!(polls_with_messages > 1);
+
+    // recording stops: the index is written, and then the header
+    std::fs::write(&path, &active).unwrap();
+    assert!(tail.poll().unwrap().is_none());
+    assert!(!tail.is_finished());
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(tail.poll().unwrap().is_none());
+    assert!(tail.is_finished());
+}
+
+/// A clock which writes more of a recording each time it's slept on.
+struct Recorder {
+    clock: MockClock,
+    path: PathBuf,
+    bytes: Vec<u8>,
+    written: RefCell<usize>,
+}
+
+impl Clock for Recorder {
+    fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.clock.sleep(duration);
+        let mut written = self.written.borrow_mut();
+        *written = (*written + 997).min(self.bytes.len());
+        std::fs::write(&self.path, &self.bytes[..*written]).unwrap();
+    }
+}
+
+#[test]
+fn follows_with_a_clock() {
+    let (bytes, _) = recording();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("recording.bag.active");
+    std::fs::write(&path, &bytes[..100]).unwrap();
+    let recorder = Recorder {
+        clock: MockClock::new(),
+        path: path.clone(),
+        bytes: bytes.clone(),
+        written: RefCell::new(100),
+    };
+
+    let mut count = 0;
+    let mut tail = Tail::open(&path).unwrap();
+    tail.follow(&recorder, Duration::from_secs(1), |bag| {
+        count += bag.read_messages(&Query::all())?.count();
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(count, 300);
+    let sleeps = recorder.clock.sleeps();
+    assert_eq!(sleeps, (bytes.len() - 100 + 996) / 997);
+    assert_eq!(recorder.clock.elapsed(), Duration::from_secs(sleeps as u64));
+
+    // a finished bag is read without waiting
+    let clock = MockClock::new();
+    let mut count = 0;
+    let mut tail = Tail::open(FILE_PATH).unwrap();
+    tail.follow(&clock, Duration::from_secs(1), |bag| {
+        count += bag.read_messages(&Query::all())?.count();
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(count, 300);
+    assert_eq!(clock.sleeps(), 0);
+}
+
+#[test]
+fn tail_command() {
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+            .arg("tail")
+            .args(args)
+            .arg(FILE_PATH)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // a finished bag has nothing new
+    assert_eq!(run(&[]), "");
+    let stdout = run(&["--from-start", "--topic", "/time"]);
+    assert_eq!(stdout.lines().count(), 100);
+    assert!(stdout
+        .lines()
+        .all(|line| line.contains("\"topic\":\"/time\"")));
+    let stdout = run(&["--from-start", "--topic", "/time", "--start", "90"]);
+    assert_eq!(stdout.lines().count(), 10);
+}
@@ -0,0 +1,64 @@
+use frost::time::Time;
+use frost::topic_search::MatchKind;
+use frost::BagMetadata;
+
+mod common;
+
+/// The metadata of a bag with a message on each of `topics`.
+fn metadata_of(topics: &[&str]) -> BagMetadata {
+    let mut writer = common::writer();
+    for topic in topics {
+        let conn_id = writer.add_connection(&common::connection(topic, "std_msgs/Empty", ""));
+        writer
+            .write_raw(conn_id, Time { secs: 1, nsecs: 0 }, &[])
+            .unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+    BagMetadata::from_bytes(&bytes).unwrap()
+}
+
+#[test]
+fn ranks_matches() {
+    let metadata = metadata_of(&[
+        "/camera/left/image_raw",
+        "/camera/left/camera_info",
+        "/camera",
+        "/imu",
+        "/odom",
+        "/tf",
+    ]);
+    let found = |query: &str| {
+        metadata
+            .find_topic(query)
+            .into_iter()
+            .map(|m| (m.topic, m.kind))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        found("/camera"),
+        [
+            ("/camera".to_string(), MatchKind::Exact),
+            ("/camera/left/image_raw".to_string(), MatchKind::Prefix),
+            ("/camera/left/camera_info".to_string(), MatchKind::Prefix),
+        ]
+    );
+    // case-insensitive, and without the leading slash
+    assert_eq!(found("IMU"), [("/imu".to_string(), MatchKind::Substring)]);
+    assert_eq!(
+        found("image"),
+        [("/camera/left/image_raw".to_string(), MatchKind::Substring)]
+    );
+    // typos and abbreviations
+    assert_eq!(found("/odmo")[0], ("/odom".to_string(), MatchKind::Fuzzy));
+    assert_eq!(
+        found("/cam/info")[0],
+        ("/camera/left/camera_info".to_string(), MatchKind::Fuzzy)
+    );
+    assert!(found("/gps/fix").is_empty());
+    assert!(found("/tf_static").is_empty());
+
+    // swapping neighbouring characters is a single typo
+    assert_eq!(metadata.find_topic("/odmo")[0].distance, 1);
+    assert_eq!(metadata.find_topic("/camera")[1].distance, 15);
+}
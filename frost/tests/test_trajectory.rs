@@ -0,0 +1,274 @@
+use std::time::Duration;
+
+use frost::dynamic::Value;
+use frost::errors::DecodeError;
+use frost::time::Time;
+use frost::trajectory::{Trajectory, TrajectoryFormat, TrajectoryPose};
+use frost::{ConnectionData, DecompressedBag};
+
+mod common;
+
+const POSE_STAMPED_DEFINITION: &str = "\
+Header header
+Pose pose
+================================================================================
+MSG: std_msgs/Header
+uint32 seq
+time stamp
+string frame_id
+================================================================================
+MSG: geometry_msgs/Pose
+Point position
+Quaternion orientation
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y
+float64 z
+================================================================================
+MSG: geometry_msgs/Quaternion
+float64 x
+float64 y
+float64 z
+float64 w
+";
+
+fn message(fields: Vec<(&str, Value)>) -> Value {
+    Value::Message(
+        fields
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
+
+fn xyzw(x: f64, y: f64, z: f64, w: Option<f64>) -> Value {
+    let mut fields = vec![
+        ("x", Value::F64(x)),
+        ("y", Value::F64(y)),
+        ("z", Value::F64(z)),
+    ];
+    fields.extend(w.map(|w| ("w", Value::F64(w))));
+    message(fields)
+}
+
+fn header(secs: u32, frame_id: &str) -> Value {
+    message(vec![
+        ("seq", Value::U32(0)),
+        ("stamp", Value::Time(Time { secs, nsecs: 0 })),
+        ("frame_id", Value::String(frame_id.to_string())),
+    ])
+}
+
+fn pose(x: f64, y: f64) -> Value {
+    message(vec![
+        ("position", xyzw(x, y, 0.0, None)),
+        ("orientation", xyzw(0.0, 0.0, 0.0, Some(1.0))),
+    ])
+}
+
+fn odometry(secs: u32, child_frame: &str, x: f64, y: f64) -> Value {
+    message(vec![
+        ("header", header(secs, "odom")),
+        ("child_frame_id", Value::String(child_frame.to_string())),
+        (
+            "pose",
+            message(vec![
+                ("pose", pose(x, y)),
+                ("covariance", Value::Array(vec![])),
+            ]),
+        ),
+    ])
+}
+
+fn tf(secs: u32, child_frame: &str, x: f64, y: f64) -> Value {
+    message(vec![
+        ("header", header(secs, "map")),
+        ("child_frame_id", Value::String(child_frame.to_string())),
+        (
+            "transform",
+            message(vec![
+                ("translation", xyzw(x, y, 0.0, None)),
+                ("rotation", xyzw(0.0, 0.0, 0.0, Some(1.0))),
+            ]),
+        ),
+    ])
+}
+
+fn trajectory(points: &[(u32, f64, f64)]) -> Trajectory {
+    Trajectory {
+        frame_id: "odom".to_string(),
+        poses: points
+            .iter()
+            .map(|&(secs, x, y)| TrajectoryPose {
+                time: Time { secs, nsecs: 0 },
+                x,
+                y,
+                z: 0.0,
+                yaw: 0.0,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn metrics_and_stops() {
+    // drives 3m east, waits 3s, creeps for 2s, then drives 4m north
+    let trajectory = trajectory(&[
+        (0, 0.0, 0.0),
+        (1, 3.0, 0.0),
+        (2, 3.0, 0.0),
+        (4, 3.0, 0.0),
+        (5, 3.01, 0.0),
+        (6, 3.02, 0.0),
+        (8, 3.02, 4.0),
+    ]);
+    assert_eq!(trajectory.duration(), Duration::from_secs(8));
+    assert!((trajectory.length() - 7.02).abs() < 1e-9);
+    assert!((trajectory.average_speed() - 7.02 / 8.0).abs() < 1e-9);
+
+    let stops = trajectory.stops(0.05, Duration::from_secs(2));
+    assert_eq!(stops.len(), 1);
+    assert_eq!(stops[0].start, Time { secs: 1, nsecs: 0 });
+    assert_eq!(stops[0].end, Time { secs: 6, nsecs: 0 });
+    assert_eq!((stops[0].x, stops[0].y), (3.0, 0.0));
+
+    // a stricter speed splits the stop in two, and only the longer one is long enough
+    let stops = trajectory.stops(0.001, Duration::from_secs(2));
+    assert_eq!(stops.len(), 1);
+    assert_eq!(stops[0].duration(), Duration::from_secs(3));
+
+    assert_eq!(Trajectory::default().average_speed(), 0.0);
+    assert!(Trajectory::default().stops(1.0, Duration::ZERO).is_empty());
+}
+
+#[test]
+fn exports() {
+    let trajectory = trajectory(&[(0, 0.0, 0.0), (1, 1.0, 0.0), (5, 1.0, 0.0)]);
+    assert_eq!(
+        trajectory.to_csv(),
+        "time,x,y,z,yaw\n0.000000000,0,0,0,0\n1.000000000,1,0,0,0\n5.000000000,1,0,0,0\n"
+    );
+
+    let stops = trajectory.stops(0.05, Duration::from_secs(2));
+    let geojson: serde_json::Value = serde_json::from_str(&trajectory.to_geojson(&stops)).unwrap();
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0]["geometry"]["type"], "LineString");
+    assert_eq!(
+        features[0]["geometry"]["coordinates"],
+        serde_json::json!([[0.0, 0.0], [1.0, 0.0], [1.0, 0.0]])
+    );
+    assert_eq!(features[0]["properties"]["frame_id"], "odom");
+    assert_eq!(features[0]["properties"]["length"], 1.0);
+    assert_eq!(
+        features[1]["geometry"]["coordinates"],
+        serde_json::json!([1.0, 0.0])
+    );
+    assert_eq!(features[1]["properties"]["duration"], 4.0);
+
+    assert_eq!("geojson".parse(), Ok(TrajectoryFormat::GeoJson));
+    assert!("kml".parse::<TrajectoryFormat>().is_err());
+}
+
+#[test]
+fn odometry_and_tf() {
+    let received = Time {
+        secs: 100,
+        nsecs: 0,
+    };
+
+    let mut odom = Trajectory::default();
+    odom.push_value(
+        "nav_msgs/Odometry",
+        &odometry(1, "base_link", 1.0, 2.0),
+        received,
+        None,
+    )
+    .unwrap();
+    odom.push_value(
+        "nav_msgs/Odometry",
+        &odometry(2, "other", 5.0, 5.0),
+        received,
+        Some("base_link"),
+    )
+    .unwrap();
+    assert_eq!(odom.frame_id, "odom");
+    assert_eq!(odom.poses.len(), 1);
+    assert_eq!((odom.poses[0].x, odom.poses[0].y), (1.0, 2.0));
+    assert_eq!(odom.poses[0].time, Time { secs: 1, nsecs: 0 });
+
+    let transforms = message(vec![(
+        "transforms",
+        Value::Array(vec![tf(0, "odom", 9.0, 9.0), tf(0, "base_link", 3.0, 4.0)]),
+    )]);
+    let mut from_tf = Trajectory::default();
+    from_tf
+        .push_value(
+            "tf2_msgs/TFMessage",
+            &transforms,
+            received,
+            Some("base_link"),
+        )
+        .unwrap();
+    assert_eq!(from_tf.frame_id, "map");
+    assert_eq!(from_tf.poses.len(), 1);
+    assert_eq!((from_tf.poses[0].x, from_tf.poses[0].y), (3.0, 4.0));
+    // a zero stamp falls back to the receive time
+    assert_eq!(from_tf.poses[0].time, received);
+
+    let err = from_tf
+        .push_value("tf2_msgs/TFMessage", &transforms, received, None)
+        .unwrap_err();
+    assert!(matches!(err, DecodeError::InvalidField(_)));
+    let err = from_tf
+        .push_value("nav_msgs/Path", &transforms, received, None)
+        .unwrap_err();
+    assert!(matches!(err, DecodeError::UnsupportedEncoding(t) if t == "nav_msgs/Path"));
+}
+
+#[test]
+fn from_bag() {
+    let mut writer = common::writer();
+    let conn_id = writer.add_connection(&ConnectionData {
+        md5sum: "d3812c3cbc69362b77dc0b19b345f8f5".to_string(),
+        ..common::connection(
+            "/robot_pose",
+            "geometry_msgs/PoseStamped",
+            POSE_STAMPED_DEFINITION,
+        )
+    });
+    // recorded out of stamp order, and the trajectory is sorted by stamp
+    for (secs, x) in [(2u32, 2.0), (1, 1.0), (3, 4.0)] {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0u32.to_le_bytes()); // seq
+        data.extend_from_slice(&secs.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"map");
+        for value in [x, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0] {
+            data.extend_from_slice(&f64::to_le_bytes(value));
+        }
+        writer
+            .write_raw(conn_id, Time { secs: 10, nsecs: 0 }, &data)
+            .unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let bag = DecompressedBag::from_bytes(&bytes).unwrap();
+    let trajectory = bag.trajectory("/robot_pose", None).unwrap();
+    assert_eq!(trajectory.frame_id, "map");
+    assert_eq!(
+        trajectory
+            .poses
+            .iter()
+            .map(|pose| pose.x)
+            .collect::<Vec<_>>(),
+        [1.0, 2.0, 4.0]
+    );
+    assert_eq!(trajectory.length(), 3.0);
+    assert_eq!(trajectory.average_speed(), 1.5);
+
+    assert!(bag.trajectory("/missing", None).unwrap().poses.is_empty());
+}
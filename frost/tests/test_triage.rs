@@ -0,0 +1,132 @@
+use std::process::Command;
+
+use frost::time::Time;
+use frost::triage::{Severity, TriageReport, Verdict};
+use frost::ConnectionData;
+use tempfile::tempdir;
+
+mod common;
+
+const FILE_PATH: &str = "./tests/fixtures/compressed_lz4.bag";
+
+fn connection(topic: &str, data_type: &str, md5sum: &str) -> ConnectionData {
+    ConnectionData {
+        md5sum: md5sum.to_string(),
+        ..common::connection(topic, data_type, "")
+    }
+}
+
+fn at_ms(ms: u32) -> Time {
+    Time {
+        secs: 1_000 + ms / 1_000,
+        nsecs: (ms % 1_000) * 1_000_000,
+    }
+}
+
+/// A minute long recording with a gap and drops on /imu, /gps stopping early, and conflicting odometry definitions.
+fn troubled_bag() -> Vec<u8> {
+    let mut writer = common::writer();
+    let imu = writer.add_connection(&connection("/imu", "sensor_msgs/Imu", "*"));
+    let gps = writer.add_connection(&connection("/gps", "sensor_msgs/NavSatFix", "*"));
+    let odom = writer.add_connection(&connection("/odom", "nav_msgs/Odometry", "a"));
+    let wheel_odom = writer.add_connection(&connection("/wheel_odom", "nav_msgs/Odometry", "b"));
+
+    let mut messages = Vec::new();
+    for i in 0..600 {
+        let ms = i * 100;
+        let in_gap = (20_000..25_000).contains(&ms);
+        let dropped = ms >= 30_000 && i % 4 == 0;
+        if !in_gap && !dropped {
+            messages.push((imu, ms));
+        }
+    }
+    messages.extend((0..30).map(|i| (gps, i * 1_000)));
+    messages.extend((0..60).map(|i| (odom, i * 1_000)));
+    messages.extend((0..60).map(|i| (wheel_odom, i * 1_000 + 500)));
+    messages.sort_by_key(|(_, ms)| *ms);
+    for (conn_id, ms) in messages {
+        writer.write_raw(conn_id, at_ms(ms), &[]).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn clean_bag() {
+    let report = TriageReport::from_file(FILE_PATH).unwrap();
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+    assert_eq!(report.score(), 100);
+    assert_eq!(report.verdict(), Verdict::Usable);
+}
+
+#[test]
+fn troubled_bag_issues() {
+    let report = TriageReport::from_bytes(&troubled_bag()).unwrap();
+    let descriptions: Vec<_> = report.issues.iter().map(|i| i.to_string()).collect();
+    assert_eq!(descriptions.len(), 4, "{descriptions:#?}");
+
+    assert_eq!(report.issues[0].severity, Severity::Error);
+    assert!(descriptions[0].starts_with(
+        "error: nav_msgs/Odometry was recorded with 2 different definitions (md5sums a, b)"
+    ));
+    assert!(descriptions[1..].iter().all(|d| d.starts_with("warning: ")));
+    assert!(descriptions.iter().any(|d| d.contains(
+        "/imu went silent once, for up to 5.1s from 19.9s into the recording, although it usually publishes every 0.100s"
+    )));
+    assert!(descriptions
+        .iter()
+        .any(|d| d.contains("/gps stopped publishing 30.9s before the end of the recording")));
+    assert!(descriptions
+        .iter()
+        .any(|d| d.contains("/imu dropped about 14% of its messages")));
+
+    assert_eq!(report.score(), 45);
+    assert_eq!(report.verdict(), Verdict::Unusable);
+}
+
+#[test]
+fn truncated_bag() {
+    let bytes = std::fs::read(FILE_PATH).unwrap();
+    let report = TriageReport::from_bytes(&bytes[..bytes.len() - 100]).unwrap();
+    assert_eq!(report.issues.len(), 1);
+    assert_eq!(report.issues[0].severity, Severity::Critical);
+    assert!(report.issues[0].description.contains("truncated"));
+    assert_eq!(report.score(), 0);
+    assert_eq!(report.verdict(), Verdict::Unusable);
+
+    // not a bag at all is an error, not a verdict
+    assert!(TriageReport::from_bytes(b"#ROSBAG V1.2\n").is_err());
+}
+
+#[test]
+fn triage_command() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("troubled.bag");
+    std::fs::write(&path, troubled_bag()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["triage", "--top", "2", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines[..3],
+        [
+            "score:    45/100",
+            "verdict:  unusable",
+            "issues:   4, the top 2:"
+        ]
+    );
+    assert_eq!(lines.len(), 5);
+    assert!(lines[3].starts_with("  error: nav_msgs/Odometry"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frost"))
+        .args(["triage", FILE_PATH])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("issues:   none\n"));
+}
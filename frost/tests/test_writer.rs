@@ -0,0 +1,394 @@
+use std::time::Duration;
+
+use frost::query::Query;
+use frost::time::Time;
+use frost::writer::{Compression, DecisionReason, Retention};
+use frost::{BagMetadata, DecompressedBag};
+
+mod common;
+use common::msgs::std_msgs;
+
+const DECOMPRESSED: &[u8] = include_bytes!("fixtures/decompressed.bag");
+const COMPRESSED_LZ4: &[u8] = include_bytes!("fixtures/compressed_lz4.bag");
+
+fn rewrite(
+    bag: &DecompressedBag,
+    query: &Query,
+    compression: Compression,
+    chunk_size: usize,
+) -> Vec<u8> {
+    let mut writer = common::writer()
+        .with_compression(compression)
+        .with_chunk_size(chunk_size);
+    writer.copy_messages(bag, query).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn round_trip() {
+    for (bytes, name) in [
+        (DECOMPRESSED, "decompressed"),
+        (COMPRESSED_LZ4, "compressed_lz4"),
+    ]
+    .iter()
+    {
+        let bag = DecompressedBag::from_bytes(bytes).unwrap();
+        for compression in [
+            Compression::None,
+            Compression::Lz4,
+            #[cfg(feature = "bz2")]
+            Compression::Bz2,
+        ] {
+            for chunk_size in [1024, 768 * 1024] {
+                let name = format!("{name} -> {compression} ({chunk_size})");
+                let out = rewrite(&bag, &Query::all(), compression, chunk_size);
+
+                let metadata = BagMetadata::from_bytes(&out).unwrap();
+                assert_eq!(metadata.message_count(), 300, "{name}");
+                assert_eq!(metadata.connection_count(), 3, "{name}");
+                assert_eq!(metadata.start_time(), bag.metadata.start_time(), "{name}");
+                assert_eq!(metadata.end_time(), bag.metadata.end_time(), "{name}");
+                assert_eq!(
+                    metadata.topic_message_counts(),
+                    bag.metadata.topic_message_counts(),
+                    "{name}"
+                );
+                assert_eq!(
+                    metadata.topics_and_types(),
+                    bag.metadata.topics_and_types(),
+                    "{name}"
+                );
+                assert!(metadata.chunk_count() >= 1, "{name}");
+                for info in metadata.compression_info() {
+                    assert_eq!(info.name, compression.as_str(), "{name}");
+                }
+
+                let out_bag = DecompressedBag::from_bytes(&out).unwrap();
+                let query = Query::new().with_topics(["/chatter"]);
+                for (i, msg_view) in out_bag.read_messages(&query).unwrap().enumerate() {
+                    let msg = msg_view.instantiate::<std_msgs::String>().unwrap();
+                    assert_eq!(msg.data, format!("foo_{i}"), "{name}");
+                }
+
+                for topic in ["/chatter", "/array", "/time"] {
+                    let query = Query::new().with_topics([topic]);
+                    let original = bag.read_messages(&query).unwrap();
+                    let copied = out_bag.read_messages(&query).unwrap();
+                    for (a, b) in original.zip(copied) {
+                        assert_eq!(a.time, b.time, "{name}");
+                        assert_eq!(a.raw_bytes().unwrap(), b.raw_bytes().unwrap(), "{name}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn filtered_copy() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let query = Query::new().with_topics(["/chatter", "/time"]);
+    let out = rewrite(&bag, &query, Compression::Lz4, 768 * 1024);
+
+    let metadata = BagMetadata::from_bytes(&out).unwrap();
+    assert_eq!(metadata.message_count(), 200);
+    assert!(metadata.topic_exists("/chatter"));
+    assert!(metadata.topic_exists("/time"));
+    assert!(!metadata.topic_exists("/array"));
+}
+
+#[test]
+fn empty_bag() {
+    let writer = common::writer();
+    let out = writer.finish().unwrap().into_inner();
+
+    let metadata = BagMetadata::from_bytes(&out).unwrap();
+    assert!(metadata.is_empty());
+    assert_eq!(metadata.chunk_count(), 0);
+    assert_eq!(metadata.connection_count(), 0);
+}
+
+#[test]
+fn retention() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let retention = Retention::new()
+        .keep_every("/chatter", 10)
+        .drop_topic("/array");
+
+    let mut writer = common::writer();
+    let count = writer
+        .copy_retained(&bag, &Query::all(), &retention)
+        .unwrap();
+    let out = writer.finish().unwrap().into_inner();
+    assert_eq!(count, 110);
+
+    let out_bag = DecompressedBag::from_bytes(&out).unwrap();
+    let counts = out_bag.metadata.topic_message_counts();
+    assert_eq!(counts.get("/chatter"), Some(&10));
+    assert_eq!(counts.get("/time"), Some(&100));
+    assert_eq!(counts.get("/array"), None);
+
+    let query = Query::new().with_topics(["/chatter"]);
+    for (i, msg_view) in out_bag.read_messages(&query).unwrap().enumerate() {
+        let msg = msg_view.instantiate::<std_msgs::String>().unwrap();
+        assert_eq!(msg.data, format!("foo_{}", i * 10));
+    }
+}
+
+#[test]
+fn source_compression() {
+    let bag = DecompressedBag::from_bytes(DECOMPRESSED).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(1024);
+    writer
+        .copy_messages(&bag, &Query::new().with_topics(["/chatter"]))
+        .unwrap();
+    let mut writer = writer.with_compression(Compression::None);
+    writer
+        .copy_messages(&bag, &Query::new().with_topics(["/time"]))
+        .unwrap();
+    let mixed = writer.finish().unwrap().into_inner();
+    let mixed_bag = DecompressedBag::from_bytes(&mixed).unwrap();
+
+    let compressions = |bytes: &[u8]| {
+        let mut names: Vec<_> = BagMetadata::from_bytes(bytes)
+            .unwrap()
+            .compression_info()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(compressions(&mixed), ["lz4", "none"]);
+
+    // the configured compression is only a fallback, and chunks are not shared across compressions
+    let mut writer = common::writer()
+        .with_source_compression()
+        .with_chunk_size(768 * 1024);
+    writer.copy_messages(&mixed_bag, &Query::all()).unwrap();
+    let out = writer.finish().unwrap().into_inner();
+    assert_eq!(compressions(&out), ["lz4", "none"]);
+    let out_bag = DecompressedBag::from_bytes(&out).unwrap();
+    assert_eq!(
+        out_bag.metadata.topic_message_counts(),
+        mixed_bag.metadata.topic_message_counts()
+    );
+
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_source_compression();
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let out = writer.finish().unwrap().into_inner();
+    assert_eq!(compressions(&out), ["none"]);
+}
+
+#[test]
+fn source_compression_with_raw_messages() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let query = Query::new().with_topics(["/chatter"]);
+    let times: Vec<_> = bag
+        .read_messages(&query)
+        .unwrap()
+        .map(|msg_view| msg_view.time)
+        .collect();
+    let connection = common::string_connection("/raw");
+
+    for reorder_window in [None, Some(Duration::MAX)] {
+        let mut writer = common::writer().with_source_compression();
+        if let Some(window) = reorder_window {
+            writer = writer.with_reorder_window(window);
+        }
+        writer.copy_messages(&bag, &query).unwrap();
+        let raw = writer.add_connection(&connection);
+        for time in times.iter() {
+            writer.write_raw(raw, *time, &[0; 4]).unwrap();
+        }
+        let out = writer.finish().unwrap().into_inner();
+
+        // raw messages use the configured compression, even when sorted in between copied ones
+        let metadata = BagMetadata::from_bytes(&out).unwrap();
+        let mut chunks: Vec<_> = metadata
+            .compression_info()
+            .into_iter()
+            .map(|info| (info.name, info.chunk_count))
+            .collect();
+        chunks.sort();
+        let expected = match reorder_window {
+            None => 1,
+            Some(_) => times.len(),
+        };
+        assert_eq!(
+            chunks,
+            [
+                ("lz4".to_string(), expected),
+                ("none".to_string(), expected)
+            ]
+        );
+        let out_bag = DecompressedBag::from_bytes(&out).unwrap();
+        assert_eq!(
+            out_bag.read_messages(&Query::all()).unwrap().count(),
+            2 * times.len()
+        );
+    }
+}
+
+#[test]
+fn stats_match_metadata() {
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_chunk_size(4096);
+    writer.copy_messages(&bag, &Query::all()).unwrap();
+    let (out, stats) = writer.finish_with_stats().unwrap();
+    let metadata = BagMetadata::from_bytes(&out.into_inner()).unwrap();
+
+    assert_eq!(stats.chunks, metadata.chunk_count());
+    assert_eq!(stats.messages, metadata.message_count() as u64);
+    assert_eq!(stats.start_time, metadata.start_time());
+    assert_eq!(stats.end_time, metadata.end_time());
+    assert_eq!(stats.duration(), metadata.duration());
+    assert_eq!(stats.uncompressed_bytes, metadata.uncompressed_size());
+    assert_eq!(stats.compression, metadata.compression_info());
+    for (topic, count) in metadata.topic_message_counts() {
+        let topic_stats = &stats.topics[&topic];
+        assert_eq!(topic_stats.messages, count as u64, "{topic}");
+        assert!(metadata
+            .topics_and_types()
+            .contains(&(topic.as_str(), topic_stats.data_type.as_str())));
+    }
+    assert_eq!(
+        stats.topics["/chatter"].bytes,
+        (0..100)
+            .map(|i| 4 + format!("foo_{i}").len() as u64)
+            .sum::<u64>()
+    );
+    assert!(stats.compression_ratio() < 1.0);
+
+    let report = stats.to_string();
+    assert!(report.contains("messages:    300\n"), "{report}");
+    assert!(report.contains("compression: lz4 ["), "{report}");
+    assert!(report.contains("/chatter"), "{report}");
+}
+
+#[test]
+fn auto_compression() {
+    let connection =
+        |topic: &str, data_type: &str| common::connection(topic, data_type, "uint8[] data");
+    let text: Vec<u8> = "the quick brown fox jumps over the lazy dog "
+        .bytes()
+        .cycle()
+        .take(64 * 1024)
+        .collect();
+    // xorshift, standing in for compressed data
+    let mut state = 0x2545f4914f6cdd1du64;
+    let noise: Vec<u8> = (0..64 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+
+    let mut writer = common::writer()
+        .with_compression(Compression::Lz4)
+        .with_auto_compression()
+        .with_chunk_size(1024);
+    let logs = writer.add_connection(&connection("/logs", "std_msgs/UInt8MultiArray"));
+    let lidar = writer.add_connection(&connection("/lidar", "std_msgs/UInt8MultiArray"));
+    let images = writer.add_connection(&connection("/image", "sensor_msgs/CompressedImage"));
+    for (secs, (conn_id, data)) in [(logs, &text), (lidar, &noise), (images, &text)]
+        .into_iter()
+        .enumerate()
+    {
+        let time = Time {
+            secs: secs as u32 + 1,
+            nsecs: 0,
+        };
+        writer.write_raw(conn_id, time, data).unwrap();
+    }
+    let (out, stats) = writer.finish_with_stats().unwrap();
+
+    assert_eq!(stats.chunks, 3);
+    assert_eq!(stats.messages, 3);
+    let decisions: Vec<_> = stats
+        .decisions
+        .iter()
+        .map(|decision| (decision.compression, decision.reason))
+        .collect();
+    assert_eq!(
+        decisions,
+        [
+            (Compression::Lz4, DecisionReason::LowEntropy),
+            (Compression::None, DecisionReason::HighEntropy),
+            (Compression::None, DecisionReason::CompressedTypes),
+        ]
+    );
+    assert!(stats.decisions[0].entropy.unwrap() < 5.0);
+    assert!(stats.decisions[1].entropy.unwrap() > 7.9);
+    assert_eq!(stats.decisions[2].entropy, None);
+    assert!(stats.written_bytes < stats.uncompressed_bytes);
+
+    let out = out.into_inner();
+    let metadata = BagMetadata::from_bytes(&out).unwrap();
+    let mut compressions: Vec<_> = metadata
+        .compression_info()
+        .into_iter()
+        .map(|info| (info.name, info.chunk_count))
+        .collect();
+    compressions.sort();
+    assert_eq!(
+        compressions,
+        [("lz4".to_string(), 1), ("none".to_string(), 2)]
+    );
+    let bag = DecompressedBag::from_bytes(&out).unwrap();
+    assert_eq!(bag.read_messages(&Query::all()).unwrap().count(), 3);
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn encrypted_round_trip() {
+    use frost::crypto::EncryptionKey;
+    use frost::errors::{EncryptionError, ErrorKind};
+
+    let key = EncryptionKey::new("fleet-2023", [7; 32]);
+    let bag = DecompressedBag::from_bytes(COMPRESSED_LZ4).unwrap();
+    for compression in [Compression::None, Compression::Lz4] {
+        let mut writer = common::writer()
+            .with_compression(compression)
+            .with_chunk_size(1024)
+            .with_encryption(key.clone());
+        writer.copy_messages(&bag, &Query::all()).unwrap();
+        let out = writer.finish().unwrap().into_inner();
+
+        // the index stays in plaintext
+        let metadata = BagMetadata::from_bytes(&out).unwrap();
+        assert_eq!(metadata.message_count(), 300);
+        assert_eq!(metadata.encryption_key_ids(), ["fleet-2023"]);
+
+        let err = DecompressedBag::from_bytes(&out).err().unwrap();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Encryption(EncryptionError::MissingKey(id)) if id == "fleet-2023"
+        ));
+
+        let wrong_key = EncryptionKey::new("fleet-2023", [8; 32]);
+        let err = DecompressedBag::from_bytes_with_keys(&out, &[wrong_key])
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Encryption(EncryptionError::Authentication)
+        ));
+
+        let out_bag =
+            DecompressedBag::from_bytes_with_keys(&out, std::slice::from_ref(&key)).unwrap();
+        let query = Query::new().with_topics(["/chatter"]);
+        for (i, msg_view) in out_bag.read_messages(&query).unwrap().enumerate() {
+            let msg = msg_view.instantiate::<std_msgs::String>().unwrap();
+            assert_eq!(msg.data, format!("foo_{i}"));
+        }
+    }
+}
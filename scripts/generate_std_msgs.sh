@@ -0,0 +1,11 @@
+#!/usr/bin/env bash
+set -e
+
+# Regenerates the types behind frost's `std-msgs` feature.
+# Requires the std_msgs submodule: git submodule update --init
+
+script_dir="${0%/*}"
+cd "${script_dir}"
+cd .. # repo root
+
+cargo run --release --bin frost_codegen -- -i ./std_msgs -o ./frost/src/util/std_msgs.rs